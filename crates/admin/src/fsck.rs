@@ -0,0 +1,249 @@
+use std::path::PathBuf;
+
+use bichon_core::{
+    admin::meta::{list_account_ids, list_mailbox_ids, list_remote_content_hashes, open_database},
+    store::fsck::{FsckOwners, FsckRepair, FsckReport, OfflineIndexes},
+};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::reindex::{open_blob_engine, prompt_storage_dirs, StorageDirs};
+
+/// Findings listed per category; the JSON report has all of them.
+const SHOWN_FINDINGS: usize = 10;
+
+fn print_findings<T>(title: &str, items: &[T], describe: impl Fn(&T) -> String) {
+    if items.is_empty() {
+        println!("{}", style(format!("  ✓ {title}: none")).green());
+        return;
+    }
+    println!(
+        "{}",
+        style(format!("  ✗ {title}: {}", items.len())).yellow()
+    );
+    for item in items.iter().take(SHOWN_FINDINGS) {
+        println!("      {}", describe(item));
+    }
+    if items.len() > SHOWN_FINDINGS {
+        println!("      ... and {} more", items.len() - SHOWN_FINDINGS);
+    }
+}
+
+fn print_report(report: &FsckReport) {
+    println!(
+        "\n  Envelopes checked: {}\n  Attachments checked: {}\n  Blobs checked: {}\n",
+        report.envelopes_checked, report.attachments_checked, report.blobs_checked
+    );
+    print_findings(
+        "Envelopes with missing EML blob",
+        &report.missing_email_blobs,
+        |m| {
+            format!(
+                "{} (account {}) -> {}",
+                m.envelope_id, m.account_id, m.content_hash
+            )
+        },
+    );
+    print_findings(
+        "Envelopes with missing attachment blob",
+        &report.missing_attachment_blobs,
+        |m| {
+            format!(
+                "{} (account {}) -> {}",
+                m.envelope_id, m.account_id, m.content_hash
+            )
+        },
+    );
+    print_findings("Unreferenced blobs", &report.orphan_blobs, |h| h.clone());
+    print_findings(
+        "Attachments without an envelope",
+        &report.orphan_attachments,
+        |o| format!("{} (envelope {})", o.attachment_id, o.envelope_id),
+    );
+    print_findings(
+        "Envelopes of deleted mailboxes or accounts",
+        &report.orphan_envelopes,
+        |o| {
+            format!(
+                "{} (account {}, mailbox {}): {:?}",
+                o.envelope_id, o.account_id, o.mailbox_id, o.reason
+            )
+        },
+    );
+}
+
+fn confirm(theme: &ColorfulTheme, prompt: String) -> bool {
+    Confirm::with_theme(theme)
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .unwrap()
+}
+
+fn write_report(theme: &ColorfulTheme, report: &FsckReport) {
+    let path: String = Input::with_theme(theme)
+        .with_prompt("Write the JSON report to (leave blank to skip)")
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    if path.trim().is_empty() {
+        return;
+    }
+    let path = PathBuf::from(path.trim());
+    let result = serde_json::to_vec_pretty(report)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => println!(
+            "{}",
+            style(format!("Report written to {}", path.display())).green()
+        ),
+        Err(e) => println!("{}", style(format!("Failed to write report: {e}")).red()),
+    }
+}
+
+pub async fn handle_fsck(theme: &ColorfulTheme) {
+    println!(
+        "\n{}",
+        style("FSCK: Check Storage Consistency").bold().yellow()
+    );
+    println!(
+        "{}\n",
+        style(
+            "Cross-checks the envelope and attachment indexes, the blob store and the\n\
+             account/mailbox database, then offers to repair what it finds. The Bichon\n\
+             server must be stopped while this runs."
+        )
+        .dim()
+    );
+
+    let Some(StorageDirs {
+        root_dir,
+        index_dir,
+        blob_path,
+    }) = prompt_storage_dirs(theme)
+    else {
+        return;
+    };
+
+    let owners = match open_database(root_dir.join("memdb")).and_then(|db| {
+        Ok(FsckOwners {
+            accounts: list_account_ids(&db)?,
+            mailboxes: list_mailbox_ids(&db)?,
+            remote_content: list_remote_content_hashes(&db)?,
+        })
+    }) {
+        Ok(owners) => owners,
+        Err(e) => {
+            println!(
+                "{}",
+                style(format!("Failed to read accounts from memdb: {e:#?}")).red()
+            );
+            return;
+        }
+    };
+
+    let indexes = match OfflineIndexes::open(
+        &index_dir.join("mail_metadata"),
+        &index_dir.join("attachment_metadata"),
+    ) {
+        Ok(indexes) => indexes,
+        Err(e) => {
+            println!("{}", style(format!("Failed to open indexes: {e:#?}")).red());
+            return;
+        }
+    };
+
+    let Some(engine) = open_blob_engine(&blob_path) else {
+        return;
+    };
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.cyan} {msg} [{elapsed_precise}]").unwrap(),
+    );
+    pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    pb.set_message("Checking storage...");
+    let mut report = match indexes.check(&engine, &owners) {
+        Ok(report) => report,
+        Err(e) => {
+            pb.abandon_with_message("Check failed");
+            println!("{}", style(format!("{e:#?}")).red());
+            let _ = engine.shutdown();
+            return;
+        }
+    };
+    pb.finish_with_message("Check finished");
+    print_report(&report);
+
+    if report.is_clean() {
+        println!("\n{}", style("Storage is consistent.").green().bold());
+        let _ = engine.shutdown();
+        write_report(theme, &report);
+        return;
+    }
+
+    let options = FsckRepair {
+        delete_orphan_attachments: !report.orphan_attachments.is_empty()
+            && confirm(
+                theme,
+                format!(
+                    "Delete {} attachment documents without an envelope?",
+                    report.orphan_attachments.len()
+                ),
+            ),
+        delete_orphan_envelopes: !report.orphan_envelopes.is_empty()
+            && confirm(
+                theme,
+                format!(
+                    "Delete {} envelopes of deleted mailboxes or accounts, with their attachments?",
+                    report.orphan_envelopes.len()
+                ),
+            ),
+        delete_orphan_blobs: !report.orphan_blobs.is_empty()
+            && confirm(
+                theme,
+                format!("Delete {} unreferenced blobs?", report.orphan_blobs.len()),
+            ),
+        recover_missing_blobs: false,
+    };
+    if !report.missing_email_blobs.is_empty() || !report.missing_attachment_blobs.is_empty() {
+        println!(
+            "{}",
+            style(
+                "Missing blobs can be re-fetched from IMAP through POST /api/v1/fsck \
+                 with `recover_missing_blobs` while the server is running."
+            )
+            .dim()
+        );
+    }
+
+    if options.delete_orphan_attachments
+        || options.delete_orphan_envelopes
+        || options.delete_orphan_blobs
+    {
+        match indexes.repair(&mut report, &options, &engine).await {
+            Ok(()) => {
+                let repaired = report.repaired.clone().unwrap_or_default();
+                println!(
+                    "\n{}\n  Attachments deleted: {}\n  Envelopes deleted: {}\n  Blobs deleted: {}",
+                    style("Repair complete.").green().bold(),
+                    repaired.attachments_deleted,
+                    repaired.envelopes_deleted,
+                    repaired.blobs_deleted
+                );
+                if options.delete_orphan_envelopes {
+                    println!(
+                        "{}",
+                        style("Run the check again to find blobs freed by the deleted envelopes.")
+                            .dim()
+                    );
+                }
+            }
+            Err(e) => println!("{}", style(format!("Repair failed: {e:#?}")).red()),
+        }
+    }
+    let _ = engine.shutdown();
+    write_report(theme, &report);
+}
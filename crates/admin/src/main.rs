@@ -19,13 +19,23 @@
 use console::style;
 use dialoguer::{theme::ColorfulTheme, Select};
 
-use crate::{migrate_v037::handle_migration_v037, migrate_v1::handle_migrate_v1, reset::handle_reset_password};
+use crate::{
+    fsck::handle_fsck,
+    migrate_s3::handle_migrate_s3,
+    migrate_v037::handle_migration_v037,
+    migrate_v1::handle_migrate_v1,
+    reindex::handle_reindex,
+    reset::{handle_reset_password, handle_reset_two_factor},
+};
 
+pub mod fsck;
 pub mod legacy;
 pub mod meta;
+pub mod migrate_s3;
 pub mod migrate_store_v2;
 pub mod migrate_v037;
 pub mod migrate_v1;
+pub mod reindex;
 pub mod reset;
 
 
@@ -43,8 +53,12 @@ async fn run_interactive() {
 
     let main_options = vec![
         "Reset Admin Password",
+        "Reset Admin Two-Factor Authentication",
         "Migrate Legacy v0.3.7 Storage to v2.x (bichon-blob)",
         "Migrate v1.x Storage to v2.x (Fjall → bichon-blob)",
+        "Rebuild Search Indexes from Blob Store",
+        "Check Storage Consistency (fsck)",
+        "Migrate Local Blob Store to S3-Compatible Storage",
         "Exit",
     ];
 
@@ -57,8 +71,12 @@ async fn run_interactive() {
 
     match selection {
         0 => handle_reset_password(&theme),
-        1 => handle_migration_v037(&theme),
-        2 => handle_migrate_v1(&theme),
+        1 => handle_reset_two_factor(&theme),
+        2 => handle_migration_v037(&theme),
+        3 => handle_migrate_v1(&theme),
+        4 => handle_reindex(&theme),
+        5 => handle_fsck(&theme).await,
+        6 => handle_migrate_s3(&theme),
         _ => {
             println!("{}", style("Exiting...").dim());
         }
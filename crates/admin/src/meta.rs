@@ -239,6 +239,9 @@ impl From<AccountV3> for AccountModel {
         Self {
             id: value.id,
             imap: value.imap,
+            pop3: None,
+            graph: None,
+            jmap: None,
             enabled: value.enabled,
             email: value.email,
             account_name: None,
@@ -257,10 +260,15 @@ impl From<AccountV3> for AccountModel {
             created_by: value.created_by,
             use_dangerous: value.use_dangerous,
             pgp_key: value.pgp_key,
+            pgp_passphrase: None,
+            smime_key: None,
+            smime_password: None,
             imap_quota_window: None,
             imap_quota_bytes: None,
             auto_download_new_mailboxes: None,
             download_schedule: None,
+            idle_folders: None,
+            gmail_sync: None,
             deleting: false,
             archive_rules: None,
             extraction_rules: None,
@@ -450,6 +458,7 @@ impl From<UserRole> for bichon_core::users::role::UserRole {
             created_at: value.created_at,
             role_type: value.role_type,
             updated_at: value.updated_at,
+            require_two_factor: false,
         }
     }
 }
@@ -610,13 +619,18 @@ impl From<AccessTokenModel> for bichon_core::token::AccessTokenModel {
     fn from(value: AccessTokenModel) -> Self {
         Self {
             user_id: value.user_id,
+            // An empty prefix marks the record as legacy; the server hashes
+            // the plaintext token on its next start.
             token: value.token,
+            prefix: String::new(),
             name: value.name,
             token_type: value.token_type,
             created_at: value.created_at,
             updated_at: value.updated_at,
             expire_at: value.expire_at,
             last_access_at: value.last_access_at,
+            permissions: None,
+            account_ids: None,
         }
     }
 }
@@ -666,6 +680,7 @@ impl From<MailBox> for bichon_core::cache::imap::mailbox::MailBox {
             uid_next: value.uid_next,
             uid_validity: value.uid_validity,
             highest_uid: None,
+            highest_modseq: None,
         }
     }
 }
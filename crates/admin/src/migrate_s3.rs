@@ -0,0 +1,164 @@
+use bichon_blob::Engine;
+use bichon_core::store::blob::{
+    backend::BlobStore,
+    migrate::{copy_blobs, BlobCopyStats},
+    s3::{S3BlobStore, S3Config},
+};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::reindex::{open_blob_engine, prompt_storage_dirs, StorageDirs};
+
+/// Prompts for a setting, offering the value of its environment variable.
+fn prompt_setting(theme: &ColorfulTheme, prompt: &str, env: &str, fallback: &str) -> String {
+    let default = std::env::var(env).unwrap_or_else(|_| fallback.to_string());
+    let mut input = Input::<String>::with_theme(theme).with_prompt(format!("{prompt} ({env})"));
+    if !default.is_empty() {
+        input = input.default(default);
+    }
+    input.interact_text().unwrap().trim().to_string()
+}
+
+fn prompt_s3_config(theme: &ColorfulTheme) -> S3Config {
+    let endpoint = prompt_setting(theme, "S3 endpoint URL", "BICHON_S3_ENDPOINT", "");
+    let region = prompt_setting(theme, "S3 region", "BICHON_S3_REGION", "us-east-1");
+    let bucket = prompt_setting(theme, "S3 bucket", "BICHON_S3_BUCKET", "");
+    let access_key = prompt_setting(theme, "S3 access key", "BICHON_S3_ACCESS_KEY", "");
+    let secret_key = match std::env::var("BICHON_S3_SECRET_KEY") {
+        Ok(secret) if !secret.is_empty() => {
+            println!(
+                "{}",
+                style("Using the secret key from BICHON_S3_SECRET_KEY.").dim()
+            );
+            secret
+        }
+        _ => Password::with_theme(theme)
+            .with_prompt("S3 secret key")
+            .interact()
+            .unwrap(),
+    };
+    let prefix: String = Input::with_theme(theme)
+        .with_prompt("Key prefix inside the bucket (BICHON_S3_PREFIX, leave blank for none)")
+        .default(std::env::var("BICHON_S3_PREFIX").unwrap_or_default())
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    let path_style = Confirm::with_theme(theme)
+        .with_prompt("Use path-style requests? (BICHON_S3_PATH_STYLE, yes for MinIO/Ceph)")
+        .default(
+            std::env::var("BICHON_S3_PATH_STYLE")
+                .map(|v| v != "false")
+                .unwrap_or(true),
+        )
+        .interact()
+        .unwrap();
+
+    let mut config =
+        S3Config::new(&endpoint, &bucket, &access_key, &secret_key).with_prefix(prefix.trim());
+    config.region = region;
+    config.path_style = path_style;
+    config
+}
+
+fn print_stats(stats: &BlobCopyStats) {
+    println!(
+        "\n  Blobs in local store: {}\n  Copied: {}\n  Already in the bucket: {}\n  Unreadable: {}",
+        stats.total, stats.copied, stats.skipped, stats.missing
+    );
+}
+
+fn shutdown(local: &Engine, s3: &S3BlobStore) {
+    if let Err(e) = s3.shutdown() {
+        println!(
+            "{}",
+            style(format!("Failed to close the S3 store: {e:#?}")).red()
+        );
+    }
+    let _ = local.shutdown();
+}
+
+pub fn handle_migrate_s3(theme: &ColorfulTheme) {
+    println!(
+        "\n{}",
+        style("MIGRATE: Copy the Local Blob Store to S3-Compatible Storage")
+            .bold()
+            .yellow()
+    );
+    println!(
+        "{}\n",
+        style(
+            "Every blob in the local store is copied to the bucket. The local store is left\n\
+             untouched, and blobs already in the bucket are skipped, so an interrupted copy\n\
+             can be resumed by running this again. The Bichon server must be stopped."
+        )
+        .dim()
+    );
+
+    let Some(StorageDirs { blob_path, .. }) = prompt_storage_dirs(theme) else {
+        return;
+    };
+    let Some(local) = open_blob_engine(&blob_path) else {
+        return;
+    };
+    let config = prompt_s3_config(theme);
+    // The server keeps the S3 index next to the local store.
+    let s3_dir = blob_path.with_file_name("s3");
+
+    println!("\n{} Connecting to the bucket...", style("⌛").yellow());
+    let s3 = match S3BlobStore::open(config, &s3_dir) {
+        Ok(s3) => s3,
+        Err(e) => {
+            println!(
+                "{}",
+                style(format!("Failed to open the S3 store: {e:#?}")).red()
+            );
+            let _ = local.shutdown();
+            return;
+        }
+    };
+
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    let result = copy_blobs(&local, &s3, |done, total| {
+        pb.set_length(total as u64);
+        pb.set_position(done as u64);
+    });
+
+    match result {
+        Ok(stats) => {
+            pb.finish_with_message("done");
+            print_stats(&stats);
+            shutdown(&local, &s3);
+            println!("\n{}", style("Migration complete!").green().bold());
+            println!(
+                "Set {} and the same {} settings, then start the server.\n\
+                 The S3 index was written to '{}'; keep it with the data directory.\n\
+                 The local store at '{}' is no longer used and can be removed once\n\
+                 the server runs correctly.",
+                style("BICHON_BLOB_BACKEND=s3").cyan(),
+                style("BICHON_S3_*").cyan(),
+                s3_dir.display(),
+                blob_path.display()
+            );
+        }
+        Err(e) => {
+            pb.abandon_with_message("Copy stopped");
+            shutdown(&local, &s3);
+            println!(
+                "{}",
+                style(format!(
+                    "Migration failed: {e:#?}\nRe-run this command to resume the copy."
+                ))
+                .red()
+            );
+        }
+    }
+}
@@ -134,6 +134,7 @@ pub fn detach_attachments_standalone(
             extracted_text: None,
             extracted_page_count: None,
             extracted_is_ocr: false,
+            archive_members: None,
         });
     }
 
@@ -339,7 +340,7 @@ impl NewIndexWriterV2 {
                     ext: a.get_extension(),
                     category: a.get_category().to_string(),
                     content_type: a.file_type.clone(),
-                    shard_id: 0,
+                    shard_id: account_id,
                     text: None,
                     has_text: false,
                     is_ocr: false,
@@ -349,6 +350,7 @@ impl NewIndexWriterV2 {
                     name: a.filename.clone(),
                     tags: None,
                     auto_tags: None,
+                    archive_content_hash: None,
                 }
                 .into_document()
             })
@@ -374,6 +376,11 @@ impl NewIndexWriterV2 {
             attachment_count: message.attachment_count(),
             regular_attachment_count: attachment_docs.len(),
             tags: None,
+            flags: None,
+            deleted_on_source: false,
+            labels: None,
+            gmail_msg_id: None,
+            authentication: None,
             account_email: None,
             account_name: None,
             mailbox_name: None,
@@ -384,7 +391,7 @@ impl NewIndexWriterV2 {
             envelope,
             attachments: Some(attachment_output.infos),
         };
-        let envelope_doc = ea.to_document(&text, 0)?;
+        let envelope_doc = ea.to_document(&text, account_id)?;
 
         self.envelope_writer
             .as_mut()
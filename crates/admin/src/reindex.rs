@@ -0,0 +1,309 @@
+use std::path::{Path, PathBuf};
+
+use bichon_blob::{Codec, Config, Engine};
+use bichon_core::{
+    admin::meta::{list_mailbox_ids, open_database},
+    store::tantivy::reindex::{ReindexDirs, ReindexJob, ReindexStats},
+};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Failures listed before asking whether to swap anyway.
+const SHOWN_FAILURES: usize = 20;
+
+fn prompt_optional_dir(theme: &ColorfulTheme, prompt: &str, fallback: &Path) -> Option<PathBuf> {
+    let input: String = Input::with_theme(theme)
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    if input.trim().is_empty() {
+        return Some(fallback.to_path_buf());
+    }
+    let path = PathBuf::from(input.trim());
+    if !path.exists() {
+        eprintln!(
+            "{}",
+            style(format!("Directory does not exist: {}", path.display())).red()
+        );
+        return None;
+    }
+    Some(path)
+}
+
+/// Install directories resolved from the `--bichon-*-dir` prompts.
+pub(crate) struct StorageDirs {
+    pub root_dir: PathBuf,
+    /// `bichon-indices` under the index directory.
+    pub index_dir: PathBuf,
+    /// `bichon-storage/blobs` under the data directory.
+    pub blob_path: PathBuf,
+}
+
+pub(crate) fn prompt_storage_dirs(theme: &ColorfulTheme) -> Option<StorageDirs> {
+    let root_dir: String = Input::with_theme(theme)
+        .with_prompt("Enter --bichon-root-dir")
+        .validate_with(|input: &String| -> Result<(), &str> {
+            let path = PathBuf::from(input);
+            if !path.is_absolute() {
+                return Err("Path must be absolute.");
+            }
+            if !path.join("memdb").is_dir() {
+                return Err("Invalid directory: 'memdb' data directory not found.");
+            }
+            Ok(())
+        })
+        .interact_text()
+        .unwrap();
+    let root_dir = PathBuf::from(root_dir.trim());
+
+    let index_base = prompt_optional_dir(
+        theme,
+        "Enter --bichon-index-dir (leave blank to use root directory)",
+        &root_dir,
+    )?;
+    let data_base = prompt_optional_dir(
+        theme,
+        "Enter --bichon-data-dir (leave blank to use root directory)",
+        &root_dir,
+    )?;
+
+    Some(StorageDirs {
+        root_dir,
+        index_dir: index_base.join("bichon-indices"),
+        blob_path: data_base.join("bichon-storage").join("blobs"),
+    })
+}
+
+/// Opens the blob store with background flush and GC disabled. Prints the
+/// error and returns `None` when the store is missing or locked.
+pub(crate) fn open_blob_engine(blob_path: &Path) -> Option<Engine> {
+    if !blob_path.exists() {
+        println!(
+            "{}",
+            style(format!(
+                "Blob store not found at '{}'. Storage older than v2.x must be migrated first.",
+                blob_path.display()
+            ))
+            .red()
+        );
+        return None;
+    }
+    let config = Config {
+        default_codec: Codec::Zstd,
+        compress_threshold: 1024,
+        flush_interval_secs: 0,
+        gc_interval_secs: 0,
+        ..Default::default()
+    };
+    match Engine::open(blob_path, config) {
+        Ok(e) => Some(e),
+        Err(e) => {
+            println!(
+                "{}",
+                style(format!(
+                    "Failed to open blob store: {e:#?}\nIs the Bichon server still running?"
+                ))
+                .red()
+            );
+            None
+        }
+    }
+}
+
+fn print_stats(stats: &ReindexStats) {
+    println!(
+        "\n  Envelopes in old index: {}\n  Rebuilt: {}\n  Resumed from earlier run: {}\n  \
+         Dropped (mailbox deleted): {}\n  Failed: {}",
+        stats.total,
+        stats.rebuilt,
+        stats.resumed,
+        stats.orphaned,
+        stats.failed.len()
+    );
+    for (id, reason) in stats.failed.iter().take(SHOWN_FAILURES) {
+        println!("{}", style(format!("  ✗ {id}: {reason}")).yellow());
+    }
+    if stats.failed.len() > SHOWN_FAILURES {
+        println!(
+            "{}",
+            style(format!(
+                "  ... and {} more",
+                stats.failed.len() - SHOWN_FAILURES
+            ))
+            .yellow()
+        );
+    }
+}
+
+pub fn handle_reindex(theme: &ColorfulTheme) {
+    println!(
+        "\n{}",
+        style("REINDEX: Rebuild Search Indexes from the Blob Store")
+            .bold()
+            .yellow()
+    );
+    println!(
+        "{}\n",
+        style(
+            "Every archived email is re-parsed from stored content into a new envelope and\n\
+             attachment index, which then replaces the current one. Tags, flags and ingest\n\
+             times are kept. The Bichon server must be stopped while this runs."
+        )
+        .dim()
+    );
+
+    let Some(StorageDirs {
+        root_dir,
+        index_dir,
+        blob_path,
+    }) = prompt_storage_dirs(theme)
+    else {
+        return;
+    };
+    let dirs = ReindexDirs::new(
+        index_dir.join("mail_metadata"),
+        index_dir.join("attachment_metadata"),
+    );
+
+    if dirs.has_pending_swap() {
+        println!(
+            "{}",
+            style("A previous index swap was interrupted, finishing it...").yellow()
+        );
+        match dirs.swap() {
+            Ok(()) => println!(
+                "{}",
+                style("Swap complete. The rebuilt indexes are in place.")
+                    .green()
+                    .bold()
+            ),
+            Err(e) => println!("{}", style(format!("Swap failed: {e:#?}")).red()),
+        }
+        return;
+    }
+
+    if !dirs.envelope_dir.exists() {
+        println!(
+            "{}",
+            style(format!(
+                "Envelope index not found at '{}'.",
+                dirs.envelope_dir.display()
+            ))
+            .red()
+        );
+        return;
+    }
+
+    let mailboxes = match open_database(root_dir.join("memdb")).and_then(|db| list_mailbox_ids(&db))
+    {
+        Ok(ids) => ids,
+        Err(e) => {
+            println!(
+                "{}",
+                style(format!("Failed to read mailboxes from memdb: {e:#?}")).red()
+            );
+            return;
+        }
+    };
+
+    let Some(engine) = open_blob_engine(&blob_path) else {
+        return;
+    };
+
+    if dirs.has_partial_rebuild() {
+        let resume = Confirm::with_theme(theme)
+            .with_prompt("An interrupted rebuild was found. Resume it? (No starts over)")
+            .default(true)
+            .interact()
+            .unwrap();
+        if !resume {
+            if let Err(e) = dirs.discard_partial_rebuild() {
+                println!("{}", style(format!("{e:#?}")).red());
+                let _ = engine.shutdown();
+                return;
+            }
+        }
+    }
+
+    let batch_size: usize = {
+        let input: String = Input::with_theme(theme)
+            .with_prompt("Enter commit batch size (envelopes between checkpoints)")
+            .default("1000".to_string())
+            .validate_with(|s: &String| match s.trim().parse::<usize>() {
+                Ok(n) if n > 0 => Ok(()),
+                _ => Err("Please enter a valid positive number"),
+            })
+            .interact_text()
+            .unwrap_or("1000".to_string());
+        input.trim().parse::<usize>().unwrap_or(1000)
+    };
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.cyan} {msg} [{elapsed_precise}]").unwrap(),
+    );
+    pb.set_message("Rebuilding indexes...");
+
+    let job = ReindexJob::new(dirs.clone(), &engine, mailboxes, batch_size);
+    let result = job.run(|stats| {
+        pb.set_message(format!(
+            "{}/{} envelopes processed...",
+            stats.processed(),
+            stats.total
+        ));
+    });
+    let _ = engine.shutdown();
+
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            pb.abandon_with_message("Rebuild stopped");
+            println!(
+                "{}",
+                style(format!(
+                    "Rebuild failed: {e:#?}\nRe-run this command to resume from the last checkpoint."
+                ))
+                .red()
+            );
+            return;
+        }
+    };
+    pb.finish_with_message("Rebuild finished");
+    print_stats(&stats);
+
+    if !stats.failed.is_empty() {
+        let swap = Confirm::with_theme(theme)
+            .with_prompt(
+                "Some envelopes could not be rebuilt and will be missing from the new index. \
+                 Replace the current index anyway?",
+            )
+            .default(false)
+            .interact()
+            .unwrap();
+        if !swap {
+            println!(
+                "{}",
+                style("The current index was left untouched. Re-run this command to resume.").dim()
+            );
+            return;
+        }
+    }
+
+    match dirs.swap() {
+        Ok(()) => println!(
+            "\n{}",
+            style("Reindex complete! Start the server to use the rebuilt indexes.")
+                .green()
+                .bold()
+        ),
+        Err(e) => println!(
+            "{}",
+            style(format!(
+                "Swap failed: {e:#?}\nRe-run this command to finish the swap."
+            ))
+            .red()
+        ),
+    }
+}
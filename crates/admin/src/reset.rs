@@ -1,13 +1,14 @@
 use std::path::{Path, PathBuf};
 
 use bichon_core::{
-    admin::meta::{find_admin, open_database, update_admin_password},
+    admin::meta::{find_admin, open_database, reset_admin_two_factor, update_admin_password},
     utils::encrypt::internal_decrypt_string,
 };
+use bichon_memdb::MemDb;
 use console::{style, Emoji};
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
 
-pub fn handle_reset_password(theme: &ColorfulTheme) {
+fn open_root_database(theme: &ColorfulTheme) -> MemDb {
     let root_dir_str: String = Input::with_theme(theme)
         .with_prompt("Enter the absolute path for 'bichon_root_dir'")
         .validate_with(|input: &String| -> Result<(), &str> {
@@ -28,15 +29,18 @@ pub fn handle_reset_password(theme: &ColorfulTheme) {
         .unwrap();
 
     let root_path = PathBuf::from(&root_dir_str);
-    let database = open_database(&root_path.join("memdb")).unwrap_or_else(|e| {
+    open_database(root_path.join("memdb")).unwrap_or_else(|e| {
         eprintln!(
             "\n{} Failed to open database.",
             style("ERROR:").red().bold()
         );
         eprintln!("Details: {:?}", e);
         std::process::exit(1);
-    });
+    })
+}
 
+pub fn handle_reset_password(theme: &ColorfulTheme) {
+    let database = open_root_database(theme);
     let admin = find_admin(&database);
 
     match admin {
@@ -246,3 +250,76 @@ pub fn handle_reset_password(theme: &ColorfulTheme) {
         }
     }
 }
+
+pub fn handle_reset_two_factor(theme: &ColorfulTheme) {
+    let database = open_root_database(theme);
+
+    match find_admin(&database) {
+        Ok(Some(user)) => {
+            println!("\n{}", style("Admin user found:").green().bold());
+            println!("----------------------------------------");
+            println!("{:<12} : {}", "Username", style(&user.username).cyan());
+            println!("{:<12} : {}", "Email", style(&user.email).cyan());
+            println!("----------------------------------------");
+        }
+        Ok(None) => {
+            println!(
+                "\n{}",
+                style("ERROR: No admin user found in the database.")
+                    .red()
+                    .bold()
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!(
+                "\n{} Failed to query admin user.",
+                style("ERROR:").red().bold()
+            );
+            eprintln!("Details: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if !Confirm::with_theme(theme)
+        .with_prompt(
+            "Remove the admin's two-factor enrollment and recovery codes? \
+             If a role requires 2FA, a new enrollment is started at the next login.",
+        )
+        .interact()
+        .unwrap()
+    {
+        println!("Operation cancelled.");
+        return;
+    }
+
+    match reset_admin_two_factor(&database) {
+        Ok(true) => {
+            println!(
+                "\n{} {}",
+                Emoji("✨", "*"),
+                style("Success! Two-factor authentication has been reset.")
+                    .green()
+                    .bold()
+            );
+            println!(
+                "{}",
+                style("The admin can now log in with the password only.").dim()
+            );
+        }
+        Ok(false) => {
+            println!(
+                "\n{}",
+                style("The admin has no two-factor enrollment. Nothing to do.").yellow()
+            );
+        }
+        Err(e) => {
+            println!(
+                "\n{}",
+                style("ERROR: Failed to update database").red().bold()
+            );
+            eprintln!("\nDetails: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+}
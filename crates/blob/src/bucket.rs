@@ -230,6 +230,31 @@ impl IndexStore {
         Ok(())
     }
 
+    /// All live (non-tombstone) keys, in key order.
+    pub fn keys(&self) -> Result<Vec<[u8; 32]>> {
+        let txn = self
+            .db
+            .begin_read()
+            .map_err(|e| crate::error::Error::IndexDb(format!("read txn: {}", e)))?;
+        let table = txn
+            .open_table(INDEX_TABLE)
+            .map_err(|e| crate::error::Error::IndexDb(format!("open table: {}", e)))?;
+
+        let mut keys = Vec::new();
+        let iter = table
+            .iter()
+            .map_err(|e| crate::error::Error::IndexDb(format!("iter: {}", e)))?;
+        for item in iter {
+            let (_, guard) =
+                item.map_err(|e| crate::error::Error::IndexDb(format!("iter next: {}", e)))?;
+            let record = IndexRecord::decode(&guard.value().0)?;
+            if !record.is_tombstone() {
+                keys.push(record.key);
+            }
+        }
+        Ok(keys)
+    }
+
     /// Total number of live (non-tombstone) keys.
     pub fn total_keys(&self) -> Result<usize> {
         let txn = self
@@ -434,6 +434,12 @@ impl Engine {
         inner.flush_active()
     }
 
+    /// Lists every stored key. Used by consistency checks to find blobs no
+    /// longer referenced by any index.
+    pub fn keys(&self) -> Result<Vec<[u8; 32]>> {
+        self.shared.index_store.keys()
+    }
+
     pub fn stats(&self) -> Result<Stats> {
         let inner = self.shared.inner.read().unwrap();
         let meta = &inner.meta;
@@ -40,6 +40,19 @@ fn test_delete() {
     assert_eq!(result, None);
 }
 
+#[test]
+fn test_keys_skips_deleted() {
+    let dir = TempDir::new().unwrap();
+    let engine = Engine::open(dir.path(), Config::default()).unwrap();
+
+    engine.put([0x02; 32], b"second", Codec::None).unwrap();
+    engine.put([0x01; 32], b"first", Codec::None).unwrap();
+    engine.put([0x03; 32], b"third", Codec::None).unwrap();
+    engine.delete(&[0x03; 32]).unwrap();
+
+    assert_eq!(engine.keys().unwrap(), vec![[0x01; 32], [0x02; 32]]);
+}
+
 #[test]
 fn test_exists() {
     let dir = TempDir::new().unwrap();
@@ -29,12 +29,25 @@ pub async fn send_batch_request(
     account_id: u64,
     folder: &str,
     emls: Vec<String>,
+) {
+    send_flagged_batch_request(client, config, account_id, folder, emls, None).await
+}
+
+/// Like [`send_batch_request`], with the IMAP flags of each message in `emls`.
+pub async fn send_flagged_batch_request(
+    client: &Client,
+    config: &BichonCliConfig,
+    account_id: u64,
+    folder: &str,
+    emls: Vec<String>,
+    flags: Option<Vec<Vec<String>>>,
 ) {
     let url = format!("{}/api/v1/import", config.base_url);
     let payload = BatchEmlRequest {
         account_id,
         mail_folder: folder.to_string(),
         emls,
+        flags,
     };
 
     let count = payload.emls.len();
@@ -17,7 +17,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
@@ -50,12 +50,12 @@ pub async fn handle_eml_directory_import(
         .unwrap();
 
     let root_path = std::path::PathBuf::from(root_str);
-    let mut tasks: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    let mut tasks: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
     println!(
         "{}",
         style("🔍 Scanning recursively using std::fs...").dim()
     );
-    if let Err(e) = scan_dir(&root_path, &root_path, &mut tasks) {
+    if let Err(e) = scan_dir(&root_path, &root_path, "eml", &mut tasks) {
         eprintln!("Error scanning directory: {}", e);
         return;
     }
@@ -67,33 +67,34 @@ pub async fn handle_eml_directory_import(
     }
 }
 
-fn scan_dir(
+/// Collects the files with `extension` (any case) below `current`, keyed by
+/// their folder relative to `root`; files directly in `root` go to `Inbox`.
+pub(crate) fn scan_dir(
     root: &Path,
     current: &Path,
-    tasks: &mut HashMap<String, Vec<PathBuf>>,
+    extension: &str,
+    tasks: &mut BTreeMap<String, Vec<PathBuf>>,
 ) -> std::io::Result<()> {
-    if current.is_dir() {
-        for entry in fs::read_dir(current)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.is_dir() {
-                scan_dir(root, &path, tasks)?;
-            } else if path.is_file() {
-                if path.extension().and_then(|s| s.to_str()) == Some("eml") {
-                    let rel_path = path.strip_prefix(root).unwrap_or(Path::new(""));
-                    let mailbox_name = rel_path
-                        .parent()
-                        .map(|p| p.to_string_lossy().replace('\\', "/"))
-                        .unwrap_or_default();
-                    let folder = if mailbox_name.is_empty() {
-                        "Inbox".to_string()
-                    } else {
-                        mailbox_name
-                    };
-                    tasks.entry(folder).or_insert_with(|| Vec::new()).push(path);
-                }
-            }
+    for entry in fs::read_dir(current)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan_dir(root, &path, extension, tasks)?;
+        } else if path
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+        {
+            let rel_path = path.strip_prefix(root).unwrap_or(Path::new(""));
+            let mailbox_name = rel_path
+                .parent()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            let folder = if mailbox_name.is_empty() {
+                "Inbox".to_string()
+            } else {
+                mailbox_name
+            };
+            tasks.entry(folder).or_default().push(path);
         }
     }
     Ok(())
@@ -102,7 +103,7 @@ fn scan_dir(
 async fn process_and_upload(
     config: &BichonCliConfig,
     account_id: u64,
-    tasks: HashMap<String, Vec<PathBuf>>,
+    tasks: BTreeMap<String, Vec<PathBuf>>,
 ) {
     let client = Client::new();
     let batch_size = 50;
@@ -0,0 +1,154 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fs, path::PathBuf};
+
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
+use mail_parser::MessageParser;
+use reqwest::Client;
+
+use bichon_core::base64_encode_url_safe;
+use bichon_core::import::emlx::{discover_messages, parse_emlx};
+
+use crate::{api::sender::send_flagged_batch_request, BichonCliConfig};
+
+const BATCH_SIZE: usize = 50;
+
+pub async fn handle_emlx_import(config: &BichonCliConfig, account_id: u64, theme: &ColorfulTheme) {
+    let root_str: String = Input::with_theme(theme)
+        .with_prompt("Enter the Apple Mail directory (e.g. ~/Library/Mail/V10/<account>)")
+        .validate_with(|input: &String| {
+            let p = std::path::Path::new(input);
+            if p.exists() && p.is_dir() {
+                Ok(())
+            } else {
+                Err("Directory not found.")
+            }
+        })
+        .interact_text()
+        .unwrap();
+
+    let root_path = PathBuf::from(root_str);
+    println!("{}", style("🔍 Scanning for .emlx files...").dim());
+
+    let folders = match discover_messages(&root_path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error scanning directory: {}", e);
+            return;
+        }
+    };
+    if folders.is_empty() {
+        println!("{}", style("No .emlx files found.").yellow());
+        return;
+    }
+
+    println!("\n{}", style("🔍 Scanned Mailboxes:").bold().underlined());
+    for (name, files) in &folders {
+        println!(
+            "  {} {} ({} messages)",
+            style("•").dim(),
+            style(name).cyan(),
+            files.len()
+        );
+    }
+
+    println!();
+    let prompt = format!("Ready to import {} mailboxes. Proceed?", folders.len());
+    if Confirm::with_theme(theme)
+        .with_prompt(prompt)
+        .default(true)
+        .interact()
+        .unwrap()
+    {
+        let client = Client::new();
+        for (name, files) in folders {
+            import_folder(&client, config, account_id, &name, files).await;
+        }
+        println!(
+            "\n{}",
+            style("✨ All mailboxes imported successfully!")
+                .green()
+                .bold()
+        );
+    } else {
+        println!("{}", style("Import cancelled.").yellow());
+    }
+}
+
+async fn import_folder(
+    client: &Client,
+    config: &BichonCliConfig,
+    account_id: u64,
+    folder: &str,
+    files: Vec<PathBuf>,
+) {
+    println!("\n🚀 Importing: {}", style(folder).cyan().bold());
+
+    let mut emls = Vec::with_capacity(BATCH_SIZE);
+    let mut flags = Vec::with_capacity(BATCH_SIZE);
+    for path in files {
+        let data = match fs::read(&path) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!(
+                    "  {} Failed to read file {:?}: {}",
+                    style("✘").red(),
+                    path,
+                    e
+                );
+                continue;
+            }
+        };
+        let emlx = match parse_emlx(&data) {
+            Ok(emlx) if MessageParser::new().parse(emlx.message).is_some() => emlx,
+            Ok(_) => {
+                eprintln!(
+                    "  {} Invalid format, skipping: {:?}",
+                    style("⚠").yellow(),
+                    path
+                );
+                continue;
+            }
+            Err(e) => {
+                eprintln!("  {} {}, skipping: {:?}", style("⚠").yellow(), e, path);
+                continue;
+            }
+        };
+
+        emls.push(base64_encode_url_safe!(emlx.message));
+        flags.push(emlx.flags);
+        if emls.len() >= BATCH_SIZE {
+            let to_send = std::mem::replace(&mut emls, Vec::with_capacity(BATCH_SIZE));
+            let to_send_flags = std::mem::replace(&mut flags, Vec::with_capacity(BATCH_SIZE));
+            send_flagged_batch_request(
+                client,
+                config,
+                account_id,
+                folder,
+                to_send,
+                Some(to_send_flags),
+            )
+            .await;
+        }
+    }
+    if !emls.is_empty() {
+        send_flagged_batch_request(client, config, account_id, folder, emls, Some(flags)).await;
+    }
+}
@@ -0,0 +1,180 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
+use mail_parser::MessageParser;
+use reqwest::Client;
+
+use bichon_core::base64_encode_url_safe;
+use bichon_core::import::maildir::{discover_folders, read_subscriptions, MaildirFolder, INBOX};
+
+use crate::{api::sender::send_flagged_batch_request, BichonCliConfig};
+
+const BATCH_SIZE: usize = 50;
+
+pub async fn handle_maildir_import(
+    config: &BichonCliConfig,
+    account_id: u64,
+    theme: &ColorfulTheme,
+) {
+    let root_str: String = Input::with_theme(theme)
+        .with_prompt("Enter the Maildir directory (the one holding cur/new/tmp)")
+        .validate_with(|input: &String| {
+            let p = std::path::Path::new(input);
+            if p.exists() && p.is_dir() {
+                Ok(())
+            } else {
+                Err("Directory not found.")
+            }
+        })
+        .interact_text()
+        .unwrap();
+
+    let root_path = std::path::PathBuf::from(root_str);
+    println!("{}", style("🔍 Scanning Maildir folders...").dim());
+
+    let mut folders = match discover_folders(&root_path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error scanning directory: {}", e);
+            return;
+        }
+    };
+    if folders.is_empty() {
+        println!("{}", style("No Maildir folders found.").yellow());
+        return;
+    }
+
+    if let Some(subscribed) = read_subscriptions(&root_path) {
+        let prompt = format!(
+            "Found a subscriptions file listing {} folders. Import only subscribed folders (and INBOX)?",
+            subscribed.len()
+        );
+        if Confirm::with_theme(theme)
+            .with_prompt(prompt)
+            .default(false)
+            .interact()
+            .unwrap()
+        {
+            folders.retain(|f| f.name == INBOX || subscribed.contains(&f.name));
+        }
+    }
+
+    println!("\n{}", style("🔍 Scanned Folders:").bold().underlined());
+    for folder in &folders {
+        let count = folder.messages().map(|m| m.len()).unwrap_or(0);
+        println!(
+            "  {} {} ({} messages)",
+            style("•").dim(),
+            style(&folder.name).cyan(),
+            count
+        );
+    }
+
+    println!();
+    let prompt = format!("Ready to import {} folders. Proceed?", folders.len());
+    if Confirm::with_theme(theme)
+        .with_prompt(prompt)
+        .default(true)
+        .interact()
+        .unwrap()
+    {
+        let client = Client::new();
+        for folder in &folders {
+            import_folder(&client, config, account_id, folder).await;
+        }
+        println!(
+            "\n{}",
+            style("✨ All folders imported successfully!")
+                .green()
+                .bold()
+        );
+    } else {
+        println!("{}", style("Import cancelled.").yellow());
+    }
+}
+
+async fn import_folder(
+    client: &Client,
+    config: &BichonCliConfig,
+    account_id: u64,
+    folder: &MaildirFolder,
+) {
+    println!("\n🚀 Importing: {}", style(&folder.name).cyan().bold());
+
+    let messages = match folder.messages() {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!(
+                "  {} Failed to list messages in {:?}: {}",
+                style("✘").red(),
+                folder.path,
+                e
+            );
+            return;
+        }
+    };
+
+    let mut emls = Vec::with_capacity(BATCH_SIZE);
+    let mut flags = Vec::with_capacity(BATCH_SIZE);
+    for message in messages {
+        let body = match fs::read(&message.path) {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!(
+                    "  {} Failed to read file {:?}: {}",
+                    style("✘").red(),
+                    message.path,
+                    e
+                );
+                continue;
+            }
+        };
+        if MessageParser::new().parse(&body).is_none() {
+            eprintln!(
+                "  {} Invalid format, skipping: {:?}",
+                style("⚠").yellow(),
+                message.path
+            );
+            continue;
+        }
+
+        emls.push(base64_encode_url_safe!(&body));
+        flags.push(message.flags);
+        if emls.len() >= BATCH_SIZE {
+            let to_send = std::mem::replace(&mut emls, Vec::with_capacity(BATCH_SIZE));
+            let to_send_flags = std::mem::replace(&mut flags, Vec::with_capacity(BATCH_SIZE));
+            send_flagged_batch_request(
+                client,
+                config,
+                account_id,
+                &folder.name,
+                to_send,
+                Some(to_send_flags),
+            )
+            .await;
+        }
+    }
+    if !emls.is_empty() {
+        send_flagged_batch_request(client, config, account_id, &folder.name, emls, Some(flags))
+            .await;
+    }
+}
@@ -25,15 +25,19 @@ use std::fs;
 
 use crate::{
     auth::verify_user_and_get_account, eml::handle_eml_directory_import,
-    export::handle_account_export, mbox::handle_mbox_single_file_import, pst::handle_pst_import,
+    emlx::handle_emlx_import, export::handle_account_export, maildir::handle_maildir_import,
+    mbox::handle_mbox_single_file_import, msg::handle_msg_import, pst::handle_pst_import,
     thunderbird::handle_thunderbird_import,
 };
 
 pub mod api;
 pub mod auth;
 pub mod eml;
+pub mod emlx;
 pub mod export;
+pub mod maildir;
 pub mod mbox;
+pub mod msg;
 pub mod pst;
 pub mod thunderbird;
 
@@ -146,6 +150,9 @@ async fn main() {
                 "2. MBOX: Single archive file (Stream from one file)",
                 "3. Thunderbird: Import from local profile directory",
                 "4. PST: Outlook Personal Storage (Single .pst file)",
+                "5. Maildir: Dovecot/Courier/Postfix mail directory (Keeps folders and flags)",
+                "6. Apple Mail: Scan directory for .emlx files (Keeps mailboxes and flags)",
+                "7. MSG: Outlook .msg file, or a directory of them (Maintains folder structure)",
             ];
 
             let mode_idx = Select::with_theme(&theme)
@@ -160,6 +167,9 @@ async fn main() {
                 1 => handle_mbox_single_file_import(&final_config, target_account.id, &theme).await,
                 2 => handle_thunderbird_import(&final_config, target_account.id, &theme).await,
                 3 => handle_pst_import(&final_config, target_account.id, &theme).await,
+                4 => handle_maildir_import(&final_config, target_account.id, &theme).await,
+                5 => handle_emlx_import(&final_config, target_account.id, &theme).await,
+                6 => handle_msg_import(&final_config, target_account.id, &theme).await,
                 _ => unreachable!(),
             }
         }
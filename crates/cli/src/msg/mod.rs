@@ -0,0 +1,129 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
+use reqwest::Client;
+
+use bichon_core::base64_encode_url_safe;
+use bichon_core::import::msg::msg_to_eml;
+
+use crate::{api::sender::send_batch_request, eml::scan_dir, BichonCliConfig};
+
+const BATCH_SIZE: usize = 50;
+
+pub async fn handle_msg_import(config: &BichonCliConfig, account_id: u64, theme: &ColorfulTheme) {
+    let root_str: String = Input::with_theme(theme)
+        .with_prompt("Enter a .msg file or a directory to scan for .msg files")
+        .validate_with(|input: &String| {
+            if Path::new(input).exists() {
+                Ok(())
+            } else {
+                Err("The specified path does not exist.")
+            }
+        })
+        .interact_text()
+        .unwrap();
+
+    let root_path = PathBuf::from(root_str);
+    let mut tasks: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    if root_path.is_file() {
+        tasks.insert("Inbox".to_string(), vec![root_path.clone()]);
+    } else {
+        println!(
+            "{}",
+            style("🔍 Scanning recursively for .msg files...").dim()
+        );
+        if let Err(e) = scan_dir(&root_path, &root_path, "msg", &mut tasks) {
+            eprintln!("Error scanning directory: {}", e);
+            return;
+        }
+    }
+
+    if tasks.is_empty() {
+        println!("{}", style("No .msg files found.").yellow());
+        return;
+    }
+
+    println!("\n{}", style("🔍 Scanned Folders:").bold().underlined());
+    for (name, files) in &tasks {
+        println!(
+            "  {} {} ({} files)",
+            style("•").dim(),
+            style(name).cyan(),
+            files.len()
+        );
+    }
+
+    println!();
+    let total: usize = tasks.values().map(Vec::len).sum();
+    let prompt = format!("Ready to import {} Outlook messages. Proceed?", total);
+    if Confirm::with_theme(theme)
+        .with_prompt(prompt)
+        .default(true)
+        .interact()
+        .unwrap()
+    {
+        process_and_upload(config, account_id, tasks).await;
+    } else {
+        println!("{}", style("Import cancelled.").yellow());
+    }
+}
+
+async fn process_and_upload(
+    config: &BichonCliConfig,
+    account_id: u64,
+    tasks: BTreeMap<String, Vec<PathBuf>>,
+) {
+    let client = Client::new();
+
+    for (mailbox, files) in tasks {
+        println!("\n🚀 Processing mailbox: {}", style(&mailbox).cyan().bold());
+
+        let mut current_batch = Vec::with_capacity(BATCH_SIZE);
+        for file_path in files {
+            let eml = match fs::read(&file_path).and_then(|data| msg_to_eml(&data)) {
+                Ok(eml) => eml,
+                Err(e) => {
+                    eprintln!(
+                        "  {} Failed to convert {:?}: {}",
+                        style("✘").red(),
+                        file_path,
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            current_batch.push(base64_encode_url_safe!(&eml));
+            if current_batch.len() >= BATCH_SIZE {
+                let to_send = std::mem::replace(&mut current_batch, Vec::with_capacity(BATCH_SIZE));
+                send_batch_request(&client, config, account_id, &mailbox, to_send).await;
+            }
+        }
+        if !current_batch.is_empty() {
+            send_batch_request(&client, config, account_id, &mailbox, current_batch).await;
+        }
+    }
+}
@@ -58,6 +58,136 @@ impl ImapConfig {
     }
 }
 
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct Pop3Config {
+    /// POP3 server hostname or IP address
+    #[cfg_attr(
+        feature = "web-api",
+        oai(validator(max_length = 253, pattern = r"^[a-zA-Z0-9\-\.]+$"))
+    )]
+    pub host: String,
+    /// POP3 server port number (usually 995 for SSL, 110 otherwise)
+    #[cfg_attr(
+        feature = "web-api",
+        oai(validator(minimum(value = "1"), maximum(value = "65535")))
+    )]
+    pub port: u16,
+    /// Connection encryption method. `StartTls` upgrades the connection with `STLS`.
+    pub encryption: Encryption,
+    /// Login method
+    pub auth_method: Pop3AuthMethod,
+    /// Account password.
+    ///
+    /// Users should provide a plaintext password (1 to 256 characters).
+    /// The server will encrypt the password using AES-256-GCM and securely store it.
+    #[cfg_attr(feature = "web-api", oai(validator(max_length = 256, min_length = 1)))]
+    pub password: Option<String>,
+    /// Optional proxy ID for establishing the connection.
+    /// - If `None` or not provided, the client will connect directly to the POP3 server.
+    /// - If `Some(proxy_id)`, the client will use the pre-configured proxy with the given ID.
+    pub use_proxy: Option<u64>,
+    /// Keep messages on the server after archiving them. `None` counts as `true`;
+    /// with `false` every archived message is deleted from the server (`DELE`)
+    /// on the first download after its envelope was committed to the index.
+    #[serde(default)]
+    pub leave_on_server: Option<bool>,
+}
+
+impl Pop3Config {
+    pub fn try_encrypt_password(self) -> BichonResult<Self> {
+        Ok(Self {
+            password: self.password.map(|p| encrypt!(&p)).transpose()?,
+            ..self
+        })
+    }
+
+    pub fn leave_on_server(&self) -> bool {
+        self.leave_on_server.unwrap_or(true)
+    }
+}
+
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Enum))]
+pub enum Pop3AuthMethod {
+    /// Plaintext `USER`/`PASS` login
+    #[default]
+    User,
+    /// `APOP` digest login (RFC 1939), the password never crosses the wire
+    Apop,
+}
+
+/// Default Microsoft Graph endpoint (global cloud).
+pub const DEFAULT_GRAPH_ENDPOINT: &str = "https://graph.microsoft.com/v1.0";
+
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct GraphConfig {
+    /// Mailbox to archive, as a user principal name or user id.
+    /// - If `None`, the mailbox of the user who authorized the account (`/me`) is archived.
+    /// - Archiving another user's mailbox requires a token with access to it.
+    #[cfg_attr(feature = "web-api", oai(validator(max_length = 320)))]
+    pub mailbox: Option<String>,
+    /// Graph API endpoint. Defaults to `https://graph.microsoft.com/v1.0`;
+    /// set it for national clouds.
+    #[cfg_attr(feature = "web-api", oai(validator(max_length = 253)))]
+    pub endpoint: Option<String>,
+    /// Optional proxy ID for establishing the connection.
+    /// - If `None` or not provided, the client will connect directly to the Graph endpoint.
+    /// - If `Some(proxy_id)`, the client will use the pre-configured proxy with the given ID.
+    pub use_proxy: Option<u64>,
+}
+
+impl GraphConfig {
+    pub fn endpoint(&self) -> &str {
+        self.endpoint
+            .as_deref()
+            .map(|e| e.trim_end_matches('/'))
+            .unwrap_or(DEFAULT_GRAPH_ENDPOINT)
+    }
+}
+
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct JmapConfig {
+    /// JMAP server URL, e.g. `https://api.fastmail.com`.
+    /// - A bare origin is resolved through `/.well-known/jmap`.
+    /// - A URL with a path is used as the session URL as is.
+    #[cfg_attr(feature = "web-api", oai(validator(max_length = 2048)))]
+    pub url: String,
+    /// How requests are authenticated
+    pub auth_method: JmapAuthMethod,
+    /// Password (`Basic`) or API token (`Bearer`).
+    ///
+    /// Users should provide a plaintext secret (1 to 1024 characters).
+    /// The server will encrypt it using AES-256-GCM and securely store it.
+    #[cfg_attr(feature = "web-api", oai(validator(max_length = 1024, min_length = 1)))]
+    pub password: Option<String>,
+    /// Optional proxy ID for establishing the connection.
+    /// - If `None` or not provided, the client will connect directly to the JMAP server.
+    /// - If `Some(proxy_id)`, the client will use the pre-configured proxy with the given ID.
+    pub use_proxy: Option<u64>,
+}
+
+impl JmapConfig {
+    pub fn try_encrypt_password(self) -> BichonResult<Self> {
+        Ok(Self {
+            password: self.password.map(|p| encrypt!(&p)).transpose()?,
+            ..self
+        })
+    }
+}
+
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Enum))]
+pub enum JmapAuthMethod {
+    /// API token sent as `Authorization: Bearer` (Fastmail)
+    #[default]
+    Bearer,
+    /// Login name and password sent as HTTP Basic credentials (Stalwart)
+    Basic,
+}
+
 #[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "web-api", derive(poem_openapi::Enum))]
 pub enum AuthType {
@@ -18,11 +18,12 @@
 
 use std::str::FromStr;
 
-use crate::account::entity::ImapConfig;
+use crate::account::entity::{GraphConfig, ImapConfig, JmapConfig, Pop3Config};
 use crate::account::migration::{
     AccountModel, AccountType, ArchiveRules, ExtractionRules, QuotaWindow,
 };
 use crate::account::since::{DateSince, RelativeDate};
+use crate::envelope::decrypt::{validate_pgp_key, validate_smime_key};
 use crate::error::code::ErrorCode;
 use crate::error::BichonResult;
 use crate::{raise_error, validate_email};
@@ -39,6 +40,13 @@ pub struct AccountCreateRequest {
     pub login_name: Option<String>,
     pub account_name: Option<String>,
     pub imap: Option<ImapConfig>,
+    /// POP3 server configuration, required for the `POP3` account type.
+    pub pop3: Option<Pop3Config>,
+    /// Mailbox settings for the `Graph` account type. Defaults to the
+    /// authorizing user's mailbox on the global Graph endpoint.
+    pub graph: Option<GraphConfig>,
+    /// JMAP server configuration, required for the `JMAP` account type.
+    pub jmap: Option<JmapConfig>,
     pub enabled: bool,
     pub date_since: Option<DateSince>,
     pub date_before: Option<RelativeDate>,
@@ -52,15 +60,30 @@ pub struct AccountCreateRequest {
     pub download_batch_size: Option<u32>,
     pub max_email_size_bytes: Option<u64>,
     pub use_dangerous: bool,
+    /// ASCII-armored OpenPGP secret key for decrypting `multipart/encrypted`
+    /// messages at ingest.
     pub pgp_key: Option<String>,
+    /// Passphrase of `pgp_key`, if the key is protected.
+    pub pgp_passphrase: Option<String>,
+    /// Base64-encoded PKCS#12 bundle holding the private key and certificate
+    /// for decrypting S/MIME messages at ingest.
+    pub smime_key: Option<String>,
+    /// Password of `smime_key`.
+    pub smime_password: Option<String>,
     pub imap_quota_bytes: Option<u64>,
     pub imap_quota_window: Option<QuotaWindow>,
     pub auto_download_new_mailboxes: Option<bool>,
     pub download_schedule: Option<String>,
+    /// Mailboxes to watch with IMAP IDLE so new mail is archived as soon as
+    /// it arrives. Polling keeps running alongside as a safety net.
+    pub idle_folders: Option<Vec<String>>,
+    /// Gmail sync mode: download only `[Gmail]/All Mail` and archive the
+    /// labels of each message instead of every label folder.
+    pub gmail_sync: Option<bool>,
     /// Email archive filtering rules (Pro feature).
     /// `None` = archive everything (backward compatible).
     pub archive_rules: Option<ArchiveRules>,
-    /// Attachment text extraction rules (Pro feature).
+    /// Attachment text extraction rules.
     /// `None` = extract everything (backward compatible).
     pub extraction_rules: Option<ExtractionRules>,
 }
@@ -110,6 +133,73 @@ impl AccountCreateRequest {
                 if let Some(ref schedule) = self.download_schedule {
                     validate_cron_expression(schedule)?;
                 }
+                if let Some(ref folders) = self.idle_folders {
+                    validate_idle_folders(folders)?;
+                }
+            }
+            AccountType::POP3 => {
+                match &self.pop3 {
+                    Some(pop3) => Self::validate_pop3_request(pop3, &self.email)?,
+                    None => {
+                        return Err(raise_error!(
+                            "POP3 configuration is required for POP3 account type".into(),
+                            ErrorCode::InvalidParameter
+                        ))
+                    }
+                }
+                if self.download_interval_min.is_none() && self.download_schedule.is_none() {
+                    return Err(raise_error!(
+                        "`sync_interval_min` or `download_schedule` is required for POP3 account type".into(),
+                        ErrorCode::InvalidParameter
+                    ));
+                }
+                if let Some(ref schedule) = self.download_schedule {
+                    validate_cron_expression(schedule)?;
+                }
+            }
+            AccountType::Graph => {
+                if let Some(graph) = &self.graph {
+                    validate_graph_config(graph)?;
+                }
+                validate_email!(&self.email)?;
+                if self.download_interval_min.is_none() && self.download_schedule.is_none() {
+                    return Err(raise_error!(
+                        "`sync_interval_min` or `download_schedule` is required for Graph account type".into(),
+                        ErrorCode::InvalidParameter
+                    ));
+                }
+                if let Some(ref schedule) = self.download_schedule {
+                    validate_cron_expression(schedule)?;
+                }
+            }
+            AccountType::JMAP => {
+                match &self.jmap {
+                    Some(jmap) => {
+                        if jmap.password.is_none() {
+                            return Err(raise_error!(
+                                "JMAP accounts require a password or API token.".into(),
+                                ErrorCode::InvalidParameter
+                            ));
+                        }
+                        validate_jmap_config(jmap)?;
+                    }
+                    None => {
+                        return Err(raise_error!(
+                            "JMAP configuration is required for JMAP account type".into(),
+                            ErrorCode::InvalidParameter
+                        ))
+                    }
+                }
+                validate_email!(&self.email)?;
+                if self.download_interval_min.is_none() && self.download_schedule.is_none() {
+                    return Err(raise_error!(
+                        "`sync_interval_min` or `download_schedule` is required for JMAP account type".into(),
+                        ErrorCode::InvalidParameter
+                    ));
+                }
+                if let Some(ref schedule) = self.download_schedule {
+                    validate_cron_expression(schedule)?;
+                }
             }
             AccountType::NoSync => {}
         }
@@ -126,6 +216,12 @@ impl AccountCreateRequest {
                 raise_error!(format!("archive_rules: {}", e), ErrorCode::InvalidParameter)
             })?;
         }
+        if let Some(ref key) = self.pgp_key {
+            validate_pgp_key(key, self.pgp_passphrase.as_deref())?;
+        }
+        if let Some(ref key) = self.smime_key {
+            validate_smime_key(key, self.smime_password.as_deref())?;
+        }
         Ok(AccountModel::new(user_id, self)?)
     }
 
@@ -136,6 +232,17 @@ impl AccountCreateRequest {
         validate_email!(email)?;
         Ok(())
     }
+
+    fn validate_pop3_request(pop3: &Pop3Config, email: &str) -> BichonResult<()> {
+        if pop3.password.is_none() {
+            return Err(raise_error!(
+                "POP3 accounts require a password.".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        validate_email!(email)?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
@@ -151,6 +258,12 @@ pub struct AccountUpdateRequest {
     pub account_name: Option<String>,
     /// IMAP server configuration
     pub imap: Option<ImapConfig>,
+    /// POP3 server configuration. Leave `password` empty to keep the current one.
+    pub pop3: Option<Pop3Config>,
+    /// Graph mailbox settings, replacing the current ones.
+    pub graph: Option<GraphConfig>,
+    /// JMAP server configuration. Leave `password` empty to keep the current one.
+    pub jmap: Option<JmapConfig>,
     /// Controls initial synchronization time range
     ///
     /// When dealing with large mailboxes, this restricts scanning to:
@@ -189,16 +302,31 @@ pub struct AccountUpdateRequest {
     pub max_email_size_bytes: Option<u64>,
     pub use_dangerous: Option<bool>,
 
+    /// Replaces the OpenPGP secret key. `pgp_passphrase` is read together
+    /// with it.
     pub pgp_key: Option<String>,
+    pub pgp_passphrase: Option<String>,
+    /// Removes the OpenPGP secret key and its passphrase.
+    pub clear_pgp_key: Option<bool>,
+    /// Replaces the S/MIME PKCS#12 bundle. `smime_password` is read together
+    /// with it.
+    pub smime_key: Option<String>,
+    pub smime_password: Option<String>,
+    /// Removes the S/MIME key and its password.
+    pub clear_smime_key: Option<bool>,
     pub imap_quota_bytes: Option<u64>,
     pub imap_quota_window: Option<QuotaWindow>,
     pub auto_download_new_mailboxes: Option<bool>,
     pub download_schedule: Option<String>,
     pub clear_download_schedule: Option<bool>,
+    /// Mailboxes to watch with IMAP IDLE. An empty list turns IDLE off.
+    pub idle_folders: Option<Vec<String>>,
+    /// Turns Gmail sync mode on or off.
+    pub gmail_sync: Option<bool>,
     /// Email archive filtering rules (Pro feature).
     /// `None` = no change. Use `Some(ArchiveRules { .. })` to set.
     pub archive_rules: Option<ArchiveRules>,
-    /// Attachment text extraction rules (Pro feature).
+    /// Attachment text extraction rules.
     /// `None` = no change. Use `Some(ExtractionRules { .. })` to set.
     pub extraction_rules: Option<ExtractionRules>,
 }
@@ -237,6 +365,27 @@ impl AccountUpdateRequest {
             date_before.validate_date()?;
         }
 
+        if account.is_downloadable() {
+            if self.clear_download_schedule == Some(true) && self.download_schedule.is_some() {
+                return Err(raise_error!(
+                    "clear_download_schedule cannot be combined with download_schedule".into(),
+                    ErrorCode::InvalidParameter
+                ));
+            }
+            if let Some(ref schedule) = self.download_schedule {
+                validate_cron_expression(schedule)?;
+            }
+        }
+        if matches!(account.account_type, AccountType::Graph) {
+            if let Some(graph) = &self.graph {
+                validate_graph_config(graph)?;
+            }
+        }
+        if matches!(account.account_type, AccountType::JMAP) {
+            if let Some(jmap) = &self.jmap {
+                validate_jmap_config(jmap)?;
+            }
+        }
         if matches!(account.account_type, AccountType::IMAP) {
             if let Some(mailboxes) = self.sync_folders.as_ref() {
                 if mailboxes.is_empty() {
@@ -246,14 +395,8 @@ impl AccountUpdateRequest {
                 ));
                 }
             }
-            if self.clear_download_schedule == Some(true) && self.download_schedule.is_some() {
-                return Err(raise_error!(
-                    "clear_download_schedule cannot be combined with download_schedule".into(),
-                    ErrorCode::InvalidParameter
-                ));
-            }
-            if let Some(ref schedule) = self.download_schedule {
-                validate_cron_expression(schedule)?;
+            if let Some(ref folders) = self.idle_folders {
+                validate_idle_folders(folders)?;
             }
         }
         if let Some(ref rules) = self.extraction_rules {
@@ -269,6 +412,24 @@ impl AccountUpdateRequest {
                 raise_error!(format!("archive_rules: {}", e), ErrorCode::InvalidParameter)
             })?;
         }
+        if self.clear_pgp_key == Some(true) && self.pgp_key.is_some() {
+            return Err(raise_error!(
+                "clear_pgp_key cannot be combined with pgp_key".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        if self.clear_smime_key == Some(true) && self.smime_key.is_some() {
+            return Err(raise_error!(
+                "clear_smime_key cannot be combined with smime_key".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        if let Some(ref key) = self.pgp_key {
+            validate_pgp_key(key, self.pgp_passphrase.as_deref())?;
+        }
+        if let Some(ref key) = self.smime_key {
+            validate_smime_key(key, self.smime_password.as_deref())?;
+        }
         Ok(())
     }
 }
@@ -289,6 +450,65 @@ fn validate_cron_expression(expr: &str) -> BichonResult<()> {
     Ok(())
 }
 
+fn validate_graph_config(graph: &GraphConfig) -> BichonResult<()> {
+    if let Some(endpoint) = &graph.endpoint {
+        let valid = reqwest::Url::parse(endpoint)
+            .is_ok_and(|url| matches!(url.scheme(), "https" | "http") && url.has_host());
+        if !valid {
+            return Err(raise_error!(
+                format!("Invalid Graph endpoint '{}'", endpoint),
+                ErrorCode::InvalidParameter
+            ));
+        }
+    }
+    if graph
+        .mailbox
+        .as_ref()
+        .is_some_and(|m| m.trim().is_empty() || m.contains(['/', '?', '#']))
+    {
+        return Err(raise_error!(
+            "Graph mailbox must be a user principal name or user id".into(),
+            ErrorCode::InvalidParameter
+        ));
+    }
+    Ok(())
+}
+
+fn validate_jmap_config(jmap: &JmapConfig) -> BichonResult<()> {
+    let valid = reqwest::Url::parse(jmap.url.trim())
+        .is_ok_and(|url| matches!(url.scheme(), "https" | "http") && url.has_host());
+    if !valid {
+        return Err(raise_error!(
+            format!("Invalid JMAP server URL '{}'", jmap.url),
+            ErrorCode::InvalidParameter
+        ));
+    }
+    Ok(())
+}
+
+/// Each watched mailbox holds its own IMAP connection, so keep the number
+/// well below typical per-user connection limits.
+pub const MAX_IDLE_FOLDERS: usize = 5;
+
+fn validate_idle_folders(folders: &[String]) -> BichonResult<()> {
+    if folders.len() > MAX_IDLE_FOLDERS {
+        return Err(raise_error!(
+            format!(
+                "idle_folders accepts at most {} mailboxes",
+                MAX_IDLE_FOLDERS
+            ),
+            ErrorCode::InvalidParameter
+        ));
+    }
+    if folders.iter().any(|f| f.trim().is_empty()) {
+        return Err(raise_error!(
+            "idle_folders must not contain empty mailbox names".into(),
+            ErrorCode::InvalidParameter
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
 #[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
 
@@ -311,7 +531,8 @@ pub fn filter_accessible_accounts<'a>(
 
 #[cfg(test)]
 mod test {
-    use super::validate_cron_expression;
+    use super::{validate_cron_expression, validate_graph_config, validate_jmap_config};
+    use crate::account::entity::{GraphConfig, JmapConfig};
 
     #[test]
     fn valid_cron_expressions() {
@@ -338,4 +559,35 @@ mod test {
     fn invalid_cron_expression_garbage() {
         assert!(validate_cron_expression("not a cron").is_err());
     }
+
+    #[test]
+    fn graph_config_validation() {
+        let config = |mailbox: Option<&str>, endpoint: Option<&str>| GraphConfig {
+            mailbox: mailbox.map(Into::into),
+            endpoint: endpoint.map(Into::into),
+            use_proxy: None,
+        };
+        assert!(validate_graph_config(&config(None, None)).is_ok());
+        assert!(validate_graph_config(&config(
+            Some("archive@contoso.com"),
+            Some("https://graph.microsoft.us/v1.0")
+        ))
+        .is_ok());
+        assert!(validate_graph_config(&config(None, Some("graph.microsoft.com"))).is_err());
+        assert!(validate_graph_config(&config(None, Some("ftp://example.com"))).is_err());
+        assert!(validate_graph_config(&config(Some(" "), None)).is_err());
+        assert!(validate_graph_config(&config(Some("a/../b"), None)).is_err());
+    }
+
+    #[test]
+    fn jmap_config_validation() {
+        let config = |url: &str| JmapConfig {
+            url: url.into(),
+            ..Default::default()
+        };
+        assert!(validate_jmap_config(&config("https://api.fastmail.com")).is_ok());
+        assert!(validate_jmap_config(&config("https://mail.example.com/jmap/session")).is_ok());
+        assert!(validate_jmap_config(&config("api.fastmail.com")).is_err());
+        assert!(validate_jmap_config(&config("imaps://mail.example.com")).is_err());
+    }
 }
@@ -21,7 +21,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     account::{
-        entity::ImapConfig,
+        entity::{GraphConfig, ImapConfig, JmapConfig, Pop3Config},
         migration::{AccountModel, AccountType, ArchiveRules, QuotaWindow},
         since::{DateSince, RelativeDate},
     },
@@ -33,6 +33,9 @@ use crate::{
 pub struct AccountResp {
     pub id: u64,
     pub imap: Option<ImapConfig>,
+    pub pop3: Option<Pop3Config>,
+    pub graph: Option<GraphConfig>,
+    pub jmap: Option<JmapConfig>,
     pub enabled: bool,
     pub email: String,
     pub account_name: Option<String>,
@@ -52,11 +55,17 @@ pub struct AccountResp {
     pub created_user_name: String,
     pub created_user_email: String,
     pub use_dangerous: bool,
-    pub pgp_key: Option<String>,
+    /// Whether an OpenPGP secret key is configured. The key itself is never
+    /// returned.
+    pub has_pgp_key: bool,
+    /// Whether an S/MIME PKCS#12 bundle is configured.
+    pub has_smime_key: bool,
     pub imap_quota_bytes: Option<u64>,
     pub imap_quota_window: Option<QuotaWindow>,
     pub auto_download_new_mailboxes: Option<bool>,
     pub download_schedule: Option<String>,
+    pub idle_folders: Option<Vec<String>>,
+    pub gmail_sync: Option<bool>,
     pub archive_rules: Option<ArchiveRules>,
     pub deleting: bool,
 }
@@ -67,6 +76,9 @@ impl AccountResp {
         AccountResp {
             id: account.id,
             imap: account.imap,
+            pop3: account.pop3,
+            graph: account.graph,
+            jmap: account.jmap,
             enabled: account.enabled,
             email: account.email,
             account_name: account.account_name,
@@ -90,11 +102,14 @@ impl AccountResp {
                 .map(|u| u.email.clone())
                 .unwrap_or_else(|| "N/A".to_string()),
             use_dangerous: account.use_dangerous,
-            pgp_key: account.pgp_key,
+            has_pgp_key: account.pgp_key.is_some(),
+            has_smime_key: account.smime_key.is_some(),
             imap_quota_bytes: account.imap_quota_bytes,
             imap_quota_window: account.imap_quota_window,
             auto_download_new_mailboxes: account.auto_download_new_mailboxes,
             download_schedule: account.download_schedule,
+            idle_folders: account.idle_folders,
+            gmail_sync: account.gmail_sync,
             archive_rules: account.archive_rules,
             deleting: account.deleting,
         }
@@ -16,15 +16,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::path::Path;
+use std::{collections::HashSet, path::Path};
 
 use bichon_memdb::{Durability, MemDb};
 
 use crate::{
+    account::migration::AccountModel,
+    cache::imap::mailbox::MailBox,
     database::MemDbModel,
     error::{code::ErrorCode, BichonResult},
+    message::remote::RemoteContent,
     raise_error,
-    users::{UserModel, DEFAULT_ADMIN_USER_ID},
+    users::{totp::UserTotp, UserModel, DEFAULT_ADMIN_USER_ID},
     utils::encrypt::internal_encrypt_string,
 };
 
@@ -66,3 +69,37 @@ pub fn update_admin_password(
 
     Ok(())
 }
+
+/// Remove the default admin's TOTP enrollment. Returns whether one existed.
+pub fn reset_admin_two_factor(db: &MemDb) -> BichonResult<bool> {
+    let coll = db.collection(UserTotp::collection());
+    coll.delete(DEFAULT_ADMIN_USER_ID.to_string())
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))
+}
+
+/// Ids of every mailbox known to the database, across all accounts.
+pub fn list_mailbox_ids(db: &MemDb) -> BichonResult<HashSet<u64>> {
+    let coll = db.collection(MailBox::collection());
+    let mailboxes: Vec<MailBox> = coll
+        .list_all()
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+    Ok(mailboxes.into_iter().map(|m| m.id).collect())
+}
+
+/// Ids of every account known to the database.
+pub fn list_account_ids(db: &MemDb) -> BichonResult<HashSet<u64>> {
+    let coll = db.collection(AccountModel::collection());
+    let accounts: Vec<AccountModel> = coll
+        .list_all()
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+    Ok(accounts.into_iter().map(|a| a.id).collect())
+}
+
+/// Blob content hashes of cached remote content, which no index references.
+pub fn list_remote_content_hashes(db: &MemDb) -> BichonResult<HashSet<String>> {
+    let coll = db.collection(RemoteContent::collection());
+    let cached: Vec<RemoteContent> = coll
+        .list_all()
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+    Ok(cached.into_iter().map(|c| c.content_hash).collect())
+}
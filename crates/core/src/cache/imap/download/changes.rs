@@ -0,0 +1,309 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Keeps archived messages in step with later changes on the server.
+//!
+//! With QRESYNC (RFC 7162) one `EXAMINE … (QRESYNC …)` reports new and changed
+//! messages and the UIDs expunged since the `HIGHESTMODSEQ` stored with the
+//! mailbox; expunged messages are marked deleted on source, never removed.
+//! CONDSTORE servers are asked only for flags changed since that MODSEQ, and
+//! other servers get a `UID FETCH 1:<highest_uid> (UID FLAGS)` sweep, which is
+//! cheap compared to the bodies and lets the index skip unchanged envelopes.
+//!
+//! In Gmail sync mode the labels of All Mail are refreshed the same way, as
+//! Gmail bumps a message's MODSEQ when its labels change.
+
+use crate::{
+    raise_error,
+    {
+        account::migration::AccountModel,
+        cache::imap::mailbox::MailBox,
+        error::{code::ErrorCode, BichonResult},
+        imap::{
+            capabilities::{fetch_capabilities, supports_condstore, supports_qresync},
+            executor::ImapExecutor,
+            session::SessionStream,
+        },
+        store::tantivy::envelope::ENVELOPE_MANAGER,
+    },
+};
+use async_imap::Session;
+use std::collections::HashMap;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+/// Outcome of [`sync_mailbox_changes`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MailboxChanges {
+    /// `HIGHESTMODSEQ` to store with the mailbox, `None` when the server has none.
+    pub highest_modseq: Option<u64>,
+    /// Whether the server reported messages above `highest_uid`. `None` when
+    /// it cannot tell (no QRESYNC), so the caller has to look for new mail.
+    pub new_mail: Option<bool>,
+}
+
+/// Applies flag changes and server-side expunges to the messages already
+/// archived from `mailbox` (UIDs up to its `highest_uid`).
+pub async fn sync_mailbox_changes(
+    account: &AccountModel,
+    mailbox: &MailBox,
+    token: CancellationToken,
+) -> BichonResult<MailboxChanges> {
+    let highest_uid = match mailbox.highest_uid {
+        Some(uid) if uid > 0 => uid,
+        _ => {
+            return Ok(MailboxChanges {
+                highest_modseq: mailbox.highest_modseq,
+                new_mail: None,
+            })
+        }
+    };
+
+    let mut session = ImapExecutor::create_connection(account.id).await?;
+    let result = sync_with_session(&mut session, account, mailbox, highest_uid, token).await;
+    session.logout().await.ok();
+    result
+}
+
+async fn sync_with_session(
+    session: &mut Session<Box<dyn SessionStream>>,
+    account: &AccountModel,
+    mailbox: &MailBox,
+    highest_uid: u32,
+    token: CancellationToken,
+) -> BichonResult<MailboxChanges> {
+    let capabilities = fetch_capabilities(session).await?;
+    // QRESYNC needs a known UIDVALIDITY/MODSEQ pair; the first run takes the
+    // CONDSTORE path below to establish it.
+    if supports_qresync(&capabilities) {
+        if let (Some(uid_validity), Some(modseq)) = (mailbox.uid_validity, mailbox.highest_modseq) {
+            return sync_with_qresync(session, account, mailbox, highest_uid, uid_validity, modseq)
+                .await;
+        }
+    }
+    let condstore = supports_condstore(&capabilities);
+    let highest_modseq =
+        refresh_flags(session, account, mailbox, highest_uid, condstore, token).await?;
+    Ok(MailboxChanges {
+        highest_modseq,
+        new_mail: None,
+    })
+}
+
+async fn sync_with_qresync(
+    session: &mut Session<Box<dyn SessionStream>>,
+    account: &AccountModel,
+    mailbox: &MailBox,
+    highest_uid: u32,
+    uid_validity: u32,
+    modseq: u64,
+) -> BichonResult<MailboxChanges> {
+    let mut changes =
+        ImapExecutor::examine_qresync(session, &mailbox.encoded_name(), uid_validity, modseq)
+            .await?;
+    if changes
+        .uid_validity
+        .is_some_and(|remote| remote != uid_validity)
+    {
+        debug!(
+            "Account {}: Mailbox '{}' UIDVALIDITY changed, skipping QRESYNC.",
+            account.id, mailbox.name
+        );
+        return Ok(MailboxChanges::default());
+    }
+
+    // Messages above highest_uid are not archived yet; the caller downloads them.
+    let new_mail = changes.changed.keys().any(|uid| *uid > highest_uid);
+    changes.changed.retain(|uid, _| *uid <= highest_uid);
+    let changed = changes.changed.len();
+    let updated = ENVELOPE_MANAGER
+        .update_envelope_flags(account.id, mailbox.id, changes.changed)
+        .await?;
+    let deleted = ENVELOPE_MANAGER
+        .mark_deleted_on_source(account.id, mailbox.id, &changes.vanished)
+        .await?;
+    info!(
+        account_id = account.id,
+        mailbox = %mailbox.name,
+        changed,
+        updated,
+        vanished = changes.vanished.len(),
+        deleted,
+        new_mail,
+        "QRESYNC finished"
+    );
+    Ok(MailboxChanges {
+        new_mail: changes.highest_modseq.map(|_| new_mail),
+        highest_modseq: changes.highest_modseq,
+    })
+}
+
+/// UIDs per `X-GM-LABELS` request when sweeping a whole mailbox.
+const GMAIL_SWEEP_CHUNK: u32 = 5000;
+
+/// Refreshes the Gmail labels and message IDs of a mailbox synced in Gmail
+/// mode. Messages above `known_uid` (downloaded in this run) are always
+/// fetched; older ones only if their MODSEQ moved past `known_modseq`, or all
+/// of them when there is none yet. Returns the number of updated envelopes.
+pub async fn sync_gmail_labels(
+    account: &AccountModel,
+    mailbox: &MailBox,
+    known_uid: Option<u32>,
+    highest_uid: Option<u32>,
+    known_modseq: Option<u64>,
+) -> BichonResult<usize> {
+    let Some(highest_uid) = highest_uid.filter(|uid| *uid > 0) else {
+        return Ok(0);
+    };
+    let known_uid = known_uid.unwrap_or(0).min(highest_uid);
+
+    let mut session = ImapExecutor::create_connection(account.id).await?;
+    let result = async {
+        session
+            .examine(mailbox.encoded_name())
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::ImapCommandFailed))?;
+
+        let mut attributes = HashMap::new();
+        for uid_set in uid_chunks(known_uid + 1, highest_uid) {
+            attributes
+                .extend(ImapExecutor::fetch_gmail_attributes(&mut session, &uid_set, None).await?);
+        }
+        if known_uid > 0 {
+            match known_modseq {
+                Some(modseq) => attributes.extend(
+                    ImapExecutor::fetch_gmail_attributes(
+                        &mut session,
+                        &format!("1:{known_uid}"),
+                        Some(modseq),
+                    )
+                    .await?,
+                ),
+                None => {
+                    for uid_set in uid_chunks(1, known_uid) {
+                        attributes.extend(
+                            ImapExecutor::fetch_gmail_attributes(&mut session, &uid_set, None)
+                                .await?,
+                        );
+                    }
+                }
+            }
+        }
+
+        let fetched = attributes.len();
+        let updated = ENVELOPE_MANAGER
+            .update_gmail_attributes(account.id, mailbox.id, attributes)
+            .await?;
+        info!(
+            account_id = account.id,
+            mailbox = %mailbox.name,
+            fetched,
+            updated,
+            "Gmail label refresh finished"
+        );
+        Ok(updated)
+    }
+    .await;
+    session.logout().await.ok();
+    result
+}
+
+/// Splits `from..=to` into UID sequence-sets of at most [`GMAIL_SWEEP_CHUNK`].
+fn uid_chunks(from: u32, to: u32) -> impl Iterator<Item = String> {
+    (from..=to)
+        .step_by(GMAIL_SWEEP_CHUNK as usize)
+        .map(move |start| {
+            format!(
+                "{}:{}",
+                start,
+                start.saturating_add(GMAIL_SWEEP_CHUNK - 1).min(to)
+            )
+        })
+}
+
+/// Refreshes flags via CONDSTORE `CHANGEDSINCE` or a full FLAGS sweep.
+/// Returns the `HIGHESTMODSEQ` to store with the mailbox.
+async fn refresh_flags(
+    session: &mut Session<Box<dyn SessionStream>>,
+    account: &AccountModel,
+    mailbox: &MailBox,
+    highest_uid: u32,
+    condstore: bool,
+    token: CancellationToken,
+) -> BichonResult<Option<u64>> {
+    let selected = if condstore {
+        session.select_condstore(mailbox.encoded_name()).await
+    } else {
+        session.examine(mailbox.encoded_name()).await
+    }
+    .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::ImapCommandFailed))?;
+
+    if let (Some(remote), Some(local)) = (selected.uid_validity, mailbox.uid_validity) {
+        if remote != local {
+            debug!(
+                "Account {}: Mailbox '{}' UIDVALIDITY changed, skipping flag refresh.",
+                account.id, mailbox.name
+            );
+            return Ok(None);
+        }
+    }
+
+    let remote_modseq = selected.highest_modseq.filter(|_| condstore);
+    let query = match (remote_modseq, mailbox.highest_modseq) {
+        (Some(remote), Some(local)) if remote <= local => {
+            debug!(
+                "Account {}: Mailbox '{}' unchanged since MODSEQ {}, skipping flag refresh.",
+                account.id, mailbox.name, local
+            );
+            return Ok(Some(remote));
+        }
+        (Some(_), Some(local)) => format!("(UID FLAGS) (CHANGEDSINCE {local})"),
+        _ => "(UID FLAGS)".to_string(),
+    };
+
+    let flags_by_uid =
+        ImapExecutor::fetch_uid_flags(session, &format!("1:{highest_uid}"), &query, token).await?;
+    let fetched = flags_by_uid.len();
+    let updated = ENVELOPE_MANAGER
+        .update_envelope_flags(account.id, mailbox.id, flags_by_uid)
+        .await?;
+    info!(
+        account_id = account.id,
+        mailbox = %mailbox.name,
+        condstore = remote_modseq.is_some(),
+        fetched,
+        updated,
+        "flag refresh finished"
+    );
+    Ok(remote_modseq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uid_chunks_cover_the_range() {
+        assert_eq!(uid_chunks(1, 3).collect::<Vec<_>>(), vec!["1:3"]);
+        assert_eq!(uid_chunks(4, 3).count(), 0);
+        assert_eq!(
+            uid_chunks(1, 12000).collect::<Vec<_>>(),
+            vec!["1:5000", "5001:10000", "10001:12000"]
+        );
+    }
+}
@@ -63,7 +63,7 @@ pub async fn get_download_folders(
     )
     .await?;
     let account = AccountModel::get(account.id)?;
-    let subscribed = &account.download_folders.unwrap_or_default();
+    let subscribed = account.download_folders.as_deref().unwrap_or_default();
     let is_noselect = |mailbox: &MailBox| {
         mailbox
             .attributes
@@ -71,6 +71,31 @@ pub async fn get_download_folders(
             .any(|attr| matches!(attr.attr, AttributeEnum::NoSelect))
     };
 
+    if account.gmail_sync_enabled() {
+        // Every Gmail message is in All Mail; label folders only hold copies.
+        let all_mail = mailboxes.iter().find(|(mailbox, _)| {
+            !is_noselect(mailbox)
+                && mailbox
+                    .attributes
+                    .iter()
+                    .any(|attr| matches!(attr.attr, AttributeEnum::All))
+        });
+        match all_mail {
+            Some((mailbox, name)) => {
+                info!(
+                    "Account {}: Gmail sync mode, downloading only '{}'",
+                    account.id, mailbox.name
+                );
+                return convert_names_to_mailboxes(account.id, session, vec![name]).await;
+            }
+            None => warn!(
+                "Account {}: Gmail sync mode is on but no \\All mailbox was found; \
+                using the folder selection instead.",
+                account.id
+            ),
+        }
+    }
+
     let is_default_mailbox = |mailbox: &MailBox| {
         mailbox.name.eq_ignore_ascii_case("INBOX")
             || mailbox
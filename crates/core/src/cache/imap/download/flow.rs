@@ -25,7 +25,10 @@ use crate::{
         },
         cache::{
             imap::{
-                download::rebuild::{rebuild_mailbox_cache, rebuild_mailbox_cache_by_date},
+                download::{
+                    changes::{sync_gmail_labels, sync_mailbox_changes, MailboxChanges},
+                    rebuild::{rebuild_mailbox_cache, rebuild_mailbox_cache_by_date},
+                },
                 find_intersecting_mailboxes, find_missing_mailboxes,
                 mailbox::MailBox,
             },
@@ -837,18 +840,69 @@ pub async fn reconcile_mailboxes(
                 }
             };
 
-            let new_highest_uid = if local_mailbox.uid_validity != Some(remote_uid_validity) {
+            let (new_highest_uid, highest_modseq) = if local_mailbox.uid_validity
+                != Some(remote_uid_validity)
+            {
                 info!(
                     "Account {}: Mailbox '{}' detected with changed uid_validity (local: {:#?}, remote: {:#?}). \
                     Comparing by Message-ID to find missing emails.",
                     account_id, local_mailbox.name, &local_mailbox.uid_validity, &remote_uid_validity
                 );
 
-                reconcile_uid_validity_change(account, local_mailbox, remote_mailbox, token.clone())
-                    .await?
+                let new_highest_uid = reconcile_uid_validity_change(
+                    account,
+                    local_mailbox,
+                    remote_mailbox,
+                    token.clone(),
+                )
+                .await?;
+                (new_highest_uid, None)
             } else {
-                perform_incremental_sync(account, local_mailbox, remote_mailbox, token.clone())
-                    .await?
+                // Flags and server-side expunges of already archived messages;
+                // a failure here must not fail the sync, the next run retries.
+                let changes =
+                    match sync_mailbox_changes(account, local_mailbox, token.clone()).await {
+                        Ok(changes) => changes,
+                        Err(err) => {
+                            warn!(
+                                "Account {}: Mailbox '{}' - change sync failed: {:#?}",
+                                account_id, local_mailbox.name, err
+                            );
+                            MailboxChanges {
+                                highest_modseq: local_mailbox.highest_modseq,
+                                new_mail: None,
+                            }
+                        }
+                    };
+                // QRESYNC already told us whether anything above highest_uid exists.
+                let new_highest_uid = if changes.new_mail == Some(false)
+                    && local_mailbox.highest_uid.is_some()
+                {
+                    local_mailbox.highest_uid
+                } else {
+                    perform_incremental_sync(account, local_mailbox, remote_mailbox, token.clone())
+                        .await?
+                };
+                let mut highest_modseq = changes.highest_modseq;
+                if account.gmail_sync_enabled() {
+                    if let Err(err) = sync_gmail_labels(
+                        account,
+                        local_mailbox,
+                        local_mailbox.highest_uid,
+                        new_highest_uid,
+                        local_mailbox.highest_modseq,
+                    )
+                    .await
+                    {
+                        warn!(
+                            "Account {}: Mailbox '{}' - Gmail label refresh failed: {:#?}",
+                            account_id, local_mailbox.name, err
+                        );
+                        // Keep the old MODSEQ so the next run asks for these label changes again.
+                        highest_modseq = local_mailbox.highest_modseq;
+                    }
+                }
+                (new_highest_uid, highest_modseq)
             };
             info!(
                 account_id,
@@ -859,6 +913,7 @@ pub async fn reconcile_mailboxes(
             );
             let mut updated = remote_mailbox.clone();
             updated.highest_uid = new_highest_uid;
+            updated.highest_modseq = highest_modseq;
             // Update uid_validity with the resolved value (either from server or synthetic)
             if updated.uid_validity.is_none() {
                 updated.uid_validity = Some(remote_uid_validity);
@@ -971,6 +1026,58 @@ pub async fn reconcile_mailboxes(
     Ok(())
 }
 
+/// Incrementally syncs one already downloaded mailbox outside of a regular
+/// download run, e.g. when an IDLE watcher is told about new mail.
+/// A changed UIDVALIDITY is left to the next regular sync, which knows how
+/// to reconcile it.
+pub async fn sync_single_mailbox(
+    account: &AccountModel,
+    mailbox_name: &str,
+    token: CancellationToken,
+) -> BichonResult<()> {
+    let local_mailbox = match MailBox::list_all(account.id)?
+        .into_iter()
+        .find(|m| m.name == mailbox_name)
+    {
+        Some(mailbox) => mailbox,
+        None => {
+            debug!(
+                "Account {}: Mailbox '{}' has not been downloaded yet, leaving it to the regular sync.",
+                account.id, mailbox_name
+            );
+            return Ok(());
+        }
+    };
+
+    let mut session = ImapExecutor::create_connection(account.id).await?;
+    let status = session
+        .status(
+            local_mailbox.encoded_name().as_str(),
+            "(MESSAGES UNSEEN UIDNEXT UIDVALIDITY)",
+        )
+        .await
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::ImapCommandFailed))?;
+    session.logout().await.ok();
+
+    if status.uid_validity.is_none() || status.uid_validity != local_mailbox.uid_validity {
+        debug!(
+            "Account {}: Mailbox '{}' UIDVALIDITY differs from the local state, leaving it to the regular sync.",
+            account.id, mailbox_name
+        );
+        return Ok(());
+    }
+
+    let mut remote_mailbox = local_mailbox.clone();
+    remote_mailbox.exists = status.exists;
+    remote_mailbox.unseen = status.unseen;
+    remote_mailbox.uid_next = status.uid_next;
+
+    let new_highest_uid =
+        perform_incremental_sync(account, &local_mailbox, &remote_mailbox, token).await?;
+    remote_mailbox.highest_uid = new_highest_uid;
+    MailBox::batch_upsert(&[remote_mailbox])
+}
+
 //only check new emails and sync
 /// Incrementally syncs a mailbox.
 /// Returns the new highest UID after sync, or `None` if nothing changed.
@@ -1678,4 +1785,102 @@ mod tests {
 
         session.logout().await.ok();
     }
+
+    /// More changes than the session's unsolicited channel holds (100) must
+    /// all come back, along with the VANISHED UIDs and the new MODSEQ.
+    #[tokio::test]
+    async fn examine_qresync_collects_every_change() {
+        let mut qresync = String::from(
+            "* FLAGS (\\Seen \\Flagged)\r\n\
+* OK [UIDVALIDITY 42]\r\n\
+* OK [HIGHESTMODSEQ 900]\r\n\
+* VANISHED (EARLIER) 300:302\r\n",
+        );
+        for uid in 1..=150 {
+            qresync.push_str(&format!(
+                "* {uid} FETCH (UID {uid} FLAGS (\\Seen) MODSEQ (800))\r\n"
+            ));
+        }
+        qresync.push_str("{TAG} OK [READ-ONLY] EXAMINE completed\r\n");
+
+        let handle = MockImapServer::new()
+            .respond("LOGIN", "{TAG} OK LOGIN done\r\n")
+            .respond("ENABLE", "{TAG} OK ENABLE completed\r\n")
+            .respond("(QRESYNC", qresync.into_bytes())
+            .respond("EXAMINE", examine_response("INBOX", 150, 42, 151))
+            .start()
+            .await;
+
+        let mut session = mock_session(&handle).await;
+
+        let changes = ImapExecutor::examine_qresync(&mut session, "INBOX", 42, 700)
+            .await
+            .unwrap();
+
+        assert_eq!(changes.uid_validity, Some(42));
+        assert_eq!(changes.highest_modseq, Some(900));
+        assert_eq!(changes.changed.len(), 150);
+        assert_eq!(changes.changed[&150], vec!["\\Seen".to_string()]);
+        assert_eq!(changes.vanished, vec![300, 301, 302]);
+
+        session.logout().await.ok();
+    }
+
+    /// A tagged NO must fail the call so the stored MODSEQ is left alone.
+    #[tokio::test]
+    async fn examine_qresync_fails_on_tagged_no() {
+        let handle = MockImapServer::new()
+            .respond("LOGIN", "{TAG} OK LOGIN done\r\n")
+            .respond("ENABLE", "{TAG} OK ENABLE completed\r\n")
+            .respond(
+                "(QRESYNC",
+                "* OK [HIGHESTMODSEQ 900]\r\n{TAG} NO [CANNOT] QRESYNC failed\r\n",
+            )
+            .respond("EXAMINE", examine_response("INBOX", 1, 42, 2))
+            .start()
+            .await;
+
+        let mut session = mock_session(&handle).await;
+
+        assert!(
+            ImapExecutor::examine_qresync(&mut session, "INBOX", 42, 700)
+                .await
+                .is_err()
+        );
+
+        session.logout().await.ok();
+    }
+
+    /// A full Gmail sweep chunk answers with far more FETCH responses than
+    /// the unsolicited channel holds; none may be lost.
+    #[tokio::test]
+    async fn fetch_gmail_attributes_collects_every_message() {
+        let mut fetch = String::new();
+        for uid in 1..=250u32 {
+            fetch.push_str(&format!(
+                "* {uid} FETCH (UID {uid} X-GM-MSGID {} X-GM-LABELS (\\Inbox \"Work\"))\r\n",
+                1_000_000 + uid as u64
+            ));
+        }
+        fetch.push_str("{TAG} OK FETCH completed\r\n");
+
+        let handle = MockImapServer::new()
+            .respond("LOGIN", "{TAG} OK LOGIN done\r\n")
+            .respond("EXAMINE", examine_response("INBOX", 250, 42, 251))
+            .respond("X-GM-LABELS", fetch.into_bytes())
+            .start()
+            .await;
+
+        let mut session = mock_session(&handle).await;
+
+        let result = ImapExecutor::fetch_gmail_attributes(&mut session, "1:250", Some(700))
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 250);
+        assert_eq!(result[&250].msg_id, Some(1_000_250));
+        assert!(result[&250].labels.contains(&"Work".to_string()));
+
+        session.logout().await.ok();
+    }
 }
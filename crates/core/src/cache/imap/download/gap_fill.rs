@@ -378,6 +378,7 @@ pub async fn gap_fill_mailbox(
         if let Some(max_uid) = missing_uids.last().copied() {
             let mut updated = remote_mailbox.clone();
             updated.highest_uid = Some(max_uid.max(local_mailbox.highest_uid.unwrap_or(0)));
+            updated.highest_modseq = local_mailbox.highest_modseq;
             crate::cache::imap::mailbox::MailBox::batch_upsert(&[updated])?;
         }
     }
@@ -36,6 +36,7 @@ use std::time::Instant;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
+pub mod changes;
 pub mod download_folders;
 pub mod download_type;
 pub mod gap_fill;
@@ -0,0 +1,283 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Push ingestion with IMAP IDLE (RFC 2177).
+//!
+//! Accounts with `idle_folders` get one long-lived session per listed
+//! mailbox. Whenever the server announces new messages (`EXISTS`) the
+//! mailbox is synced right away instead of waiting for the next polling
+//! interval. Regular polling keeps running, so a server without the IDLE
+//! capability or a watcher that keeps failing only costs latency.
+
+use crate::account::migration::{AccountModel, AccountType};
+use crate::cache::imap::download::flow::sync_single_mailbox;
+use crate::cache::imap::task::SYNC_TASKS;
+use crate::encode_mailbox_name;
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::imap::capabilities::{fetch_capabilities, supports_idle};
+use crate::imap::executor::ImapExecutor;
+use crate::raise_error;
+use async_imap::extensions::idle::IdleResponse;
+use imap_proto::{MailboxDatum, Response};
+use std::collections::HashMap;
+use std::{sync::LazyLock, time::Duration};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+pub static IDLE_WATCHERS: LazyLock<IdleWatchers> = LazyLock::new(IdleWatchers::default);
+
+/// RFC 2177 asks clients to re-issue IDLE at least every 29 minutes.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(29 * 60);
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+/// How often a push sync retries while a regular sync holds the account.
+const BUSY_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const BUSY_RETRIES: u32 = 12;
+
+enum WatchEnd {
+    Cancelled,
+    Unsupported,
+}
+
+/// The watcher tasks of an account and the token stopping them.
+type AccountWatchers = (Vec<JoinHandle<()>>, CancellationToken);
+
+#[derive(Default)]
+pub struct IdleWatchers {
+    watchers: Mutex<HashMap<u64, AccountWatchers>>,
+}
+
+impl IdleWatchers {
+    /// Start (or restart) the watchers of an account from its current
+    /// `idle_folders`. Disabled accounts and accounts without IDLE folders
+    /// end up with no watcher.
+    pub async fn restart(&self, account_id: u64) {
+        self.stop(account_id).await;
+        let account = match AccountModel::get(account_id) {
+            Ok(account) => account,
+            Err(e) => {
+                error!(
+                    "Account {}: cannot start IDLE watchers: {:?}",
+                    account_id, e
+                );
+                return;
+            }
+        };
+        if account.account_type != AccountType::IMAP || !account.enabled || account.deleting {
+            return;
+        }
+        let folders = account.idle_folders.unwrap_or_default();
+        if folders.is_empty() {
+            return;
+        }
+
+        let token = CancellationToken::new();
+        let handles = folders
+            .into_iter()
+            .map(|folder| tokio::spawn(watch_mailbox(account_id, folder, token.clone())))
+            .collect();
+        let previous = self
+            .watchers
+            .lock()
+            .await
+            .insert(account_id, (handles, token));
+        // A concurrent restart may have raced us; never leave orphans behind.
+        if let Some((_, token)) = previous {
+            token.cancel();
+        }
+    }
+
+    pub async fn stop(&self, account_id: u64) {
+        let removed = self.watchers.lock().await.remove(&account_id);
+        if let Some((handles, token)) = removed {
+            token.cancel();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        }
+    }
+
+    pub async fn shutdown(&self) {
+        let watchers: Vec<_> = self.watchers.lock().await.drain().collect();
+        for (account_id, (handles, token)) in watchers {
+            token.cancel();
+            for handle in handles {
+                if tokio::time::timeout(Duration::from_secs(5), handle)
+                    .await
+                    .is_err()
+                {
+                    warn!(
+                        "Shutdown: Account {} IDLE watcher forced timeout.",
+                        account_id
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Keep an IDLE session open on one mailbox, reconnecting with exponential
+/// backoff until cancelled.
+async fn watch_mailbox(account_id: u64, mailbox: String, token: CancellationToken) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match idle_session(account_id, &mailbox, &token, &mut backoff).await {
+            Ok(WatchEnd::Cancelled) => return,
+            Ok(WatchEnd::Unsupported) => {
+                info!(
+                    "Account {}: Server does not support IDLE, '{}' stays on polling.",
+                    account_id, mailbox
+                );
+                return;
+            }
+            Err(e) => {
+                warn!(
+                    "Account {}: IDLE on '{}' failed, reconnecting in {}s: {:?}",
+                    account_id,
+                    mailbox,
+                    backoff.as_secs(),
+                    e
+                );
+            }
+        }
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn idle_session(
+    account_id: u64,
+    mailbox: &str,
+    token: &CancellationToken,
+    backoff: &mut Duration,
+) -> BichonResult<WatchEnd> {
+    let mut session = ImapExecutor::create_connection(account_id).await?;
+    if !supports_idle(&fetch_capabilities(&mut session).await?) {
+        session.logout().await.ok();
+        return Ok(WatchEnd::Unsupported);
+    }
+    session
+        .examine(encode_mailbox_name!(mailbox))
+        .await
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::ImapCommandFailed))?;
+    debug!("Account {}: IDLE started on '{}'.", account_id, mailbox);
+    *backoff = MIN_BACKOFF;
+    // Catch up on anything that arrived while the watcher was down.
+    push_sync(account_id, mailbox, token).await;
+
+    loop {
+        let mut handle = session.idle();
+        handle
+            .init()
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::ImapCommandFailed))?;
+        let (wait, _interrupt) = handle.wait_with_timeout(IDLE_TIMEOUT);
+        let response = tokio::select! {
+            response = wait => response
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::NetworkError))?,
+            _ = token.cancelled() => IdleResponse::ManualInterrupt,
+        };
+        session = handle
+            .done()
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::ImapCommandFailed))?;
+
+        match response {
+            IdleResponse::ManualInterrupt => {
+                session.logout().await.ok();
+                return Ok(WatchEnd::Cancelled);
+            }
+            IdleResponse::Timeout => {}
+            IdleResponse::NewData(data) => {
+                if announces_new_mail(data.parsed()) {
+                    push_sync(account_id, mailbox, token).await;
+                }
+            }
+        }
+    }
+}
+
+/// Only `EXISTS` means new messages; expunges and flag changes are left to
+/// the regular sync.
+fn announces_new_mail(response: &Response) -> bool {
+    matches!(response, Response::MailboxData(MailboxDatum::Exists(_)))
+}
+
+/// Sync `mailbox` now, waiting briefly if a regular sync of the account is
+/// running. If it stays busy, the regular sync will pick the mail up.
+async fn push_sync(account_id: u64, mailbox: &str, token: &CancellationToken) {
+    let mut attempts = 0;
+    while !SYNC_TASKS.try_set_busy(account_id).await {
+        attempts += 1;
+        if attempts > BUSY_RETRIES {
+            debug!(
+                "Account {}: Push sync of '{}' skipped (regular sync still active).",
+                account_id, mailbox
+            );
+            return;
+        }
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(BUSY_RETRY_INTERVAL) => {}
+        }
+    }
+    let _busy_guard = scopeguard::guard(account_id, |id| {
+        tokio::spawn(async move {
+            SYNC_TASKS.set_busy(id, false).await;
+        });
+    });
+
+    let result = match AccountModel::get(account_id) {
+        Ok(account) if account.enabled && !account.deleting => {
+            sync_single_mailbox(&account, mailbox, token.clone()).await
+        }
+        Ok(_) => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        error!(
+            "Account {}: Push sync of '{}' failed: {:?}",
+            account_id, mailbox, e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &[u8]) -> Response<'_> {
+        imap_proto::parser::parse_response(line).unwrap().1
+    }
+
+    #[test]
+    fn only_exists_triggers_a_sync() {
+        assert!(announces_new_mail(&parse(b"* 23 EXISTS\r\n")));
+        assert!(!announces_new_mail(&parse(b"* 4 EXPUNGE\r\n")));
+        assert!(!announces_new_mail(&parse(b"* 1 RECENT\r\n")));
+        assert!(!announces_new_mail(&parse(
+            b"* 12 FETCH (FLAGS (\\Seen))\r\n"
+        )));
+    }
+}
@@ -60,6 +60,10 @@ pub struct MailBox {
     /// Used for incremental sync: next fetch starts from `highest_uid + 1`.
     /// If `None`, a fallback query against the Tantivy index will be performed once.
     pub highest_uid: Option<u32>,
+    /// The mailbox `HIGHESTMODSEQ` (RFC 7162) seen by the last flag refresh.
+    /// Only set for servers supporting CONDSTORE; the next refresh then asks
+    /// for flags changed since this value instead of sweeping the mailbox.
+    pub highest_modseq: Option<u64>,
 }
 
 impl MemDbModel for MailBox {
@@ -17,11 +17,15 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::account::entity::AuthType;
+use crate::account::migration::AccountType;
 use crate::account::state::{DownloadState, TriggerType};
 use crate::cache::imap::download::process_imap_download;
 use crate::common::periodic::{PeriodicTask, TaskHandle};
 use crate::error::code::ErrorCode;
+use crate::graph::download::process_graph_download;
+use crate::jmap::download::process_jmap_download;
 use crate::oauth2::token::OAuth2AccessToken;
+use crate::pop3::download::process_pop3_download;
 use crate::{account::migration::AccountModel, error::BichonResult};
 use crate::{raise_error, utc_now};
 use std::collections::{HashMap, HashSet};
@@ -38,6 +42,22 @@ pub static SYNC_TASKS: LazyLock<AccountDownTask> = LazyLock::new(AccountDownTask
 static LAST_WARN_TIME: AtomicI64 = AtomicI64::new(0);
 const WARN_INTERVAL_MS: i64 = 600_000;
 
+/// Runs one download of the account with the protocol its type uses.
+/// Gap-fill is IMAP-only.
+async fn process_download(
+    account: &AccountModel,
+    token: CancellationToken,
+    trigger_type: TriggerType,
+    run_gap_fill: bool,
+) -> BichonResult<()> {
+    match account.account_type {
+        AccountType::POP3 => process_pop3_download(account, token, trigger_type).await,
+        AccountType::Graph => process_graph_download(account, token, trigger_type).await,
+        AccountType::JMAP => process_jmap_download(account, token, trigger_type).await,
+        _ => process_imap_download(account, token, trigger_type, run_gap_fill).await,
+    }
+}
+
 pub struct AccountDownTask {
     tasks: Mutex<Option<HashMap<u64, (TaskHandle, CancellationToken)>>>,
     manual_tasks: Mutex<HashMap<u64, (JoinHandle<()>, CancellationToken)>>,
@@ -53,7 +73,7 @@ impl AccountDownTask {
         }
     }
 
-    async fn set_busy(&self, account_id: u64, is_busy: bool) {
+    pub(crate) async fn set_busy(&self, account_id: u64, is_busy: bool) {
         let mut guard = self.busy_accounts.lock().await;
         if is_busy {
             guard.insert(account_id);
@@ -64,7 +84,7 @@ impl AccountDownTask {
 
     /// Atomically check and set busy. Returns true if we claimed the slot,
     /// false if another task is already busy on this account.
-    async fn try_set_busy(&self, account_id: u64) -> bool {
+    pub(crate) async fn try_set_busy(&self, account_id: u64) -> bool {
         let mut guard = self.busy_accounts.lock().await;
         if guard.contains(&account_id) {
             false
@@ -127,17 +147,18 @@ impl AccountDownTask {
                                 );
                             }
                         } else {
-                            if let Some(imap) = &account.imap {
-                                if let AuthType::OAuth2 = imap.auth.auth_type {
-                                    if OAuth2AccessToken::get(account.id)?.is_none() {
-                                        if utc_now!() % 300_000 == 0 {
-                                            warn!("Account {}: download aborted. OAuth2 authorization not completed. Please visit the rustmailer admin page to authorize this account.", account_id);
-                                        }
-                                        return Ok(());
-                                    }
+                            let uses_oauth2 = matches!(account.account_type, AccountType::Graph)
+                                || account
+                                    .imap
+                                    .as_ref()
+                                    .is_some_and(|imap| imap.auth.auth_type == AuthType::OAuth2);
+                            if uses_oauth2 && OAuth2AccessToken::get(account.id)?.is_none() {
+                                if utc_now!() % 300_000 == 0 {
+                                    warn!("Account {}: download aborted. OAuth2 authorization not completed. Please visit the rustmailer admin page to authorize this account.", account_id);
                                 }
+                                return Ok(());
                             }
-                            if let Err(e) = process_imap_download(
+                            if let Err(e) = process_download(
                                 &account,
                                 internal_token,
                                 TriggerType::Scheduled,
@@ -255,8 +276,7 @@ impl AccountDownTask {
             }
 
             if let Err(e) =
-                process_imap_download(&account, token_clone, TriggerType::Manual, run_gap_fill)
-                    .await
+                process_download(&account, token_clone, TriggerType::Manual, run_gap_fill).await
             {
                 error!("Manual download failed for {}: {:?}", account_id, e);
                 let error_msg = format!("error in account download task: {:#?}", e);
@@ -6,6 +6,7 @@ use std::{
 use crate::{
     error::{code::ErrorCode, BichonResult},
     raise_error,
+    token::TokenScope,
     users::{permissions::Permission, role::UserRole, UserModel},
 };
 
@@ -13,6 +14,8 @@ use crate::{
 pub struct ClientContext {
     pub ip_addr: Option<IpAddr>,
     pub user: UserModel,
+    /// Limits of the token used for this request.
+    pub scope: TokenScope,
 }
 
 impl ClientContext {
@@ -40,7 +43,7 @@ impl ClientContext {
             return true;
         }
 
-        let mut global_perms = HashSet::new();
+        let mut global_perms = BTreeSet::new();
         for rid in &user.global_roles {
             if let Some(role) = UserRole::find(*rid).ok().flatten() {
                 global_perms.extend(role.permissions);
@@ -67,37 +70,72 @@ impl ClientContext {
     }
 
     pub fn has_permission(&self, account_id: Option<u64>, permission: &str) -> bool {
-        if self.user.is_admin() {
-            return true;
+        self.scope_allows(account_id, permission)
+            && Self::check_has_permission(&self.user, account_id, permission)
+    }
+
+    /// Whether the token scope lets `permission` through. Account-scoped
+    /// tokens cannot perform global (account-less) operations.
+    fn scope_allows(&self, account_id: Option<u64>, permission: &str) -> bool {
+        if !self.scope_grants(permission) {
+            return false;
+        }
+        match (&self.scope.account_ids, account_id) {
+            (None, _) => true,
+            (Some(ids), Some(aid)) => ids.contains(&aid),
+            (Some(_), None) => false,
         }
+    }
 
-        let mut global_perms = HashSet::new();
-        for rid in &self.user.global_roles {
-            if let Some(role) = UserRole::find(*rid).ok().flatten() {
-                global_perms.extend(role.permissions);
+    fn scope_grants(&self, permission: &str) -> bool {
+        match &self.scope.permissions {
+            None => true,
+            Some(perms) => {
+                Self::check_global_logic(perms, permission)
+                    || Self::check_account_logic(perms, permission)
             }
         }
+    }
 
-        if Self::check_global_logic(&global_perms, permission) {
-            return true;
+    /// Accounts the caller holds a role on, narrowed to the token's account
+    /// scope. Admins using an account-scoped token get the scoped accounts.
+    pub fn assigned_account_ids(&self) -> HashSet<u64> {
+        match &self.scope.account_ids {
+            Some(ids) if self.user.is_admin() => ids.iter().copied().collect(),
+            Some(ids) => self
+                .user
+                .account_access_map
+                .keys()
+                .filter(|aid| ids.contains(aid))
+                .copied()
+                .collect(),
+            None => self.user.account_access_map.keys().copied().collect(),
         }
+    }
 
-        if let Some(aid) = account_id {
-            if let Some(role_id) = self.user.account_access_map.get(&aid) {
-                if let Some(role) = UserRole::find(*role_id).ok().flatten() {
-                    if role.permissions.contains(&permission.to_string())
-                        || Self::check_account_logic(&role.permissions, permission)
-                    {
-                        return true;
-                    }
-                }
+    /// Accounts a cross-account query may cover, or `None` when
+    /// `all_permission` grants every account. `permission` is the
+    /// per-account right the query needs, checked against the token scope.
+    pub fn authorized_account_ids(
+        &self,
+        all_permission: &str,
+        permission: &str,
+    ) -> Option<HashSet<u64>> {
+        if self.has_permission(None, all_permission) {
+            return None;
+        }
+        if !self.scope_grants(permission) {
+            return Some(HashSet::new());
+        }
+        match &self.scope.account_ids {
+            Some(ids) if Self::check_has_permission(&self.user, None, all_permission) => {
+                Some(ids.iter().copied().collect())
             }
+            _ => Some(self.assigned_account_ids()),
         }
-
-        false
     }
 
-    fn check_global_logic(global: &HashSet<String>, perm: &str) -> bool {
+    fn check_global_logic(global: &BTreeSet<String>, perm: &str) -> bool {
         if global.contains(perm) {
             return true;
         }
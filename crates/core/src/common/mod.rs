@@ -21,6 +21,8 @@ use std::ops::Deref;
 use mail_parser::{Addr as ImapAddr, Address as ImapAddress};
 use serde::{Deserialize, Serialize};
 pub mod auth;
+#[cfg(test)]
+pub mod mock_http;
 pub mod paginated;
 pub mod periodic;
 pub mod rustls;
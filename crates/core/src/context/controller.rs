@@ -16,7 +16,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{cache::imap::task::SYNC_TASKS, error::BichonResult};
+use crate::{
+    cache::imap::{idle::IDLE_WATCHERS, task::SYNC_TASKS},
+    error::BichonResult,
+};
 use std::{sync::LazyLock, time::Duration};
 use tokio::sync::mpsc;
 use tracing::{error, info};
@@ -65,6 +68,7 @@ impl DownloadController {
             account_id, email
         );
         SYNC_TASKS.start_download_task(account_id, email).await;
+        IDLE_WATCHERS.restart(account_id).await;
         tokio::time::sleep(Duration::from_millis(100)).await;
         Ok(())
     }
@@ -16,7 +16,6 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::account::migration::AccountType;
 use crate::account::state::{DownloadState, GapFillState};
 use crate::context::Initialize;
 use crate::{
@@ -36,6 +35,10 @@ pub struct BichonContext {
 
 impl Initialize for BichonContext {
     async fn initialize() -> BichonResult<()> {
+        let encrypted = AccountModel::encrypt_legacy_pgp_keys()?;
+        if encrypted > 0 {
+            info!("Encrypted {} plaintext OpenPGP keys at rest", encrypted);
+        }
         BICHON_CONTEXT.start_account_downloader().await
     }
 }
@@ -54,7 +57,7 @@ impl BichonContext {
         let accounts = AccountModel::list_all()?;
         let active_accounts: Vec<AccountModel> = accounts
             .into_iter()
-            .filter(|a| a.enabled && matches!(a.account_type, AccountType::IMAP))
+            .filter(|a| a.enabled && a.is_downloadable())
             .collect();
 
         if active_accounts.is_empty() {
@@ -62,7 +65,7 @@ impl BichonContext {
             return Ok(());
         }
         info!(
-            "System has {} active IMAP accounts to initialize.",
+            "System has {} active IMAP/POP3 accounts to initialize.",
             active_accounts.len()
         );
         for account in active_accounts {
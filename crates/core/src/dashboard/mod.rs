@@ -62,12 +62,8 @@ pub struct DashboardStats {
 
 impl DashboardStats {
     pub async fn get(context: ClientContext) -> BichonResult<Self> {
-        let has_all_accounts = context.has_permission(None, Permission::ACCOUNT_MANAGE_ALL);
-        let authorized_ids: Option<HashSet<u64>> = if has_all_accounts {
-            None
-        } else {
-            Some(context.user.account_access_map.keys().cloned().collect())
-        };
+        let authorized_ids: Option<HashSet<u64>> =
+            context.authorized_account_ids(Permission::ACCOUNT_MANAGE_ALL, Permission::DATA_READ);
 
         let mut stat = ENVELOPE_MANAGER.get_dashboard_stats(&authorized_ids)?;
 
@@ -75,10 +71,9 @@ impl DashboardStats {
         stat.top_largest_attachments =
             ATTACHMENT_MANAGER.top_10_largest_attachments(&authorized_ids)?;
 
-        stat.account_count = if has_all_accounts {
-            AccountModel::count()?
-        } else {
-            authorized_ids.as_ref().map(|ids| ids.len()).unwrap_or(0)
+        stat.account_count = match authorized_ids.as_ref() {
+            None => AccountModel::count()?,
+            Some(ids) => ids.len(),
         };
 
         stat.email_count = ENVELOPE_MANAGER.total_emails(&authorized_ids)?;
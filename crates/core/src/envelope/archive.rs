@@ -0,0 +1,783 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Expansion of archive attachments (zip, tar, gzip, 7z).
+//!
+//! Archives are walked in memory at ingest so that every file inside them can
+//! be indexed as an attachment of its own, and walked again to serve one of
+//! those files for download. Nested archives are expanded too. Since the
+//! input is untrusted, a walk is bounded by [`ArchiveLimits`]: it stops at the
+//! first limit reached and keeps what it expanded up to there.
+//!
+//! Members are linked to the archive attachment they came from by a reserved
+//! facet under [`ARCHIVE_FACET_ROOT`] in the attachment's tag field.
+
+use std::io::{Cursor, Read};
+use std::ops::ControlFlow;
+
+use flate2::read::MultiGzDecoder;
+use sevenz_rust::{Archive, Password, SevenZMethod, SevenZReader};
+use tantivy::schema::Facet;
+use zip::ZipArchive;
+
+use crate::utils::compute_content_hash;
+
+/// Root of the facets linking archive members to their archive.
+pub const ARCHIVE_FACET_ROOT: &str = "/_archive";
+
+const ARCHIVE_ROOT_SEGMENT: &str = "_archive";
+
+/// Bounds on the expansion of one archive attachment.
+#[derive(Clone, Debug)]
+pub struct ArchiveLimits {
+    /// How many levels of archives are opened, the attachment itself being
+    /// the first.
+    pub max_depth: usize,
+    /// Members expanded, across all levels.
+    pub max_members: usize,
+    /// Uncompressed bytes expanded, across all levels.
+    pub max_expanded_bytes: u64,
+    /// A zip member that inflates more than this many times its compressed
+    /// size ends the walk.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_members: 1_000,
+            max_expanded_bytes: 256 * 1024 * 1024,
+            max_compression_ratio: 100,
+        }
+    }
+}
+
+/// Members smaller than this are exempt from the compression ratio check;
+/// small text files legitimately compress very well.
+const RATIO_CHECK_MIN_BYTES: u64 = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    Gzip,
+    SevenZ,
+}
+
+impl ArchiveFormat {
+    /// The archive format of an attachment, from its file name or, when the
+    /// name has no extension, its MIME type, and for unlabelled files their
+    /// leading bytes. Zip-based documents such as docx or odt are not
+    /// archives in this sense.
+    pub fn detect(file_type: &str, name: &str, bytes: &[u8]) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let ext = std::path::Path::new(&name)
+            .extension()
+            .and_then(|e| e.to_str());
+        if let Some(ext) = ext {
+            return match ext {
+                "zip" => Some(Self::Zip),
+                "tar" => Some(Self::Tar),
+                "gz" | "tgz" => Some(Self::Gzip),
+                "7z" => Some(Self::SevenZ),
+                _ => None,
+            };
+        }
+        match file_type.to_ascii_lowercase().as_str() {
+            "application/zip" | "application/x-zip-compressed" => return Some(Self::Zip),
+            "application/x-tar" => return Some(Self::Tar),
+            "application/gzip" | "application/x-gzip" => return Some(Self::Gzip),
+            "application/x-7z-compressed" => return Some(Self::SevenZ),
+            // Only an unlabelled file is sniffed.
+            "" | "application/octet-stream" => {}
+            _ => return None,
+        }
+        if bytes.starts_with(b"PK\x03\x04") {
+            Some(Self::Zip)
+        } else if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(b"7z\xbc\xaf\x27\x1c") {
+            Some(Self::SevenZ)
+        } else if is_tar(bytes) {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_tar(bytes: &[u8]) -> bool {
+    bytes.get(257..262) == Some(b"ustar")
+}
+
+/// Walks the members of an archive named `name`, calling `visit` with the
+/// path and content of each file, nested archives included. `visit` can
+/// end the walk early by returning [`ControlFlow::Break`].
+pub fn expand_archive(
+    format: ArchiveFormat,
+    name: &str,
+    bytes: &[u8],
+    limits: &ArchiveLimits,
+    mut visit: impl FnMut(&str, &[u8]) -> ControlFlow<()>,
+) {
+    let mut walker = Walker {
+        limits,
+        members: 0,
+        expanded: 0,
+        visit: &mut visit,
+    };
+    let _ = walker.archive(format, "", name, bytes, 1);
+}
+
+/// The path and content of the member of an archive whose content hash is
+/// `content_hash`, found by expanding the archive with the default limits,
+/// as at ingest.
+pub fn find_archive_member(
+    format: ArchiveFormat,
+    name: &str,
+    bytes: &[u8],
+    content_hash: &str,
+) -> Option<(String, Vec<u8>)> {
+    let mut found = None;
+    expand_archive(
+        format,
+        name,
+        bytes,
+        &ArchiveLimits::default(),
+        |path, data| {
+            if compute_content_hash(data) == content_hash {
+                found = Some((path.to_string(), data.to_vec()));
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        },
+    );
+    found
+}
+
+/// MIME type of an archive member, guessed from its file name.
+pub fn member_file_type(path: &str) -> String {
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+struct Walker<'a> {
+    limits: &'a ArchiveLimits,
+    members: usize,
+    expanded: u64,
+    visit: &'a mut dyn FnMut(&str, &[u8]) -> ControlFlow<()>,
+}
+
+impl Walker<'_> {
+    fn archive(
+        &mut self,
+        format: ArchiveFormat,
+        prefix: &str,
+        name: &str,
+        bytes: &[u8],
+        depth: usize,
+    ) -> ControlFlow<()> {
+        match format {
+            ArchiveFormat::Zip => self.zip(prefix, bytes, depth),
+            ArchiveFormat::Tar => self.tar(prefix, bytes, depth),
+            ArchiveFormat::Gzip => self.gzip(prefix, name, bytes, depth),
+            ArchiveFormat::SevenZ => self.seven_z(prefix, bytes, depth),
+        }
+    }
+
+    /// Reads one member, charging it to the limits. Breaks once a limit is
+    /// reached; an unreadable member reads as `None`.
+    fn read(&mut self, reader: impl Read) -> ControlFlow<(), Option<Vec<u8>>> {
+        if self.members >= self.limits.max_members {
+            return ControlFlow::Break(());
+        }
+        self.members += 1;
+        let remaining = self.limits.max_expanded_bytes.saturating_sub(self.expanded);
+        let mut data = Vec::new();
+        if reader.take(remaining + 1).read_to_end(&mut data).is_err() {
+            return ControlFlow::Continue(None);
+        }
+        if data.len() as u64 > remaining {
+            return ControlFlow::Break(());
+        }
+        self.expanded += data.len() as u64;
+        ControlFlow::Continue(Some(data))
+    }
+
+    /// Hands a member to the visitor and expands it if it is itself an
+    /// archive.
+    fn member(&mut self, prefix: &str, path: &str, data: &[u8], depth: usize) -> ControlFlow<()> {
+        let path = format!("{prefix}{}", path.trim_start_matches('/'));
+        (self.visit)(&path, data)?;
+        if depth < self.limits.max_depth {
+            let name = path.rsplit('/').next().unwrap_or(&path);
+            if let Some(format) = ArchiveFormat::detect("", name, data) {
+                self.archive(format, &format!("{path}/"), name, data, depth + 1)?;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn too_compressed(&self, size: u64, compressed_size: u64) -> bool {
+        size >= RATIO_CHECK_MIN_BYTES
+            && size / compressed_size.max(1) > self.limits.max_compression_ratio
+    }
+
+    fn zip(&mut self, prefix: &str, bytes: &[u8], depth: usize) -> ControlFlow<()> {
+        let Ok(mut archive) = ZipArchive::new(Cursor::new(bytes)) else {
+            return ControlFlow::Continue(());
+        };
+        for index in 0..archive.len() {
+            // Encrypted members and unsupported compression methods fail here.
+            let Ok(file) = archive.by_index(index) else {
+                continue;
+            };
+            if !file.is_file() {
+                continue;
+            }
+            // Checked on the declared size first, then on what was inflated,
+            // since the header can lie.
+            let compressed_size = file.compressed_size();
+            if self.too_compressed(file.size(), compressed_size) {
+                return ControlFlow::Break(());
+            }
+            let path = file.name().to_string();
+            let Some(data) = self.read(file)? else {
+                continue;
+            };
+            if self.too_compressed(data.len() as u64, compressed_size) {
+                return ControlFlow::Break(());
+            }
+            self.member(prefix, &path, &data, depth)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn tar(&mut self, prefix: &str, reader: impl Read, depth: usize) -> ControlFlow<()> {
+        let mut archive = tar::Archive::new(reader);
+        let Ok(entries) = archive.entries() else {
+            return ControlFlow::Continue(());
+        };
+        for entry in entries {
+            let Ok(entry) = entry else {
+                break;
+            };
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let Ok(path) = entry.path().map(|p| p.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            // A tar is a single stream; nothing after a bad member is readable.
+            let Some(data) = self.read(entry)? else {
+                break;
+            };
+            self.member(prefix, &path, &data, depth)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// A gzip file holds a single member, named after the file. A gzipped
+    /// tar is read as one archive rather than as a tar inside a gzip.
+    fn gzip(&mut self, prefix: &str, name: &str, bytes: &[u8], depth: usize) -> ControlFlow<()> {
+        let lower = name.to_ascii_lowercase();
+        let inner = if lower.ends_with(".tgz") {
+            format!("{}.tar", &name[..name.len() - 4])
+        } else if lower.ends_with(".gz") {
+            name[..name.len() - 3].to_string()
+        } else {
+            name.to_string()
+        };
+        // Bounds the stream itself, which tar reads through for headers and
+        // skipped entries.
+        let decoder = MultiGzDecoder::new(bytes).take(self.limits.max_expanded_bytes);
+        if inner.to_ascii_lowercase().ends_with(".tar") {
+            return self.tar(prefix, decoder, depth);
+        }
+        let Some(data) = self.read(decoder)? else {
+            return ControlFlow::Continue(());
+        };
+        if is_tar(&data) {
+            return self.tar(prefix, data.as_slice(), depth);
+        }
+        let inner = if inner.is_empty() { "data" } else { &inner };
+        self.member(prefix, inner, &data, depth)
+    }
+
+    /// The reader allocates buffers and LZMA dictionaries of the sizes the
+    /// archive declares before reading anything, so those are checked against
+    /// the remaining budget first: once for the header, once for the folders
+    /// holding the members.
+    fn seven_z(&mut self, prefix: &str, bytes: &[u8], depth: usize) -> ControlFlow<()> {
+        let budget = self.limits.max_expanded_bytes.saturating_sub(self.expanded);
+        if !seven_z_header_fits(bytes, budget) {
+            return ControlFlow::Continue(());
+        }
+        let mut source = Cursor::new(bytes);
+        let Ok(archive) = Archive::read(&mut source, bytes.len() as u64, &[]) else {
+            return ControlFlow::Continue(());
+        };
+        let oversized = archive.folders.iter().any(|folder| {
+            folder.coders.iter().any(|coder| {
+                let unpack_size = folder.get_unpack_size_for_coder(coder);
+                dictionary_size(
+                    coder.decompression_method_id(),
+                    &coder.properties,
+                    unpack_size,
+                ) > budget
+            })
+        });
+        if oversized {
+            return ControlFlow::Continue(());
+        }
+        let mut reader = SevenZReader::from_archive(archive, source, Password::empty());
+        let mut flow = ControlFlow::Continue(());
+        // Errors (a wrong checksum, an unsupported method) end the walk of
+        // this archive with what was read so far.
+        let _ = reader.for_each_entries(|entry, data| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            flow = match self.read(data) {
+                ControlFlow::Break(()) => ControlFlow::Break(()),
+                ControlFlow::Continue(None) => return Ok(false),
+                ControlFlow::Continue(Some(content)) => {
+                    self.member(prefix, entry.name(), &content, depth)
+                }
+            };
+            Ok(flow.is_continue())
+        });
+        flow
+    }
+}
+
+const SEVEN_Z_SIGNATURE_HEADER_SIZE: usize = 32;
+const SEVEN_Z_ENCODED_HEADER: u8 = 0x17;
+const SEVEN_Z_PACK_INFO: u8 = 0x06;
+const SEVEN_Z_UNPACK_INFO: u8 = 0x07;
+const SEVEN_Z_SIZE: u8 = 0x09;
+const SEVEN_Z_CRC: u8 = 0x0a;
+const SEVEN_Z_FOLDER: u8 = 0x0b;
+const SEVEN_Z_CODERS_UNPACK_SIZE: u8 = 0x0c;
+const SEVEN_Z_END: u8 = 0x00;
+
+/// Whether the header of a 7z archive can be read within `budget` bytes.
+/// The header is read into a buffer of its declared size and, when it is
+/// compressed itself, unpacked into another; both sizes and the dictionary
+/// of the header's coder are checked here, on the raw bytes.
+fn seven_z_header_fits(bytes: &[u8], budget: u64) -> bool {
+    let Some(start) = bytes.get(12..28) else {
+        return false;
+    };
+    let offset = u64::from_le_bytes(start[..8].try_into().unwrap());
+    let size = u64::from_le_bytes(start[8..].try_into().unwrap());
+    // A zeroed start header makes the reader search for the header instead;
+    // such archives are left unexpanded.
+    let Some(header) = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| offset.checked_add(SEVEN_Z_SIGNATURE_HEADER_SIZE))
+        .and_then(|begin| Some(begin..begin.checked_add(usize::try_from(size).ok()?)?))
+        .and_then(|range| bytes.get(range))
+    else {
+        return false;
+    };
+    match header.split_first() {
+        Some((&SEVEN_Z_ENCODED_HEADER, streams_info)) => {
+            encoded_header_size(streams_info).is_some_and(|size| size <= budget)
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Memory needed to unpack an encoded header: the largest of its declared
+/// unpack sizes and coder dictionaries. `None` if it cannot be parsed.
+fn encoded_header_size(mut streams_info: &[u8]) -> Option<u64> {
+    let input = &mut streams_info;
+    let mut nid = read_byte(input)?;
+    if nid == SEVEN_Z_PACK_INFO {
+        read_number(input)?;
+        let streams = read_number(input)?;
+        loop {
+            match read_byte(input)? {
+                SEVEN_Z_END => break,
+                SEVEN_Z_SIZE => {
+                    for _ in 0..streams {
+                        read_number(input)?;
+                    }
+                }
+                SEVEN_Z_CRC => skip_digests(input, streams)?,
+                _ => return None,
+            }
+        }
+        nid = read_byte(input)?;
+    }
+    if nid != SEVEN_Z_UNPACK_INFO || read_byte(input)? != SEVEN_Z_FOLDER {
+        return None;
+    }
+    let folder_count = read_number(input)?;
+    // Folders stored elsewhere in the archive are not supported by the reader.
+    if read_byte(input)? != 0 {
+        return None;
+    }
+    let mut folders = Vec::new();
+    for _ in 0..folder_count {
+        let mut coders = Vec::new();
+        let (mut in_streams, mut out_streams) = (0u64, 0u64);
+        for _ in 0..read_number(input)? {
+            let flags = read_byte(input)?;
+            let id = take(input, u64::from(flags & 0x0f))?;
+            let (inputs, outputs) = if flags & 0x10 != 0 {
+                (read_number(input)?, read_number(input)?)
+            } else {
+                (1, 1)
+            };
+            let properties = if flags & 0x20 != 0 {
+                let len = read_number(input)?;
+                take(input, len)?
+            } else {
+                &[]
+            };
+            in_streams = in_streams.checked_add(inputs)?;
+            out_streams = out_streams.checked_add(outputs)?;
+            coders.push((id, properties));
+        }
+        let bind_pairs = out_streams.checked_sub(1)?;
+        for _ in 0..bind_pairs.checked_mul(2)? {
+            read_number(input)?;
+        }
+        let packed = in_streams.checked_sub(bind_pairs)?;
+        if packed > 1 {
+            for _ in 0..packed {
+                read_number(input)?;
+            }
+        }
+        folders.push((coders, out_streams));
+    }
+    if read_byte(input)? != SEVEN_Z_CODERS_UNPACK_SIZE {
+        return None;
+    }
+    let mut largest = 0;
+    for (coders, out_streams) in folders {
+        let mut unpack_size = 0;
+        for _ in 0..out_streams {
+            unpack_size = unpack_size.max(read_number(input)?);
+        }
+        for (id, properties) in coders {
+            largest = largest.max(dictionary_size(id, properties, unpack_size));
+        }
+        largest = largest.max(unpack_size);
+    }
+    Some(largest)
+}
+
+/// Dictionary an LZMA or LZMA2 coder allocates for its declared properties.
+/// LZMA shrinks it to the data it unpacks; LZMA2 does not.
+fn dictionary_size(method: &[u8], properties: &[u8], unpack_size: u64) -> u64 {
+    if method == SevenZMethod::ID_LZMA {
+        properties
+            .get(1..5)
+            .map_or(0, |p| u64::from(u32::from_le_bytes(p.try_into().unwrap())))
+            .min(unpack_size)
+    } else if method == SevenZMethod::ID_LZMA2 {
+        match properties.first().map(|&bits| u64::from(bits)) {
+            None => 0,
+            Some(bits @ 0..=39) => (2 | (bits & 1)) << (bits / 2 + 11),
+            Some(40) => u64::from(u32::MAX),
+            Some(_) => u64::MAX,
+        }
+    } else {
+        0
+    }
+}
+
+fn read_byte(input: &mut &[u8]) -> Option<u8> {
+    let (&byte, rest) = input.split_first()?;
+    *input = rest;
+    Some(byte)
+}
+
+fn take<'a>(input: &mut &'a [u8], len: u64) -> Option<&'a [u8]> {
+    let len = usize::try_from(len).ok()?;
+    if len > input.len() {
+        return None;
+    }
+    let (taken, rest) = input.split_at(len);
+    *input = rest;
+    Some(taken)
+}
+
+/// A 7z variable-length number: the high bits of the first byte count the
+/// little-endian bytes that follow.
+fn read_number(input: &mut &[u8]) -> Option<u64> {
+    let first = read_byte(input)?;
+    let mut mask = 0x80u8;
+    let mut value = 0u64;
+    for i in 0..8 {
+        if first & mask == 0 {
+            let high = u64::from(first & (mask - 1));
+            return Some(value | high << (8 * i));
+        }
+        value |= u64::from(read_byte(input)?) << (8 * i);
+        mask >>= 1;
+    }
+    Some(value)
+}
+
+/// Skips a CRC list: a defined-bits vector unless all are defined, then a
+/// CRC32 per defined stream.
+fn skip_digests(input: &mut &[u8], count: u64) -> Option<()> {
+    let defined = if read_byte(input)? == 0 {
+        let bits = take(input, count.div_ceil(8))?;
+        bits.iter().map(|b| u64::from(b.count_ones())).sum()
+    } else {
+        count
+    };
+    take(input, defined.checked_mul(4)?).map(|_| ())
+}
+
+/// The facet linking an archive member to the archive attachment with
+/// content hash `content_hash`.
+pub fn archive_facet(content_hash: &str) -> Facet {
+    Facet::from_path([ARCHIVE_ROOT_SEGMENT, content_hash])
+}
+
+/// The content hash of the archive a member facet points to.
+pub fn facet_to_archive(facet: &Facet) -> Option<String> {
+    match facet.to_path().as_slice() {
+        [ARCHIVE_ROOT_SEGMENT, content_hash] => Some(content_hash.to_string()),
+        _ => None,
+    }
+}
+
+/// Whether a facet lies in the archive namespace.
+pub fn is_archive_facet(facet: &Facet) -> bool {
+    facet.to_path().first() == Some(&ARCHIVE_ROOT_SEGMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, data) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn tar_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn expand(name: &str, bytes: &[u8], limits: &ArchiveLimits) -> Vec<(String, Vec<u8>)> {
+        let format = ArchiveFormat::detect("", name, bytes).expect("an archive");
+        let mut members = Vec::new();
+        expand_archive(format, name, bytes, limits, |path, data| {
+            members.push((path.to_string(), data.to_vec()));
+            ControlFlow::Continue(())
+        });
+        members
+    }
+
+    fn paths(members: &[(String, Vec<u8>)]) -> Vec<&str> {
+        members.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    #[test]
+    fn zip_members_include_nested_archives() {
+        let inner = zip_of(&[("b.txt", b"inner")]);
+        let outer = zip_of(&[("invoices/a.txt", b"outer"), ("inner.zip", &inner)]);
+        let members = expand("bundle.zip", &outer, &ArchiveLimits::default());
+        assert_eq!(
+            paths(&members),
+            ["invoices/a.txt", "inner.zip", "inner.zip/b.txt"]
+        );
+        assert_eq!(members[2].1, b"inner");
+    }
+
+    #[test]
+    fn tar_and_gzipped_tar() {
+        let tar = tar_of(&[("x/one.txt", b"one"), ("x/two.txt", b"two")]);
+        let members = expand("bundle.tar", &tar, &ArchiveLimits::default());
+        assert_eq!(paths(&members), ["x/one.txt", "x/two.txt"]);
+
+        let tgz = gzip(&tar);
+        for name in ["bundle.tgz", "bundle.tar.gz", "bundle.gz"] {
+            let members = expand(name, &tgz, &ArchiveLimits::default());
+            assert_eq!(paths(&members), ["x/one.txt", "x/two.txt"], "{name}");
+        }
+    }
+
+    #[test]
+    fn gzip_member_is_named_after_the_file() {
+        let members = expand("notes.txt.gz", &gzip(b"notes"), &ArchiveLimits::default());
+        assert_eq!(members, [("notes.txt".to_string(), b"notes".to_vec())]);
+    }
+
+    #[test]
+    fn seven_z_members() {
+        let bytes = include_bytes!("testdata/reports.7z");
+        let members = expand("reports.7z", bytes, &ArchiveLimits::default());
+        assert_eq!(paths(&members), ["reports/q1.txt", "reports/q2.txt"]);
+        assert_eq!(members[0].1, b"first quarter revenue\n");
+    }
+
+    #[test]
+    fn seven_z_declared_sizes_are_checked_before_decoding() {
+        let bytes = include_bytes!("testdata/reports.7z");
+        // The LZMA-compressed header declares 145 bytes unpacked.
+        assert!(seven_z_header_fits(bytes, 145));
+        assert!(!seven_z_header_fits(bytes, 144));
+        let limits = ArchiveLimits {
+            max_expanded_bytes: 144,
+            ..Default::default()
+        };
+        assert!(expand("reports.7z", bytes, &limits).is_empty());
+
+        let mut huge_header = bytes.to_vec();
+        huge_header[20..28].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        assert!(!seven_z_header_fits(&huge_header, u64::MAX));
+
+        // LZMA2 allocates its whole dictionary: 1.5 GiB for property 37.
+        assert_eq!(dictionary_size(SevenZMethod::ID_LZMA2, &[37], 10), 3 << 29);
+        assert_eq!(
+            dictionary_size(SevenZMethod::ID_LZMA, &[0x5d, 0, 0, 0, 0x80], 10),
+            10
+        );
+    }
+
+    #[test]
+    fn corrupt_archives_expand_to_nothing() {
+        for name in ["a.zip", "a.tar", "a.gz", "a.7z"] {
+            assert!(expand(name, b"not an archive", &ArchiveLimits::default()).is_empty());
+        }
+    }
+
+    #[test]
+    fn walks_stop_at_the_limits() {
+        let files: &[(&str, &[u8])] = &[
+            ("1.txt", b"aaaaaa"),
+            ("2.txt", b"bbbbbb"),
+            ("3.txt", b"cccccc"),
+        ];
+        let zip = zip_of(files);
+
+        let limits = ArchiveLimits {
+            max_members: 2,
+            ..Default::default()
+        };
+        assert_eq!(paths(&expand("a.zip", &zip, &limits)), ["1.txt", "2.txt"]);
+
+        let limits = ArchiveLimits {
+            max_expanded_bytes: 10,
+            ..Default::default()
+        };
+        assert_eq!(paths(&expand("a.zip", &zip, &limits)), ["1.txt"]);
+
+        let nested = zip_of(&[("inner.zip", &zip)]);
+        let limits = ArchiveLimits {
+            max_depth: 1,
+            ..Default::default()
+        };
+        assert_eq!(paths(&expand("a.zip", &nested, &limits)), ["inner.zip"]);
+    }
+
+    #[test]
+    fn zip_bombs_end_the_walk() {
+        let zeros = vec![0u8; 2 * 1024 * 1024];
+        let zip = zip_of(&[("zeros.bin", &zeros), ("after.txt", b"after")]);
+        assert!(expand("bomb.zip", &zip, &ArchiveLimits::default()).is_empty());
+    }
+
+    #[test]
+    fn detects_archives_but_not_zip_based_documents() {
+        let zip = zip_of(&[("a.txt", b"a")]);
+        assert_eq!(
+            ArchiveFormat::detect("", "a.ZIP", &zip),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::detect("application/zip", "a.docx", &zip),
+            None
+        );
+        assert_eq!(
+            ArchiveFormat::detect("application/x-7z-compressed", "", b""),
+            Some(ArchiveFormat::SevenZ)
+        );
+        assert_eq!(
+            ArchiveFormat::detect("application/octet-stream", "", &zip),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::detect("", "", &tar_of(&[("a.txt", b"a")])),
+            Some(ArchiveFormat::Tar)
+        );
+        assert_eq!(ArchiveFormat::detect("application/pdf", "", &zip), None);
+    }
+
+    #[test]
+    fn finds_members_by_content_hash() {
+        let inner = zip_of(&[("b.txt", b"inner")]);
+        let outer = zip_of(&[("a.txt", b"outer"), ("inner.zip", &inner)]);
+        let hash = compute_content_hash(b"inner");
+        assert_eq!(
+            find_archive_member(ArchiveFormat::Zip, "a.zip", &outer, &hash),
+            Some(("inner.zip/b.txt".to_string(), b"inner".to_vec()))
+        );
+        let missing = compute_content_hash(b"missing");
+        assert_eq!(
+            find_archive_member(ArchiveFormat::Zip, "a.zip", &outer, &missing),
+            None
+        );
+    }
+
+    #[test]
+    fn archive_facets_round_trip() {
+        let facet = archive_facet("0123abcd");
+        assert_eq!(facet.to_string(), "/_archive/0123abcd");
+        assert!(is_archive_facet(&facet));
+        assert_eq!(facet_to_archive(&facet).as_deref(), Some("0123abcd"));
+        assert!(!is_archive_facet(&Facet::from("/finance")));
+        assert_eq!(member_file_type("2026/invoice.pdf"), "application/pdf");
+    }
+}
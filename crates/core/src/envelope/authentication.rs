@@ -0,0 +1,363 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Authentication results of archived messages: DKIM and S/MIME or OpenPGP
+//! signatures, verified once when the message is ingested.
+//!
+//! The verdicts are summarized on the envelope as facets below
+//! [`AUTH_FACET_ROOT`], in the same facet field as user tags, so they can be
+//! searched without a schema change. The details behind them (every DKIM
+//! signature with the key record it was checked against, the signer's
+//! certificate) are kept in a [`MessageAuthentication`] record per message
+//! and shown with its content.
+
+use mail_parser::Message;
+use serde::{Deserialize, Serialize};
+use tantivy::schema::Facet;
+
+use crate::account::migration::AccountModel;
+use crate::database::manager::DB_MANAGER;
+use crate::database::{find_impl, upsert_impl, MemDbModel};
+use crate::envelope::dkim::{verify_dkim, DnsTxtResolver, OfflineTxtResolver, TxtResolver};
+use crate::envelope::signature::{verify_message_signature, SignatureVerification};
+use crate::error::BichonResult;
+use crate::settings::cli::SETTINGS;
+use crate::utc_now;
+
+/// Facet root holding authentication results. User tags may not be created
+/// below it.
+pub const AUTH_FACET_ROOT: &str = "/_auth";
+
+const AUTH_ROOT_SEGMENT: &str = "_auth";
+const DKIM_SEGMENT: &str = "dkim";
+const DKIM_DOMAIN_SEGMENT: &str = "dkim_domain";
+const SIGNATURE_SEGMENT: &str = "signature";
+const SIGNATURE_KIND_SEGMENT: &str = "signature_kind";
+const SIGNER_SEGMENT: &str = "signer";
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Enum))]
+pub enum VerificationStatus {
+    /// Verified.
+    Pass,
+    /// Present but invalid: altered content, a wrong or revoked key.
+    Fail,
+    /// Not signed.
+    #[default]
+    None,
+    /// Could not be decided, e.g. the key lookup failed or the signer is
+    /// not trusted.
+    Unknown,
+}
+
+impl VerificationStatus {
+    fn segment(self) -> &'static str {
+        match self {
+            VerificationStatus::Pass => "pass",
+            VerificationStatus::Fail => "fail",
+            VerificationStatus::None => "none",
+            VerificationStatus::Unknown => "unknown",
+        }
+    }
+
+    fn from_segment(segment: &str) -> Option<Self> {
+        [
+            VerificationStatus::Pass,
+            VerificationStatus::Fail,
+            VerificationStatus::None,
+            VerificationStatus::Unknown,
+        ]
+        .into_iter()
+        .find(|status| status.segment() == segment)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Enum))]
+pub enum SignatureKind {
+    Smime,
+    OpenPgp,
+}
+
+impl SignatureKind {
+    fn segment(self) -> &'static str {
+        match self {
+            SignatureKind::Smime => "smime",
+            SignatureKind::OpenPgp => "openpgp",
+        }
+    }
+}
+
+/// The searchable verdicts of a message, stored on its envelope.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct AuthenticationSummary {
+    /// `Pass` if any DKIM signature verified, else `Fail` if any was
+    /// invalid.
+    pub dkim: VerificationStatus,
+    /// Signing domain (`d=`) of the signature the DKIM verdict rests on.
+    pub dkim_domain: Option<String>,
+    /// Verdict on the S/MIME or OpenPGP signature.
+    pub signature: VerificationStatus,
+    pub signature_kind: Option<SignatureKind>,
+    /// Lower-cased email address of the S/MIME signer, or the OpenPGP
+    /// issuer key ID.
+    pub signer: Option<String>,
+}
+
+/// Result of checking one `DKIM-Signature` header.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct DkimResult {
+    /// Signing domain (`d=`).
+    pub domain: String,
+    /// Key selector (`s=`).
+    pub selector: String,
+    /// Signing algorithm (`a=`), e.g. `rsa-sha256`.
+    pub algorithm: String,
+    pub status: VerificationStatus,
+    /// The DNS TXT record the signature was checked against, as it was at
+    /// ingest time.
+    pub key_record: Option<String>,
+    /// Why the signature did not pass.
+    pub error: Option<String>,
+}
+
+/// Full authentication results of a message, keyed by the content hash of
+/// its EML.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct MessageAuthentication {
+    pub content_hash: String,
+    /// When the message was verified, in milliseconds since the Unix epoch.
+    pub verified_at: i64,
+    pub summary: AuthenticationSummary,
+    pub dkim_signatures: Vec<DkimResult>,
+    /// PEM certificate of the S/MIME signer.
+    pub signer_certificate: Option<String>,
+    /// Issuer key ID of the OpenPGP signature.
+    pub signer_key_id: Option<String>,
+    /// Why the S/MIME or OpenPGP signature did not pass.
+    pub signature_error: Option<String>,
+}
+
+impl MemDbModel for MessageAuthentication {
+    fn collection() -> &'static str {
+        "message_authentication"
+    }
+    fn key(&self) -> String {
+        self.content_hash.clone()
+    }
+}
+
+impl MessageAuthentication {
+    pub fn new(
+        content_hash: String,
+        dkim_signatures: Vec<DkimResult>,
+        signature: Option<SignatureVerification>,
+    ) -> Self {
+        let pick = |status| dkim_signatures.iter().find(|r| r.status == status);
+        let decisive = pick(VerificationStatus::Pass)
+            .or_else(|| pick(VerificationStatus::Fail))
+            .or_else(|| pick(VerificationStatus::Unknown));
+        let signature = signature.unwrap_or_default();
+        let summary = AuthenticationSummary {
+            dkim: decisive.map_or(VerificationStatus::None, |r| r.status),
+            dkim_domain: decisive.map(|r| r.domain.clone()).filter(|d| !d.is_empty()),
+            signature: signature.status,
+            signature_kind: signature.kind,
+            signer: signature.signer.map(|s| s.to_lowercase()),
+        };
+        Self {
+            content_hash,
+            verified_at: utc_now!(),
+            summary,
+            dkim_signatures,
+            signer_certificate: signature.certificate,
+            signer_key_id: signature.key_id,
+            signature_error: signature.error,
+        }
+    }
+
+    pub fn find(content_hash: &str) -> BichonResult<Option<Self>> {
+        find_impl(DB_MANAGER.db(), content_hash)
+    }
+
+    pub fn save(self) -> BichonResult<()> {
+        upsert_impl(DB_MANAGER.db(), self)
+    }
+}
+
+/// Verifies a message as it is ingested: DKIM over `raw`, the bytes as
+/// received, and the S/MIME or OpenPGP signature of `readable`, which is
+/// the decrypted message when `raw` was encrypted. `received` is when the
+/// message arrived, in milliseconds since the Unix epoch, or 0 if unknown.
+pub async fn authenticate_message(
+    raw: &[u8],
+    readable: &Message<'_>,
+    account: Option<&AccountModel>,
+    content_hash: &str,
+    received: i64,
+) -> MessageAuthentication {
+    let signature = verify_message_signature(readable, account);
+    let resolver: &dyn TxtResolver = if SETTINGS.bichon_dkim_dns_lookup {
+        &DnsTxtResolver
+    } else {
+        &OfflineTxtResolver
+    };
+    let received = if received > 0 { received } else { utc_now!() };
+    let dkim = verify_dkim(raw, resolver, received / 1000).await;
+    MessageAuthentication::new(content_hash.to_string(), dkim, signature)
+}
+
+/// Facets recording `summary` on the envelope.
+pub fn summary_to_facets(summary: &AuthenticationSummary) -> Vec<Facet> {
+    let mut facets = vec![
+        status_facet(DKIM_SEGMENT, summary.dkim),
+        status_facet(SIGNATURE_SEGMENT, summary.signature),
+    ];
+    if let Some(domain) = &summary.dkim_domain {
+        facets.push(dkim_domain_facet(domain));
+    }
+    if let Some(kind) = summary.signature_kind {
+        facets.push(Facet::from_path([
+            AUTH_ROOT_SEGMENT,
+            SIGNATURE_KIND_SEGMENT,
+            kind.segment(),
+        ]));
+    }
+    if let Some(signer) = &summary.signer {
+        facets.push(signer_facet(signer));
+    }
+    facets
+}
+
+/// Reads the summary back from an envelope's facets; `None` for messages
+/// archived before verification existed.
+pub fn facets_to_summary(facets: &[Facet]) -> Option<AuthenticationSummary> {
+    let mut summary = AuthenticationSummary::default();
+    let mut found = false;
+    for facet in facets {
+        let path = facet.to_path();
+        let [AUTH_ROOT_SEGMENT, segment, value] = path.as_slice() else {
+            continue;
+        };
+        found = true;
+        match *segment {
+            DKIM_SEGMENT => {
+                summary.dkim = VerificationStatus::from_segment(value).unwrap_or_default()
+            }
+            DKIM_DOMAIN_SEGMENT => summary.dkim_domain = Some(value.to_string()),
+            SIGNATURE_SEGMENT => {
+                summary.signature = VerificationStatus::from_segment(value).unwrap_or_default()
+            }
+            SIGNATURE_KIND_SEGMENT => {
+                summary.signature_kind = [SignatureKind::Smime, SignatureKind::OpenPgp]
+                    .into_iter()
+                    .find(|kind| kind.segment() == *value)
+            }
+            SIGNER_SEGMENT => summary.signer = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    found.then_some(summary)
+}
+
+/// The facet matching messages whose DKIM verdict is `status`.
+pub fn dkim_status_facet(status: VerificationStatus) -> Facet {
+    status_facet(DKIM_SEGMENT, status)
+}
+
+/// The facet matching messages whose signature verdict is `status`.
+pub fn signature_status_facet(status: VerificationStatus) -> Facet {
+    status_facet(SIGNATURE_SEGMENT, status)
+}
+
+/// The facet matching messages DKIM-signed by `domain`.
+pub fn dkim_domain_facet(domain: &str) -> Facet {
+    Facet::from_path([
+        AUTH_ROOT_SEGMENT,
+        DKIM_DOMAIN_SEGMENT,
+        &domain.to_lowercase(),
+    ])
+}
+
+/// The facet matching messages signed by `signer`.
+pub fn signer_facet(signer: &str) -> Facet {
+    Facet::from_path([AUTH_ROOT_SEGMENT, SIGNER_SEGMENT, &signer.to_lowercase()])
+}
+
+fn status_facet(segment: &str, status: VerificationStatus) -> Facet {
+    Facet::from_path([AUTH_ROOT_SEGMENT, segment, status.segment()])
+}
+
+/// Whether a facet lies in the authentication namespace.
+pub fn is_auth_facet(facet: &Facet) -> bool {
+    facet.to_path().first() == Some(&AUTH_ROOT_SEGMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dkim(domain: &str, status: VerificationStatus) -> DkimResult {
+        DkimResult {
+            domain: domain.into(),
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn summary_prefers_passing_signature() {
+        let record = MessageAuthentication::new(
+            "hash".into(),
+            vec![
+                dkim("relay.example.net", VerificationStatus::Fail),
+                dkim("example.com", VerificationStatus::Pass),
+            ],
+            None,
+        );
+        assert_eq!(record.summary.dkim, VerificationStatus::Pass);
+        assert_eq!(record.summary.dkim_domain.as_deref(), Some("example.com"));
+        assert_eq!(record.summary.signature, VerificationStatus::None);
+
+        let unsigned = MessageAuthentication::new("hash".into(), Vec::new(), None);
+        assert_eq!(unsigned.summary.dkim, VerificationStatus::None);
+        assert_eq!(unsigned.summary.dkim_domain, None);
+    }
+
+    #[test]
+    fn summary_round_trips_through_facets() {
+        let summary = AuthenticationSummary {
+            dkim: VerificationStatus::Fail,
+            dkim_domain: Some("example.com".into()),
+            signature: VerificationStatus::Unknown,
+            signature_kind: Some(SignatureKind::Smime),
+            signer: Some("alice@example.com".into()),
+        };
+        let facets = summary_to_facets(&summary);
+        assert!(facets.iter().all(is_auth_facet));
+        assert!(facets.contains(&dkim_status_facet(VerificationStatus::Fail)));
+        assert!(facets.contains(&signer_facet("Alice@Example.com")));
+        assert_eq!(facets_to_summary(&facets), Some(summary));
+
+        let unrelated = [Facet::from_text("/work").unwrap()];
+        assert_eq!(facets_to_summary(&unrelated), None);
+    }
+}
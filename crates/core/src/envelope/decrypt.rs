@@ -0,0 +1,734 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decryption of OpenPGP/MIME (RFC 3156) and S/MIME (RFC 8551) messages
+//! with the private keys configured on their account.
+//!
+//! The archived EML is always the ciphertext as delivered. The plaintext is
+//! rebuilt from it when needed: at ingest, to index the body and detach the
+//! attachments, and when the message content is viewed.
+
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use cbc::cipher::{block_padding::Pkcs7, BlockCipher, BlockDecryptMut, KeyInit, KeyIvInit};
+use cms::enveloped_data::{EnvelopedData, RecipientIdentifier, RecipientInfo};
+use const_oid::db::{rfc5911, rfc5912};
+use mail_parser::{Message, MessageParser, MimeHeaders};
+use p12_keystore::KeyStore;
+use pgp::{types::SecretKeyTrait, Deserializable, SignedSecretKey};
+use rsa::{pkcs8::DecodePrivateKey, Pkcs1v15Encrypt, RsaPrivateKey};
+use x509_cert::{
+    der::{asn1::OctetString, Decode},
+    Certificate,
+};
+
+use crate::{
+    account::migration::AccountModel,
+    decrypt,
+    envelope::extractor::reattach_eml_content,
+    envelope::smime::{content_info, issued_as, key_identifier},
+    error::{code::ErrorCode, BichonResult},
+    raise_error,
+    store::envelope::Envelope,
+};
+
+/// Private keys of one account, unlocked for decryption.
+pub struct DecryptionKeys {
+    pgp: Option<(SignedSecretKey, String)>,
+    smime: Option<(RsaPrivateKey, Certificate)>,
+}
+
+impl DecryptionKeys {
+    /// Loads the keys configured on `account`, or `None` if it has none.
+    pub fn for_account(account: &AccountModel) -> BichonResult<Option<Self>> {
+        if account.pgp_key.is_none() && account.smime_key.is_none() {
+            return Ok(None);
+        }
+        let pgp = match &account.pgp_key {
+            Some(key) => {
+                let armored = decrypt!(key)?;
+                let passphrase = account
+                    .pgp_passphrase
+                    .as_ref()
+                    .map(|p| decrypt!(p))
+                    .transpose()?;
+                Some(load_pgp_key(&armored, passphrase.as_deref())?)
+            }
+            None => None,
+        };
+        let smime = match &account.smime_key {
+            Some(key) => {
+                let password = account
+                    .smime_password
+                    .as_ref()
+                    .map(|p| decrypt!(p))
+                    .transpose()?;
+                Some(load_smime_key(&decrypt!(key)?, password.as_deref())?)
+            }
+            None => None,
+        };
+        Ok(Some(Self { pgp, smime }))
+    }
+
+    /// The account's OpenPGP key. Its public half also verifies what the
+    /// account owner signed.
+    pub(crate) fn pgp_key(&self) -> Option<&SignedSecretKey> {
+        self.pgp.as_ref().map(|(key, _)| key)
+    }
+}
+
+/// Checks that `armored` is a usable OpenPGP secret key and that
+/// `passphrase` (none meaning an unprotected key) unlocks it and its subkeys.
+pub fn validate_pgp_key(armored: &str, passphrase: Option<&str>) -> BichonResult<()> {
+    let (key, passphrase) = load_pgp_key(armored, passphrase)?;
+    let locked = |e: pgp::errors::Error| {
+        raise_error!(
+            format!("The OpenPGP passphrase does not unlock the secret key: {e}"),
+            ErrorCode::InvalidParameter
+        )
+    };
+    key.unlock(|| passphrase.clone(), |_| Ok(()))
+        .map_err(locked)?;
+    for subkey in &key.secret_subkeys {
+        subkey
+            .unlock(|| passphrase.clone(), |_| Ok(()))
+            .map_err(locked)?;
+    }
+    Ok(())
+}
+
+/// Checks that `pkcs12` is a base64 PKCS#12 bundle that `password` opens
+/// and that holds both a private key and a certificate.
+pub fn validate_smime_key(pkcs12: &str, password: Option<&str>) -> BichonResult<()> {
+    load_smime_key(pkcs12, password).map(|_| ())
+}
+
+fn load_pgp_key(
+    armored: &str,
+    passphrase: Option<&str>,
+) -> BichonResult<(SignedSecretKey, String)> {
+    let invalid = |e: pgp::errors::Error| {
+        raise_error!(
+            format!("Invalid OpenPGP secret key: {e}"),
+            ErrorCode::InvalidParameter
+        )
+    };
+    let (key, _) = SignedSecretKey::from_string(armored).map_err(invalid)?;
+    key.verify().map_err(invalid)?;
+    Ok((key, passphrase.unwrap_or_default().to_string()))
+}
+
+fn load_smime_key(
+    pkcs12: &str,
+    password: Option<&str>,
+) -> BichonResult<(RsaPrivateKey, Certificate)> {
+    let invalid = |reason: String| {
+        raise_error!(
+            format!("Invalid S/MIME PKCS#12 bundle: {reason}"),
+            ErrorCode::InvalidParameter
+        )
+    };
+    let der = general_purpose::STANDARD
+        .decode(pkcs12.split_whitespace().collect::<String>())
+        .map_err(|e| invalid(e.to_string()))?;
+    let store = KeyStore::from_pkcs12(&der, password.unwrap_or_default())
+        .map_err(|e| invalid(e.to_string()))?;
+    let Some((_, chain)) = store.private_key_chain() else {
+        return Err(invalid(
+            "it must contain a private key and its certificate".into(),
+        ));
+    };
+    let cert = chain
+        .chain()
+        .first()
+        .ok_or_else(|| invalid("it must contain a private key and its certificate".into()))?;
+    let cert = Certificate::from_der(cert.as_der()).map_err(|e| invalid(e.to_string()))?;
+    let key = RsaPrivateKey::from_pkcs8_der(chain.key())
+        .map_err(|_| invalid("only RSA keys are supported".into()))?;
+    Ok((key, cert))
+}
+
+/// Decrypts a CMS `EnvelopedData` addressed to `cert` by RSA key transport.
+fn decrypt_enveloped(
+    key: &RsaPrivateKey,
+    cert: &Certificate,
+    ber: &[u8],
+) -> Result<Vec<u8>, String> {
+    let enveloped = content_info(ber, rfc5911::ID_ENVELOPED_DATA)?
+        .decode_as::<EnvelopedData>()
+        .map_err(|e| e.to_string())?;
+    let recipient = enveloped
+        .recip_infos
+        .0
+        .iter()
+        .find_map(|info| match info {
+            RecipientInfo::Ktri(ktri) => {
+                let addressed = match &ktri.rid {
+                    RecipientIdentifier::IssuerAndSerialNumber(id) => issued_as(cert, id),
+                    RecipientIdentifier::SubjectKeyIdentifier(id) => {
+                        key_identifier(cert).as_ref() == Some(id)
+                    }
+                };
+                addressed.then_some(ktri)
+            }
+            _ => None,
+        })
+        .ok_or("the message is not encrypted to the account's certificate")?;
+    if recipient.key_enc_alg.oid != rfc5912::RSA_ENCRYPTION {
+        return Err(format!(
+            "unsupported key encryption algorithm {}",
+            recipient.key_enc_alg.oid
+        ));
+    }
+    let content_key = key
+        .decrypt(Pkcs1v15Encrypt, recipient.enc_key.as_bytes())
+        .map_err(|e| format!("content key: {e}"))?;
+
+    let content = &enveloped.encrypted_content;
+    let iv = content
+        .content_enc_alg
+        .parameters
+        .as_ref()
+        .and_then(|p| p.decode_as::<OctetString>().ok())
+        .ok_or("content encryption IV is missing")?;
+    let ciphertext = content
+        .encrypted_content
+        .as_ref()
+        .ok_or("no encrypted content")?
+        .as_bytes();
+    let (key, iv) = (content_key.as_slice(), iv.as_bytes());
+    match content.content_enc_alg.oid {
+        rfc5911::ID_AES_128_CBC => cbc_decrypt::<aes::Aes128>(key, iv, ciphertext),
+        rfc5911::ID_AES_192_CBC => cbc_decrypt::<aes::Aes192>(key, iv, ciphertext),
+        rfc5911::ID_AES_256_CBC => cbc_decrypt::<aes::Aes256>(key, iv, ciphertext),
+        rfc5911::DES_EDE_3_CBC => cbc_decrypt::<des::TdesEde3>(key, iv, ciphertext),
+        oid => Err(format!("unsupported content encryption algorithm {oid}")),
+    }
+}
+
+fn cbc_decrypt<C>(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String>
+where
+    C: BlockCipher + BlockDecryptMut + KeyInit,
+{
+    cbc::Decryptor::<C>::new_from_slices(key, iv)
+        .map_err(|_| "invalid content key".to_string())?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| "wrong content key or corrupt ciphertext".to_string())
+}
+
+enum EncryptedPayload<'a> {
+    OpenPgp(&'a [u8]),
+    Smime(&'a [u8]),
+}
+
+/// Finds the ciphertext of an encrypted message: the second part of a
+/// `multipart/encrypted` OpenPGP message, or the body of an enveloped
+/// `application/pkcs7-mime` one.
+fn encrypted_payload<'a>(message: &'a Message<'_>) -> Option<EncryptedPayload<'a>> {
+    let root = message.root_part();
+    let content_type = root.content_type()?;
+    let subtype = content_type.subtype().unwrap_or_default();
+    match (content_type.ctype(), subtype) {
+        (multipart, "encrypted") if multipart.eq_ignore_ascii_case("multipart") => {
+            let protocol = content_type.attribute("protocol")?;
+            if !protocol.eq_ignore_ascii_case("application/pgp-encrypted") {
+                return None;
+            }
+            let payload = message.part(*root.sub_parts()?.get(1)?)?;
+            Some(EncryptedPayload::OpenPgp(payload.contents()))
+        }
+        (application, "pkcs7-mime" | "x-pkcs7-mime")
+            if application.eq_ignore_ascii_case("application") =>
+        {
+            // Signed-only and compressed S/MIME messages are not encrypted.
+            let enveloped = content_type
+                .attribute("smime-type")
+                .is_none_or(|t| t.eq_ignore_ascii_case("enveloped-data"));
+            enveloped.then(|| EncryptedPayload::Smime(root.contents()))
+        }
+        _ => None,
+    }
+}
+
+/// Whether `message` is an OpenPGP/MIME or S/MIME encrypted message.
+pub fn is_encrypted(message: &Message<'_>) -> bool {
+    encrypted_payload(message).is_some()
+}
+
+/// Decrypts `message` into a complete EML: its own headers, minus the
+/// `Content-*` ones, followed by the decrypted MIME entity.
+///
+/// Returns `Ok(None)` if the message is not encrypted or there is no key
+/// for its format.
+pub fn decrypt_message(
+    keys: &DecryptionKeys,
+    message: &Message<'_>,
+) -> BichonResult<Option<Vec<u8>>> {
+    let failed = |reason: String| {
+        raise_error!(
+            format!("Failed to decrypt message: {reason}"),
+            ErrorCode::InternalError
+        )
+    };
+    let entity = match encrypted_payload(message) {
+        Some(EncryptedPayload::OpenPgp(armored)) => {
+            let Some((key, passphrase)) = &keys.pgp else {
+                return Ok(None);
+            };
+            let armored = std::str::from_utf8(armored).map_err(|e| failed(e.to_string()))?;
+            let (encrypted, _) =
+                pgp::Message::from_string(armored).map_err(|e| failed(e.to_string()))?;
+            let passphrase = passphrase.clone();
+            let (mut decrypted, _) = encrypted
+                .decrypt(move || passphrase, &[key])
+                .map_err(|e| failed(e.to_string()))?;
+            if let pgp::Message::Compressed(_) = decrypted {
+                decrypted = decrypted.decompress().map_err(|e| failed(e.to_string()))?;
+            }
+            decrypted
+                .get_content()
+                .map_err(|e| failed(e.to_string()))?
+                .ok_or_else(|| failed("no literal data in the OpenPGP message".into()))?
+        }
+        Some(EncryptedPayload::Smime(ber)) => {
+            let Some((key, cert)) = &keys.smime else {
+                return Ok(None);
+            };
+            decrypt_enveloped(key, cert, ber).map_err(failed)?
+        }
+        None => return Ok(None),
+    };
+    let header_end =
+        (message.root_part().raw_body_offset() as usize).min(message.raw_message().len());
+    Ok(Some(rebuild_message(
+        &message.raw_message()[..header_end],
+        &entity,
+    )))
+}
+
+/// Decrypts `message` with the keys of `account`. Failures are logged and
+/// yield `None`, so callers carry on with the ciphertext.
+pub fn try_decrypt(account: &AccountModel, message: &Message<'_>) -> Option<Vec<u8>> {
+    if !is_encrypted(message) {
+        return None;
+    }
+    let result = DecryptionKeys::for_account(account).and_then(|keys| match keys {
+        Some(keys) => decrypt_message(&keys, message),
+        None => Ok(None),
+    });
+    match result {
+        Ok(plaintext) => plaintext,
+        Err(e) => {
+            tracing::warn!(
+                account_id = account.id,
+                message_id = message.message_id().unwrap_or_default(),
+                error = %e,
+                "Encrypted message could not be decrypted"
+            );
+            None
+        }
+    }
+}
+
+/// Like [`reattach_eml_content`], but returns the decrypted EML when the
+/// message is encrypted and its account holds the key. The flag tells
+/// whether it was decrypted.
+pub fn readable_eml_content(
+    account_id: u64,
+    envelope_id: String,
+) -> BichonResult<(Envelope, Bytes, bool)> {
+    let (envelope, eml) = reattach_eml_content(account_id, envelope_id)?;
+    let decrypted = match MessageParser::default().parse(&eml) {
+        Some(message) if is_encrypted(&message) => AccountModel::get(account_id)
+            .ok()
+            .and_then(|account| try_decrypt(&account, &message)),
+        _ => None,
+    };
+    Ok(match decrypted {
+        Some(plaintext) => (envelope, Bytes::from(plaintext), true),
+        None => (envelope, eml, false),
+    })
+}
+
+/// Joins the outer header block of an encrypted message with its decrypted
+/// MIME entity. The outer `Content-*` headers describe the ciphertext and
+/// are dropped; the entity brings its own.
+fn rebuild_message(header_block: &[u8], entity: &[u8]) -> Vec<u8> {
+    let mut rebuilt = Vec::with_capacity(header_block.len() + entity.len());
+    let mut keep = false;
+    for line in header_block.split_inclusive(|&b| b == b'\n') {
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            // The blank line ending the header block.
+            break;
+        }
+        if !matches!(line.first(), Some(b' ' | b'\t')) {
+            keep = !line
+                .get(..8)
+                .is_some_and(|name| name.eq_ignore_ascii_case(b"content-"));
+        }
+        if keep {
+            rebuilt.extend_from_slice(line);
+            if !line.ends_with(b"\n") {
+                rebuilt.extend_from_slice(b"\r\n");
+            }
+        }
+    }
+    rebuilt.extend_from_slice(entity);
+    rebuilt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cms::{
+        builder::{
+            ContentEncryptionAlgorithm, EnvelopedDataBuilder, KeyEncryptionInfo,
+            KeyTransRecipientInfoBuilder,
+        },
+        cert::IssuerAndSerialNumber,
+        content_info::ContentInfo,
+    };
+    use p12_keystore::{KeyStoreEntry, PrivateKeyChain};
+    use pgp::{
+        crypto::{ecc_curve::ECCCurve, sym::SymmetricKeyAlgorithm},
+        types::{CompressionAlgorithm, SecretKeyTrait},
+        KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder,
+    };
+    use rsa::{
+        pkcs8::{DecodePublicKey, EncodePrivateKey},
+        rand_core::OsRng,
+        RsaPublicKey,
+    };
+    use x509_cert::der::{Any, Encode};
+
+    use crate::envelope::smime::tests::identity;
+
+    const ENTITY: &str = concat!(
+        "Content-Type: multipart/mixed; boundary=\"inner\"\r\n",
+        "\r\n",
+        "--inner\r\n",
+        "Content-Type: text/plain\r\n",
+        "\r\n",
+        "The launch moves to Thursday.\r\n",
+        "--inner\r\n",
+        "Content-Type: application/pdf\r\n",
+        "Content-Disposition: attachment; filename=\"plan.pdf\"\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "\r\n",
+        "JVBERi0xLjQK\r\n",
+        "--inner--\r\n",
+    );
+
+    fn parse(raw: &[u8]) -> Message<'_> {
+        MessageParser::default().parse(raw).unwrap()
+    }
+
+    fn assert_decrypted(plaintext: &[u8]) {
+        let decrypted = parse(plaintext);
+        assert_eq!(decrypted.subject(), Some("Launch"));
+        assert_eq!(
+            decrypted.body_text(0).as_deref(),
+            Some("The launch moves to Thursday.")
+        );
+        let attachment = decrypted.attachments().next().unwrap();
+        assert_eq!(attachment.attachment_name(), Some("plan.pdf"));
+        assert_eq!(attachment.contents(), b"%PDF-1.4\n");
+    }
+
+    /// An Ed25519 key with an encryption subkey, armored as users paste it.
+    fn pgp_key(passphrase: Option<&str>) -> String {
+        let passphrase = passphrase.map(str::to_string);
+        let subkey = SubkeyParamsBuilder::default()
+            .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+            .can_encrypt(true)
+            .passphrase(passphrase.clone())
+            .build()
+            .unwrap();
+        let key = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSALegacy)
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id("Archive <archive@example.com>".into())
+            .passphrase(passphrase.clone())
+            .subkey(subkey)
+            .build()
+            .unwrap()
+            .generate(OsRng)
+            .unwrap()
+            .sign(OsRng, move || passphrase.unwrap_or_default())
+            .unwrap();
+        key.to_armored_string(None.into()).unwrap()
+    }
+
+    fn pgp_keys(armored: &str, passphrase: Option<&str>) -> DecryptionKeys {
+        DecryptionKeys {
+            pgp: Some(load_pgp_key(armored, passphrase).unwrap()),
+            smime: None,
+        }
+    }
+
+    fn pgp_message(armored_key: &str, compressed: bool) -> Vec<u8> {
+        let (key, _) = SignedSecretKey::from_string(armored_key).unwrap();
+        let recipient = key.secret_subkeys[0].public_key();
+        let mut literal = pgp::Message::new_literal_bytes("", ENTITY.as_bytes());
+        if compressed {
+            literal = literal.compress(CompressionAlgorithm::ZLIB).unwrap();
+        }
+        let encrypted = literal
+            .encrypt_to_keys_seipdv1(OsRng, SymmetricKeyAlgorithm::AES128, &[&recipient])
+            .unwrap()
+            .to_armored_string(None.into())
+            .unwrap();
+        format!(
+            concat!(
+                "From: alice@example.com\r\n",
+                "To: archive@example.com\r\n",
+                "Subject: Launch\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: multipart/encrypted; boundary=\"enc\";\r\n",
+                " protocol=\"application/pgp-encrypted\"\r\n",
+                "\r\n",
+                "--enc\r\n",
+                "Content-Type: application/pgp-encrypted\r\n",
+                "\r\n",
+                "Version: 1\r\n",
+                "--enc\r\n",
+                "Content-Type: application/octet-stream; name=\"encrypted.asc\"\r\n",
+                "\r\n",
+                "{}\r\n",
+                "--enc--\r\n",
+            ),
+            encrypted
+        )
+        .into_bytes()
+    }
+
+    fn smime_keys() -> (DecryptionKeys, &'static Certificate) {
+        let (key, cert) = identity();
+        let mut store = KeyStore::new();
+        let chain = PrivateKeyChain::new(
+            key.to_pkcs8_der().unwrap().as_bytes(),
+            b"archive",
+            [p12_keystore::Certificate::from_der(&cert.to_der().unwrap()).unwrap()],
+        );
+        store.add_entry("archive", KeyStoreEntry::PrivateKeyChain(chain));
+        let bundle = store.writer("secret").write().unwrap();
+        let encoded = general_purpose::STANDARD.encode(bundle);
+        assert!(validate_smime_key(&encoded, Some("wrong")).is_err());
+        let keys = DecryptionKeys {
+            pgp: None,
+            smime: Some(load_smime_key(&encoded, Some("secret")).unwrap()),
+        };
+        (keys, cert)
+    }
+
+    fn smime_message(cert: &Certificate) -> Vec<u8> {
+        let mut rng = OsRng;
+        let recipient = KeyTransRecipientInfoBuilder::new(
+            RecipientIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                issuer: cert.tbs_certificate.issuer.clone(),
+                serial_number: cert.tbs_certificate.serial_number.clone(),
+            }),
+            KeyEncryptionInfo::Rsa(
+                RsaPublicKey::from_public_key_der(
+                    &cert
+                        .tbs_certificate
+                        .subject_public_key_info
+                        .to_der()
+                        .unwrap(),
+                )
+                .unwrap(),
+            ),
+            &mut rng,
+        )
+        .unwrap();
+        let enveloped = EnvelopedDataBuilder::new(
+            None,
+            ENTITY.as_bytes(),
+            ContentEncryptionAlgorithm::Aes256Cbc,
+            None,
+        )
+        .unwrap()
+        .add_recipient_info(recipient)
+        .unwrap()
+        .build_with_rng(&mut OsRng)
+        .unwrap();
+        let info = ContentInfo {
+            content_type: rfc5911::ID_ENVELOPED_DATA,
+            content: Any::encode_from(&enveloped).unwrap(),
+        };
+        let body = general_purpose::STANDARD.encode(info.to_der().unwrap());
+        format!(
+            concat!(
+                "From: alice@example.com\r\n",
+                "To: archive@example.com\r\n",
+                "Subject: Launch\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: application/pkcs7-mime; smime-type=enveloped-data;\r\n",
+                " name=\"smime.p7m\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "{}\r\n",
+            ),
+            body
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn decrypts_smime_message() {
+        let (keys, cert) = smime_keys();
+        let raw = smime_message(cert);
+        let message = parse(&raw);
+        assert!(is_encrypted(&message));
+        assert!(message.body_text(0).is_none());
+
+        let plaintext = decrypt_message(&keys, &message).unwrap().unwrap();
+        assert_decrypted(&plaintext);
+    }
+
+    #[test]
+    fn decrypts_openpgp_message() {
+        let armored = pgp_key(None);
+        let raw = pgp_message(&armored, false);
+        let message = parse(&raw);
+        assert!(is_encrypted(&message));
+
+        let plaintext = decrypt_message(&pgp_keys(&armored, None), &message)
+            .unwrap()
+            .unwrap();
+        assert_decrypted(&plaintext);
+    }
+
+    #[test]
+    fn decrypts_compressed_openpgp_message() {
+        let armored = pgp_key(None);
+        let raw = pgp_message(&armored, true);
+        let plaintext = decrypt_message(&pgp_keys(&armored, None), &parse(&raw))
+            .unwrap()
+            .unwrap();
+        assert_decrypted(&plaintext);
+    }
+
+    #[test]
+    fn passphrase_protected_openpgp_key_needs_its_passphrase() {
+        let armored = pgp_key(Some("correct horse"));
+        let raw = pgp_message(&armored, false);
+        let message = parse(&raw);
+
+        let keys = pgp_keys(&armored, Some("correct horse"));
+        let plaintext = decrypt_message(&keys, &message).unwrap().unwrap();
+        assert_decrypted(&plaintext);
+
+        let keys = pgp_keys(&armored, Some("wrong"));
+        assert!(decrypt_message(&keys, &message).is_err());
+    }
+
+    #[test]
+    fn validation_requires_the_passphrase_to_unlock_the_key() {
+        let armored = pgp_key(Some("correct horse"));
+        validate_pgp_key(&armored, Some("correct horse")).unwrap();
+        assert!(validate_pgp_key(&armored, Some("wrong")).is_err());
+        assert!(validate_pgp_key(&armored, None).is_err());
+
+        validate_pgp_key(&pgp_key(None), None).unwrap();
+        assert!(validate_pgp_key("not a key", None).is_err());
+    }
+
+    #[test]
+    fn missing_key_leaves_message_encrypted() {
+        let (_, cert) = smime_keys();
+        let raw = smime_message(cert);
+        let keys = DecryptionKeys {
+            pgp: None,
+            smime: None,
+        };
+        assert!(decrypt_message(&keys, &parse(&raw)).unwrap().is_none());
+    }
+
+    #[test]
+    fn detects_encrypted_formats() {
+        let pgp = concat!(
+            "Subject: Secret\r\n",
+            "Content-Type: multipart/encrypted; boundary=\"b\";\r\n",
+            " protocol=\"application/pgp-encrypted\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: application/pgp-encrypted\r\n",
+            "\r\n",
+            "Version: 1\r\n",
+            "--b\r\n",
+            "Content-Type: application/octet-stream; name=\"encrypted.asc\"\r\n",
+            "\r\n",
+            "-----BEGIN PGP MESSAGE-----\r\n",
+            "\r\n",
+            "hQEMA1234\r\n",
+            "-----END PGP MESSAGE-----\r\n",
+            "--b--\r\n",
+        );
+        let message = parse(pgp.as_bytes());
+        match encrypted_payload(&message) {
+            Some(EncryptedPayload::OpenPgp(payload)) => {
+                assert!(payload.starts_with(b"-----BEGIN PGP MESSAGE-----"))
+            }
+            _ => panic!("expected an OpenPGP payload"),
+        }
+
+        let signed = concat!(
+            "Subject: Signed\r\n",
+            "Content-Type: application/pkcs7-mime; smime-type=signed-data\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "MIAGCSqGSIb3DQEHAqCAMIACAQEx\r\n",
+        );
+        assert!(!is_encrypted(&parse(signed.as_bytes())));
+
+        let plain = "Subject: Hello\r\nContent-Type: text/plain\r\n\r\nHi\r\n";
+        assert!(!is_encrypted(&parse(plain.as_bytes())));
+    }
+
+    #[test]
+    fn rebuild_drops_outer_content_headers() {
+        let header_block = concat!(
+            "From: alice@example.com\r\n",
+            "Subject: Folded\r\n",
+            " subject\r\n",
+            "Content-Type: application/pkcs7-mime;\r\n",
+            " smime-type=enveloped-data\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "MIME-Version: 1.0\r\n",
+            "\r\n",
+        );
+        let rebuilt = rebuild_message(
+            header_block.as_bytes(),
+            b"Content-Type: text/plain\r\n\r\nHi\r\n",
+        );
+        assert_eq!(
+            String::from_utf8(rebuilt).unwrap(),
+            concat!(
+                "From: alice@example.com\r\n",
+                "Subject: Folded\r\n",
+                " subject\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Hi\r\n",
+            )
+        );
+    }
+}
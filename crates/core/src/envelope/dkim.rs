@@ -0,0 +1,684 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! DKIM signature verification (RFC 6376), with the Ed25519 algorithm of
+//! RFC 8463.
+//!
+//! Signing keys are looked up through a [`TxtResolver`] when a message is
+//! ingested. The TXT record each verdict was based on is kept with the
+//! result, since selectors are routinely rotated and revoked and a later
+//! lookup would not tell what the key was when the message arrived.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::LazyLock;
+
+use base64::{engine::general_purpose, Engine as _};
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use hickory_resolver::proto::rr::RData;
+use hickory_resolver::TokioResolver;
+use ring::digest::{self, SHA256};
+use ring::signature::{UnparsedPublicKey, ED25519, RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY};
+
+use crate::envelope::authentication::{DkimResult, VerificationStatus};
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::raise_error;
+
+/// Signatures checked per message; later ones are ignored.
+const MAX_SIGNATURES: usize = 5;
+
+pub type TxtFuture<'a> = Pin<Box<dyn Future<Output = BichonResult<Vec<String>>> + Send + 'a>>;
+
+/// Source of the DNS TXT records holding DKIM keys.
+pub trait TxtResolver: Send + Sync {
+    /// Returns the TXT records of `name`, each with its strings joined. A
+    /// name without TXT records yields an empty list, not an error.
+    fn lookup_txt<'a>(&'a self, name: &'a str) -> TxtFuture<'a>;
+}
+
+/// [`TxtResolver`] using the system's DNS configuration.
+pub struct DnsTxtResolver;
+
+static DNS_RESOLVER: LazyLock<Option<TokioResolver>> = LazyLock::new(|| {
+    TokioResolver::builder(TokioRuntimeProvider::default())
+        .ok()?
+        .build()
+        .ok()
+});
+
+impl TxtResolver for DnsTxtResolver {
+    fn lookup_txt<'a>(&'a self, name: &'a str) -> TxtFuture<'a> {
+        Box::pin(async move {
+            let Some(resolver) = DNS_RESOLVER.as_ref() else {
+                return Err(raise_error!(
+                    "DNS resolver is not available".into(),
+                    ErrorCode::NetworkError
+                ));
+            };
+            let lookup = match resolver.txt_lookup(format!("{name}.")).await {
+                Ok(lookup) => lookup,
+                Err(e) if e.is_no_records_found() => return Ok(Vec::new()),
+                Err(e) => {
+                    return Err(raise_error!(
+                        format!("TXT lookup of {name} failed: {e}"),
+                        ErrorCode::NetworkError
+                    ))
+                }
+            };
+            Ok(lookup
+                .answers()
+                .iter()
+                .filter_map(|record| match &record.data {
+                    RData::TXT(txt) => Some(
+                        txt.txt_data
+                            .iter()
+                            .map(|s| String::from_utf8_lossy(s))
+                            .collect::<String>(),
+                    ),
+                    _ => None,
+                })
+                .collect())
+        })
+    }
+}
+
+/// [`TxtResolver`] for hosts that must not query DNS: every lookup fails,
+/// so signatures are reported as `Unknown` rather than checked.
+pub struct OfflineTxtResolver;
+
+impl TxtResolver for OfflineTxtResolver {
+    fn lookup_txt<'a>(&'a self, _name: &'a str) -> TxtFuture<'a> {
+        Box::pin(async {
+            Err(raise_error!(
+                "DNS lookups are disabled".into(),
+                ErrorCode::NetworkError
+            ))
+        })
+    }
+}
+
+/// Verifies the DKIM signatures of `raw`, a complete message as received.
+/// `received` (seconds since the Unix epoch), when the message arrived, is
+/// checked against the `x=` expiry: an archived message stays valid after
+/// its signature expires.
+/// Returns one result per signature, in header order.
+pub async fn verify_dkim(raw: &[u8], resolver: &dyn TxtResolver, received: i64) -> Vec<DkimResult> {
+    let raw = to_crlf(raw);
+    let (fields, body) = split_message(&raw);
+    let mut results = Vec::new();
+    for field in fields
+        .iter()
+        .filter(|f| f.name.eq_ignore_ascii_case("dkim-signature"))
+        .take(MAX_SIGNATURES)
+    {
+        let mut result = DkimResult::default();
+        match verify_signature(&mut result, field, &fields, body, resolver, received).await {
+            Ok(()) => result.status = VerificationStatus::Pass,
+            Err(failure) => {
+                result.status = failure.status;
+                result.error = Some(failure.reason);
+            }
+        }
+        results.push(result);
+    }
+    results
+}
+
+/// Why a signature did not verify. A `Fail` status means the signature is
+/// invalid, `Unknown` that it could not be checked (a DNS failure or no key
+/// published under the selector).
+struct Failure {
+    status: VerificationStatus,
+    reason: String,
+}
+
+fn fail(reason: impl Into<String>) -> Failure {
+    Failure {
+        status: VerificationStatus::Fail,
+        reason: reason.into(),
+    }
+}
+
+async fn verify_signature(
+    result: &mut DkimResult,
+    signature: &HeaderField<'_>,
+    fields: &[HeaderField<'_>],
+    body: &[u8],
+    resolver: &dyn TxtResolver,
+    received: i64,
+) -> Result<(), Failure> {
+    let value =
+        std::str::from_utf8(signature.value).map_err(|_| fail("signature is not valid ASCII"))?;
+    let tags = parse_tags(value).ok_or_else(|| fail("malformed tag list"))?;
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let required = |name: &str| tag(name).ok_or_else(|| fail(format!("missing {name}= tag")));
+
+    let domain = required("d")?.to_ascii_lowercase();
+    result.domain = domain.clone();
+    let selector = required("s")?.to_ascii_lowercase();
+    result.selector = selector.clone();
+    let algorithm = required("a")?.to_ascii_lowercase();
+    result.algorithm = algorithm.clone();
+    if required("v")? != "1" {
+        return Err(fail("unsupported version"));
+    }
+    let signed_headers: Vec<&str> = required("h")?.split(':').map(str::trim).collect();
+    if !signed_headers
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case("from"))
+    {
+        return Err(fail("From header is not signed"));
+    }
+    if let Some(identity) = tag("i") {
+        let identity_domain = identity
+            .rsplit_once('@')
+            .map_or(identity, |(_, d)| d)
+            .to_ascii_lowercase();
+        if identity_domain != domain && !identity_domain.ends_with(&format!(".{domain}")) {
+            return Err(fail("i= is not within the signing domain"));
+        }
+    }
+    if let Some(expires) = tag("x") {
+        let expires: i64 = expires.parse().map_err(|_| fail("malformed x= tag"))?;
+        if expires < received {
+            return Err(fail("signature has expired"));
+        }
+    }
+    let (header_relaxed, body_relaxed) = match tag("c").unwrap_or("simple") {
+        "simple" | "simple/simple" => (false, false),
+        "simple/relaxed" => (false, true),
+        "relaxed" | "relaxed/simple" => (true, false),
+        "relaxed/relaxed" => (true, true),
+        other => return Err(fail(format!("unknown canonicalization {other}"))),
+    };
+    let key_type = match algorithm.as_str() {
+        "rsa-sha256" => "rsa",
+        "ed25519-sha256" => "ed25519",
+        // rsa-sha1 is no longer acceptable (RFC 8301).
+        other => return Err(fail(format!("unsupported algorithm {other}"))),
+    };
+    let body_hash = decode_base64(required("bh")?)?;
+    let signature_bytes = decode_base64(required("b")?)?;
+
+    let mut canonical_body = canonicalize_body(body, body_relaxed);
+    if let Some(length) = tag("l") {
+        let length: usize = length.parse().map_err(|_| fail("malformed l= tag"))?;
+        if length > canonical_body.len() {
+            return Err(fail("l= exceeds the body length"));
+        }
+        canonical_body.truncate(length);
+    }
+    if digest::digest(&SHA256, &canonical_body).as_ref() != body_hash.as_slice() {
+        return Err(fail("body hash mismatch"));
+    }
+
+    let name = format!("{selector}._domainkey.{domain}");
+    let unknown = |reason: String| Failure {
+        status: VerificationStatus::Unknown,
+        reason,
+    };
+    let records = resolver
+        .lookup_txt(&name)
+        .await
+        .map_err(|e| unknown(e.to_string()))?;
+    // A missing key is a permanent error (RFC 6376 6.1.2), but says nothing
+    // about whether the message was altered.
+    let (record, key) = records
+        .iter()
+        .find_map(|record| parse_key_record(record).map(|key| (record, key)))
+        .ok_or_else(|| unknown(format!("no key record at {name}")))?;
+    result.key_record = Some(record.clone());
+    if key.public_key.is_empty() {
+        return Err(fail("key has been revoked"));
+    }
+    if !key.key_type.eq_ignore_ascii_case(key_type) {
+        return Err(fail("key type does not match the algorithm"));
+    }
+    if key
+        .hashes
+        .as_ref()
+        .is_some_and(|hashes| !hashes.iter().any(|h| h.eq_ignore_ascii_case("sha256")))
+    {
+        return Err(fail("key does not allow sha256"));
+    }
+
+    let mut data = Vec::new();
+    let mut used = vec![false; fields.len()];
+    for name in &signed_headers {
+        // Repeated names select instances from the bottom up; names with no
+        // instance left contribute nothing.
+        if let Some(i) = (0..fields.len())
+            .rev()
+            .find(|&i| !used[i] && fields[i].name.eq_ignore_ascii_case(name))
+        {
+            used[i] = true;
+            canonicalize_header(&mut data, &fields[i], fields[i].value, header_relaxed);
+        }
+    }
+    let unsigned = strip_signature_value(signature.value);
+    canonicalize_header(&mut data, signature, &unsigned, header_relaxed);
+    // The signature header is hashed without its line break.
+    data.truncate(data.len() - 2);
+
+    let public_key = decode_base64(&key.public_key)?;
+    let verified = match key_type {
+        "rsa" => {
+            let rsa_key = spki_public_key(&public_key).unwrap_or(&public_key);
+            UnparsedPublicKey::new(&RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY, rsa_key)
+                .verify(&data, &signature_bytes)
+        }
+        _ => UnparsedPublicKey::new(&ED25519, &public_key)
+            .verify(digest::digest(&SHA256, &data).as_ref(), &signature_bytes),
+    };
+    verified.map_err(|_| fail("signature mismatch"))
+}
+
+/// A header field: its name and its value as it appears after the colon,
+/// including folding and the final CRLF.
+struct HeaderField<'a> {
+    name: &'a str,
+    value: &'a [u8],
+}
+
+/// Splits a CRLF message into its header fields and its body.
+fn split_message(raw: &[u8]) -> (Vec<HeaderField<'_>>, &[u8]) {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut pos = 0;
+    while pos < raw.len() {
+        let end = raw[pos..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map_or(raw.len(), |i| pos + i + 2);
+        if end == pos + 2 {
+            pos = end;
+            break;
+        }
+        match (raw[pos], spans.last_mut()) {
+            (b' ' | b'\t', Some(last)) => last.1 = end,
+            _ => spans.push((pos, end)),
+        }
+        pos = end;
+    }
+    let fields = spans
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let line = &raw[start..end];
+            let colon = line.iter().position(|&b| b == b':')?;
+            let name = std::str::from_utf8(&line[..colon]).ok()?.trim();
+            Some(HeaderField {
+                name,
+                value: &line[colon + 1..],
+            })
+        })
+        .collect();
+    (fields, &raw[pos.min(raw.len())..])
+}
+
+/// Archived messages may have bare LF line endings; DKIM hashes CRLF.
+pub(crate) fn to_crlf(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len() + raw.len() / 32);
+    for (i, &b) in raw.iter().enumerate() {
+        if b == b'\n' && (i == 0 || raw[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out
+}
+
+fn canonicalize_header(out: &mut Vec<u8>, field: &HeaderField<'_>, value: &[u8], relaxed: bool) {
+    if !relaxed {
+        out.extend_from_slice(field.name.as_bytes());
+        out.push(b':');
+        out.extend_from_slice(value);
+        if !value.ends_with(b"\r\n") {
+            out.extend_from_slice(b"\r\n");
+        }
+        return;
+    }
+    out.extend(field.name.to_ascii_lowercase().bytes());
+    out.push(b':');
+    let start = out.len();
+    let mut pending_space = false;
+    for &b in value {
+        match b {
+            b'\r' | b'\n' => {}
+            b' ' | b'\t' => pending_space = true,
+            _ => {
+                if pending_space && out.len() > start {
+                    out.push(b' ');
+                }
+                pending_space = false;
+                out.push(b);
+            }
+        }
+    }
+    out.extend_from_slice(b"\r\n");
+}
+
+fn canonicalize_body(body: &[u8], relaxed: bool) -> Vec<u8> {
+    let mut lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+    if body.ends_with(b"\n") {
+        lines.pop();
+    }
+    let mut out = Vec::with_capacity(body.len());
+    let mut empty_run = 0;
+    for line in lines {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let mut canonical = Vec::with_capacity(line.len());
+        if relaxed {
+            let mut pending_space = false;
+            for &b in line {
+                if b == b' ' || b == b'\t' {
+                    pending_space = true;
+                } else {
+                    if pending_space {
+                        canonical.push(b' ');
+                    }
+                    pending_space = false;
+                    canonical.push(b);
+                }
+            }
+        } else {
+            canonical.extend_from_slice(line);
+        }
+        // Trailing empty lines are ignored; inner ones are kept.
+        if canonical.is_empty() {
+            empty_run += 1;
+            continue;
+        }
+        for _ in 0..empty_run {
+            out.extend_from_slice(b"\r\n");
+        }
+        empty_run = 0;
+        out.extend_from_slice(&canonical);
+        out.extend_from_slice(b"\r\n");
+    }
+    if out.is_empty() && !relaxed {
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Empties the value of the `b=` tag, keeping everything else verbatim.
+fn strip_signature_value(value: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(value.len());
+    for (i, segment) in value.split(|&b| b == b';').enumerate() {
+        if i > 0 {
+            out.push(b';');
+        }
+        match segment.iter().position(|&b| b == b'=') {
+            Some(eq)
+                if segment[..eq]
+                    .iter()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .eq(b"b".iter()) =>
+            {
+                out.extend_from_slice(&segment[..=eq]);
+                // Keep the line break that ends the field.
+                if segment.ends_with(b"\r\n") {
+                    out.extend_from_slice(b"\r\n");
+                }
+            }
+            _ => out.extend_from_slice(segment),
+        }
+    }
+    out
+}
+
+/// Parses a `tag=value; ...` list. Whitespace around tags and values is
+/// dropped; duplicate tags make the list invalid.
+fn parse_tags(list: &str) -> Option<Vec<(&str, String)>> {
+    let mut tags: Vec<(&str, String)> = Vec::new();
+    for entry in list.split(';') {
+        if entry.trim().is_empty() {
+            continue;
+        }
+        let (name, value) = entry.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() || tags.iter().any(|(n, _)| *n == name) {
+            return None;
+        }
+        // Folding inside values is insignificant.
+        let value = value
+            .split(['\r', '\n'])
+            .collect::<String>()
+            .trim()
+            .to_string();
+        tags.push((name, value));
+    }
+    Some(tags)
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, Failure> {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    general_purpose::STANDARD
+        .decode(compact)
+        .map_err(|_| fail("malformed base64 value"))
+}
+
+/// The fields of a DKIM key record that verification relies on.
+struct KeyRecord {
+    key_type: String,
+    hashes: Option<Vec<String>>,
+    public_key: String,
+}
+
+fn parse_key_record(record: &str) -> Option<KeyRecord> {
+    let tags = parse_tags(record)?;
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.clone())
+    };
+    if tag("v").is_some_and(|v| v != "DKIM1") {
+        return None;
+    }
+    Some(KeyRecord {
+        key_type: tag("k").unwrap_or_else(|| "rsa".into()),
+        hashes: tag("h").map(|h| h.split(':').map(|s| s.trim().to_string()).collect()),
+        public_key: tag("p")?,
+    })
+}
+
+/// Extracts the key bits of a DER `SubjectPublicKeyInfo`, which is how DKIM
+/// publishes RSA keys. ring expects the bare `RSAPublicKey` inside.
+fn spki_public_key(der: &[u8]) -> Option<&[u8]> {
+    let (tag, info, _) = der_element(der)?;
+    if tag != 0x30 {
+        return None;
+    }
+    let (tag, _, rest) = der_element(info)?;
+    if tag != 0x30 {
+        return None;
+    }
+    let (tag, bits, _) = der_element(rest)?;
+    match (tag, bits.split_first()) {
+        (0x03, Some((0, key))) => Some(key),
+        _ => None,
+    }
+}
+
+/// Splits one DER element off `input`: (tag, contents, remainder).
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        (length, &rest[count..])
+    };
+    (rest.len() >= length).then(|| (tag, &rest[..length], &rest[length..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Ed25519 example of RFC 8463, Appendix A.
+    const SIGNED: &str = concat!(
+        "DKIM-Signature: v=1; a=ed25519-sha256; c=relaxed/relaxed;\r\n",
+        " d=football.example.com; i=@football.example.com;\r\n",
+        " q=dns/txt; s=brisbane; t=1528637909; h=from : to :\r\n",
+        " subject : date : message-id : from : subject : date;\r\n",
+        " bh=2jUSOH9NhtVGCQWNr9BrIAPreKQjO6Sn7XIkfJVOzv8=;\r\n",
+        " b=/gCrinpcQOoIfuHNQIbq4pgh9kyIK3AQUdt9OdqQehSwhEIug4D11Bus\r\n",
+        " Fa3bT3FY5OsU7ZbnKELq+eXdp1Q1Dw==\r\n",
+        "From: Joe SixPack <joe@football.example.com>\r\n",
+        "To: Suzie Q <suzie@shopping.example.net>\r\n",
+        "Subject: Is dinner ready?\r\n",
+        "Date: Fri, 11 Jul 2003 21:00:37 -0700 (PDT)\r\n",
+        "Message-ID: <20030712040037.46341.5F8J@football.example.com>\r\n",
+        "\r\n",
+        "Hi.\r\n",
+        "\r\n",
+        "We lost the game.  Are you hungry yet?\r\n",
+        "\r\n",
+        "Joe.\r\n",
+    );
+    const KEY_NAME: &str = "brisbane._domainkey.football.example.com";
+    const KEY_RECORD: &str = "v=DKIM1; k=ed25519; p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
+
+    /// Stands in for DNS: answers from a fixed table, or fails every lookup.
+    struct StaticResolver(Option<Vec<(&'static str, &'static str)>>);
+
+    impl TxtResolver for StaticResolver {
+        fn lookup_txt<'a>(&'a self, name: &'a str) -> TxtFuture<'a> {
+            Box::pin(async move {
+                match &self.0 {
+                    Some(records) => Ok(records
+                        .iter()
+                        .filter(|(n, _)| *n == name)
+                        .map(|(_, r)| r.to_string())
+                        .collect()),
+                    None => Err(raise_error!("SERVFAIL".into(), ErrorCode::NetworkError)),
+                }
+            })
+        }
+    }
+
+    async fn verify(raw: &str, records: Vec<(&'static str, &'static str)>) -> DkimResult {
+        let mut results = verify_dkim(raw.as_bytes(), &StaticResolver(Some(records)), 0).await;
+        assert_eq!(results.len(), 1);
+        results.remove(0)
+    }
+
+    #[tokio::test]
+    async fn verifies_rfc8463_example() {
+        let result = verify(SIGNED, vec![(KEY_NAME, KEY_RECORD)]).await;
+        assert_eq!(
+            result.status,
+            VerificationStatus::Pass,
+            "{:?}",
+            result.error
+        );
+        assert_eq!(result.domain, "football.example.com");
+        assert_eq!(result.selector, "brisbane");
+        assert_eq!(result.algorithm, "ed25519-sha256");
+        assert_eq!(result.key_record.as_deref(), Some(KEY_RECORD));
+
+        // Archived copies may have lost their CRLF line endings.
+        let lf = SIGNED.replace("\r\n", "\n");
+        let result = verify(&lf, vec![(KEY_NAME, KEY_RECORD)]).await;
+        assert_eq!(
+            result.status,
+            VerificationStatus::Pass,
+            "{:?}",
+            result.error
+        );
+    }
+
+    #[tokio::test]
+    async fn altered_messages_fail() {
+        let body = SIGNED.replace("We lost the game.", "We won the game.");
+        let result = verify(&body, vec![(KEY_NAME, KEY_RECORD)]).await;
+        assert_eq!(result.status, VerificationStatus::Fail);
+        assert_eq!(result.error.as_deref(), Some("body hash mismatch"));
+
+        let header = SIGNED.replace("Is dinner ready?", "Is lunch ready?");
+        let result = verify(&header, vec![(KEY_NAME, KEY_RECORD)]).await;
+        assert_eq!(result.status, VerificationStatus::Fail);
+        assert_eq!(result.error.as_deref(), Some("signature mismatch"));
+
+        // Whitespace changes are tolerated by relaxed canonicalization.
+        let reflowed = SIGNED.replace("Subject: Is dinner", "Subject:   Is  dinner");
+        let result = verify(&reflowed, vec![(KEY_NAME, KEY_RECORD)]).await;
+        assert_eq!(result.status, VerificationStatus::Pass);
+    }
+
+    #[tokio::test]
+    async fn key_lookup_outcomes() {
+        let missing = verify(SIGNED, Vec::new()).await;
+        assert_eq!(missing.status, VerificationStatus::Unknown);
+        assert_eq!(missing.key_record, None);
+
+        let revoked = verify(SIGNED, vec![(KEY_NAME, "v=DKIM1; k=ed25519; p=")]).await;
+        assert_eq!(revoked.status, VerificationStatus::Fail);
+        assert_eq!(revoked.error.as_deref(), Some("key has been revoked"));
+
+        let results = verify_dkim(SIGNED.as_bytes(), &StaticResolver(None), 0).await;
+        assert_eq!(results[0].status, VerificationStatus::Unknown);
+
+        let results = verify_dkim(SIGNED.as_bytes(), &OfflineTxtResolver, 0).await;
+        assert_eq!(results[0].status, VerificationStatus::Unknown);
+        assert_eq!(
+            results[0].error.as_deref(),
+            Some("DNS lookups are disabled")
+        );
+
+        let unsigned = "From: a@example.com\r\n\r\nHi\r\n";
+        assert!(verify_dkim(unsigned.as_bytes(), &StaticResolver(None), 0)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn expiry_is_checked_against_the_received_date() {
+        // Adding x= breaks the signature itself, so a message received in
+        // time gets past the expiry check only to fail on the signature.
+        let expiring = SIGNED.replace("t=1528637909;", "t=1528637909; x=1528641509;");
+        let resolver = StaticResolver(Some(vec![(KEY_NAME, KEY_RECORD)]));
+        let late = verify_dkim(expiring.as_bytes(), &resolver, 1528641510).await;
+        assert_eq!(late[0].status, VerificationStatus::Fail);
+        assert_eq!(late[0].error.as_deref(), Some("signature has expired"));
+
+        let in_time = verify_dkim(expiring.as_bytes(), &resolver, 1528637910).await;
+        assert_eq!(in_time[0].error.as_deref(), Some("signature mismatch"));
+    }
+
+    #[test]
+    fn canonicalizes_body() {
+        let body = b"a  b \t\r\n\r\nc\r\n\r\n\r\n";
+        assert_eq!(canonicalize_body(body, true), b"a b\r\n\r\nc\r\n");
+        assert_eq!(canonicalize_body(body, false), b"a  b \t\r\n\r\nc\r\n");
+        assert_eq!(canonicalize_body(b"", false), b"\r\n");
+        assert!(canonicalize_body(b"\r\n", true).is_empty());
+    }
+}
@@ -16,15 +16,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::account::migration::AccountModel;
+use crate::account::migration::{AccountModel, ExtractionRules};
 use crate::cache::imap::mailbox::MailBox;
 use crate::common::AddrVec;
+use crate::envelope::archive::{expand_archive, member_file_type, ArchiveFormat, ArchiveLimits};
+use crate::envelope::authentication::authenticate_message;
+use crate::envelope::decrypt::try_decrypt;
+use crate::envelope::flags::{flag_name, normalize_flags};
 use crate::envelope::meta::parse_bichon_metadata;
 use crate::envelope::utils::normalize_subject;
 use crate::error::code::ErrorCode;
 use crate::error::BichonResult;
 use crate::imap::executor::ImapExecutor;
-use crate::message::content::AttachmentInfo;
+use crate::message::content::{ArchiveMember, AttachmentInfo};
 use crate::store::blob::{DetachedEmail, BLOB_MANAGER};
 use crate::store::tantivy::attachment::ATTACHMENT_MANAGER;
 use crate::store::tantivy::dedup_cache::DEDUP_CACHE;
@@ -36,7 +40,8 @@ use crate::{id, store::envelope::Envelope};
 use crate::{raise_error, utc_now};
 use async_imap::types::Fetch;
 use bytes::Bytes;
-use mail_parser::{Address, HeaderName, Message, MessageParser, MimeHeaders};
+use mail_parser::{Address, HeaderName, Message, MessageParser, MessagePart, MimeHeaders};
+use std::ops::ControlFlow;
 use tantivy::TantivyDocument;
 use tantivy::schema::Facet;
 use tracing::error;
@@ -64,7 +69,17 @@ pub async fn extract_envelope_and_store_it(
         }
     };
     let size = fetch.size.unwrap_or(body.len() as u32);
-    extract_envelope_core(body, uid, size, internal_date, account_id, mailbox_id).await
+    let flags = normalize_flags(fetch.flags().filter_map(|f| flag_name(&f)));
+    extract_envelope_core(
+        body,
+        uid,
+        size,
+        internal_date,
+        flags,
+        account_id,
+        mailbox_id,
+    )
+    .await
 }
 
 pub async fn extract_envelope_from_eml(
@@ -72,30 +87,171 @@ pub async fn extract_envelope_from_eml(
     account_id: u64,
     mailbox_id: u64,
 ) -> BichonResult<()> {
-    extract_envelope_core(body, 0, body.len() as u32, 0, account_id, mailbox_id).await
+    extract_envelope_core(
+        body,
+        0,
+        body.len() as u32,
+        0,
+        Vec::new(),
+        account_id,
+        mailbox_id,
+    )
+    .await
+}
+
+/// Imported message whose flags were recovered from the source format,
+/// e.g. a Maildir file name or an Apple Mail `.emlx` plist.
+pub async fn extract_envelope_with_flags(
+    body: &[u8],
+    flags: Vec<String>,
+    account_id: u64,
+    mailbox_id: u64,
+) -> BichonResult<()> {
+    extract_envelope_core(
+        body,
+        0,
+        body.len() as u32,
+        0,
+        normalize_flags(flags),
+        account_id,
+        mailbox_id,
+    )
+    .await
 }
 
+/// Graph and JMAP messages have no IMAP UID; `uid` is the one assigned per
+/// mailbox when the message was first seen, so flag updates and deletions
+/// can find it.
+pub async fn extract_envelope_from_api(
+    body: &[u8],
+    uid: u32,
+    received_at: i64,
+    flags: Vec<String>,
+    account_id: u64,
+    mailbox_id: u64,
+) -> BichonResult<()> {
+    extract_envelope_core(
+        body,
+        uid,
+        body.len() as u32,
+        received_at,
+        normalize_flags(flags),
+        account_id,
+        mailbox_id,
+    )
+    .await
+}
+
+/// `received_at` is when the SMTP server accepted the message, which may be
+/// well before ingestion when it was replayed from the spool.
 pub async fn extract_envelope_from_smtp(
     body: &[u8],
     account_id: u64,
     mailbox_id: u64,
+    received_at: i64,
 ) -> BichonResult<()> {
     extract_envelope_core(
         body,
         0,
         body.len() as u32,
-        utc_now!(),
+        received_at,
+        Vec::new(),
         account_id,
         mailbox_id,
     )
     .await
 }
 
+/// Searchable fields derived from a parsed message.
+///
+/// Shared by ingestion and by the index rebuild, so both produce identical
+/// documents for the same EML.
+pub struct MessageFields {
+    pub body_text: String,
+    pub preview: String,
+    pub message_id: String,
+    pub thread_id: String,
+    pub subject: String,
+    pub date: i64,
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+}
+
+impl MessageFields {
+    pub fn from_message(message: &Message<'_>) -> Self {
+        let preview_limit = 100;
+        let text = if let Some(text) = message.body_text(0).map(|cow| cow.into_owned()) {
+            text
+        } else if let Some(html) = message.body_html(0).map(|cow| cow.into_owned()) {
+            extract_text(html)
+        } else {
+            String::new()
+        };
+
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let preview = if text.chars().count() > preview_limit {
+            text.chars().take(preview_limit).collect::<String>() + "..."
+        } else {
+            text.clone()
+        };
+
+        let message_id = message
+            .message_id()
+            .map(String::from)
+            .unwrap_or_else(generate_message_id);
+
+        let in_reply_to = message.in_reply_to().as_text().map(String::from);
+        let references = extract_references(message);
+        let thread_id = compute_thread_id(in_reply_to, references, &message_id);
+
+        let mut subject = message.subject().map(String::from).unwrap_or_default();
+        if subject.contains('\u{FFFD}') {
+            subject = normalize_subject(message.header_raw(HeaderName::Subject));
+        }
+
+        let date = message.date().map(|d| d.to_timestamp() * 1000).unwrap_or(0);
+        let parse_addrs = |addrs: Option<&Address<'_>>| {
+            addrs
+                .map(|addr| {
+                    AddrVec::from(addr)
+                        .0
+                        .into_iter()
+                        .filter_map(|a| a.address)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let from = message
+            .from()
+            .and_then(|addr| AddrVec::from(addr).0.into_iter().next())
+            .and_then(|add| add.address)
+            .unwrap_or_else(|| "unknown".to_string());
+
+        Self {
+            body_text: text,
+            preview,
+            message_id,
+            thread_id,
+            subject,
+            date,
+            from,
+            to: parse_addrs(message.to()),
+            cc: parse_addrs(message.cc()),
+            bcc: parse_addrs(message.bcc()),
+        }
+    }
+}
+
 async fn extract_envelope_core(
     body: &[u8],
     uid: u32,
     size: u32,
     internal_date: i64,
+    flags: Vec<String>,
     account_id: u64,
     mailbox_id: u64,
 ) -> BichonResult<()> {
@@ -113,7 +269,8 @@ async fn extract_envelope_core(
         )
     })?;
 
-    if let Ok(account) = AccountModel::get(account_id) {
+    let account = AccountModel::get(account_id).ok();
+    if let Some(account) = &account {
         if let Some(ref rules) = account.archive_rules {
             let sender = message.from().and_then(|addr| {
                 AddrVec::from(addr).0.into_iter().next().and_then(|a| a.address)
@@ -141,69 +298,56 @@ async fn extract_envelope_core(
         }
     }
 
-    let preview_limit = 100;
-    let text = if let Some(text) = message.body_text(0).map(|cow| cow.into_owned()) {
-        text
-    } else if let Some(html) = message.body_html(0).map(|cow| cow.into_owned()) {
-        extract_text(html)
-    } else {
-        String::new()
-    };
-
-    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
-
-    let preview = if text.chars().count() > preview_limit {
-        text.chars().take(preview_limit).collect::<String>() + "..."
-    } else {
-        text.clone()
-    };
-
-    let body_text = text;
-
-    let message_id = message
-        .message_id()
-        .map(String::from)
-        .unwrap_or_else(generate_message_id);
-
-    let in_reply_to = message.in_reply_to().as_text().map(String::from);
-    let references = extract_references(&message);
-    let thread_id = compute_thread_id(in_reply_to, references, &message_id);
-
-    let mut subject = message.subject().map(String::from).unwrap_or_default();
-    if subject.contains('\u{FFFD}') {
-        subject = normalize_subject(message.header_raw(HeaderName::Subject));
-    }
+    // Encrypted messages are indexed from their plaintext; the ciphertext
+    // EML is what gets preserved.
+    let plaintext = account
+        .as_ref()
+        .and_then(|account| try_decrypt(account, &message));
+    let decrypted = plaintext
+        .as_deref()
+        .and_then(|plaintext| MessageParser::new().parse(plaintext));
+
+    let MessageFields {
+        body_text,
+        preview,
+        message_id,
+        thread_id,
+        subject,
+        date,
+        from,
+        to,
+        cc,
+        bcc,
+    } = MessageFields::from_message(decrypted.as_ref().unwrap_or(&message));
 
-    let date = message.date().map(|d| d.to_timestamp() * 1000).unwrap_or(0);
     let internal_date = if internal_date == 0 {
         date
     } else {
         internal_date
     };
-    let parse_addrs = |addrs: Option<&Address<'_>>| {
-        addrs
-            .map(|addr| {
-                AddrVec::from(addr)
-                    .0
-                    .into_iter()
-                    .filter_map(|a| a.address)
-                    .collect()
-            })
-            .unwrap_or_default()
+    let attachments = detach_and_store_attachments(body, &message, decrypted.as_ref(), &email_content_hash, account_id, mailbox_id).await;
+    // DKIM covers the message as received; an S/MIME or OpenPGP signature
+    // inside an encrypted message is only visible in the plaintext.
+    let authentication = authenticate_message(
+        body,
+        decrypted.as_ref().unwrap_or(&message),
+        account.as_ref(),
+        &email_content_hash,
+        internal_date,
+    )
+    .await;
+    let summary = authentication.summary.clone();
+    if let Err(e) = authentication.save() {
+        tracing::warn!(account_id, error = %e, "Failed to store authentication results");
+    }
+    let attachment_count = attachments.len();
+    // Once decrypted, the ciphertext parts are not worth indexing on their own.
+    let ciphertext_parts = if decrypted.is_some() {
+        message.attachment_count()
+    } else {
+        0
     };
 
-    let bcc = parse_addrs(message.bcc());
-    let cc = parse_addrs(message.cc());
-    let to = parse_addrs(message.to());
-
-    let from = message
-        .from()
-        .and_then(|addr| AddrVec::from(addr).0.into_iter().next())
-        .and_then(|add| add.address)
-        .unwrap_or_else(|| "unknown".to_string());
-    let attachment_count = message.attachment_count();
-    let attachments = detach_and_store_attachments(body, &message, &email_content_hash, account_id, mailbox_id).await;
-
     let envelope_id = Uuid::new_v4().to_string();
     let now = utc_now!();
 
@@ -237,12 +381,16 @@ async fn extract_envelope_core(
         }
     }
 
-    let attachment_docs: Vec<TantivyDocument> = attachments
+    let regular_attachments: Vec<&AttachmentInfo> = attachments
         .iter()
+        .skip(ciphertext_parts)
         .filter(|a| !a.inline || a.content_id.is_none())
-        .map(|a| {
+        .collect();
+    let attachment_docs: Vec<TantivyDocument> = regular_attachments
+        .iter()
+        .flat_map(|a| {
             let has_text = a.extracted_text.is_some();
-            AttachmentModel {
+            let model = AttachmentModel {
                 id: Uuid::new_v4().to_string(),
                 envelope_id: envelope_id.clone(),
                 account_id,
@@ -258,7 +406,7 @@ async fn extract_envelope_core(
                 ext: a.get_extension(),
                 category: a.get_category().to_string(),
                 content_type: a.file_type.clone(),
-                shard_id: 0,
+                shard_id: account_id,
                 text: a.extracted_text.clone(),
                 has_text,
                 is_ocr: a.extracted_is_ocr,
@@ -268,7 +416,16 @@ async fn extract_envelope_core(
                 name: a.filename.clone(),
                 tags: None,
                 auto_tags: None,
-            }
+                archive_content_hash: None,
+            };
+            // Files inside an archive are indexed next to it.
+            let members: Vec<AttachmentModel> = a
+                .archive_members
+                .iter()
+                .flatten()
+                .map(|member| model.archive_member(member))
+                .collect();
+            std::iter::once(model).chain(members)
         })
         .map(|a| a.into_document())
         .collect();
@@ -291,8 +448,13 @@ async fn extract_envelope_core(
         size,
         thread_id,
         attachment_count,
-        regular_attachment_count: attachment_docs.len(),
+        regular_attachment_count: regular_attachments.len(),
         tags: (!final_tags.is_empty()).then_some(final_tags),
+        flags: (!flags.is_empty()).then_some(flags),
+        deleted_on_source: false,
+        labels: None,
+        gmail_msg_id: None,
+        authentication: Some(summary),
         account_email: None,
         mailbox_name: None,
         content_hash: email_content_hash.clone(),
@@ -303,7 +465,7 @@ async fn extract_envelope_core(
         envelope,
         attachments: Some(attachments),
     };
-    let doc = ea.to_document(&body_text, 0)?;
+    let doc = ea.to_document(&body_text, account_id)?;
     tracing::debug!(
         "[account {}][mailbox {}] extract: uid={} msg_id={} content_hash={}",
         account_id,
@@ -390,6 +552,11 @@ pub fn extract_envelope_from_nested_message(
         attachment_count: Default::default(),
         regular_attachment_count: Default::default(),
         tags: Default::default(),
+        flags: Default::default(),
+        deleted_on_source: Default::default(),
+        labels: Default::default(),
+        gmail_msg_id: Default::default(),
+        authentication: Default::default(),
         account_email: Default::default(),
         account_name: Default::default(),
         mailbox_name: Default::default(),
@@ -426,9 +593,78 @@ pub fn extract_references(message: &Message<'_>) -> Option<Vec<String>> {
     }
 }
 
+/// Describes one attachment of a parsed message, without its extracted text.
+pub fn attachment_info(att: &MessagePart<'_>, content_hash: String) -> AttachmentInfo {
+    AttachmentInfo {
+        filename: att.attachment_name().map(|n| n.to_string()),
+        size: att.contents().len(),
+        inline: att
+            .content_disposition()
+            .map(|d| d.is_inline())
+            .unwrap_or_else(|| att.content_id().is_some()),
+        file_type: att
+            .content_type()
+            .map(|ct| {
+                format!(
+                    "{}/{}",
+                    ct.c_type.as_ref(),
+                    ct.c_subtype.as_deref().unwrap_or("")
+                )
+            })
+            .unwrap_or_else(|| "application/octet-stream".to_string()),
+        content_id: att.content_id().map(|id| id.to_string()),
+        content_hash,
+        is_message: att.is_message(),
+        extracted_text: None,
+        extracted_page_count: None,
+        extracted_is_ocr: false,
+        archive_members: None,
+    }
+}
+
+/// Builds the record of a file expanded from an archive attachment. Its text
+/// is extracted under the same rules as that of a regular attachment.
+pub(crate) fn archive_member(
+    path: &str,
+    data: &[u8],
+    rules: Option<&ExtractionRules>,
+    mailbox_name: Option<&str>,
+    sender: Option<&str>,
+) -> ArchiveMember {
+    let mut member = ArchiveMember {
+        path: path.to_string(),
+        file_type: member_file_type(path),
+        size: data.len(),
+        content_hash: compute_content_hash(data),
+        ..Default::default()
+    };
+    let ext = member.get_extension().unwrap_or_default();
+    let should_extract = rules.is_none_or(|r| {
+        r.should_extract(&ext, mailbox_name, Some(member.file_name()), sender)
+    });
+    if should_extract
+        && data.len() <= crate::ext::text_extractor::MAX_EXTRACT_BYTES
+        && crate::ext::text_extractor::should_try_extract(&member.file_type, &ext)
+    {
+        if let Some(r) = crate::ext::text_extractor::extract_text(&member.file_type, &ext, data) {
+            member.extracted_text = Some(r.text);
+            member.extracted_page_count = r.page_count;
+            member.extracted_is_ocr = r.is_ocr;
+        }
+    }
+    member
+}
+
+/// Stores the attachments of `message` as blobs and the EML stripped of
+/// them under `eml_content_hash`.
+///
+/// The attachments of `decrypted`, the plaintext of an encrypted `message`,
+/// are stored and returned after those of `message`. They have no
+/// placeholder in the stored EML, which stays the ciphertext.
 pub async fn detach_and_store_attachments(
     original_body: &[u8],
     message: &Message<'_>,
+    decrypted: Option<&Message<'_>>,
     eml_content_hash: &str,
     account_id: u64,
     mailbox_id: u64,
@@ -467,6 +703,17 @@ pub async fn detach_and_store_attachments(
 
     ranges.sort_by(|a, b| b.0.cmp(&a.0));
     let mut attachments = Vec::with_capacity(ranges.len());
+    let decrypted_ranges = decrypted.into_iter().flat_map(|decrypted| {
+        decrypted.attachments().map(move |att| {
+            (
+                decrypted.raw_message(),
+                false,
+                att.raw_body_offset() as usize,
+                att.raw_end_offset() as usize,
+                att,
+            )
+        })
+    });
 
     // Collect candidates for text extraction (non-inline, known document types).
     struct TextCandidate {
@@ -476,11 +723,22 @@ pub async fn detach_and_store_attachments(
         bytes: Vec<u8>,
     }
     let mut text_candidates: Vec<TextCandidate> = Vec::new();
+    // Archive attachments, expanded in the same batch.
+    struct ArchiveCandidate {
+        content_hash: String,
+        format: ArchiveFormat,
+        name: String,
+        bytes: Vec<u8>,
+    }
+    let mut archive_candidates: Vec<ArchiveCandidate> = Vec::new();
 
-    for (raw_start, raw_end, att) in ranges {
+    let original_ranges = ranges
+        .into_iter()
+        .map(|(raw_start, raw_end, att)| (original_body, true, raw_start, raw_end, att));
+    for (source, strip, raw_start, raw_end, att) in original_ranges.chain(decrypted_ranges) {
         // mail-parser may report attachment offsets past the body end for
         // malformed messages; clamp the range to avoid a slice panic.
-        let body_len = original_body.len();
+        let body_len = source.len();
         let raw_start = raw_start.min(body_len);
         let raw_end = raw_end.min(body_len);
         let range_valid = raw_start < raw_end;
@@ -490,13 +748,15 @@ pub async fn detach_and_store_attachments(
         let content_hash = compute_content_hash(att.contents());
 
         if range_valid {
-            let raw_bytes = &original_body[raw_start..raw_end];
+            let raw_bytes = &source[raw_start..raw_end];
             // The actual content stored in the blob is the raw undecoded data.
             attachments.push((content_hash.clone(), Bytes::copy_from_slice(raw_bytes)));
 
-            // Replace raw attachment content with a hash-based placeholder
-            let placeholder = format!("<<BICHON_DETACH_HASH:{}>>", &content_hash);
-            stripped_eml.splice(raw_start..raw_end, placeholder.as_bytes().iter().cloned());
+            if strip {
+                // Replace raw attachment content with a hash-based placeholder
+                let placeholder = format!("<<BICHON_DETACH_HASH:{}>>", &content_hash);
+                stripped_eml.splice(raw_start..raw_end, placeholder.as_bytes().iter().cloned());
+            }
         } else {
             // Invalid range: store a zero-length blob so the consistency
             // check passes; reattachment will log a warning for the missing
@@ -504,20 +764,9 @@ pub async fn detach_and_store_attachments(
             attachments.push((content_hash.clone(), Bytes::new()));
         }
 
-        let inline = att
-            .content_disposition()
-            .map(|d| d.is_inline())
-            .unwrap_or_else(|| att.content_id().is_some());
-        let file_type = att
-            .content_type()
-            .map(|ct| {
-                format!(
-                    "{}/{}",
-                    ct.c_type.as_ref(),
-                    ct.c_subtype.as_deref().unwrap_or("")
-                )
-            })
-            .unwrap_or_else(|| "application/octet-stream".to_string());
+        let info = attachment_info(att, content_hash.clone());
+        let inline = info.inline;
+        let file_type = info.file_type.clone();
         let has_cid = att.content_id().is_some();
         let att_name = att.attachment_name().map(|n| n.to_string());
         let ext = att_name
@@ -552,27 +801,23 @@ pub async fn detach_and_store_attachments(
                     bytes: att.contents().to_vec(),
                 });
             }
+            let name = att_name.clone().unwrap_or_default();
+            if let Some(format) = ArchiveFormat::detect(&file_type, &name, att.contents()) {
+                archive_candidates.push(ArchiveCandidate {
+                    content_hash: content_hash.clone(),
+                    format,
+                    name,
+                    bytes: att.contents().to_vec(),
+                });
+            }
         }
 
-        let info = AttachmentInfo {
-            filename: att.attachment_name().map(|n| n.to_string()),
-            size: att.contents().len(),
-            inline,
-            file_type,
-            content_id: att.content_id().map(|id| id.to_string()),
-            content_hash: content_hash.clone(),
-            is_message: att.is_message(),
-            extracted_text: None,
-            extracted_page_count: None,
-            extracted_is_ocr: false,
-        };
-
         attachment_infos.push(info);
     }
 
-    // Run text extraction in a single spawn_blocking batch.
-    if !text_candidates.is_empty() {
-        if let Ok(mut extracted_map) = tokio::task::spawn_blocking(move || {
+    // Run text extraction and archive expansion in a single spawn_blocking batch.
+    if !text_candidates.is_empty() || !archive_candidates.is_empty() {
+        if let Ok((mut extracted_map, mut archive_map)) = tokio::task::spawn_blocking(move || {
             let mut map: std::collections::HashMap<
                 String,
                 (String, Option<u32>, bool),
@@ -584,7 +829,24 @@ pub async fn detach_and_store_attachments(
                     map.insert(c.content_hash, (r.text, r.page_count, r.is_ocr));
                 }
             }
-            map
+            let mut archives: std::collections::HashMap<String, Vec<ArchiveMember>> =
+                std::collections::HashMap::new();
+            for c in archive_candidates {
+                let mut members = Vec::new();
+                let limits = ArchiveLimits::default();
+                expand_archive(c.format, &c.name, &c.bytes, &limits, |path, data| {
+                    members.push(archive_member(
+                        path,
+                        data,
+                        rules.as_ref(),
+                        mailbox_name.as_deref(),
+                        sender.as_deref(),
+                    ));
+                    ControlFlow::Continue(())
+                });
+                archives.insert(c.content_hash, members);
+            }
+            (map, archives)
         })
         .await
         {
@@ -594,6 +856,9 @@ pub async fn detach_and_store_attachments(
                     info.extracted_page_count = pages;
                     info.extracted_is_ocr = is_ocr;
                 }
+                if let Some(members) = archive_map.remove(&info.content_hash) {
+                    info.archive_members = Some(members);
+                }
             }
         }
     }
@@ -751,7 +1016,7 @@ pub async fn reattach_eml_content_self_healing(
 /// Fails if the message cannot be fetched, or if the fetched bytes do not match
 /// the archived `content_hash` (the server-side message no longer matches what
 /// Bichon archived, so it cannot be treated as a recovery of that blob).
-async fn recover_message_blob(envelope: &Envelope) -> BichonResult<Bytes> {
+pub(crate) async fn recover_message_blob(envelope: &Envelope) -> BichonResult<Bytes> {
     let mailbox = MailBox::find_mailbox(envelope.account_id, envelope.mailbox_id)?
         .ok_or_else(|| {
             raise_error!(
@@ -793,7 +1058,13 @@ async fn recover_message_blob(envelope: &Envelope) -> BichonResult<Bytes> {
             ErrorCode::InternalError
         )
     })?;
-    detach_and_store_attachments(&raw_body, &message, &fetched_hash, envelope.account_id, envelope.mailbox_id).await;
+    let plaintext = AccountModel::get(envelope.account_id)
+        .ok()
+        .and_then(|account| try_decrypt(&account, &message));
+    let decrypted = plaintext
+        .as_deref()
+        .and_then(|plaintext| MessageParser::new().parse(plaintext));
+    detach_and_store_attachments(&raw_body, &message, decrypted.as_ref(), &fetched_hash, envelope.account_id, envelope.mailbox_id).await;
 
     Ok(Bytes::from(raw_body))
 }
@@ -889,6 +1160,7 @@ mod test {
         let infos = super::detach_and_store_attachments(
             truncated,
             &message,
+            None,
             "test_content_hash",
             0,
             0,
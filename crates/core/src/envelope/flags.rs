@@ -0,0 +1,245 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! IMAP flags and keywords as searchable envelope metadata.
+//!
+//! Flags live in the envelope index as facets below [`FLAG_FACET_ROOT`] in the
+//! same facet field as user tags, so existing indexes need no schema change.
+//! System flags and the common `$` keywords get a short, stable path
+//! (`/_flags/flagged`, `/_flags/junk`); every other keyword is lower-cased
+//! (IMAP keywords are case-insensitive) and stored below `/_flags/keyword/`.
+//!
+//! Messages expunged on the server stay in the archive and are marked with
+//! [`DELETED_ON_SOURCE_FACET`], another reserved facet outside the user's tags.
+
+use crate::envelope::archive::is_archive_facet;
+use crate::envelope::authentication::is_auth_facet;
+use crate::envelope::gmail::is_gmail_facet;
+use async_imap::types::Flag;
+use std::collections::BTreeSet;
+use tantivy::schema::Facet;
+
+/// Facet root holding IMAP flags. User tags may not be created below it.
+pub const FLAG_FACET_ROOT: &str = "/_flags";
+
+/// Facet marking envelopes whose message was expunged on the IMAP server.
+pub const DELETED_ON_SOURCE_FACET: &str = "/_source/deleted";
+
+const FLAG_ROOT_SEGMENT: &str = "_flags";
+const SOURCE_ROOT_SEGMENT: &str = "_source";
+const KEYWORD_SEGMENT: &str = "keyword";
+
+/// Flags with a well-known meaning: (IMAP name, facet segment).
+const KNOWN_FLAGS: &[(&str, &str)] = &[
+    ("\\Seen", "seen"),
+    ("\\Answered", "answered"),
+    ("\\Flagged", "flagged"),
+    ("\\Draft", "draft"),
+    ("\\Deleted", "deleted"),
+    ("$Forwarded", "forwarded"),
+    ("$Junk", "junk"),
+    ("$NotJunk", "notjunk"),
+    ("$Phishing", "phishing"),
+    ("$MDNSent", "mdnsent"),
+];
+
+/// Returns the IMAP name of a fetched flag, or `None` for session-only
+/// flags (`\Recent`, `\*`) that carry no information worth archiving.
+pub fn flag_name(flag: &Flag<'_>) -> Option<String> {
+    match flag {
+        Flag::Seen => Some("\\Seen".into()),
+        Flag::Answered => Some("\\Answered".into()),
+        Flag::Flagged => Some("\\Flagged".into()),
+        Flag::Deleted => Some("\\Deleted".into()),
+        Flag::Draft => Some("\\Draft".into()),
+        Flag::Recent | Flag::MayCreate => None,
+        Flag::Custom(name) => canonical_flag(name),
+    }
+}
+
+/// Normalizes a raw IMAP flag or keyword to the form kept on envelopes.
+/// Unknown system flags (`\Something`) are dropped.
+pub fn canonical_flag(flag: &str) -> Option<String> {
+    let flag = flag.trim();
+    if flag.is_empty() || flag == "\\*" {
+        return None;
+    }
+    if let Some((name, _)) = KNOWN_FLAGS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(flag))
+    {
+        return Some(name.to_string());
+    }
+    if flag.starts_with('\\') {
+        return None;
+    }
+    Some(flag.to_lowercase())
+}
+
+/// Canonicalizes, de-duplicates and sorts a flag list.
+pub fn normalize_flags<I, S>(flags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    flags
+        .into_iter()
+        .filter_map(|f| canonical_flag(f.as_ref()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Maps an IMAP flag or keyword to its index facet.
+pub fn flag_to_facet(flag: &str) -> Option<Facet> {
+    let flag = canonical_flag(flag)?;
+    match KNOWN_FLAGS.iter().find(|(name, _)| *name == flag) {
+        Some((_, segment)) => Some(Facet::from_path([FLAG_ROOT_SEGMENT, segment])),
+        None => Some(Facet::from_path([
+            FLAG_ROOT_SEGMENT,
+            KEYWORD_SEGMENT,
+            flag.as_str(),
+        ])),
+    }
+}
+
+/// Maps a search term to a flag facet. Besides IMAP names (`\Flagged`,
+/// `$Junk`, keywords) this accepts the short names of well-known flags
+/// (`flagged`, `seen`, `junk`).
+pub fn search_term_to_facet(term: &str) -> Option<Facet> {
+    let term = term.trim();
+    match KNOWN_FLAGS
+        .iter()
+        .find(|(_, segment)| segment.eq_ignore_ascii_case(term))
+    {
+        Some((_, segment)) => Some(Facet::from_path([FLAG_ROOT_SEGMENT, segment])),
+        None => flag_to_facet(term),
+    }
+}
+
+/// Maps a facet below [`FLAG_FACET_ROOT`] back to its IMAP flag name.
+pub fn facet_to_flag(facet: &Facet) -> Option<String> {
+    match facet.to_path().as_slice() {
+        [FLAG_ROOT_SEGMENT, KEYWORD_SEGMENT, keyword] => Some(keyword.to_string()),
+        [FLAG_ROOT_SEGMENT, segment] => KNOWN_FLAGS
+            .iter()
+            .find(|(_, s)| s == segment)
+            .map(|(name, _)| name.to_string()),
+        _ => None,
+    }
+}
+
+/// Whether a facet lies in the flag namespace.
+pub fn is_flag_facet(facet: &Facet) -> bool {
+    facet.to_path().first() == Some(&FLAG_ROOT_SEGMENT)
+}
+
+/// Whether a facet is maintained by Bichon (flags, source state, Gmail
+/// metadata, authentication results, archive membership) rather than being a
+/// user tag.
+pub fn is_reserved_facet(facet: &Facet) -> bool {
+    matches!(
+        facet.to_path().first(),
+        Some(&FLAG_ROOT_SEGMENT) | Some(&SOURCE_ROOT_SEGMENT)
+    ) || is_gmail_facet(facet)
+        || is_auth_facet(facet)
+        || is_archive_facet(facet)
+}
+
+/// The facet marking envelopes expunged on the server.
+pub fn deleted_on_source_facet() -> Facet {
+    Facet::from_path([SOURCE_ROOT_SEGMENT, "deleted"])
+}
+
+/// Builds the flag list for an IMAP `APPEND`, e.g. `(\Seen $Forwarded)`.
+/// `\Deleted` is left out so a restored message is not expunged right away.
+pub fn append_flag_list(flags: &[String]) -> Option<String> {
+    let flags: Vec<String> = normalize_flags(flags)
+        .into_iter()
+        .filter(|f| f != "\\Deleted")
+        .collect();
+    (!flags.is_empty()).then(|| format!("({})", flags.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalizes_system_flags_and_keywords() {
+        assert_eq!(canonical_flag("\\seen").as_deref(), Some("\\Seen"));
+        assert_eq!(canonical_flag("$junk").as_deref(), Some("$Junk"));
+        assert_eq!(canonical_flag("ProjectX").as_deref(), Some("projectx"));
+        assert_eq!(canonical_flag("\\Recent"), None);
+        assert_eq!(canonical_flag("\\*"), None);
+        assert_eq!(
+            normalize_flags(["\\Flagged", "\\flagged", "$Forwarded"]),
+            vec!["$Forwarded".to_string(), "\\Flagged".to_string()]
+        );
+    }
+
+    #[test]
+    fn facets_round_trip() {
+        for flag in ["\\Seen", "\\Flagged", "$Junk", "invoice/2019"] {
+            let facet = flag_to_facet(flag).unwrap();
+            assert!(is_flag_facet(&facet));
+            assert_eq!(facet_to_flag(&facet).as_deref(), Some(flag));
+        }
+        assert_eq!(
+            flag_to_facet("\\Flagged").unwrap().to_string(),
+            "/_flags/flagged"
+        );
+        assert_eq!(
+            flag_to_facet("Todo").unwrap().to_string(),
+            "/_flags/keyword/todo"
+        );
+        assert!(!is_flag_facet(&Facet::from_text("/inbox").unwrap()));
+    }
+
+    #[test]
+    fn reserved_facets_cover_flags_and_source_state() {
+        let deleted = deleted_on_source_facet();
+        assert_eq!(deleted.to_string(), DELETED_ON_SOURCE_FACET);
+        assert!(is_reserved_facet(&deleted));
+        assert!(!is_flag_facet(&deleted));
+        assert_eq!(facet_to_flag(&deleted), None);
+        assert!(is_reserved_facet(&flag_to_facet("\\Seen").unwrap()));
+        assert!(is_reserved_facet(&crate::envelope::gmail::msg_id_facet(1)));
+        assert!(is_reserved_facet(
+            &crate::envelope::authentication::signer_facet("alice@example.com")
+        ));
+        assert!(is_reserved_facet(&crate::envelope::archive::archive_facet(
+            "0123abcd"
+        )));
+        assert!(!is_reserved_facet(&Facet::from_text("/_other").unwrap()));
+    }
+
+    #[test]
+    fn search_terms_accept_short_names() {
+        assert_eq!(search_term_to_facet("flagged"), flag_to_facet("\\Flagged"));
+        assert_eq!(search_term_to_facet("JUNK"), flag_to_facet("$Junk"));
+        assert_eq!(search_term_to_facet("todo"), flag_to_facet("todo"));
+    }
+
+    #[test]
+    fn append_list_skips_deleted() {
+        let flags = vec!["\\Deleted".to_string(), "\\Seen".to_string()];
+        assert_eq!(append_flag_list(&flags).as_deref(), Some("(\\Seen)"));
+        assert_eq!(append_flag_list(&["\\Deleted".to_string()]), None);
+    }
+}
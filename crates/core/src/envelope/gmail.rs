@@ -0,0 +1,207 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gmail labels and message IDs as searchable envelope metadata.
+//!
+//! Gmail shows each message in `[Gmail]/All Mail` and again in every
+//! folder-like label. In Gmail sync mode only All Mail is downloaded; the
+//! `X-GM-MSGID` and `X-GM-LABELS` of each message are kept as facets below
+//! [`GMAIL_FACET_ROOT`], so labels work as virtual folders in search.
+//!
+//! System labels are renamed to what Google Takeout writes into
+//! `X-Gmail-Labels` (`\Inbox` becomes `Inbox`), so IMAP sync and mbox imports
+//! share one vocabulary and [`determine_folder`].
+
+use std::collections::{BTreeSet, HashSet};
+use tantivy::schema::Facet;
+
+/// Facet root holding Gmail metadata. User tags may not be created below it.
+pub const GMAIL_FACET_ROOT: &str = "/_gmail";
+
+const GMAIL_ROOT_SEGMENT: &str = "_gmail";
+const LABEL_SEGMENT: &str = "label";
+const MSGID_SEGMENT: &str = "msgid";
+
+/// Gmail system labels: (IMAP name, Takeout name).
+const SYSTEM_LABELS: &[(&str, &str)] = &[
+    ("\\Inbox", "Inbox"),
+    ("\\Sent", "Sent"),
+    ("\\Draft", "Drafts"),
+    ("\\Important", "Important"),
+    ("\\Starred", "Starred"),
+    ("\\Spam", "Spam"),
+    ("\\Trash", "Trash"),
+];
+
+/// Gmail metadata of one message as fetched from the server.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GmailAttributes {
+    pub msg_id: Option<u64>,
+    /// Normalized labels, see [`normalize_labels`].
+    pub labels: Vec<String>,
+}
+
+/// Normalizes a raw `X-GM-LABELS` entry. System labels get their Takeout
+/// name; unknown ones (`\Something`) simply lose the backslash.
+pub fn label_name(label: &str) -> Option<String> {
+    let label = label.trim();
+    if let Some((_, name)) = SYSTEM_LABELS
+        .iter()
+        .find(|(imap, _)| imap.eq_ignore_ascii_case(label))
+    {
+        return Some(name.to_string());
+    }
+    let label = label.strip_prefix('\\').unwrap_or(label).trim_matches('/');
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+/// Normalizes, de-duplicates and sorts a label list.
+pub fn normalize_labels<I, S>(labels: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    labels
+        .into_iter()
+        .filter_map(|l| label_name(l.as_ref()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Maps a label to its index facet; nested labels (`Work/Projects`) become
+/// nested facets.
+pub fn label_to_facet(label: &str) -> Option<Facet> {
+    let label = label_name(label)?;
+    let segments = [GMAIL_ROOT_SEGMENT, LABEL_SEGMENT]
+        .into_iter()
+        .chain(label.split('/').filter(|s| !s.is_empty()));
+    Some(Facet::from_path(segments))
+}
+
+/// Maps a label facet back to the label.
+pub fn facet_to_label(facet: &Facet) -> Option<String> {
+    match facet.to_path().as_slice() {
+        [GMAIL_ROOT_SEGMENT, LABEL_SEGMENT, rest @ ..] if !rest.is_empty() => Some(rest.join("/")),
+        _ => None,
+    }
+}
+
+/// The facet carrying a message's `X-GM-MSGID`.
+pub fn msg_id_facet(msg_id: u64) -> Facet {
+    Facet::from_path([GMAIL_ROOT_SEGMENT, MSGID_SEGMENT, &msg_id.to_string()])
+}
+
+/// Reads the `X-GM-MSGID` back from its facet.
+pub fn facet_to_msg_id(facet: &Facet) -> Option<u64> {
+    match facet.to_path().as_slice() {
+        [GMAIL_ROOT_SEGMENT, MSGID_SEGMENT, id] => id.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Whether a facet lies in the Gmail namespace.
+pub fn is_gmail_facet(facet: &Facet) -> bool {
+    facet.to_path().first() == Some(&GMAIL_ROOT_SEGMENT)
+}
+
+/// Picks the folder a message with the given comma-separated labels belongs
+/// in, e.g. for the `X-Gmail-Labels` header of a Takeout mbox.
+pub fn determine_folder(labels_raw: &str) -> String {
+    let mut status_blacklist = HashSet::new();
+    status_blacklist.insert("Opened");
+    status_blacklist.insert("Unread");
+    status_blacklist.insert("Archived");
+
+    let all_labels: Vec<&str> = labels_raw
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if all_labels.is_empty() {
+        return "Unknown".to_string();
+    }
+
+    let filtered: Vec<&str> = all_labels
+        .iter()
+        .filter(|&&l| !status_blacklist.contains(l))
+        .cloned()
+        .collect();
+
+    match filtered.len() {
+        // Case A: If all labels were status labels, fallback to the first original label
+        0 => all_labels[0].to_string(),
+        // Case B: If only one label remains, that's our target destination
+        1 => filtered[0].to_string(),
+        // Case C: Multiple labels remain (e.g., ["Inbox", "medium"])
+        _ => {
+            // Prioritize custom business labels by excluding generic locations like "Inbox" or "Sent"
+            let business_label = filtered.iter().find(|&&l| l != "Inbox" && l != "Sent");
+
+            match business_label {
+                // Return the first non-generic label found
+                Some(label) => label.to_string(),
+                // If only generic labels remain (e.g., ["Sent", "Inbox"]), pick the first available
+                None => filtered[0].to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_labels_use_takeout_names() {
+        assert_eq!(label_name("\\Inbox").as_deref(), Some("Inbox"));
+        assert_eq!(label_name("\\important").as_deref(), Some("Important"));
+        assert_eq!(label_name("\\Muted").as_deref(), Some("Muted"));
+        assert_eq!(
+            label_name("Work/Projects").as_deref(),
+            Some("Work/Projects")
+        );
+        assert_eq!(label_name("  "), None);
+        assert_eq!(
+            normalize_labels(["\\Inbox", "Receipts", "\\Inbox"]),
+            vec!["Inbox".to_string(), "Receipts".to_string()]
+        );
+    }
+
+    #[test]
+    fn facets_round_trip() {
+        let facet = label_to_facet("Work/Projects").unwrap();
+        assert_eq!(facet.to_string(), "/_gmail/label/Work/Projects");
+        assert_eq!(facet_to_label(&facet).as_deref(), Some("Work/Projects"));
+        assert!(is_gmail_facet(&facet));
+
+        let facet = msg_id_facet(1_278_455_344_230_334_865);
+        assert_eq!(facet_to_msg_id(&facet), Some(1_278_455_344_230_334_865));
+        assert_eq!(facet_to_label(&facet), None);
+        assert!(!is_gmail_facet(&Facet::from_text("/Work").unwrap()));
+    }
+
+    #[test]
+    fn imap_labels_pick_the_same_folder_as_takeout() {
+        let labels = normalize_labels(["\\Inbox", "\\Important", "Receipts"]);
+        assert_eq!(determine_folder(&labels.join(",")), "Important");
+        assert_eq!(determine_folder("Inbox, Opened"), "Inbox");
+        assert_eq!(determine_folder(""), "Unknown");
+    }
+}
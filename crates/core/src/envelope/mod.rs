@@ -16,6 +16,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod archive;
+pub mod authentication;
+pub mod decrypt;
+pub mod dkim;
 pub mod extractor;
+pub mod flags;
+pub mod gmail;
 pub mod meta;
+pub mod signature;
+pub mod smime;
 pub mod utils;
@@ -0,0 +1,542 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Verification of S/MIME (RFC 8551) and OpenPGP/MIME (RFC 3156) message
+//! signatures.
+//!
+//! An S/MIME signature passes when it matches the content and its
+//! certificate chains to the bundled Mozilla roots, the trust store TLS
+//! connections use too. A matching signature from an untrusted certificate
+//! is reported as unknown, with the certificate kept for review. OpenPGP
+//! has no trust store to fall back on: signatures are checked against the
+//! account's own key, so mail the owner signed can pass, and any other
+//! signer is unknown by key ID.
+
+use cms::{
+    cert::CertificateChoices,
+    signed_data::{SignedData, SignerIdentifier, SignerInfo},
+};
+use const_oid::{
+    db::{rfc3280, rfc4519, rfc5911, rfc5912},
+    ObjectIdentifier,
+};
+use mail_parser::{Message, MimeHeaders, PartType};
+use pgp::{types::PublicKeyTrait, Deserializable, SignedSecretKey, StandaloneSignature};
+use ring::{digest, signature as ring_signature};
+use rustls_pki_types::{CertificateDer, UnixTime};
+use x509_cert::{
+    der::{asn1::OctetString, pem::LineEnding, Encode, EncodePem},
+    ext::pkix::{name::GeneralName, SubjectAltName},
+    Certificate,
+};
+
+use crate::account::migration::AccountModel;
+use crate::envelope::authentication::{SignatureKind, VerificationStatus};
+use crate::envelope::decrypt::DecryptionKeys;
+use crate::envelope::dkim::to_crlf;
+use crate::envelope::smime::{content_info, issued_as, key_identifier};
+
+/// `id-kp-emailProtection` (1.3.6.1.5.5.7.3.4), DER content octets.
+const EMAIL_PROTECTION: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x04];
+
+/// Outcome of checking the signature of a message.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SignatureVerification {
+    pub kind: Option<SignatureKind>,
+    pub status: VerificationStatus,
+    /// Email address of the S/MIME signer, or the OpenPGP issuer key ID.
+    pub signer: Option<String>,
+    /// PEM certificate of the S/MIME signer.
+    pub certificate: Option<String>,
+    /// Issuer key ID of an OpenPGP signature, lower-case hex.
+    pub key_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl SignatureVerification {
+    fn new(kind: SignatureKind) -> Self {
+        Self {
+            kind: Some(kind),
+            ..Default::default()
+        }
+    }
+
+    fn failed(mut self, status: VerificationStatus, error: impl ToString) -> Self {
+        self.status = status;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// Verifies the signature of `message`, or returns `None` if it is not a
+/// signed message. OpenPGP signatures are checked with the key configured
+/// on `account`, if any.
+pub fn verify_message_signature(
+    message: &Message<'_>,
+    account: Option<&AccountModel>,
+) -> Option<SignatureVerification> {
+    let root = message.root_part();
+    let content_type = root.content_type()?;
+    let subtype = content_type.subtype().unwrap_or_default();
+    let ctype = content_type.ctype();
+    if ctype.eq_ignore_ascii_case("multipart") && subtype.eq_ignore_ascii_case("signed") {
+        let protocol = content_type.attribute("protocol")?.to_ascii_lowercase();
+        let PartType::Multipart(children) = &root.body else {
+            return None;
+        };
+        let signed = message.part(*children.first()?)?;
+        let signature = message.part(*children.get(1)?)?;
+        // The signed part is hashed exactly as transmitted, headers included,
+        // in canonical CRLF form.
+        let start = signed.raw_header_offset() as usize;
+        let end = (signed.raw_end_offset() as usize).min(message.raw_message().len());
+        let content = to_crlf(message.raw_message().get(start..end)?);
+        return match protocol.as_str() {
+            "application/pkcs7-signature" | "application/x-pkcs7-signature" => {
+                Some(verify_smime(signature.contents(), Some(&content)))
+            }
+            "application/pgp-signature" => {
+                let keys = account.and_then(|a| DecryptionKeys::for_account(a).ok().flatten());
+                let key = keys.as_ref().and_then(DecryptionKeys::pgp_key);
+                Some(verify_openpgp(signature.contents(), &content, key))
+            }
+            _ => None,
+        };
+    }
+    let opaque = ctype.eq_ignore_ascii_case("application")
+        && matches!(subtype, "pkcs7-mime" | "x-pkcs7-mime")
+        && content_type
+            .attribute("smime-type")
+            .is_some_and(|t| t.eq_ignore_ascii_case("signed-data"));
+    opaque.then(|| verify_smime(root.contents(), None))
+}
+
+/// Verifies a PKCS#7 signature over `content`, or over the content it
+/// embeds when `content` is `None`.
+fn verify_smime(ber: &[u8], content: Option<&[u8]>) -> SignatureVerification {
+    let mut result = SignatureVerification::new(SignatureKind::Smime);
+    let signed = match content_info(ber, rfc5911::ID_SIGNED_DATA)
+        .and_then(|any| any.decode_as::<SignedData>().map_err(|e| e.to_string()))
+    {
+        Ok(signed) => signed,
+        Err(e) => return result.failed(VerificationStatus::Fail, e),
+    };
+    let Some(signer) = signed.signer_infos.0.iter().next() else {
+        return result.failed(VerificationStatus::Fail, "no signer information");
+    };
+    let Some(cert) = signer_certificate(&signed, &signer.sid) else {
+        return result.failed(VerificationStatus::Unknown, "signer certificate is missing");
+    };
+    result.signer = certificate_email(cert);
+    result.certificate = cert.to_pem(LineEnding::LF).ok();
+
+    let embedded;
+    let content = match content {
+        Some(content) => content,
+        None => match signed
+            .encap_content_info
+            .econtent
+            .as_ref()
+            .map(|any| any.decode_as::<OctetString>())
+        {
+            Some(Ok(octets)) => {
+                embedded = octets.into_bytes();
+                &embedded
+            }
+            Some(Err(e)) => return result.failed(VerificationStatus::Fail, e),
+            None => return result.failed(VerificationStatus::Fail, "no signed content"),
+        },
+    };
+    if let Err(e) = verify_signer(signer, cert, content) {
+        return result.failed(VerificationStatus::Fail, e);
+    }
+    if is_trusted(&signed, cert) {
+        result.status = VerificationStatus::Pass;
+        result
+    } else {
+        result.failed(
+            VerificationStatus::Unknown,
+            "signer certificate is not trusted",
+        )
+    }
+}
+
+/// The certificate `sid` names among those the message carries.
+fn signer_certificate<'a>(
+    signed: &'a SignedData,
+    sid: &SignerIdentifier,
+) -> Option<&'a Certificate> {
+    signed
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(cert) => Some(cert),
+            CertificateChoices::Other(_) => None,
+        })
+        .find(|cert| match sid {
+            SignerIdentifier::IssuerAndSerialNumber(id) => issued_as(cert, id),
+            SignerIdentifier::SubjectKeyIdentifier(id) => key_identifier(cert).as_ref() == Some(id),
+        })
+}
+
+/// Checks the signature of `signer` over `content`. With signed
+/// attributes, the signature covers them and they carry the content digest.
+fn verify_signer(signer: &SignerInfo, cert: &Certificate, content: &[u8]) -> Result<(), String> {
+    let digest_alg = signer.digest_alg.oid;
+    let signed_bytes = match &signer.signed_attrs {
+        Some(attrs) => {
+            let expected = attrs
+                .iter()
+                .find(|attr| attr.oid == rfc5911::ID_MESSAGE_DIGEST)
+                .and_then(|attr| attr.values.iter().next())
+                .and_then(|value| value.decode_as::<OctetString>().ok())
+                .ok_or("message digest attribute is missing")?;
+            let actual = digest::digest(digest_algorithm(digest_alg)?, content);
+            if expected.as_bytes() != actual.as_ref() {
+                return Err("message digest mismatch".into());
+            }
+            attrs.to_der().map_err(|e| e.to_string())?
+        }
+        None => content.to_vec(),
+    };
+    let public_key = &cert.tbs_certificate.subject_public_key_info;
+    let algorithm = verification_algorithm(
+        signer.signature_algorithm.oid,
+        digest_alg,
+        public_key.algorithm.oid,
+        public_key
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|p| p.decode_as::<ObjectIdentifier>().ok()),
+    )?;
+    ring_signature::UnparsedPublicKey::new(algorithm, public_key.subject_public_key.raw_bytes())
+        .verify(&signed_bytes, signer.signature.as_bytes())
+        .map_err(|_| "signature mismatch".to_string())
+}
+
+fn digest_algorithm(oid: ObjectIdentifier) -> Result<&'static digest::Algorithm, String> {
+    Ok(match oid {
+        rfc5912::ID_SHA_1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        rfc5912::ID_SHA_256 => &digest::SHA256,
+        rfc5912::ID_SHA_384 => &digest::SHA384,
+        rfc5912::ID_SHA_512 => &digest::SHA512,
+        _ => return Err(format!("unsupported digest algorithm {oid}")),
+    })
+}
+
+/// Picks the verifier for an RSA PKCS#1 v1.5 or ECDSA signature. Legacy
+/// RSA key sizes are accepted: old mail stays verifiable.
+fn verification_algorithm(
+    signature: ObjectIdentifier,
+    digest: ObjectIdentifier,
+    key: ObjectIdentifier,
+    curve: Option<ObjectIdentifier>,
+) -> Result<&'static dyn ring_signature::VerificationAlgorithm, String> {
+    use ring_signature::*;
+    let rsa = matches!(
+        signature,
+        rfc5912::RSA_ENCRYPTION
+            | rfc5912::SHA_1_WITH_RSA_ENCRYPTION
+            | rfc5912::SHA_256_WITH_RSA_ENCRYPTION
+            | rfc5912::SHA_384_WITH_RSA_ENCRYPTION
+            | rfc5912::SHA_512_WITH_RSA_ENCRYPTION
+    );
+    let ecdsa = matches!(
+        signature,
+        rfc5912::ECDSA_WITH_SHA_256 | rfc5912::ECDSA_WITH_SHA_384 | rfc5912::ECDSA_WITH_SHA_512
+    );
+    Ok(match (key, curve, digest) {
+        (rfc5912::RSA_ENCRYPTION, _, rfc5912::ID_SHA_1) if rsa => {
+            &RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY
+        }
+        (rfc5912::RSA_ENCRYPTION, _, rfc5912::ID_SHA_256) if rsa => {
+            &RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY
+        }
+        (rfc5912::RSA_ENCRYPTION, _, rfc5912::ID_SHA_384) if rsa => &RSA_PKCS1_2048_8192_SHA384,
+        (rfc5912::RSA_ENCRYPTION, _, rfc5912::ID_SHA_512) if rsa => {
+            &RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY
+        }
+        (rfc5912::ID_EC_PUBLIC_KEY, Some(rfc5912::SECP_256_R_1), rfc5912::ID_SHA_256) if ecdsa => {
+            &ECDSA_P256_SHA256_ASN1
+        }
+        (rfc5912::ID_EC_PUBLIC_KEY, Some(rfc5912::SECP_256_R_1), rfc5912::ID_SHA_384) if ecdsa => {
+            &ECDSA_P256_SHA384_ASN1
+        }
+        (rfc5912::ID_EC_PUBLIC_KEY, Some(rfc5912::SECP_384_R_1), rfc5912::ID_SHA_256) if ecdsa => {
+            &ECDSA_P384_SHA256_ASN1
+        }
+        (rfc5912::ID_EC_PUBLIC_KEY, Some(rfc5912::SECP_384_R_1), rfc5912::ID_SHA_384) if ecdsa => {
+            &ECDSA_P384_SHA384_ASN1
+        }
+        _ => return Err(format!("unsupported signature algorithm {signature}")),
+    })
+}
+
+/// Whether `cert` chains to a trusted root, with the other certificates of
+/// the message as intermediates.
+fn is_trusted(signed: &SignedData, cert: &Certificate) -> bool {
+    let Ok(der) = cert.to_der().map(CertificateDer::from) else {
+        return false;
+    };
+    let Ok(end_entity) = webpki::EndEntityCert::try_from(&der) else {
+        return false;
+    };
+    let intermediates: Vec<CertificateDer<'static>> = signed
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(other) if other != cert => other.to_der().ok(),
+            _ => None,
+        })
+        .map(CertificateDer::from)
+        .collect();
+    end_entity
+        .verify_for_usage(
+            webpki::ALL_VERIFICATION_ALGS,
+            webpki_roots::TLS_SERVER_ROOTS,
+            &intermediates,
+            UnixTime::now(),
+            webpki::KeyUsage::required_if_present(EMAIL_PROTECTION),
+            None,
+            None,
+        )
+        .is_ok()
+}
+
+/// The signer's address: the first email subject alternative name, else
+/// the `emailAddress` or common name of the subject.
+fn certificate_email(cert: &Certificate) -> Option<String> {
+    let san = cert
+        .tbs_certificate
+        .get::<SubjectAltName>()
+        .ok()
+        .flatten()
+        .and_then(|(_, names)| {
+            names.0.into_iter().find_map(|name| match name {
+                GeneralName::Rfc822Name(email) => Some(email.to_string()),
+                _ => None,
+            })
+        });
+    san.or_else(|| {
+        [rfc3280::EMAIL_ADDRESS, rfc4519::CN]
+            .into_iter()
+            .find_map(|oid| {
+                let entry = cert
+                    .tbs_certificate
+                    .subject
+                    .0
+                    .iter()
+                    .flat_map(|rdn| rdn.0.iter())
+                    .find(|entry| entry.oid == oid)?;
+                std::str::from_utf8(entry.value.value())
+                    .ok()
+                    .map(str::to_string)
+            })
+    })
+    .map(|email| email.to_lowercase())
+}
+
+fn verify_openpgp(
+    armored: &[u8],
+    content: &[u8],
+    key: Option<&SignedSecretKey>,
+) -> SignatureVerification {
+    let mut result = SignatureVerification::new(SignatureKind::OpenPgp);
+    let signature = match std::str::from_utf8(armored)
+        .map_err(|e| e.to_string())
+        .and_then(|text| StandaloneSignature::from_string(text).map_err(|e| e.to_string()))
+    {
+        Ok((signature, _)) => signature,
+        Err(e) => return result.failed(VerificationStatus::Fail, e),
+    };
+    let issuers: Vec<String> = signature
+        .signature
+        .issuer()
+        .into_iter()
+        .map(|id| hex::encode(id.as_ref()))
+        .collect();
+    result.key_id = issuers.first().cloned();
+    result.signer = result.key_id.clone();
+
+    let Some(key) = key else {
+        return result.failed(VerificationStatus::Unknown, "signer key is not available");
+    };
+    let primary = std::iter::once((
+        hex::encode(key.key_id().as_ref()),
+        signature.verify(key, content).is_ok(),
+    ));
+    let subkeys = key.secret_subkeys.iter().map(|subkey| {
+        (
+            hex::encode(subkey.key_id().as_ref()),
+            signature.verify(subkey, content).is_ok(),
+        )
+    });
+    let mut own_key = false;
+    for (key_id, verified) in primary.chain(subkeys) {
+        if verified {
+            result.key_id = Some(key_id.clone());
+            result.signer = Some(key_id);
+            result.status = VerificationStatus::Pass;
+            return result;
+        }
+        own_key |= issuers.contains(&key_id);
+    }
+    if own_key {
+        result.failed(VerificationStatus::Fail, "signature mismatch")
+    } else {
+        result.failed(VerificationStatus::Unknown, "signer key is not available")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+    use cms::{
+        builder::{SignedDataBuilder, SignerInfoBuilder},
+        cert::IssuerAndSerialNumber,
+        signed_data::EncapsulatedContentInfo,
+    };
+    use mail_parser::MessageParser;
+    use rsa::{pkcs1v15::SigningKey, sha2::Sha256};
+    use x509_cert::{
+        der::{Any, Tag},
+        spki::AlgorithmIdentifierOwned,
+    };
+
+    use crate::envelope::smime::tests::identity;
+
+    const ENTITY: &str = "Content-Type: text/plain\r\n\r\nApproved.\r\n";
+
+    fn verify(raw: &str) -> Option<SignatureVerification> {
+        let message = MessageParser::default().parse(raw.as_bytes()).unwrap();
+        verify_message_signature(&message, None)
+    }
+
+    fn sign(detached: bool) -> String {
+        let (key, cert) = identity();
+        let signer = SigningKey::<Sha256>::new(key.clone());
+        let content = EncapsulatedContentInfo {
+            econtent_type: rfc5911::ID_DATA,
+            econtent: (!detached).then(|| Any::new(Tag::OctetString, ENTITY.as_bytes()).unwrap()),
+        };
+        let digest = digest::digest(&digest::SHA256, ENTITY.as_bytes());
+        let digest_alg = AlgorithmIdentifierOwned {
+            oid: rfc5912::ID_SHA_256,
+            parameters: None,
+        };
+        let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: cert.tbs_certificate.issuer.clone(),
+            serial_number: cert.tbs_certificate.serial_number.clone(),
+        });
+        let signer_info = SignerInfoBuilder::new(
+            &signer,
+            sid,
+            digest_alg.clone(),
+            &content,
+            detached.then_some(digest.as_ref()),
+        )
+        .unwrap();
+        let signed = SignedDataBuilder::new(&content)
+            .add_digest_algorithm(digest_alg)
+            .unwrap()
+            .add_certificate(CertificateChoices::Certificate(cert.clone()))
+            .unwrap()
+            .add_signer_info::<_, rsa::pkcs1v15::Signature>(signer_info)
+            .unwrap()
+            .build()
+            .unwrap();
+        general_purpose::STANDARD.encode(signed.to_der().unwrap())
+    }
+
+    fn detached_message() -> String {
+        format!(
+            concat!(
+                "From: alice@example.com\r\n",
+                "Subject: Budget\r\n",
+                "MIME-Version: 1.0\r\n",
+                "Content-Type: multipart/signed; micalg=sha-256; boundary=\"sig\";\r\n",
+                " protocol=\"application/pkcs7-signature\"\r\n",
+                "\r\n",
+                "--sig\r\n",
+                "{}",
+                "\r\n",
+                "--sig\r\n",
+                "Content-Type: application/pkcs7-signature; name=\"smime.p7s\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "{}\r\n",
+                "--sig--\r\n",
+            ),
+            ENTITY,
+            sign(true)
+        )
+    }
+
+    #[test]
+    fn untrusted_smime_signature_is_unknown() {
+        let result = verify(&detached_message()).unwrap();
+        assert_eq!(result.kind, Some(SignatureKind::Smime));
+        assert_eq!(result.status, VerificationStatus::Unknown);
+        assert_eq!(result.signer.as_deref(), Some("alice@example.com"));
+        assert!(result
+            .certificate
+            .unwrap()
+            .starts_with("-----BEGIN CERTIFICATE-----"));
+    }
+
+    #[test]
+    fn altered_smime_content_fails() {
+        let raw = detached_message().replace("Approved.", "Rejected.");
+        let result = verify(&raw).unwrap();
+        assert_eq!(result.status, VerificationStatus::Fail);
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn verifies_opaque_smime_signature() {
+        let raw = format!(
+            concat!(
+                "From: alice@example.com\r\n",
+                "Content-Type: application/pkcs7-mime; smime-type=signed-data;\r\n",
+                " name=\"smime.p7m\"\r\n",
+                "Content-Transfer-Encoding: base64\r\n",
+                "\r\n",
+                "{}\r\n",
+            ),
+            sign(false)
+        );
+        let result = verify(&raw).unwrap();
+        assert_eq!(result.status, VerificationStatus::Unknown);
+        assert_eq!(result.signer.as_deref(), Some("alice@example.com"));
+    }
+
+    #[test]
+    fn unsigned_messages_are_skipped() {
+        assert_eq!(verify("From: a@example.com\r\n\r\nHi\r\n"), None);
+        let enveloped = concat!(
+            "Content-Type: application/pkcs7-mime; smime-type=enveloped-data\r\n",
+            "\r\n",
+            "MIAGCSqGSIb3DQEHA6CAMIACAQAx\r\n",
+        );
+        assert_eq!(verify(enveloped), None);
+    }
+}
@@ -0,0 +1,303 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! CMS (RFC 5652) plumbing shared by S/MIME signature checks and
+//! decryption.
+//!
+//! The `cms` crate only reads DER, while mail clients commonly send BER:
+//! indefinite lengths and octet strings split into segments. Structures are
+//! re-encoded as DER before they are decoded.
+
+use cms::{cert::IssuerAndSerialNumber, content_info::ContentInfo};
+use const_oid::ObjectIdentifier;
+use x509_cert::{
+    der::{Any, Decode},
+    ext::pkix::SubjectKeyIdentifier,
+    Certificate,
+};
+
+/// Nesting deeper than any CMS structure needs; guards the recursion.
+const MAX_DEPTH: usize = 64;
+
+const OCTET_STRING: u8 = 0x04;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const CONSTRUCTED: u8 = 0x20;
+/// `[0]`, constructed.
+const CONTEXT_0: u8 = 0xa0;
+
+/// Decodes a BER `ContentInfo` and returns its content, which must be of
+/// type `expected`.
+pub(crate) fn content_info(ber: &[u8], expected: ObjectIdentifier) -> Result<Any, String> {
+    let der = ber_to_der(ber)?;
+    let info = ContentInfo::from_der(&der).map_err(|e| format!("malformed CMS structure: {e}"))?;
+    if info.content_type != expected {
+        return Err(format!("unexpected CMS content type {}", info.content_type));
+    }
+    Ok(info.content)
+}
+
+/// Whether `cert` is the one `id` names.
+pub(crate) fn issued_as(cert: &Certificate, id: &IssuerAndSerialNumber) -> bool {
+    cert.tbs_certificate.issuer == id.issuer
+        && cert.tbs_certificate.serial_number == id.serial_number
+}
+
+/// The subject key identifier extension of `cert`, if present.
+pub(crate) fn key_identifier(cert: &Certificate) -> Option<SubjectKeyIdentifier> {
+    let (_, id) = cert.tbs_certificate.get::<SubjectKeyIdentifier>().ok()??;
+    Some(id)
+}
+
+struct Tlv<'a> {
+    identifier: &'a [u8],
+    body: Body<'a>,
+}
+
+enum Body<'a> {
+    Primitive(&'a [u8]),
+    Constructed(Vec<Tlv<'a>>),
+}
+
+/// Re-encodes `ber` as DER: lengths become definite and segmented octet
+/// strings are joined.
+pub(crate) fn ber_to_der(ber: &[u8]) -> Result<Vec<u8>, String> {
+    let mut input = ber;
+    let tlv = read_tlv(&mut input, 0)?;
+    let mut der = Vec::with_capacity(ber.len());
+    write_tlv(&tlv, &mut der);
+    Ok(der)
+}
+
+fn read_tlv<'a>(input: &mut &'a [u8], depth: usize) -> Result<Tlv<'a>, String> {
+    let truncated = || "truncated BER data".to_string();
+    if depth > MAX_DEPTH {
+        return Err("BER data is nested too deeply".into());
+    }
+    let start = *input;
+    let first = *input.first().ok_or_else(truncated)?;
+    let mut identifier_len = 1;
+    if first & 0x1f == 0x1f {
+        // High tag number: base-128 continuation octets.
+        loop {
+            let byte = *start.get(identifier_len).ok_or_else(truncated)?;
+            identifier_len += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    let identifier = &start[..identifier_len];
+    *input = &start[identifier_len..];
+
+    let length = *input.first().ok_or_else(truncated)?;
+    *input = &input[1..];
+    if length == 0x80 {
+        if first & CONSTRUCTED == 0 {
+            return Err("indefinite length on a primitive BER element".into());
+        }
+        let mut children = Vec::new();
+        while !input.starts_with(&[0, 0]) {
+            if input.is_empty() {
+                return Err(truncated());
+            }
+            children.push(read_tlv(input, depth + 1)?);
+        }
+        *input = &input[2..];
+        return Ok(Tlv {
+            identifier,
+            body: Body::Constructed(children),
+        });
+    }
+    let length = if length & 0x80 == 0 {
+        length as usize
+    } else {
+        let count = (length & 0x7f) as usize;
+        if count > std::mem::size_of::<usize>() {
+            return Err("BER length is too large".into());
+        }
+        let bytes = input.get(..count).ok_or_else(truncated)?;
+        *input = &input[count..];
+        bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize)
+    };
+    let mut content = input.get(..length).ok_or_else(truncated)?;
+    *input = &input[length..];
+    if first & CONSTRUCTED == 0 {
+        return Ok(Tlv {
+            identifier,
+            body: Body::Primitive(content),
+        });
+    }
+    let mut children = Vec::new();
+    while !content.is_empty() {
+        children.push(read_tlv(&mut content, depth + 1)?);
+    }
+    Ok(Tlv {
+        identifier,
+        body: Body::Constructed(children),
+    })
+}
+
+fn write_tlv(tlv: &Tlv<'_>, out: &mut Vec<u8>) {
+    let children = match &tlv.body {
+        Body::Primitive(value) => return write_element(tlv.identifier, value, out),
+        Body::Constructed(children) => children,
+    };
+    if tlv.identifier == [OCTET_STRING | CONSTRUCTED] {
+        let mut value = Vec::new();
+        join_octets(tlv, &mut value);
+        return write_element(&[OCTET_STRING], &value, out);
+    }
+    if is_encrypted_content_info(tlv.identifier, children) {
+        // `encryptedContent` is an IMPLICIT [0] OCTET STRING, so its
+        // segments arrive under the context tag itself.
+        let (head, content) = children.split_at(2);
+        let mut value = Vec::new();
+        for child in head {
+            write_tlv(child, &mut value);
+        }
+        let mut octets = Vec::new();
+        join_octets(&content[0], &mut octets);
+        write_element(&[CONTEXT_0 & !CONSTRUCTED], &octets, &mut value);
+        return write_element(tlv.identifier, &value, out);
+    }
+    let mut value = Vec::new();
+    for child in children {
+        write_tlv(child, &mut value);
+    }
+    write_element(tlv.identifier, &value, out);
+}
+
+/// Whether `children` have the shape of an `EncryptedContentInfo`: a
+/// content type, an algorithm and a constructed `[0]` of octet strings.
+fn is_encrypted_content_info(identifier: &[u8], children: &[Tlv<'_>]) -> bool {
+    let [content_type, algorithm, content] = children else {
+        return false;
+    };
+    identifier == [SEQUENCE]
+        && content_type.identifier == [OBJECT_IDENTIFIER]
+        && algorithm.identifier == [SEQUENCE]
+        && content.identifier == [CONTEXT_0]
+        && matches!(&content.body, Body::Constructed(segments)
+            if segments.iter().all(|s| s.identifier[0] & !CONSTRUCTED == OCTET_STRING))
+}
+
+fn join_octets(tlv: &Tlv<'_>, out: &mut Vec<u8>) {
+    match &tlv.body {
+        Body::Primitive(value) => out.extend_from_slice(value),
+        Body::Constructed(children) => children.iter().for_each(|c| join_octets(c, out)),
+    }
+}
+
+fn write_element(identifier: &[u8], value: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(identifier);
+    let len = value.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(value);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rsa::{pkcs1v15::SigningKey, sha2::Sha256, RsaPrivateKey};
+    use std::{str::FromStr, sync::LazyLock, time::Duration};
+    use x509_cert::{
+        builder::{Builder, CertificateBuilder, Profile},
+        der::asn1::Ia5String,
+        ext::pkix::{name::GeneralName, SubjectAltName},
+        name::Name,
+        serial_number::SerialNumber,
+        spki::SubjectPublicKeyInfoOwned,
+        time::Validity,
+        Certificate,
+    };
+
+    static IDENTITY: LazyLock<(RsaPrivateKey, Certificate)> = LazyLock::new(|| {
+        let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, 2048).unwrap();
+        let signer = SigningKey::<Sha256>::new(key.clone());
+        let public_key = SubjectPublicKeyInfoOwned::from_key(key.to_public_key()).unwrap();
+        let mut builder = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(1u32),
+            Validity::from_now(Duration::from_secs(86_400)).unwrap(),
+            Name::from_str("CN=Alice").unwrap(),
+            public_key,
+            &signer,
+        )
+        .unwrap();
+        let email = Ia5String::new("Alice@Example.com").unwrap();
+        builder
+            .add_extension(&SubjectAltName(vec![GeneralName::Rfc822Name(email)]))
+            .unwrap();
+        let cert = builder.build::<rsa::pkcs1v15::Signature>().unwrap();
+        (key, cert)
+    });
+
+    /// A self-signed RSA identity for Alice@Example.com, shared by the
+    /// S/MIME tests since generating the key is slow.
+    pub fn identity() -> &'static (RsaPrivateKey, Certificate) {
+        &IDENTITY
+    }
+
+    #[test]
+    fn indefinite_lengths_and_segments_become_der() {
+        let ber = [
+            0x30, 0x80, // SEQUENCE, indefinite
+            0x24, 0x80, // OCTET STRING, constructed
+            0x04, 0x02, b'a', b'b', //
+            0x04, 0x01, b'c', //
+            0x00, 0x00, //
+            0x00, 0x00,
+        ];
+        assert_eq!(
+            ber_to_der(&ber).unwrap(),
+            [0x30, 0x05, 0x04, 0x03, b'a', b'b', b'c']
+        );
+    }
+
+    #[test]
+    fn segmented_encrypted_content_is_joined() {
+        let ber = [
+            0x30, 0x80, // EncryptedContentInfo
+            0x06, 0x01, 0x2a, // content type
+            0x30, 0x00, // algorithm
+            0xa0, 0x80, // [0], constructed
+            0x04, 0x01, 0x01, //
+            0x04, 0x01, 0x02, //
+            0x00, 0x00, //
+            0x00, 0x00,
+        ];
+        assert_eq!(
+            ber_to_der(&ber).unwrap(),
+            [0x30, 0x09, 0x06, 0x01, 0x2a, 0x30, 0x00, 0x80, 0x02, 0x01, 0x02]
+        );
+    }
+
+    #[test]
+    fn truncated_ber_is_rejected() {
+        assert!(ber_to_der(&[0x30, 0x80, 0x04, 0x02, b'a']).is_err());
+        assert!(ber_to_der(&[0x30, 0x05, 0x04]).is_err());
+    }
+}
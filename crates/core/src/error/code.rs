@@ -31,7 +31,11 @@ pub enum ErrorCode {
     ImapCommandFailed = 50000,
     ImapAuthenticationFailed = 50010,
     ImapUnexpectedResult = 50020,
+    Pop3CommandFailed = 50030,
+    Pop3AuthenticationFailed = 50040,
+    GraphRequestFailed = 50050,
     AutoconfigFetchFailed = 50060,
+    JmapRequestFailed = 50070,
     // Internal system errors (70000–70999)
     InternalError = 70000,
     UnhandledPoemError = 70010,
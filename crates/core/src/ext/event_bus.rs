@@ -97,6 +97,15 @@ pub enum Event {
         user: String,
         ip: IpAddr,
     },
+    /// Rejected WebUI password login or SMTP AUTH attempt.
+    LoginFailed {
+        /// The username as submitted (it may not exist).
+        user: String,
+        ip: Option<IpAddr>,
+        /// `"webui"` or `"smtp"`.
+        channel: String,
+        reason: String,
+    },
     UserCreated {
         created_by: String,
         new_user: String,
@@ -109,6 +118,15 @@ pub enum Event {
         removed_by: String,
         target_user: String,
     },
+    /// TOTP enrollment completed.
+    TwoFactorEnabled {
+        user: String,
+    },
+    /// TOTP turned off by the user, or reset by an administrator.
+    TwoFactorDisabled {
+        user: String,
+        target_user: String,
+    },
     RoleCreated {
         created_by: String,
         role_name: String,
@@ -216,6 +234,13 @@ pub enum Event {
         account_id: u64,
         mailbox_id: u64,
     },
+    /// Consistency check run with repairs that changed stored data.
+    StorageRepaired {
+        user: String,
+        attachments_deleted: u64,
+        envelopes_deleted: u64,
+        messages_recovered: u64,
+    },
     ProxyCreated {
         user: String,
         url: String,
@@ -0,0 +1,344 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The built-in [`AttachmentTextExtractor`]: plain text, CSV, HTML and
+//! Markdown, OOXML and OpenDocument office files, RTF, and text-layer PDFs,
+//! all in pure Rust.
+
+mod office;
+mod pdf;
+mod rtf;
+mod xml;
+
+use office::OfficeFormat;
+
+use crate::ext::text_extractor::{AttachmentTextExtractor, ExtractedText, MAX_EXTRACT_BYTES};
+use crate::utils::html;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Plain,
+    Html,
+    Rtf,
+    Pdf,
+    Office(OfficeFormat),
+}
+
+pub struct BuiltinExtractor;
+
+impl AttachmentTextExtractor for BuiltinExtractor {
+    fn extract(&self, content_type: &str, ext: &str, bytes: &[u8]) -> Option<ExtractedText> {
+        if bytes.len() > MAX_EXTRACT_BYTES {
+            return None;
+        }
+        let extracted = match detect(content_type, ext, bytes)? {
+            Format::Plain => plain(decode_text(bytes)),
+            Format::Html => plain(html::extract_text(decode_text(bytes))),
+            Format::Rtf => plain(rtf::to_text(bytes)),
+            Format::Pdf => pdf::extract(bytes)?,
+            Format::Office(format) => office::extract(format, bytes)?,
+        };
+        let text = normalize(&extracted.text);
+        if text.is_empty() {
+            return None;
+        }
+        Some(ExtractedText { text, ..extracted })
+    }
+}
+
+/// Picks the format from the file extension, then the content type, then
+/// the leading bytes, since mail clients often label attachments
+/// `application/octet-stream`.
+fn detect(content_type: &str, ext: &str, bytes: &[u8]) -> Option<Format> {
+    let by_ext = match ext {
+        "txt" | "text" | "csv" | "tsv" | "md" | "markdown" | "log" => Some(Format::Plain),
+        "html" | "htm" => Some(Format::Html),
+        "rtf" => Some(Format::Rtf),
+        "pdf" => Some(Format::Pdf),
+        "docx" | "docm" => Some(Format::Office(OfficeFormat::Docx)),
+        "xlsx" | "xlsm" => Some(Format::Office(OfficeFormat::Xlsx)),
+        "pptx" | "pptm" => Some(Format::Office(OfficeFormat::Pptx)),
+        "odt" => Some(Format::Office(OfficeFormat::Odt)),
+        "ods" => Some(Format::Office(OfficeFormat::Ods)),
+        "odp" => Some(Format::Office(OfficeFormat::Odp)),
+        _ => None,
+    };
+    if by_ext.is_some() {
+        return by_ext;
+    }
+
+    const OOXML: &str = "application/vnd.openxmlformats-officedocument.";
+    const ODF: &str = "application/vnd.oasis.opendocument.";
+    let content_type = content_type.to_ascii_lowercase();
+    let by_type = match content_type.as_str() {
+        "application/pdf" => Some(Format::Pdf),
+        "application/rtf" | "text/rtf" => Some(Format::Rtf),
+        "text/html" => Some(Format::Html),
+        t if t.starts_with(OOXML) => match &t[OOXML.len()..] {
+            s if s.starts_with("wordprocessingml.") => Some(Format::Office(OfficeFormat::Docx)),
+            s if s.starts_with("spreadsheetml.") => Some(Format::Office(OfficeFormat::Xlsx)),
+            s if s.starts_with("presentationml.") => Some(Format::Office(OfficeFormat::Pptx)),
+            _ => None,
+        },
+        t if t.starts_with(ODF) => match &t[ODF.len()..] {
+            "text" => Some(Format::Office(OfficeFormat::Odt)),
+            "spreadsheet" => Some(Format::Office(OfficeFormat::Ods)),
+            "presentation" => Some(Format::Office(OfficeFormat::Odp)),
+            _ => None,
+        },
+        t if t.starts_with("text/") => Some(Format::Plain),
+        _ => None,
+    };
+    if by_type.is_some() {
+        return by_type;
+    }
+
+    if bytes.starts_with(b"%PDF-") {
+        Some(Format::Pdf)
+    } else if bytes.starts_with(b"{\\rtf") {
+        Some(Format::Rtf)
+    } else {
+        None
+    }
+}
+
+fn plain(text: String) -> ExtractedText {
+    ExtractedText {
+        text,
+        page_count: None,
+        is_ocr: false,
+    }
+}
+
+/// Decodes a text attachment: by byte order mark if there is one, as UTF-8
+/// if it is valid, and as Windows-1252 otherwise.
+fn decode_text(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = encoding_rs::Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_len..])
+            .0
+            .into_owned();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+/// Cleans extracted text up for indexing: control characters other than tab
+/// and newline are dropped, trailing spaces are trimmed, runs of blank lines
+/// are collapsed, and the result is capped at [`MAX_EXTRACT_BYTES`].
+fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len().min(MAX_EXTRACT_BYTES));
+    let mut blank_lines = 0;
+    for line in text.lines() {
+        let line: String = line
+            .chars()
+            .filter(|c| !c.is_control() || *c == '\t')
+            .collect();
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        blank_lines = 0;
+        if out.len() + line.len() > MAX_EXTRACT_BYTES {
+            let mut end = MAX_EXTRACT_BYTES.saturating_sub(out.len());
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            out.push_str(&line[..end]);
+            break;
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(content_type: &str, ext: &str, bytes: &[u8]) -> ExtractedText {
+        BuiltinExtractor
+            .extract(content_type, ext, bytes)
+            .expect("text extracted")
+    }
+
+    #[test]
+    fn extracts_docx_with_footnotes() {
+        let out = extract(
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "docx",
+            include_bytes!("testdata/report.docx"),
+        );
+        assert_eq!(
+            out.text,
+            "Quarterly revenue report\nRegion\tCafé & Bar\nSecond line\nNorth\n1200\nSee note\nUnaudited figures."
+        );
+        assert_eq!(out.page_count, None);
+    }
+
+    #[test]
+    fn extracts_xlsx_cells_in_sheet_order() {
+        let out = extract(
+            "application/octet-stream",
+            "xlsx",
+            include_bytes!("testdata/budget.xlsx"),
+        );
+        assert_eq!(
+            out.text,
+            "Item\tCost\nServer rack\t4999.5\tTRUE\nCabling\t499.95\n\n東京\tOffice"
+        );
+    }
+
+    #[test]
+    fn extracts_pptx_slides_in_numeric_order() {
+        let out = extract("", "pptx", include_bytes!("testdata/deck.pptx"));
+        assert_eq!(
+            out.text,
+            "Roadmap 2026\n\nMigrate archives\nRetire legacy\n\nQuestions?"
+        );
+        assert_eq!(out.page_count, Some(3));
+    }
+
+    #[test]
+    fn extracts_opendocument_formats() {
+        let odt = extract("", "odt", include_bytes!("testdata/minutes.odt"));
+        assert_eq!(
+            odt.text,
+            "Meeting minutes\nAttendees:  Ana\tBo\nAction\nitems follow"
+        );
+
+        let ods = extract(
+            "application/vnd.oasis.opendocument.spreadsheet",
+            "",
+            include_bytes!("testdata/inventory.ods"),
+        );
+        assert_eq!(ods.text, "Part\tQty\nWidget\t7\t7");
+
+        let odp = extract("", "odp", include_bytes!("testdata/pitch.odp"));
+        assert_eq!(odp.text, "Welcome\n\nPricing tiers");
+        assert_eq!(odp.page_count, Some(2));
+    }
+
+    #[test]
+    fn extracts_rtf_text_and_skips_destinations() {
+        let out = extract(
+            "application/rtf",
+            "rtf",
+            include_bytes!("testdata/letter.rtf"),
+        );
+        assert_eq!(
+            out.text,
+            "Dear Customer,\nYour invoice for the café is attached \u{2014} thank you.\n\
+             Total:\t42 €\nUnicode: 日本 and 中\nexample link\nRegards\nBilling"
+        );
+    }
+
+    #[test]
+    fn extracts_pdf_text_layer_with_page_count() {
+        let out = extract(
+            "application/pdf",
+            "pdf",
+            include_bytes!("testdata/invoice.pdf"),
+        );
+        assert!(out.text.contains("Invoice number 1234"), "{:?}", out.text);
+        assert!(
+            out.text.contains("Payment due in thirty days"),
+            "{:?}",
+            out.text
+        );
+        assert_eq!(out.page_count, Some(2));
+        assert!(!out.is_ocr);
+    }
+
+    #[test]
+    fn extracts_plain_text_formats() {
+        let csv = extract("text/csv", "csv", include_bytes!("testdata/notes.csv"));
+        assert!(csv
+            .text
+            .starts_with("name,email,amount\nAlice,alice@example.com,10"));
+
+        let md = extract("text/markdown", "md", include_bytes!("testdata/readme.md"));
+        assert_eq!(
+            md.text,
+            "# Release notes\n\n* Faster **search**\n* Archive import"
+        );
+
+        let legacy = extract("text/plain", "txt", include_bytes!("testdata/legacy.txt"));
+        assert_eq!(legacy.text, "Naïve café résumé");
+
+        let utf16 = extract("text/plain", "txt", include_bytes!("testdata/utf16.txt"));
+        assert_eq!(utf16.text, "Hello from UTF-16");
+    }
+
+    #[test]
+    fn extracts_html_without_markup() {
+        let out = extract("text/html", "html", include_bytes!("testdata/page.html"));
+        assert!(out.text.contains("Welcome aboard"), "{:?}", out.text);
+        assert!(out.text.contains("ready"), "{:?}", out.text);
+        assert!(!out.text.contains("<b>"));
+        assert!(!out.text.contains("color:red"));
+    }
+
+    #[test]
+    fn detects_mislabelled_attachments_by_content() {
+        let pdf = include_bytes!("testdata/invoice.pdf");
+        assert_eq!(
+            detect("application/octet-stream", "bin", pdf),
+            Some(Format::Pdf)
+        );
+        let rtf = include_bytes!("testdata/letter.rtf");
+        assert_eq!(
+            detect("application/octet-stream", "", rtf),
+            Some(Format::Rtf)
+        );
+        assert_eq!(detect("image/png", "png", b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn rejects_unreadable_and_oversized_input() {
+        assert!(BuiltinExtractor.extract("", "docx", b"not a zip").is_none());
+        assert!(BuiltinExtractor
+            .extract("", "pdf", b"%PDF-1.4 garbage")
+            .is_none());
+        assert!(BuiltinExtractor
+            .extract("text/plain", "txt", b" \n\n ")
+            .is_none());
+        let big = vec![b'a'; MAX_EXTRACT_BYTES + 1];
+        assert!(BuiltinExtractor
+            .extract("text/plain", "txt", &big)
+            .is_none());
+    }
+
+    #[test]
+    fn normalize_collapses_blank_lines_and_caps_output() {
+        assert_eq!(normalize("a  \r\n\n\n\nb\u{0}c\t \n"), "a\n\nbc");
+        let long = "é".repeat(MAX_EXTRACT_BYTES);
+        let capped = normalize(&long);
+        assert!(capped.len() <= MAX_EXTRACT_BYTES);
+        assert!(capped.len() > MAX_EXTRACT_BYTES - 2);
+    }
+}
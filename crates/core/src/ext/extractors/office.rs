@@ -0,0 +1,296 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Text from OOXML (docx, xlsx, pptx) and OpenDocument (odt, ods, odp)
+//! packages, both of which are zip archives of XML parts.
+
+use std::io::{Cursor, Read};
+
+use zip::ZipArchive;
+
+use super::xml::{attribute, walk, XmlEvent};
+use crate::ext::text_extractor::ExtractedText;
+
+/// Upper bound on the uncompressed bytes read out of one package. Office
+/// parts compress very well, so this is far above any real document while
+/// still stopping a crafted archive from inflating without limit.
+const MAX_PACKAGE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum OfficeFormat {
+    Docx,
+    Xlsx,
+    Pptx,
+    Odt,
+    Ods,
+    Odp,
+}
+
+pub(super) fn extract(format: OfficeFormat, bytes: &[u8]) -> Option<ExtractedText> {
+    let mut package = Package::open(bytes)?;
+    let (text, page_count) = match format {
+        OfficeFormat::Docx => (docx(&mut package), None),
+        OfficeFormat::Xlsx => (xlsx(&mut package), None),
+        OfficeFormat::Pptx => pptx(&mut package),
+        OfficeFormat::Odt | OfficeFormat::Ods => (odf(&mut package).0, None),
+        OfficeFormat::Odp => {
+            let (text, pages) = odf(&mut package);
+            (text, Some(pages))
+        }
+    };
+    Some(ExtractedText {
+        text,
+        page_count,
+        is_ocr: false,
+    })
+}
+
+struct Package<'a> {
+    archive: ZipArchive<Cursor<&'a [u8]>>,
+    budget: u64,
+}
+
+impl<'a> Package<'a> {
+    fn open(bytes: &'a [u8]) -> Option<Self> {
+        Some(Self {
+            archive: ZipArchive::new(Cursor::new(bytes)).ok()?,
+            budget: MAX_PACKAGE_BYTES,
+        })
+    }
+
+    /// Reads one part, charging its inflated size to the package budget.
+    /// Missing parts, and parts that would exceed the budget, read as `None`.
+    fn part(&mut self, name: &str) -> Option<Vec<u8>> {
+        let file = self.archive.by_name(name).ok()?;
+        let mut data = Vec::new();
+        file.take(self.budget + 1).read_to_end(&mut data).ok()?;
+        if data.len() as u64 > self.budget {
+            self.budget = 0;
+            return None;
+        }
+        self.budget -= data.len() as u64;
+        Some(data)
+    }
+
+    /// Names of the numbered parts `{prefix}N{suffix}`, in numeric order.
+    fn numbered_parts(&self, prefix: &str, suffix: &str) -> Vec<String> {
+        let mut parts: Vec<(u32, String)> = self
+            .archive
+            .file_names()
+            .filter_map(|name| {
+                let n = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                Some((n.parse().ok()?, name.to_string()))
+            })
+            .collect();
+        parts.sort();
+        parts.into_iter().map(|(_, name)| name).collect()
+    }
+}
+
+fn docx(package: &mut Package) -> String {
+    let mut text = String::new();
+    for part in [
+        "word/document.xml",
+        "word/footnotes.xml",
+        "word/endnotes.xml",
+    ] {
+        if let Some(xml) = package.part(part) {
+            wordprocessing_text(&xml, &mut text);
+        }
+    }
+    text
+}
+
+fn wordprocessing_text(xml: &[u8], out: &mut String) {
+    let mut in_text = false;
+    walk(xml, |event| match event {
+        XmlEvent::Start(b"t", _) => in_text = true,
+        XmlEvent::Start(b"tab", _) => out.push('\t'),
+        XmlEvent::Start(b"br" | b"cr", _) => out.push('\n'),
+        XmlEvent::End(b"t") => in_text = false,
+        XmlEvent::End(b"p") => out.push('\n'),
+        XmlEvent::Text(t) if in_text => out.push_str(t),
+        _ => {}
+    });
+}
+
+fn pptx(package: &mut Package) -> (String, Option<u32>) {
+    let slides = package.numbered_parts("ppt/slides/slide", ".xml");
+    let mut text = String::new();
+    for slide in &slides {
+        let Some(xml) = package.part(slide) else {
+            continue;
+        };
+        let mut in_text = false;
+        walk(&xml, |event| match event {
+            XmlEvent::Start(b"t", _) => in_text = true,
+            XmlEvent::Start(b"br", _) => text.push('\n'),
+            XmlEvent::End(b"t") => in_text = false,
+            XmlEvent::End(b"p") => text.push('\n'),
+            XmlEvent::Text(t) if in_text => text.push_str(t),
+            _ => {}
+        });
+        text.push('\n');
+    }
+    (text, Some(slides.len() as u32))
+}
+
+fn xlsx(package: &mut Package) -> String {
+    let shared = package
+        .part("xl/sharedStrings.xml")
+        .map(|xml| shared_strings(&xml))
+        .unwrap_or_default();
+    let mut text = String::new();
+    for sheet in package.numbered_parts("xl/worksheets/sheet", ".xml") {
+        if let Some(xml) = package.part(&sheet) {
+            worksheet_text(&xml, &shared, &mut text);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The shared string table, one entry per `si`. Phonetic runs (`rPh`) are
+/// reading hints for East Asian text and are left out.
+fn shared_strings(xml: &[u8]) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    let mut in_phonetic = false;
+    walk(xml, |event| match event {
+        XmlEvent::Start(b"rPh", _) => in_phonetic = true,
+        XmlEvent::End(b"rPh") => in_phonetic = false,
+        XmlEvent::Start(b"t", _) => in_text = !in_phonetic,
+        XmlEvent::End(b"t") => in_text = false,
+        XmlEvent::End(b"si") => strings.push(std::mem::take(&mut current)),
+        XmlEvent::Text(t) if in_text => current.push_str(t),
+        _ => {}
+    });
+    strings
+}
+
+/// Rows become lines and cells are separated by tabs. Shared-string and
+/// boolean cells are resolved; numbers and formula results are kept as
+/// stored.
+fn worksheet_text(xml: &[u8], shared: &[String], out: &mut String) {
+    let mut cell_type = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut row: Vec<String> = Vec::new();
+    walk(xml, |event| match event {
+        XmlEvent::Start(b"c", e) => {
+            cell_type = attribute(e, b"t").unwrap_or_default();
+            value.clear();
+        }
+        XmlEvent::Start(b"v" | b"t", _) => in_value = true,
+        XmlEvent::End(b"v" | b"t") => in_value = false,
+        XmlEvent::Text(t) if in_value => value.push_str(t),
+        XmlEvent::End(b"c") => {
+            let cell = match cell_type.as_str() {
+                "s" => value
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| shared.get(i))
+                    .cloned()
+                    .unwrap_or_default(),
+                "b" => match value.trim() {
+                    "1" => "TRUE".into(),
+                    _ => "FALSE".into(),
+                },
+                _ => std::mem::take(&mut value),
+            };
+            row.push(cell);
+        }
+        XmlEvent::End(b"row") => {
+            while row.last().is_some_and(|c| c.is_empty()) {
+                row.pop();
+            }
+            if !row.is_empty() {
+                out.push_str(&row.join("\t"));
+                out.push('\n');
+            }
+            row.clear();
+        }
+        _ => {}
+    });
+}
+
+/// Text of an OpenDocument `content.xml`, plus the number of presentation
+/// pages (`draw:page`) it contains.
+fn odf(package: &mut Package) -> (String, u32) {
+    let Some(xml) = package.part("content.xml") else {
+        return (String::new(), 0);
+    };
+    let mut text = String::new();
+    let mut pages = 0;
+    // Cell text is collected separately so a table row ends up on one line.
+    let mut cell: Option<String> = None;
+    let mut cell_repeat = 1;
+    let mut row: Vec<String> = Vec::new();
+    walk(&xml, |event| match event {
+        XmlEvent::Start(b"page", _) => pages += 1,
+        XmlEvent::End(b"page") => text.push('\n'),
+        XmlEvent::Start(b"s", e) => {
+            let n = attribute(e, b"c").and_then(|c| c.parse().ok()).unwrap_or(1);
+            sink(&mut cell, &mut text).extend(std::iter::repeat_n(' ', n.min(1024)));
+        }
+        XmlEvent::Start(b"tab", _) => sink(&mut cell, &mut text).push('\t'),
+        XmlEvent::Start(b"line-break", _) => sink(&mut cell, &mut text).push('\n'),
+        XmlEvent::End(b"p" | b"h") => sink(&mut cell, &mut text).push('\n'),
+        XmlEvent::Text(t) => sink(&mut cell, &mut text).push_str(t),
+        XmlEvent::Start(b"table-cell", e) => {
+            cell_repeat = attribute(e, b"number-columns-repeated")
+                .and_then(|c| c.parse().ok())
+                .unwrap_or(1);
+            cell = Some(String::new());
+        }
+        XmlEvent::End(b"table-cell") => {
+            let value = cell.take().unwrap_or_default();
+            let value = value.trim_end_matches('\n').replace('\n', " ");
+            // Spreadsheets pad rows with long runs of repeated empty cells;
+            // only cells that hold something are repeated.
+            let n = if value.is_empty() {
+                1
+            } else {
+                cell_repeat.min(256)
+            };
+            row.extend(std::iter::repeat_n(value, n));
+        }
+        XmlEvent::End(b"table-row") => {
+            while row.last().is_some_and(|c| c.is_empty()) {
+                row.pop();
+            }
+            if !row.is_empty() {
+                text.push_str(&row.join("\t"));
+                text.push('\n');
+            }
+            row.clear();
+        }
+        _ => {}
+    });
+    (text, pages)
+}
+
+/// Where character data goes: the open table cell if there is one.
+fn sink<'a>(cell: &'a mut Option<String>, text: &'a mut String) -> &'a mut String {
+    match cell {
+        Some(cell) => cell,
+        None => text,
+    }
+}
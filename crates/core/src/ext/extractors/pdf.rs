@@ -0,0 +1,50 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Text from the text layer of PDF documents. Scanned pages without a
+//! text layer yield nothing; there is no OCR here.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use tracing::debug;
+
+use crate::ext::text_extractor::ExtractedText;
+
+pub(super) fn extract(bytes: &[u8]) -> Option<ExtractedText> {
+    // pdf-extract panics on some malformed or unsupported inputs; a bad
+    // attachment must not take the ingest worker down with it.
+    let pages = match catch_unwind(AssertUnwindSafe(|| {
+        pdf_extract::extract_text_from_mem_by_pages(bytes)
+    })) {
+        Ok(Ok(pages)) => pages,
+        Ok(Err(e)) => {
+            debug!("PDF text extraction failed: {e}");
+            return None;
+        }
+        Err(_) => {
+            debug!("PDF text extraction panicked");
+            return None;
+        }
+    };
+    let page_count = pages.len() as u32;
+    Some(ExtractedText {
+        text: pages.join("\n"),
+        page_count: Some(page_count),
+        is_ocr: false,
+    })
+}
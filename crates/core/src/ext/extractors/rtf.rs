@@ -0,0 +1,229 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Plain text from RTF documents.
+//!
+//! This is a reader for the text stream only: formatting is dropped, and
+//! destinations that hold no readable text (font and colour tables, style
+//! sheets, pictures, embedded objects, field instructions, ...) are skipped.
+
+/// Destinations whose content is never document text.
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "listtable",
+    "listoverridetable",
+    "rsidtbl",
+    "info",
+    "pict",
+    "object",
+    "objdata",
+    "fldinst",
+    "datastore",
+    "themedata",
+    "colorschememapping",
+    "latentstyles",
+    "xmlnstbl",
+    "generator",
+    "filetbl",
+    "revtbl",
+    "bkmkstart",
+    "bkmkend",
+    "nonshppict",
+    "sp",
+];
+
+/// Windows-1252, the code page RTF assumes when `\ansicpg` is absent.
+const DEFAULT_CODE_PAGE: u16 = 1252;
+
+#[derive(Clone, Copy)]
+struct Group {
+    skip: bool,
+    /// Number of fallback characters that follow a `\uN` (`\ucN`).
+    uc: usize,
+}
+
+pub(super) fn to_text(rtf: &[u8]) -> String {
+    let mut reader = Reader {
+        text: String::new(),
+        pending: Vec::new(),
+        code_page: DEFAULT_CODE_PAGE,
+    };
+    let mut stack: Vec<Group> = Vec::new();
+    let mut group = Group { skip: false, uc: 1 };
+    // Fallback characters still to drop after a `\uN`.
+    let mut fallback = 0usize;
+    let mut i = 0;
+
+    while i < rtf.len() {
+        let b = rtf[i];
+        i += 1;
+        match b {
+            b'{' => {
+                stack.push(group);
+                fallback = 0;
+            }
+            b'}' => {
+                group = stack.pop().unwrap_or(group);
+                fallback = 0;
+            }
+            b'\r' | b'\n' => {}
+            b'\\' => {
+                let Some(&next) = rtf.get(i) else {
+                    break;
+                };
+                if next.is_ascii_alphabetic() {
+                    let start = i;
+                    while i < rtf.len() && rtf[i].is_ascii_alphabetic() {
+                        i += 1;
+                    }
+                    let word = std::str::from_utf8(&rtf[start..i]).unwrap_or_default();
+                    let num_start = i;
+                    if rtf.get(i) == Some(&b'-') {
+                        i += 1;
+                    }
+                    while i < rtf.len() && rtf[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    let param = std::str::from_utf8(&rtf[num_start..i])
+                        .ok()
+                        .and_then(|n| n.parse::<i32>().ok());
+                    if rtf.get(i) == Some(&b' ') {
+                        i += 1;
+                    }
+
+                    if word == "bin" {
+                        // Raw binary data of the given length follows.
+                        i = i.saturating_add(param.unwrap_or(0).max(0) as usize);
+                        continue;
+                    }
+                    if SKIPPED_DESTINATIONS.contains(&word) {
+                        group.skip = true;
+                        continue;
+                    }
+                    match word {
+                        "ansicpg" => {
+                            if let Some(cp) = param.and_then(|p| u16::try_from(p).ok()) {
+                                reader.code_page = cp;
+                            }
+                        }
+                        "uc" => group.uc = param.unwrap_or(1).max(0) as usize,
+                        "u" if !group.skip => {
+                            if let Some(p) = param {
+                                // Parameters are signed 16-bit values.
+                                let unit = if p < 0 { p + 65536 } else { p } as u32;
+                                reader.push_char(char::from_u32(unit).unwrap_or('\u{fffd}'));
+                            }
+                            fallback = group.uc;
+                        }
+                        _ if group.skip => {}
+                        _ => {
+                            if fallback > 0 {
+                                fallback -= 1;
+                            } else if let Some(s) = control_word_text(word) {
+                                reader.push_str(s);
+                            }
+                        }
+                    }
+                } else {
+                    i += 1;
+                    match next {
+                        b'*' => group.skip = true,
+                        b'\'' => {
+                            let hex = rtf.get(i..i + 2).and_then(|h| std::str::from_utf8(h).ok());
+                            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                                i += 2;
+                                if fallback > 0 {
+                                    fallback -= 1;
+                                } else if !group.skip {
+                                    reader.pending.push(byte);
+                                }
+                            }
+                        }
+                        _ if group.skip => {}
+                        b'\\' | b'{' | b'}' => reader.push_char(next as char),
+                        b'~' => reader.push_char(' '),
+                        b'_' => reader.push_char('-'),
+                        b'\r' | b'\n' => reader.push_char('\n'),
+                        _ => {}
+                    }
+                }
+            }
+            _ if group.skip => {}
+            _ if fallback > 0 => fallback -= 1,
+            _ => reader.pending.push(b),
+        }
+    }
+    reader.flush();
+    reader.text
+}
+
+/// Text produced by a control word, if any.
+fn control_word_text(word: &str) -> Option<&'static str> {
+    Some(match word {
+        "par" | "line" | "sect" | "page" | "row" => "\n",
+        "tab" | "cell" => "\t",
+        "emdash" => "\u{2014}",
+        "endash" => "\u{2013}",
+        "lquote" => "\u{2018}",
+        "rquote" => "\u{2019}",
+        "ldblquote" => "\u{201c}",
+        "rdblquote" => "\u{201d}",
+        "bullet" => "\u{2022}",
+        "emspace" | "enspace" | "qmspace" => " ",
+        _ => return None,
+    })
+}
+
+struct Reader {
+    text: String,
+    /// Code page bytes not yet decoded, so multi-byte sequences written as
+    /// consecutive `\'hh` escapes decode together.
+    pending: Vec<u8>,
+    code_page: u16,
+}
+
+impl Reader {
+    fn push_char(&mut self, c: char) {
+        self.flush();
+        self.text.push(c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.flush();
+        self.text.push_str(s);
+    }
+
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let decoded = codepage_strings::Coding::new(self.code_page)
+            .ok()
+            .and_then(|coding| coding.decode(&self.pending).ok().map(|s| s.into_owned()))
+            .unwrap_or_else(|| {
+                encoding_rs::WINDOWS_1252
+                    .decode_without_bom_handling(&self.pending)
+                    .0
+                    .into_owned()
+            });
+        self.text.push_str(&decoded);
+        self.pending.clear();
+    }
+}
@@ -0,0 +1,87 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal event walk over the XML parts of office documents.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// What [`walk`] reports. Element names are local names, without their
+/// namespace prefix; empty elements are reported as a start and an end.
+pub(super) enum XmlEvent<'a, 'e> {
+    Start(&'a [u8], &'a BytesStart<'e>),
+    End(&'a [u8]),
+    Text(&'a str),
+}
+
+/// Calls `visit` for every element and every piece of character data in
+/// `xml`, with entity and character references resolved. Stops quietly at
+/// the first syntax error, keeping what was read up to there.
+pub(super) fn walk(xml: &[u8], mut visit: impl FnMut(XmlEvent<'_, '_>)) {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => visit(XmlEvent::Start(e.local_name().into_inner(), &e)),
+            Ok(Event::Empty(e)) => {
+                let name = e.local_name();
+                visit(XmlEvent::Start(name.into_inner(), &e));
+                visit(XmlEvent::End(name.into_inner()));
+            }
+            Ok(Event::End(e)) => visit(XmlEvent::End(e.local_name().into_inner())),
+            Ok(Event::Text(e)) => {
+                if let Ok(text) = e.xml10_content() {
+                    visit(XmlEvent::Text(&text));
+                }
+            }
+            Ok(Event::CData(e)) => {
+                if let Ok(text) = e.decode() {
+                    visit(XmlEvent::Text(&text));
+                }
+            }
+            Ok(Event::GeneralRef(e)) => {
+                let resolved = match e.resolve_char_ref() {
+                    Ok(Some(c)) => Some(c),
+                    _ => match e.decode().as_deref() {
+                        Ok("amp") => Some('&'),
+                        Ok("lt") => Some('<'),
+                        Ok("gt") => Some('>'),
+                        Ok("quot") => Some('"'),
+                        Ok("apos") => Some('\''),
+                        _ => None,
+                    },
+                };
+                if let Some(c) = resolved {
+                    visit(XmlEvent::Text(c.encode_utf8(&mut [0; 4])));
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+        buf.clear();
+    }
+}
+
+/// The value of attribute `name` (matched by local name) of `element`.
+pub(super) fn attribute(element: &BytesStart<'_>, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| String::from_utf8(a.value.into_owned()).ok())
+}
@@ -26,4 +26,5 @@
 // It never reads from the event bus — events are fire-and-forget.
 
 pub mod event_bus;
+pub mod extractors;
 pub mod text_extractor;
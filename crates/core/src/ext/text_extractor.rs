@@ -18,13 +18,16 @@
 
 // Attachment text extraction extension point.
 //
-// Community edition: NoopExtractor — no attachments are text-indexed.
-// Pro edition: PdfExtractor — extracts text from PDF, Word, etc.
+// Default: BuiltinExtractor (ext/extractors) — plain text, CSV, HTML, Markdown,
+// OOXML and OpenDocument files, RTF, and text-layer PDFs.
+// Pro edition: PdfExtractor — installed over the default via set_extractor.
 //
 // Used in: crates/core/src/envelope/extractor.rs
 
 use std::sync::{LazyLock, RwLock};
 
+use crate::ext::extractors::BuiltinExtractor;
+
 pub struct ExtractedText {
     pub text: String,
     pub page_count: Option<u32>,
@@ -37,18 +40,10 @@ pub trait AttachmentTextExtractor: Send + Sync {
     fn extract(&self, content_type: &str, ext: &str, bytes: &[u8]) -> Option<ExtractedText>;
 }
 
-/// Default — all attachments are skipped.
-struct NoopExtractor;
-impl AttachmentTextExtractor for NoopExtractor {
-    fn extract(&self, _ct: &str, _ext: &str, _bytes: &[u8]) -> Option<ExtractedText> {
-        None
-    }
-}
-
 static EXTRACTOR: LazyLock<RwLock<Box<dyn AttachmentTextExtractor>>> =
-    LazyLock::new(|| RwLock::new(Box::new(NoopExtractor)));
+    LazyLock::new(|| RwLock::new(Box::new(BuiltinExtractor)));
 
-/// Called by Pro/Enterprise at startup to replace the noop default.
+/// Called by Pro/Enterprise at startup to replace the built-in default.
 pub fn set_extractor(extractor: Box<dyn AttachmentTextExtractor>) {
     *EXTRACTOR.write().unwrap() = extractor;
 }
@@ -66,16 +61,28 @@ pub fn should_try_extract(content_type: &str, ext: &str) -> bool {
         "pdf"
             | "doc"
             | "docx"
+            | "docm"
             | "xls"
             | "xlsx"
+            | "xlsm"
             | "ppt"
             | "pptx"
+            | "pptm"
             | "txt"
+            | "csv"
+            | "tsv"
+            | "md"
+            | "markdown"
+            | "htm"
+            | "html"
             | "rtf"
             | "odt"
             | "ods"
             | "odp"
     ) || content_type.starts_with("text/")
+        || matches!(content_type, "application/pdf" | "application/rtf")
+        || content_type.starts_with("application/vnd.openxmlformats-officedocument.")
+        || content_type.starts_with("application/vnd.oasis.opendocument.")
 }
 
 /// Called by the attachment pipeline during IMAP sync.
@@ -0,0 +1,627 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    account::migration::AccountModel,
+    error::{code::ErrorCode, BichonError, BichonResult},
+    oauth2::{
+        flow::{build_http_client, OAuth2Flow},
+        token::{OAuth2AccessToken, EXTERNAL_OAUTH_APP_ID},
+    },
+    raise_error,
+};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::VecDeque, time::Duration};
+use tracing::{info, warn};
+
+/// Asks Graph for ids that stay the same when a message moves between folders.
+const PREFER_IMMUTABLE_ID: &str = "IdType=\"ImmutableId\"";
+/// Message properties requested by delta queries.
+const DELTA_SELECT: &str = "receivedDateTime,isRead,isDraft,flag";
+const FOLDER_PAGE_SIZE: u32 = 100;
+const MAX_THROTTLE_RETRIES: u32 = 3;
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// A mail folder, with its path built from the display names of its parents.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GraphFolder {
+    pub id: String,
+    pub path: String,
+    pub total_item_count: u32,
+}
+
+/// One entry of a message delta query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MessageChange {
+    /// A message new to the folder, or one whose properties changed.
+    Upsert {
+        id: String,
+        received_at: i64,
+        flags: Vec<String>,
+    },
+    /// A message deleted from the folder or moved out of it.
+    Removed { id: String },
+}
+
+impl MessageChange {
+    pub fn id(&self) -> &str {
+        match self {
+            MessageChange::Upsert { id, .. } | MessageChange::Removed { id } => id,
+        }
+    }
+}
+
+/// All changes of a folder since the previous delta link.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MessageDelta {
+    pub changes: Vec<MessageChange>,
+    /// Link to pass to the next query to get only later changes.
+    pub delta_link: String,
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    #[serde(default = "Vec::new")]
+    value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    delta_link: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FolderResource {
+    id: String,
+    display_name: String,
+    #[serde(default)]
+    child_folder_count: u32,
+    #[serde(default)]
+    total_item_count: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageResource {
+    id: String,
+    #[serde(rename = "@removed")]
+    removed: Option<serde_json::Value>,
+    received_date_time: Option<String>,
+    #[serde(default)]
+    is_read: bool,
+    #[serde(default)]
+    is_draft: bool,
+    flag: Option<FollowupFlag>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FollowupFlag {
+    flag_status: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+impl From<MessageResource> for MessageChange {
+    fn from(message: MessageResource) -> Self {
+        if message.removed.is_some() {
+            return MessageChange::Removed { id: message.id };
+        }
+        let received_at = message
+            .received_date_time
+            .as_deref()
+            .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.timestamp_millis())
+            .unwrap_or(0);
+        let mut flags = Vec::new();
+        if message.is_read {
+            flags.push("\\Seen".to_string());
+        }
+        if message.is_draft {
+            flags.push("\\Draft".to_string());
+        }
+        if message
+            .flag
+            .and_then(|f| f.flag_status)
+            .is_some_and(|status| status == "flagged")
+        {
+            flags.push("\\Flagged".to_string());
+        }
+        MessageChange::Upsert {
+            id: message.id,
+            received_at,
+            flags,
+        }
+    }
+}
+
+/// Read-only Microsoft Graph client for one mailbox.
+pub struct GraphClient {
+    http: reqwest::Client,
+    /// `{endpoint}/me` or `{endpoint}/users/{mailbox}`.
+    base: String,
+    access_token: String,
+    /// Account whose stored OAuth2 token is refreshed when Graph rejects it.
+    refresh_account: Option<u64>,
+}
+
+impl GraphClient {
+    pub fn new(
+        http: reqwest::Client,
+        endpoint: &str,
+        mailbox: Option<&str>,
+        access_token: String,
+    ) -> Self {
+        let endpoint = endpoint.trim_end_matches('/');
+        let base = match mailbox {
+            Some(mailbox) => format!("{}/users/{}", endpoint, mailbox),
+            None => format!("{}/me", endpoint),
+        };
+        Self {
+            http,
+            base,
+            access_token,
+            refresh_account: None,
+        }
+    }
+
+    /// Builds a client with the account's OAuth2 token. An expired token is
+    /// refreshed through the account's OAuth2 configuration on first use.
+    pub fn for_account(account: &AccountModel) -> BichonResult<Self> {
+        let config = account.graph.clone().unwrap_or_default();
+        let access_token = Self::stored_access_token(account.id)?;
+        let http = build_http_client(config.use_proxy)?;
+        let mut client = Self::new(
+            http,
+            config.endpoint(),
+            config.mailbox.as_deref(),
+            access_token,
+        );
+        client.refresh_account = Some(account.id);
+        Ok(client)
+    }
+
+    /// Lists all mail folders of the mailbox, parents before children.
+    pub async fn list_folders(&mut self) -> BichonResult<Vec<GraphFolder>> {
+        let mut folders = Vec::new();
+        let mut pending = VecDeque::from([(
+            String::new(),
+            format!("{}/mailFolders?$top={}", self.base, FOLDER_PAGE_SIZE),
+        )]);
+        while let Some((parent, url)) = pending.pop_front() {
+            let mut next = Some(url);
+            while let Some(url) = next {
+                let page: Page<FolderResource> = self.get_json(&url).await?;
+                for folder in page.value {
+                    let path = if parent.is_empty() {
+                        folder.display_name
+                    } else {
+                        format!("{}/{}", parent, folder.display_name)
+                    };
+                    if folder.child_folder_count > 0 {
+                        pending.push_back((
+                            path.clone(),
+                            format!(
+                                "{}/mailFolders/{}/childFolders?$top={}",
+                                self.base, folder.id, FOLDER_PAGE_SIZE
+                            ),
+                        ));
+                    }
+                    folders.push(GraphFolder {
+                        id: folder.id,
+                        path,
+                        total_item_count: folder.total_item_count,
+                    });
+                }
+                next = page.next_link;
+            }
+        }
+        Ok(folders)
+    }
+
+    /// Runs a delta query over the messages of a folder, following all
+    /// pages. Without `delta_link` every message of the folder is returned.
+    ///
+    /// Returns `None` when Graph no longer knows the sync state behind
+    /// `delta_link`; the caller has to start over without it.
+    pub async fn message_delta(
+        &mut self,
+        folder_id: &str,
+        delta_link: Option<&str>,
+    ) -> BichonResult<Option<MessageDelta>> {
+        let mut next = Some(match delta_link {
+            Some(link) => link.to_string(),
+            None => format!(
+                "{}/mailFolders/{}/messages/delta?$select={}",
+                self.base, folder_id, DELTA_SELECT
+            ),
+        });
+        let mut changes = Vec::new();
+        while let Some(url) = next {
+            let response = self.send(&url).await?;
+            if response.status() == StatusCode::GONE {
+                return Ok(None);
+            }
+            let page: Page<MessageResource> = Self::parse_json(response).await?;
+            changes.extend(page.value.into_iter().map(MessageChange::from));
+            if let Some(delta_link) = page.delta_link {
+                return Ok(Some(MessageDelta {
+                    changes,
+                    delta_link,
+                }));
+            }
+            next = page.next_link;
+        }
+        Err(raise_error!(
+            "Graph delta query ended without a delta link".into(),
+            ErrorCode::GraphRequestFailed
+        ))
+    }
+
+    /// Downloads the raw RFC 822 content of a message.
+    pub async fn message_mime(&mut self, message_id: &str) -> BichonResult<Vec<u8>> {
+        let url = format!("{}/messages/{}/$value", self.base, message_id);
+        let response = Self::check(self.send(&url).await?).await?;
+        let body = response.bytes().await.map_err(classify_reqwest_error)?;
+        Ok(body.to_vec())
+    }
+
+    async fn get_json<T: DeserializeOwned>(&mut self, url: &str) -> BichonResult<T> {
+        let response = self.send(url).await?;
+        Self::parse_json(response).await
+    }
+
+    async fn parse_json<T: DeserializeOwned>(response: Response) -> BichonResult<T> {
+        let response = Self::check(response).await?;
+        let body = response.bytes().await.map_err(classify_reqwest_error)?;
+        serde_json::from_slice(&body).map_err(|e| {
+            raise_error!(
+                format!("Unexpected Graph response: {}", e),
+                ErrorCode::GraphRequestFailed
+            )
+        })
+    }
+
+    /// Sends a GET request. Throttled requests are retried after the delay
+    /// Graph asks for, and a rejected token is refreshed once.
+    async fn send(&mut self, url: &str) -> BichonResult<Response> {
+        let mut refreshed = false;
+        let mut throttled = 0;
+        loop {
+            let response = self
+                .http
+                .get(url)
+                .bearer_auth(&self.access_token)
+                .header("Prefer", PREFER_IMMUTABLE_ID)
+                .send()
+                .await
+                .map_err(classify_reqwest_error)?;
+            match response.status() {
+                StatusCode::UNAUTHORIZED if !refreshed && self.refresh_account.is_some() => {
+                    refreshed = true;
+                    self.refresh_access_token().await?;
+                }
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                    if throttled < MAX_THROTTLE_RETRIES =>
+                {
+                    throttled += 1;
+                    let delay = retry_after(&response);
+                    warn!(
+                        "Graph request throttled ({}), retrying in {}s",
+                        response.status(),
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+
+    /// Turns a non-success response into an error carrying Graph's error code.
+    async fn check(response: Response) -> BichonResult<Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.bytes().await.unwrap_or_default();
+        let detail = serde_json::from_slice::<ErrorResponse>(&body)
+            .map(|e| format!("{}: {}", e.error.code, e.error.message))
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned());
+        Err(raise_error!(
+            format!("Graph request failed with {}: {}", status, detail),
+            ErrorCode::GraphRequestFailed
+        ))
+    }
+
+    async fn refresh_access_token(&mut self) -> BichonResult<()> {
+        let Some(account_id) = self.refresh_account else {
+            return Ok(());
+        };
+        let token = OAuth2AccessToken::get(account_id)?.ok_or_else(|| {
+            raise_error!(
+                "Graph account has no OAuth2 token.".into(),
+                ErrorCode::MissingConfiguration
+            )
+        })?;
+        if token.oauth2_id == EXTERNAL_OAUTH_APP_ID {
+            return Err(raise_error!(
+                "Graph rejected the externally managed access token.".into(),
+                ErrorCode::GraphRequestFailed
+            ));
+        }
+        OAuth2Flow::new(token.oauth2_id)
+            .refresh_access_token(&token)
+            .await?;
+        self.access_token = Self::stored_access_token(account_id)?;
+        info!("Refreshed Graph access token for account {}", account_id);
+        Ok(())
+    }
+
+    fn stored_access_token(account_id: u64) -> BichonResult<String> {
+        OAuth2AccessToken::get(account_id)?
+            .and_then(|t| t.access_token)
+            .ok_or_else(|| {
+                raise_error!(
+                    "Graph account requires OAuth2, but OAuth2 authorization is not yet complete."
+                        .into(),
+                    ErrorCode::MissingConfiguration
+                )
+            })
+    }
+}
+
+fn retry_after(response: &Response) -> Duration {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_RETRY_AFTER)
+        .min(MAX_RETRY_AFTER)
+}
+
+fn classify_reqwest_error(e: reqwest::Error) -> BichonError {
+    if e.is_timeout() {
+        raise_error!(
+            format!("Graph request timed out: {}", e),
+            ErrorCode::ConnectionTimeout
+        )
+    } else {
+        raise_error!(
+            format!("Graph request failed: {}", e),
+            ErrorCode::NetworkError
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::mock_server::{MockGraphServer, MockGraphServerHandle};
+
+    const INITIAL_DELTA: &str =
+        "/me/mailFolders/inbox-id/messages/delta?$select=receivedDateTime,isRead,isDraft,flag";
+    const DELTA_PAGE_2: &str = "/me/mailFolders/inbox-id/messages/delta?$skiptoken=LztZwWjo5IivWBhyxw5rACKxf7mPm0oW6JZZ7fvKxYPS_67JnEYmfQQMPccy6FRun0DWJF5775dvuXxlZnMYhBubC1v4SBVT9ZjO8f7acnI.z5Rpl4iryKrIjA0GKzsyTlIXRUU4WGYrIbWF3jVVvMk";
+
+    fn client(server: &MockGraphServerHandle, mailbox: Option<&str>) -> GraphClient {
+        GraphClient::new(
+            reqwest::Client::new(),
+            &server.endpoint(),
+            mailbox,
+            "test-token".into(),
+        )
+    }
+
+    fn upsert(id: &str, received_at: i64, flags: &[&str]) -> MessageChange {
+        MessageChange::Upsert {
+            id: id.into(),
+            received_at,
+            flags: flags.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn folders_are_listed_across_pages_and_levels() {
+        let server = MockGraphServer::new()
+            .json("/me/mailFolders?$top=100", include_str!("testdata/folders.json"))
+            .json(
+                "/me/mailFolders?$top=100&$skip=2",
+                include_str!("testdata/folders_page2.json"),
+            )
+            .json(
+                "/me/mailFolders/AAMkAGVmMDEzMTM4LTZmYWUtNDdkNC1hMDZiLTU1OGY5OTZhYmY4OAAuAAAAAAAiQ8W967B7TKBjgx9rVEURAQAiIsqMbYjsT5e-T7KzowPTAAAAAAEMAAA=/childFolders?$top=100",
+                include_str!("testdata/child_folders.json"),
+            )
+            .start()
+            .await;
+
+        let folders = client(&server, None).list_folders().await.unwrap();
+        let paths: Vec<(&str, u32)> = folders
+            .iter()
+            .map(|f| (f.path.as_str(), f.total_item_count))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("Inbox", 3),
+                ("Sent Items", 12),
+                ("Archive", 0),
+                ("Inbox/Invoices", 5)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn delta_follows_next_links_until_the_delta_link() {
+        let server = MockGraphServer::new()
+            .json(INITIAL_DELTA, include_str!("testdata/delta.json"))
+            .json(DELTA_PAGE_2, include_str!("testdata/delta_page2.json"))
+            .start()
+            .await;
+
+        let delta = client(&server, None)
+            .message_delta("inbox-id", None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            delta.changes,
+            vec![
+                upsert(
+                    "AAkALgAAAAAAHYQDEapmEc2byACqAC-EWg0AIiLKjG2I7E-Xvk-ys6MD0wAAAAArXwAA",
+                    1710144930000,
+                    &["\\Seen", "\\Flagged"]
+                ),
+                upsert(
+                    "AAkALgAAAAAAHYQDEapmEc2byACqAC-EWg0AIiLKjG2I7E-Xvk-ys6MD0wAAAAArYAAA",
+                    1710262931000,
+                    &[]
+                ),
+                upsert(
+                    "AAkALgAAAAAAHYQDEapmEc2byACqAC-EWg0AIiLKjG2I7E-Xvk-ys6MD0wAAAAArYQAA",
+                    1710323100000,
+                    &["\\Seen", "\\Draft"]
+                ),
+                MessageChange::Removed {
+                    id: "AAkALgAAAAAAHYQDEapmEc2byACqAC-EWg0AIiLKjG2I7E-Xvk-ys6MD0wAAAAArXgAA"
+                        .into()
+                },
+            ]
+        );
+        assert!(delta.delta_link.starts_with(&format!(
+            "{}/me/mailFolders/inbox-id/messages/delta?$deltatoken=",
+            server.endpoint()
+        )));
+        assert_eq!(server.targets(), vec![INITIAL_DELTA, DELTA_PAGE_2]);
+    }
+
+    #[tokio::test]
+    async fn expired_delta_link_asks_for_a_full_sync() {
+        let server = MockGraphServer::new()
+            .respond(
+                "/me/mailFolders/inbox-id/messages/delta?$deltatoken=stale",
+                410,
+                &[("Content-Type", "application/json")],
+                br#"{"error":{"code":"SyncStateNotFound","message":"The sync state generation is not found."}}"#,
+            )
+            .start()
+            .await;
+        let link = format!(
+            "{}/me/mailFolders/inbox-id/messages/delta?$deltatoken=stale",
+            server.endpoint()
+        );
+        let delta = client(&server, None)
+            .message_delta("inbox-id", Some(&link))
+            .await
+            .unwrap();
+        assert!(delta.is_none());
+    }
+
+    #[tokio::test]
+    async fn mime_is_fetched_with_token_and_immutable_ids() {
+        let eml = include_bytes!("testdata/message.eml");
+        let server = MockGraphServer::new()
+            .respond(
+                "/users/archive@contoso.onmicrosoft.com/messages/msg-1/$value",
+                200,
+                &[("Content-Type", "text/plain")],
+                eml,
+            )
+            .start()
+            .await;
+
+        let body = client(&server, Some("archive@contoso.onmicrosoft.com"))
+            .message_mime("msg-1")
+            .await
+            .unwrap();
+        assert_eq!(body, eml.to_vec());
+        let request = &server.requests()[0];
+        assert_eq!(request.headers["authorization"], "Bearer test-token");
+        assert_eq!(request.headers["prefer"], PREFER_IMMUTABLE_ID);
+    }
+
+    #[tokio::test]
+    async fn throttled_requests_are_retried() {
+        let server = MockGraphServer::new()
+            .respond(
+                "/me/mailFolders?$top=100",
+                429,
+                &[("Retry-After", "0")],
+                b"",
+            )
+            .json(
+                "/me/mailFolders?$top=100",
+                include_str!("testdata/folders_page2.json"),
+            )
+            .start()
+            .await;
+
+        let folders = client(&server, None).list_folders().await.unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(server.targets().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn graph_errors_carry_the_graph_error_code() {
+        let server = MockGraphServer::new()
+            .respond(
+                "/me/mailFolders?$top=100",
+                403,
+                &[("Content-Type", "application/json")],
+                br#"{"error":{"code":"ErrorAccessDenied","message":"Access is denied. Check credentials and try again."}}"#,
+            )
+            .start()
+            .await;
+
+        let error = client(&server, None).list_folders().await.unwrap_err();
+        assert_eq!(error.code(), ErrorCode::GraphRequestFailed);
+        assert!(error.to_string().contains("ErrorAccessDenied"));
+    }
+
+    #[tokio::test]
+    async fn rejected_token_fails_without_a_refreshable_account() {
+        let server = MockGraphServer::new()
+            .respond(
+                "/me/mailFolders?$top=100",
+                401,
+                &[("Content-Type", "application/json")],
+                br#"{"error":{"code":"InvalidAuthenticationToken","message":"Access token has expired or is not yet valid."}}"#,
+            )
+            .start()
+            .await;
+
+        let error = client(&server, None).list_folders().await.unwrap_err();
+        assert_eq!(error.code(), ErrorCode::GraphRequestFailed);
+        assert_eq!(server.targets().len(), 1);
+    }
+}
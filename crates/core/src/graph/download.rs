@@ -0,0 +1,456 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    account::{
+        migration::{AccountModel, AccountType},
+        state::{DownloadState, DownloadStatus, FolderStatus, TriggerType},
+    },
+    cache::imap::{
+        download::download_type::{decide_next_download_task, DownloadTask},
+        mailbox::MailBox,
+    },
+    envelope::extractor::extract_envelope_from_api,
+    error::{code::ErrorCode, BichonResult},
+    graph::{
+        client::{GraphClient, GraphFolder, MessageChange},
+        state::{GraphFolderState, GraphMessageRef},
+    },
+    imap::executor::DEFAULT_MAX_EMAIL_SIZE,
+    raise_error,
+    store::tantivy::envelope::ENVELOPE_MANAGER,
+    utils::create_hash,
+};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// A message new to a folder, to be downloaded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NewMessage {
+    pub id: String,
+    pub received_at: i64,
+    pub flags: Vec<String>,
+}
+
+/// What one delta of a folder means for the archive.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct FolderPlan {
+    /// Messages not archived yet, in delta order.
+    pub fetch: Vec<NewMessage>,
+    /// New flags of archived messages, by UID.
+    pub flag_updates: HashMap<u32, Vec<String>>,
+    /// Archived messages removed from the folder, with their UIDs.
+    pub removed: Vec<(String, u32)>,
+}
+
+/// Sorts the changes of a delta by whether the message is archived
+/// already (`known` maps message ids to UIDs). A message can appear more
+/// than once in a delta; its last entry wins.
+pub fn plan_folder_changes(changes: &[MessageChange], known: &HashMap<String, u32>) -> FolderPlan {
+    let mut latest: HashMap<&str, &MessageChange> = HashMap::new();
+    let mut order = Vec::new();
+    for change in changes {
+        if latest.insert(change.id(), change).is_none() {
+            order.push(change.id());
+        }
+    }
+
+    let mut plan = FolderPlan::default();
+    for id in order {
+        match (latest[id], known.get(id)) {
+            (
+                MessageChange::Upsert {
+                    received_at, flags, ..
+                },
+                None,
+            ) => plan.fetch.push(NewMessage {
+                id: id.to_string(),
+                received_at: *received_at,
+                flags: flags.clone(),
+            }),
+            (MessageChange::Upsert { flags, .. }, Some(uid)) => {
+                plan.flag_updates.insert(*uid, flags.clone());
+            }
+            (MessageChange::Removed { .. }, Some(uid)) => plan.removed.push((id.to_string(), *uid)),
+            (MessageChange::Removed { .. }, None) => {}
+        }
+    }
+    plan
+}
+
+/// Folders to download: all of them, or those named in `download_folders`.
+pub fn select_folders(folders: Vec<GraphFolder>, selected: Option<&[String]>) -> Vec<GraphFolder> {
+    match selected {
+        Some(names) if !names.is_empty() => {
+            let names: HashSet<&str> = names.iter().map(String::as_str).collect();
+            folders
+                .into_iter()
+                .filter(|f| names.contains(f.path.as_str()))
+                .collect()
+        }
+        _ => folders,
+    }
+}
+
+pub async fn process_graph_download(
+    account: &AccountModel,
+    token: CancellationToken,
+    trigger_type: TriggerType,
+) -> BichonResult<()> {
+    assert_eq!(account.account_type, AccountType::Graph);
+    let start_time = Instant::now();
+    let account_id = account.id;
+    let download_task = decide_next_download_task(account, trigger_type).await?;
+    if matches!(download_task, DownloadTask::Idle) {
+        return Ok(());
+    }
+
+    match download_mailbox(account, &token).await {
+        Ok(true) => DownloadState::update_session_status(
+            account_id,
+            DownloadStatus::Cancelled,
+            Some("User stopped or system shutdown".to_string()),
+        )?,
+        Ok(false) => {
+            DownloadState::update_session_status(account_id, DownloadStatus::Success, None)?
+        }
+        Err(e) => {
+            let err_msg = format!("Graph download interrupted: {:#?}", e);
+            DownloadState::append_session_error(account_id, err_msg.clone())?;
+            DownloadState::update_session_status(
+                account_id,
+                DownloadStatus::Failed,
+                Some(err_msg),
+            )?;
+        }
+    }
+
+    debug!(
+        "Account{{{}}} Graph download completed: {} seconds elapsed.",
+        account.email,
+        start_time.elapsed().as_secs()
+    );
+    Ok(())
+}
+
+/// Downloads all selected folders. Returns whether the run was cancelled.
+///
+/// A folder that fails is reported and skipped; the run as a whole fails
+/// once every folder has been tried.
+async fn download_mailbox(account: &AccountModel, token: &CancellationToken) -> BichonResult<bool> {
+    let account_id = account.id;
+    let mut client = GraphClient::for_account(account)?;
+    let folders = select_folders(
+        client.list_folders().await?,
+        account.download_folders.as_deref(),
+    );
+    let mailboxes = sync_mailboxes(account_id, &folders)?;
+
+    for mailbox in &mailboxes {
+        DownloadState::update_folder_progress(
+            account_id,
+            mailbox.name.clone(),
+            0,
+            0,
+            FolderStatus::Pending,
+            None,
+        )?;
+    }
+
+    let mut failed = 0usize;
+    for (folder, mailbox) in folders.iter().zip(&mailboxes) {
+        if token.is_cancelled() {
+            return Ok(true);
+        }
+        match download_folder(&mut client, account, folder, mailbox, token).await {
+            Ok(true) => return Ok(true),
+            Ok(false) => {}
+            Err(e) => {
+                failed += 1;
+                let err_msg = format!("Failed to download folder '{}': {:#?}", folder.path, e);
+                warn!(account_id, "{}", err_msg);
+                DownloadState::update_folder_progress(
+                    account_id,
+                    mailbox.name.clone(),
+                    0,
+                    0,
+                    FolderStatus::Failed,
+                    Some(err_msg),
+                )?;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(raise_error!(
+            format!("{} of {} folders failed to download", failed, folders.len()),
+            ErrorCode::GraphRequestFailed
+        ));
+    }
+    Ok(false)
+}
+
+/// Applies the folder's delta to the archive and downloads its new
+/// messages. Returns whether the run was cancelled.
+///
+/// The delta link is only saved once every new message is archived, so a
+/// failed or cancelled run replays the same delta next time; messages
+/// archived in between are then recognized by their references.
+async fn download_folder(
+    client: &mut GraphClient,
+    account: &AccountModel,
+    folder: &GraphFolder,
+    mailbox: &MailBox,
+    token: &CancellationToken,
+) -> BichonResult<bool> {
+    let account_id = account.id;
+    let mut state = GraphFolderState::get(mailbox.id)?
+        .unwrap_or_else(|| GraphFolderState::new(account_id, mailbox.id, &folder.id));
+
+    let delta = match client
+        .message_delta(&folder.id, state.delta_link.as_deref())
+        .await?
+    {
+        Some(delta) => delta,
+        None => {
+            info!(
+                account_id,
+                folder = %folder.path,
+                "Graph sync state expired, resyncing folder"
+            );
+            client
+                .message_delta(&folder.id, None)
+                .await?
+                .ok_or_else(|| {
+                    raise_error!(
+                        "Graph rejected a delta query without sync state".into(),
+                        ErrorCode::GraphRequestFailed
+                    )
+                })?
+        }
+    };
+
+    let mut known = HashMap::new();
+    for change in &delta.changes {
+        if let Some(reference) = GraphMessageRef::find(account_id, mailbox.id, change.id())? {
+            known.insert(reference.message_id, reference.uid);
+        }
+    }
+    let plan = plan_folder_changes(&delta.changes, &known);
+
+    if !plan.flag_updates.is_empty() {
+        ENVELOPE_MANAGER
+            .update_envelope_flags(account_id, mailbox.id, plan.flag_updates)
+            .await?;
+    }
+    if !plan.removed.is_empty() {
+        let uids: Vec<u32> = plan.removed.iter().map(|(_, uid)| *uid).collect();
+        ENVELOPE_MANAGER
+            .mark_deleted_on_source(account_id, mailbox.id, &uids)
+            .await?;
+        let ids: Vec<String> = plan.removed.into_iter().map(|(id, _)| id).collect();
+        GraphMessageRef::forget(account_id, mailbox.id, &ids)?;
+    }
+
+    let max_size = account
+        .max_email_size_bytes
+        .unwrap_or(DEFAULT_MAX_EMAIL_SIZE);
+    let planned = plan.fetch.len() as u64;
+    let mut processed = 0u64;
+    let mut failed = 0u64;
+    let mut cancelled = false;
+    for message in &plan.fetch {
+        if token.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        let body = client.message_mime(&message.id).await?;
+        if body.len() as u64 > max_size {
+            warn!(
+                account_id,
+                folder = %folder.path,
+                size = body.len(),
+                "Skipping oversized Graph message"
+            );
+        } else {
+            match extract_envelope_from_api(
+                &body,
+                state.next_uid,
+                message.received_at,
+                message.flags.clone(),
+                account_id,
+                mailbox.id,
+            )
+            .await
+            {
+                Ok(()) => {
+                    GraphMessageRef::record(account_id, mailbox.id, &message.id, state.next_uid)?;
+                    state.next_uid += 1;
+                    state.save()?;
+                }
+                Err(e) => {
+                    failed += 1;
+                    let err_msg = format!(
+                        "Failed to archive Graph message {} in '{}': {:#?}",
+                        message.id, folder.path, e
+                    );
+                    warn!(account_id, "{}", err_msg);
+                    DownloadState::append_session_error(account_id, err_msg)?;
+                }
+            }
+        }
+        processed += 1;
+        DownloadState::update_folder_progress(
+            account_id,
+            mailbox.name.clone(),
+            planned,
+            processed,
+            FolderStatus::Downloading,
+            None,
+        )?;
+    }
+
+    let status = if cancelled {
+        FolderStatus::Cancelled
+    } else if failed > 0 {
+        FolderStatus::Failed
+    } else {
+        state.delta_link = Some(delta.delta_link);
+        FolderStatus::Success
+    };
+    state.save()?;
+    DownloadState::update_folder_progress(
+        account_id,
+        mailbox.name.clone(),
+        planned,
+        processed,
+        status,
+        None,
+    )?;
+    Ok(cancelled)
+}
+
+/// Creates or updates the mailbox rows of the folders, keyed by folder id
+/// so a renamed folder keeps its mailbox. Returns them in folder order.
+fn sync_mailboxes(account_id: u64, folders: &[GraphFolder]) -> BichonResult<Vec<MailBox>> {
+    let mailboxes: Vec<MailBox> = folders
+        .iter()
+        .map(|folder| {
+            let mailbox_id = create_hash(account_id, &folder.id);
+            let mut mailbox =
+                MailBox::find_mailbox(account_id, mailbox_id)?.unwrap_or_else(|| MailBox {
+                    id: mailbox_id,
+                    account_id,
+                    name: folder.path.clone(),
+                    delimiter: Some("/".to_string()),
+                    attributes: vec![],
+                    exists: 0,
+                    unseen: None,
+                    uid_next: None,
+                    uid_validity: None,
+                    highest_uid: None,
+                    highest_modseq: None,
+                });
+            mailbox.name = folder.path.clone();
+            mailbox.exists = folder.total_item_count;
+            Ok(mailbox)
+        })
+        .collect::<BichonResult<_>>()?;
+    MailBox::batch_upsert(&mailboxes)?;
+    Ok(mailboxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upsert(id: &str, flags: &[&str]) -> MessageChange {
+        MessageChange::Upsert {
+            id: id.into(),
+            received_at: 1,
+            flags: flags.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    fn removed(id: &str) -> MessageChange {
+        MessageChange::Removed { id: id.into() }
+    }
+
+    fn folder(path: &str) -> GraphFolder {
+        GraphFolder {
+            id: format!("{}-id", path),
+            path: path.into(),
+            total_item_count: 0,
+        }
+    }
+
+    #[test]
+    fn unknown_messages_are_fetched_and_known_ones_get_new_flags() {
+        let known = HashMap::from([("a".to_string(), 7)]);
+        let plan = plan_folder_changes(&[upsert("a", &["\\Seen"]), upsert("b", &[])], &known);
+        assert_eq!(plan.fetch.len(), 1);
+        assert_eq!(plan.fetch[0].id, "b");
+        assert_eq!(
+            plan.flag_updates,
+            HashMap::from([(7, vec!["\\Seen".to_string()])])
+        );
+        assert!(plan.removed.is_empty());
+    }
+
+    #[test]
+    fn removals_only_concern_archived_messages() {
+        let known = HashMap::from([("a".to_string(), 3)]);
+        let plan = plan_folder_changes(&[removed("a"), removed("never-seen")], &known);
+        assert_eq!(plan.removed, vec![("a".to_string(), 3)]);
+        assert!(plan.fetch.is_empty());
+    }
+
+    #[test]
+    fn last_entry_of_a_message_wins() {
+        let plan = plan_folder_changes(
+            &[
+                upsert("a", &[]),
+                upsert("b", &[]),
+                removed("a"),
+                upsert("b", &["\\Flagged"]),
+            ],
+            &HashMap::new(),
+        );
+        assert_eq!(plan.fetch.len(), 1);
+        assert_eq!(plan.fetch[0].id, "b");
+        assert_eq!(plan.fetch[0].flags, vec!["\\Flagged".to_string()]);
+    }
+
+    #[test]
+    fn folders_are_filtered_by_path() {
+        let folders = vec![
+            folder("Inbox"),
+            folder("Inbox/Invoices"),
+            folder("Junk Email"),
+        ];
+        let selected = vec!["Inbox/Invoices".to_string()];
+        let paths: Vec<String> = select_folders(folders.clone(), Some(&selected))
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(paths, vec!["Inbox/Invoices"]);
+        assert_eq!(select_folders(folders.clone(), Some(&[])).len(), 3);
+        assert_eq!(select_folders(folders, None).len(), 3);
+    }
+}
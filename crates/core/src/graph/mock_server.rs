@@ -0,0 +1,187 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal in-process HTTP server that replays recorded Microsoft Graph
+//! responses for integration testing.
+//!
+//! Responses are registered per request target (path and query, relative to
+//! the `/v1.0` endpoint). `{base}` in a body is replaced with the server's
+//! endpoint URL, so recorded `@odata.nextLink` and `@odata.deltaLink` values
+//! point back at the server. When several responses are registered for one
+//! target they are served in order and the last one repeats. Unknown targets
+//! get a Graph-style `404`. Every request is recorded for assertions.
+//!
+//! # Example
+//! ```ignore
+//! let server = MockGraphServer::new()
+//!     .json("/me/mailFolders?$top=100", include_str!("testdata/folders.json"))
+//!     .start()
+//!     .await;
+//!
+//! let client = GraphClient::new(reqwest::Client::new(), &server.endpoint(), None, "token".into());
+//! ```
+
+use crate::common::mock_http::{HttpRequest, HttpResponse, MockHttpServer};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Path prefix of the endpoint the server mimics.
+const API_VERSION: &str = "/v1.0";
+
+/// A request received by the server. Header names are lowercase.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub target: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct MockGraphServer {
+    routes: HashMap<String, VecDeque<HttpResponse>>,
+}
+
+impl MockGraphServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `body` as a `200` JSON response.
+    pub fn json(self, target: &str, body: &str) -> Self {
+        self.respond(
+            target,
+            200,
+            &[("Content-Type", "application/json")],
+            body.as_bytes(),
+        )
+    }
+
+    pub fn respond(
+        mut self,
+        target: &str,
+        status: u16,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Self {
+        let response = headers.iter().fold(
+            HttpResponse::new(status, body),
+            |response, (name, value)| response.header(name, *value),
+        );
+        self.routes
+            .entry(target.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    pub async fn start(self) -> MockGraphServerHandle {
+        let routes = Mutex::new(self.routes);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let server =
+            MockHttpServer::start(move |request| respond(&routes, &recorded, request)).await;
+        MockGraphServerHandle { server, requests }
+    }
+}
+
+pub struct MockGraphServerHandle {
+    server: MockHttpServer,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockGraphServerHandle {
+    /// Endpoint URL to configure the client with.
+    pub fn endpoint(&self) -> String {
+        format!("{}{}", self.server.url(), API_VERSION)
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Targets of all requests received so far, in order.
+    pub fn targets(&self) -> Vec<String> {
+        self.requests().into_iter().map(|r| r.target).collect()
+    }
+}
+
+fn respond(
+    routes: &Mutex<HashMap<String, VecDeque<HttpResponse>>>,
+    requests: &Mutex<Vec<RecordedRequest>>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let target = request
+        .target
+        .strip_prefix(API_VERSION)
+        .unwrap_or(&request.target)
+        .to_string();
+    requests.lock().unwrap().push(RecordedRequest {
+        target: target.clone(),
+        headers: request.headers,
+    });
+
+    let response = {
+        let mut routes = routes.lock().unwrap();
+        match routes.get_mut(&target) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        }
+    };
+    let Some(mut response) = response else {
+        return HttpResponse::new(
+            404,
+            r#"{"error":{"code":"ErrorItemNotFound","message":"The specified object was not found in the store."}}"#,
+        )
+        .header("Content-Type", "application/json");
+    };
+    let base = format!("http://{}{}", request.local_addr, API_VERSION);
+    response.body = String::from_utf8_lossy(&response.body)
+        .replace("{base}", &base)
+        .into_bytes();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_responses_in_order_and_repeats_the_last() {
+        let server = MockGraphServer::new()
+            .respond("/me/x", 429, &[("Retry-After", "0")], b"")
+            .json("/me/x", r#"{"next":"{base}/me/y"}"#)
+            .start()
+            .await;
+        let http = reqwest::Client::new();
+        let url = format!("{}/me/x", server.endpoint());
+
+        let first = http.get(&url).send().await.unwrap();
+        assert_eq!(first.status(), 429);
+        for _ in 0..2 {
+            let body = http.get(&url).send().await.unwrap().text().await.unwrap();
+            assert_eq!(body, format!(r#"{{"next":"{}/me/y"}}"#, server.endpoint()));
+        }
+        let missing = http
+            .get(format!("{}/me/z", server.endpoint()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), 404);
+        assert_eq!(server.targets(), vec!["/me/x", "/me/x", "/me/x", "/me/z"]);
+    }
+}
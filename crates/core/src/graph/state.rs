@@ -0,0 +1,163 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    database::{
+        batch_delete_impl, filter_impl, find_impl, manager::DB_MANAGER, upsert_impl, MemDbModel,
+    },
+    error::BichonResult,
+    utils::create_hash2,
+};
+use serde::{Deserialize, Serialize};
+
+/// Sync state of one Graph mail folder, keyed by the `MailBox` row the
+/// folder is archived into.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GraphFolderState {
+    pub mailbox_id: u64,
+    pub account_id: u64,
+    /// Graph id of the folder.
+    pub folder_id: String,
+    /// `@odata.deltaLink` returned by the last completed sync, `None` until
+    /// the first one completes.
+    pub delta_link: Option<String>,
+    /// UID handed to the next new message of the folder.
+    pub next_uid: u32,
+}
+
+impl MemDbModel for GraphFolderState {
+    fn collection() -> &'static str {
+        "graph_folders"
+    }
+    fn key(&self) -> String {
+        self.mailbox_id.to_string()
+    }
+}
+
+impl GraphFolderState {
+    pub fn new(account_id: u64, mailbox_id: u64, folder_id: &str) -> Self {
+        Self {
+            mailbox_id,
+            account_id,
+            folder_id: folder_id.to_string(),
+            delta_link: None,
+            next_uid: 1,
+        }
+    }
+
+    pub fn get(mailbox_id: u64) -> BichonResult<Option<GraphFolderState>> {
+        find_impl(DB_MANAGER.db(), &mailbox_id.to_string())
+    }
+
+    pub fn list_all(account_id: u64) -> BichonResult<Vec<GraphFolderState>> {
+        filter_impl::<GraphFolderState, _>(DB_MANAGER.db(), move |f| f.account_id == account_id)
+    }
+
+    pub fn save(&self) -> BichonResult<()> {
+        upsert_impl(DB_MANAGER.db(), self.clone())
+    }
+
+    pub fn clean(account_id: u64) -> BichonResult<()> {
+        let keys: Vec<String> = Self::list_all(account_id)?
+            .iter()
+            .map(|f| f.key())
+            .collect();
+        if !keys.is_empty() {
+            batch_delete_impl::<GraphFolderState>(DB_MANAGER.db(), keys)?;
+        }
+        Ok(())
+    }
+}
+
+/// A Graph message already archived into a mailbox, with the UID its
+/// envelope was stored under.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GraphMessageRef {
+    pub id: u64,
+    pub account_id: u64,
+    pub mailbox_id: u64,
+    /// Immutable Graph id of the message.
+    pub message_id: String,
+    pub uid: u32,
+}
+
+impl MemDbModel for GraphMessageRef {
+    fn collection() -> &'static str {
+        "graph_messages"
+    }
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl GraphMessageRef {
+    pub fn find(
+        account_id: u64,
+        mailbox_id: u64,
+        message_id: &str,
+    ) -> BichonResult<Option<GraphMessageRef>> {
+        find_impl(
+            DB_MANAGER.db(),
+            &create_hash2(account_id, mailbox_id, message_id).to_string(),
+        )
+    }
+
+    pub fn record(
+        account_id: u64,
+        mailbox_id: u64,
+        message_id: &str,
+        uid: u32,
+    ) -> BichonResult<()> {
+        upsert_impl(
+            DB_MANAGER.db(),
+            GraphMessageRef {
+                id: create_hash2(account_id, mailbox_id, message_id),
+                account_id,
+                mailbox_id,
+                message_id: message_id.to_string(),
+                uid,
+            },
+        )
+    }
+
+    /// Drops the references of messages removed from the folder.
+    pub fn forget(account_id: u64, mailbox_id: u64, message_ids: &[String]) -> BichonResult<()> {
+        if message_ids.is_empty() {
+            return Ok(());
+        }
+        let keys = message_ids
+            .iter()
+            .map(|id| create_hash2(account_id, mailbox_id, id).to_string())
+            .collect();
+        batch_delete_impl::<GraphMessageRef>(DB_MANAGER.db(), keys)?;
+        Ok(())
+    }
+
+    pub fn clean(account_id: u64) -> BichonResult<()> {
+        let keys: Vec<String> = filter_impl::<GraphMessageRef, _>(DB_MANAGER.db(), move |m| {
+            m.account_id == account_id
+        })?
+        .iter()
+        .map(|m| m.key())
+        .collect();
+        if !keys.is_empty() {
+            batch_delete_impl::<GraphMessageRef>(DB_MANAGER.db(), keys)?;
+        }
+        Ok(())
+    }
+}
@@ -41,6 +41,24 @@ pub fn check_capabilities(capabilities: &Capabilities) -> BichonResult<()> {
     Ok(())
 }
 
+/// Capability of Gmail's IMAP extensions (`X-GM-MSGID`, `X-GM-LABELS`).
+pub const GMAIL_EXTENSION: &str = "X-GM-EXT-1";
+
+/// Whether the server supports RFC 2177 IDLE.
+pub fn supports_idle(capabilities: &Capabilities) -> bool {
+    capabilities.has_str("IDLE")
+}
+
+/// Whether the server supports RFC 7162 CONDSTORE (implied by QRESYNC).
+pub fn supports_condstore(capabilities: &Capabilities) -> bool {
+    capabilities.has_str("CONDSTORE") || supports_qresync(capabilities)
+}
+
+/// Whether the server supports RFC 7162 QRESYNC.
+pub fn supports_qresync(capabilities: &Capabilities) -> bool {
+    capabilities.has_str("QRESYNC")
+}
+
 pub fn capability_to_string(capability: &Capability) -> String {
     match capability {
         Capability::Imap4rev1 => "IMAP4rev1".into(),
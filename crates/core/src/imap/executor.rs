@@ -20,18 +20,21 @@ use crate::account::migration::AccountModel;
 use crate::account::state::{DownloadState, DownloadStatus, FolderStatus};
 use crate::cache::imap::mailbox::MailBox;
 use crate::envelope::extractor::extract_envelope_and_store_it;
+use crate::envelope::flags::{flag_name, normalize_flags};
+use crate::envelope::gmail::{normalize_labels, GmailAttributes};
 use crate::error::code::ErrorCode;
 use crate::imap::session::SessionStream;
-use crate::raise_error;
+use crate::{decode_mailbox_name, raise_error};
 use crate::{error::BichonResult, imap::manager::ImapConnectionManager};
 use async_imap::types::Name;
 use async_imap::Session;
 use futures::TryStreamExt;
+use imap_proto::{AttributeValue, Response, ResponseCode, Status};
 use std::collections::{HashMap, HashSet};
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
-const BODY_FETCH_COMMAND: &str = "(UID INTERNALDATE RFC822.SIZE BODY.PEEK[])";
+const BODY_FETCH_COMMAND: &str = "(UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[])";
 const SIZE_ONLY_FETCH: &str = "(UID RFC822.SIZE)";
 const MAX_NETWORK_RETRIES: u32 = 3;
 
@@ -52,6 +55,119 @@ fn classify_imap_error(e: &async_imap::error::Error) -> ErrorCode {
     }
 }
 
+/// What a `SELECT`/`EXAMINE … (QRESYNC …)` reported since the known MODSEQ.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct QresyncChanges {
+    pub uid_validity: Option<u32>,
+    pub highest_modseq: Option<u64>,
+    /// New and changed messages with their current flags, keyed by UID.
+    pub changed: HashMap<u32, Vec<String>>,
+    /// UIDs expunged since the known MODSEQ.
+    pub vanished: Vec<u32>,
+}
+
+impl QresyncChanges {
+    fn apply(&mut self, response: &Response<'_>) {
+        match response {
+            Response::Vanished { uids, .. } => {
+                self.vanished.extend(uids.iter().cloned().flatten());
+            }
+            Response::Fetch(_, attributes) => {
+                let mut uid = None;
+                let mut flags = Vec::new();
+                for attribute in attributes {
+                    match attribute {
+                        AttributeValue::Uid(value) => uid = Some(*value),
+                        AttributeValue::Flags(values) => {
+                            flags = normalize_flags(values.iter());
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(uid) = uid {
+                    self.changed.insert(uid, flags);
+                }
+            }
+            Response::Data {
+                code: Some(ResponseCode::HighestModSeq(modseq)),
+                ..
+            } => self.highest_modseq = Some(*modseq),
+            Response::Data {
+                code: Some(ResponseCode::UidValidity(uid_validity)),
+                ..
+            } => self.uid_validity = Some(*uid_validity),
+            _ => {}
+        }
+    }
+}
+
+/// Runs `command` and hands every untagged response to `on_response` until
+/// the tagged completion, which must be OK. The responses are read off the
+/// connection directly: the session's unsolicited channel is bounded and
+/// silently drops what does not fit, so large replies would come back short.
+async fn run_command_with_responses(
+    session: &mut Session<Box<dyn SessionStream>>,
+    command: &str,
+    mut on_response: impl FnMut(&Response<'_>),
+) -> BichonResult<()> {
+    let request_id = session
+        .run_command(command)
+        .await
+        .map_err(|e| raise_error!(format!("{:#?}", e), classify_imap_error(&e)))?;
+    loop {
+        let response = session
+            .read_response()
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::NetworkError))?
+            .ok_or_else(|| {
+                raise_error!(
+                    format!("Connection closed while waiting for '{command}'"),
+                    ErrorCode::NetworkError
+                )
+            })?;
+        match response.parsed() {
+            Response::Done {
+                tag,
+                status,
+                code,
+                information,
+            } if *tag == request_id => {
+                return match status {
+                    Status::Ok => Ok(()),
+                    _ => Err(raise_error!(
+                        format!("'{command}' failed: {status:?} {code:?} {information:?}"),
+                        ErrorCode::ImapCommandFailed
+                    )),
+                };
+            }
+            parsed => on_response(parsed),
+        }
+    }
+}
+
+/// Collects the Gmail metadata of an untagged `FETCH` response.
+fn apply_gmail_attributes(response: &Response<'_>, result: &mut HashMap<u32, GmailAttributes>) {
+    let Response::Fetch(_, attributes) = response else {
+        return;
+    };
+    let mut uid = None;
+    let mut gmail = GmailAttributes::default();
+    for attribute in attributes {
+        match attribute {
+            AttributeValue::Uid(value) => uid = Some(*value),
+            AttributeValue::GmailMsgId(value) => gmail.msg_id = Some(*value),
+            // Labels are encoded like mailbox names (modified UTF-7).
+            AttributeValue::GmailLabels(values) => {
+                gmail.labels = normalize_labels(values.iter().map(|l| decode_mailbox_name!(l)));
+            }
+            _ => {}
+        }
+    }
+    if let Some(uid) = uid {
+        result.insert(uid, gmail);
+    }
+}
+
 pub struct ImapExecutor;
 
 impl ImapExecutor {
@@ -750,6 +866,91 @@ impl ImapExecutor {
         Ok(result)
     }
 
+    /// Opens the mailbox read-only with RFC 7162 QRESYNC, so the server reports
+    /// new and changed messages (with their flags) and the UIDs expunged since
+    /// `modseq` in a single round trip.
+    pub async fn examine_qresync(
+        session: &mut Session<Box<dyn SessionStream>>,
+        mailbox_name: &str,
+        uid_validity: u32,
+        modseq: u64,
+    ) -> BichonResult<QresyncChanges> {
+        session
+            .run_command_and_check_ok("ENABLE QRESYNC")
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), classify_imap_error(&e)))?;
+
+        let mut changes = QresyncChanges::default();
+        // VANISHED has no variant of its own in async-imap, so the responses
+        // are taken straight from the connection.
+        run_command_with_responses(
+            session,
+            &format!(
+                "EXAMINE {} (QRESYNC ({} {}))",
+                quote_mailbox_name(mailbox_name),
+                uid_validity,
+                modseq
+            ),
+            |response| changes.apply(response),
+        )
+        .await?;
+        Ok(changes)
+    }
+
+    /// Fetch `X-GM-MSGID` and `X-GM-LABELS` of a UID sequence-set in the
+    /// selected mailbox. With `changed_since` only messages whose MODSEQ is
+    /// higher are returned; Gmail bumps it on label changes as well.
+    pub async fn fetch_gmail_attributes(
+        session: &mut Session<Box<dyn SessionStream>>,
+        uid_set: &str,
+        changed_since: Option<u64>,
+    ) -> BichonResult<HashMap<u32, GmailAttributes>> {
+        let mut command = format!("UID FETCH {uid_set} (UID X-GM-MSGID X-GM-LABELS)");
+        if let Some(modseq) = changed_since {
+            command.push_str(&format!(" (CHANGEDSINCE {modseq})"));
+        }
+        let mut result = HashMap::new();
+        run_command_with_responses(session, &command, |response| {
+            apply_gmail_attributes(response, &mut result)
+        })
+        .await?;
+        Ok(result)
+    }
+
+    /// Fetch the flags of a UID sequence-set. `query` is the FETCH item list,
+    /// e.g. `(UID FLAGS)` or `(UID FLAGS) (CHANGEDSINCE 42)` on CONDSTORE
+    /// servers. Flags are returned in canonical form, keyed by UID.
+    pub async fn fetch_uid_flags(
+        session: &mut Session<Box<dyn SessionStream>>,
+        uid_set: &str,
+        query: &str,
+        token: CancellationToken,
+    ) -> BichonResult<HashMap<u32, Vec<String>>> {
+        let mut stream = session
+            .uid_fetch(uid_set, query)
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), classify_imap_error(&e)))?;
+
+        let mut result = HashMap::new();
+        while let Some(fetch) = stream
+            .try_next()
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), classify_imap_error(&e)))?
+        {
+            if token.is_cancelled() {
+                return Err(raise_error!(
+                    "Stream cancelled".into(),
+                    ErrorCode::InternalError
+                ));
+            }
+            if let Some(uid) = fetch.uid {
+                let flags = normalize_flags(fetch.flags().filter_map(|f| flag_name(&f)));
+                result.insert(uid, flags);
+            }
+        }
+        Ok(result)
+    }
+
     /// Fetch lightweight header metadata (UID, size, internal date, message-id)
     /// for a UID sequence-set, without downloading bodies. Used by gap-fill to
     /// build the remote side of the diff. The fetch deliberately asks only for
@@ -944,6 +1145,11 @@ pub fn generate_uid_sequence_hashset(
     result
 }
 
+/// Quotes an (already UTF-7 encoded) mailbox name for a raw command.
+fn quote_mailbox_name(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 fn parse_message_id_header(header_bytes: &[u8]) -> Option<String> {
     let header = std::str::from_utf8(header_bytes).ok()?;
     for line in header.lines() {
@@ -969,6 +1175,71 @@ fn parse_message_id_header(header_bytes: &[u8]) -> Option<String> {
 mod test {
     use super::*;
 
+    // ── QRESYNC ────────────────────────────────────────────────────
+
+    fn apply_lines(lines: &[&[u8]]) -> QresyncChanges {
+        let mut changes = QresyncChanges::default();
+        for line in lines {
+            changes.apply(&imap_proto::parser::parse_response(line).unwrap().1);
+        }
+        changes
+    }
+
+    #[test]
+    fn qresync_collects_changes_and_vanished_uids() {
+        let changes = apply_lines(&[
+            b"* OK [UIDVALIDITY 3857529045] UIDs valid\r\n",
+            b"* OK [HIGHESTMODSEQ 715194045007] Highest\r\n",
+            b"* VANISHED (EARLIER) 41,43:45\r\n",
+            b"* 49 FETCH (UID 117 FLAGS (\\Seen \\Answered) MODSEQ (90060115194045001))\r\n",
+            b"* 50 FETCH (UID 119 FLAGS (\\Draft $Forwarded) MODSEQ (90060115194045308))\r\n",
+        ]);
+        assert_eq!(changes.uid_validity, Some(3857529045));
+        assert_eq!(changes.highest_modseq, Some(715194045007));
+        assert_eq!(changes.vanished, vec![41, 43, 44, 45]);
+        assert_eq!(
+            changes.changed.get(&117),
+            Some(&vec!["\\Answered".to_string(), "\\Seen".to_string()])
+        );
+        assert_eq!(
+            changes.changed.get(&119),
+            Some(&vec!["$Forwarded".to_string(), "\\Draft".to_string()])
+        );
+    }
+
+    #[test]
+    fn gmail_fetch_yields_msgid_and_labels() {
+        let mut result = HashMap::new();
+        for line in [
+            &b"* 1 FETCH (X-GM-MSGID 1278455344230334865 X-GM-LABELS (\\Inbox \\Sent Important \"Work/Projects\") UID 4)\r\n"[..],
+            b"* 2 FETCH (UID 5 X-GM-MSGID 1278455344230334866 X-GM-LABELS ())\r\n",
+        ] {
+            apply_gmail_attributes(
+                &imap_proto::parser::parse_response(line).unwrap().1,
+                &mut result,
+            );
+        }
+        assert_eq!(
+            result.get(&4),
+            Some(&GmailAttributes {
+                msg_id: Some(1278455344230334865),
+                labels: vec![
+                    "Important".to_string(),
+                    "Inbox".to_string(),
+                    "Sent".to_string(),
+                    "Work/Projects".to_string(),
+                ],
+            })
+        );
+        assert_eq!(result.get(&5).map(|g| g.labels.len()), Some(0));
+    }
+
+    #[test]
+    fn quotes_mailbox_names() {
+        assert_eq!(quote_mailbox_name("INBOX"), "\"INBOX\"");
+        assert_eq!(quote_mailbox_name("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    }
+
     // ── compress_uid_list ──────────────────────────────────────────
 
     #[test]
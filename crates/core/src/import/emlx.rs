@@ -0,0 +1,224 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Apple Mail `.emlx` messages.
+//!
+//! An `.emlx` file is the byte length of the message on the first line, the
+//! RFC 822 message itself, then an XML property list whose `flags` integer
+//! holds the message state as a bit field. Mail keeps each mailbox in a
+//! `<Name>.mbox` directory, nested for subfolders, with the messages a few
+//! levels further down in `Messages/`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Folder for messages found outside any `.mbox` directory.
+pub const DEFAULT_FOLDER: &str = "INBOX";
+
+/// Bits of the plist `flags` value: (bit, IMAP flag).
+const FLAG_BITS: &[(u32, &str)] = &[
+    (0, "\\Seen"),
+    (1, "\\Deleted"),
+    (2, "\\Answered"),
+    (4, "\\Flagged"),
+    (6, "\\Draft"),
+    (8, "$Forwarded"),
+    (24, "$Junk"),
+    (25, "$NotJunk"),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Emlx<'a> {
+    /// The RFC 822 message.
+    pub message: &'a [u8],
+    /// IMAP flags decoded from the trailing plist.
+    pub flags: Vec<String>,
+}
+
+/// Splits an `.emlx` file into the message and its flags. A missing or
+/// unreadable plist yields no flags rather than an error.
+pub fn parse_emlx(data: &[u8]) -> io::Result<Emlx<'_>> {
+    let newline = data
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(|| invalid("missing byte count line"))?;
+    let length: usize = std::str::from_utf8(&data[..newline])
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .ok_or_else(|| invalid("invalid byte count line"))?;
+    let start = newline + 1;
+    let end = start
+        .checked_add(length)
+        .filter(|&end| end <= data.len())
+        .ok_or_else(|| invalid("byte count exceeds file size"))?;
+
+    let flags = plist_integer(&data[end..], "flags")
+        .map(decode_flags)
+        .unwrap_or_default();
+    Ok(Emlx {
+        message: &data[start..end],
+        flags,
+    })
+}
+
+/// Maps the Mail `flags` bit field to IMAP flags.
+pub fn decode_flags(bits: u64) -> Vec<String> {
+    let mut flags: Vec<String> = FLAG_BITS
+        .iter()
+        .filter(|(bit, _)| bits & (1 << bit) != 0)
+        .map(|(_, flag)| flag.to_string())
+        .collect();
+    flags.sort();
+    flags
+}
+
+/// Finds `<key>{key}</key><integer>N</integer>` in a property list.
+fn plist_integer(plist: &[u8], key: &str) -> Option<u64> {
+    let plist = String::from_utf8_lossy(plist);
+    let marker = format!("<key>{key}</key>");
+    let rest = plist[plist.find(&marker)? + marker.len()..].trim_start();
+    let rest = rest.strip_prefix("<integer>")?;
+    rest[..rest.find("</integer>")?].trim().parse().ok()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid emlx file: {msg}"),
+    )
+}
+
+/// Collects the `.emlx` files below `root`, keyed by folder name. The folder
+/// is built from the enclosing `.mbox` directories, e.g.
+/// `Archive.mbox/2019.mbox` becomes `Archive/2019`.
+///
+/// `.partial.emlx` files are included; Mail stores their larger attachments
+/// separately, so only the parts kept in the file itself are imported.
+pub fn discover_messages(root: &Path) -> io::Result<BTreeMap<String, Vec<PathBuf>>> {
+    let mut folders = BTreeMap::new();
+    scan_dir(root, root, &mut folders)?;
+    for files in folders.values_mut() {
+        files.sort();
+    }
+    Ok(folders)
+}
+
+fn scan_dir(
+    root: &Path,
+    dir: &Path,
+    folders: &mut BTreeMap<String, Vec<PathBuf>>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan_dir(root, &path, folders)?;
+        } else if is_emlx(&path) {
+            folders
+                .entry(folder_name(root, &path))
+                .or_default()
+                .push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_emlx(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("emlx"))
+}
+
+fn folder_name(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file);
+    let segments: Vec<&str> = relative
+        .components()
+        .filter_map(|c| c.as_os_str().to_str()?.strip_suffix(".mbox"))
+        .collect();
+    if segments.is_empty() {
+        DEFAULT_FOLDER.to_string()
+    } else {
+        segments.join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn emlx(message: &[u8], flags: u64) -> Vec<u8> {
+        let mut data = format!("{}\n", message.len()).into_bytes();
+        data.extend_from_slice(message);
+        data.extend_from_slice(
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n<dict>\n\
+                 \t<key>date-received</key>\n\t<integer>1700000000</integer>\n\
+                 \t<key>flags</key>\n\t<integer>{flags}</integer>\n</dict>\n</plist>\n"
+            )
+            .as_bytes(),
+        );
+        data
+    }
+
+    #[test]
+    fn splits_message_and_flags() {
+        let message = b"Subject: hi\r\n\r\nbody\r\n";
+        // read, flagged, forwarded, plus an attachment count in bits 10-15
+        let data = emlx(message, 1 | (1 << 4) | (1 << 8) | (2 << 10));
+        let parsed = parse_emlx(&data).unwrap();
+        assert_eq!(parsed.message, message);
+        assert_eq!(parsed.flags, vec!["$Forwarded", "\\Flagged", "\\Seen"]);
+    }
+
+    #[test]
+    fn tolerates_missing_plist() {
+        let parsed = parse_emlx(b"  5\nHello").unwrap();
+        assert_eq!(parsed.message, b"Hello");
+        assert!(parsed.flags.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_byte_count() {
+        assert!(parse_emlx(b"Subject: hi\n\nbody").is_err());
+        assert!(parse_emlx(b"500\nshort").is_err());
+        assert!(parse_emlx(b"").is_err());
+    }
+
+    #[test]
+    fn groups_messages_by_mbox_directories() {
+        let root = std::env::temp_dir().join(format!("bichon-emlx-{}", Uuid::new_v4()));
+        let inbox = root.join("INBOX.mbox/1A2B/Data/Messages");
+        let nested = root.join("Archive.mbox/2019.mbox/3C4D/Data/1/Messages");
+        fs::create_dir_all(&inbox).unwrap();
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(inbox.join("1.emlx"), emlx(b"a", 0)).unwrap();
+        fs::write(inbox.join("2.partial.emlx"), emlx(b"b", 0)).unwrap();
+        fs::write(inbox.join("Info.plist"), b"").unwrap();
+        fs::write(nested.join("3.emlx"), emlx(b"c", 0)).unwrap();
+
+        let folders = discover_messages(&root).unwrap();
+        assert_eq!(
+            folders.keys().collect::<Vec<_>>(),
+            vec!["Archive/2019", "INBOX"]
+        );
+        assert_eq!(folders["INBOX"].len(), 2);
+        assert_eq!(folders["Archive/2019"].len(), 1);
+    }
+}
@@ -0,0 +1,351 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Maildir folder discovery and flag mapping.
+//!
+//! Both common layouts are understood: Maildir++ (Dovecot's default and
+//! Courier), where subfolders are `.Parent.Child` directories next to the
+//! INBOX's `cur/new/tmp`, and Dovecot's `LAYOUT=fs`, where they are nested
+//! directories. Folder names are decoded from modified UTF-7 and use `/` as
+//! the hierarchy separator.
+
+use crate::decode_mailbox_name;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name given to the Maildir at the root of the tree.
+pub const INBOX: &str = "INBOX";
+
+/// Maildir info flags: (flag letter, IMAP flag).
+const INFO_FLAGS: &[(char, &str)] = &[
+    ('D', "\\Draft"),
+    ('F', "\\Flagged"),
+    ('P', "$Forwarded"),
+    ('R', "\\Answered"),
+    ('S', "\\Seen"),
+    ('T', "\\Deleted"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaildirFolder {
+    /// Folder name, e.g. `INBOX` or `Archive/2019`.
+    pub name: String,
+    /// Directory holding `cur`, `new` and `tmp`.
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaildirMessage {
+    pub path: PathBuf,
+    /// IMAP flags and keywords taken from the file name.
+    pub flags: Vec<String>,
+}
+
+impl MaildirFolder {
+    /// Lists the messages in `cur` and `new`. Messages still in `tmp` are
+    /// being delivered and are left alone.
+    pub fn messages(&self) -> io::Result<Vec<MaildirMessage>> {
+        let keywords = read_keywords(&self.path);
+        let mut messages = Vec::new();
+        for sub in ["cur", "new"] {
+            let dir = self.path.join(sub);
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if file_name.starts_with('.') || !path.is_file() {
+                    continue;
+                }
+                let flags = parse_info_flags(file_name, &keywords);
+                messages.push(MaildirMessage { path, flags });
+            }
+        }
+        messages.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(messages)
+    }
+}
+
+/// Whether `path` is a Maildir, i.e. holds a `cur` directory.
+pub fn is_maildir(path: &Path) -> bool {
+    path.join("cur").is_dir()
+}
+
+/// Finds every folder below `root`, sorted by name. A Maildir at the root
+/// itself is reported as [`INBOX`].
+pub fn discover_folders(root: &Path) -> io::Result<Vec<MaildirFolder>> {
+    let mut folders = Vec::new();
+    if is_maildir(root) {
+        folders.push(MaildirFolder {
+            name: INBOX.to_string(),
+            path: root.to_path_buf(),
+        });
+    }
+    scan_dir(root, &[], &mut folders)?;
+    folders.sort_by(|a, b| a.name.cmp(&b.name));
+    folders.dedup_by(|a, b| a.name == b.name);
+    Ok(folders)
+}
+
+fn scan_dir(dir: &Path, parents: &[String], folders: &mut Vec<MaildirFolder>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if matches!(dir_name, "cur" | "new" | "tmp" | "." | "..") || dir_name.starts_with("courier")
+        {
+            continue;
+        }
+
+        let segments: Vec<String> = match dir_name.strip_prefix('.') {
+            // Maildir++: the whole hierarchy is spelled out in the name.
+            Some(dotted) if parents.is_empty() => dotted
+                .split('.')
+                .filter(|s| !s.is_empty())
+                .map(|s| decode_mailbox_name!(s))
+                .collect(),
+            Some(_) => continue,
+            None => {
+                let mut segments = parents.to_vec();
+                segments.push(decode_mailbox_name!(dir_name));
+                segments
+            }
+        };
+        if segments.is_empty() {
+            continue;
+        }
+        if is_maildir(&path) {
+            folders.push(MaildirFolder {
+                name: segments.join("/"),
+                path: path.clone(),
+            });
+        }
+        if !dir_name.starts_with('.') {
+            scan_dir(&path, &segments, folders)?;
+        }
+    }
+    Ok(())
+}
+
+/// Maps the info part of a Maildir file name (`<unique>:2,<flags>`) to IMAP
+/// flags. Lower-case letters are Dovecot keywords, resolved through
+/// `keywords` (see [`read_keywords`]); unknown letters are dropped.
+/// Messages without an info part (still in `new`) have no flags.
+pub fn parse_info_flags(file_name: &str, keywords: &HashMap<char, String>) -> Vec<String> {
+    // `;` is used instead of `:` on filesystems that do not allow colons.
+    let info = file_name
+        .rsplit_once(":2,")
+        .or_else(|| file_name.rsplit_once(";2,"))
+        .map(|(_, info)| info)
+        .unwrap_or_default();
+
+    let mut flags: Vec<String> = info
+        .chars()
+        .filter_map(
+            |c| match INFO_FLAGS.iter().find(|(letter, _)| *letter == c) {
+                Some((_, flag)) => Some(flag.to_string()),
+                None => keywords.get(&c).cloned(),
+            },
+        )
+        .collect();
+    flags.sort();
+    flags.dedup();
+    flags
+}
+
+/// Reads Dovecot's `dovecot-keywords` file, which assigns the letters `a`
+/// to `z` used in file names to keyword names (`0 $Junk`, `1 project`, ...).
+pub fn read_keywords(folder: &Path) -> HashMap<char, String> {
+    let Ok(content) = fs::read_to_string(folder.join("dovecot-keywords")) else {
+        return HashMap::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let (index, name) = line.trim().split_once(char::is_whitespace)?;
+            let index: u8 = index.parse().ok()?;
+            let name = name.trim();
+            (index < 26 && !name.is_empty()).then(|| ((b'a' + index) as char, name.to_string()))
+        })
+        .collect()
+}
+
+/// Reads the folders listed in Dovecot's `subscriptions` file at the root,
+/// normalized to the names returned by [`discover_folders`]. Returns `None`
+/// when there is no such file.
+///
+/// Version 2 files (`V\t2` header) separate hierarchy levels with tabs;
+/// older files use the namespace separator, `.` for Maildir++.
+pub fn read_subscriptions(root: &Path) -> Option<Vec<String>> {
+    let content = fs::read_to_string(root.join("subscriptions")).ok()?;
+    let mut lines = content.lines().peekable();
+    let tab_separated = lines
+        .peek()
+        .is_some_and(|l| l.trim() == "V\t2" || l.trim() == "V 2");
+    if tab_separated {
+        lines.next();
+    }
+
+    let mut names: Vec<String> = lines
+        .map(str::trim_end)
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let segments: Vec<&str> = if tab_separated {
+                line.split('\t').collect()
+            } else if line.contains('/') {
+                line.split('/').collect()
+            } else {
+                line.split('.').collect()
+            };
+            let mut segments: Vec<String> = segments
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .map(|s| decode_mailbox_name!(s))
+                .collect();
+            // Courier lists subfolders below the INBOX namespace.
+            if segments.len() > 1 && segments[0].eq_ignore_ascii_case(INBOX) {
+                segments.remove(0);
+            }
+            segments.join("/")
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    Some(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("bichon-maildir-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn make_maildir(path: &Path) {
+        for sub in ["cur", "new", "tmp"] {
+            fs::create_dir_all(path.join(sub)).unwrap();
+        }
+    }
+
+    #[test]
+    fn maps_info_flags_and_keywords() {
+        let keywords = HashMap::from([('a', "$Junk".to_string()), ('b', "project".to_string())]);
+        assert_eq!(
+            parse_info_flags("1700000000.M1P2.host,S=120:2,FRSb", &keywords),
+            vec!["\\Answered", "\\Flagged", "\\Seen", "project"]
+        );
+        assert_eq!(
+            parse_info_flags("1.M1.host:2,PTz", &keywords),
+            vec!["$Forwarded", "\\Deleted"]
+        );
+        assert_eq!(
+            parse_info_flags("1.M1.host;2,D", &keywords),
+            vec!["\\Draft"]
+        );
+        assert!(parse_info_flags("1.M1.host", &keywords).is_empty());
+    }
+
+    #[test]
+    fn reads_dovecot_keywords() {
+        let root = temp_root();
+        fs::write(
+            root.join("dovecot-keywords"),
+            "0 $Junk\n1 project x\nbad\n30 overflow\n",
+        )
+        .unwrap();
+        let keywords = read_keywords(&root);
+        assert_eq!(keywords.len(), 2);
+        assert_eq!(keywords[&'a'], "$Junk");
+        assert_eq!(keywords[&'b'], "project x");
+    }
+
+    #[test]
+    fn discovers_maildir_plus_plus_and_fs_layouts() {
+        let root = temp_root();
+        make_maildir(&root);
+        make_maildir(&root.join(".Archive"));
+        make_maildir(&root.join(".Archive.2019"));
+        make_maildir(&root.join(".Entw&APw-rfe"));
+        make_maildir(&root.join("Projects/Alpha"));
+        fs::create_dir_all(root.join("courierimapkeywords")).unwrap();
+
+        let names: Vec<String> = discover_folders(&root)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Archive",
+                "Archive/2019",
+                "Entwürfe",
+                "INBOX",
+                "Projects/Alpha"
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_messages_with_flags() {
+        let root = temp_root();
+        make_maildir(&root);
+        fs::write(root.join("dovecot-keywords"), "0 $Junk\n").unwrap();
+        fs::write(root.join("cur/1.M1.host:2,Sa"), b"Subject: a\r\n\r\n").unwrap();
+        fs::write(root.join("new/2.M2.host"), b"Subject: b\r\n\r\n").unwrap();
+        fs::write(root.join("tmp/3.M3.host"), b"Subject: c\r\n\r\n").unwrap();
+
+        let folder = discover_folders(&root).unwrap().remove(0);
+        let messages = folder.messages().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].flags, vec!["$Junk", "\\Seen"]);
+        assert!(messages[1].flags.is_empty());
+    }
+
+    #[test]
+    fn reads_subscriptions() {
+        let root = temp_root();
+        assert_eq!(read_subscriptions(&root), None);
+
+        fs::write(root.join("subscriptions"), "V\t2\n\nArchive\t2019\nSent\n").unwrap();
+        assert_eq!(
+            read_subscriptions(&root).unwrap(),
+            vec!["Archive/2019", "Sent"]
+        );
+
+        fs::write(root.join("subscriptions"), "INBOX.Sent\nArchive.2019\n").unwrap();
+        assert_eq!(
+            read_subscriptions(&root).unwrap(),
+            vec!["Archive/2019", "Sent"]
+        );
+    }
+}
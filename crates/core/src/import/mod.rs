@@ -18,7 +18,11 @@
 
 
 //use poem_openapi::Object;
+pub mod emlx;
 pub mod history;
+pub mod maildir;
+pub mod msg;
+pub mod outlook;
 pub mod reader;
 pub mod pst;
 pub use history::ImportHistory;
@@ -34,7 +38,7 @@ use crate::{
     {
         account::migration::{AccountModel, AccountType},
         cache::imap::mailbox::{Attribute, AttributeEnum, MailBox},
-        envelope::extractor::extract_envelope_from_eml,
+        envelope::extractor::{extract_envelope_from_eml, extract_envelope_with_flags},
         error::{BichonResult, code::ErrorCode},
         settings::dir::DATA_DIR_MANAGER,
         utils::create_hash,
@@ -56,6 +60,10 @@ pub struct BatchEmlRequest {
     pub mail_folder: String,
     /// A list of emails in base64-encoded format. Each element represents one .eml file.
     pub emls: Vec<String>,
+    /// Optional IMAP flags for each entry of `emls`, in the same order
+    /// (e.g. `\Seen`, `$Junk`), as recovered from Maildir or Apple Mail.
+    #[serde(default)]
+    pub flags: Option<Vec<Vec<String>>>,
 }
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
@@ -93,7 +101,7 @@ impl ImportEmls {
         }
 
         let mailbox_id = match account.account_type {
-            AccountType::IMAP => {
+            AccountType::IMAP | AccountType::POP3 | AccountType::Graph | AccountType::JMAP => {
                 let all_mailboxes = MailBox::list_all(account.id)?;
                 let mailbox = all_mailboxes.into_iter().find(|m| m.name == request.mail_folder);
                 
@@ -122,6 +130,7 @@ impl ImportEmls {
                     uid_next: None,
                     uid_validity: None,
                     highest_uid: None,
+                    highest_modseq: None,
                 };
                 let mailbox_id = mailbox.id;
                 // Upsert the mailbox, creating it if it doesn't exist
@@ -135,8 +144,11 @@ impl ImportEmls {
         let mut failed_details: Vec<FailedItemDetail> = Vec::new(); // Store failure details
 
         let total = request.emls.len();
+        let mut flags = request.flags.take().unwrap_or_default();
+        flags.resize(total, Vec::new());
         let mut index: usize = 0;
         while let Some(eml_base64) = request.emls.pop() {
+            let eml_flags = flags.pop().unwrap_or_default();
             let decoded = match base64_decode_url_safe!(eml_base64.as_bytes()) {
                 Ok(bytes) => bytes,
                 Err(e) => {
@@ -168,7 +180,7 @@ impl ImportEmls {
                 continue;
             }
 
-            match extract_envelope_from_eml(&decoded, account_id, mailbox_id).await {
+            match extract_envelope_with_flags(&decoded, eml_flags, account_id, mailbox_id).await {
                 Ok(_) => {
                     success_count += 1;
                 },
@@ -259,11 +271,15 @@ pub enum FileFormat {
     Eml,
     Mbox,
     Pst,
+    Msg,
 }
 
 pub fn detect_format(bytes: &[u8], file_name: &str) -> Option<FileFormat> {
-    // PST files start with OLE2 compound document magic bytes
-    if bytes.len() >= 8 && &bytes[..8] == b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1" {
+    // PST files start with OLE2 compound document magic bytes; so do Outlook .msg files
+    if msg::is_compound_file(bytes) {
+        if file_name.to_lowercase().ends_with(".msg") {
+            return Some(FileFormat::Msg);
+        }
         return Some(FileFormat::Pst);
     }
 
@@ -299,6 +315,8 @@ pub fn detect_format(bytes: &[u8], file_name: &str) -> Option<FileFormat> {
         Some(FileFormat::Mbox)
     } else if lower.ends_with(".pst") {
         Some(FileFormat::Pst)
+    } else if lower.ends_with(".msg") {
+        Some(FileFormat::Msg)
     } else {
         None
     }
@@ -386,7 +404,7 @@ fn validate_import_account(account_id: u64) -> BichonResult<AccountModel> {
 /// Resolve or create a mailbox/folder for the given account.
 pub(super) fn resolve_mailbox(account: &AccountModel, folder: &str) -> BichonResult<u64> {
     match account.account_type {
-        AccountType::IMAP => {
+        AccountType::IMAP | AccountType::POP3 | AccountType::Graph | AccountType::JMAP => {
             // Shouldn't reach here (validated above), but handle gracefully
             let all_mailboxes = MailBox::list_all(account.id)?;
             let mailbox = all_mailboxes.into_iter().find(|m| m.name == folder);
@@ -413,6 +431,7 @@ pub(super) fn resolve_mailbox(account: &AccountModel, folder: &str) -> BichonRes
                 uid_next: None,
                 uid_validity: None,
                 highest_uid: None,
+                highest_modseq: None,
             };
             let mailbox_id = mailbox.id;
             MailBox::batch_upsert(&[mailbox])?;
@@ -514,6 +533,7 @@ pub fn process_uploaded_file(
         FileFormat::Eml => process_eml_file(import_id, file_path, account_id, mailbox_id, user_id, folder),
         FileFormat::Mbox => process_mbox_file(import_id, file_path, account_id, mailbox_id, user_id, folder),
         FileFormat::Pst => process_pst_upload(import_id, file_path, account_id, mailbox_id, user_id, folder),
+        FileFormat::Msg => process_msg_file(import_id, file_path, account_id, mailbox_id, user_id, folder),
     }
 }
 
@@ -529,7 +549,7 @@ fn detect_format_from_file(file_path: &Path, file_name: &str) -> BichonResult<Fi
 
     detect_format(&buf, file_name).ok_or_else(|| {
         raise_error!(
-            "Unknown file format. Supported: .eml, .mbox, .pst".into(),
+            "Unknown file format. Supported: .eml, .mbox, .pst, .msg".into(),
             ErrorCode::InvalidParameter
         )
     })
@@ -585,6 +605,56 @@ fn process_eml_file(
     update_progress(import_id, final_progress);
 }
 
+/// Process a single Outlook .msg file. Like EML uploads it is at most
+/// `MAX_WEB_EML_BYTES`, so it is read entirely and converted in memory.
+fn process_msg_file(
+    import_id: &str,
+    file_path: &Path,
+    account_id: u64,
+    mailbox_id: u64,
+    user_id: u64,
+    folder: &str,
+) {
+    let eml_bytes = match std::fs::read(file_path).and_then(|data| msg::msg_to_eml(&data)) {
+        Ok(b) => b,
+        Err(e) => {
+            fail_progress(import_id, "msg", &format!("Failed to read MSG file: {}", e), user_id, account_id, folder);
+            let _ = std::fs::remove_file(file_path);
+            return;
+        }
+    };
+
+    let total = 1;
+    update_progress(import_id, ImportProgress {
+        import_id: import_id.to_string(),
+        status: ImportStatus::Processing,
+        format: "msg".to_string(),
+        total,
+        success: 0,
+        duplicates: 0,
+        failed: 0,
+        failed_details: vec![],
+    });
+
+    let (success_count, failed_details) = process_single_eml(&eml_bytes, 0, account_id, mailbox_id);
+
+    // Clean up
+    let _ = std::fs::remove_file(file_path);
+
+    let final_progress = ImportProgress {
+        import_id: import_id.to_string(),
+        status: ImportStatus::Completed,
+        format: "msg".to_string(),
+        total,
+        success: success_count,
+        duplicates: 0,
+        failed: failed_details.len(),
+        failed_details,
+    };
+    history::save_import_history(user_id, account_id, folder, &final_progress);
+    update_progress(import_id, final_progress);
+}
+
 /// Process an MBOX file using memory-mapped I/O. Messages are yielded one at a
 /// time by `MboxReader` — the full file is never loaded into RAM.
 fn process_mbox_file(
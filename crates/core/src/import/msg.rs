@@ -0,0 +1,510 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Outlook `.msg` files (MS-OXMSG).
+//!
+//! A `.msg` file is a compound file holding one message. Fixed-size
+//! properties sit in the `__properties_version1.0` stream of each storage;
+//! strings and binaries get a `__substg1.0_<tag>` stream of their own.
+//! Recipients and attachments are sub-storages, and an attached Outlook item
+//! is a complete message in the attachment's `__substg1.0_3701000D` storage.
+
+use super::outlook::{strip_subject_marker, AttachmentContent, OutlookAttachment, OutlookMessage};
+use super::pst::encoding;
+use cfb::CompoundFile;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// First bytes of every compound file.
+pub const CFB_MAGIC: &[u8] = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1";
+
+const PROPERTIES_STREAM: &str = "__properties_version1.0";
+const SUBSTG_PREFIX: &str = "__substg1.0_";
+const RECIPIENT_PREFIX: &str = "__recip_version1.0_#";
+const ATTACHMENT_PREFIX: &str = "__attach_version1.0_#";
+const EMBEDDED_MESSAGE: &str = "__substg1.0_3701000D";
+
+/// Header sizes of the property stream, which differ per storage kind.
+const TOP_LEVEL_HEADER: usize = 32;
+const EMBEDDED_HEADER: usize = 24;
+const SUB_OBJECT_HEADER: usize = 8;
+
+/// Attached messages nested deeper than this are dropped.
+const MAX_EMBEDDING_DEPTH: usize = 8;
+
+const PT_SHORT: u16 = 0x0002;
+const PT_LONG: u16 = 0x0003;
+const PT_BOOLEAN: u16 = 0x000B;
+const PT_STRING8: u16 = 0x001E;
+const PT_UNICODE: u16 = 0x001F;
+const PT_SYSTIME: u16 = 0x0040;
+const PT_BINARY: u16 = 0x0102;
+
+/// Windows-1252, used for 8-bit strings without a code page.
+const DEFAULT_CODE_PAGE: u16 = 1252;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Integer(i32),
+    Boolean(bool),
+    Time(i64),
+    String(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, Default)]
+struct Properties(HashMap<u16, Value>);
+
+impl Properties {
+    fn string(&self, id: u16) -> Option<String> {
+        match self.0.get(&id) {
+            Some(Value::String(s)) if !s.is_empty() => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    fn binary(&self, id: u16) -> Option<&[u8]> {
+        match self.0.get(&id) {
+            Some(Value::Binary(b)) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn integer(&self, id: u16) -> Option<i32> {
+        match self.0.get(&id) {
+            Some(Value::Integer(i)) => Some(*i),
+            Some(Value::Boolean(b)) => Some(*b as i32),
+            _ => None,
+        }
+    }
+
+    fn time(&self, id: u16) -> Option<i64> {
+        match self.0.get(&id) {
+            Some(Value::Time(t)) => Some(*t),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `bytes` starts like a compound file (`.msg`, but also `.doc`).
+pub fn is_compound_file(bytes: &[u8]) -> bool {
+    bytes.starts_with(CFB_MAGIC)
+}
+
+/// Reads a `.msg` file.
+pub fn parse_msg(data: &[u8]) -> io::Result<OutlookMessage> {
+    let mut file = CompoundFile::open(Cursor::new(data))?;
+    if !file.is_stream(Path::new("/").join(PROPERTIES_STREAM)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not an Outlook message: missing property stream",
+        ));
+    }
+    read_message(&mut file, Path::new("/"), TOP_LEVEL_HEADER, None, 0)
+}
+
+/// Converts a `.msg` file to an RFC 5322 message.
+pub fn msg_to_eml(data: &[u8]) -> io::Result<Vec<u8>> {
+    parse_msg(data)?.to_eml()
+}
+
+fn read_message<F: Read + Seek>(
+    file: &mut CompoundFile<F>,
+    storage: &Path,
+    header_len: usize,
+    code_page: Option<u16>,
+    depth: usize,
+) -> io::Result<OutlookMessage> {
+    let props = read_properties(file, storage, header_len, code_page)?;
+    let code_page = code_page.or_else(|| message_code_page(&props));
+
+    let mut to = Vec::new();
+    let mut cc = Vec::new();
+    let mut bcc = Vec::new();
+    for recipient in sub_storages(file, storage, RECIPIENT_PREFIX)? {
+        let r = read_properties(file, &recipient, SUB_OBJECT_HEADER, code_page)?;
+        let Some(email) = r.string(0x39FE).or_else(|| r.string(0x3003)) else {
+            continue;
+        };
+        match r.integer(0x0C15) {
+            Some(1) => to.push(email),
+            Some(2) => cc.push(email),
+            Some(3) => bcc.push(email),
+            _ => {}
+        }
+    }
+    if to.is_empty() && cc.is_empty() && bcc.is_empty() {
+        to.extend(props.string(0x0076));
+    }
+
+    let mut attachments = Vec::new();
+    for storage in sub_storages(file, storage, ATTACHMENT_PREFIX)? {
+        if let Some(attachment) = read_attachment(file, &storage, code_page, depth)? {
+            attachments.push(attachment);
+        }
+    }
+
+    Ok(OutlookMessage {
+        subject: props.string(0x0037).map(|s| strip_subject_marker(&s)),
+        message_id: props.string(0x1035),
+        in_reply_to: props.string(0x1042),
+        references: props.string(0x1039),
+        conversation_index: props.binary(0x3013).map(<[u8]>::to_vec),
+        from: props
+            .string(0x5D01)
+            .or_else(|| props.string(0x5D02))
+            .or_else(|| props.string(0x0C1F)),
+        filetime: props.time(0x0039).or_else(|| props.time(0x0E06)),
+        to,
+        cc,
+        bcc,
+        html: read_html(&props),
+        text: props.string(0x1000).or_else(|| {
+            props
+                .binary(0x1009)
+                .and_then(encoding::decode_rtf_compressed)
+        }),
+        attachments,
+    })
+}
+
+fn read_attachment<F: Read + Seek>(
+    file: &mut CompoundFile<F>,
+    storage: &Path,
+    code_page: Option<u16>,
+    depth: usize,
+) -> io::Result<Option<OutlookAttachment>> {
+    let props = read_properties(file, storage, SUB_OBJECT_HEADER, code_page)?;
+    let embedded = storage.join(EMBEDDED_MESSAGE);
+    let content = if file.is_storage(&embedded) {
+        if depth >= MAX_EMBEDDING_DEPTH {
+            tracing::warn!("Skipping attached message nested {} levels deep", depth + 1);
+            return Ok(None);
+        }
+        let message = read_message(file, &embedded, EMBEDDED_HEADER, code_page, depth + 1)?;
+        AttachmentContent::Message(Box::new(message))
+    } else {
+        match props.binary(0x3701) {
+            Some(data) => AttachmentContent::Data(data.to_vec()),
+            // OLE objects and attachments by reference carry no data.
+            None => return Ok(None),
+        }
+    };
+
+    Ok(Some(OutlookAttachment {
+        name: props
+            .string(0x3707)
+            .or_else(|| props.string(0x3704))
+            .or_else(|| props.string(0x3001)),
+        mime: props.string(0x370E),
+        content_id: props.string(0x3712),
+        inline: props.integer(0x3714).is_some_and(|flags| flags & 0x4 != 0),
+        content,
+    }))
+}
+
+fn read_html(props: &Properties) -> Option<String> {
+    match props.0.get(&0x1013) {
+        Some(Value::Binary(buffer)) => {
+            let code_page = props.integer(0x3FDE).map(|c| c as u16).unwrap_or(65001);
+            encoding::decode_html_body(buffer, code_page)
+        }
+        Some(Value::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// `PR_MESSAGE_CODEPAGE`, else `PR_INTERNET_CPID`.
+fn message_code_page(props: &Properties) -> Option<u16> {
+    props
+        .integer(0x3FFD)
+        .or_else(|| props.integer(0x3FDE))
+        .map(|c| c as u16)
+}
+
+/// Child storages whose name starts with `prefix`, in index order.
+fn sub_storages<F: Read + Seek>(
+    file: &CompoundFile<F>,
+    storage: &Path,
+    prefix: &str,
+) -> io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = file
+        .read_storage(storage)?
+        .filter(|e| e.is_storage() && e.name().starts_with(prefix))
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn read_stream<F: Read + Seek>(file: &mut CompoundFile<F>, path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.open_stream(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn read_properties<F: Read + Seek>(
+    file: &mut CompoundFile<F>,
+    storage: &Path,
+    header_len: usize,
+    code_page: Option<u16>,
+) -> io::Result<Properties> {
+    let mut props = Properties::default();
+
+    let fixed = storage.join(PROPERTIES_STREAM);
+    if file.is_stream(&fixed) {
+        let data = read_stream(file, &fixed)?;
+        for entry in data.get(header_len..).unwrap_or_default().chunks_exact(16) {
+            let tag = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            let (id, kind) = ((tag >> 16) as u16, tag as u16);
+            let value = &entry[8..16];
+            let value = match kind {
+                PT_SHORT => Value::Integer(i16::from_le_bytes([value[0], value[1]]) as i32),
+                PT_LONG => Value::Integer(i32::from_le_bytes(value[0..4].try_into().unwrap())),
+                PT_BOOLEAN => Value::Boolean(value[0] != 0),
+                PT_SYSTIME => Value::Time(i64::from_le_bytes(value.try_into().unwrap())),
+                _ => continue,
+            };
+            props.0.insert(id, value);
+        }
+    }
+
+    let code_page = code_page
+        .or_else(|| message_code_page(&props))
+        .unwrap_or(DEFAULT_CODE_PAGE);
+    let streams: Vec<(PathBuf, u16, u16)> = file
+        .read_storage(storage)?
+        .filter(|e| e.is_stream())
+        .filter_map(|e| {
+            let tag = u32::from_str_radix(e.name().strip_prefix(SUBSTG_PREFIX)?, 16).ok()?;
+            Some((e.path().to_path_buf(), (tag >> 16) as u16, tag as u16))
+        })
+        .collect();
+    for (path, id, kind) in streams {
+        let value = match kind {
+            PT_UNICODE => Value::String(decode_unicode(&read_stream(file, &path)?)),
+            PT_STRING8 => Value::String(decode_string8(&read_stream(file, &path)?, code_page)),
+            PT_BINARY => Value::Binary(read_stream(file, &path)?),
+            _ => continue,
+        };
+        props.0.insert(id, value);
+    }
+    Ok(props)
+}
+
+fn decode_unicode(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+fn decode_string8(data: &[u8], code_page: u16) -> String {
+    let data = match data.iter().position(|&b| b == 0) {
+        Some(end) => &data[..end],
+        None => data,
+    };
+    encoding::decode_html_body(data, code_page)
+        .unwrap_or_else(|| String::from_utf8_lossy(data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail_parser::{MessageParser, MimeHeaders};
+    use std::io::Write;
+
+    /// Writes a property stream and the variable-length property streams
+    /// for one storage.
+    fn write_props<F: Read + Write + Seek>(
+        file: &mut CompoundFile<F>,
+        storage: &Path,
+        header_len: usize,
+        fixed: &[(u32, [u8; 8])],
+        strings: &[(u16, &str)],
+        binaries: &[(u16, &[u8])],
+    ) {
+        let mut data = vec![0u8; header_len];
+        for (tag, value) in fixed {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&[0u8; 4]);
+            data.extend_from_slice(value);
+        }
+        file.create_stream(storage.join(PROPERTIES_STREAM))
+            .unwrap()
+            .write_all(&data)
+            .unwrap();
+        for (id, value) in strings {
+            let utf16: Vec<u8> = value.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            file.create_stream(storage.join(format!("{SUBSTG_PREFIX}{id:04X}001F")))
+                .unwrap()
+                .write_all(&utf16)
+                .unwrap();
+        }
+        for (id, value) in binaries {
+            file.create_stream(storage.join(format!("{SUBSTG_PREFIX}{id:04X}0102")))
+                .unwrap()
+                .write_all(value)
+                .unwrap();
+        }
+    }
+
+    fn long(id: u16, value: i32) -> (u32, [u8; 8]) {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&value.to_le_bytes());
+        (((id as u32) << 16) | PT_LONG as u32, bytes)
+    }
+
+    fn write_recipient<F: Read + Write + Seek>(
+        file: &mut CompoundFile<F>,
+        parent: &Path,
+        index: usize,
+        kind: i32,
+        email: &str,
+    ) {
+        let storage = parent.join(format!("{RECIPIENT_PREFIX}{index:08X}"));
+        file.create_storage(&storage).unwrap();
+        write_props(
+            file,
+            &storage,
+            SUB_OBJECT_HEADER,
+            &[long(0x0C15, kind)],
+            &[(0x39FE, email)],
+            &[],
+        );
+    }
+
+    fn sample_msg() -> Vec<u8> {
+        let mut file = CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        let root = Path::new("/");
+        let submit: i64 = 133_000_000_000_000_000;
+        write_props(
+            &mut file,
+            root,
+            TOP_LEVEL_HEADER,
+            &[(0x0039_0040, submit.to_le_bytes())],
+            &[
+                (0x0037, "\u{1}\u{5}Fwd: quarterly numbers"),
+                (0x1035, "<outer@example.com>"),
+                (0x5D01, "alice@example.com"),
+                (0x1000, "See attached."),
+            ],
+            &[],
+        );
+        write_recipient(&mut file, root, 0, 1, "bob@example.com");
+        write_recipient(&mut file, root, 1, 2, "carol@example.com");
+
+        let pdf = root.join(format!("{ATTACHMENT_PREFIX}00000000"));
+        file.create_storage(&pdf).unwrap();
+        write_props(
+            &mut file,
+            &pdf,
+            SUB_OBJECT_HEADER,
+            &[long(0x3705, 1)],
+            &[(0x3707, "numbers.pdf"), (0x370E, "application/pdf")],
+            &[(0x3701, b"%PDF-1.4")],
+        );
+
+        let item = root.join(format!("{ATTACHMENT_PREFIX}00000001"));
+        file.create_storage(&item).unwrap();
+        write_props(
+            &mut file,
+            &item,
+            SUB_OBJECT_HEADER,
+            &[long(0x3705, 5)],
+            &[(0x3001, "quarterly numbers")],
+            &[],
+        );
+        let embedded = item.join(EMBEDDED_MESSAGE);
+        file.create_storage(&embedded).unwrap();
+        write_props(
+            &mut file,
+            &embedded,
+            EMBEDDED_HEADER,
+            &[],
+            &[
+                (0x0037, "quarterly numbers"),
+                (0x5D01, "dave@example.com"),
+                (0x1000, "Numbers inside."),
+            ],
+            &[],
+        );
+        write_recipient(&mut file, &embedded, 0, 1, "alice@example.com");
+
+        file.flush().unwrap();
+        file.into_inner().into_inner()
+    }
+
+    #[test]
+    fn parses_message_with_recipients_and_attachments() {
+        let data = sample_msg();
+        assert!(is_compound_file(&data));
+
+        let message = parse_msg(&data).unwrap();
+        assert_eq!(message.subject.as_deref(), Some("Fwd: quarterly numbers"));
+        assert_eq!(message.from.as_deref(), Some("alice@example.com"));
+        assert_eq!(message.to, vec!["bob@example.com"]);
+        assert_eq!(message.cc, vec!["carol@example.com"]);
+        assert_eq!(message.filetime, Some(133_000_000_000_000_000));
+        assert_eq!(message.attachments.len(), 2);
+        match &message.attachments[1].content {
+            AttachmentContent::Message(inner) => {
+                assert_eq!(inner.from.as_deref(), Some("dave@example.com"));
+                assert_eq!(inner.to, vec!["alice@example.com"]);
+            }
+            other => panic!("expected an embedded message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn converts_to_eml() {
+        let eml = msg_to_eml(&sample_msg()).unwrap();
+        let parsed = MessageParser::new().parse(&eml).unwrap();
+        assert_eq!(parsed.subject(), Some("Fwd: quarterly numbers"));
+        assert_eq!(parsed.message_id(), Some("outer@example.com"));
+        assert_eq!(parsed.body_text(0).as_deref(), Some("See attached."));
+        assert_eq!(
+            parsed.attachment(0).unwrap().attachment_name(),
+            Some("numbers.pdf")
+        );
+
+        let nested = parsed.attachment(1).unwrap();
+        assert_eq!(nested.attachment_name(), Some("quarterly numbers.eml"));
+        let nested = nested.message().unwrap();
+        assert_eq!(nested.subject(), Some("quarterly numbers"));
+        assert_eq!(nested.body_text(0).as_deref(), Some("Numbers inside."));
+    }
+
+    #[test]
+    fn rejects_other_compound_files() {
+        let mut file = CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        file.create_stream("/WordDocument")
+            .unwrap()
+            .write_all(b"doc")
+            .unwrap();
+        file.flush().unwrap();
+        let data = file.into_inner().into_inner();
+        assert!(is_compound_file(&data));
+        assert!(parse_msg(&data).is_err());
+        assert!(parse_msg(b"not a compound file").is_err());
+    }
+}
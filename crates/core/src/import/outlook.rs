@@ -0,0 +1,223 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Outlook messages as read from PST stores and `.msg` files, and their
+//! conversion to RFC 5322. The readers only decode MAPI properties; building
+//! the MIME message is shared here so both formats come out the same.
+
+use chrono::{DateTime, TimeZone, Utc};
+use mail_send::mail_builder::headers::text::Text;
+use mail_send::mail_builder::MessageBuilder;
+
+/// Fallback name for attachments without a file name.
+pub const UNNAMED_ATTACHMENT: &str = "unnamed_attachment";
+
+#[derive(Debug, Clone, Default)]
+pub struct OutlookMessage {
+    pub subject: Option<String>,
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub references: Option<String>,
+    /// `PR_CONVERSATION_INDEX`, kept as `X-Bichon-Conversation-ID`.
+    pub conversation_index: Option<Vec<u8>>,
+    pub from: Option<String>,
+    /// Submit or delivery time, as a Windows FILETIME.
+    pub filetime: Option<i64>,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub html: Option<String>,
+    pub text: Option<String>,
+    pub attachments: Vec<OutlookAttachment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutlookAttachment {
+    pub name: Option<String>,
+    pub mime: Option<String>,
+    pub content_id: Option<String>,
+    /// Rendered in the body (`ATT_MHTML_REF`) rather than listed.
+    pub inline: bool,
+    pub content: AttachmentContent,
+}
+
+#[derive(Debug, Clone)]
+pub enum AttachmentContent {
+    Data(Vec<u8>),
+    /// An attached Outlook item, e.g. a forwarded message.
+    Message(Box<OutlookMessage>),
+}
+
+impl OutlookMessage {
+    /// Builds the RFC 5322 message. Embedded messages become
+    /// `message/rfc822` attachments.
+    pub fn to_eml(&self) -> std::io::Result<Vec<u8>> {
+        let mut builder = MessageBuilder::new();
+        if let Some(sub) = &self.subject {
+            builder = builder.subject(sub.as_str());
+        }
+        if let Some(mid) = &self.message_id {
+            builder = builder.message_id(mid.as_str());
+        }
+        if let Some(irt) = &self.in_reply_to {
+            builder = builder.in_reply_to(irt.as_str());
+        }
+        if let Some(refs) = &self.references {
+            builder = builder.header("References", Text::new(refs.as_str()));
+        }
+        if let Some(index) = &self.conversation_index {
+            builder = builder.header("X-Bichon-Conversation-ID", Text::new(hex::encode(index)));
+        }
+        if let Some(f) = &self.from {
+            builder = builder.from(f.as_str());
+        }
+        if let Some(filetime) = self.filetime {
+            builder = builder.date(filetime_to_datetime(filetime).timestamp());
+        }
+        if !self.to.is_empty() {
+            builder = builder.to(self.to.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        }
+        if !self.cc.is_empty() {
+            builder = builder.cc(self.cc.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        }
+        if !self.bcc.is_empty() {
+            builder = builder.bcc(self.bcc.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        }
+        if let Some(html) = &self.html {
+            builder = builder.html_body(html.as_str());
+        }
+        if let Some(text) = &self.text {
+            builder = builder.text_body(text.as_str());
+        }
+
+        for attachment in &self.attachments {
+            match &attachment.content {
+                AttachmentContent::Data(data) => {
+                    let mime = attachment
+                        .mime
+                        .clone()
+                        .unwrap_or_else(|| "application/octet-stream".into());
+                    match (&attachment.content_id, attachment.inline) {
+                        (Some(content_id), true) => {
+                            builder = builder.inline(mime, content_id.clone(), data.clone());
+                        }
+                        _ => {
+                            let name = attachment
+                                .name
+                                .clone()
+                                .unwrap_or_else(|| UNNAMED_ATTACHMENT.to_string());
+                            builder = builder.attachment(mime, name, data.clone());
+                        }
+                    }
+                }
+                AttachmentContent::Message(message) => {
+                    let eml = message.to_eml()?;
+                    let name = attachment
+                        .name
+                        .clone()
+                        .or_else(|| message.subject.clone())
+                        .map(|n| {
+                            if n.to_lowercase().ends_with(".eml") {
+                                n
+                            } else {
+                                format!("{n}.eml")
+                            }
+                        })
+                        .unwrap_or_else(|| format!("{UNNAMED_ATTACHMENT}.eml"));
+                    builder = builder.attachment("message/rfc822", name, eml);
+                }
+            }
+        }
+
+        builder.write_to_vec()
+    }
+}
+
+pub fn filetime_to_datetime(filetime: i64) -> DateTime<Utc> {
+    let unix_secs = (filetime / 10_000_000) - 11_644_473_600;
+    let nsecs = (filetime % 10_000_000) * 100;
+    Utc.timestamp_opt(unix_secs, nsecs as u32).unwrap()
+}
+
+/// Drops the subject prefix marker Outlook may store in `PR_SUBJECT`: a
+/// `0x01` followed by the prefix length.
+pub fn strip_subject_marker(subject: &str) -> String {
+    match subject.strip_prefix('\u{1}') {
+        Some(rest) => rest.chars().skip(1).collect(),
+        None => subject.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail_parser::{MessageParser, MimeHeaders};
+
+    fn message(subject: &str) -> OutlookMessage {
+        OutlookMessage {
+            subject: Some(subject.into()),
+            from: Some("alice@example.com".into()),
+            to: vec!["bob@example.com".into()],
+            text: Some("hello".into()),
+            filetime: Some(133_000_000_000_000_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn builds_message_with_attachments() {
+        let mut outer = message("Fwd: report");
+        outer.attachments = vec![
+            OutlookAttachment {
+                name: Some("report.pdf".into()),
+                mime: Some("application/pdf".into()),
+                content_id: None,
+                inline: false,
+                content: AttachmentContent::Data(b"%PDF-1.4".to_vec()),
+            },
+            OutlookAttachment {
+                name: None,
+                mime: None,
+                content_id: None,
+                inline: false,
+                content: AttachmentContent::Message(Box::new(message("report"))),
+            },
+        ];
+
+        let eml = outer.to_eml().unwrap();
+        let parsed = MessageParser::new().parse(&eml).unwrap();
+        assert_eq!(parsed.subject(), Some("Fwd: report"));
+        assert_eq!(parsed.body_text(0).as_deref(), Some("hello"));
+        assert_eq!(parsed.attachment_count(), 2);
+        assert_eq!(
+            parsed.attachment(0).unwrap().attachment_name(),
+            Some("report.pdf")
+        );
+
+        let nested = parsed.attachment(1).unwrap();
+        assert_eq!(nested.attachment_name(), Some("report.eml"));
+        let nested = nested.message().expect("embedded message is parsed");
+        assert_eq!(nested.subject(), Some("report"));
+    }
+
+    #[test]
+    fn strips_subject_prefix_marker() {
+        assert_eq!(strip_subject_marker("\u{1}\u{4}RE: hi"), "RE: hi");
+        assert_eq!(strip_subject_marker("hi"), "hi");
+    }
+}
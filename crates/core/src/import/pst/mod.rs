@@ -16,11 +16,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use super::outlook::{AttachmentContent, OutlookAttachment, OutlookMessage};
 use crate::base64_encode_url_safe;
 use crate::envelope::extractor::extract_envelope_from_eml;
-use chrono::{DateTime, TimeZone, Utc};
-use mail_send::mail_builder::headers::text::Text;
-use mail_send::mail_builder::MessageBuilder;
 use outlook_pst::ltp::prop_context::PropertyValue;
 use outlook_pst::messaging::attachment::AttachmentProperties;
 use outlook_pst::messaging::folder::Folder;
@@ -28,77 +26,57 @@ use outlook_pst::messaging::message::{Message, MessageProperties};
 use outlook_pst::ndb::node_id::NodeId;
 use std::rc::Rc;
 
-mod encoding;
+pub(super) mod encoding;
 
 /// Convert a PST Message into a base64-encoded EML string.
 pub fn build_eml_base64(message: Rc<dyn Message>) -> Option<String> {
-    let properties = message.properties();
-
-    let mut builder = MessageBuilder::new();
-    if let Some(sub) = extract_subject(properties) {
-        builder = builder.subject(sub);
-    }
-    if let Some(mid) = extract_string_property(properties, 0x1035) {
-        builder = builder.message_id(mid);
-    }
-    if let Some(irt) = extract_string_property(properties, 0x1042) {
-        builder = builder.in_reply_to(irt);
+    match read_message(&message).to_eml() {
+        Ok(eml_vec) => Some(base64_encode_url_safe!(eml_vec)),
+        Err(e) => {
+            tracing::error!("Failed to generate EML from PST message: {:?}", e);
+            None
+        }
     }
+}
 
-    if let Some(refs) = extract_string_property(properties, 0x1039) {
-        builder = builder.header("References", Text::new(refs));
-    }
+/// Read the properties of a PST message needed to rebuild it.
+fn read_message(message: &Rc<dyn Message>) -> OutlookMessage {
+    let properties = message.properties();
+    let (to, cc, bcc) = extract_recipients_list(message);
 
-    if let Some(cid_val) = properties.get(0x3013) {
-        if let PropertyValue::Binary(bin) = cid_val {
-            builder = builder.header(
-                "X-Bichon-Conversation-ID",
-                Text::new(hex::encode(bin.buffer())),
-            );
-        }
-    }
+    let conversation_index = match properties.get(0x3013) {
+        Some(PropertyValue::Binary(bin)) => Some(bin.buffer().to_vec()),
+        _ => None,
+    };
 
     let from = extract_string_property(properties, 0x5D01)
         .or_else(|| extract_string_property(properties, 0x5D02))
         .or_else(|| extract_string_property(properties, 0x0C1F));
 
-    if let Some(f) = from {
-        builder = builder.from(f);
-    }
-
-    if let Some(filetime) = extract_i64_property(properties, &[0x0039, 0x0E06]) {
-        let dt = filetime_to_datetime(filetime).timestamp();
-        builder = builder.date(dt);
-    }
-
-    let (to, cc, bcc) = extract_recipients_list(&message);
-    if !to.is_empty() {
-        builder = builder.to(to.iter().map(|s| s.as_str()).collect::<Vec<_>>());
-    }
-    if !cc.is_empty() {
-        builder = builder.cc(cc.iter().map(|s| s.as_str()).collect::<Vec<_>>());
-    }
-    if !bcc.is_empty() {
-        builder = builder.bcc(bcc.iter().map(|s| s.as_str()).collect::<Vec<_>>());
-    }
-
-    if let Some(html) = extract_html(properties) {
-        builder = builder.html_body(html);
-    }
-
-    if let Some(text) = extract_text(properties) {
-        builder = builder.text_body(text);
+    OutlookMessage {
+        subject: extract_subject(properties),
+        message_id: extract_string_property(properties, 0x1035),
+        in_reply_to: extract_string_property(properties, 0x1042),
+        references: extract_string_property(properties, 0x1039),
+        conversation_index,
+        from,
+        filetime: extract_i64_property(properties, &[0x0039, 0x0E06]),
+        to,
+        cc,
+        bcc,
+        html: extract_html(properties),
+        text: extract_text(properties),
+        attachments: read_attachments(message),
     }
+}
 
+fn read_attachments(message: &Rc<dyn Message>) -> Vec<OutlookAttachment> {
+    let mut attachments = Vec::new();
     if let Some(attachment_table) = message.attachment_table() {
         for row in attachment_table.rows_matrix() {
             let node_id = NodeId::from(u32::from(row.id()));
             if let Ok(attachment) = message.clone().read_attachment(node_id, None) {
                 let att_props = attachment.properties();
-                let name = extract_attachment_string_property(att_props, 0x3707);
-                let mime = extract_attachment_string_property(att_props, 0x370E)
-                    .unwrap_or_else(|| "application/octet-stream".into());
-                let cid = extract_attachment_string_property(att_props, 0x3712);
                 let is_inline = att_props
                     .get(0x3714)
                     .and_then(|val| {
@@ -112,33 +90,18 @@ pub fn build_eml_base64(message: Rc<dyn Message>) -> Option<String> {
                     .unwrap_or(false);
 
                 if let Some(PropertyValue::Binary(bin)) = att_props.get(0x3701) {
-                    let data = bin.buffer().to_vec();
-                    let file_name = name.unwrap_or_else(|| "unnamed_attachment".to_string());
-
-                    if is_inline && cid.is_some() {
-                        let content_id = cid.unwrap();
-                        builder = builder.inline(mime, content_id, data);
-                    } else {
-                        builder = builder.attachment(mime, file_name, data);
-                    }
+                    attachments.push(OutlookAttachment {
+                        name: extract_attachment_string_property(att_props, 0x3707),
+                        mime: extract_attachment_string_property(att_props, 0x370E),
+                        content_id: extract_attachment_string_property(att_props, 0x3712),
+                        inline: is_inline,
+                        content: AttachmentContent::Data(bin.buffer().to_vec()),
+                    });
                 }
             }
         }
     }
-
-    match builder.write_to_vec() {
-        Ok(eml_vec) => Some(base64_encode_url_safe!(eml_vec)),
-        Err(e) => {
-            tracing::error!("Failed to generate EML from PST message: {:?}", e);
-            None
-        }
-    }
-}
-
-fn filetime_to_datetime(filetime: i64) -> DateTime<Utc> {
-    let unix_secs = (filetime / 10_000_000) - 11_644_473_600;
-    let nsecs = (filetime % 10_000_000) * 100;
-    Utc.timestamp_opt(unix_secs, nsecs as u32).unwrap()
+    attachments
 }
 
 fn extract_recipients_list(message: &Rc<dyn Message>) -> (Vec<String>, Vec<String>, Vec<String>) {
@@ -0,0 +1,880 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    account::{entity::JmapAuthMethod, migration::AccountModel},
+    decrypt,
+    error::{code::ErrorCode, BichonError, BichonResult},
+    oauth2::flow::build_http_client,
+    raise_error,
+};
+use reqwest::{header::LOCATION, RequestBuilder, Response, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+const CORE_CAPABILITY: &str = "urn:ietf:params:jmap:core";
+const MAIL_CAPABILITY: &str = "urn:ietf:params:jmap:mail";
+const WELL_KNOWN_PATH: &str = "/.well-known/jmap";
+/// Email properties needed to archive a message and keep it in sync.
+const EMAIL_PROPERTIES: [&str; 6] = [
+    "id",
+    "blobId",
+    "mailboxIds",
+    "keywords",
+    "receivedAt",
+    "size",
+];
+const QUERY_PAGE_SIZE: usize = 256;
+const MAX_CHANGES: usize = 256;
+/// Upper bound for ids per `Email/get`, whatever the server allows.
+const MAX_IDS_PER_GET: usize = 256;
+const MAX_REDIRECTS: usize = 5;
+
+/// A mailbox, with its path built from the names of its parents.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JmapMailbox {
+    pub id: String,
+    pub path: String,
+    pub role: Option<String>,
+    pub total_emails: u32,
+}
+
+/// The properties of an email the archive needs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JmapEmail {
+    pub id: String,
+    pub blob_id: String,
+    pub mailbox_ids: Vec<String>,
+    /// Keywords, mapped to IMAP flags.
+    pub flags: Vec<String>,
+    pub received_at: i64,
+    pub size: u64,
+}
+
+/// Result of an `Email/get`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EmailBatch {
+    pub emails: Vec<JmapEmail>,
+    /// Requested ids the server no longer knows.
+    pub not_found: Vec<String>,
+}
+
+/// All email changes since a state.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EmailChanges {
+    /// Emails created or updated, in the order the server reported them.
+    pub changed: Vec<String>,
+    pub destroyed: Vec<String>,
+    pub new_state: String,
+}
+
+pub enum JmapCredentials {
+    Bearer(String),
+    Basic { user: String, password: String },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    #[serde(default)]
+    capabilities: HashMap<String, Value>,
+    #[serde(default)]
+    primary_accounts: HashMap<String, String>,
+    api_url: String,
+    download_url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CoreCapability {
+    max_objects_in_get: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ApiResponse {
+    #[serde(rename = "methodResponses")]
+    method_responses: Vec<(String, Value, String)>,
+}
+
+#[derive(Deserialize)]
+struct MethodError {
+    #[serde(rename = "type")]
+    kind: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetResponse<T> {
+    state: String,
+    #[serde(default = "Vec::new")]
+    list: Vec<T>,
+    #[serde(default)]
+    not_found: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangesResponse {
+    new_state: String,
+    #[serde(default)]
+    has_more_changes: bool,
+    #[serde(default)]
+    created: Vec<String>,
+    #[serde(default)]
+    updated: Vec<String>,
+    #[serde(default)]
+    destroyed: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryResponse {
+    ids: Vec<String>,
+    total: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MailboxResource {
+    id: String,
+    name: String,
+    parent_id: Option<String>,
+    role: Option<String>,
+    #[serde(default)]
+    total_emails: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmailResource {
+    id: String,
+    blob_id: String,
+    #[serde(default)]
+    mailbox_ids: HashMap<String, bool>,
+    #[serde(default)]
+    keywords: HashMap<String, bool>,
+    received_at: Option<String>,
+    #[serde(default)]
+    size: u64,
+}
+
+/// RFC 7807 problem details returned for request-level errors.
+#[derive(Deserialize)]
+struct ProblemDetails {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    detail: String,
+}
+
+impl From<EmailResource> for JmapEmail {
+    fn from(email: EmailResource) -> Self {
+        let received_at = email
+            .received_at
+            .as_deref()
+            .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.timestamp_millis())
+            .unwrap_or(0);
+        let mut mailbox_ids: Vec<String> = email
+            .mailbox_ids
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(id, _)| id)
+            .collect();
+        mailbox_ids.sort();
+        let mut flags: Vec<String> = email
+            .keywords
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(keyword, _)| keyword_to_flag(&keyword))
+            .collect();
+        flags.sort();
+        JmapEmail {
+            id: email.id,
+            blob_id: email.blob_id,
+            mailbox_ids,
+            flags,
+            received_at,
+            size: email.size,
+        }
+    }
+}
+
+/// Maps a JMAP keyword to the IMAP flag it stands for (RFC 8621,
+/// section 4.1.1). Other keywords are IMAP keywords already.
+pub fn keyword_to_flag(keyword: &str) -> String {
+    match keyword.to_ascii_lowercase().as_str() {
+        "$seen" => "\\Seen".into(),
+        "$flagged" => "\\Flagged".into(),
+        "$answered" => "\\Answered".into(),
+        "$draft" => "\\Draft".into(),
+        _ => keyword.to_string(),
+    }
+}
+
+/// Read-only JMAP client bound to the mail account of a session.
+pub struct JmapClient {
+    http: reqwest::Client,
+    credentials: JmapCredentials,
+    api_url: String,
+    download_url: String,
+    account_id: String,
+    max_objects_in_get: usize,
+}
+
+impl JmapClient {
+    /// Fetches the session resource and picks the primary mail account.
+    ///
+    /// `url` is either the server origin, resolved through
+    /// `/.well-known/jmap`, or the session URL itself.
+    pub async fn connect(
+        http: reqwest::Client,
+        url: &str,
+        credentials: JmapCredentials,
+    ) -> BichonResult<Self> {
+        let mut session_url = Url::parse(url.trim()).map_err(|e| {
+            raise_error!(
+                format!("Invalid JMAP URL '{}': {}", url, e),
+                ErrorCode::InvalidParameter
+            )
+        })?;
+        if session_url.path() == "/" {
+            session_url.set_path(WELL_KNOWN_PATH);
+        }
+
+        let mut redirects = 0;
+        let response = loop {
+            let response = authorize(http.get(session_url.clone()), &credentials)
+                .send()
+                .await
+                .map_err(classify_reqwest_error)?;
+            if !response.status().is_redirection() {
+                break response;
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| session_url.join(v).ok());
+            match location {
+                Some(location) if redirects < MAX_REDIRECTS => {
+                    redirects += 1;
+                    session_url = location;
+                }
+                _ => {
+                    return Err(raise_error!(
+                        format!("JMAP session discovery at '{}' did not resolve", url),
+                        ErrorCode::JmapRequestFailed
+                    ))
+                }
+            }
+        };
+        let session: Session = parse_json(response).await?;
+
+        let account_id = session
+            .primary_accounts
+            .get(MAIL_CAPABILITY)
+            .cloned()
+            .ok_or_else(|| {
+                raise_error!(
+                    "JMAP session has no primary mail account".into(),
+                    ErrorCode::JmapRequestFailed
+                )
+            })?;
+        let max_objects_in_get = session
+            .capabilities
+            .get(CORE_CAPABILITY)
+            .and_then(|c| serde_json::from_value::<CoreCapability>(c.clone()).ok())
+            .and_then(|c| c.max_objects_in_get)
+            .unwrap_or(MAX_IDS_PER_GET)
+            .clamp(1, MAX_IDS_PER_GET);
+        // The API and download URLs may be relative to the session URL. They
+        // are not reparsed, which would escape the download URL's template.
+        let origin = session_url.origin().ascii_serialization();
+        let resolve = |u: &str| {
+            if u.starts_with('/') {
+                format!("{}{}", origin, u)
+            } else {
+                u.to_string()
+            }
+        };
+        Ok(Self {
+            api_url: resolve(&session.api_url),
+            download_url: resolve(&session.download_url),
+            http,
+            credentials,
+            account_id,
+            max_objects_in_get,
+        })
+    }
+
+    pub async fn for_account(account: &AccountModel) -> BichonResult<Self> {
+        let config = account.jmap.as_ref().ok_or_else(|| {
+            raise_error!(
+                "JMAP account has no JMAP configuration.".into(),
+                ErrorCode::MissingConfiguration
+            )
+        })?;
+        let password = config.password.as_ref().ok_or_else(|| {
+            raise_error!(
+                "JMAP account has no password or API token.".into(),
+                ErrorCode::MissingConfiguration
+            )
+        })?;
+        let password = decrypt!(password)?;
+        let credentials = match config.auth_method {
+            JmapAuthMethod::Bearer => JmapCredentials::Bearer(password),
+            JmapAuthMethod::Basic => JmapCredentials::Basic {
+                user: account
+                    .login_name
+                    .clone()
+                    .unwrap_or_else(|| account.email.clone()),
+                password,
+            },
+        };
+        let http = build_http_client(config.use_proxy)?;
+        Self::connect(http, &config.url, credentials).await
+    }
+
+    /// JMAP id of the mail account the client reads.
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    /// How many ids one `Email/get` may ask for.
+    pub fn max_objects_in_get(&self) -> usize {
+        self.max_objects_in_get
+    }
+
+    /// Lists all mailboxes, parents before children, with the current
+    /// `Mailbox` state.
+    pub async fn mailboxes(&self) -> BichonResult<(Vec<JmapMailbox>, String)> {
+        let response: GetResponse<MailboxResource> = self
+            .call(
+                "Mailbox/get",
+                json!({
+                    "accountId": self.account_id,
+                    "ids": null,
+                    "properties": ["id", "name", "parentId", "role", "totalEmails"],
+                }),
+            )
+            .await?
+            .map_err(method_failed("Mailbox/get"))?;
+
+        let by_id: HashMap<&str, &MailboxResource> =
+            response.list.iter().map(|m| (m.id.as_str(), m)).collect();
+        let mut mailboxes: Vec<JmapMailbox> = response
+            .list
+            .iter()
+            .map(|mailbox| {
+                let mut names = vec![mailbox.name.as_str()];
+                let mut visited = HashSet::from([mailbox.id.as_str()]);
+                let mut parent = mailbox.parent_id.as_deref();
+                while let Some(parent_mailbox) = parent.and_then(|id| by_id.get(id)) {
+                    if !visited.insert(parent_mailbox.id.as_str()) {
+                        break;
+                    }
+                    names.push(parent_mailbox.name.as_str());
+                    parent = parent_mailbox.parent_id.as_deref();
+                }
+                names.reverse();
+                JmapMailbox {
+                    id: mailbox.id.clone(),
+                    path: names.join("/"),
+                    role: mailbox.role.clone(),
+                    total_emails: mailbox.total_emails,
+                }
+            })
+            .collect();
+        mailboxes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok((mailboxes, response.state))
+    }
+
+    /// Whether any mailbox was created, changed or destroyed since `since`.
+    /// A state the server cannot compare against counts as changed.
+    pub async fn mailboxes_changed(&self, since: &str) -> BichonResult<bool> {
+        let response: Result<ChangesResponse, MethodError> = self
+            .call(
+                "Mailbox/changes",
+                json!({ "accountId": self.account_id, "sinceState": since }),
+            )
+            .await?;
+        match response {
+            Ok(changes) => Ok(changes.has_more_changes
+                || !changes.created.is_empty()
+                || !changes.updated.is_empty()
+                || !changes.destroyed.is_empty()),
+            Err(e) if e.kind == "cannotCalculateChanges" => Ok(true),
+            Err(e) => Err(method_failed("Mailbox/changes")(e)),
+        }
+    }
+
+    /// Current `Email` state. Taken before a full listing, later changes
+    /// are then picked up by the next `Email/changes`.
+    pub async fn email_state(&self) -> BichonResult<String> {
+        let response: GetResponse<EmailResource> = self
+            .call(
+                "Email/get",
+                json!({ "accountId": self.account_id, "ids": [], "properties": ["id"] }),
+            )
+            .await?
+            .map_err(method_failed("Email/get"))?;
+        Ok(response.state)
+    }
+
+    /// Ids of all emails of the account, oldest first, following all pages.
+    pub async fn query_emails(&self) -> BichonResult<Vec<String>> {
+        let mut ids = Vec::new();
+        loop {
+            let response: QueryResponse = self
+                .call(
+                    "Email/query",
+                    json!({
+                        "accountId": self.account_id,
+                        "sort": [{ "property": "receivedAt", "isAscending": true }],
+                        "position": ids.len(),
+                        "limit": QUERY_PAGE_SIZE,
+                        "calculateTotal": true,
+                    }),
+                )
+                .await?
+                .map_err(method_failed("Email/query"))?;
+            if response.ids.is_empty() {
+                break;
+            }
+            ids.extend(response.ids);
+            if response.total.is_some_and(|total| ids.len() >= total) {
+                break;
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Collects the email changes since `since`, following
+    /// `hasMoreChanges`.
+    ///
+    /// Returns `None` when the server can no longer calculate changes from
+    /// that state; the caller has to list all emails instead.
+    pub async fn email_changes(&self, since: &str) -> BichonResult<Option<EmailChanges>> {
+        let mut changes = EmailChanges {
+            new_state: since.to_string(),
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        loop {
+            let response: Result<ChangesResponse, MethodError> = self
+                .call(
+                    "Email/changes",
+                    json!({
+                        "accountId": self.account_id,
+                        "sinceState": changes.new_state,
+                        "maxChanges": MAX_CHANGES,
+                    }),
+                )
+                .await?;
+            let response = match response {
+                Ok(response) => response,
+                Err(e) if e.kind == "cannotCalculateChanges" => return Ok(None),
+                Err(e) => return Err(method_failed("Email/changes")(e)),
+            };
+            for id in response.created.into_iter().chain(response.updated) {
+                if seen.insert(id.clone()) {
+                    changes.changed.push(id);
+                }
+            }
+            changes.destroyed.extend(response.destroyed);
+            let more = response.has_more_changes && response.new_state != changes.new_state;
+            changes.new_state = response.new_state;
+            if !more {
+                break;
+            }
+        }
+        // An email created and destroyed again is only reported destroyed.
+        let destroyed: HashSet<&String> = changes.destroyed.iter().collect();
+        changes.changed.retain(|id| !destroyed.contains(id));
+        Ok(Some(changes))
+    }
+
+    /// Fetches the given emails; at most [`Self::max_objects_in_get`] ids.
+    pub async fn get_emails(&self, ids: &[String]) -> BichonResult<EmailBatch> {
+        let response: GetResponse<EmailResource> = self
+            .call(
+                "Email/get",
+                json!({
+                    "accountId": self.account_id,
+                    "ids": ids,
+                    "properties": EMAIL_PROPERTIES,
+                }),
+            )
+            .await?
+            .map_err(method_failed("Email/get"))?;
+        Ok(EmailBatch {
+            emails: response.list.into_iter().map(JmapEmail::from).collect(),
+            not_found: response.not_found,
+        })
+    }
+
+    /// Downloads a blob; for an email blob that is the raw RFC 5322 message.
+    pub async fn download(&self, blob_id: &str) -> BichonResult<Vec<u8>> {
+        let url = self
+            .download_url
+            .replace("{accountId}", &encode_component(&self.account_id))
+            .replace("{blobId}", &encode_component(blob_id))
+            .replace("{type}", &encode_component("message/rfc822"))
+            .replace("{name}", "message.eml");
+        let response = authorize(self.http.get(url), &self.credentials)
+            .send()
+            .await
+            .map_err(classify_reqwest_error)?;
+        let response = check(response).await?;
+        let body = response.bytes().await.map_err(classify_reqwest_error)?;
+        Ok(body.to_vec())
+    }
+
+    /// Sends a request with a single method call. Method-level errors are
+    /// handed back to the caller, which may expect some of them.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        arguments: Value,
+    ) -> BichonResult<Result<T, MethodError>> {
+        let request = json!({
+            "using": [CORE_CAPABILITY, MAIL_CAPABILITY],
+            "methodCalls": [[method, arguments, "0"]],
+        });
+        let response = authorize(self.http.post(&self.api_url), &self.credentials)
+            .json(&request)
+            .send()
+            .await
+            .map_err(classify_reqwest_error)?;
+        let response: ApiResponse = parse_json(response).await?;
+        let (name, arguments, _) =
+            response
+                .method_responses
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    raise_error!(
+                        format!("JMAP server sent no response to {}", method),
+                        ErrorCode::JmapRequestFailed
+                    )
+                })?;
+        if name == "error" {
+            let error: MethodError = serde_json::from_value(arguments).map_err(unexpected)?;
+            return Ok(Err(error));
+        }
+        serde_json::from_value(arguments)
+            .map(Ok)
+            .map_err(unexpected)
+    }
+}
+
+fn authorize(request: RequestBuilder, credentials: &JmapCredentials) -> RequestBuilder {
+    match credentials {
+        JmapCredentials::Bearer(token) => request.bearer_auth(token),
+        JmapCredentials::Basic { user, password } => request.basic_auth(user, Some(password)),
+    }
+}
+
+async fn parse_json<T: DeserializeOwned>(response: Response) -> BichonResult<T> {
+    let response = check(response).await?;
+    let body = response.bytes().await.map_err(classify_reqwest_error)?;
+    serde_json::from_slice(&body).map_err(unexpected)
+}
+
+/// Turns a non-success response into an error carrying the problem type.
+async fn check(response: Response) -> BichonResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.bytes().await.unwrap_or_default();
+    let detail = serde_json::from_slice::<ProblemDetails>(&body)
+        .map(|p| format!("{}: {}", p.kind, p.detail))
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned());
+    Err(raise_error!(
+        format!("JMAP request failed with {}: {}", status, detail),
+        ErrorCode::JmapRequestFailed
+    ))
+}
+
+fn method_failed(method: &'static str) -> impl Fn(MethodError) -> BichonError {
+    move |e| {
+        raise_error!(
+            format!(
+                "JMAP {} failed: {}{}",
+                method,
+                e.kind,
+                e.description
+                    .map(|d| format!(" ({})", d))
+                    .unwrap_or_default()
+            ),
+            ErrorCode::JmapRequestFailed
+        )
+    }
+}
+
+fn unexpected(e: serde_json::Error) -> BichonError {
+    raise_error!(
+        format!("Unexpected JMAP response: {}", e),
+        ErrorCode::JmapRequestFailed
+    )
+}
+
+/// Percent-encodes a value for a URI template variable (RFC 6570 simple
+/// expansion).
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn classify_reqwest_error(e: reqwest::Error) -> BichonError {
+    if e.is_timeout() {
+        raise_error!(
+            format!("JMAP request timed out: {}", e),
+            ErrorCode::ConnectionTimeout
+        )
+    } else {
+        raise_error!(
+            format!("JMAP request failed: {}", e),
+            ErrorCode::NetworkError
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jmap::mock_server::{MockJmapServer, MockJmapServerHandle, ACCOUNT_ID};
+
+    const AUTHORIZATION: &str = "Bearer fmu1-test-token";
+
+    fn token() -> JmapCredentials {
+        JmapCredentials::Bearer("fmu1-test-token".into())
+    }
+
+    fn eml(subject: &str) -> Vec<u8> {
+        format!(
+            "From: alice@example.com\r\nTo: archive@example.com\r\nSubject: {}\r\n\r\nHello\r\n",
+            subject
+        )
+        .into_bytes()
+    }
+
+    async fn connect(server: &MockJmapServerHandle) -> JmapClient {
+        JmapClient::connect(reqwest::Client::new(), &server.url(), token())
+            .await
+            .unwrap()
+    }
+
+    fn server() -> MockJmapServer {
+        MockJmapServer::new(AUTHORIZATION)
+            .mailbox("mb-inbox", "Inbox", None)
+            .mailbox("mb-receipts", "Receipts", Some("mb-inbox"))
+            .mailbox("mb-archive", "Archive", None)
+    }
+
+    #[tokio::test]
+    async fn session_is_discovered_through_well_known() {
+        let server = server().max_objects_in_get(50).start().await;
+        let client = connect(&server).await;
+        assert_eq!(client.account_id(), ACCOUNT_ID);
+        assert_eq!(client.max_objects_in_get(), 50);
+        assert_eq!(client.api_url, format!("{}/jmap/api", server.url()));
+    }
+
+    #[tokio::test]
+    async fn basic_credentials_are_sent_as_http_basic() {
+        // "archive@example.com:app-password"
+        let server = MockJmapServer::new("Basic YXJjaGl2ZUBleGFtcGxlLmNvbTphcHAtcGFzc3dvcmQ=")
+            .start()
+            .await;
+        let credentials = JmapCredentials::Basic {
+            user: "archive@example.com".into(),
+            password: "app-password".into(),
+        };
+        let client = JmapClient::connect(reqwest::Client::new(), &server.url(), credentials)
+            .await
+            .unwrap();
+        assert_eq!(client.account_id(), ACCOUNT_ID);
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_fail_discovery() {
+        let server = server().start().await;
+        let error = match JmapClient::connect(
+            reqwest::Client::new(),
+            &server.url(),
+            JmapCredentials::Bearer("wrong".into()),
+        )
+        .await
+        {
+            Ok(_) => panic!("discovery must fail"),
+            Err(e) => e,
+        };
+        assert_eq!(error.code(), ErrorCode::JmapRequestFailed);
+        assert!(error.to_string().contains("401"));
+    }
+
+    #[tokio::test]
+    async fn mailbox_paths_follow_parents() {
+        let server = server().start().await;
+        let (mailboxes, state) = connect(&server).await.mailboxes().await.unwrap();
+        let paths: Vec<&str> = mailboxes.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, vec!["Archive", "Inbox", "Inbox/Receipts"]);
+        assert_eq!(state, "0");
+    }
+
+    #[tokio::test]
+    async fn mailbox_changes_are_detected() {
+        let server = server().start().await;
+        let client = connect(&server).await;
+        assert!(!client.mailboxes_changed("0").await.unwrap());
+        server.rename_mailbox("mb-archive", "Old Mail");
+        assert!(client.mailboxes_changed("0").await.unwrap());
+        assert!(client.mailboxes_changed("42").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn emails_are_listed_across_pages_with_keywords_as_flags() {
+        let mut builder = server().page_limit(2);
+        for i in 0..5 {
+            builder = builder.email(&format!("m{}", i), &["mb-inbox"], &[], &eml("x"));
+        }
+        let server = builder
+            .email(
+                "m5",
+                &["mb-inbox", "mb-archive"],
+                &["$seen", "$flagged", "$Forwarded", "project-x"],
+                &eml("tagged"),
+            )
+            .start()
+            .await;
+        let client = connect(&server).await;
+
+        let ids = client.query_emails().await.unwrap();
+        assert_eq!(ids, vec!["m0", "m1", "m2", "m3", "m4", "m5"]);
+        let queries = server
+            .methods()
+            .iter()
+            .filter(|m| *m == "Email/query")
+            .count();
+        assert_eq!(queries, 3);
+
+        let batch = client
+            .get_emails(&["m5".to_string(), "gone".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(batch.not_found, vec!["gone"]);
+        let email = &batch.emails[0];
+        assert_eq!(email.blob_id, "Bm5");
+        assert_eq!(email.mailbox_ids, vec!["mb-archive", "mb-inbox"]);
+        assert_eq!(
+            email.flags,
+            vec!["$Forwarded", "\\Flagged", "\\Seen", "project-x"]
+        );
+        assert_eq!(email.received_at, 1709287560000);
+    }
+
+    #[tokio::test]
+    async fn changes_are_collected_across_calls() {
+        let server = server()
+            .max_changes(1)
+            .email("m1", &["mb-inbox"], &[], &eml("one"))
+            .email("m2", &["mb-inbox"], &[], &eml("two"))
+            .start()
+            .await;
+        let client = connect(&server).await;
+        let since = client.email_state().await.unwrap();
+
+        server.set_keywords("m1", &["$seen"]);
+        server.add_email("m3", &["mb-archive"], &[], &eml("three"));
+        server.destroy_email("m2");
+        server.add_email("m4", &["mb-inbox"], &[], &eml("four"));
+        server.destroy_email("m4");
+
+        let changes = client.email_changes(&since).await.unwrap().unwrap();
+        assert_eq!(changes.changed, vec!["m1", "m3"]);
+        assert_eq!(changes.destroyed, vec!["m2"]);
+        assert_eq!(changes.new_state, client.email_state().await.unwrap());
+        let calls = server
+            .methods()
+            .iter()
+            .filter(|m| *m == "Email/changes")
+            .count();
+        assert_eq!(calls, 4);
+    }
+
+    #[tokio::test]
+    async fn lost_history_asks_for_a_full_listing() {
+        let server = server()
+            .email("m1", &["mb-inbox"], &[], &eml("one"))
+            .start()
+            .await;
+        let client = connect(&server).await;
+        let since = client.email_state().await.unwrap();
+        server.add_email("m2", &["mb-inbox"], &[], &eml("two"));
+        server.forget_history();
+        assert!(client.email_changes(&since).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn blobs_are_downloaded_as_raw_messages() {
+        let body = eml("raw");
+        let server = server()
+            .email("m1", &["mb-inbox"], &[], &body)
+            .start()
+            .await;
+        let client = connect(&server).await;
+        assert_eq!(client.download("Bm1").await.unwrap(), body);
+
+        let error = client.download("missing").await.unwrap_err();
+        assert_eq!(error.code(), ErrorCode::JmapRequestFailed);
+    }
+
+    #[tokio::test]
+    async fn method_errors_carry_the_error_type() {
+        let server = server().max_objects_in_get(1).start().await;
+        let client = connect(&server).await;
+        let error = client
+            .get_emails(&["a".to_string(), "b".to_string()])
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::JmapRequestFailed);
+        assert!(error.to_string().contains("requestTooLarge"));
+    }
+
+    #[test]
+    fn system_keywords_map_to_imap_flags() {
+        assert_eq!(keyword_to_flag("$seen"), "\\Seen");
+        assert_eq!(keyword_to_flag("$Answered"), "\\Answered");
+        assert_eq!(keyword_to_flag("$draft"), "\\Draft");
+        assert_eq!(keyword_to_flag("$junk"), "$junk");
+        assert_eq!(encode_component("a/b c"), "a%2Fb%20c");
+    }
+}
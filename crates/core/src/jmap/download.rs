@@ -0,0 +1,610 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    account::{
+        migration::{AccountModel, AccountType},
+        state::{DownloadState, DownloadStatus, FolderStatus, TriggerType},
+    },
+    cache::imap::{
+        download::download_type::{decide_next_download_task, DownloadTask},
+        mailbox::MailBox,
+    },
+    envelope::extractor::extract_envelope_from_api,
+    error::BichonResult,
+    imap::executor::DEFAULT_MAX_EMAIL_SIZE,
+    jmap::{
+        client::{JmapClient, JmapEmail},
+        state::{JmapCopy, JmapEmailRef, JmapMailboxState, JmapSyncState},
+    },
+    store::tantivy::envelope::ENVELOPE_MANAGER,
+    utils::create_hash,
+};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// What to do with one email of a batch.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct EmailPlan {
+    pub email_id: String,
+    /// Selected mailboxes the email is in but not archived into yet.
+    pub fetch_into: Vec<u64>,
+    /// Archived copies still in their mailbox.
+    pub kept: Vec<JmapCopy>,
+}
+
+/// What a batch of fetched emails means for the archive.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct BatchPlan {
+    pub emails: Vec<EmailPlan>,
+    /// New flags of archived copies, by mailbox and UID.
+    pub flag_updates: HashMap<u64, HashMap<u32, Vec<String>>>,
+    /// Archived copies whose email left the mailbox, by mailbox.
+    pub removed: HashMap<u64, Vec<u32>>,
+}
+
+impl BatchPlan {
+    fn remove(&mut self, copies: &[JmapCopy]) {
+        for copy in copies {
+            self.removed
+                .entry(copy.mailbox_id)
+                .or_default()
+                .push(copy.uid);
+        }
+    }
+}
+
+/// Compares fetched emails with their archived copies (`archived` maps
+/// email ids to copies). Copies in mailboxes the email left are removed,
+/// the others get the email's current flags, and the email is fetched into
+/// every `selected` mailbox it has no copy in yet.
+pub fn plan_batch(
+    account_id: u64,
+    emails: &[JmapEmail],
+    archived: &HashMap<String, Vec<JmapCopy>>,
+    selected: &HashSet<u64>,
+) -> BatchPlan {
+    let mut plan = BatchPlan::default();
+    for email in emails {
+        let present: Vec<u64> = email
+            .mailbox_ids
+            .iter()
+            .map(|id| create_hash(account_id, id))
+            .collect();
+        let copies = archived.get(&email.id).map(Vec::as_slice).unwrap_or(&[]);
+        let (kept, gone): (Vec<JmapCopy>, Vec<JmapCopy>) = copies
+            .iter()
+            .partition(|copy| present.contains(&copy.mailbox_id));
+        plan.remove(&gone);
+        for copy in &kept {
+            plan.flag_updates
+                .entry(copy.mailbox_id)
+                .or_default()
+                .insert(copy.uid, email.flags.clone());
+        }
+        let fetch_into = present
+            .into_iter()
+            .filter(|id| selected.contains(id) && !kept.iter().any(|c| c.mailbox_id == *id))
+            .collect();
+        plan.emails.push(EmailPlan {
+            email_id: email.id.clone(),
+            fetch_into,
+            kept,
+        });
+    }
+    plan
+}
+
+/// Mailboxes to download: all of them, or those named in `download_folders`.
+pub fn select_mailboxes(
+    mailboxes: &[JmapMailboxState],
+    selected: Option<&[String]>,
+) -> Vec<JmapMailboxState> {
+    match selected {
+        Some(names) if !names.is_empty() => {
+            let names: HashSet<&str> = names.iter().map(String::as_str).collect();
+            mailboxes
+                .iter()
+                .filter(|m| names.contains(m.path.as_str()))
+                .cloned()
+                .collect()
+        }
+        _ => mailboxes.to_vec(),
+    }
+}
+
+pub async fn process_jmap_download(
+    account: &AccountModel,
+    token: CancellationToken,
+    trigger_type: TriggerType,
+) -> BichonResult<()> {
+    assert_eq!(account.account_type, AccountType::JMAP);
+    let start_time = Instant::now();
+    let account_id = account.id;
+    let download_task = decide_next_download_task(account, trigger_type).await?;
+    if matches!(download_task, DownloadTask::Idle) {
+        return Ok(());
+    }
+
+    match download_account(account, &token).await {
+        Ok(true) => DownloadState::update_session_status(
+            account_id,
+            DownloadStatus::Cancelled,
+            Some("User stopped or system shutdown".to_string()),
+        )?,
+        Ok(false) => {
+            DownloadState::update_session_status(account_id, DownloadStatus::Success, None)?
+        }
+        Err(e) => {
+            let err_msg = format!("JMAP download interrupted: {:#?}", e);
+            DownloadState::append_session_error(account_id, err_msg.clone())?;
+            DownloadState::update_session_status(
+                account_id,
+                DownloadStatus::Failed,
+                Some(err_msg),
+            )?;
+        }
+    }
+
+    debug!(
+        "Account{{{}}} JMAP download completed: {} seconds elapsed.",
+        account.email,
+        start_time.elapsed().as_secs()
+    );
+    Ok(())
+}
+
+/// Progress of one selected mailbox during a run.
+struct MailboxRun {
+    state: JmapMailboxState,
+    planned: u64,
+    processed: u64,
+    failed: u64,
+}
+
+impl MailboxRun {
+    fn report(&self, account_id: u64, status: FolderStatus) -> BichonResult<()> {
+        DownloadState::update_folder_progress(
+            account_id,
+            self.state.path.clone(),
+            self.planned,
+            self.processed,
+            status,
+            None,
+        )
+    }
+}
+
+/// Syncs the account from its last `Email` state, or lists all emails on
+/// the first run and when the server lost track of that state. Returns
+/// whether the run was cancelled.
+///
+/// The new state is only saved once every change is archived, so a failed
+/// or cancelled run replays the same changes next time; emails archived in
+/// between are then recognized by their references.
+async fn download_account(account: &AccountModel, token: &CancellationToken) -> BichonResult<bool> {
+    let account_id = account.id;
+    let client = JmapClient::for_account(account).await?;
+    let mut state = JmapSyncState::get(account_id)?
+        .filter(|s| s.session_account_id == client.account_id())
+        .unwrap_or_else(|| JmapSyncState::new(account_id, client.account_id()));
+
+    let mailboxes = sync_mailboxes(&client, account_id, &mut state).await?;
+    state.save()?;
+    let mut runs: HashMap<u64, MailboxRun> =
+        select_mailboxes(&mailboxes, account.download_folders.as_deref())
+            .into_iter()
+            .map(|state| {
+                (
+                    state.mailbox_id,
+                    MailboxRun {
+                        state,
+                        planned: 0,
+                        processed: 0,
+                        failed: 0,
+                    },
+                )
+            })
+            .collect();
+    for run in runs.values() {
+        run.report(account_id, FolderStatus::Pending)?;
+    }
+    let selected: HashSet<u64> = runs.keys().copied().collect();
+
+    let changes = match &state.email_state {
+        Some(since) => client.email_changes(since).await?,
+        None => None,
+    };
+    let (changed, destroyed, new_state, full) = match changes {
+        Some(changes) => (changes.changed, changes.destroyed, changes.new_state, false),
+        None => {
+            if state.email_state.is_some() {
+                info!(account_id, "JMAP email state expired, listing all emails");
+            }
+            let new_state = client.email_state().await?;
+            (client.query_emails().await?, Vec::new(), new_state, true)
+        }
+    };
+
+    let max_size = account
+        .max_email_size_bytes
+        .unwrap_or(DEFAULT_MAX_EMAIL_SIZE);
+    let mut cancelled = false;
+    let mut gone = destroyed;
+    'batches: for ids in changed.chunks(client.max_objects_in_get()) {
+        if token.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        let batch = client.get_emails(ids).await?;
+        gone.extend(batch.not_found);
+
+        let mut refs = HashMap::new();
+        for email in &batch.emails {
+            if let Some(reference) = JmapEmailRef::find(account_id, &email.id)? {
+                refs.insert(email.id.clone(), reference);
+            }
+        }
+        let archived = refs
+            .iter()
+            .map(|(id, r)| (id.clone(), r.copies.clone()))
+            .collect();
+        let plan = plan_batch(account_id, &batch.emails, &archived, &selected);
+        apply_removals_and_flags(account_id, plan.removed, plan.flag_updates).await?;
+
+        for email_plan in &plan.emails {
+            for mailbox_id in &email_plan.fetch_into {
+                if let Some(run) = runs.get_mut(mailbox_id) {
+                    run.planned += 1;
+                }
+            }
+        }
+        for (email, email_plan) in batch.emails.iter().zip(plan.emails) {
+            let mut reference = refs
+                .remove(&email.id)
+                .unwrap_or_else(|| JmapEmailRef::new(account_id, &email.id));
+            reference.copies = email_plan.kept;
+            if !email_plan.fetch_into.is_empty() {
+                if token.is_cancelled() {
+                    reference.save()?;
+                    cancelled = true;
+                    break 'batches;
+                }
+                archive_email(
+                    &client,
+                    account_id,
+                    email,
+                    &email_plan.fetch_into,
+                    max_size,
+                    &mut runs,
+                    &mut reference,
+                )
+                .await?;
+            }
+            reference.save()?;
+        }
+    }
+
+    if !cancelled {
+        if full {
+            // Emails archived before but missing from the listing are gone.
+            let listed: HashSet<&String> = changed.iter().collect();
+            gone.extend(
+                JmapEmailRef::list_all(account_id)?
+                    .into_iter()
+                    .map(|r| r.email_id)
+                    .filter(|id| !listed.contains(id)),
+            );
+        }
+        forget_emails(account_id, &gone).await?;
+    }
+
+    let failed = runs.values().any(|run| run.failed > 0);
+    if !cancelled && !failed {
+        state.email_state = Some(new_state);
+    }
+    state.save()?;
+    for run in runs.values() {
+        let status = if cancelled {
+            FolderStatus::Cancelled
+        } else if run.failed > 0 {
+            FolderStatus::Failed
+        } else {
+            FolderStatus::Success
+        };
+        run.report(account_id, status)?;
+    }
+    Ok(cancelled)
+}
+
+/// Downloads an email once and archives it into each of `mailbox_ids`,
+/// recording every copy on `reference`.
+async fn archive_email(
+    client: &JmapClient,
+    account_id: u64,
+    email: &JmapEmail,
+    mailbox_ids: &[u64],
+    max_size: u64,
+    runs: &mut HashMap<u64, MailboxRun>,
+    reference: &mut JmapEmailRef,
+) -> BichonResult<()> {
+    let body = if email.size > max_size {
+        warn!(
+            account_id,
+            email = %email.id,
+            size = email.size,
+            "Skipping oversized JMAP email"
+        );
+        None
+    } else {
+        Some(client.download(&email.blob_id).await?)
+    };
+
+    for mailbox_id in mailbox_ids {
+        let Some(run) = runs.get_mut(mailbox_id) else {
+            continue;
+        };
+        if let Some(body) = &body {
+            match extract_envelope_from_api(
+                body,
+                run.state.next_uid,
+                email.received_at,
+                email.flags.clone(),
+                account_id,
+                *mailbox_id,
+            )
+            .await
+            {
+                Ok(()) => {
+                    reference.copies.push(JmapCopy {
+                        mailbox_id: *mailbox_id,
+                        uid: run.state.next_uid,
+                    });
+                    run.state.next_uid += 1;
+                    run.state.save()?;
+                }
+                Err(e) => {
+                    run.failed += 1;
+                    let err_msg = format!(
+                        "Failed to archive JMAP email {} in '{}': {:#?}",
+                        email.id, run.state.path, e
+                    );
+                    warn!(account_id, "{}", err_msg);
+                    DownloadState::append_session_error(account_id, err_msg)?;
+                }
+            }
+        }
+        run.processed += 1;
+        run.report(account_id, FolderStatus::Downloading)?;
+    }
+    Ok(())
+}
+
+/// Marks every archived copy of the given emails deleted on the source and
+/// drops their references.
+async fn forget_emails(account_id: u64, email_ids: &[String]) -> BichonResult<()> {
+    let mut plan = BatchPlan::default();
+    let mut references = Vec::new();
+    for id in email_ids {
+        if let Some(reference) = JmapEmailRef::find(account_id, id)? {
+            plan.remove(&reference.copies);
+            references.push(reference);
+        }
+    }
+    apply_removals_and_flags(account_id, plan.removed, plan.flag_updates).await?;
+    for mut reference in references {
+        reference.copies.clear();
+        reference.save()?;
+    }
+    Ok(())
+}
+
+async fn apply_removals_and_flags(
+    account_id: u64,
+    removed: HashMap<u64, Vec<u32>>,
+    flag_updates: HashMap<u64, HashMap<u32, Vec<String>>>,
+) -> BichonResult<()> {
+    for (mailbox_id, flags) in flag_updates {
+        ENVELOPE_MANAGER
+            .update_envelope_flags(account_id, mailbox_id, flags)
+            .await?;
+    }
+    for (mailbox_id, uids) in removed {
+        ENVELOPE_MANAGER
+            .mark_deleted_on_source(account_id, mailbox_id, &uids)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Returns all mailboxes of the account. The list is reused as long as
+/// `Mailbox/changes` reports nothing new; otherwise it is fetched again
+/// and the mailbox rows, keyed by JMAP id so a renamed mailbox keeps its
+/// row, are updated.
+async fn sync_mailboxes(
+    client: &JmapClient,
+    account_id: u64,
+    state: &mut JmapSyncState,
+) -> BichonResult<Vec<JmapMailboxState>> {
+    let mut stored = JmapMailboxState::list_all(account_id)?;
+    if let Some(since) = &state.mailbox_state {
+        if !stored.is_empty() && !client.mailboxes_changed(since).await? {
+            stored.sort_by(|a, b| a.path.cmp(&b.path));
+            return Ok(stored);
+        }
+    }
+
+    let (mailboxes, mailbox_state) = client.mailboxes().await?;
+    let mut stored: HashMap<u64, JmapMailboxState> =
+        stored.into_iter().map(|m| (m.mailbox_id, m)).collect();
+    let mut states = Vec::with_capacity(mailboxes.len());
+    let mut rows = Vec::with_capacity(mailboxes.len());
+    for mailbox in &mailboxes {
+        let mailbox_id = create_hash(account_id, &mailbox.id);
+        let mut mailbox_state = stored
+            .remove(&mailbox_id)
+            .unwrap_or_else(|| JmapMailboxState {
+                mailbox_id,
+                account_id,
+                jmap_id: mailbox.id.clone(),
+                path: String::new(),
+                next_uid: 1,
+            });
+        mailbox_state.path = mailbox.path.clone();
+        mailbox_state.save()?;
+        states.push(mailbox_state);
+
+        let mut row = MailBox::find_mailbox(account_id, mailbox_id)?.unwrap_or_else(|| MailBox {
+            id: mailbox_id,
+            account_id,
+            name: mailbox.path.clone(),
+            delimiter: Some("/".to_string()),
+            attributes: vec![],
+            exists: 0,
+            unseen: None,
+            uid_next: None,
+            uid_validity: None,
+            highest_uid: None,
+            highest_modseq: None,
+        });
+        row.name = mailbox.path.clone();
+        row.exists = mailbox.total_emails;
+        rows.push(row);
+    }
+    MailBox::batch_upsert(&rows)?;
+    state.mailbox_state = Some(mailbox_state);
+    Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: u64 = 7;
+
+    fn email(id: &str, mailboxes: &[&str], flags: &[&str]) -> JmapEmail {
+        JmapEmail {
+            id: id.into(),
+            blob_id: format!("B{}", id),
+            mailbox_ids: mailboxes.iter().map(|m| m.to_string()).collect(),
+            flags: flags.iter().map(|f| f.to_string()).collect(),
+            received_at: 1,
+            size: 10,
+        }
+    }
+
+    fn mailbox(jmap_id: &str) -> u64 {
+        create_hash(ACCOUNT, jmap_id)
+    }
+
+    fn copy(jmap_id: &str, uid: u32) -> JmapCopy {
+        JmapCopy {
+            mailbox_id: mailbox(jmap_id),
+            uid,
+        }
+    }
+
+    fn selected(jmap_ids: &[&str]) -> HashSet<u64> {
+        jmap_ids.iter().map(|id| mailbox(id)).collect()
+    }
+
+    #[test]
+    fn new_emails_are_fetched_into_each_selected_mailbox() {
+        let plan = plan_batch(
+            ACCOUNT,
+            &[email("m1", &["inbox", "work", "trash"], &[])],
+            &HashMap::new(),
+            &selected(&["inbox", "work"]),
+        );
+        assert_eq!(
+            plan.emails[0].fetch_into,
+            vec![mailbox("inbox"), mailbox("work")]
+        );
+        assert!(plan.flag_updates.is_empty());
+        assert!(plan.removed.is_empty());
+    }
+
+    #[test]
+    fn archived_copies_get_new_flags() {
+        let archived = HashMap::from([("m1".to_string(), vec![copy("inbox", 4)])]);
+        let plan = plan_batch(
+            ACCOUNT,
+            &[email("m1", &["inbox"], &["\\Seen"])],
+            &archived,
+            &selected(&["inbox"]),
+        );
+        assert!(plan.emails[0].fetch_into.is_empty());
+        assert_eq!(plan.emails[0].kept, vec![copy("inbox", 4)]);
+        assert_eq!(
+            plan.flag_updates,
+            HashMap::from([(
+                mailbox("inbox"),
+                HashMap::from([(4, vec!["\\Seen".to_string()])])
+            )])
+        );
+    }
+
+    #[test]
+    fn moved_emails_leave_a_removed_copy_and_are_fetched_again() {
+        let archived = HashMap::from([("m1".to_string(), vec![copy("inbox", 4)])]);
+        let plan = plan_batch(
+            ACCOUNT,
+            &[email("m1", &["archive"], &[])],
+            &archived,
+            &selected(&["inbox", "archive"]),
+        );
+        assert_eq!(plan.removed, HashMap::from([(mailbox("inbox"), vec![4])]));
+        assert!(plan.emails[0].kept.is_empty());
+        assert_eq!(plan.emails[0].fetch_into, vec![mailbox("archive")]);
+    }
+
+    #[test]
+    fn copies_in_deselected_mailboxes_are_kept_in_sync() {
+        let archived = HashMap::from([("m1".to_string(), vec![copy("old", 2)])]);
+        let plan = plan_batch(
+            ACCOUNT,
+            &[email("m1", &["old"], &["\\Flagged"])],
+            &archived,
+            &selected(&["inbox"]),
+        );
+        assert!(plan.emails[0].fetch_into.is_empty());
+        assert_eq!(plan.emails[0].kept, vec![copy("old", 2)]);
+        assert!(plan.removed.is_empty());
+    }
+
+    #[test]
+    fn mailboxes_are_filtered_by_path() {
+        let mailboxes: Vec<JmapMailboxState> = ["Inbox", "Inbox/Receipts", "Trash"]
+            .iter()
+            .map(|path| JmapMailboxState {
+                path: path.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let chosen = vec!["Inbox/Receipts".to_string()];
+        let paths: Vec<String> = select_mailboxes(&mailboxes, Some(&chosen))
+            .into_iter()
+            .map(|m| m.path)
+            .collect();
+        assert_eq!(paths, vec!["Inbox/Receipts"]);
+        assert_eq!(select_mailboxes(&mailboxes, Some(&[])).len(), 3);
+        assert_eq!(select_mailboxes(&mailboxes, None).len(), 3);
+    }
+}
@@ -0,0 +1,518 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal in-process JMAP server backed by an in-memory mail store, for
+//! integration testing.
+//!
+//! The server implements session discovery (`/.well-known/jmap` redirects to
+//! the session resource), blob downloads and the methods the archive uses:
+//! `Mailbox/get`, `Mailbox/changes`, `Email/get`, `Email/query` and
+//! `Email/changes`. Every change made through the handle bumps the `Email`
+//! state and is logged, so `Email/changes` reports it like a real server.
+//! `forget_history` drops the log to provoke `cannotCalculateChanges`.
+//!
+//! # Example
+//! ```ignore
+//! let server = MockJmapServer::new("Bearer secret")
+//!     .mailbox("inbox", "Inbox", None)
+//!     .email("m1", &["inbox"], &["$seen"], b"Subject: hi\r\n\r\nbody")
+//!     .start()
+//!     .await;
+//!
+//! let client = JmapClient::connect(reqwest::Client::new(), &server.url(), credentials).await?;
+//! ```
+
+use crate::common::mock_http::{HttpRequest, HttpResponse, MockHttpServer};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// JMAP account id of the single account the server hosts.
+pub const ACCOUNT_ID: &str = "u1a2b3c4";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Change {
+    Created,
+    Updated,
+    Destroyed,
+}
+
+#[derive(Clone, Debug)]
+struct MockMailbox {
+    id: String,
+    name: String,
+    parent_id: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct MockEmail {
+    mailbox_ids: Vec<String>,
+    keywords: Vec<String>,
+    /// Position in arrival order, doubling as receive time.
+    received: u64,
+    body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Store {
+    authorization: String,
+    max_objects_in_get: usize,
+    page_limit: usize,
+    max_changes: usize,
+    mailboxes: Vec<MockMailbox>,
+    mailbox_state: u64,
+    emails: BTreeMap<String, MockEmail>,
+    email_state: u64,
+    /// `(state, email id, change)`, one entry per state.
+    log: Vec<(u64, String, Change)>,
+    /// Oldest state `Email/changes` can start from.
+    history_floor: u64,
+    received: u64,
+    methods: Vec<String>,
+}
+
+impl Store {
+    fn record(&mut self, id: &str, change: Change) {
+        self.email_state += 1;
+        self.log.push((self.email_state, id.to_string(), change));
+    }
+
+    fn insert(&mut self, id: &str, mailbox_ids: &[&str], keywords: &[&str], body: &[u8]) {
+        self.received += 1;
+        self.emails.insert(
+            id.to_string(),
+            MockEmail {
+                mailbox_ids: mailbox_ids.iter().map(|m| m.to_string()).collect(),
+                keywords: keywords.iter().map(|k| k.to_string()).collect(),
+                received: self.received,
+                body: body.to_vec(),
+            },
+        );
+    }
+}
+
+pub struct MockJmapServer {
+    store: Store,
+}
+
+impl MockJmapServer {
+    /// `authorization` is the `Authorization` header value the server accepts.
+    pub fn new(authorization: &str) -> Self {
+        Self {
+            store: Store {
+                authorization: authorization.to_string(),
+                max_objects_in_get: 500,
+                page_limit: 256,
+                max_changes: usize::MAX,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn mailbox(mut self, id: &str, name: &str, parent_id: Option<&str>) -> Self {
+        self.store.mailboxes.push(MockMailbox {
+            id: id.to_string(),
+            name: name.to_string(),
+            parent_id: parent_id.map(str::to_string),
+        });
+        self
+    }
+
+    pub fn email(mut self, id: &str, mailbox_ids: &[&str], keywords: &[&str], body: &[u8]) -> Self {
+        self.store.insert(id, mailbox_ids, keywords, body);
+        self
+    }
+
+    /// Caps the number of ids `Email/query` returns per call.
+    pub fn page_limit(mut self, limit: usize) -> Self {
+        self.store.page_limit = limit;
+        self
+    }
+
+    /// Caps the number of emails `Email/changes` reports per call.
+    pub fn max_changes(mut self, max: usize) -> Self {
+        self.store.max_changes = max;
+        self
+    }
+
+    pub fn max_objects_in_get(mut self, max: usize) -> Self {
+        self.store.max_objects_in_get = max;
+        self
+    }
+
+    pub async fn start(self) -> MockJmapServerHandle {
+        let store = Arc::new(Mutex::new(self.store));
+        let shared = store.clone();
+        let server = MockHttpServer::start(move |request| respond(&request, &shared)).await;
+        MockJmapServerHandle { server, store }
+    }
+}
+
+pub struct MockJmapServerHandle {
+    server: MockHttpServer,
+    store: Arc<Mutex<Store>>,
+}
+
+impl MockJmapServerHandle {
+    /// Server origin, to be resolved through `/.well-known/jmap`.
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+
+    /// Names of all JMAP methods called so far, in order.
+    pub fn methods(&self) -> Vec<String> {
+        self.store.lock().unwrap().methods.clone()
+    }
+
+    pub fn add_email(&self, id: &str, mailbox_ids: &[&str], keywords: &[&str], body: &[u8]) {
+        let mut store = self.store.lock().unwrap();
+        store.insert(id, mailbox_ids, keywords, body);
+        store.record(id, Change::Created);
+    }
+
+    pub fn set_keywords(&self, id: &str, keywords: &[&str]) {
+        let mut store = self.store.lock().unwrap();
+        store.emails.get_mut(id).expect("email").keywords =
+            keywords.iter().map(|k| k.to_string()).collect();
+        store.record(id, Change::Updated);
+    }
+
+    pub fn set_mailboxes(&self, id: &str, mailbox_ids: &[&str]) {
+        let mut store = self.store.lock().unwrap();
+        store.emails.get_mut(id).expect("email").mailbox_ids =
+            mailbox_ids.iter().map(|m| m.to_string()).collect();
+        store.record(id, Change::Updated);
+    }
+
+    pub fn destroy_email(&self, id: &str) {
+        let mut store = self.store.lock().unwrap();
+        store.emails.remove(id);
+        store.record(id, Change::Destroyed);
+    }
+
+    pub fn rename_mailbox(&self, id: &str, name: &str) {
+        let mut store = self.store.lock().unwrap();
+        store
+            .mailboxes
+            .iter_mut()
+            .find(|m| m.id == id)
+            .expect("mailbox")
+            .name = name.to_string();
+        store.mailbox_state += 1;
+    }
+
+    /// Forgets all logged changes; older states can no longer be synced from.
+    pub fn forget_history(&self) {
+        let mut store = self.store.lock().unwrap();
+        store.log.clear();
+        store.history_floor = store.email_state;
+    }
+}
+
+fn respond(request: &HttpRequest, store: &Mutex<Store>) -> HttpResponse {
+    let mut store = store.lock().unwrap();
+    let addr = request.local_addr;
+    let json_reply = |status: u16, value: Value| -> HttpResponse {
+        HttpResponse::new(status, value.to_string()).header("Content-Type", "application/json")
+    };
+
+    if request.target == "/.well-known/jmap" {
+        return HttpResponse::new(307, "").header("Location", "/jmap/session");
+    }
+    if request.headers.get("authorization") != Some(&store.authorization) {
+        return json_reply(
+            401,
+            json!({
+                "type": "about:blank",
+                "status": 401,
+                "detail": "Authentication required",
+            }),
+        );
+    }
+
+    match (request.method.as_str(), request.target.as_str()) {
+        ("GET", "/jmap/session") => json_reply(
+            200,
+            json!({
+                "capabilities": {
+                    "urn:ietf:params:jmap:core": {
+                        "maxSizeUpload": 50000000,
+                        "maxConcurrentUpload": 4,
+                        "maxSizeRequest": 10000000,
+                        "maxConcurrentRequests": 4,
+                        "maxCallsInRequest": 16,
+                        "maxObjectsInGet": store.max_objects_in_get,
+                        "maxObjectsInSet": 500,
+                        "collationAlgorithms": ["i;ascii-casemap", "i;octet"]
+                    },
+                    "urn:ietf:params:jmap:mail": {}
+                },
+                "accounts": {
+                    ACCOUNT_ID: {
+                        "name": "archive@example.com",
+                        "isPersonal": true,
+                        "isReadOnly": false,
+                        "accountCapabilities": { "urn:ietf:params:jmap:mail": {} }
+                    }
+                },
+                "primaryAccounts": { "urn:ietf:params:jmap:mail": ACCOUNT_ID },
+                "username": "archive@example.com",
+                "apiUrl": "/jmap/api",
+                "downloadUrl": format!(
+                    "http://{}/jmap/download/{{accountId}}/{{blobId}}/{{name}}?type={{type}}",
+                    addr
+                ),
+                "uploadUrl": format!("http://{}/jmap/upload/{{accountId}}/", addr),
+                "eventSourceUrl": format!(
+                    "http://{}/jmap/eventsource/?types={{types}}&closeafter={{closeafter}}&ping={{ping}}",
+                    addr
+                ),
+                "state": "session-1"
+            }),
+        ),
+        ("POST", "/jmap/api") => {
+            let Ok(body) = serde_json::from_slice::<Value>(&request.body) else {
+                return json_reply(
+                    400,
+                    json!({
+                        "type": "urn:ietf:params:jmap:error:notJSON",
+                        "status": 400,
+                        "detail": "The request body is not valid JSON",
+                    }),
+                );
+            };
+            let calls = body["methodCalls"].as_array().cloned().unwrap_or_default();
+            let responses: Vec<Value> = calls
+                .iter()
+                .map(|call| {
+                    let method = call[0].as_str().unwrap_or_default();
+                    store.methods.push(method.to_string());
+                    let (name, arguments) = match call_method(&store, method, &call[1]) {
+                        Ok(arguments) => (method.to_string(), arguments),
+                        Err(kind) => ("error".to_string(), json!({ "type": kind })),
+                    };
+                    json!([name, arguments, call[2]])
+                })
+                .collect();
+            json_reply(
+                200,
+                json!({ "methodResponses": responses, "sessionState": "session-1" }),
+            )
+        }
+        ("GET", target) if target.starts_with("/jmap/download/") => {
+            let blob_id = target.split('/').nth(4).unwrap_or_default();
+            match store
+                .emails
+                .iter()
+                .find(|(id, _)| blob_id == format!("B{}", id))
+            {
+                Some((_, email)) => HttpResponse::new(200, email.body.clone())
+                    .header("Content-Type", "message/rfc822"),
+                None => json_reply(
+                    404,
+                    json!({ "type": "about:blank", "status": 404, "detail": "Blob not found" }),
+                ),
+            }
+        }
+        _ => json_reply(
+            404,
+            json!({ "type": "about:blank", "status": 404, "detail": "Not found" }),
+        ),
+    }
+}
+
+/// Runs one method call; errors are JMAP method error types.
+fn call_method(store: &Store, method: &str, args: &Value) -> Result<Value, &'static str> {
+    if args["accountId"] != ACCOUNT_ID {
+        return Err("accountNotFound");
+    }
+    match method {
+        "Mailbox/get" => {
+            let list: Vec<Value> = store
+                .mailboxes
+                .iter()
+                .map(|m| {
+                    let total = store
+                        .emails
+                        .values()
+                        .filter(|e| e.mailbox_ids.contains(&m.id))
+                        .count();
+                    json!({
+                        "id": m.id,
+                        "name": m.name,
+                        "parentId": m.parent_id,
+                        "role": null,
+                        "totalEmails": total,
+                    })
+                })
+                .collect();
+            Ok(json!({
+                "accountId": ACCOUNT_ID,
+                "state": store.mailbox_state.to_string(),
+                "list": list,
+                "notFound": [],
+            }))
+        }
+        "Mailbox/changes" => {
+            let since: u64 = parse_state(&args["sinceState"])?;
+            if since > store.mailbox_state {
+                return Err("cannotCalculateChanges");
+            }
+            let updated: Vec<&str> = if since < store.mailbox_state {
+                store.mailboxes.iter().map(|m| m.id.as_str()).collect()
+            } else {
+                vec![]
+            };
+            Ok(json!({
+                "accountId": ACCOUNT_ID,
+                "oldState": since.to_string(),
+                "newState": store.mailbox_state.to_string(),
+                "hasMoreChanges": false,
+                "created": [],
+                "updated": updated,
+                "destroyed": [],
+            }))
+        }
+        "Email/get" => {
+            let ids: Vec<String> = match args["ids"].as_array() {
+                Some(ids) => ids
+                    .iter()
+                    .filter_map(|id| id.as_str().map(str::to_string))
+                    .collect(),
+                None => store.emails.keys().cloned().collect(),
+            };
+            if ids.len() > store.max_objects_in_get {
+                return Err("requestTooLarge");
+            }
+            let mut list = Vec::new();
+            let mut not_found = Vec::new();
+            for id in ids {
+                match store.emails.get(&id) {
+                    Some(email) => list.push(json!({
+                        "id": id,
+                        "blobId": format!("B{}", id),
+                        "mailboxIds": email
+                            .mailbox_ids
+                            .iter()
+                            .map(|m| (m.clone(), json!(true)))
+                            .collect::<serde_json::Map<_, _>>(),
+                        "keywords": email
+                            .keywords
+                            .iter()
+                            .map(|k| (k.clone(), json!(true)))
+                            .collect::<serde_json::Map<_, _>>(),
+                        "receivedAt": format!("2024-03-01T10:{:02}:00Z", email.received % 60),
+                        "size": email.body.len(),
+                    })),
+                    None => not_found.push(id),
+                }
+            }
+            Ok(json!({
+                "accountId": ACCOUNT_ID,
+                "state": store.email_state.to_string(),
+                "list": list,
+                "notFound": not_found,
+            }))
+        }
+        "Email/query" => {
+            let mut ids: Vec<(&u64, &String)> = store
+                .emails
+                .iter()
+                .map(|(id, email)| (&email.received, id))
+                .collect();
+            ids.sort();
+            let position = args["position"].as_u64().unwrap_or(0) as usize;
+            let limit = args["limit"]
+                .as_u64()
+                .map(|l| l as usize)
+                .unwrap_or(usize::MAX)
+                .min(store.page_limit);
+            let page: Vec<&String> = ids
+                .iter()
+                .skip(position)
+                .take(limit)
+                .map(|(_, id)| *id)
+                .collect();
+            Ok(json!({
+                "accountId": ACCOUNT_ID,
+                "queryState": store.email_state.to_string(),
+                "canCalculateChanges": false,
+                "position": position,
+                "ids": page,
+                "total": ids.len(),
+            }))
+        }
+        "Email/changes" => {
+            let since = parse_state(&args["sinceState"])?;
+            if since < store.history_floor || since > store.email_state {
+                return Err("cannotCalculateChanges");
+            }
+            let max = args["maxChanges"]
+                .as_u64()
+                .map(|m| m as usize)
+                .unwrap_or(usize::MAX)
+                .min(store.max_changes);
+            let pending: Vec<&(u64, String, Change)> = store
+                .log
+                .iter()
+                .filter(|(state, ..)| *state > since)
+                .collect();
+
+            // First and last change of every email, in order of appearance.
+            let mut order: Vec<&str> = Vec::new();
+            let mut seen: HashMap<&str, (Change, Change)> = HashMap::new();
+            let mut new_state = since;
+            for (state, id, change) in &pending {
+                if !seen.contains_key(id.as_str()) {
+                    if order.len() == max {
+                        break;
+                    }
+                    order.push(id);
+                }
+                seen.entry(id).or_insert((*change, *change)).1 = *change;
+                new_state = *state;
+            }
+            let (mut created, mut updated, mut destroyed) = (vec![], vec![], vec![]);
+            for id in order {
+                match seen[id] {
+                    (Change::Created, Change::Destroyed) => {}
+                    (_, Change::Destroyed) => destroyed.push(id),
+                    (Change::Created, _) => created.push(id),
+                    _ => updated.push(id),
+                }
+            }
+            Ok(json!({
+                "accountId": ACCOUNT_ID,
+                "oldState": since.to_string(),
+                "newState": new_state.to_string(),
+                "hasMoreChanges": new_state < store.email_state,
+                "created": created,
+                "updated": updated,
+                "destroyed": destroyed,
+            }))
+        }
+        _ => Err("unknownMethod"),
+    }
+}
+
+fn parse_state(state: &Value) -> Result<u64, &'static str> {
+    state
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .ok_or("invalidArguments")
+}
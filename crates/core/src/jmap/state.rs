@@ -0,0 +1,190 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    database::{
+        batch_delete_impl, delete_impl, filter_impl, find_impl, manager::DB_MANAGER, upsert_impl,
+        MemDbModel,
+    },
+    error::BichonResult,
+    utils::create_hash,
+};
+use serde::{Deserialize, Serialize};
+
+/// Account-wide JMAP sync state, kept next to the account's `DownloadState`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct JmapSyncState {
+    pub account_id: u64,
+    /// JMAP account id the states below belong to.
+    pub session_account_id: String,
+    /// `Email` state of the last completed sync, `None` until the first one
+    /// completes.
+    pub email_state: Option<String>,
+    /// `Mailbox` state the archived mailbox list was built from.
+    pub mailbox_state: Option<String>,
+}
+
+impl MemDbModel for JmapSyncState {
+    fn collection() -> &'static str {
+        "jmap_sync_states"
+    }
+    fn key(&self) -> String {
+        self.account_id.to_string()
+    }
+}
+
+impl JmapSyncState {
+    pub fn new(account_id: u64, session_account_id: &str) -> Self {
+        Self {
+            account_id,
+            session_account_id: session_account_id.to_string(),
+            email_state: None,
+            mailbox_state: None,
+        }
+    }
+
+    pub fn get(account_id: u64) -> BichonResult<Option<JmapSyncState>> {
+        find_impl(DB_MANAGER.db(), &account_id.to_string())
+    }
+
+    pub fn save(&self) -> BichonResult<()> {
+        upsert_impl(DB_MANAGER.db(), self.clone())
+    }
+
+    pub fn clean(account_id: u64) -> BichonResult<()> {
+        if Self::get(account_id)?.is_some() {
+            delete_impl::<JmapSyncState>(DB_MANAGER.db(), &account_id.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// A JMAP mailbox, keyed by the `MailBox` row it is archived into.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct JmapMailboxState {
+    pub mailbox_id: u64,
+    pub account_id: u64,
+    /// JMAP id of the mailbox.
+    pub jmap_id: String,
+    /// Path built from the names of the mailbox and its parents.
+    pub path: String,
+    /// UID handed to the next message archived into the mailbox.
+    pub next_uid: u32,
+}
+
+impl MemDbModel for JmapMailboxState {
+    fn collection() -> &'static str {
+        "jmap_mailboxes"
+    }
+    fn key(&self) -> String {
+        self.mailbox_id.to_string()
+    }
+}
+
+impl JmapMailboxState {
+    pub fn list_all(account_id: u64) -> BichonResult<Vec<JmapMailboxState>> {
+        filter_impl::<JmapMailboxState, _>(DB_MANAGER.db(), move |m| m.account_id == account_id)
+    }
+
+    pub fn save(&self) -> BichonResult<()> {
+        upsert_impl(DB_MANAGER.db(), self.clone())
+    }
+
+    pub fn clean(account_id: u64) -> BichonResult<()> {
+        let keys: Vec<String> = Self::list_all(account_id)?
+            .iter()
+            .map(|m| m.key())
+            .collect();
+        if !keys.is_empty() {
+            batch_delete_impl::<JmapMailboxState>(DB_MANAGER.db(), keys)?;
+        }
+        Ok(())
+    }
+}
+
+/// One archived copy of an email: the mailbox it was archived into and the
+/// UID its envelope was stored under.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct JmapCopy {
+    pub mailbox_id: u64,
+    pub uid: u32,
+}
+
+/// A JMAP email already archived. An email in several mailboxes is archived
+/// once per mailbox, like the same message in several IMAP folders.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct JmapEmailRef {
+    pub id: u64,
+    pub account_id: u64,
+    /// JMAP id of the email.
+    pub email_id: String,
+    pub copies: Vec<JmapCopy>,
+}
+
+impl MemDbModel for JmapEmailRef {
+    fn collection() -> &'static str {
+        "jmap_emails"
+    }
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl JmapEmailRef {
+    pub fn new(account_id: u64, email_id: &str) -> Self {
+        Self {
+            id: create_hash(account_id, email_id),
+            account_id,
+            email_id: email_id.to_string(),
+            copies: Vec::new(),
+        }
+    }
+
+    pub fn find(account_id: u64, email_id: &str) -> BichonResult<Option<JmapEmailRef>> {
+        find_impl(
+            DB_MANAGER.db(),
+            &create_hash(account_id, email_id).to_string(),
+        )
+    }
+
+    pub fn list_all(account_id: u64) -> BichonResult<Vec<JmapEmailRef>> {
+        filter_impl::<JmapEmailRef, _>(DB_MANAGER.db(), move |e| e.account_id == account_id)
+    }
+
+    /// Saves the reference, or drops it once no archived copy is left.
+    pub fn save(&self) -> BichonResult<()> {
+        if self.copies.is_empty() {
+            if Self::find(self.account_id, &self.email_id)?.is_some() {
+                delete_impl::<JmapEmailRef>(DB_MANAGER.db(), &self.key())?;
+            }
+            return Ok(());
+        }
+        upsert_impl(DB_MANAGER.db(), self.clone())
+    }
+
+    pub fn clean(account_id: u64) -> BichonResult<()> {
+        let keys: Vec<String> = Self::list_all(account_id)?
+            .iter()
+            .map(|e| e.key())
+            .collect();
+        if !keys.is_empty() {
+            batch_delete_impl::<JmapEmailRef>(DB_MANAGER.db(), keys)?;
+        }
+        Ok(())
+    }
+}
@@ -9,14 +9,18 @@ pub mod dashboard;
 pub mod database;
 pub mod envelope;
 pub mod error;
+pub mod graph;
 pub mod imap;
 pub mod import;
+pub mod jmap;
 pub mod logger;
 pub mod mailbox;
 pub mod message;
 pub mod migrate;
 pub mod oauth2;
+pub mod pop3;
 pub mod settings;
+pub mod smtp;
 pub mod store;
 pub mod tasks;
 pub mod token;
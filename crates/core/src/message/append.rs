@@ -2,7 +2,7 @@ use crate::{
     encode_mailbox_name, raise_error,
     {
         account::migration::{AccountModel, AccountType},
-        envelope::extractor::reattach_eml_content,
+        envelope::{extractor::reattach_eml_content, flags::append_flag_list},
         error::{code::ErrorCode, BichonResult},
         imap::executor::ImapExecutor,
     },
@@ -45,10 +45,11 @@ pub async fn restore_emails(account_id: u64, envelope_ids: Vec<String>) -> Bicho
         let result: BichonResult<()> = async {
             let (envelope, eml) = reattach_eml_content(account_id, envelope_id.clone())?;
             if let Some(mailbox_name) = envelope.mailbox_name {
+                let flags = append_flag_list(envelope.flags.as_deref().unwrap_or_default());
                 ImapExecutor::append(
                     &mut session,
                     encode_mailbox_name!(&mailbox_name),
-                    None,
+                    flags.as_deref(),
                     None,
                     &eml,
                 )
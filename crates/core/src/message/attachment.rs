@@ -4,13 +4,17 @@ use crate::{
     raise_error,
     {
         dashboard::Group,
-        envelope::extractor::reattach_eml_content,
+        envelope::{
+            archive::{find_archive_member, ArchiveFormat},
+            decrypt::readable_eml_content,
+            extractor::attachment_info,
+        },
         error::{code::ErrorCode, BichonResult},
         utils::compute_content_hash,
     },
 };
 use bytes::Bytes;
-use mail_parser::MessageParser;
+use mail_parser::{MessageParser, MessagePart, MimeHeaders};
 //use poem_openapi::Object;
 use serde::{Deserialize, Serialize};
 
@@ -38,7 +42,7 @@ pub fn retrieve_attachment_content(
     envelope_id: String,
     content_hash: &str,
 ) -> BichonResult<Cursor<Bytes>> {
-    let (_, eml) = reattach_eml_content(account_id, envelope_id)?;
+    let (_, eml, _) = readable_eml_content(account_id, envelope_id)?;
     let message = MessageParser::default()
         .parse(&eml)
         .ok_or_else(|| raise_error!("Failed to parse EML".into(), ErrorCode::InternalError))?;
@@ -62,7 +66,7 @@ pub fn retrieve_nested_attachment_content(
     content_hash: &str,
     nested_content_hash: &str,
 ) -> BichonResult<Cursor<Bytes>> {
-    let (_, eml) = reattach_eml_content(account_id, envelope_id)?;
+    let (_, eml, _) = readable_eml_content(account_id, envelope_id)?;
     let parent_message = MessageParser::default().parse(&eml).ok_or_else(|| {
         raise_error!(
             "Failed to parse parent EML".into(),
@@ -103,3 +107,101 @@ pub fn retrieve_nested_attachment_content(
 
     Ok(Cursor::new(Bytes::copy_from_slice(attachment_content)))
 }
+
+/// Returns the path within the archive and the content of a file expanded
+/// from an archive attachment at ingest. Members are not stored on their
+/// own, so the archive is expanded again and the member with
+/// `member_content_hash` picked out of it.
+pub fn retrieve_archive_member_content(
+    account_id: u64,
+    envelope_id: String,
+    content_hash: &str,
+    member_content_hash: &str,
+) -> BichonResult<(String, Cursor<Bytes>)> {
+    let (_, eml, _) = readable_eml_content(account_id, envelope_id)?;
+    let message = MessageParser::default()
+        .parse(&eml)
+        .ok_or_else(|| raise_error!("Failed to parse EML".into(), ErrorCode::InternalError))?;
+
+    let archive = message
+        .attachments()
+        .find(|att| compute_content_hash(att.contents()) == content_hash)
+        .ok_or_else(|| {
+            raise_error!(
+                "Target archive attachment not found".into(),
+                ErrorCode::ResourceNotFound
+            )
+        })?;
+    let file_type = attachment_info(archive, content_hash.to_string()).file_type;
+    let name = archive.attachment_name().unwrap_or_default();
+    let format = ArchiveFormat::detect(&file_type, name, archive.contents()).ok_or_else(|| {
+        raise_error!(
+            "Attachment is not a supported archive".into(),
+            ErrorCode::InvalidParameter
+        )
+    })?;
+    let (path, member) = find_archive_member(format, name, archive.contents(), member_content_hash)
+        .ok_or_else(|| {
+            raise_error!(
+                "Target archive member not found".into(),
+                ErrorCode::ResourceNotFound
+            )
+        })?;
+    Ok((path, Cursor::new(Bytes::from(member))))
+}
+
+/// Returns the content type and bytes of an inline part for the signed
+/// inline-attachment endpoint. With `nested_content_hash`, `content_hash`
+/// names the attached message and the part is looked up inside it.
+pub fn retrieve_inline_attachment(
+    account_id: u64,
+    envelope_id: String,
+    content_hash: &str,
+    nested_content_hash: Option<&str>,
+) -> BichonResult<(String, Bytes)> {
+    let (_, eml, _) = readable_eml_content(account_id, envelope_id)?;
+    let message = MessageParser::default()
+        .parse(&eml)
+        .ok_or_else(|| raise_error!("Failed to parse EML".into(), ErrorCode::InternalError))?;
+
+    let not_found = || {
+        raise_error!(
+            "Target attachment not found".into(),
+            ErrorCode::ResourceNotFound
+        )
+    };
+    let part = message
+        .attachments()
+        .find(|att| compute_content_hash(att.contents()) == content_hash)
+        .ok_or_else(not_found)?;
+
+    let Some(nested_content_hash) = nested_content_hash else {
+        return Ok((
+            part_content_type(part),
+            Bytes::copy_from_slice(part.contents()),
+        ));
+    };
+    let nested_message = MessageParser::default()
+        .parse(part.contents())
+        .ok_or_else(|| {
+            raise_error!(
+                "Failed to parse nested EML".into(),
+                ErrorCode::InternalError
+            )
+        })?;
+    let nested_part = nested_message
+        .attachments()
+        .find(|att| compute_content_hash(att.contents()) == nested_content_hash)
+        .ok_or_else(not_found)?;
+    Ok((
+        part_content_type(nested_part),
+        Bytes::copy_from_slice(nested_part.contents()),
+    ))
+}
+
+fn part_content_type(part: &MessagePart<'_>) -> String {
+    part.content_type().map_or_else(
+        || "application/octet-stream".to_string(),
+        |ct| format!("{}/{}", ct.c_type, ct.c_subtype.as_deref().unwrap_or("")),
+    )
+}
@@ -16,17 +16,28 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
+
 use crate::account::migration::AccountModel;
-use crate::base64_encode;
-use crate::envelope::extractor::{extract_envelope_from_nested_message, reattach_eml_content};
+use crate::envelope::authentication::MessageAuthentication;
+use crate::envelope::decrypt::readable_eml_content;
+use crate::envelope::extractor::extract_envelope_from_nested_message;
 use crate::error::code::ErrorCode;
+use crate::message::remote::remote_content_url;
+use crate::settings::cli::SETTINGS;
 use crate::store::envelope::Envelope;
 use crate::utils::compute_content_hash;
-use crate::utils::html::block_remote_content;
+use crate::utils::html::{block_remote_content, rewrite_remote_content};
+use crate::utils::sanitize::sanitize_html;
+use crate::utils::signed_url;
 use crate::{error::BichonResult, raise_error};
 use mail_parser::{MessageParser, MimeHeaders};
 //use poem_openapi::Object;
 use serde::{Deserialize, Serialize};
+
+/// Signing scope of the URLs that serve inline parts to rendered message bodies.
+const INLINE_ATTACHMENT_SCOPE: &str = "inline-attachment";
+
 /// Represents metadata of an attachment in a Gmail message.
 ///
 /// This struct stores information required to identify, download,
@@ -55,6 +66,10 @@ pub struct AttachmentInfo {
     /// Whether the extracted text came from OCR.
     #[serde(default)]
     pub extracted_is_ocr: bool,
+    /// Files inside the attachment when it is a zip, tar, gzip or 7z archive,
+    /// including those of nested archives. None for other attachments.
+    #[serde(default)]
+    pub archive_members: Option<Vec<ArchiveMember>>,
 }
 
 impl AttachmentInfo {
@@ -71,67 +86,114 @@ impl AttachmentInfo {
     }
 
     pub fn get_category(&self) -> &'static str {
-        if let Some(ext) = self.get_extension() {
-            let category = match ext.as_str() {
-                "doc" | "docx" | "pdf" | "rtf" | "odt" | "pages" | "pptx" | "ppt" => {
-                    Some("document")
-                }
-                "xls" | "xlsx" | "ods" | "numbers" | "csv" => Some("spreadsheet"),
-                "ical" | "ics" | "vcs" | "ifb" | "icalendar" => Some("event"),
-                "txt" | "log" | "md" => Some("text"),
-                "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tiff" | "avif" | "heic" | "heif"
-                | "webp" => Some("image"),
-                "mp4" | "mkv" | "mov" | "avi" | "webm" => Some("video"),
-                "wav" | "mp3" | "aac" | "ogg" | "wma" | "flac" | "aiff" => Some("audio"),
-                "psd" | "eps" | "svg" | "cdr" | "ai" => Some("graphics_2d"),
-                "stl" | "obj" | "3mf" | "amf" | "f3d" | "sldprt" | "stp" | "step" | "dwg"
-                | "x_t" | "x_b" | "sat" | "ipt" => Some("graphics_3d"),
-                "c" | "h" | "html" | "css" | "js" | "ts" | "vue" | "tsx" | "svelte" | "py"
-                | "java" | "cs" | "go" | "rb" | "php" | "swift" | "rs" | "r" | "jl" | "lua"
-                | "sql" => Some("code"),
-                "tsv" | "xml" | "json" | "yml" | "yaml" | "toml" | "env" | "ini" => Some("data"),
-                "ps1" | "sh" | "bat" | "cmd" | "exe" | "msi" | "dmg" | "pkg" | "deb" | "rpm" => {
-                    Some("executable")
-                }
-                "zip" | "gz" | "tgz" | "7z" | "rar" | "tar" | "bz2" | "zst" | "xz" | "iso"
-                | "img" => Some("archive"),
-                "eml" | "msg" => Some("message"),
-                _ => None,
-            };
-
-            if let Some(cat) = category {
-                return cat;
-            }
-        }
+        attachment_category(self.get_extension().as_deref(), &self.file_type)
+    }
+}
 
-        let mime = self.file_type.to_lowercase();
-        if mime.starts_with("image/") {
-            return "image";
-        }
-        if mime.starts_with("video/") {
-            return "video";
-        }
-        if mime.starts_with("audio/") {
-            return "audio";
-        }
-        if mime.starts_with("text/") {
-            return "text";
-        }
-        if mime == "message/rfc822" {
-            return "message";
-        }
-        if mime.contains("compressed") || mime.contains("zip") || mime.contains("archive") {
-            return "archive";
-        }
-        if mime.contains("pdf") || mime.contains("msword") || mime.contains("officedocument") {
-            return "document";
-        }
-        if mime.contains("spreadsheet") || mime.contains("excel") {
-            return "spreadsheet";
+/// A file inside an archive attachment. Each member is indexed as an
+/// attachment of its own and is downloaded by expanding its archive again.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct ArchiveMember {
+    /// Path of the member inside the archive. Members of a nested archive
+    /// are prefixed with its path, e.g. `2026/q1.zip/invoice.pdf`.
+    pub path: String,
+    /// MIME type guessed from the file extension.
+    pub file_type: String,
+    /// Uncompressed size in bytes.
+    pub size: usize,
+    /// Hash of the uncompressed content.
+    pub content_hash: String,
+    /// Text extracted from the member, as for a regular attachment.
+    pub extracted_text: Option<String>,
+    /// Page count reported by the extractor, if any.
+    pub extracted_page_count: Option<u32>,
+    /// Whether the extracted text came from OCR.
+    #[serde(default)]
+    pub extracted_is_ocr: bool,
+}
+
+impl ArchiveMember {
+    /// The last component of [`Self::path`].
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn get_extension(&self) -> Option<String> {
+        std::path::Path::new(self.file_name())
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+    }
+
+    pub fn get_category(&self) -> &'static str {
+        attachment_category(self.get_extension().as_deref(), &self.file_type)
+    }
+}
+
+/// The broad category of an attachment (document, image, archive, ...),
+/// from its file extension or, failing that, its MIME type.
+pub fn attachment_category(ext: Option<&str>, file_type: &str) -> &'static str {
+    if let Some(ext) = ext {
+        let category = match ext {
+            "doc" | "docx" | "pdf" | "rtf" | "odt" | "pages" | "pptx" | "ppt" => {
+                Some("document")
+            }
+            "xls" | "xlsx" | "ods" | "numbers" | "csv" => Some("spreadsheet"),
+            "ical" | "ics" | "vcs" | "ifb" | "icalendar" => Some("event"),
+            "txt" | "log" | "md" => Some("text"),
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tiff" | "avif" | "heic" | "heif"
+            | "webp" => Some("image"),
+            "mp4" | "mkv" | "mov" | "avi" | "webm" => Some("video"),
+            "wav" | "mp3" | "aac" | "ogg" | "wma" | "flac" | "aiff" => Some("audio"),
+            "psd" | "eps" | "svg" | "cdr" | "ai" => Some("graphics_2d"),
+            "stl" | "obj" | "3mf" | "amf" | "f3d" | "sldprt" | "stp" | "step" | "dwg"
+            | "x_t" | "x_b" | "sat" | "ipt" => Some("graphics_3d"),
+            "c" | "h" | "html" | "css" | "js" | "ts" | "vue" | "tsx" | "svelte" | "py"
+            | "java" | "cs" | "go" | "rb" | "php" | "swift" | "rs" | "r" | "jl" | "lua"
+            | "sql" => Some("code"),
+            "tsv" | "xml" | "json" | "yml" | "yaml" | "toml" | "env" | "ini" => Some("data"),
+            "ps1" | "sh" | "bat" | "cmd" | "exe" | "msi" | "dmg" | "pkg" | "deb" | "rpm" => {
+                Some("executable")
+            }
+            "zip" | "gz" | "tgz" | "7z" | "rar" | "tar" | "bz2" | "zst" | "xz" | "iso"
+            | "img" => Some("archive"),
+            "eml" | "msg" => Some("message"),
+            _ => None,
+        };
+
+        if let Some(cat) = category {
+            return cat;
         }
+    }
 
-        "other"
+    let mime = file_type.to_lowercase();
+    if mime.starts_with("image/") {
+        return "image";
+    }
+    if mime.starts_with("video/") {
+        return "video";
+    }
+    if mime.starts_with("audio/") {
+        return "audio";
+    }
+    if mime.starts_with("text/") {
+        return "text";
+    }
+    if mime == "message/rfc822" {
+        return "message";
+    }
+    if mime.contains("compressed") || mime.contains("zip") || mime.contains("archive") {
+        return "archive";
     }
+    if mime.contains("pdf") || mime.contains("msword") || mime.contains("officedocument") {
+        return "document";
+    }
+    if mime.contains("spreadsheet") || mime.contains("excel") {
+        return "spreadsheet";
+    }
+
+    "other"
 }
 /// Represents the content of an email message in both plain text and HTML formats.
 ///
@@ -147,13 +209,23 @@ impl AttachmentInfo {
 pub struct FullMessageContent {
     /// Optional plain text version of the message.
     pub text: Option<String>,
-    /// Optional HTML version of the message.
+    /// Optional HTML version of the message, sanitized for display.
     pub html: Option<String>,
     // all Attachments include inline attachments
     pub attachments: Option<Vec<AttachmentInfo>>,
-    /// True when remote content (http/https URLs) was detected and stripped from html.
+    /// True when html references remote content (http/https URLs). Such content is
+    /// stripped when blocking is requested and loaded through the server's remote
+    /// content proxy otherwise.
     #[serde(default)]
     pub has_remote_content: bool,
+    /// True when the message was encrypted and is shown decrypted with the
+    /// account's OpenPGP or S/MIME key.
+    #[serde(default)]
+    pub decrypted: bool,
+    /// DKIM and signature verification results recorded at ingest. Absent
+    /// for messages archived before verification existed.
+    #[serde(default)]
+    pub authentication: Option<MessageAuthentication>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
@@ -161,13 +233,15 @@ pub struct FullMessageContent {
 pub struct FullNestedMessageContent {
     /// Optional plain text version of the message.
     pub text: Option<String>,
-    /// Optional HTML version of the message.
+    /// Optional HTML version of the message, sanitized for display.
     pub html: Option<String>,
     // all Attachments include inline attachments
     pub attachments: Option<Vec<AttachmentInfo>>,
     /// Metadata for the email envelope.
     pub envelope: Envelope,
-    /// True when remote content (http/https URLs) was detected and stripped from html.
+    /// True when html references remote content (http/https URLs). Such content is
+    /// stripped when blocking is requested and loaded through the server's remote
+    /// content proxy otherwise.
     #[serde(default)]
     pub has_remote_content: bool,
 }
@@ -178,16 +252,17 @@ pub fn retrieve_email_content(
     block_remote: bool,
 ) -> BichonResult<FullMessageContent> {
     AccountModel::check_account_exists(account_id)?;
-    let (envelope, eml) = reattach_eml_content(account_id, envelope_id)?;
+    let (envelope, eml, decrypted) = readable_eml_content(account_id, envelope_id)?;
     let message = MessageParser::default().parse(&eml).ok_or_else(|| {
         raise_error!(
             "Failed to parse EML data — the message may be corrupted.".into(),
             ErrorCode::InternalError
         )
     })?;
-    let mut html: Option<String> = message.body_html(0).map(|cow| cow.into_owned());
+    let html: Option<String> = message.body_html(0).map(|cow| cow.into_owned());
     let text: Option<String> = message.body_text(0).map(|cow| cow.into_owned());
     let mut attachments = Vec::new();
+    let mut inline_urls = HashMap::new();
     for attachment in message.attachments() {
         let content_type = attachment.content_type().ok_or_else(|| {
             raise_error!(
@@ -209,28 +284,19 @@ pub fn retrieve_email_content(
         let inline = disposition
             .map(|d| d.is_inline())
             .unwrap_or_else(|| attachment.content_id().is_some());
+        let content_hash = compute_content_hash(attachment.contents());
 
+        //inline attachment will not be displayed in email attachment list
         if inline {
-            if let Some(html1) = html.as_deref() {
-                if let Some(cid) = attachment.content_id() {
-                    if html1.contains(cid) {
-                        let data = attachment.contents();
-                        let base64_encoded = base64_encode!(data);
-                        let html_content = html1.replace(
-                            &format!("cid:{}", cid),
-                            &format!("data:{};base64,{}", file_type, base64_encoded),
-                        );
-                        html = Some(html_content);
-                    }
-                }
+            if let Some(cid) = attachment.content_id() {
+                inline_urls.insert(
+                    cid.to_string(),
+                    inline_attachment_url(account_id, &envelope.id, &content_hash, None),
+                );
+                continue;
             }
         }
-        //inline attachment will not be displayed in email attachment list
-        if inline && attachment.content_id().is_some() {
-            continue;
-        }
         let is_message = attachment.is_message();
-        let content_hash = compute_content_hash(attachment.contents());
         attachments.push(AttachmentInfo {
             filename: filename.or(Some(content_hash.clone())),
             size: attachment.contents().len(),
@@ -242,21 +308,18 @@ pub fn retrieve_email_content(
             extracted_text: None,
             extracted_page_count: None,
             extracted_is_ocr: false,
+            archive_members: None,
         });
     }
-    let mut has_remote_content = false;
-    if let Some(ref html_body) = html {
-        let filtered = block_remote_content(html_body);
-        has_remote_content = *html_body != filtered;
-        if block_remote {
-            html = Some(filtered);
-        }
-    }
+    let (html, has_remote_content) = render_html(html, &inline_urls, block_remote);
+    let authentication = MessageAuthentication::find(&envelope.content_hash)?;
     Ok(FullMessageContent {
         text,
         html,
         attachments: Some(attachments),
         has_remote_content,
+        decrypted,
+        authentication,
     })
 }
 
@@ -266,7 +329,7 @@ pub fn retrieve_nested_eml_content(
     content_hash: &str,
     block_remote: bool,
 ) -> BichonResult<FullNestedMessageContent> {
-    let (_, eml) = reattach_eml_content(account_id, envelope_id)?;
+    let (parent_envelope, eml, _) = readable_eml_content(account_id, envelope_id)?;
     let parent_message = MessageParser::default().parse(&eml).ok_or_else(|| {
         raise_error!(
             "Failed to parse parent EML".into(),
@@ -294,12 +357,11 @@ pub fn retrieve_nested_eml_content(
             )
         })?;
 
-    let mut html = nested_message.body_html(0).map(|c| c.into_owned());
+    let html = nested_message.body_html(0).map(|c| c.into_owned());
     let text = nested_message.body_text(0).map(|c| c.into_owned());
 
     let mut attachments = Vec::new();
-
-    let has_html = html.is_some();
+    let mut inline_urls = HashMap::new();
 
     for attachment in nested_message.attachments() {
         let cid = attachment.content_id();
@@ -307,21 +369,19 @@ pub fn retrieve_nested_eml_content(
         let is_inline = disposition
             .map(|d| d.is_inline())
             .unwrap_or_else(|| cid.is_some());
-
-        if has_html && is_inline && cid.is_some() {
-            let content_id = cid.unwrap();
-            let html_ref = html.as_mut().unwrap();
-
-            let cid_pattern = format!("cid:{}", content_id);
-            if html_ref.contains(&cid_pattern) {
-                let data = attachment.contents();
-                let ct = attachment
-                    .content_type()
-                    .map(|ct| format!("{}/{}", ct.c_type, ct.c_subtype.as_deref().unwrap_or("")))
-                    .unwrap_or_else(|| "image/png".to_string());
-
-                let base64_data = format!("data:{};base64,{}", ct, base64_encode!(data));
-                *html_ref = html_ref.replace(&cid_pattern, &base64_data);
+        let part_hash = compute_content_hash(attachment.contents());
+
+        if let (Some(body), Some(content_id), true) = (html.as_deref(), cid, is_inline) {
+            if body.contains(&format!("cid:{}", content_id)) {
+                inline_urls.insert(
+                    content_id.to_string(),
+                    inline_attachment_url(
+                        account_id,
+                        &parent_envelope.id,
+                        content_hash,
+                        Some(&part_hash),
+                    ),
+                );
                 continue;
             }
         }
@@ -330,34 +390,27 @@ pub fn retrieve_nested_eml_content(
             || "application/octet-stream".to_string(),
             |ct| format!("{}/{}", ct.c_type, ct.c_subtype.as_deref().unwrap_or("")),
         );
-        let content_hash = compute_content_hash(attachment.contents());
         attachments.push(AttachmentInfo {
             filename: attachment
                 .attachment_name()
                 .map(|n| n.to_string())
-                .or(Some(content_hash.clone())),
+                .or(Some(part_hash.clone())),
             size: attachment.contents().len(),
             inline: is_inline,
             file_type,
-            content_hash,
+            content_hash: part_hash,
             is_message: attachment.is_message(),
             content_id: cid.map(Into::into),
             extracted_text: None,
             extracted_page_count: None,
             extracted_is_ocr: false,
+            archive_members: None,
         });
     }
 
     let envelope = extract_envelope_from_nested_message(nested_message, account_id)?;
 
-    let mut has_remote_content = false;
-    if let Some(ref html_body) = html {
-        let filtered = block_remote_content(html_body);
-        has_remote_content = *html_body != filtered;
-        if block_remote {
-            html = Some(filtered);
-        }
-    }
+    let (html, has_remote_content) = render_html(html, &inline_urls, block_remote);
     Ok(FullNestedMessageContent {
         text,
         html,
@@ -367,6 +420,89 @@ pub fn retrieve_nested_eml_content(
     })
 }
 
+/// Sanitizes a message body, then either strips remote content or rewrites it
+/// to go through the remote content proxy. Returns the HTML to send and
+/// whether it references remote content.
+fn render_html(
+    html: Option<String>,
+    inline_urls: &HashMap<String, String>,
+    block_remote: bool,
+) -> (Option<String>, bool) {
+    let Some(html) = html else {
+        return (None, false);
+    };
+    let sanitized = sanitize_html(&html, inline_urls);
+    let filtered = block_remote_content(&sanitized);
+    let has_remote_content = sanitized != filtered;
+    let html = if block_remote {
+        filtered
+    } else if has_remote_content {
+        rewrite_remote_content(&sanitized, remote_content_url)
+    } else {
+        sanitized
+    };
+    (Some(html), has_remote_content)
+}
+
+/// Builds a signed, expiring URL for an inline part so that `<img>` tags in
+/// the rendered body can load it without an access token. For parts of an
+/// attached message, `content_hash` names the attached message and
+/// `nested_content_hash` the part within it.
+fn inline_attachment_url(
+    account_id: u64,
+    envelope_id: &str,
+    content_hash: &str,
+    nested_content_hash: Option<&str>,
+) -> String {
+    let account = account_id.to_string();
+    let signature = signed_url::sign(
+        INLINE_ATTACHMENT_SCOPE,
+        &[
+            &account,
+            envelope_id,
+            content_hash,
+            nested_content_hash.unwrap_or(""),
+        ],
+    );
+    let mut url = format!(
+        "{}/api/inline-attachment/{}/{}?content_hash={}",
+        SETTINGS.bichon_base_url.trim_end_matches('/'),
+        account_id,
+        urlencoding::encode(envelope_id),
+        content_hash
+    );
+    if let Some(nested) = nested_content_hash {
+        url.push_str("&nested_content_hash=");
+        url.push_str(nested);
+    }
+    url.push('&');
+    url.push_str(&signature.to_query());
+    url
+}
+
+/// Checks the signature of a URL produced by [`inline_attachment_url`].
+pub fn verify_inline_attachment_url(
+    account_id: u64,
+    envelope_id: &str,
+    content_hash: &str,
+    nested_content_hash: Option<&str>,
+    expires: i64,
+    signature: &str,
+) -> BichonResult<()> {
+    let account = account_id.to_string();
+    signed_url::verify(
+        INLINE_ATTACHMENT_SCOPE,
+        &[
+            &account,
+            envelope_id,
+            content_hash,
+            nested_content_hash.unwrap_or(""),
+        ],
+        expires,
+        signature,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -443,6 +579,7 @@ mod tests {
                 extracted_text: Some("hello world".into()),
                 extracted_page_count: Some(1),
                 extracted_is_ocr: false,
+                archive_members: None,
             },
             AttachmentInfo {
                 file_type: "application/zip".into(),
@@ -455,6 +592,7 @@ mod tests {
                 extracted_text: None,
                 extracted_page_count: None,
                 extracted_is_ocr: true,
+                archive_members: None,
             },
         ];
 
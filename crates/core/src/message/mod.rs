@@ -22,5 +22,6 @@ pub mod contacts;
 pub mod content;
 pub mod delete;
 pub mod list;
+pub mod remote;
 pub mod search;
 pub mod tags;
@@ -0,0 +1,456 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Server-side proxy for remote images referenced by archived messages.
+//!
+//! When a reviewer chooses to load remote content, the message HTML points at
+//! signed, expiring proxy URLs instead of the original hosts. The server then
+//! fetches each resource without cookies or a referrer, through the proxy
+//! profile named by `bichon_remote_content_proxy` if set, and caches the bytes
+//! in the blob store by content hash. Later views are served from the cache,
+//! so they stay reproducible after the remote host disappears.
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use bytes::Bytes;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::database::manager::DB_MANAGER;
+use crate::database::{find_impl, upsert_impl, MemDbModel};
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::settings::cli::SETTINGS;
+use crate::settings::proxy::Proxy;
+use crate::store::blob::{hex_to_key, BLOB_MANAGER};
+use crate::utils::net::parse_proxy_url;
+use crate::utils::{compute_content_hash, signed_url};
+use crate::{raise_error, utc_now};
+
+/// Signing scope of proxy URLs.
+const REMOTE_CONTENT_SCOPE: &str = "remote-content";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+const MAX_REDIRECTS: usize = 5;
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; Bichon image proxy)";
+
+/// Image formats the proxy passes through. SVG is excluded because it can
+/// carry script.
+const ALLOWED_CONTENT_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/avif",
+    "image/x-icon",
+    "image/vnd.microsoft.icon",
+];
+
+/// A cached remote resource, keyed by the hash of its URL. The bytes live in
+/// the blob store under `content_hash`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RemoteContent {
+    /// Hash of `url`, used as the record key.
+    pub url_hash: String,
+    pub url: String,
+    /// Hash of the fetched bytes, which is also their blob store key.
+    pub content_hash: String,
+    pub content_type: String,
+    pub size: u64,
+    /// When the resource was fetched, in milliseconds since the Unix epoch.
+    pub fetched_at: i64,
+}
+
+impl MemDbModel for RemoteContent {
+    fn collection() -> &'static str {
+        "remote_content"
+    }
+    fn key(&self) -> String {
+        self.url_hash.clone()
+    }
+}
+
+/// Bytes and content type of a proxied resource.
+pub struct RemoteResource {
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+/// Builds the signed proxy URL that replaces `url` in rendered message HTML.
+pub fn remote_content_url(url: &str) -> String {
+    let signature = signed_url::sign(REMOTE_CONTENT_SCOPE, &[url]);
+    format!(
+        "{}/api/remote-content?url={}&{}",
+        SETTINGS.bichon_base_url.trim_end_matches('/'),
+        urlencoding::encode(url),
+        signature.to_query()
+    )
+}
+
+/// Checks the signature of a URL produced by [`remote_content_url`].
+pub fn verify_remote_content_url(url: &str, expires: i64, signature: &str) -> BichonResult<()> {
+    signed_url::verify(REMOTE_CONTENT_SCOPE, &[url], expires, signature)
+}
+
+/// Returns the resource at `url`, from the cache when it was fetched before.
+pub async fn fetch_remote_content(url: &str) -> BichonResult<RemoteResource> {
+    let url_hash = compute_content_hash(url.as_bytes());
+    if let Some(cached) = find_impl::<RemoteContent>(DB_MANAGER.db(), &url_hash)? {
+        let key = hex_to_key(&cached.content_hash)?;
+        if let Some(data) = BLOB_MANAGER.store().get(&key)? {
+            return Ok(RemoteResource {
+                content_type: cached.content_type,
+                data: Bytes::from(data),
+            });
+        }
+    }
+
+    let resource = download(url).await?;
+    let content_hash = compute_content_hash(&resource.data);
+    let key = hex_to_key(&content_hash)?;
+    let store = BLOB_MANAGER.store();
+    if !store.exists(&key)? {
+        store.put(key, &resource.data)?;
+    }
+    upsert_impl(
+        DB_MANAGER.db(),
+        RemoteContent {
+            url_hash,
+            url: url.to_string(),
+            content_hash,
+            content_type: resource.content_type.clone(),
+            size: resource.data.len() as u64,
+            fetched_at: utc_now!(),
+        },
+    )?;
+    Ok(resource)
+}
+
+async fn download(url: &str) -> BichonResult<RemoteResource> {
+    let proxy = SETTINGS
+        .bichon_remote_content_proxy
+        .map(Proxy::get)
+        .transpose()?;
+    let max_bytes = SETTINGS.bichon_remote_content_max_mb * 1024 * 1024;
+    let mut target = parse_remote_url(url)?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let client = build_client(&target, proxy.as_ref()).await?;
+        let mut response = client
+            .get(target.clone())
+            .header(ACCEPT, "image/*")
+            .send()
+            .await
+            .map_err(|e| {
+                raise_error!(
+                    format!("Failed to fetch remote content: {e}"),
+                    ErrorCode::NetworkError
+                )
+            })?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| {
+                    raise_error!(
+                        "Redirect without a Location header".into(),
+                        ErrorCode::HttpResponseError
+                    )
+                })?;
+            let next = target.join(location).map_err(|_| {
+                raise_error!(
+                    format!("Invalid redirect target '{location}'"),
+                    ErrorCode::HttpResponseError
+                )
+            })?;
+            target = parse_remote_url(next.as_str())?;
+            continue;
+        }
+        if !response.status().is_success() {
+            return Err(raise_error!(
+                format!("Remote server answered {}", response.status()),
+                ErrorCode::HttpResponseError
+            ));
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(allowed_content_type)
+            .ok_or_else(|| {
+                raise_error!(
+                    "Remote content is not a supported image type".into(),
+                    ErrorCode::InvalidParameter
+                )
+            })?;
+        let too_large = || {
+            raise_error!(
+                format!("Remote content exceeds {} bytes", max_bytes),
+                ErrorCode::PayloadTooLarge
+            )
+        };
+        if response.content_length().is_some_and(|len| len > max_bytes) {
+            return Err(too_large());
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| {
+            raise_error!(
+                format!("Failed to read remote content: {e}"),
+                ErrorCode::NetworkError
+            )
+        })? {
+            if (data.len() + chunk.len()) as u64 > max_bytes {
+                return Err(too_large());
+            }
+            data.extend_from_slice(&chunk);
+        }
+        return Ok(RemoteResource {
+            content_type,
+            data: Bytes::from(data),
+        });
+    }
+
+    Err(raise_error!(
+        "Too many redirects while fetching remote content".into(),
+        ErrorCode::HttpResponseError
+    ))
+}
+
+/// A client for a single request to `target`: no redirects, cookies or
+/// referrer. The host must resolve to public addresses, so a message cannot
+/// point the server at internal hosts. Without a proxy profile the client is
+/// pinned to the checked addresses; a proxy resolves the host itself, which
+/// [`parse_remote_url`] narrows down by rejecting internal host names.
+async fn build_client(target: &Url, proxy: Option<&Proxy>) -> BichonResult<Client> {
+    let host = target.host_str().unwrap_or_default();
+    let addrs = resolve_public(host, target.port_or_known_default().unwrap_or(80)).await?;
+    let builder = Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(Policy::none())
+        .referer(false)
+        .user_agent(USER_AGENT);
+    let builder = match proxy {
+        Some(proxy) => {
+            let proxy_url = parse_proxy_url(&proxy.url)?.standard_url();
+            builder.proxy(reqwest::Proxy::all(&proxy_url).map_err(|_| {
+                raise_error!(
+                    "Failed to configure proxy. Please check the proxy configuration.".into(),
+                    ErrorCode::InternalError
+                )
+            })?)
+        }
+        None => builder.no_proxy().resolve_to_addrs(host, &addrs),
+    };
+    builder
+        .build()
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))
+}
+
+async fn resolve_public(host: &str, port: u16) -> BichonResult<Vec<SocketAddr>> {
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((bare, port))
+        .await
+        .map_err(|e| {
+            raise_error!(
+                format!("Failed to resolve '{host}': {e}"),
+                ErrorCode::NetworkError
+            )
+        })?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(raise_error!(
+            format!("Remote content host '{host}' does not resolve to a public address"),
+            ErrorCode::Forbidden
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Accepts absolute http(s) URLs whose host is a public IP literal or a
+/// fully qualified name outside the local and internal namespaces.
+fn parse_remote_url(url: &str) -> BichonResult<Url> {
+    let invalid = || {
+        raise_error!(
+            format!("Unsupported remote content URL '{url}'"),
+            ErrorCode::InvalidParameter
+        )
+    };
+    let parsed = Url::parse(url).map_err(|_| invalid())?;
+    if !matches!(parsed.scheme(), "http" | "https") || !parsed.username().is_empty() {
+        return Err(invalid());
+    }
+    let host = parsed.host_str().ok_or_else(invalid)?;
+    if let Ok(ip) = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        if !is_public_ip(ip) {
+            return Err(invalid());
+        }
+    } else if is_internal_host_name(host) {
+        return Err(invalid());
+    }
+    Ok(parsed)
+}
+
+/// Names that only resolve inside a network: `localhost`, single labels
+/// completed by the resolver's search domains, and reserved suffixes.
+fn is_internal_host_name(host: &str) -> bool {
+    const INTERNAL_SUFFIXES: &[&str] = &[
+        ".localhost",
+        ".local",
+        ".internal",
+        ".localdomain",
+        ".home.arpa",
+    ];
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == "localhost"
+        || !host.contains('.')
+        || INTERNAL_SUFFIXES
+            .iter()
+            .any(|suffix| host.ends_with(suffix))
+}
+
+fn allowed_content_type(header: &str) -> Option<String> {
+    let mime = header.split(';').next()?.trim().to_ascii_lowercase();
+    ALLOWED_CONTENT_TYPES
+        .contains(&mime.as_str())
+        .then_some(mime)
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_unspecified()
+                || v4.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b))
+                || (a == 192 && b == 0 && v4.octets()[2] == 0))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || (first == 0x2001 && v6.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn only_public_http_urls_are_fetched() {
+        assert!(parse_remote_url("https://example.com/a.png").is_ok());
+        assert!(parse_remote_url("http://93.184.216.34/a.png").is_ok());
+        assert!(parse_remote_url("ftp://example.com/a.png").is_err());
+        assert!(parse_remote_url("file:///etc/passwd").is_err());
+        assert!(parse_remote_url("http://127.0.0.1/admin").is_err());
+        assert!(parse_remote_url("http://[::1]/admin").is_err());
+        assert!(parse_remote_url("http://169.254.169.254/latest/meta-data").is_err());
+        assert!(parse_remote_url("https://user@example.com/a.png").is_err());
+        assert!(parse_remote_url("/relative.png").is_err());
+    }
+
+    #[test]
+    fn internal_host_names_are_rejected() {
+        for url in [
+            "http://localhost/a.png",
+            "http://LOCALHOST./a.png",
+            "http://api.localhost/a.png",
+            "http://intranet/a.png",
+            "http://metadata.google.internal/computeMetadata/v1/",
+            "http://printer.local/a.png",
+            "http://nas.home.arpa/a.png",
+        ] {
+            assert!(parse_remote_url(url).is_err(), "{url}");
+        }
+        assert!(parse_remote_url("https://images.example.org./a.png").is_ok());
+    }
+
+    #[tokio::test]
+    async fn proxied_fetches_check_the_resolved_address() {
+        let proxy = Proxy {
+            id: 1,
+            url: "http://proxy.example.com:3128".into(),
+            created_at: 0,
+            updated_at: 0,
+        };
+        // `localhost` resolves from the hosts file, without DNS.
+        let target = Url::parse("http://localhost:8080/a.png").unwrap();
+        let error = build_client(&target, Some(&proxy)).await.unwrap_err();
+        assert_eq!(error.code(), ErrorCode::Forbidden);
+        let target = Url::parse("http://127.0.0.1/a.png").unwrap();
+        assert!(build_client(&target, Some(&proxy)).await.is_err());
+    }
+
+    #[test]
+    fn content_type_must_be_a_raster_image() {
+        assert_eq!(
+            allowed_content_type("image/PNG; charset=binary").as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(allowed_content_type("image/svg+xml"), None);
+        assert_eq!(allowed_content_type("text/html"), None);
+    }
+}
@@ -22,6 +22,7 @@ use std::collections::HashSet;
 
 use crate::{
     common::paginated::DataPage,
+    envelope::authentication::VerificationStatus,
     error::{code::ErrorCode, BichonResult},
     raise_error,
     store::{
@@ -65,6 +66,26 @@ pub struct EmailSearchFilter {
     pub has_attachment: Option<bool>,
     pub attachment_name: Option<String>,
     pub tags: Option<HashSet<String>>,
+    /// IMAP flags or keywords the message must carry (AND semantics), e.g.
+    /// `\Flagged`, `$Junk` or the short forms `flagged`, `seen`, `junk`.
+    pub flags: Option<HashSet<String>>,
+    /// IMAP flags or keywords the message must not carry, e.g. `seen` for unread mail.
+    pub without_flags: Option<HashSet<String>>,
+    /// `true` for messages expunged on the IMAP server, `false` for messages
+    /// still present there.
+    pub deleted_on_source: Option<bool>,
+    /// Gmail labels, e.g. `Inbox` or `Work/Projects` (matches any of them,
+    /// nested labels included).
+    pub labels: Option<HashSet<String>>,
+    /// DKIM verdict, e.g. `Fail` for messages whose DKIM signature did not
+    /// verify at ingest.
+    pub dkim: Option<VerificationStatus>,
+    /// Domain of the DKIM signature the verdict rests on.
+    pub dkim_domain: Option<String>,
+    /// S/MIME or OpenPGP signature verdict.
+    pub signature: Option<VerificationStatus>,
+    /// Email address of the S/MIME signer or OpenPGP issuer key ID.
+    pub signer: Option<String>,
     pub attachment_extension: Option<String>,
     pub attachment_category: Option<String>,
     pub attachment_content_type: Option<String>,
@@ -157,6 +178,12 @@ pub struct AttachmentSearchFilter {
 
     pub min_page_count: Option<u64>,
     pub max_page_count: Option<u64>,
+
+    /// Only files expanded from the archive attachment with this content hash.
+    pub archive_content_hash: Option<String>,
+    /// `true` for files expanded from archive attachments only, `false` for
+    /// attachments of the messages themselves only.
+    pub in_archive: Option<bool>,
 }
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
@@ -263,7 +263,7 @@ impl OAuth2Flow {
 }
 
 // Helper function to build the HTTP client
-fn build_http_client(use_proxy: Option<u64>) -> BichonResult<reqwest::Client> {
+pub(crate) fn build_http_client(use_proxy: Option<u64>) -> BichonResult<reqwest::Client> {
     if let Some(proxy_id) = use_proxy {
         let proxy = Proxy::get(proxy_id)?;
         let proxy_url = parse_proxy_url(&proxy.url)?.standard_url();
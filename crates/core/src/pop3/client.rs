@@ -0,0 +1,439 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::account::entity::Encryption;
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::imap::session::SessionStream;
+use crate::raise_error;
+use crate::utils::net::establish_tcp_connection_with_timeout;
+use crate::utils::net::establish_tls_connection;
+use crate::utils::tls::establish_tls_stream;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::debug;
+
+/// A message of the maildrop as reported by `UIDL` and `LIST`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pop3Message {
+    /// Message number, only valid within the current session.
+    pub number: u32,
+    /// Unique id assigned by the server, stable across sessions.
+    pub uidl: String,
+    /// Size in octets.
+    pub size: u64,
+}
+
+/// Same classification as the IMAP client: connection drops are network
+/// errors (and retried by the caller), anything else is a protocol failure.
+fn classify_io_error(e: &std::io::Error) -> ErrorCode {
+    use std::io::ErrorKind;
+    matches!(
+        e.kind(),
+        ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::TimedOut
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::NotConnected
+    )
+    .then_some(ErrorCode::NetworkError)
+    .unwrap_or(ErrorCode::Pop3CommandFailed)
+}
+
+/// A POP3 client (RFC 1939) covering what archiving needs: login, listing
+/// the maildrop, retrieving and deleting messages.
+pub struct Pop3Client {
+    stream: BufReader<Box<dyn SessionStream>>,
+    greeting: String,
+}
+
+impl Pop3Client {
+    /// Wraps an established stream and reads the server greeting.
+    pub(crate) async fn new(stream: Box<dyn SessionStream>) -> BichonResult<Self> {
+        let mut client = Self {
+            stream: BufReader::new(stream),
+            greeting: String::new(),
+        };
+        client.greeting = client.read_status(ErrorCode::Pop3CommandFailed).await?;
+        Ok(client)
+    }
+
+    pub async fn connection(
+        domain: &str,
+        encryption: &Encryption,
+        port: u16,
+        use_proxy: Option<u64>,
+        dangerous: bool,
+    ) -> BichonResult<Self> {
+        let address = Self::resolve_to_socket_addr(domain, port)?;
+        debug!("Attempting POP3 connection to {domain} ({address}).");
+        match encryption {
+            Encryption::Ssl => {
+                let tls_stream =
+                    establish_tls_connection(address, domain, &[], use_proxy, dangerous).await?;
+                Self::new(Box::new(tls_stream)).await
+            }
+            Encryption::StartTls => {
+                let tcp_stream = establish_tcp_connection_with_timeout(address, use_proxy).await?;
+                let client = Self::new(Box::new(tcp_stream)).await?;
+                client.stls(domain, dangerous).await
+            }
+            Encryption::None => {
+                let tcp_stream = establish_tcp_connection_with_timeout(address, use_proxy).await?;
+                Self::new(Box::new(tcp_stream)).await
+            }
+        }
+    }
+
+    /// Upgrades the connection to TLS with `STLS` (RFC 2595).
+    async fn stls(mut self, domain: &str, dangerous: bool) -> BichonResult<Self> {
+        self.command("STLS", ErrorCode::Pop3CommandFailed)
+            .await
+            .map_err(|e| {
+                raise_error!(
+                    format!("STLS command failed: {:#?}", e),
+                    ErrorCode::Pop3CommandFailed
+                )
+            })?;
+        let greeting = self.greeting;
+        let stream = self.stream.into_inner();
+        let tls_stream = establish_tls_stream(domain, &[], stream, dangerous).await?;
+        Ok(Self {
+            stream: BufReader::new(Box::new(tls_stream)),
+            greeting,
+        })
+    }
+
+    /// The `<...>` timestamp of the greeting that `APOP` digests, if the
+    /// server offered one.
+    pub fn apop_timestamp(&self) -> Option<&str> {
+        let start = self.greeting.find('<')?;
+        let end = start + self.greeting[start..].find('>')?;
+        Some(&self.greeting[start..=end])
+    }
+
+    /// Logs in with `USER`/`PASS`.
+    pub async fn login(&mut self, username: &str, password: &str) -> BichonResult<()> {
+        self.command(
+            &format!("USER {username}"),
+            ErrorCode::Pop3AuthenticationFailed,
+        )
+        .await?;
+        self.command(
+            &format!("PASS {password}"),
+            ErrorCode::Pop3AuthenticationFailed,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Logs in with `APOP`, which only sends a digest of the password.
+    pub async fn apop(&mut self, username: &str, password: &str) -> BichonResult<()> {
+        let timestamp = self.apop_timestamp().ok_or_else(|| {
+            raise_error!(
+                "The POP3 server does not offer APOP (no timestamp in its greeting); use USER/PASS login instead.".into(),
+                ErrorCode::Pop3AuthenticationFailed
+            )
+        })?;
+        let digest = apop_digest(timestamp, password);
+        self.command(
+            &format!("APOP {username} {digest}"),
+            ErrorCode::Pop3AuthenticationFailed,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the maildrop, joining `UIDL` and `LIST` on the message number.
+    pub async fn list_messages(&mut self) -> BichonResult<Vec<Pop3Message>> {
+        self.command("UIDL", ErrorCode::Pop3CommandFailed)
+            .await
+            .map_err(|e| {
+                raise_error!(
+                    format!(
+                        "The POP3 server does not support UIDL, which incremental download relies on: {:#?}",
+                        e
+                    ),
+                    ErrorCode::Pop3CommandFailed
+                )
+            })?;
+        let uidls = parse_listing(&self.read_multiline().await?, |v| Some(v.to_string()))?;
+
+        self.command("LIST", ErrorCode::Pop3CommandFailed).await?;
+        let sizes: HashMap<u32, u64> =
+            parse_listing(&self.read_multiline().await?, |v| v.parse().ok())?
+                .into_iter()
+                .collect();
+
+        Ok(uidls
+            .into_iter()
+            .map(|(number, uidl)| Pop3Message {
+                number,
+                size: sizes.get(&number).copied().unwrap_or(0),
+                uidl,
+            })
+            .collect())
+    }
+
+    /// Retrieves the raw message, with the byte-stuffing removed.
+    pub async fn retr(&mut self, number: u32) -> BichonResult<Vec<u8>> {
+        self.command(&format!("RETR {number}"), ErrorCode::Pop3CommandFailed)
+            .await?;
+        self.read_multiline().await
+    }
+
+    /// Marks a message as deleted. The server only removes it once the
+    /// session ends with [`Pop3Client::quit`].
+    pub async fn dele(&mut self, number: u32) -> BichonResult<()> {
+        self.command(&format!("DELE {number}"), ErrorCode::Pop3CommandFailed)
+            .await?;
+        Ok(())
+    }
+
+    /// Ends the session and commits any `DELE`.
+    pub async fn quit(mut self) -> BichonResult<()> {
+        self.command("QUIT", ErrorCode::Pop3CommandFailed).await?;
+        Ok(())
+    }
+
+    /// Sends a command and reads its status line. `-ERR` is reported with
+    /// `failure`, so login errors surface as authentication failures.
+    async fn command(&mut self, line: &str, failure: ErrorCode) -> BichonResult<String> {
+        if line.contains(['\r', '\n']) {
+            return Err(raise_error!(
+                "POP3 command arguments must not contain line breaks".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), classify_io_error(&e)))?;
+        stream
+            .flush()
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), classify_io_error(&e)))?;
+        self.read_status(failure).await
+    }
+
+    async fn read_status(&mut self, failure: ErrorCode) -> BichonResult<String> {
+        let line = self.read_line().await?;
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end();
+        if let Some(text) = line.strip_prefix("+OK") {
+            Ok(text.trim().to_string())
+        } else if let Some(text) = line.strip_prefix("-ERR") {
+            Err(raise_error!(
+                format!("POP3 server replied: -ERR {}", text.trim()),
+                failure
+            ))
+        } else {
+            Err(raise_error!(
+                format!("Unexpected POP3 response: {line}"),
+                ErrorCode::Pop3CommandFailed
+            ))
+        }
+    }
+
+    /// Reads a multi-line response up to the terminating `.` line.
+    async fn read_multiline(&mut self) -> BichonResult<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            if line == b".\r\n" || line == b".\n" {
+                return Ok(data);
+            }
+            data.extend_from_slice(line.strip_prefix(b".").unwrap_or(&line));
+        }
+    }
+
+    async fn read_line(&mut self) -> BichonResult<Vec<u8>> {
+        let mut line = Vec::new();
+        let read = self
+            .stream
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), classify_io_error(&e)))?;
+        if read == 0 {
+            return Err(raise_error!(
+                "POP3 server closed the connection".into(),
+                ErrorCode::NetworkError
+            ));
+        }
+        Ok(line)
+    }
+
+    fn resolve_to_socket_addr(domain: &str, port: u16) -> BichonResult<SocketAddr> {
+        if domain.is_empty() || domain.contains(|c: char| !c.is_ascii() && c != '.') {
+            return Err(raise_error!(
+                "Invalid domain format".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        format!("{}:{}", domain, port)
+            .to_socket_addrs()
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::NetworkError))?
+            .next()
+            .ok_or_else(|| {
+                raise_error!("Unable to resolve address".into(), ErrorCode::NetworkError)
+            })
+    }
+}
+
+/// The `APOP` digest: MD5 of the greeting timestamp followed by the password.
+pub fn apop_digest(timestamp: &str, password: &str) -> String {
+    format!("{:x}", md5::compute(format!("{timestamp}{password}")))
+}
+
+/// Parses the `<number> <value>` lines of a `UIDL` or `LIST` response.
+fn parse_listing<T>(data: &[u8], value: impl Fn(&str) -> Option<T>) -> BichonResult<Vec<(u32, T)>> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            let number = parts.next().and_then(|n| n.parse().ok());
+            match (number, parts.next().and_then(&value)) {
+                (Some(number), Some(value)) => Ok((number, value)),
+                _ => Err(raise_error!(
+                    format!("Malformed POP3 listing line: {line}"),
+                    ErrorCode::Pop3CommandFailed
+                )),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imap::mock_server::minimal_eml;
+    use crate::pop3::mock_server::{MockPop3Server, MockPop3ServerHandle};
+    use tokio::net::TcpStream;
+    use tokio_io_timeout::TimeoutStream;
+
+    async fn connect(handle: &MockPop3ServerHandle) -> Pop3Client {
+        let tcp = TcpStream::connect((handle.host(), handle.port()))
+            .await
+            .unwrap();
+        let stream: Box<dyn SessionStream> = Box::new(Box::pin(TimeoutStream::new(tcp)));
+        Pop3Client::new(stream).await.unwrap()
+    }
+
+    #[test]
+    fn apop_digest_matches_rfc_example() {
+        // RFC 1939, section 7.
+        assert_eq!(
+            apop_digest("<1896.697170952@dbc.mtview.ca.us>", "tanstaaf"),
+            "c4c9334bac560ecc979e58001b3e22fb"
+        );
+    }
+
+    #[test]
+    fn malformed_listing_is_rejected() {
+        assert!(parse_listing(b"1 abc\r\n2\r\n", |v| Some(v.to_string())).is_err());
+        assert_eq!(
+            parse_listing(b"1 120\r\n2 300\r\n", |v| v.parse::<u64>().ok()).unwrap(),
+            vec![(1, 120), (2, 300)]
+        );
+    }
+
+    #[tokio::test]
+    async fn user_pass_login_lists_maildrop() {
+        let first = minimal_eml("first", "m1@example.com");
+        let handle = MockPop3Server::new()
+            .user("alice", "secret")
+            .message("uid-a", first.clone())
+            .message("uid-b", minimal_eml("second", "m2@example.com"))
+            .start()
+            .await;
+
+        let mut client = connect(&handle).await;
+        client.login("alice", "secret").await.unwrap();
+        let messages = client.list_messages().await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].number, 1);
+        assert_eq!(messages[0].uidl, "uid-a");
+        assert_eq!(messages[0].size, first.len() as u64);
+        assert_eq!(messages[1].uidl, "uid-b");
+        client.quit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn wrong_password_is_an_authentication_error() {
+        let handle = MockPop3Server::new().user("alice", "secret").start().await;
+        let mut client = connect(&handle).await;
+        let err = client.login("alice", "nope").await.unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Pop3AuthenticationFailed);
+    }
+
+    #[tokio::test]
+    async fn apop_login_uses_greeting_timestamp() {
+        let handle = MockPop3Server::new().user("alice", "secret").start().await;
+        let mut client = connect(&handle).await;
+        assert!(client.apop_timestamp().is_some());
+        client.apop("alice", "secret").await.unwrap();
+        assert!(client.list_messages().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn apop_without_timestamp_fails() {
+        let handle = MockPop3Server::new()
+            .greeting("+OK plain server")
+            .user("alice", "secret")
+            .start()
+            .await;
+        let mut client = connect(&handle).await;
+        let err = client.apop("alice", "secret").await.unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Pop3AuthenticationFailed);
+    }
+
+    #[tokio::test]
+    async fn retr_removes_byte_stuffing() {
+        let body = b"Subject: dots\r\n\r\n.leading dot\r\n..two dots\r\nend\r\n".to_vec();
+        let handle = MockPop3Server::new()
+            .user("alice", "secret")
+            .message("uid-a", body.clone())
+            .start()
+            .await;
+        let mut client = connect(&handle).await;
+        client.login("alice", "secret").await.unwrap();
+        assert_eq!(client.retr(1).await.unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn dele_is_committed_on_quit() {
+        let handle = MockPop3Server::new()
+            .user("alice", "secret")
+            .message("uid-a", minimal_eml("first", "m1@example.com"))
+            .message("uid-b", minimal_eml("second", "m2@example.com"))
+            .start()
+            .await;
+
+        let mut client = connect(&handle).await;
+        client.login("alice", "secret").await.unwrap();
+        client.dele(1).await.unwrap();
+        assert_eq!(handle.uidls(), vec!["uid-a", "uid-b"]);
+        client.quit().await.unwrap();
+        assert_eq!(handle.uidls(), vec!["uid-b"]);
+    }
+}
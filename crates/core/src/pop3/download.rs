@@ -0,0 +1,343 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    account::{
+        migration::{AccountModel, AccountType},
+        state::{DownloadState, DownloadStatus, FolderStatus, TriggerType},
+    },
+    cache::imap::{
+        download::download_type::{decide_next_download_task, DownloadTask},
+        mailbox::MailBox,
+    },
+    envelope::extractor::extract_envelope_from_eml,
+    error::{code::ErrorCode, BichonResult},
+    imap::executor::DEFAULT_MAX_EMAIL_SIZE,
+    pop3::{client::Pop3Message, manager::Pop3ConnectionManager, uidl::Pop3Uidl},
+    raise_error,
+    store::tantivy::envelope::ENVELOPE_MANAGER,
+    utils::{compute_content_hash, create_hash},
+};
+use std::collections::HashSet;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// POP3 has a single maildrop; it is archived as the account's `INBOX`.
+pub const POP3_MAILBOX: &str = "INBOX";
+
+/// What one download run does with the maildrop.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct DownloadPlan {
+    /// New messages to retrieve and archive.
+    pub fetch: Vec<Pop3Message>,
+    /// Message numbers of archived messages to delete from the server.
+    pub delete: Vec<u32>,
+    /// Known UIDLs that have disappeared from the server.
+    pub forget: Vec<String>,
+    /// New messages skipped for exceeding the size limit.
+    pub oversized: usize,
+}
+
+/// Compares the maildrop with the UIDLs archived so far.
+///
+/// When `leave_on_server` is off, archived messages are deleted from the
+/// server once `committed` confirms their envelope is in the index. A
+/// message retrieved in this run is only queued for indexing, so it is
+/// deleted on a later run; until then a crash cannot lose it. Messages the
+/// archive rules filtered out never reach the index and stay on the server.
+pub fn plan_download(
+    messages: &[Pop3Message],
+    known: &HashSet<String>,
+    committed: &HashSet<String>,
+    leave_on_server: bool,
+    max_size: u64,
+) -> DownloadPlan {
+    let mut plan = DownloadPlan::default();
+    for message in messages {
+        if known.contains(&message.uidl) {
+            if !leave_on_server && committed.contains(&message.uidl) {
+                plan.delete.push(message.number);
+            }
+        } else if message.size == 0 || message.size <= max_size {
+            plan.fetch.push(message.clone());
+        } else {
+            plan.oversized += 1;
+        }
+    }
+    let on_server: HashSet<&str> = messages.iter().map(|m| m.uidl.as_str()).collect();
+    plan.forget = known
+        .iter()
+        .filter(|uidl| !on_server.contains(uidl.as_str()))
+        .cloned()
+        .collect();
+    plan
+}
+
+pub async fn process_pop3_download(
+    account: &AccountModel,
+    token: CancellationToken,
+    trigger_type: TriggerType,
+) -> BichonResult<()> {
+    assert_eq!(account.account_type, AccountType::POP3);
+    let start_time = Instant::now();
+    let account_id = account.id;
+    let download_task = decide_next_download_task(account, trigger_type).await?;
+    if matches!(download_task, DownloadTask::Idle) {
+        return Ok(());
+    }
+
+    match download_maildrop(account, token).await {
+        Ok(true) => DownloadState::update_session_status(
+            account_id,
+            DownloadStatus::Cancelled,
+            Some("User stopped or system shutdown".to_string()),
+        )?,
+        Ok(false) => {
+            DownloadState::update_session_status(account_id, DownloadStatus::Success, None)?
+        }
+        Err(e) => {
+            let err_msg = format!("POP3 download interrupted: {:#?}", e);
+            DownloadState::update_folder_progress(
+                account_id,
+                POP3_MAILBOX.into(),
+                0,
+                0,
+                FolderStatus::Failed,
+                Some(err_msg.clone()),
+            )?;
+            DownloadState::append_session_error(account_id, err_msg.clone())?;
+            DownloadState::update_session_status(
+                account_id,
+                DownloadStatus::Failed,
+                Some(err_msg),
+            )?;
+        }
+    }
+
+    debug!(
+        "Account{{{}}} POP3 download completed: {} seconds elapsed.",
+        account.email,
+        start_time.elapsed().as_secs()
+    );
+    Ok(())
+}
+
+/// Downloads new messages of the maildrop. Returns whether the run was
+/// cancelled.
+///
+/// Each UIDL is recorded as soon as its message is archived, so an
+/// interrupted run resumes where it stopped. A message that fails to
+/// archive is not recorded and is retried on the next run.
+async fn download_maildrop(account: &AccountModel, token: CancellationToken) -> BichonResult<bool> {
+    let account_id = account.id;
+    let config = account.pop3.as_ref().ok_or_else(|| {
+        raise_error!(
+            "POP3 account has no server configuration".into(),
+            ErrorCode::MissingConfiguration
+        )
+    })?;
+    let leave_on_server = config.leave_on_server();
+
+    let mut client = Pop3ConnectionManager::build(account).await?;
+    let messages = client.list_messages().await?;
+    let mailbox_id = ensure_mailbox(account_id, messages.len() as u32)?;
+
+    let archived = Pop3Uidl::list_all(account_id)?;
+    let known: HashSet<String> = archived.iter().map(|u| u.uidl.clone()).collect();
+    let mut committed = HashSet::new();
+    if !leave_on_server {
+        let on_server: HashSet<&str> = messages.iter().map(|m| m.uidl.as_str()).collect();
+        for archived in archived
+            .iter()
+            .filter(|u| on_server.contains(u.uidl.as_str()))
+        {
+            if ENVELOPE_MANAGER.mailbox_contains_content_hash(
+                account_id,
+                mailbox_id,
+                &archived.content_hash,
+            )? {
+                committed.insert(archived.uidl.clone());
+            }
+        }
+    }
+    let plan = plan_download(
+        &messages,
+        &known,
+        &committed,
+        leave_on_server,
+        account
+            .max_email_size_bytes
+            .unwrap_or(DEFAULT_MAX_EMAIL_SIZE),
+    );
+    if plan.oversized > 0 {
+        warn!(
+            account_id,
+            count = plan.oversized,
+            "Skipping oversized POP3 messages"
+        );
+    }
+    Pop3Uidl::forget(account_id, &plan.forget)?;
+    for number in &plan.delete {
+        client.dele(*number).await?;
+    }
+
+    let planned = plan.fetch.len() as u64;
+    DownloadState::update_folder_progress(
+        account_id,
+        POP3_MAILBOX.into(),
+        planned,
+        0,
+        FolderStatus::Pending,
+        None,
+    )?;
+
+    let mut processed = 0u64;
+    let mut failed = 0u64;
+    let mut cancelled = false;
+    for message in &plan.fetch {
+        if token.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        let body = client.retr(message.number).await?;
+        match extract_envelope_from_eml(&body, account_id, mailbox_id).await {
+            // Not deleted yet: the envelope is only queued for indexing.
+            Ok(()) => Pop3Uidl::record(account_id, &message.uidl, &compute_content_hash(&body))?,
+            Err(e) => {
+                failed += 1;
+                let err_msg = format!(
+                    "Failed to archive POP3 message {} (UIDL {}): {:#?}",
+                    message.number, message.uidl, e
+                );
+                warn!(account_id, "{}", err_msg);
+                DownloadState::append_session_error(account_id, err_msg)?;
+            }
+        }
+        processed += 1;
+        DownloadState::update_folder_progress(
+            account_id,
+            POP3_MAILBOX.into(),
+            planned,
+            processed,
+            FolderStatus::Downloading,
+            None,
+        )?;
+    }
+
+    // Deletions only take effect once the server acknowledges QUIT.
+    client.quit().await?;
+
+    let status = if cancelled {
+        FolderStatus::Cancelled
+    } else if failed > 0 {
+        FolderStatus::Failed
+    } else {
+        FolderStatus::Success
+    };
+    DownloadState::update_folder_progress(
+        account_id,
+        POP3_MAILBOX.into(),
+        planned,
+        processed,
+        status,
+        None,
+    )?;
+    Ok(cancelled)
+}
+
+/// Creates the mailbox row messages are archived into, and keeps its
+/// message count in line with the maildrop.
+fn ensure_mailbox(account_id: u64, exists: u32) -> BichonResult<u64> {
+    let mailbox_id = create_hash(account_id, POP3_MAILBOX);
+    let mut mailbox = MailBox::find_mailbox(account_id, mailbox_id)?.unwrap_or_else(|| MailBox {
+        id: mailbox_id,
+        account_id,
+        name: POP3_MAILBOX.into(),
+        delimiter: Some("/".to_string()),
+        attributes: vec![],
+        exists: 0,
+        unseen: None,
+        uid_next: None,
+        uid_validity: None,
+        highest_uid: None,
+        highest_modseq: None,
+    });
+    mailbox.exists = exists;
+    MailBox::batch_upsert(&[mailbox])?;
+    Ok(mailbox_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(number: u32, uidl: &str, size: u64) -> Pop3Message {
+        Pop3Message {
+            number,
+            uidl: uidl.into(),
+            size,
+        }
+    }
+
+    fn known(uidls: &[&str]) -> HashSet<String> {
+        uidls.iter().map(|u| u.to_string()).collect()
+    }
+
+    #[test]
+    fn only_unknown_messages_are_fetched() {
+        let messages = [message(1, "a", 10), message(2, "b", 10)];
+        let plan = plan_download(&messages, &known(&["a"]), &known(&["a"]), true, 100);
+        assert_eq!(plan.fetch, vec![message(2, "b", 10)]);
+        assert!(plan.delete.is_empty());
+        assert!(plan.forget.is_empty());
+    }
+
+    #[test]
+    fn archived_messages_are_deleted_when_not_left_on_server() {
+        let messages = [message(1, "a", 10), message(2, "b", 10)];
+        let plan = plan_download(&messages, &known(&["a"]), &known(&["a"]), false, 100);
+        assert_eq!(plan.fetch, vec![message(2, "b", 10)]);
+        assert_eq!(plan.delete, vec![1]);
+    }
+
+    #[test]
+    fn archived_messages_are_kept_until_committed() {
+        let messages = [message(1, "a", 10), message(2, "b", 10)];
+        let plan = plan_download(&messages, &known(&["a", "b"]), &known(&["a"]), false, 100);
+        assert!(plan.fetch.is_empty());
+        assert_eq!(plan.delete, vec![1]);
+    }
+
+    #[test]
+    fn vanished_uidls_are_forgotten() {
+        let messages = [message(1, "b", 10)];
+        let plan = plan_download(&messages, &known(&["a", "b"]), &known(&["b"]), true, 100);
+        assert!(plan.fetch.is_empty());
+        assert_eq!(plan.forget, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn oversized_messages_are_skipped_and_kept() {
+        let messages = [message(1, "big", 500), message(2, "small", 50)];
+        let plan = plan_download(&messages, &HashSet::new(), &HashSet::new(), false, 100);
+        assert_eq!(plan.fetch, vec![message(2, "small", 50)]);
+        assert_eq!(plan.oversized, 1);
+        assert!(plan.delete.is_empty());
+    }
+}
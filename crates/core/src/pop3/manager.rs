@@ -0,0 +1,112 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::account::entity::{Pop3AuthMethod, Pop3Config};
+use crate::account::migration::{AccountModel, AccountType};
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::pop3::client::Pop3Client;
+use crate::{decrypt, raise_error};
+use tracing::{error, warn};
+
+pub struct Pop3ConnectionManager;
+
+impl Pop3ConnectionManager {
+    fn config(account: &AccountModel) -> BichonResult<&Pop3Config> {
+        assert_eq!(account.account_type, AccountType::POP3);
+        account.pop3.as_ref().ok_or_else(|| {
+            raise_error!(
+                "POP3 account has no server configuration".into(),
+                ErrorCode::MissingConfiguration
+            )
+        })
+    }
+
+    async fn authenticate(client: &mut Pop3Client, account: &AccountModel) -> BichonResult<()> {
+        let pop3 = Self::config(account)?;
+        let login_name = account.login_name.clone().unwrap_or(account.email.clone());
+        let password = pop3.password.as_ref().ok_or_else(|| {
+            raise_error!(
+                "POP3 password not set".into(),
+                ErrorCode::MissingConfiguration
+            )
+        })?;
+        let password = decrypt!(password)?;
+        let result = match pop3.auth_method {
+            Pop3AuthMethod::User => client.login(&login_name, &password).await,
+            Pop3AuthMethod::Apop => client.apop(&login_name, &password).await,
+        };
+        result.map_err(|e| {
+            error!(
+                "POP3 {:?} auth failed for username '{}': {}",
+                pop3.auth_method, login_name, e
+            );
+            e
+        })
+    }
+
+    /// Connects and logs in, retrying connection-level network errors.
+    pub async fn build(account: &AccountModel) -> BichonResult<Pop3Client> {
+        let pop3 = Self::config(account)?;
+
+        let mut client = None;
+        for attempt in 0..3u32 {
+            match Pop3Client::connection(
+                &pop3.host,
+                &pop3.encryption,
+                pop3.port,
+                pop3.use_proxy,
+                account.use_dangerous,
+            )
+            .await
+            {
+                Ok(c) => {
+                    client = Some(c);
+                    break;
+                }
+                Err(error) if error.code() == ErrorCode::NetworkError && attempt < 2 => {
+                    warn!(
+                        "POP3 connection attempt {}/3 to {} failed (network error), retrying...",
+                        attempt + 1,
+                        account.email
+                    );
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+                Err(error) => {
+                    error!(
+                        "Failed to create POP3 {}'s client: {:#?}",
+                        account.email, error
+                    );
+                    return Err(error);
+                }
+            }
+        }
+
+        let mut client = client.ok_or_else(|| {
+            raise_error!(
+                format!(
+                    "Failed to create POP3 {}'s client after 3 attempts",
+                    account.email
+                ),
+                ErrorCode::NetworkError
+            )
+        })?;
+        Self::authenticate(&mut client, account).await?;
+        Ok(client)
+    }
+}
@@ -0,0 +1,334 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal in-process POP3 server for integration testing.
+//!
+//! Each instance listens on a random localhost port and serves a fixed
+//! maildrop to a single user. It understands the commands the client sends
+//! (`USER`, `PASS`, `APOP`, `UIDL`, `LIST`, `RETR`, `DELE`, `RSET`, `NOOP`,
+//! `QUIT`) and, like a real server, only removes messages marked with `DELE`
+//! when the session ends with `QUIT`.
+//!
+//! # Example
+//! ```ignore
+//! let server = MockPop3Server::new()
+//!     .user("alice", "secret")
+//!     .message("uid-1", minimal_eml("hello", "m1@example.com"))
+//!     .start()
+//!     .await;
+//!
+//! // connect to server.host():server.port() with Encryption::None
+//! assert_eq!(server.uidls(), vec!["uid-1"]);
+//! ```
+
+use crate::pop3::client::apop_digest;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+type Maildrop = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+pub struct MockPop3Server {
+    greeting: String,
+    username: String,
+    password: String,
+    messages: Vec<(String, Vec<u8>)>,
+}
+
+impl MockPop3Server {
+    pub fn new() -> Self {
+        Self {
+            // The APOP timestamp from the RFC 1939 example.
+            greeting: "+OK Mock POP3 server ready <1896.697170952@dbc.mtview.ca.us>".into(),
+            username: "user".into(),
+            password: "password".into(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Set the greeting sent after connection, without the trailing CRLF.
+    pub fn greeting(mut self, greeting: impl Into<String>) -> Self {
+        self.greeting = greeting.into();
+        self
+    }
+
+    /// Set the only credentials the server accepts.
+    pub fn user(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = username.into();
+        self.password = password.into();
+        self
+    }
+
+    /// Append a message to the maildrop. Message numbers follow insertion order.
+    pub fn message(mut self, uidl: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        self.messages.push((uidl.into(), body.into()));
+        self
+    }
+
+    /// Start the server on a random port.
+    pub async fn start(self) -> MockPop3ServerHandle {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local_addr");
+        let maildrop: Maildrop = Arc::new(Mutex::new(self.messages.clone()));
+
+        let server = Arc::new(self);
+        let shared = maildrop.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let srv = server.clone();
+                let maildrop = shared.clone();
+                tokio::spawn(async move {
+                    srv.handle_connection(stream, maildrop).await;
+                });
+            }
+        });
+
+        MockPop3ServerHandle { addr, maildrop }
+    }
+
+    async fn handle_connection(&self, mut stream: TcpStream, maildrop: Maildrop) {
+        let (reader, mut writer) = stream.split();
+        let mut reader = BufReader::new(reader);
+        if writer
+            .write_all(format!("{}\r\n", self.greeting).as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut session = Session::default();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let (command, arg) = match line.trim_end().split_once(' ') {
+                Some((command, arg)) => (command.to_ascii_uppercase(), arg),
+                None => (line.trim_end().to_ascii_uppercase(), ""),
+            };
+            let (reply, close) = self.reply(&mut session, &maildrop, &command, arg);
+            if writer.write_all(&reply).await.is_err() || close {
+                break;
+            }
+        }
+    }
+
+    fn reply(
+        &self,
+        session: &mut Session,
+        maildrop: &Maildrop,
+        command: &str,
+        arg: &str,
+    ) -> (Vec<u8>, bool) {
+        if command == "QUIT" {
+            if session.messages.is_some() {
+                let deleted = &session.deleted;
+                maildrop
+                    .lock()
+                    .unwrap()
+                    .retain(|(uidl, _)| !deleted.contains(uidl));
+            }
+            return (ok("bye"), true);
+        }
+
+        let Some(messages) = &session.messages else {
+            let authenticated = match command {
+                "USER" => {
+                    session.user = Some(arg.to_string());
+                    return (ok("send PASS"), false);
+                }
+                "PASS" => session.user.as_deref() == Some(&self.username) && arg == self.password,
+                "APOP" => arg.split_once(' ').is_some_and(|(name, digest)| {
+                    let timestamp = self
+                        .greeting
+                        .find('<')
+                        .and_then(|start| {
+                            self.greeting[start..]
+                                .find('>')
+                                .map(|end| &self.greeting[start..=start + end])
+                        })
+                        .unwrap_or_default();
+                    name == self.username
+                        && !timestamp.is_empty()
+                        && digest == apop_digest(timestamp, &self.password)
+                }),
+                _ => return (err("not authenticated"), false),
+            };
+            if !authenticated {
+                return (err("invalid credentials"), false);
+            }
+            session.messages = Some(maildrop.lock().unwrap().clone());
+            return (ok("maildrop locked"), false);
+        };
+
+        let live = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, (uidl, _))| !session.deleted.contains(uidl))
+            .map(|(i, (uidl, body))| (i as u32 + 1, uidl, body));
+        let selected = arg
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| messages.get(i))
+            .filter(|(uidl, _)| !session.deleted.contains(uidl));
+
+        match command {
+            "NOOP" => (ok(""), false),
+            "UIDL" => (
+                listing(live.map(|(n, uidl, _)| format!("{n} {uidl}"))),
+                false,
+            ),
+            "LIST" => (
+                listing(live.map(|(n, _, body)| format!("{n} {}", body.len()))),
+                false,
+            ),
+            "RETR" => match selected {
+                Some((_, body)) => {
+                    let mut out = ok("message follows");
+                    out.extend_from_slice(&dot_stuff(body));
+                    out.extend_from_slice(b".\r\n");
+                    (out, false)
+                }
+                None => (err("no such message"), false),
+            },
+            "DELE" => match selected {
+                Some((uidl, _)) => {
+                    session.deleted.insert(uidl.clone());
+                    (ok("marked"), false)
+                }
+                None => (err("no such message"), false),
+            },
+            "RSET" => {
+                session.deleted.clear();
+                (ok(""), false)
+            }
+            _ => (err("unknown command"), false),
+        }
+    }
+}
+
+impl Default for MockPop3Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    user: Option<String>,
+    /// Snapshot of the maildrop, taken once the user is authenticated.
+    messages: Option<Vec<(String, Vec<u8>)>>,
+    deleted: HashSet<String>,
+}
+
+/// Handle to a running mock POP3 server.
+pub struct MockPop3ServerHandle {
+    addr: SocketAddr,
+    maildrop: Maildrop,
+}
+
+impl MockPop3ServerHandle {
+    pub fn host(&self) -> String {
+        self.addr.ip().to_string()
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// UIDLs of the messages still in the maildrop.
+    pub fn uidls(&self) -> Vec<String> {
+        self.maildrop
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(uidl, _)| uidl.clone())
+            .collect()
+    }
+}
+
+fn ok(text: &str) -> Vec<u8> {
+    format!("+OK {text}\r\n").into_bytes()
+}
+
+fn err(text: &str) -> Vec<u8> {
+    format!("-ERR {text}\r\n").into_bytes()
+}
+
+fn listing(lines: impl Iterator<Item = String>) -> Vec<u8> {
+    let mut out = ok("listing follows");
+    for line in lines {
+        out.extend_from_slice(line.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b".\r\n");
+    out
+}
+
+/// Doubles the leading dot of every line, as RFC 1939 requires for
+/// multi-line responses. The body is expected to end with CRLF.
+fn dot_stuff(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut line_start = true;
+    for &b in body {
+        if line_start && b == b'.' {
+            out.push(b'.');
+        }
+        out.push(b);
+        line_start = b == b'\n';
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_stuffing_doubles_leading_dots_only() {
+        assert_eq!(
+            dot_stuff(b".a\r\nb.c\r\n..d\r\n"),
+            b"..a\r\nb.c\r\n...d\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_greeting_and_unauthenticated_commands() {
+        let handle = MockPop3Server::new().start().await;
+        let mut stream = TcpStream::connect((handle.host(), handle.port()))
+            .await
+            .unwrap();
+        let (reader, mut writer) = stream.split();
+        let mut reader = BufReader::new(reader);
+
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("+OK"));
+
+        writer.write_all(b"UIDL\r\n").await.unwrap();
+        line.clear();
+        reader.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("-ERR"));
+    }
+}
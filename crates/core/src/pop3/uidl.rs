@@ -0,0 +1,92 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    database::{batch_delete_impl, filter_impl, manager::DB_MANAGER, upsert_impl, MemDbModel},
+    error::BichonResult,
+    utc_now,
+    utils::create_hash,
+};
+use serde::{Deserialize, Serialize};
+
+/// A message already archived from a POP3 maildrop, remembered by its
+/// `UIDL` so the next download skips it.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Pop3Uidl {
+    pub id: u64,
+    pub account_id: u64,
+    /// Unique id the server assigned to the message.
+    pub uidl: String,
+    /// Content hash of the retrieved message, used to confirm it reached
+    /// the index before it is deleted from the server.
+    #[serde(default)]
+    pub content_hash: String,
+    pub downloaded_at: i64,
+}
+
+impl MemDbModel for Pop3Uidl {
+    fn collection() -> &'static str {
+        "pop3_uidls"
+    }
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl Pop3Uidl {
+    pub fn list_all(account_id: u64) -> BichonResult<Vec<Pop3Uidl>> {
+        filter_impl::<Pop3Uidl, _>(DB_MANAGER.db(), move |u| u.account_id == account_id)
+    }
+
+    pub fn record(account_id: u64, uidl: &str, content_hash: &str) -> BichonResult<()> {
+        upsert_impl(
+            DB_MANAGER.db(),
+            Pop3Uidl {
+                id: create_hash(account_id, uidl),
+                account_id,
+                uidl: uidl.to_string(),
+                content_hash: content_hash.to_string(),
+                downloaded_at: utc_now!(),
+            },
+        )
+    }
+
+    /// Drops UIDLs of messages that are no longer on the server.
+    pub fn forget(account_id: u64, uidls: &[String]) -> BichonResult<()> {
+        if uidls.is_empty() {
+            return Ok(());
+        }
+        let keys = uidls
+            .iter()
+            .map(|uidl| create_hash(account_id, uidl).to_string())
+            .collect();
+        batch_delete_impl::<Pop3Uidl>(DB_MANAGER.db(), keys)?;
+        Ok(())
+    }
+
+    pub fn clean(account_id: u64) -> BichonResult<()> {
+        let keys: Vec<String> = Self::list_all(account_id)?
+            .iter()
+            .map(|u| u.id.to_string())
+            .collect();
+        if !keys.is_empty() {
+            batch_delete_impl::<Pop3Uidl>(DB_MANAGER.db(), keys)?;
+        }
+        Ok(())
+    }
+}
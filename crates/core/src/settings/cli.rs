@@ -165,6 +165,22 @@ pub struct Settings {
     )]
     pub bichon_webui_token_expiration_hours: u32,
 
+    #[clap(
+        long,
+        default_value = "10",
+        env,
+        help = "Failed logins after which a username is temporarily locked out (an IP address after five times as many)"
+    )]
+    pub bichon_login_max_failures: u32,
+
+    #[clap(
+        long,
+        default_value = "15",
+        env,
+        help = "How long a login lockout lasts, in minutes"
+    )]
+    pub bichon_login_lockout_minutes: u32,
+
     #[clap(
         long,
         env,
@@ -413,6 +429,90 @@ pub struct Settings {
         help = "Audit log retention period in days (0 disables cleanup). Pro edition only."
     )]
     pub bichon_audit_retention_days: u64,
+
+    /// Where email and attachment blobs are stored: `local` keeps them under
+    /// the data directory, `s3` in an S3-compatible bucket configured by the
+    /// `bichon_s3_*` settings. Use the admin tool to copy an existing local
+    /// store before switching.
+    #[clap(
+        long,
+        default_value = "local",
+        env,
+        help = "Blob storage backend: local or s3"
+    )]
+    pub bichon_blob_backend: BlobBackend,
+
+    /// S3 endpoint URL, e.g. `https://s3.eu-west-1.amazonaws.com` or `http://minio:9000`.
+    #[clap(long, env, help = "S3 endpoint URL for the s3 blob backend")]
+    pub bichon_s3_endpoint: Option<String>,
+
+    #[clap(
+        long,
+        default_value = "us-east-1",
+        env,
+        help = "S3 region used for request signing"
+    )]
+    pub bichon_s3_region: String,
+
+    #[clap(long, env, help = "S3 bucket for the s3 blob backend")]
+    pub bichon_s3_bucket: Option<String>,
+
+    #[clap(long, env, help = "S3 access key ID")]
+    pub bichon_s3_access_key: Option<String>,
+
+    #[clap(long, env, help = "S3 secret access key")]
+    pub bichon_s3_secret_key: Option<String>,
+
+    /// Key prefix inside the bucket, so one bucket can hold several archives.
+    #[clap(long, env, help = "Key prefix for objects in the S3 bucket")]
+    pub bichon_s3_prefix: Option<String>,
+
+    /// Address the bucket as `endpoint/bucket` instead of `bucket.endpoint`.
+    /// Most self-hosted stores (MinIO, Ceph) expect path-style requests.
+    #[clap(
+        long,
+        default_value = "true",
+        env,
+        help = "Use path-style S3 requests instead of virtual-hosted style"
+    )]
+    pub bichon_s3_path_style: bool,
+
+    /// Size in MB of the in-memory cache for blobs read from S3 (default: 256 MB).
+    #[clap(
+        long,
+        default_value = "256",
+        env,
+        help = "Size in MB of the read cache for the s3 blob backend"
+    )]
+    pub bichon_s3_cache_mb: u64,
+
+    /// Proxy profile used when fetching remote email content for the WebUI.
+    #[clap(
+        long,
+        env,
+        help = "ID of the proxy profile used to fetch remote images shown in the WebUI (default: direct connection)"
+    )]
+    pub bichon_remote_content_proxy: Option<u64>,
+
+    /// Largest remote resource the content proxy will fetch, in MB (default: 10 MB).
+    #[clap(
+        long,
+        default_value = "10",
+        env,
+        help = "Maximum size in MB of a remote image fetched by the content proxy",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub bichon_remote_content_max_mb: u64,
+
+    /// Look up DKIM keys in DNS when messages are ingested. Turn off on hosts
+    /// without DNS access; DKIM verdicts are then recorded as unknown.
+    #[clap(
+        long,
+        default_value = "true",
+        env,
+        help = "Look up DKIM signing keys in DNS when archiving messages"
+    )]
+    pub bichon_dkim_dns_lookup: bool,
 }
 
 impl Settings {
@@ -490,3 +590,20 @@ impl fmt::Display for EncryptionMode {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum BlobBackend {
+    #[clap(name = "local")]
+    Local,
+    #[clap(name = "s3")]
+    S3,
+}
+
+impl fmt::Display for BlobBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlobBackend::Local => write!(f, "local"),
+            BlobBackend::S3 => write!(f, "s3"),
+        }
+    }
+}
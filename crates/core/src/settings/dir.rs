@@ -33,6 +33,7 @@ const ATTACHMENT_METADATA: &str = "attachment_metadata";
 const STORAGE: &str = "bichon-storage";
 const TMP_DIR: &str = "tmp";
 const LOG_DIR: &str = "logs";
+const SMTP_SPOOL: &str = "smtp-spool";
 
 const TLS_CERT: &str = "cert.pem";
 const TLS_KEY: &str = "key.pem";
@@ -51,6 +52,7 @@ pub struct DataDirManager {
     pub attachment_dir: PathBuf,
     pub storage_dir: PathBuf,
     pub log_dir: PathBuf,
+    pub smtp_spool_dir: PathBuf,
 }
 
 impl Initialize for DataDirManager {
@@ -98,6 +100,7 @@ impl DataDirManager {
             envelope_dir: index_dir.join(MAIL_METADATA),
             attachment_dir: index_dir.join(ATTACHMENT_METADATA),
             temp_dir: root_dir.join(TMP_DIR),
+            smtp_spool_dir: root_dir.join(SMTP_SPOOL),
             storage_dir,
         }
     }
@@ -43,6 +43,8 @@ pub struct SystemConfigurations {
 
     pub bichon_encrypt_password_set: bool,
     pub bichon_webui_token_expiration_hours: u32,
+    pub bichon_login_max_failures: u32,
+    pub bichon_login_lockout_minutes: u32,
 
     pub bichon_root_dir: String,
 
@@ -92,6 +94,8 @@ impl From<&Settings> for SystemConfigurations {
             bichon_encrypt_password_set: s.bichon_encrypt_password.is_some()
                 || s.bichon_encrypt_password_file.is_some(),
             bichon_webui_token_expiration_hours: s.bichon_webui_token_expiration_hours,
+            bichon_login_max_failures: s.bichon_login_max_failures,
+            bichon_login_lockout_minutes: s.bichon_login_lockout_minutes,
             bichon_root_dir: s.bichon_root_dir.clone(),
             bichon_enable_rest_https: s.bichon_enable_rest_https,
             bichon_http_compression_enabled: s.bichon_http_compression_enabled,
@@ -0,0 +1,682 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Disk-backed spool for mail accepted by the embedded SMTP server.
+//!
+//! The SMTP session only replies `250` once the raw DATA has been written and
+//! fsynced under `pending/`. Ingestion into the index happens afterwards on a
+//! single background worker, with exponential backoff between attempts.
+//! Entries that keep failing are moved to `quarantine/`, where an admin can
+//! inspect, retry or drop them through the REST API.
+//!
+//! Each entry is a directory named after its spool id, holding the raw
+//! message and its metadata, so moving it between queues is a single rename.
+//!
+//! Successfully ingested entries are kept in `ingested/` for a short grace
+//! period, because the envelope and blob writers only commit periodically.
+//! On startup everything left in `pending/` and `ingested/` is replayed; the
+//! dedup cache makes re-ingesting an already committed message a no-op.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc, task};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    account::migration::AccountModel,
+    cache::imap::mailbox::{Attribute, AttributeEnum, MailBox},
+    common::signal::SIGNAL_MANAGER,
+    context::Initialize,
+    envelope::extractor::extract_envelope_from_smtp,
+    error::{code::ErrorCode, BichonResult},
+    raise_error,
+    settings::dir::DATA_DIR_MANAGER,
+    utc_now,
+    utils::create_hash,
+};
+
+pub static SMTP_SPOOL: LazyLock<SmtpSpool> = LazyLock::new(SmtpSpool::new);
+
+const PENDING_DIR: &str = "pending";
+const INGESTED_DIR: &str = "ingested";
+const QUARANTINE_DIR: &str = "quarantine";
+
+const MESSAGE_FILE: &str = "message.eml";
+const META_FILE: &str = "entry.json";
+const TMP_EXT: &str = "tmp";
+
+/// Number of ingest attempts before an entry is quarantined.
+const MAX_INGEST_ATTEMPTS: u32 = 8;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+/// How long ingested entries are kept around so a crash before the next
+/// index/blob commit can still be replayed. Well above the 60s commit
+/// interval of the envelope writer.
+const INGESTED_RETENTION_MS: i64 = 10 * 60 * 1000;
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Metadata stored next to each spooled message.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct SpoolEntry {
+    /// Unique spool identifier, also reported to the SMTP client on `250`.
+    pub id: String,
+    /// The Bichon account the message was delivered to.
+    pub account_id: u64,
+    /// The recipient address of that account.
+    pub account_email: String,
+    /// The envelope sender given in `MAIL FROM`.
+    pub mail_from: Option<String>,
+    /// Size of the raw message in bytes.
+    pub size: u64,
+    /// The timestamp (in milliseconds since epoch) when the message was accepted.
+    pub received_at: i64,
+    /// Number of ingest attempts made so far.
+    pub attempts: u32,
+    /// The timestamp (in milliseconds since epoch) of the last ingest attempt.
+    pub last_attempt_at: Option<i64>,
+    /// The error returned by the last failed ingest attempt.
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct SpoolDirs {
+    pending: PathBuf,
+    ingested: PathBuf,
+    quarantine: PathBuf,
+}
+
+impl SpoolDirs {
+    fn new(root: &Path) -> Self {
+        Self {
+            pending: root.join(PENDING_DIR),
+            ingested: root.join(INGESTED_DIR),
+            quarantine: root.join(QUARANTINE_DIR),
+        }
+    }
+
+    fn create_all(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.pending)?;
+        fs::create_dir_all(&self.ingested)?;
+        fs::create_dir_all(&self.quarantine)
+    }
+
+    /// Stage the raw message and its metadata in a temp directory, both
+    /// fsynced, then rename the directory into place. Until that rename
+    /// nothing is visible; a staging directory left by a crash is a torn
+    /// write.
+    fn persist(&self, entry: &SpoolEntry, data: &[u8]) -> io::Result<()> {
+        let staging = self.pending.join(format!("{}.{TMP_EXT}", entry.id));
+        fs::create_dir_all(&staging)?;
+        write_synced(&staging.join(MESSAGE_FILE), data)?;
+        write_synced(&staging.join(META_FILE), &meta_json(entry)?)?;
+        sync_dir(&staging)?;
+        fs::rename(&staging, entry_dir(&self.pending, &entry.id))?;
+        sync_dir(&self.pending)
+    }
+
+    fn write_meta(&self, dir: &Path, entry: &SpoolEntry) -> io::Result<()> {
+        write_durable(&entry_dir(dir, &entry.id), META_FILE, &meta_json(entry)?)
+    }
+
+    fn read_meta(&self, dir: &Path, id: &str) -> io::Result<SpoolEntry> {
+        let bytes = fs::read(entry_dir(dir, id).join(META_FILE))?;
+        serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn read_data(&self, dir: &Path, id: &str) -> io::Result<Vec<u8>> {
+        fs::read(entry_dir(dir, id).join(MESSAGE_FILE))
+    }
+
+    /// Move an entry between spool directories. Message and metadata
+    /// travel together in one rename, so a crash leaves the entry whole in
+    /// exactly one of the two places.
+    fn relocate(&self, from: &Path, to: &Path, id: &str) -> io::Result<()> {
+        fs::rename(entry_dir(from, id), entry_dir(to, id))?;
+        sync_dir(to)?;
+        sync_dir(from)
+    }
+
+    fn remove(&self, dir: &Path, id: &str) -> io::Result<()> {
+        remove_if_exists(&entry_dir(dir, id))?;
+        sync_dir(dir)
+    }
+
+    /// List complete entries in `dir`, oldest first. Leftover staging
+    /// directories are torn writes from a crash before the client got its
+    /// `250`, so they are removed.
+    fn list(&self, dir: &Path) -> io::Result<Vec<SpoolEntry>> {
+        let mut entries = Vec::new();
+        for item in fs::read_dir(dir)? {
+            let path = item?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(TMP_EXT) {
+                warn!("SMTP spool: removing incomplete entry {}", path.display());
+                remove_if_exists(&path)?;
+                continue;
+            }
+            let Some(id) = path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if !path.is_dir() {
+                continue;
+            }
+            match self.read_meta(dir, id) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("SMTP spool: unreadable metadata {}: {e}", path.display()),
+            }
+        }
+        entries.sort_by_key(|e| e.received_at);
+        Ok(entries)
+    }
+}
+
+fn entry_dir(dir: &Path, id: &str) -> PathBuf {
+    dir.join(id)
+}
+
+fn meta_json(entry: &SpoolEntry) -> io::Result<Vec<u8>> {
+    serde_json::to_vec_pretty(entry).map_err(io::Error::other)
+}
+
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Replace `dir/name` through a fsynced temp file.
+fn write_durable(dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!("{name}.{TMP_EXT}"));
+    write_synced(&tmp, data)?;
+    fs::rename(&tmp, dir.join(name))?;
+    sync_dir(dir)
+}
+
+/// Removes a file or a whole entry directory.
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match removed {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    let factor = 1u32 << attempts.saturating_sub(1).min(16);
+    RETRY_BASE_DELAY.saturating_mul(factor).min(RETRY_MAX_DELAY)
+}
+
+fn validate_id(id: &str) -> BichonResult<()> {
+    Uuid::parse_str(id).map(|_| ()).map_err(|_| {
+        raise_error!(
+            format!("Invalid spool entry id '{id}'"),
+            ErrorCode::InvalidParameter
+        )
+    })
+}
+
+fn io_error(e: io::Error) -> crate::error::BichonError {
+    raise_error!(format!("{:#?}", e), ErrorCode::InternalError)
+}
+
+pub struct SmtpSpool {
+    dirs: SpoolDirs,
+    sender: mpsc::UnboundedSender<String>,
+}
+
+impl Initialize for SmtpSpool {
+    async fn initialize() -> BichonResult<()> {
+        SMTP_SPOOL.replay().await
+    }
+}
+
+impl SmtpSpool {
+    fn new() -> Self {
+        let dirs = SpoolDirs::new(&DATA_DIR_MANAGER.smtp_spool_dir);
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+
+        let worker_dirs = dirs.clone();
+        let retry_sender = sender.clone();
+        task::spawn(async move {
+            let mut shutdown = SIGNAL_MANAGER.subscribe();
+            let mut cleanup = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                tokio::select! {
+                    maybe_id = receiver.recv() => {
+                        match maybe_id {
+                            Some(id) => process_entry(&worker_dirs, &retry_sender, &id).await,
+                            None => break,
+                        }
+                    }
+                    _ = cleanup.tick() => {
+                        let dirs = worker_dirs.clone();
+                        if let Ok(Err(e)) = task::spawn_blocking(move || purge_ingested(&dirs)).await {
+                            warn!("SMTP spool: failed to purge ingested entries: {e}");
+                        }
+                    }
+                    _ = shutdown.recv() => {
+                        info!("SMTP spool: shutdown signal received, remaining entries stay on disk.");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self { dirs, sender }
+    }
+
+    /// Durably store an accepted message and schedule it for ingestion.
+    /// Returns the spool id once the data is on disk; only then may the
+    /// SMTP session acknowledge the transaction.
+    pub async fn enqueue(
+        &self,
+        account: &AccountModel,
+        mail_from: Option<String>,
+        data: Vec<u8>,
+    ) -> BichonResult<String> {
+        let entry = SpoolEntry {
+            id: Uuid::new_v4().to_string(),
+            account_id: account.id,
+            account_email: account.email.clone(),
+            mail_from,
+            size: data.len() as u64,
+            received_at: utc_now!(),
+            attempts: 0,
+            last_attempt_at: None,
+            last_error: None,
+        };
+        let id = entry.id.clone();
+        let dirs = self.dirs.clone();
+        task::spawn_blocking(move || {
+            dirs.create_all()?;
+            dirs.persist(&entry, &data)
+        })
+        .await
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?
+        .map_err(io_error)?;
+        self.schedule(&id);
+        Ok(id)
+    }
+
+    /// Re-queue everything left over from a previous run.
+    async fn replay(&self) -> BichonResult<()> {
+        let dirs = self.dirs.clone();
+        let entries = task::spawn_blocking(move || -> io::Result<Vec<SpoolEntry>> {
+            dirs.create_all()?;
+            for entry in dirs.list(&dirs.ingested)? {
+                dirs.relocate(&dirs.ingested, &dirs.pending, &entry.id)?;
+            }
+            dirs.list(&dirs.pending)
+        })
+        .await
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?
+        .map_err(io_error)?;
+
+        if !entries.is_empty() {
+            info!(
+                "SMTP spool: replaying {} message(s) from a previous run.",
+                entries.len()
+            );
+        }
+        for entry in entries {
+            self.schedule(&entry.id);
+        }
+        Ok(())
+    }
+
+    fn schedule(&self, id: &str) {
+        if let Err(e) = self.sender.send(id.to_string()) {
+            error!("SMTP spool: failed to schedule entry {}: {:?}", id, e);
+        }
+    }
+
+    /// List messages that exhausted their ingest attempts, oldest first.
+    pub fn list_quarantined(&self) -> BichonResult<Vec<SpoolEntry>> {
+        if !self.dirs.quarantine.exists() {
+            return Ok(Vec::new());
+        }
+        self.dirs.list(&self.dirs.quarantine).map_err(io_error)
+    }
+
+    /// Read the raw message of a quarantined entry.
+    pub fn get_quarantined_content(&self, id: &str) -> BichonResult<Vec<u8>> {
+        validate_id(id)?;
+        self.ensure_quarantined(id)?;
+        self.dirs
+            .read_data(&self.dirs.quarantine, id)
+            .map_err(io_error)
+    }
+
+    /// Move a quarantined entry back to the pending queue with a fresh
+    /// attempt budget.
+    pub fn retry_quarantined(&self, id: &str) -> BichonResult<()> {
+        validate_id(id)?;
+        let mut entry = self.ensure_quarantined(id)?;
+        entry.attempts = 0;
+        self.dirs
+            .write_meta(&self.dirs.quarantine, &entry)
+            .and_then(|_| {
+                self.dirs
+                    .relocate(&self.dirs.quarantine, &self.dirs.pending, id)
+            })
+            .map_err(io_error)?;
+        self.schedule(id);
+        Ok(())
+    }
+
+    /// Permanently drop a quarantined entry.
+    pub fn delete_quarantined(&self, id: &str) -> BichonResult<()> {
+        validate_id(id)?;
+        self.ensure_quarantined(id)?;
+        self.dirs
+            .remove(&self.dirs.quarantine, id)
+            .map_err(io_error)
+    }
+
+    fn ensure_quarantined(&self, id: &str) -> BichonResult<SpoolEntry> {
+        self.dirs
+            .read_meta(&self.dirs.quarantine, id)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => raise_error!(
+                    format!("Quarantined message '{id}' not found"),
+                    ErrorCode::ResourceNotFound
+                ),
+                _ => io_error(e),
+            })
+    }
+}
+
+async fn process_entry(dirs: &SpoolDirs, sender: &mpsc::UnboundedSender<String>, id: &str) {
+    let loaded = {
+        let dirs = dirs.clone();
+        let id = id.to_string();
+        task::spawn_blocking(move || {
+            let entry = dirs.read_meta(&dirs.pending, &id)?;
+            let data = dirs.read_data(&dirs.pending, &id)?;
+            Ok::<_, io::Error>((entry, data))
+        })
+        .await
+    };
+    let (mut entry, data) = match loaded {
+        Ok(Ok(loaded)) => loaded,
+        // Retried after an admin dropped or re-queued it elsewhere.
+        Ok(Err(e)) if e.kind() == io::ErrorKind::NotFound => return,
+        Ok(Err(e)) => {
+            error!("SMTP spool: failed to load entry {}: {e}", id);
+            return;
+        }
+        Err(e) => {
+            error!("SMTP spool: failed to load entry {}: {e:?}", id);
+            return;
+        }
+    };
+
+    entry.attempts += 1;
+    entry.last_attempt_at = Some(utc_now!());
+    let result = ingest(&entry, &data).await;
+    if let Err(ref e) = result {
+        entry.last_error = Some(e.to_string());
+    }
+
+    let succeeded = result.is_ok();
+    let quarantine = !succeeded && entry.attempts >= MAX_INGEST_ATTEMPTS;
+    let target = if succeeded {
+        dirs.ingested.clone()
+    } else if quarantine {
+        dirs.quarantine.clone()
+    } else {
+        dirs.pending.clone()
+    };
+
+    let moved = {
+        let dirs = dirs.clone();
+        let entry = entry.clone();
+        task::spawn_blocking(move || {
+            dirs.write_meta(&dirs.pending, &entry)?;
+            if target != dirs.pending {
+                dirs.relocate(&dirs.pending, &target, &entry.id)?;
+            }
+            Ok::<_, io::Error>(())
+        })
+        .await
+    };
+    if !matches!(moved, Ok(Ok(()))) {
+        error!(
+            "SMTP spool: failed to update entry {} after ingest attempt: {:?}",
+            id, moved
+        );
+    }
+
+    if succeeded {
+        tracing::debug!(
+            "SMTP spool: entry {} ingested for <{}>",
+            id,
+            entry.account_email
+        );
+    } else if quarantine {
+        error!(
+            "SMTP spool: entry {} for <{}> quarantined after {} attempts: {}",
+            id,
+            entry.account_email,
+            entry.attempts,
+            entry.last_error.as_deref().unwrap_or_default()
+        );
+    } else {
+        let delay = retry_delay(entry.attempts);
+        warn!(
+            "SMTP spool: ingest of entry {} failed (attempt {}/{}), retrying in {}s: {}",
+            id,
+            entry.attempts,
+            MAX_INGEST_ATTEMPTS,
+            delay.as_secs(),
+            entry.last_error.as_deref().unwrap_or_default()
+        );
+        let sender = sender.clone();
+        let id = id.to_string();
+        task::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = sender.send(id);
+        });
+    }
+}
+
+async fn ingest(entry: &SpoolEntry, data: &[u8]) -> BichonResult<()> {
+    let account = AccountModel::get(entry.account_id)?;
+    let mailbox_id = create_hash(account.id, "INBOX");
+
+    // The INBOX row is owned by the IMAP sync, which maintains `uid_validity`,
+    // `highest_uid` and `uid_next` on it. `batch_upsert` replaces the *whole*
+    // row, so blindly upserting here (with those fields = None) clobbers the
+    // IMAP-maintained state back to None. The next reconcile then sees
+    // `uid_validity` change from Some -> None, treats the mailbox as invalid,
+    // and wipes + rebuilds it — silently losing the local copy of a large
+    // mailbox when that rebuild is interrupted (see #297).
+    //
+    // We only need the row to *exist* so the journaled envelope can attach to
+    // it, so create it only when it is missing and otherwise leave the
+    // IMAP-owned row untouched.
+    if MailBox::find_mailbox(account.id, mailbox_id)?.is_none() {
+        let mailbox = MailBox {
+            id: mailbox_id,
+            account_id: account.id,
+            name: "INBOX".into(),
+            delimiter: Some("/".to_string()),
+            attributes: vec![Attribute {
+                attr: AttributeEnum::Extension,
+                extension: Some("CreatedByBichon".into()),
+            }],
+            exists: 0,
+            unseen: None,
+            uid_next: None,
+            uid_validity: None,
+            highest_uid: None,
+            highest_modseq: None,
+        };
+        MailBox::batch_upsert(&[mailbox])?;
+    }
+
+    extract_envelope_from_smtp(data, account.id, mailbox_id, entry.received_at).await
+}
+
+fn purge_ingested(dirs: &SpoolDirs) -> io::Result<()> {
+    let cutoff = utc_now!() - INGESTED_RETENTION_MS;
+    for entry in dirs.list(&dirs.ingested)? {
+        if entry.last_attempt_at.unwrap_or(entry.received_at) < cutoff {
+            dirs.remove(&dirs.ingested, &entry.id)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dirs() -> SpoolDirs {
+        let root = std::env::temp_dir().join(format!("bichon-spool-{}", Uuid::new_v4()));
+        let dirs = SpoolDirs::new(&root);
+        dirs.create_all().unwrap();
+        dirs
+    }
+
+    fn entry(received_at: i64) -> SpoolEntry {
+        SpoolEntry {
+            id: Uuid::new_v4().to_string(),
+            account_id: 1,
+            account_email: "archive@example.com".into(),
+            mail_from: Some("sender@example.com".into()),
+            size: 5,
+            received_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn persisted_entry_round_trips() {
+        let dirs = temp_dirs();
+        let e = entry(1);
+        dirs.persist(&e, b"hello").unwrap();
+
+        assert_eq!(dirs.list(&dirs.pending).unwrap(), vec![e.clone()]);
+        assert_eq!(dirs.read_data(&dirs.pending, &e.id).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn list_drops_torn_writes_and_sorts_by_arrival() {
+        let dirs = temp_dirs();
+        let later = entry(20);
+        let earlier = entry(10);
+        dirs.persist(&later, b"b").unwrap();
+        dirs.persist(&earlier, b"a").unwrap();
+
+        // A crash before the staging directory was renamed into place.
+        let torn = dirs.pending.join(format!("{}.{TMP_EXT}", Uuid::new_v4()));
+        fs::create_dir_all(&torn).unwrap();
+        fs::write(torn.join(MESSAGE_FILE), b"partial").unwrap();
+
+        let ids: Vec<String> = dirs
+            .list(&dirs.pending)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(ids, vec![earlier.id, later.id]);
+        assert!(!torn.exists());
+        assert_eq!(fs::read_dir(&dirs.pending).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn relocate_moves_message_and_metadata() {
+        let dirs = temp_dirs();
+        let e = entry(1);
+        dirs.persist(&e, b"hello").unwrap();
+        dirs.relocate(&dirs.pending, &dirs.quarantine, &e.id)
+            .unwrap();
+
+        assert!(dirs.list(&dirs.pending).unwrap().is_empty());
+        assert_eq!(dirs.list(&dirs.quarantine).unwrap(), vec![e.clone()]);
+
+        dirs.remove(&dirs.quarantine, &e.id).unwrap();
+        assert!(dirs.list(&dirs.quarantine).unwrap().is_empty());
+    }
+
+    #[test]
+    fn crash_during_moves_leaves_complete_entries() {
+        let dirs = temp_dirs();
+        let moved = entry(1);
+        let rewritten = entry(2);
+        dirs.persist(&moved, b"moved").unwrap();
+        dirs.persist(&rewritten, b"rewritten").unwrap();
+
+        // The worker crashes right after moving one entry to `ingested/`,
+        // before syncing either directory, and halfway through rewriting
+        // the metadata of the other.
+        fs::rename(
+            entry_dir(&dirs.pending, &moved.id),
+            entry_dir(&dirs.ingested, &moved.id),
+        )
+        .unwrap();
+        let staged_meta =
+            entry_dir(&dirs.pending, &rewritten.id).join(format!("{META_FILE}.{TMP_EXT}"));
+        fs::write(staged_meta, b"{").unwrap();
+
+        // Replay on the next start.
+        for e in dirs.list(&dirs.ingested).unwrap() {
+            dirs.relocate(&dirs.ingested, &dirs.pending, &e.id).unwrap();
+        }
+        assert_eq!(
+            dirs.list(&dirs.pending).unwrap(),
+            vec![moved.clone(), rewritten.clone()]
+        );
+        assert_eq!(dirs.read_data(&dirs.pending, &moved.id).unwrap(), b"moved");
+        assert_eq!(
+            dirs.read_data(&dirs.pending, &rewritten.id).unwrap(),
+            b"rewritten"
+        );
+    }
+
+    #[test]
+    fn retry_delay_grows_and_is_capped() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(3), RETRY_BASE_DELAY * 4);
+        assert_eq!(retry_delay(64), RETRY_MAX_DELAY);
+    }
+}
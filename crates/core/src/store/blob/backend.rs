@@ -0,0 +1,101 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The content-addressed blob store behind [`super::BlobManager`].
+//!
+//! Blobs are keyed by the 32-byte BLAKE3 hash of their content, so a key is
+//! written at most once and never changes. [`Engine`] stores them on local
+//! disk; [`super::s3::S3BlobStore`] keeps them in an S3-compatible bucket.
+
+use std::path::Path;
+
+use bichon_blob::{Codec, Config, Engine};
+
+use crate::{
+    error::{code::ErrorCode, BichonResult},
+    raise_error,
+};
+
+pub trait BlobStore: Send + Sync {
+    /// Stores `value` under `key`. Storing a key that already exists keeps
+    /// the stored copy.
+    fn put(&self, key: [u8; 32], value: &[u8]) -> BichonResult<()>;
+
+    fn get(&self, key: &[u8; 32]) -> BichonResult<Option<Vec<u8>>>;
+
+    fn exists(&self, key: &[u8; 32]) -> BichonResult<bool>;
+
+    fn delete_batch(&self, keys: &[[u8; 32]]) -> BichonResult<()>;
+
+    /// Every stored key.
+    fn keys(&self) -> BichonResult<Vec<[u8; 32]>>;
+
+    /// Makes everything stored so far durable.
+    fn flush(&self) -> BichonResult<()>;
+
+    fn shutdown(&self) -> BichonResult<()>;
+}
+
+/// Opens the local store with the settings the server uses.
+pub fn open_local_store(dir: &Path) -> BichonResult<Engine> {
+    let config = Config {
+        default_codec: Codec::Zstd,
+        compress_threshold: 1024,
+        flush_interval_secs: 60,
+        gc_interval_secs: 300,
+        ..Default::default()
+    };
+    Engine::open(dir, config).map_err(blob_error)
+}
+
+pub(crate) fn blob_error(e: bichon_blob::Error) -> crate::error::BichonError {
+    raise_error!(format!("{:#?}", e), ErrorCode::InternalError)
+}
+
+impl BlobStore for Engine {
+    fn put(&self, key: [u8; 32], value: &[u8]) -> BichonResult<()> {
+        if Engine::exists(self, &key).map_err(blob_error)? {
+            return Ok(());
+        }
+        Engine::put(self, key, value, Codec::Lz4).map_err(blob_error)
+    }
+
+    fn get(&self, key: &[u8; 32]) -> BichonResult<Option<Vec<u8>>> {
+        Engine::get(self, key).map_err(blob_error)
+    }
+
+    fn exists(&self, key: &[u8; 32]) -> BichonResult<bool> {
+        Engine::exists(self, key).map_err(blob_error)
+    }
+
+    fn delete_batch(&self, keys: &[[u8; 32]]) -> BichonResult<()> {
+        Engine::delete_batch(self, keys).map_err(blob_error)
+    }
+
+    fn keys(&self) -> BichonResult<Vec<[u8; 32]>> {
+        Engine::keys(self).map_err(blob_error)
+    }
+
+    fn flush(&self) -> BichonResult<()> {
+        Engine::flush(self).map_err(blob_error)
+    }
+
+    fn shutdown(&self) -> BichonResult<()> {
+        Engine::shutdown(self).map_err(blob_error)
+    }
+}
@@ -0,0 +1,123 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Copies blobs between [`BlobStore`]s, e.g. from the local store to S3.
+
+use super::backend::BlobStore;
+use crate::error::BichonResult;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlobCopyStats {
+    pub total: usize,
+    pub copied: usize,
+    /// Already present in the target, e.g. from an interrupted earlier run.
+    pub skipped: usize,
+    /// Listed by the source but unreadable from it.
+    pub missing: usize,
+}
+
+/// Copies every blob of `source` that `target` does not hold yet, then
+/// flushes `target`. Keys are content hashes, so an interrupted copy can
+/// simply be run again. `on_progress` is called with the number of keys
+/// processed so far and the total.
+pub fn copy_blobs(
+    source: &dyn BlobStore,
+    target: &dyn BlobStore,
+    mut on_progress: impl FnMut(usize, usize),
+) -> BichonResult<BlobCopyStats> {
+    let keys = source.keys()?;
+    let mut stats = BlobCopyStats {
+        total: keys.len(),
+        ..Default::default()
+    };
+    for (done, key) in keys.iter().enumerate() {
+        if target.exists(key)? {
+            stats.skipped += 1;
+        } else {
+            match source.get(key)? {
+                Some(value) => {
+                    target.put(*key, &value)?;
+                    stats.copied += 1;
+                }
+                None => {
+                    tracing::warn!("blob {} vanished during the copy", hex::encode(key));
+                    stats.missing += 1;
+                }
+            }
+        }
+        on_progress(done + 1, stats.total);
+    }
+    target.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::blob::{
+        backend::open_local_store,
+        mock_s3::{MockS3Server, BUCKET},
+        s3::{S3BlobStore, S3Config},
+    };
+
+    #[test]
+    fn copies_a_local_store_to_s3_and_resumes() {
+        let root = std::env::temp_dir()
+            .join("bichon-blob-migrate-test")
+            .join(uuid::Uuid::new_v4().to_string());
+        let local = open_local_store(&root.join("blobs")).unwrap();
+        let blobs: Vec<([u8; 32], Vec<u8>)> = (0..30u32)
+            .map(|i| {
+                let value = format!("message {i} ")
+                    .repeat(i as usize * 50 + 1)
+                    .into_bytes();
+                (*blake3::hash(&value).as_bytes(), value)
+            })
+            .collect();
+        for (key, value) in &blobs[..10] {
+            BlobStore::put(&local, *key, value).unwrap();
+        }
+
+        let server = MockS3Server::start(1000);
+        let config = S3Config::new(&server.endpoint(), BUCKET, "test", "secret");
+        let s3 = S3BlobStore::open(config, &root.join("s3")).unwrap();
+        let first = copy_blobs(&local, &s3, |_, _| {}).unwrap();
+        assert_eq!(first.copied, 10);
+
+        for (key, value) in &blobs[10..] {
+            BlobStore::put(&local, *key, value).unwrap();
+        }
+        let mut progress = Vec::new();
+        let second = copy_blobs(&local, &s3, |done, total| progress.push((done, total))).unwrap();
+        assert_eq!(
+            second,
+            BlobCopyStats {
+                total: 30,
+                copied: 20,
+                skipped: 10,
+                missing: 0
+            }
+        );
+        assert_eq!(progress.last(), Some(&(30, 30)));
+        for (key, value) in &blobs {
+            assert_eq!(s3.get(key).unwrap().as_ref(), Some(value));
+        }
+        s3.shutdown().unwrap();
+        BlobStore::shutdown(&local).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal in-process stand-in for an S3-compatible bucket, used to test
+//! the S3 blob store without a real service.
+//!
+//! It understands path-style `PUT`, `GET` (with a single `Range`), `DELETE`
+//! and ListObjectsV2 with prefix filtering and continuation tokens. Requests
+//! without an `Authorization` header are rejected with `403`; signatures are
+//! not checked. Every request is recorded for assertions.
+//!
+//! The server runs on a thread with its own runtime, so it can be used from
+//! both synchronous and async tests.
+//!
+//! # Example
+//! ```ignore
+//! let server = MockS3Server::start(1000);
+//! let client = server.client();
+//! client.put("blobs/a", b"data".to_vec())?;
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use super::s3_client::S3Client;
+use crate::common::mock_http::{HttpRequest, HttpResponse, MockHttpServer};
+
+pub const BUCKET: &str = "bichon-test";
+
+/// A request received by the server. Header names are lowercase.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub target: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct State {
+    objects: BTreeMap<String, Vec<u8>>,
+    requests: Vec<RecordedRequest>,
+    /// Number of upcoming requests to answer with `503`.
+    failures: usize,
+}
+
+pub struct MockS3Server {
+    server: MockHttpServer,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockS3Server {
+    /// Starts a server that returns at most `page_size` keys per list page.
+    pub fn start(page_size: usize) -> Self {
+        let state: Arc<Mutex<State>> = Arc::default();
+        let shared = state.clone();
+        let server = MockHttpServer::start_on_thread(move |request| {
+            respond(&mut shared.lock().unwrap(), request, page_size)
+        });
+        Self { server, state }
+    }
+
+    pub fn endpoint(&self) -> String {
+        self.server.url()
+    }
+
+    /// A path-style client for the test bucket.
+    pub fn client(&self) -> S3Client {
+        S3Client::new(
+            &self.endpoint(),
+            "us-east-1",
+            BUCKET,
+            "test",
+            "secret",
+            true,
+        )
+        .expect("client")
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Keys of all stored objects, in order.
+    pub fn keys(&self) -> Vec<String> {
+        self.state.lock().unwrap().objects.keys().cloned().collect()
+    }
+
+    pub fn object(&self, key: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().objects.get(key).cloned()
+    }
+
+    /// Answers the next `count` requests with a `503` `SlowDown` error.
+    pub fn fail_next(&self, count: usize) {
+        self.state.lock().unwrap().failures = count;
+    }
+}
+
+fn respond(state: &mut State, request: HttpRequest, page_size: usize) -> HttpResponse {
+    state.requests.push(RecordedRequest {
+        method: request.method.clone(),
+        target: request.target.clone(),
+        headers: request.headers.clone(),
+    });
+    if state.failures > 0 {
+        state.failures -= 1;
+        error(503, "SlowDown")
+    } else if !request.headers.contains_key("authorization") {
+        error(403, "AccessDenied")
+    } else {
+        route(state, request, page_size)
+    }
+}
+
+fn error(status: u16, code: &str) -> HttpResponse {
+    HttpResponse::new(
+        status,
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{code}</Code></Error>"),
+    )
+    .header("Content-Type", "application/xml")
+}
+
+fn route(state: &mut State, request: HttpRequest, page_size: usize) -> HttpResponse {
+    let target = request.target.as_str();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path);
+    let Some(rest) = path.strip_prefix(&format!("/{BUCKET}")) else {
+        return error(404, "NoSuchBucket");
+    };
+    let key = rest.strip_prefix('/').unwrap_or(rest);
+
+    match (request.method.as_str(), key.is_empty()) {
+        ("GET", true) => list(state, query, page_size),
+        ("PUT", false) => {
+            state.objects.insert(key.to_string(), request.body);
+            HttpResponse::new(200, "")
+        }
+        ("GET", false) => {
+            let Some(object) = state.objects.get(key) else {
+                return error(404, "NoSuchKey");
+            };
+            match request.headers.get("range").and_then(|r| parse_range(r)) {
+                Some((start, end)) => {
+                    if start >= object.len() {
+                        return error(416, "InvalidRange");
+                    }
+                    let end = end.min(object.len() - 1);
+                    HttpResponse::new(206, object[start..=end].to_vec()).header(
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, object.len()),
+                    )
+                }
+                None => HttpResponse::new(200, object.clone()),
+            }
+        }
+        ("DELETE", false) => {
+            state.objects.remove(key);
+            HttpResponse::new(204, "")
+        }
+        _ => error(405, "MethodNotAllowed"),
+    }
+}
+
+fn list(state: &State, query: &str, page_size: usize) -> HttpResponse {
+    let params: HashMap<String, String> = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect();
+    if params.get("list-type").map(String::as_str) != Some("2") {
+        return error(400, "InvalidArgument");
+    }
+    let prefix = params.get("prefix").cloned().unwrap_or_default();
+    let after = params.get("continuation-token").cloned();
+
+    let matching: Vec<(&String, &Vec<u8>)> = state
+        .objects
+        .iter()
+        .filter(|(k, _)| k.starts_with(&prefix))
+        .filter(|(k, _)| after.as_ref().is_none_or(|a| k.as_str() > a.as_str()))
+        .collect();
+    let truncated = matching.len() > page_size;
+    let page = &matching[..matching.len().min(page_size)];
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">",
+    );
+    xml.push_str(&format!("<Name>{BUCKET}</Name><Prefix>{prefix}</Prefix>"));
+    xml.push_str(&format!("<KeyCount>{}</KeyCount>", page.len()));
+    xml.push_str(&format!("<IsTruncated>{truncated}</IsTruncated>"));
+    if truncated {
+        if let Some((last, _)) = page.last() {
+            xml.push_str(&format!(
+                "<NextContinuationToken>{last}</NextContinuationToken>"
+            ));
+        }
+    }
+    for (key, value) in page {
+        xml.push_str(&format!(
+            "<Contents><Key>{}</Key><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+            key,
+            value.len()
+        ));
+    }
+    xml.push_str("</ListBucketResult>");
+    HttpResponse::new(200, xml).header("Content-Type", "application/xml")
+}
+
+/// Parses `bytes=start-end` into an inclusive range.
+fn parse_range(value: &str) -> Option<(usize, usize)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    Some((start.parse().ok()?, end.parse().ok()?))
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -21,10 +21,14 @@ use crate::{
     common::signal::SIGNAL_MANAGER,
     envelope::extractor::reattach_eml_content_self_healing,
     error::{code::ErrorCode, BichonResult},
-    settings::dir::DATA_DIR_MANAGER,
+    settings::{
+        cli::{BlobBackend, SETTINGS},
+        dir::DATA_DIR_MANAGER,
+    },
 };
-use bichon_blob::{Codec, Config, Engine};
+use backend::{open_local_store, BlobStore};
 use bytes::Bytes;
+use s3::{S3BlobStore, S3Config};
 
 use std::{io::Cursor, sync::Arc, sync::LazyLock};
 use tokio::{
@@ -32,6 +36,13 @@ use tokio::{
     task::{self, JoinHandle},
 };
 
+pub mod backend;
+pub mod migrate;
+#[cfg(test)]
+pub mod mock_s3;
+pub mod s3;
+pub mod s3_client;
+
 pub static BLOB_MANAGER: LazyLock<BlobManager> = LazyLock::new(BlobManager::new);
 
 pub struct DetachedEmail {
@@ -41,11 +52,11 @@ pub struct DetachedEmail {
 
 pub struct BlobManager {
     sender: mpsc::Sender<DetachedEmail>,
-    engine: Arc<Engine>,
+    store: Arc<dyn BlobStore>,
     handle: Mutex<Option<JoinHandle<()>>>,
 }
 
-fn hex_to_key(hex: &str) -> BichonResult<[u8; 32]> {
+pub(crate) fn hex_to_key(hex: &str) -> BichonResult<[u8; 32]> {
     let mut key = [0u8; 32];
     hex::decode_to_slice(hex, &mut key).map_err(|e| {
         raise_error!(
@@ -62,12 +73,12 @@ impl BlobManager {
         if let Some(handle) = guard.take() {
             let _ = handle.await;
         }
-        if let Err(e) = self.engine.shutdown() {
-            tracing::error!("blob engine shutdown error: {}", e);
+        if let Err(e) = self.store.shutdown() {
+            tracing::error!("blob store shutdown error: {}", e);
         }
     }
 
-    fn process_detached_email(eml: DetachedEmail, engine: &Engine) {
+    fn process_detached_email(eml: DetachedEmail, store: &dyn BlobStore) {
         let (email_hash, email_data) = eml.email;
         let email_key = match hex_to_key(&email_hash) {
             Ok(k) => k,
@@ -76,13 +87,13 @@ impl BlobManager {
                 return;
             }
         };
-        match engine.exists(&email_key) {
+        match store.exists(&email_key) {
             Ok(false) => {
-                if let Err(e) = engine.put(email_key, &email_data, Codec::Lz4) {
+                if let Err(e) = store.put(email_key, &email_data) {
                     tracing::error!("CRITICAL: Failed to insert email blob: {:?}", e);
                 }
             }
-            Err(e) => tracing::error!("blob engine error: {:?}", e),
+            Err(e) => tracing::error!("blob store error: {:?}", e),
             Ok(true) => {
                 tracing::debug!("Email blob already exists (dedup): {}", &email_hash);
             }
@@ -97,13 +108,13 @@ impl BlobManager {
                         continue;
                     }
                 };
-                match engine.exists(&a_key) {
+                match store.exists(&a_key) {
                     Ok(false) => {
-                        if let Err(e) = engine.put(a_key, &a_data, Codec::Lz4) {
+                        if let Err(e) = store.put(a_key, &a_data) {
                             tracing::error!("CRITICAL: Failed to insert attachment blob: {:?}", e);
                         }
                     }
-                    Err(e) => tracing::error!("blob engine error: {:?}", e),
+                    Err(e) => tracing::error!("blob store error: {:?}", e),
                     Ok(true) => {
                         tracing::debug!("Attachment blob already exists (dedup): {}", &a_hash);
                     }
@@ -113,22 +124,23 @@ impl BlobManager {
     }
 
     pub fn new() -> Self {
-        let blob_dir = DATA_DIR_MANAGER.storage_dir.join("blobs");
-
-        let mut config = Config::default();
-        config.default_codec = Codec::Zstd;
-        config.compress_threshold = 1024;
-        config.flush_interval_secs = 60;
-        config.gc_interval_secs = 300;
-
-        let engine = Engine::open(&blob_dir, config)
-            .expect("Failed to initialize blob engine: Check disk space and permissions.");
-
-        let engine = Arc::new(engine);
+        let store: Arc<dyn BlobStore> = match SETTINGS.bichon_blob_backend {
+            BlobBackend::Local => Arc::new(
+                open_local_store(&DATA_DIR_MANAGER.storage_dir.join("blobs"))
+                    .expect("Failed to initialize blob engine: Check disk space and permissions."),
+            ),
+            BlobBackend::S3 => Arc::new(
+                S3Config::from_settings()
+                    .and_then(|config| {
+                        S3BlobStore::open(config, &DATA_DIR_MANAGER.storage_dir.join("s3"))
+                    })
+                    .expect("Failed to initialize S3 blob store: Check the bichon_s3_* settings."),
+            ),
+        };
 
         let (sender, mut receiver) = mpsc::channel::<DetachedEmail>(100);
 
-        let engine_bg = Arc::clone(&engine);
+        let store_bg = Arc::clone(&store);
         let handler = task::spawn(async move {
             let mut shutdown = SIGNAL_MANAGER.subscribe();
             loop {
@@ -140,10 +152,10 @@ impl BlobManager {
                                 while let Ok(next_eml) = receiver.try_recv() {
                                     batch.push(next_eml);
                                 }
-                                let engine_bg = Arc::clone(&engine_bg);
+                                let store_bg = Arc::clone(&store_bg);
                                 if let Err(e) = tokio::task::spawn_blocking(move || {
                                     for eml in batch {
-                                        Self::process_detached_email(eml, &engine_bg);
+                                        Self::process_detached_email(eml, store_bg.as_ref());
                                     }
                                 }).await {
                                     tracing::error!("BlobManager: spawn_blocking join error: {:#?}", e);
@@ -166,10 +178,10 @@ impl BlobManager {
                             remaining.len()
                         );
                         if !remaining.is_empty() {
-                            let engine_bg = Arc::clone(&engine_bg);
+                            let store_bg = Arc::clone(&store_bg);
                             if let Err(e) = tokio::task::spawn_blocking(move || {
                                 for eml in remaining {
-                                    Self::process_detached_email(eml, &engine_bg);
+                                    Self::process_detached_email(eml, store_bg.as_ref());
                                 }
                             }).await {
                                 tracing::error!("BlobManager: shutdown spawn_blocking join error: {:#?}", e);
@@ -184,11 +196,15 @@ impl BlobManager {
 
         Self {
             sender,
-            engine,
+            store,
             handle: Mutex::new(Some(handler)),
         }
     }
 
+    pub(crate) fn store(&self) -> &dyn BlobStore {
+        self.store.as_ref()
+    }
+
     pub async fn queue(&self, email: DetachedEmail) {
         if let Err(e) = self.sender.send(email).await {
             tracing::error!("BlobManager channel closed, email lost: {:#?}", e);
@@ -205,10 +221,7 @@ impl BlobManager {
 
     fn get(&self, content_hash: &str) -> BichonResult<Option<Bytes>> {
         let key = hex_to_key(content_hash)?;
-        self.engine
-            .get(&key)
-            .map(|v| v.map(Bytes::from))
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))
+        self.store.get(&key).map(|v| v.map(Bytes::from))
     }
 
     pub fn delete<I1, I2>(
@@ -232,9 +245,7 @@ impl BlobManager {
         }
 
         if !keys.is_empty() {
-            self.engine
-                .delete_batch(&keys)
-                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            self.store.delete_batch(&keys)?;
         }
 
         Ok(())
@@ -0,0 +1,991 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A [`BlobStore`] that keeps blobs in an S3-compatible bucket.
+//!
+//! Archives hold millions of small blobs, and an object per blob is slow and
+//! expensive to write and list. Blobs smaller than `pack_threshold` are
+//! staged in a local store and uploaded together in packs of about
+//! `pack_size` bytes; larger blobs get an object of their own. A local index
+//! maps every key to the object and byte range holding it, so a read is a
+//! single (ranged) GET. Recently read blobs are kept in an in-memory LRU
+//! cache.
+//!
+//! Local directory:
+//! - `index/`: key → [`Location`]
+//! - `staging/`: blobs waiting to be packed
+//!
+//! Bucket:
+//! - `{prefix}blobs/<hex key>`: one compressed blob
+//! - `{prefix}packs/<id>`: compressed blobs back to back
+//! - `<object>.manifest` next to each of them: the key and [`Location`] of
+//!   every blob in the object
+//!
+//! Manifests make the bucket self-describing: an empty local index is
+//! rebuilt from them on open. Deletes do not rewrite manifests, so a
+//! rebuild brings back packed blobs deleted since their pack was last
+//! compacted; the consistency checker reports them as orphan blobs.
+//!
+//! Deleting a packed blob only drops its index entry. A background
+//! compaction removes packs with nothing live left and rewrites packs that
+//! are mostly dead. Liveness is read from the pack's manifest: a pack is
+//! only deleted when none of the keys it lists is indexed into it.
+
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use bichon_blob::{
+    compress::{compress, decompress},
+    Codec, Config, Engine,
+};
+use lru::LruCache;
+
+use super::{
+    backend::{blob_error, BlobStore},
+    s3_client::S3Client,
+};
+use crate::{
+    error::{code::ErrorCode, BichonResult},
+    raise_error,
+    settings::cli::SETTINGS,
+};
+
+/// Blobs are compressed only from this size on.
+const COMPRESS_THRESHOLD: usize = 1024;
+const WORKER_TICK: Duration = Duration::from_secs(1);
+/// A pack is rewritten once less than this share of it is live.
+const MIN_LIVE_RATIO: f64 = 0.5;
+const MANIFEST_SUFFIX: &str = ".manifest";
+/// Entries written to the index at once during a rebuild.
+const REBUILD_BATCH: usize = 10_000;
+
+#[derive(Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Prepended to every object key; empty or ending with `/`.
+    pub prefix: String,
+    pub path_style: bool,
+    /// Byte budget of the read cache.
+    pub cache_bytes: u64,
+    /// Blobs at least this large are stored as objects of their own.
+    pub pack_threshold: usize,
+    /// Staged blobs are packed once they add up to this many bytes...
+    pub pack_size: usize,
+    /// ...or once the oldest of them has waited this long.
+    pub max_staged_age: Duration,
+    pub compact_interval: Duration,
+}
+
+impl S3Config {
+    pub fn new(endpoint: &str, bucket: &str, access_key: &str, secret_key: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            region: "us-east-1".into(),
+            bucket: bucket.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            prefix: String::new(),
+            path_style: true,
+            cache_bytes: 256 * 1024 * 1024,
+            pack_threshold: 512 * 1024,
+            pack_size: 16 * 1024 * 1024,
+            max_staged_age: Duration::from_secs(60),
+            compact_interval: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        self.prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{prefix}/")
+        };
+        self
+    }
+
+    /// Reads the `bichon_s3_*` settings.
+    pub fn from_settings() -> BichonResult<Self> {
+        let required = |value: &Option<String>, name: &str| {
+            value.clone().filter(|v| !v.is_empty()).ok_or_else(|| {
+                raise_error!(
+                    format!("'{name}' must be set when the blob backend is 's3'"),
+                    ErrorCode::InvalidParameter
+                )
+            })
+        };
+        let mut config = Self::new(
+            &required(&SETTINGS.bichon_s3_endpoint, "bichon_s3_endpoint")?,
+            &required(&SETTINGS.bichon_s3_bucket, "bichon_s3_bucket")?,
+            &required(&SETTINGS.bichon_s3_access_key, "bichon_s3_access_key")?,
+            &required(&SETTINGS.bichon_s3_secret_key, "bichon_s3_secret_key")?,
+        )
+        .with_prefix(SETTINGS.bichon_s3_prefix.as_deref().unwrap_or(""));
+        config.region = SETTINGS.bichon_s3_region.clone();
+        config.path_style = SETTINGS.bichon_s3_path_style;
+        config.cache_bytes = SETTINGS.bichon_s3_cache_mb * 1024 * 1024;
+        Ok(config)
+    }
+}
+
+/// Where a blob lives in the bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    /// The pack holding the blob, or `None` for a blob stored on its own.
+    pack: Option<u64>,
+    offset: u64,
+    len: u64,
+    raw_len: u64,
+    codec: Codec,
+}
+
+impl Location {
+    const ENCODED_LEN: usize = 34;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Self::ENCODED_LEN);
+        out.push(self.pack.is_some() as u8);
+        out.extend_from_slice(&self.pack.unwrap_or(0).to_le_bytes());
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&self.len.to_le_bytes());
+        out.extend_from_slice(&self.raw_len.to_le_bytes());
+        out.push(self.codec as u8);
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return None;
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        Some(Self {
+            pack: (bytes[0] == 1).then(|| u64_at(1)),
+            offset: u64_at(9),
+            len: u64_at(17),
+            raw_len: u64_at(25),
+            codec: Codec::from_u8(bytes[33])?,
+        })
+    }
+}
+
+/// The blobs of one object in the bucket.
+type Manifest = Vec<([u8; 32], Location)>;
+
+/// Serializes manifest entries as `key ++ location` records.
+fn encode_manifest(entries: &[([u8; 32], Location)]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(entries.len() * (32 + Location::ENCODED_LEN));
+    for (key, location) in entries {
+        out.extend_from_slice(key);
+        out.extend_from_slice(&location.encode());
+    }
+    out
+}
+
+fn decode_manifest(bytes: &[u8]) -> Option<Manifest> {
+    const RECORD_LEN: usize = 32 + Location::ENCODED_LEN;
+    if !bytes.len().is_multiple_of(RECORD_LEN) {
+        return None;
+    }
+    bytes
+        .chunks_exact(RECORD_LEN)
+        .map(|record| {
+            let key: [u8; 32] = record[..32].try_into().unwrap();
+            Some((key, Location::decode(&record[32..])?))
+        })
+        .collect()
+}
+
+/// What a compaction pass did.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub packs_deleted: usize,
+    pub packs_rewritten: usize,
+    pub bytes_reclaimed: u64,
+}
+
+pub struct S3BlobStore {
+    inner: Arc<Inner>,
+    worker: Mutex<Option<(mpsc::Sender<()>, JoinHandle<()>)>>,
+}
+
+impl S3BlobStore {
+    /// Opens the store, keeping its index and staged blobs under `dir`.
+    /// An empty index is rebuilt from the bucket's manifests, and blobs
+    /// staged by a previous run are uploaded before this returns.
+    pub fn open(config: S3Config, dir: &Path) -> BichonResult<Self> {
+        let client = S3Client::new(
+            &config.endpoint,
+            &config.region,
+            &config.bucket,
+            &config.access_key,
+            &config.secret_key,
+            config.path_style,
+        )?;
+        let local = Config {
+            default_codec: Codec::None,
+            flush_interval_secs: 60,
+            gc_interval_secs: 300,
+            ..Default::default()
+        };
+        let index = Engine::open(&dir.join("index"), local.clone()).map_err(blob_error)?;
+        let staging = Engine::open(&dir.join("staging"), local).map_err(blob_error)?;
+
+        let inner = Arc::new(Inner {
+            cache: Mutex::new(ReadCache::new(config.cache_bytes)),
+            config,
+            client,
+            index,
+            staging,
+            staged: Mutex::new(Staged::default()),
+            write_lock: Mutex::new(()),
+        });
+        if inner.index.stats().map_err(blob_error)?.total_keys == 0 {
+            inner.rebuild_index()?;
+        }
+        inner.flush()?;
+
+        let (stop, stopped) = mpsc::channel();
+        let worker_inner = inner.clone();
+        let handle = std::thread::Builder::new()
+            .name("bichon-s3-packer".into())
+            .spawn(move || run_worker(worker_inner, stopped))
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        Ok(Self {
+            inner,
+            worker: Mutex::new(Some((stop, handle))),
+        })
+    }
+
+    /// Deletes packs without live blobs and rewrites packs that are mostly
+    /// dead. Runs in the background every `compact_interval`.
+    pub fn compact(&self) -> BichonResult<CompactionStats> {
+        self.inner.compact()
+    }
+}
+
+impl BlobStore for S3BlobStore {
+    fn put(&self, key: [u8; 32], value: &[u8]) -> BichonResult<()> {
+        self.inner.put(key, value)
+    }
+
+    fn get(&self, key: &[u8; 32]) -> BichonResult<Option<Vec<u8>>> {
+        self.inner.get(key)
+    }
+
+    fn exists(&self, key: &[u8; 32]) -> BichonResult<bool> {
+        self.inner.exists(key)
+    }
+
+    fn delete_batch(&self, keys: &[[u8; 32]]) -> BichonResult<()> {
+        self.inner.delete_batch(keys)
+    }
+
+    fn keys(&self) -> BichonResult<Vec<[u8; 32]>> {
+        let mut keys = self.inner.index.keys().map_err(blob_error)?;
+        keys.extend(self.inner.staging.keys().map_err(blob_error)?);
+        keys.sort_unstable();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn flush(&self) -> BichonResult<()> {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) -> BichonResult<()> {
+        if let Some((stop, handle)) = self.worker.lock().unwrap().take() {
+            let _ = stop.send(());
+            let _ = handle.join();
+        }
+        self.inner.flush()?;
+        self.inner.index.shutdown().map_err(blob_error)?;
+        self.inner.staging.shutdown().map_err(blob_error)
+    }
+}
+
+struct Inner {
+    config: S3Config,
+    client: S3Client,
+    index: Engine,
+    staging: Engine,
+    cache: Mutex<ReadCache>,
+    staged: Mutex<Staged>,
+    /// Serializes flushes, deletes and compaction, which all rewrite index
+    /// entries and remove objects.
+    write_lock: Mutex<()>,
+}
+
+/// Blobs staged since the last flush.
+#[derive(Default)]
+struct Staged {
+    bytes: usize,
+    since: Option<Instant>,
+}
+
+impl Inner {
+    fn blob_object(&self, key: &[u8; 32]) -> String {
+        format!("{}blobs/{}", self.config.prefix, hex::encode(key))
+    }
+
+    fn pack_object(&self, id: u64) -> String {
+        format!("{}packs/{:016x}", self.config.prefix, id)
+    }
+
+    /// Uploads `object` and then the manifest describing it.
+    fn upload(
+        &self,
+        object: &str,
+        data: Vec<u8>,
+        entries: &[([u8; 32], Location)],
+    ) -> BichonResult<()> {
+        self.client.put(object, data)?;
+        self.client.put(
+            &format!("{object}{MANIFEST_SUFFIX}"),
+            encode_manifest(entries),
+        )
+    }
+
+    /// Removes `object` and its manifest. The manifest goes first: an object
+    /// left without one is never indexed again and is swept by compaction.
+    fn remove(&self, object: &str) -> BichonResult<()> {
+        self.client.delete(&format!("{object}{MANIFEST_SUFFIX}"))?;
+        self.client.delete(object)
+    }
+
+    fn manifest(&self, object: &str) -> BichonResult<Option<Manifest>> {
+        let manifest = format!("{object}{MANIFEST_SUFFIX}");
+        let Some(bytes) = self.client.get(&manifest)? else {
+            return Ok(None);
+        };
+        decode_manifest(&bytes).map(Some).ok_or_else(|| {
+            raise_error!(
+                format!("corrupt S3 manifest {manifest}"),
+                ErrorCode::InternalError
+            )
+        })
+    }
+
+    /// Indexes every blob listed by a manifest whose object still exists.
+    fn rebuild_index(&self) -> BichonResult<()> {
+        let mut batch = Vec::new();
+        let mut rebuilt = 0;
+        for kind in ["packs/", "blobs/"] {
+            let objects = self
+                .client
+                .list(&format!("{}{}", self.config.prefix, kind))?;
+            let present: HashSet<&str> = objects.iter().map(|o| o.key.as_str()).collect();
+            for object in &objects {
+                let Some(data) = object.key.strip_suffix(MANIFEST_SUFFIX) else {
+                    continue;
+                };
+                if !present.contains(data) {
+                    continue;
+                }
+                for (key, location) in self.manifest(data)?.unwrap_or_default() {
+                    batch.push((key, location.encode(), Codec::None));
+                }
+                if batch.len() >= REBUILD_BATCH {
+                    rebuilt += batch.len();
+                    self.index.put_batch(&batch).map_err(blob_error)?;
+                    batch.clear();
+                }
+            }
+        }
+        if !batch.is_empty() {
+            rebuilt += batch.len();
+            self.index.put_batch(&batch).map_err(blob_error)?;
+        }
+        if rebuilt > 0 {
+            self.index.flush().map_err(blob_error)?;
+            tracing::info!(
+                "Rebuilt the S3 blob index from {} manifest entries",
+                rebuilt
+            );
+        }
+        Ok(())
+    }
+
+    fn location(&self, key: &[u8; 32]) -> BichonResult<Option<Location>> {
+        let Some(bytes) = self.index.get(key).map_err(blob_error)? else {
+            return Ok(None);
+        };
+        Location::decode(&bytes).map(Some).ok_or_else(|| {
+            raise_error!(
+                format!("corrupt S3 index entry for blob {}", hex::encode(key)),
+                ErrorCode::InternalError
+            )
+        })
+    }
+
+    fn exists(&self, key: &[u8; 32]) -> BichonResult<bool> {
+        Ok(self.staging.exists(key).map_err(blob_error)?
+            || self.index.exists(key).map_err(blob_error)?)
+    }
+
+    fn put(&self, key: [u8; 32], value: &[u8]) -> BichonResult<()> {
+        if self.exists(&key)? {
+            return Ok(());
+        }
+        if value.len() >= self.config.pack_threshold {
+            let (data, codec) = compress(value, Codec::Zstd, COMPRESS_THRESHOLD, 0);
+            let location = Location {
+                pack: None,
+                offset: 0,
+                len: data.len() as u64,
+                raw_len: value.len() as u64,
+                codec,
+            };
+            self.upload(&self.blob_object(&key), data, &[(key, location)])?;
+            return self
+                .index
+                .put(key, &location.encode(), Codec::None)
+                .map_err(blob_error);
+        }
+        self.staging
+            .put(key, value, Codec::Lz4)
+            .map_err(blob_error)?;
+        let mut staged = self.staged.lock().unwrap();
+        staged.bytes += value.len();
+        staged.since.get_or_insert_with(Instant::now);
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8; 32]) -> BichonResult<Option<Vec<u8>>> {
+        if let Some(value) = self.cache.lock().unwrap().get(key) {
+            return Ok(Some(value));
+        }
+        // Staging before the index: a flush indexes blobs before it drops
+        // the staged copies.
+        if let Some(value) = self.staging.get(key).map_err(blob_error)? {
+            return Ok(Some(value));
+        }
+        let Some(mut location) = self.location(key)? else {
+            return Ok(None);
+        };
+        loop {
+            let data = match location.pack {
+                Some(pack) => {
+                    self.client
+                        .get_range(&self.pack_object(pack), location.offset, location.len)?
+                }
+                None => self.client.get(&self.blob_object(key))?,
+            };
+            if let Some(data) = data {
+                let value = decompress(&data, location.codec, location.raw_len as usize)
+                    .map_err(blob_error)?;
+                self.cache.lock().unwrap().insert(*key, value.clone());
+                return Ok(Some(value));
+            }
+            // Compaction may have moved the blob since the index was read.
+            match self.location(key)? {
+                None => return Ok(None),
+                Some(current) if current != location => location = current,
+                Some(_) => {
+                    return Err(raise_error!(
+                        format!(
+                            "blob {} is indexed but missing from the bucket",
+                            hex::encode(key)
+                        ),
+                        ErrorCode::InternalError
+                    ))
+                }
+            }
+        }
+    }
+
+    fn delete_batch(&self, keys: &[[u8; 32]]) -> BichonResult<()> {
+        let _guard = self.write_lock.lock().unwrap();
+        let mut staged = Vec::new();
+        let mut indexed = Vec::new();
+        let mut objects = Vec::new();
+        for key in keys {
+            if self.staging.exists(key).map_err(blob_error)? {
+                staged.push(*key);
+            }
+            if let Some(location) = self.location(key)? {
+                indexed.push(*key);
+                if location.pack.is_none() {
+                    objects.push(self.blob_object(key));
+                }
+            }
+        }
+        if !staged.is_empty() {
+            self.staging.delete_batch(&staged).map_err(blob_error)?;
+        }
+        if !indexed.is_empty() {
+            // Index first: a crash in between leaks an object rather than
+            // leaving an entry that points nowhere.
+            self.index.delete_batch(&indexed).map_err(blob_error)?;
+            self.index.flush().map_err(blob_error)?;
+        }
+        for object in objects {
+            self.remove(&object)?;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        for key in keys {
+            cache.remove(key);
+        }
+        Ok(())
+    }
+
+    fn flush_due(&self) -> bool {
+        let staged = self.staged.lock().unwrap();
+        staged.bytes >= self.config.pack_size
+            || staged
+                .since
+                .is_some_and(|since| since.elapsed() >= self.config.max_staged_age)
+    }
+
+    /// Uploads all staged blobs in packs.
+    fn flush(&self) -> BichonResult<()> {
+        let _guard = self.write_lock.lock().unwrap();
+        // Reset before listing, so blobs staged meanwhile count towards the
+        // next flush.
+        *self.staged.lock().unwrap() = Staged::default();
+        let keys = self.staging.keys().map_err(blob_error)?;
+        let mut pack = PackBuilder::default();
+        let mut done = Vec::new();
+        for key in keys {
+            if self.index.exists(&key).map_err(blob_error)? {
+                done.push(key);
+                continue;
+            }
+            let Some(value) = self.staging.get(&key).map_err(blob_error)? else {
+                continue;
+            };
+            let (data, codec) = compress(&value, Codec::Zstd, COMPRESS_THRESHOLD, 0);
+            pack.add(key, &data, value.len() as u64, codec);
+            if pack.data.len() >= self.config.pack_size {
+                done.extend(self.upload_pack(std::mem::take(&mut pack))?);
+            }
+        }
+        if !pack.entries.is_empty() {
+            done.extend(self.upload_pack(pack)?);
+        }
+        if !done.is_empty() {
+            self.staging.delete_batch(&done).map_err(blob_error)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads `pack` and points the index at it. Returns the packed keys.
+    fn upload_pack(&self, pack: PackBuilder) -> BichonResult<Vec<[u8; 32]>> {
+        let id = rand::random::<u64>();
+        let entries: Vec<([u8; 32], Location)> = pack
+            .entries
+            .into_iter()
+            .map(|(key, location)| {
+                let location = Location {
+                    pack: Some(id),
+                    ..location
+                };
+                (key, location)
+            })
+            .collect();
+        // The manifest lands before the index points into the pack.
+        self.upload(&self.pack_object(id), pack.data, &entries)?;
+        let encoded: Vec<([u8; 32], Vec<u8>, Codec)> = entries
+            .iter()
+            .map(|(key, location)| (*key, location.encode(), Codec::None))
+            .collect();
+        self.index.put_batch(&encoded).map_err(blob_error)?;
+        self.index.flush().map_err(blob_error)?;
+        Ok(entries.into_iter().map(|(key, _)| key).collect())
+    }
+
+    fn compact(&self) -> BichonResult<CompactionStats> {
+        let _guard = self.write_lock.lock().unwrap();
+        let mut stats = CompactionStats::default();
+        let packs_prefix = format!("{}packs/", self.config.prefix);
+        'packs: for object in self.client.list(&packs_prefix)? {
+            let is_pack = object
+                .key
+                .strip_prefix(&packs_prefix)
+                .is_some_and(|id| u64::from_str_radix(id, 16).is_ok());
+            if !is_pack {
+                continue;
+            }
+            // A pack without a manifest was never indexed: its upload was
+            // interrupted, or its removal was.
+            let mut entries = Vec::new();
+            for (key, location) in self.manifest(&object.key)?.unwrap_or_default() {
+                if self.location(&key)? == Some(location) {
+                    entries.push((key, location));
+                }
+            }
+            let live_bytes: u64 = entries.iter().map(|(_, location)| location.len).sum();
+            if entries.is_empty() {
+                self.remove(&object.key)?;
+                stats.packs_deleted += 1;
+                stats.bytes_reclaimed += object.size;
+            } else if (live_bytes as f64) < object.size as f64 * MIN_LIVE_RATIO {
+                let Some(data) = self.client.get(&object.key)? else {
+                    continue;
+                };
+                let mut pack = PackBuilder::default();
+                for (key, location) in &entries {
+                    let start = location.offset as usize;
+                    let Some(slice) = data.get(start..start + location.len as usize) else {
+                        tracing::warn!(
+                            "S3 pack {} is shorter than its index entries, keeping it",
+                            object.key
+                        );
+                        continue 'packs;
+                    };
+                    pack.add(*key, slice, location.raw_len, location.codec);
+                }
+                self.upload_pack(pack)?;
+                self.remove(&object.key)?;
+                stats.packs_rewritten += 1;
+                stats.bytes_reclaimed += object.size - live_bytes;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+fn run_worker(inner: Arc<Inner>, stopped: mpsc::Receiver<()>) {
+    let mut last_compaction = Instant::now();
+    loop {
+        match stopped.recv_timeout(WORKER_TICK) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return,
+        }
+        if inner.flush_due() {
+            if let Err(e) = inner.flush() {
+                tracing::error!("failed to upload staged blobs to S3: {:#?}", e);
+            }
+        }
+        if last_compaction.elapsed() >= inner.config.compact_interval {
+            last_compaction = Instant::now();
+            match inner.compact() {
+                Ok(stats) if stats != CompactionStats::default() => {
+                    tracing::info!("S3 blob store compaction: {:?}", stats)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("S3 blob store compaction failed: {:#?}", e),
+            }
+        }
+    }
+}
+
+/// A pack being assembled. Locations are relative to the pack.
+#[derive(Default)]
+struct PackBuilder {
+    data: Vec<u8>,
+    entries: Vec<([u8; 32], Location)>,
+}
+
+impl PackBuilder {
+    fn add(&mut self, key: [u8; 32], data: &[u8], raw_len: u64, codec: Codec) {
+        self.entries.push((
+            key,
+            Location {
+                pack: None,
+                offset: self.data.len() as u64,
+                len: data.len() as u64,
+                raw_len,
+                codec,
+            },
+        ));
+        self.data.extend_from_slice(data);
+    }
+}
+
+/// An LRU cache bounded by the total size of its values.
+struct ReadCache {
+    entries: LruCache<[u8; 32], Vec<u8>>,
+    bytes: u64,
+    capacity: u64,
+}
+
+impl ReadCache {
+    fn new(capacity: u64) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            bytes: 0,
+            capacity,
+        }
+    }
+
+    fn get(&mut self, key: &[u8; 32]) -> Option<Vec<u8>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: [u8; 32], value: Vec<u8>) {
+        if value.len() as u64 > self.capacity {
+            return;
+        }
+        self.bytes += value.len() as u64;
+        if let Some(old) = self.entries.put(key, value) {
+            self.bytes -= old.len() as u64;
+        }
+        while self.bytes > self.capacity {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.bytes -= evicted.len() as u64,
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[u8; 32]) {
+        if let Some(old) = self.entries.pop(key) {
+            self.bytes -= old.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::blob::mock_s3::{MockS3Server, BUCKET};
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join("bichon-s3-test")
+            .join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn open(server: &MockS3Server, dir: &Path) -> S3BlobStore {
+        let mut config =
+            S3Config::new(&server.endpoint(), BUCKET, "test", "secret").with_prefix("archive");
+        config.pack_threshold = 4096;
+        config.pack_size = 64 * 1024;
+        config.cache_bytes = 16 * 1024;
+        S3BlobStore::open(config, dir).unwrap()
+    }
+
+    fn blob(seed: u8, len: usize) -> ([u8; 32], Vec<u8>) {
+        let value: Vec<u8> = (0..len).map(|i| (i as u8).wrapping_mul(seed)).collect();
+        (*blake3::hash(&value).as_bytes(), value)
+    }
+
+    /// Data objects of one kind, without their manifests.
+    fn objects(server: &MockS3Server, kind: &str) -> Vec<String> {
+        let prefix = format!("archive/{kind}/");
+        server
+            .keys()
+            .into_iter()
+            .filter(|k| k.starts_with(&prefix) && !k.ends_with(MANIFEST_SUFFIX))
+            .collect()
+    }
+
+    #[test]
+    fn small_blobs_are_packed_and_large_ones_stored_alone() {
+        let server = MockS3Server::start(1000);
+        let dir = temp_dir();
+        let store = open(&server, &dir);
+
+        let small: Vec<_> = (1..=20).map(|seed| blob(seed, 1500)).collect();
+        let (large_key, large) = blob(99, 10_000);
+        for (key, value) in &small {
+            store.put(*key, value).unwrap();
+        }
+        store.put(large_key, &large).unwrap();
+
+        // Staged blobs are readable before they reach the bucket.
+        assert!(objects(&server, "packs").is_empty());
+        assert_eq!(store.get(&small[0].0).unwrap().unwrap(), small[0].1);
+        assert_eq!(
+            objects(&server, "blobs"),
+            vec![format!("archive/blobs/{}", hex::encode(large_key))]
+        );
+
+        store.flush().unwrap();
+        assert_eq!(objects(&server, "packs").len(), 1);
+        for (key, value) in &small {
+            assert_eq!(store.get(key).unwrap().as_ref(), Some(value));
+        }
+        assert_eq!(store.get(&large_key).unwrap().unwrap(), large);
+        assert_eq!(store.keys().unwrap().len(), 21);
+
+        // Content-hash dedup: storing a known key uploads nothing.
+        let before = server.requests().len();
+        store.put(large_key, &large).unwrap();
+        store.put(small[0].0, &small[0].1).unwrap();
+        store.flush().unwrap();
+        assert_eq!(server.requests().len(), before);
+        store.shutdown().unwrap();
+    }
+
+    #[test]
+    fn reads_are_cached_and_survive_a_restart() {
+        let server = MockS3Server::start(1000);
+        let dir = temp_dir();
+        let (key, value) = blob(3, 2000);
+        {
+            let store = open(&server, &dir);
+            store.put(key, &value).unwrap();
+            // Shutdown uploads what is still staged.
+            store.shutdown().unwrap();
+        }
+        assert_eq!(objects(&server, "packs").len(), 1);
+
+        let store = open(&server, &dir);
+        assert_eq!(store.get(&key).unwrap().unwrap(), value);
+        let before = server.requests().len();
+        assert_eq!(store.get(&key).unwrap().unwrap(), value);
+        assert_eq!(server.requests().len(), before);
+
+        let range_reads = server
+            .requests()
+            .iter()
+            .filter(|r| r.headers.contains_key("range"))
+            .count();
+        assert_eq!(range_reads, 1);
+        store.shutdown().unwrap();
+    }
+
+    #[test]
+    fn deletes_and_compaction_reclaim_space() {
+        let server = MockS3Server::start(1000);
+        let dir = temp_dir();
+        let store = open(&server, &dir);
+
+        let first: Vec<_> = (1..=4).map(|seed| blob(seed, 3000)).collect();
+        for (key, value) in &first {
+            store.put(*key, value).unwrap();
+        }
+        store.flush().unwrap();
+        let second: Vec<_> = (5..=8).map(|seed| blob(seed, 3000)).collect();
+        for (key, value) in &second {
+            store.put(*key, value).unwrap();
+        }
+        store.flush().unwrap();
+        let (large_key, large) = blob(42, 8000);
+        store.put(large_key, &large).unwrap();
+        assert_eq!(objects(&server, "packs").len(), 2);
+
+        // Everything from the first pack, most of the second and the large
+        // blob go away.
+        let mut doomed: Vec<_> = first.iter().map(|(k, _)| *k).collect();
+        doomed.extend(second[..3].iter().map(|(k, _)| *k));
+        doomed.push(large_key);
+        store.delete_batch(&doomed).unwrap();
+        assert!(objects(&server, "blobs").is_empty());
+        assert_eq!(store.get(&first[0].0).unwrap(), None);
+        assert_eq!(store.get(&large_key).unwrap(), None);
+
+        let stats = store.compact().unwrap();
+        assert_eq!(stats.packs_deleted, 1);
+        assert_eq!(stats.packs_rewritten, 1);
+        assert_eq!(objects(&server, "packs").len(), 1);
+        assert_eq!(store.keys().unwrap(), vec![second[3].0]);
+        assert_eq!(store.get(&second[3].0).unwrap().unwrap(), second[3].1);
+        store.shutdown().unwrap();
+    }
+
+    #[test]
+    fn lost_index_is_rebuilt_from_manifests() {
+        let server = MockS3Server::start(1000);
+        let dir = temp_dir();
+        let small: Vec<_> = (1..=6).map(|seed| blob(seed, 2000)).collect();
+        let (large_key, large) = blob(77, 9000);
+        {
+            let store = open(&server, &dir);
+            for (key, value) in &small {
+                store.put(*key, value).unwrap();
+            }
+            store.put(large_key, &large).unwrap();
+            store.shutdown().unwrap();
+        }
+        for object in objects(&server, "packs")
+            .into_iter()
+            .chain(objects(&server, "blobs"))
+        {
+            assert!(server
+                .object(&format!("{object}{MANIFEST_SUFFIX}"))
+                .is_some());
+        }
+
+        std::fs::remove_dir_all(dir.join("index")).unwrap();
+        let store = open(&server, &dir);
+        assert_eq!(store.keys().unwrap().len(), 7);
+        for (key, value) in &small {
+            assert_eq!(store.get(key).unwrap().as_ref(), Some(value));
+        }
+        assert_eq!(store.get(&large_key).unwrap().unwrap(), large);
+
+        // Every pack is live again, so compaction leaves the bucket alone.
+        let before = server.keys();
+        assert_eq!(store.compact().unwrap(), CompactionStats::default());
+        assert_eq!(server.keys(), before);
+        store.shutdown().unwrap();
+    }
+
+    #[test]
+    fn packs_without_a_manifest_are_swept() {
+        let server = MockS3Server::start(1000);
+        let dir = temp_dir();
+        let store = open(&server, &dir);
+        let (key, value) = blob(9, 2000);
+        store.put(key, &value).unwrap();
+        store.flush().unwrap();
+
+        // An upload interrupted between the pack and its manifest.
+        let client = server.client();
+        client
+            .put("archive/packs/00000000000000ff", vec![0; 100])
+            .unwrap();
+        let stats = store.compact().unwrap();
+        assert_eq!(stats.packs_deleted, 1);
+        assert_eq!(objects(&server, "packs").len(), 1);
+        assert_eq!(store.get(&key).unwrap().unwrap(), value);
+        store.shutdown().unwrap();
+    }
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let server = MockS3Server::start(1000);
+        let dir = temp_dir();
+        let store = open(&server, &dir);
+        let (key, value) = blob(7, 5000);
+
+        server.fail_next(2);
+        store.put(key, &value).unwrap();
+        assert_eq!(store.get(&key).unwrap().unwrap(), value);
+        store.shutdown().unwrap();
+    }
+
+    #[test]
+    fn read_cache_stays_within_budget() {
+        let mut cache = ReadCache::new(10);
+        cache.insert([1; 32], vec![0; 4]);
+        cache.insert([2; 32], vec![0; 4]);
+        assert!(cache.get(&[1; 32]).is_some());
+        cache.insert([3; 32], vec![0; 4]);
+        assert_eq!(cache.bytes, 8);
+        assert!(cache.get(&[2; 32]).is_none());
+        cache.insert([4; 32], vec![0; 11]);
+        assert!(cache.get(&[4; 32]).is_none());
+    }
+}
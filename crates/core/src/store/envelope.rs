@@ -20,6 +20,8 @@
 use serde::{Deserialize, Serialize};
 use tantivy::doc;
 
+use crate::envelope::authentication::AuthenticationSummary;
+
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
 #[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
 pub struct Envelope {
@@ -45,6 +47,25 @@ pub struct Envelope {
     pub attachment_count: usize,
     pub regular_attachment_count: usize,
     pub tags: Option<Vec<String>>,
+    /// IMAP flags and keywords as last seen on the server, e.g. `\Seen`,
+    /// `$Forwarded`. Keywords are lower-cased.
+    #[serde(default)]
+    pub flags: Option<Vec<String>>,
+    /// Set once the message was expunged on the IMAP server. The archived
+    /// copy is kept.
+    #[serde(default)]
+    pub deleted_on_source: bool,
+    /// Gmail labels (`X-GM-LABELS`) of messages archived in Gmail sync mode,
+    /// with system labels named as in Takeout (`Inbox`, `Starred`).
+    #[serde(default)]
+    pub labels: Option<Vec<String>>,
+    /// Gmail's stable message identity (`X-GM-MSGID`).
+    #[serde(default)]
+    pub gmail_msg_id: Option<u64>,
+    /// DKIM and S/MIME/OpenPGP signature verdicts from ingest. `None` for
+    /// messages archived before verification existed.
+    #[serde(default)]
+    pub authentication: Option<AuthenticationSummary>,
     pub content_hash: String,
 }
 
@@ -0,0 +1,774 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tantivy::columnar::StrColumn;
+use tantivy::schema::Term;
+use tantivy::{DocId, SegmentReader};
+
+use crate::admin::meta::{list_account_ids, list_mailbox_ids, list_remote_content_hashes};
+use crate::database::manager::DB_MANAGER;
+use crate::envelope::extractor::recover_message_blob;
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::raise_error;
+use crate::store::blob::{backend::BlobStore, hex_to_key, BLOB_MANAGER};
+use crate::store::tantivy::attachment::ATTACHMENT_MANAGER;
+use crate::store::tantivy::envelope::ENVELOPE_MANAGER;
+use crate::store::tantivy::fields::{
+    F_ACCOUNT_ID, F_ATTACHMENT_CONTENT_HASH, F_CONTENT_HASH, F_ENVELOPE_ID, F_ID, F_MAILBOX_ID,
+};
+use crate::store::tantivy::schema::SchemaTools;
+use crate::store::tantivy::shard::{ShardLock, ShardSearcher, ShardedIndex};
+
+// ─── Types ────────────────────────────────────────────────────────────────────
+
+/// Repairs to apply after the consistency check. Everything is off by default,
+/// so an empty request only reports.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct FsckRepair {
+    /// Delete attachment documents whose parent envelope is gone.
+    #[serde(default)]
+    pub delete_orphan_attachments: bool,
+    /// Delete envelopes (and their attachment documents) whose mailbox or
+    /// account no longer exists.
+    #[serde(default)]
+    pub delete_orphan_envelopes: bool,
+    /// Delete blobs no document references. Only allowed while the server is
+    /// stopped, because ingestion stores blobs before the index commit.
+    #[serde(default)]
+    pub delete_orphan_blobs: bool,
+    /// Re-fetch messages with missing blobs from the source server. Only works
+    /// for IMAP accounts whose message is still on the server unchanged.
+    #[serde(default)]
+    pub recover_missing_blobs: bool,
+}
+
+impl FsckRepair {
+    fn touches_indexes(&self) -> bool {
+        self.delete_orphan_attachments || self.delete_orphan_envelopes
+    }
+}
+
+/// A blob referenced by an envelope that is absent from the blob store.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct MissingBlob {
+    pub envelope_id: String,
+    pub account_id: u64,
+    pub mailbox_id: u64,
+    pub content_hash: String,
+}
+
+/// An attachment document whose parent envelope is not in the envelope index.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct OrphanAttachment {
+    pub attachment_id: String,
+    pub account_id: u64,
+    pub envelope_id: String,
+    pub content_hash: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Enum))]
+pub enum OrphanReason {
+    /// The account was removed.
+    AccountDeleted,
+    /// The account exists but the mailbox was removed.
+    MailboxDeleted,
+}
+
+/// An envelope pointing at an account or mailbox that no longer exists.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct OrphanEnvelope {
+    pub envelope_id: String,
+    pub account_id: u64,
+    pub mailbox_id: u64,
+    pub reason: OrphanReason,
+}
+
+/// A message whose blobs could not be re-fetched.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct RecoveryFailure {
+    pub envelope_id: String,
+    pub reason: String,
+}
+
+/// What the repair pass changed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct FsckRepaired {
+    pub attachments_deleted: u64,
+    pub envelopes_deleted: u64,
+    pub blobs_deleted: u64,
+    /// Messages whose blobs were re-fetched and stored again.
+    pub messages_recovered: u64,
+    pub recovery_failed: Vec<RecoveryFailure>,
+}
+
+/// Result of a consistency check across the envelope index, the attachment
+/// index, the blob store and the account/mailbox metadata.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct FsckReport {
+    pub envelopes_checked: u64,
+    pub attachments_checked: u64,
+    pub blobs_checked: u64,
+    /// Envelopes whose EML blob is missing.
+    pub missing_email_blobs: Vec<MissingBlob>,
+    /// Envelopes referencing an attachment blob that is missing.
+    pub missing_attachment_blobs: Vec<MissingBlob>,
+    /// Blobs no envelope or attachment document references, as content hashes.
+    pub orphan_blobs: Vec<String>,
+    pub orphan_attachments: Vec<OrphanAttachment>,
+    pub orphan_envelopes: Vec<OrphanEnvelope>,
+    /// Present when repairs were requested.
+    pub repaired: Option<FsckRepaired>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing_email_blobs.is_empty()
+            && self.missing_attachment_blobs.is_empty()
+            && self.orphan_blobs.is_empty()
+            && self.orphan_attachments.is_empty()
+            && self.orphan_envelopes.is_empty()
+    }
+}
+
+/// Accounts and mailboxes that currently exist in the metadata database.
+pub struct FsckOwners {
+    pub accounts: HashSet<u64>,
+    pub mailboxes: HashSet<u64>,
+    /// Blobs cached by the remote content proxy, which no index references.
+    pub remote_content: HashSet<String>,
+}
+
+// ─── Check ────────────────────────────────────────────────────────────────────
+
+/// Cross-checks both indexes against the blob store and the metadata database.
+///
+/// Only FAST columns are read from the indexes. Every distinct content hash is
+/// looked up in the blob store once, and the referenced hashes, together with
+/// the blobs cached by the remote content proxy, are then subtracted from the
+/// full key listing to find orphan blobs.
+pub fn check(
+    envelopes: &ShardSearcher,
+    attachments: &ShardSearcher,
+    store: &dyn BlobStore,
+    owners: &FsckOwners,
+) -> BichonResult<FsckReport> {
+    let mut report = FsckReport::default();
+    // Referenced blob key -> whether it exists in the store.
+    let mut referenced: HashMap<[u8; 32], bool> = HashMap::new();
+    let mut exists = |hash: &str| -> BichonResult<bool> {
+        // A malformed hash cannot name a stored blob.
+        let Ok(key) = hex_to_key(hash) else {
+            return Ok(false);
+        };
+        if let Some(found) = referenced.get(&key) {
+            return Ok(*found);
+        }
+        let found = store.exists(&key)?;
+        referenced.insert(key, found);
+        Ok(found)
+    };
+
+    let mut envelope_ids = HashSet::new();
+    for segment in envelopes.segment_readers() {
+        let account_col = u64_column(segment, F_ACCOUNT_ID)?;
+        let mailbox_col = u64_column(segment, F_MAILBOX_ID)?;
+        let id_col = str_column(segment, F_ID)?;
+        let hash_col = str_column(segment, F_CONTENT_HASH)?;
+        let attachment_hash_col = str_column(segment, F_ATTACHMENT_CONTENT_HASH)?;
+
+        for doc_id in 0..segment.max_doc() {
+            if segment.is_deleted(doc_id) {
+                continue;
+            }
+            report.envelopes_checked += 1;
+            let envelope_id = first_str(&id_col, doc_id)?;
+            let account_id = account_col.values.get_val(doc_id);
+            let mailbox_id = mailbox_col.values.get_val(doc_id);
+            let missing = |content_hash: String| MissingBlob {
+                envelope_id: envelope_id.clone(),
+                account_id,
+                mailbox_id,
+                content_hash,
+            };
+
+            let content_hash = first_str(&hash_col, doc_id)?;
+            if !exists(&content_hash)? {
+                report.missing_email_blobs.push(missing(content_hash));
+            }
+            for hash in all_str(&attachment_hash_col, doc_id)? {
+                if !exists(&hash)? {
+                    report.missing_attachment_blobs.push(missing(hash));
+                }
+            }
+
+            let reason = if !owners.accounts.contains(&account_id) {
+                Some(OrphanReason::AccountDeleted)
+            } else if !owners.mailboxes.contains(&mailbox_id) {
+                Some(OrphanReason::MailboxDeleted)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                report.orphan_envelopes.push(OrphanEnvelope {
+                    envelope_id: envelope_id.clone(),
+                    account_id,
+                    mailbox_id,
+                    reason,
+                });
+            }
+            envelope_ids.insert(envelope_id);
+        }
+    }
+
+    for segment in attachments.segment_readers() {
+        let account_col = u64_column(segment, F_ACCOUNT_ID)?;
+        let id_col = str_column(segment, F_ID)?;
+        let envelope_col = str_column(segment, F_ENVELOPE_ID)?;
+        let hash_col = str_column(segment, F_CONTENT_HASH)?;
+
+        for doc_id in 0..segment.max_doc() {
+            if segment.is_deleted(doc_id) {
+                continue;
+            }
+            report.attachments_checked += 1;
+            let content_hash = first_str(&hash_col, doc_id)?;
+            // Missing attachment blobs are reported through their envelope;
+            // this only marks the blob as referenced.
+            exists(&content_hash)?;
+            let envelope_id = first_str(&envelope_col, doc_id)?;
+            if !envelope_ids.contains(&envelope_id) {
+                report.orphan_attachments.push(OrphanAttachment {
+                    attachment_id: first_str(&id_col, doc_id)?,
+                    account_id: account_col.values.get_val(doc_id),
+                    envelope_id,
+                    content_hash,
+                });
+            }
+        }
+    }
+
+    for hash in &owners.remote_content {
+        exists(hash)?;
+    }
+
+    let keys = store.keys()?;
+    report.blobs_checked = keys.len() as u64;
+    report.orphan_blobs = keys
+        .into_iter()
+        .filter(|key| !referenced.contains_key(key))
+        .map(hex::encode)
+        .collect();
+
+    Ok(report)
+}
+
+// ─── Repair ───────────────────────────────────────────────────────────────────
+
+/// Applies the index and blob repairs selected in `options` to the findings of
+/// [`check`] and records what changed in `report.repaired`.
+///
+/// Documents are deleted through the shard of their account, so `envelopes`
+/// and `attachments` must hold the locks from [`ShardedIndex::commit_all`]
+/// taken before the check. An account without a shard has nothing to delete.
+///
+/// Orphan blobs are only deleted when `store` is given, which callers do only
+/// while nothing else writes to the store. Blobs that become unreferenced by
+/// deleting documents here are picked up by the next check.
+pub fn repair(
+    report: &mut FsckReport,
+    options: &FsckRepair,
+    envelopes: &mut [ShardLock],
+    attachments: &mut [ShardLock],
+    store: Option<&dyn BlobStore>,
+) -> BichonResult<()> {
+    let email_fields = SchemaTools::email_fields();
+    let attachment_fields = SchemaTools::attachment_fields();
+    let repaired = report.repaired.get_or_insert_with(FsckRepaired::default);
+    let mut envelopes: HashMap<u64, &mut ShardLock> =
+        envelopes.iter_mut().map(|lock| (lock.id(), lock)).collect();
+    let mut attachments: HashMap<u64, &mut ShardLock> = attachments
+        .iter_mut()
+        .map(|lock| (lock.id(), lock))
+        .collect();
+    let mut touched_envelopes = BTreeSet::new();
+    let mut touched_attachments = BTreeSet::new();
+
+    if options.delete_orphan_attachments {
+        for orphan in &report.orphan_attachments {
+            if let Some(lock) = attachments.get_mut(&orphan.account_id) {
+                lock.writer()?.delete_term(Term::from_field_text(
+                    attachment_fields.f_id,
+                    &orphan.attachment_id,
+                ));
+                touched_attachments.insert(orphan.account_id);
+            }
+            repaired.attachments_deleted += 1;
+        }
+    }
+    if options.delete_orphan_envelopes {
+        for orphan in &report.orphan_envelopes {
+            if let Some(lock) = envelopes.get_mut(&orphan.account_id) {
+                lock.writer()?.delete_term(Term::from_field_text(
+                    email_fields.f_id,
+                    &orphan.envelope_id,
+                ));
+                touched_envelopes.insert(orphan.account_id);
+            }
+            if let Some(lock) = attachments.get_mut(&orphan.account_id) {
+                lock.writer()?.delete_term(Term::from_field_text(
+                    attachment_fields.f_envelope_id,
+                    &orphan.envelope_id,
+                ));
+                touched_attachments.insert(orphan.account_id);
+            }
+            repaired.envelopes_deleted += 1;
+        }
+    }
+    for (locks, touched) in [
+        (&mut attachments, touched_attachments),
+        (&mut envelopes, touched_envelopes),
+    ] {
+        for account_id in touched {
+            if let Some(lock) = locks.get_mut(&account_id) {
+                lock.writer()?
+                    .commit()
+                    .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            }
+        }
+    }
+
+    if options.delete_orphan_blobs {
+        let store = store.ok_or_else(|| {
+            raise_error!(
+                "Orphan blobs can only be deleted while the server is stopped".into(),
+                ErrorCode::InvalidParameter
+            )
+        })?;
+        let keys = report
+            .orphan_blobs
+            .iter()
+            .map(|hash| hex_to_key(hash))
+            .collect::<BichonResult<Vec<_>>>()?;
+        if !keys.is_empty() {
+            store.delete_batch(&keys)?;
+        }
+        repaired.blobs_deleted = keys.len() as u64;
+    }
+    Ok(())
+}
+
+// ─── Offline entry point ──────────────────────────────────────────────────────
+
+/// Envelope and attachment indexes opened straight from disk, for the admin
+/// tool while the server is stopped.
+pub struct OfflineIndexes {
+    envelopes: ShardedIndex,
+    attachments: ShardedIndex,
+}
+
+impl OfflineIndexes {
+    pub fn open(envelope_dir: &Path, attachment_dir: &Path) -> BichonResult<Self> {
+        Ok(Self {
+            envelopes: ShardedIndex::open(envelope_dir, SchemaTools::email_schema())?,
+            attachments: ShardedIndex::open(attachment_dir, SchemaTools::attachment_schema())?,
+        })
+    }
+
+    pub fn check(&self, store: &dyn BlobStore, owners: &FsckOwners) -> BichonResult<FsckReport> {
+        check(
+            &self.envelopes.searcher(None)?,
+            &self.attachments.searcher(None)?,
+            store,
+            owners,
+        )
+    }
+
+    /// Applies `options` to a report from [`OfflineIndexes::check`], including
+    /// orphan blob deletion. Missing blobs cannot be recovered offline.
+    pub async fn repair(
+        &self,
+        report: &mut FsckReport,
+        options: &FsckRepair,
+        store: &dyn BlobStore,
+    ) -> BichonResult<()> {
+        if options.recover_missing_blobs {
+            return Err(raise_error!(
+                "Missing blobs can only be recovered while the server is running".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        let mut envelopes = self.envelopes.commit_all().await;
+        let mut attachments = self.attachments.commit_all().await;
+        repair(
+            report,
+            options,
+            &mut envelopes,
+            &mut attachments,
+            Some(store),
+        )
+    }
+}
+
+// ─── Online entry point ───────────────────────────────────────────────────────
+
+/// Runs the consistency check against the live indexes and blob store.
+///
+/// The writers of every shard of both indexes are held for the whole check
+/// when index repairs are requested, so no document is added between finding
+/// an orphan and deleting it. Messages ingested while a report-only check runs may show up as
+/// missing blobs, since blobs are stored in the background.
+pub async fn run_fsck(options: FsckRepair) -> BichonResult<FsckReport> {
+    if options.delete_orphan_blobs {
+        return Err(raise_error!(
+            "Orphan blobs can only be deleted with the offline admin tool while the server is stopped"
+                .into(),
+            ErrorCode::InvalidParameter
+        ));
+    }
+    let owners = FsckOwners {
+        accounts: list_account_ids(DB_MANAGER.db())?,
+        mailboxes: list_mailbox_ids(DB_MANAGER.db())?,
+        remote_content: list_remote_content_hashes(DB_MANAGER.db())?,
+    };
+    let store = BLOB_MANAGER.store();
+
+    let mut report = if options.touches_indexes() {
+        let mut envelopes = ENVELOPE_MANAGER.shards().commit_all().await;
+        let mut attachments = ATTACHMENT_MANAGER.shards().commit_all().await;
+        let mut report = check(
+            &ENVELOPE_MANAGER.create_searcher()?,
+            &ATTACHMENT_MANAGER.create_searcher()?,
+            store,
+            &owners,
+        )?;
+        repair(
+            &mut report,
+            &options,
+            &mut envelopes,
+            &mut attachments,
+            None,
+        )?;
+        report
+    } else {
+        check(
+            &ENVELOPE_MANAGER.create_searcher()?,
+            &ATTACHMENT_MANAGER.create_searcher()?,
+            store,
+            &owners,
+        )?
+    };
+
+    if options.recover_missing_blobs {
+        recover_missing_blobs(&mut report).await;
+    }
+    Ok(report)
+}
+
+/// Re-fetches every message with a missing EML or attachment blob from its
+/// source server. Envelopes already flagged as orphans are skipped.
+async fn recover_missing_blobs(report: &mut FsckReport) {
+    let orphans: HashSet<&str> = report
+        .orphan_envelopes
+        .iter()
+        .map(|o| o.envelope_id.as_str())
+        .collect();
+    let targets: BTreeSet<(u64, String)> = report
+        .missing_email_blobs
+        .iter()
+        .chain(report.missing_attachment_blobs.iter())
+        .filter(|m| !orphans.contains(m.envelope_id.as_str()))
+        .map(|m| (m.account_id, m.envelope_id.clone()))
+        .collect();
+
+    let repaired = report.repaired.get_or_insert_with(FsckRepaired::default);
+    for (account_id, envelope_id) in targets {
+        let result = match ENVELOPE_MANAGER.get_envelope_by_id(account_id, &envelope_id) {
+            Ok(Some(found)) => recover_message_blob(&found.envelope).await.map(|_| ()),
+            Ok(None) => Err(raise_error!(
+                "Envelope no longer exists".into(),
+                ErrorCode::ResourceNotFound
+            )),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => repaired.messages_recovered += 1,
+            Err(e) => repaired.recovery_failed.push(RecoveryFailure {
+                envelope_id,
+                reason: e.to_string(),
+            }),
+        }
+    }
+}
+
+// ─── Internals ────────────────────────────────────────────────────────────────
+
+fn u64_column(segment: &SegmentReader, name: &str) -> BichonResult<tantivy::columnar::Column<u64>> {
+    segment
+        .fast_fields()
+        .u64(name)
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))
+}
+
+fn str_column(segment: &SegmentReader, name: &str) -> BichonResult<StrColumn> {
+    segment
+        .fast_fields()
+        .str(name)
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?
+        .ok_or_else(|| {
+            raise_error!(
+                format!("FAST str column '{}' not found in segment", name),
+                ErrorCode::InternalError
+            )
+        })
+}
+
+fn all_str(col: &StrColumn, doc_id: DocId) -> BichonResult<Vec<String>> {
+    col.ords()
+        .values_for_doc(doc_id)
+        .map(|ord| {
+            let mut buf = String::new();
+            col.ord_to_str(ord, &mut buf)
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            Ok(buf)
+        })
+        .collect()
+}
+
+fn first_str(col: &StrColumn, doc_id: DocId) -> BichonResult<String> {
+    Ok(all_str(col, doc_id)?.into_iter().next().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bichon_blob::{Codec, Config, Engine};
+    use std::fs;
+    use tantivy::doc;
+
+    fn temp_dir(prefix: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join("bichon-fsck-test")
+            .join(prefix)
+            .join(uuid::Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn hash(byte: u8) -> String {
+        hex::encode([byte; 32])
+    }
+
+    struct Fixture {
+        engine: Engine,
+        envelopes: ShardedIndex,
+        attachments: ShardedIndex,
+        owners: FsckOwners,
+    }
+
+    impl Fixture {
+        fn check(&self) -> FsckReport {
+            check(
+                &self.envelopes.searcher(None).unwrap(),
+                &self.attachments.searcher(None).unwrap(),
+                &self.engine,
+                &self.owners,
+            )
+            .unwrap()
+        }
+
+        async fn repair(
+            &self,
+            report: &mut FsckReport,
+            options: &FsckRepair,
+            engine: Option<&Engine>,
+        ) -> BichonResult<()> {
+            let mut envelopes = self.envelopes.commit_all().await;
+            let mut attachments = self.attachments.commit_all().await;
+            let engine = engine.map(|e| e as &dyn BlobStore);
+            repair(report, options, &mut envelopes, &mut attachments, engine)
+        }
+    }
+
+    /// Account 1 owns mailbox 10. Blobs 0xA1 (EML) and 0xB1 (attachment) are
+    /// referenced by `e1`; 0xEE is referenced by nothing. `e2` lives in a
+    /// deleted mailbox and its EML blob is missing; `e3` belongs to a deleted
+    /// account. Attachment `a2` points at an envelope that does not exist.
+    async fn fixture(name: &str) -> Fixture {
+        let root = temp_dir(name);
+        let engine = Engine::open(&root.join("blobs"), Config::default()).unwrap();
+        for byte in [0xA1, 0xB1, 0xEE] {
+            engine.put([byte; 32], b"blob", Codec::None).unwrap();
+        }
+
+        let envelopes =
+            ShardedIndex::open(&root.join("envelopes"), SchemaTools::email_schema()).unwrap();
+        let attachments =
+            ShardedIndex::open(&root.join("attachments"), SchemaTools::attachment_schema())
+                .unwrap();
+
+        let f = SchemaTools::email_fields();
+        for (id, account_id, mailbox_id, content_hash) in [
+            ("e1", 1u64, 10u64, hash(0xA1)),
+            ("e2", 1, 99, hash(0xC1)),
+            ("e3", 2, 20, hash(0xA1)),
+        ] {
+            let mut d = doc!(
+                f.f_id => id,
+                f.f_account_id => account_id,
+                f.f_mailbox_id => mailbox_id,
+                f.f_content_hash => content_hash,
+            );
+            if id == "e1" {
+                d.add_text(f.f_attachment_content_hash, hash(0xB1));
+            }
+            let shard = envelopes.shard_or_create(account_id).unwrap();
+            let mut writer = shard.writer().await.unwrap();
+            writer.add_document(d).unwrap();
+            writer.commit().unwrap();
+        }
+        let a = SchemaTools::attachment_fields();
+        let shard = attachments.shard_or_create(1).unwrap();
+        let mut writer = shard.writer().await.unwrap();
+        for (id, envelope_id) in [("a1", "e1"), ("a2", "gone")] {
+            writer
+                .add_document(doc!(
+                    a.f_id => id,
+                    a.f_envelope_id => envelope_id,
+                    a.f_account_id => 1u64,
+                    a.f_mailbox_id => 10u64,
+                    a.f_content_hash => hash(0xB1),
+                ))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        drop(writer);
+
+        Fixture {
+            engine,
+            envelopes,
+            attachments,
+            owners: FsckOwners {
+                accounts: HashSet::from([1]),
+                mailboxes: HashSet::from([10]),
+                remote_content: HashSet::new(),
+            },
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_reports_every_kind_of_drift() {
+        let fx = fixture("check").await;
+        let report = fx.check();
+
+        assert_eq!(report.envelopes_checked, 3);
+        assert_eq!(report.attachments_checked, 2);
+        assert_eq!(report.blobs_checked, 3);
+        assert!(!report.is_clean());
+
+        let missing: Vec<_> = report
+            .missing_email_blobs
+            .iter()
+            .map(|m| (m.envelope_id.as_str(), m.content_hash.clone()))
+            .collect();
+        assert_eq!(missing, vec![("e2", hash(0xC1))]);
+        assert!(report.missing_attachment_blobs.is_empty());
+        assert_eq!(report.orphan_blobs, vec![hash(0xEE)]);
+
+        let attachments: Vec<_> = report
+            .orphan_attachments
+            .iter()
+            .map(|o| (o.attachment_id.as_str(), o.account_id))
+            .collect();
+        assert_eq!(attachments, vec![("a2", 1)]);
+
+        let mut envelopes: Vec<_> = report
+            .orphan_envelopes
+            .iter()
+            .map(|o| (o.envelope_id.as_str(), o.reason.clone()))
+            .collect();
+        envelopes.sort_by_key(|(id, _)| *id);
+        assert_eq!(
+            envelopes,
+            vec![
+                ("e2", OrphanReason::MailboxDeleted),
+                ("e3", OrphanReason::AccountDeleted),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn repair_removes_orphans_and_converges() {
+        let fx = fixture("repair").await;
+        let mut report = fx.check();
+        let options = FsckRepair {
+            delete_orphan_attachments: true,
+            delete_orphan_envelopes: true,
+            delete_orphan_blobs: true,
+            recover_missing_blobs: false,
+        };
+        fx.repair(&mut report, &options, Some(&fx.engine))
+            .await
+            .unwrap();
+
+        let repaired = report.repaired.unwrap();
+        assert_eq!(repaired.attachments_deleted, 1);
+        assert_eq!(repaired.envelopes_deleted, 2);
+        assert_eq!(repaired.blobs_deleted, 1);
+        assert!(!fx.engine.exists(&[0xEE; 32]).unwrap());
+
+        let after = fx.check();
+        assert!(after.is_clean(), "{after:#?}");
+        assert_eq!(after.envelopes_checked, 1);
+        assert_eq!(after.attachments_checked, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn orphan_blobs_need_the_engine() {
+        let fx = fixture("no-engine").await;
+        let mut report = fx.check();
+        let options = FsckRepair {
+            delete_orphan_blobs: true,
+            ..Default::default()
+        };
+        let err = fx.repair(&mut report, &options, None).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidParameter);
+        assert!(fx.engine.exists(&[0xEE; 32]).unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cached_remote_content_is_not_an_orphan_blob() {
+        let mut fx = fixture("remote-content").await;
+        fx.owners.remote_content.insert(hash(0xEE));
+        let report = fx.check();
+        assert!(report.orphan_blobs.is_empty(), "{report:#?}");
+        assert_eq!(report.blobs_checked, 3);
+    }
+}
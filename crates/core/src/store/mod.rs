@@ -18,4 +18,5 @@
 
 pub mod envelope;
 pub mod blob;
+pub mod fsck;
 pub mod tantivy;
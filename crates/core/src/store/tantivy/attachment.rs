@@ -19,14 +19,16 @@
 use std::{
     collections::{HashMap, HashSet},
     ops::Bound,
-    path::PathBuf,
     sync::{Arc, LazyLock},
-    time::Duration,
 };
 
 use crate::{
-    common::{paginated::DataPage, signal::SIGNAL_MANAGER},
+    common::paginated::DataPage,
     dashboard::{Group, LargestAttachment},
+    envelope::{
+        archive::{archive_facet, ARCHIVE_FACET_ROOT},
+        flags::is_reserved_facet,
+    },
     error::{code::ErrorCode, BichonResult},
     message::{
         attachment::AttachmentMetadata,
@@ -36,18 +38,18 @@ use crate::{
     raise_error,
     settings::dir::DATA_DIR_MANAGER,
     store::tantivy::{
-        fatal_commit,
         fields::{
             F_ATTACHMENT_CATEGORY, F_ATTACHMENT_CONTENT_TYPE, F_ATTACHMENT_EXT, F_DATE,
             F_INGEST_AT, F_SIZE, F_TAGS,
         },
         model::{extract_senders, AttachmentModel},
         schema::SchemaTools,
-        tokenizers::EuroTokenizer,
+        shard::{ShardSearcher, ShardedIndex},
     },
 };
 
 use serde_json::json;
+use tantivy::schema::Facet;
 use tantivy::{
     aggregation::{
         agg_req::Aggregations,
@@ -55,31 +57,27 @@ use tantivy::{
         AggregationCollector, Key,
     },
     collector::{Count, FacetCollector, TopDocs},
-    indexer::{LogMergePolicy, UserOperation},
-    query::{AllQuery, BooleanQuery, EmptyQuery, Occur, Query, QueryParser, RangeQuery, TermQuery},
+    indexer::UserOperation,
+    query::{AllQuery, BooleanQuery, EmptyQuery, Occur, Query, RangeQuery, TermQuery},
     schema::{Field, IndexRecordOption, Value},
-    DocAddress, Index, IndexReader, IndexWriter, Order, TantivyDocument, Term,
+    DocAddress, Order, TantivyDocument, Term,
 };
-use tantivy::{schema::Facet, Searcher};
 use tokio::{
     sync::{mpsc, Mutex},
-    task::{self, JoinHandle},
+    task::JoinHandle,
 };
-use tracing::info;
 
 pub static ATTACHMENT_MANAGER: LazyLock<IndexManager> = LazyLock::new(IndexManager::new);
 
 pub struct IndexManager {
-    index: Arc<Index>,
-    index_writer: Arc<Mutex<IndexWriter>>,
+    shards: Arc<ShardedIndex>,
     sender: mpsc::Sender<TantivyDocument>,
-    reader: IndexReader,
     handle: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl IndexManager {
-    pub(crate) fn index_writer(&self) -> &Arc<Mutex<IndexWriter>> {
-        &self.index_writer
+    pub(crate) fn shards(&self) -> &Arc<ShardedIndex> {
+        &self.shards
     }
 
     pub async fn shutdown(&self) {
@@ -89,118 +87,22 @@ impl IndexManager {
         }
     }
     pub fn new() -> Self {
-        let index = Self::open_or_create_index(&DATA_DIR_MANAGER.attachment_dir);
-        index.tokenizers().register("euro", EuroTokenizer::new());
-        let mut merge_policy = LogMergePolicy::default();
-        merge_policy.set_min_num_segments(25);
-        merge_policy.set_min_layer_size(10_000);
-        merge_policy.set_max_docs_before_merge(100_000);
-
-        let index_writer = index
-            .writer_with_num_threads(4, 67_108_864)
-            .unwrap_or_else(|e| {
-                panic!(
-                    "Failed to create IndexWriter with 4 threads and 64MB buffer for {:?}: {}",
-                    &DATA_DIR_MANAGER.envelope_dir, e
-                )
-            });
-        index_writer.set_merge_policy(Box::new(merge_policy));
-        let index_writer = Arc::new(Mutex::new(index_writer));
-        let reader = index.reader().unwrap_or_else(|e| {
+        let shards = ShardedIndex::open(
+            &DATA_DIR_MANAGER.attachment_dir,
+            SchemaTools::attachment_schema(),
+        )
+        .unwrap_or_else(|e| {
             panic!(
-                "Failed to create IndexReader for {:?}: {}",
-                &DATA_DIR_MANAGER.envelope_dir, e
+                "Failed to open attachment index at {:?}: {:#?}",
+                &DATA_DIR_MANAGER.attachment_dir, e
             )
         });
-
-        let (sender, mut receiver) = mpsc::channel::<TantivyDocument>(100);
-
-        let writer = index_writer.clone();
-        let handler = task::spawn(async move {
-            let mut shutdown = SIGNAL_MANAGER.subscribe();
-            let mut commit_interval = tokio::time::interval(Duration::from_secs(60));
-            let mut pending_count = 0;
-            let commit_threshold = 1000;
-            loop {
-                tokio::select! {
-                    maybe_msg = receiver.recv() => {
-                        match maybe_msg {
-                            Some(doc) => {
-                                let mut writer = writer.lock().await;
-                                let mut batch_count = 0;
-                                match writer.add_document(doc) {
-                                    Ok(_) => {
-                                        batch_count += 1;
-                                    }
-                                    Err(e) => {
-                                        eprintln!("[ERROR] Failed to add document: {e:?}");
-                                        tracing::error!("Tantivy: Failed to add document: {e:?}");
-                                    }
-                                }
-                                while let Ok(next_doc) = receiver.try_recv() {
-                                    match writer.add_document(next_doc) {
-                                        Ok(_) => batch_count += 1,
-                                        Err(e) => {
-                                            eprintln!("[ERROR] Failed to add document: {e:?}");
-                                            tracing::error!("Tantivy: Failed to add document: {e:?}");
-                                        }
-                                    }
-                                }
-                                if batch_count > 0 {
-                                    pending_count += batch_count;
-                                }
-                                if pending_count >= commit_threshold {
-                                    tracing::info!(
-                                        "Tantivy: Reached threshold ({} docs), committing...",
-                                        pending_count
-                                    );
-                                    tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                                    tracing::debug!(
-                                        "Tantivy attach: committed {} docs, pending reset to 0",
-                                        pending_count
-                                    );
-                                    pending_count = 0;
-                                    commit_interval.reset();
-                                }
-                            }
-                            None => {
-                                tracing::info!("Tantivy: Receiver closed. Finalizing...");
-                                if pending_count > 0 {
-                                    let mut writer = writer.lock().await;
-                                    tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                                }
-                                break;
-                            },
-                        }
-                    }
-                    _ = commit_interval.tick() => {
-                        if pending_count > 0 {
-                            let mut writer = writer.lock().await;
-                            tracing::debug!(
-                                "Tantivy attach: periodic commit ({} docs pending)",
-                                pending_count
-                            );
-                            tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                            pending_count = 0;
-                        }
-                    }
-                    _ = shutdown.recv() => {
-                        tracing::info!("Tantivy: Shutdown signal received. Performing final commit...");
-                        if pending_count > 0 {
-                            let mut writer = writer.lock().await;
-                            tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                        }
-                        tracing::info!("Tantivy: Shutdown cleanup complete.");
-                        break;
-                    }
-                }
-            }
-        });
+        let shards = Arc::new(shards);
+        let (sender, receiver) = mpsc::channel::<TantivyDocument>(100);
+        let handler = shards.spawn_ingest(receiver);
         Self {
-            index: Arc::new(index),
-            index_writer,
+            shards,
             sender,
-            reader,
             handle: Mutex::new(Some(handler)),
         }
     }
@@ -209,42 +111,6 @@ impl IndexManager {
         let _ = self.sender.send(doc).await;
     }
 
-    fn open_or_create_index(index_dir: &PathBuf) -> Index {
-        let need_create = !index_dir.exists()
-            || index_dir
-                .read_dir()
-                .map(|mut d| d.next().is_none())
-                .unwrap_or(true);
-        if need_create {
-            info!(
-                "Attachment index not found or empty, creating new index at {}",
-                index_dir.display()
-            );
-            std::fs::create_dir_all(&index_dir).unwrap_or_else(|e| {
-                panic!("Failed to create index directory {:?}: {}", index_dir, e)
-            });
-            Index::create_in_dir(&index_dir, SchemaTools::attachment_schema())
-                .unwrap_or_else(|e| panic!("Failed to create index in {:?}: {}", index_dir, e))
-        } else {
-            info!(
-                "Opening existing attachment index at {}",
-                index_dir.display()
-            );
-            Self::open(&index_dir)
-        }
-    }
-
-    fn open(index_dir: &PathBuf) -> Index {
-        Index::open_in_dir(index_dir)
-            .unwrap_or_else(|e| panic!("Failed to open index in {:?}: {}", index_dir, e))
-    }
-
-    fn account_query(&self, account_id: u64) -> Box<TermQuery> {
-        let account_term =
-            Term::from_field_u64(SchemaTools::attachment_fields().f_account_id, account_id);
-        Box::new(TermQuery::new(account_term, IndexRecordOption::Basic))
-    }
-
     fn mailbox_query(&self, account_id: u64, mailbox_id: u64) -> Box<dyn Query> {
         let account_query = TermQuery::new(
             Term::from_field_u64(SchemaTools::attachment_fields().f_account_id, account_id),
@@ -278,7 +144,7 @@ impl IndexManager {
     }
 
     pub fn total_attachments(&self, accounts: &Option<HashSet<u64>>) -> BichonResult<u64> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -333,8 +199,9 @@ impl IndexManager {
         }
 
         if let Some(ref text) = filter.text {
-            let query_parser =
-                QueryParser::for_index(&self.index, SchemaTools::attachment_default_fields());
+            let query_parser = self
+                .shards
+                .query_parser(SchemaTools::attachment_default_fields());
 
             let query = query_parser
                 .parse_query(text)
@@ -343,7 +210,7 @@ impl IndexManager {
         }
 
         if let Some(ref subject_val) = filter.subject {
-            let query_parser = QueryParser::for_index(&self.index, vec![f.f_subject]);
+            let query_parser = self.shards.query_parser(vec![f.f_subject]);
             let q = query_parser
                 .parse_query(subject_val)
                 .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InvalidParameter))?;
@@ -371,7 +238,7 @@ impl IndexManager {
         }
 
         if let Some(from_query) = &filter.from {
-            let query_parser = QueryParser::for_index(&self.index, vec![f.f_from_text]);
+            let query_parser = self.shards.query_parser(vec![f.f_from_text]);
             let q = query_parser
                 .parse_query(from_query)
                 .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InvalidParameter))?;
@@ -391,8 +258,9 @@ impl IndexManager {
         }
 
         if let Some(ref name) = filter.attachment_name {
-            let query_parser =
-                QueryParser::for_index(&self.index, vec![f.f_name_text, f.f_name_exact]);
+            let query_parser = self
+                .shards
+                .query_parser(vec![f.f_name_text, f.f_name_exact]);
 
             let q = query_parser
                 .parse_query(name)
@@ -505,6 +373,28 @@ impl IndexManager {
             subqueries.push((Occur::Must, Box::new(q)));
         }
 
+        if let Some(archive) = &filter.archive_content_hash {
+            let term = Term::from_facet(f.f_tags, &archive_facet(archive));
+            let query = TermQuery::new(term, IndexRecordOption::Basic);
+            subqueries.push((Occur::Must, Box::new(query)));
+        }
+
+        if let Some(in_archive) = filter.in_archive {
+            // Facet terms match their ancestors, so the root matches every
+            // archive member.
+            let root = Facet::from(ARCHIVE_FACET_ROOT);
+            let query = Box::new(TermQuery::new(
+                Term::from_facet(f.f_tags, &root),
+                IndexRecordOption::Basic,
+            ));
+            if in_archive {
+                subqueries.push((Occur::Must, query));
+            } else {
+                subqueries.push((Occur::Must, Box::new(AllQuery)));
+                subqueries.push((Occur::MustNot, query));
+            }
+        }
+
         if subqueries.is_empty() {
             return Ok(Box::new(AllQuery));
         }
@@ -517,7 +407,7 @@ impl IndexManager {
         account_id: u64,
         id: &str,
     ) -> BichonResult<Option<AttachmentModel>> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
         let f = SchemaTools::attachment_fields();
 
         let query = BooleanQuery::new(vec![
@@ -556,10 +446,7 @@ impl IndexManager {
         &self,
         accounts: &Option<HashSet<u64>>,
     ) -> BichonResult<Vec<LargestAttachment>> {
-        self.reader
-            .reload()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-        let searcher = self.reader.searcher();
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         let query: Box<dyn Query> = match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -604,14 +491,7 @@ impl IndexManager {
     }
 
     pub async fn delete_account_attachments(&self, account_id: u64) -> BichonResult<()> {
-        let query = self.account_query(account_id);
-        let mut writer = self.index_writer.lock().await;
-        writer
-            .delete_query(query)
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-        writer
-            .commit()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        self.shards.drop_shard(account_id).await?;
         Ok(())
     }
 
@@ -628,7 +508,10 @@ impl IndexManager {
         for mailbox_id in mailbox_ids {
             queries.push(self.mailbox_query(account_id, mailbox_id));
         }
-        let mut writer = self.index_writer.lock().await;
+        let Some(shard) = self.shards.shard(account_id) else {
+            return Ok(());
+        };
+        let mut writer = shard.writer().await?;
         for query in queries {
             writer
                 .delete_query(query)
@@ -649,28 +532,31 @@ impl IndexManager {
             return Ok(());
         }
 
-        let mut writer = self.index_writer.lock().await;
         for (account_id, envelope_ids) in deletes {
             let unique_ids: HashSet<&String> = envelope_ids.iter().collect();
             if unique_ids.is_empty() {
                 continue;
             }
+            let Some(shard) = self.shards.shard(account_id) else {
+                continue;
+            };
+            let mut writer = shard.writer().await?;
             for eid in unique_ids {
                 let query = self.attachment_query(account_id, eid);
                 writer
                     .delete_query(query)
                     .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
             }
+            writer
+                .commit()
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
         }
-        writer
-            .commit()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
         Ok(())
     }
 
     fn collect_facets_recursive(
         query: &dyn Query,
-        searcher: &Searcher,
+        searcher: &ShardSearcher,
         parent_facet: &str,
         all_facets: &mut Vec<TagCount>,
         field_name: &str,
@@ -683,6 +569,9 @@ impl IndexManager {
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
         for (facet, count) in facet_counts.get(parent_facet) {
+            if is_reserved_facet(facet) {
+                continue;
+            }
             all_facets.push(TagCount {
                 tag: facet.to_string(),
                 count,
@@ -700,7 +589,7 @@ impl IndexManager {
     }
 
     pub fn get_all_tags(&self, accounts: Option<HashSet<u64>>) -> BichonResult<Vec<TagCount>> {
-        let searcher = self.reader.searcher();
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         let query: Box<dyn Query> = match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -729,9 +618,6 @@ impl IndexManager {
             tracing::warn!("update_attachment_tags: request is empty, nothing to update");
             return Ok(());
         }
-        let searcher = self.create_searcher()?;
-        let mut writer = self.index_writer.lock().await;
-
         let f_tags = SchemaTools::attachment_fields().f_tags;
         let f_id = SchemaTools::attachment_fields().f_id;
         let deduplicated_updates: HashMap<u64, HashSet<String>> = request
@@ -740,9 +626,13 @@ impl IndexManager {
             .map(|(account_id, envelope_ids)| (account_id, envelope_ids.into_iter().collect()))
             .collect();
 
-        let mut operations = Vec::new();
-
         for (account_id, att_ids) in &deduplicated_updates {
+            let Some(shard) = self.shards.shard(*account_id) else {
+                continue;
+            };
+            let mut writer = shard.writer().await?;
+            let searcher = shard.searcher()?;
+            let mut operations = Vec::new();
             for aid in att_ids {
                 let query = self.attachment_query(*account_id, aid);
                 let docs = searcher
@@ -754,11 +644,14 @@ impl IndexManager {
                         .doc(*doc_address)
                         .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
-                    let mut current_tags: HashSet<String> = old_doc
+                    // Archive membership is kept whatever the action.
+                    let (reserved, tags): (Vec<Facet>, Vec<Facet>) = old_doc
                         .get_all(f_tags)
                         .filter_map(|val| val.as_facet())
-                        .map(|facet| facet.to_string())
-                        .collect();
+                        .filter_map(|facet| Facet::from_encoded(facet.as_bytes().to_vec()).ok())
+                        .partition(is_reserved_facet);
+                    let mut current_tags: HashSet<String> =
+                        tags.iter().map(|facet| facet.to_string()).collect();
 
                     match request.action {
                         TagAction::Add => {
@@ -786,22 +679,25 @@ impl IndexManager {
                     for tag in current_tags {
                         new_doc.add_facet(f_tags, &tag);
                     }
+                    for facet in reserved {
+                        new_doc.add_facet(f_tags, facet);
+                    }
 
                     let delete_term = Term::from_field_text(f_id, aid);
                     operations.push(UserOperation::Delete(delete_term));
                     operations.push(UserOperation::Add(new_doc));
                 }
             }
-        }
 
-        writer
-            .run(operations)
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            writer
+                .run(operations)
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
-        // commit
-        writer
-            .commit()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            // commit
+            writer
+                .commit()
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        }
 
         Ok(())
     }
@@ -817,8 +713,8 @@ impl IndexManager {
     ) -> BichonResult<DataPage<AttachmentModel>> {
         assert!(page > 0, "Page number must be greater than 0");
         assert!(page_size > 0, "Page size must be greater than 0");
+        let searcher = self.shards.searcher(accounts.as_ref())?;
         let query = self.filter_query(accounts, filter)?;
-        let searcher = self.create_searcher()?;
         let total = searcher
             .search(&query, &Count)
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?
@@ -915,15 +811,12 @@ impl IndexManager {
         })
     }
 
-    fn create_searcher(&self) -> BichonResult<Searcher> {
-        self.reader
-            .reload()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-        Ok(self.reader.searcher())
+    pub(crate) fn create_searcher(&self) -> BichonResult<ShardSearcher> {
+        self.shards.searcher(None)
     }
 
     pub fn get_all_senders(&self, accounts: Option<HashSet<u64>>) -> BichonResult<HashSet<String>> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         let query: Box<dyn Query> = match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -964,7 +857,7 @@ impl IndexManager {
         &self,
         accounts: Option<HashSet<u64>>,
     ) -> BichonResult<AttachmentMetadata> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.searcher(accounts.as_ref())?;
         let aggregations: Aggregations = serde_json::from_value(json!({
             "exts": {
                 "terms": {
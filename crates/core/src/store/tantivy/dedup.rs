@@ -85,22 +85,33 @@ impl BichonTask for DedupTask {
 
         let task = move |_: Option<u64>| {
             Box::pin(async move {
-                // Acquire both writers before creating a reader. The fresh reader
-                // sees the last committed state, while the writers ensure we have
-                // exclusive access to perform deletions.
-                let mut email_writer = ENVELOPE_MANAGER.index_writer().lock().await;
-                let mut attach_writer = ATTACHMENT_MANAGER.index_writer().lock().await;
-                let email_reader = ENVELOPE_MANAGER.create_reader()?;
-
-                dedup_task(&email_reader, &mut email_writer, &mut attach_writer).await?;
-
-                // Commit any remaining changes from the dedup pass.
-                // dedup_account commits per-account, but we ensure a final commit
-                // so the attachment index is in sync.
-                crate::store::tantivy::fatal_commit(&mut attach_writer);
-
-                drop(attach_writer);
-                drop(email_writer);
+                // Each account lives in its own shard, so shards are deduplicated
+                // one at a time. Acquire both writers of the account before
+                // creating a reader. The fresh reader sees the last committed
+                // state, while the writers ensure we have exclusive access to
+                // perform deletions.
+                for shard in ENVELOPE_MANAGER.shards().shards() {
+                    let Ok(mut email_writer) = shard.writer().await else {
+                        // Dropped together with its account in the meantime.
+                        continue;
+                    };
+                    let mut attach_writer = ATTACHMENT_MANAGER
+                        .shards()
+                        .shard_or_create(shard.id())?
+                        .writer()
+                        .await?;
+                    let email_reader = shard
+                        .index()
+                        .reader()
+                        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+
+                    dedup_task(&email_reader, &mut email_writer, &mut attach_writer).await?;
+
+                    // Commit any remaining changes from the dedup pass.
+                    // dedup_account commits per-account, but we ensure a final commit
+                    // so the attachment index is in sync.
+                    crate::store::tantivy::fatal_commit(&mut attach_writer);
+                }
                 Ok(())
             })
         };
@@ -110,15 +110,14 @@ impl DedupCache {
             return;
         }
 
-        let reader = match ENVELOPE_MANAGER.create_reader() {
-            Ok(r) => r,
+        let searcher = match ENVELOPE_MANAGER.create_searcher() {
+            Ok(s) => s,
             Err(e) => {
-                tracing::warn!("DedupCache: failed to create reader for populate: {e}");
+                tracing::warn!("DedupCache: failed to create searcher for populate: {e}");
                 return;
             }
         };
 
-        let searcher = reader.searcher();
         let cutoff = utc_now!() - POPULATE_WINDOW_MS;
         let mut entries = self.entries.lock().unwrap();
 
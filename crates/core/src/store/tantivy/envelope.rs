@@ -19,15 +19,24 @@
 use std::{
     collections::{HashMap, HashSet},
     ops::Bound,
-    path::PathBuf,
     sync::{Arc, LazyLock},
     time::Duration,
 };
 
 use crate::{
     account::{migration::AccountModel, stats::AccountStats},
-    common::{paginated::DataPage, signal::SIGNAL_MANAGER},
+    common::paginated::DataPage,
     dashboard::{DashboardStats, Group, LargestEmail, TimeBucket},
+    envelope::{
+        authentication::{
+            dkim_domain_facet, dkim_status_facet, signature_status_facet, signer_facet,
+        },
+        flags::{
+            deleted_on_source_facet, flag_to_facet, is_flag_facet, is_reserved_facet,
+            search_term_to_facet,
+        },
+        gmail::{is_gmail_facet, label_to_facet, msg_id_facet, GmailAttributes},
+    },
     error::{code::ErrorCode, BichonResult},
     message::{
         search::{EmailSearchFilter, SortBy},
@@ -41,14 +50,13 @@ use crate::{
         tantivy::{
             attachment::ATTACHMENT_MANAGER,
             dedup_cache::DEDUP_CACHE,
-            fatal_commit,
             fields::{
                 F_ACCOUNT_ID, F_DATE, F_FROM, F_ID, F_INGEST_AT, F_INTERNAL_DATE,
                 F_REGULAR_ATTACHMENT_COUNT, F_SIZE, F_TAGS, F_THREAD_ID, F_UID,
             },
             model::{extract_contacts, EnvelopeWithAttachments},
             schema::SchemaTools,
-            tokenizers::EuroTokenizer,
+            shard::{ShardSearcher, ShardedIndex},
         },
     },
     utc_now,
@@ -58,6 +66,7 @@ use crate::{
 use chrono::Utc;
 use mail_parser::MessageParser;
 use serde_json::json;
+use tantivy::schema::Facet;
 use tantivy::{
     aggregation::{
         agg_req::Aggregations,
@@ -67,17 +76,18 @@ use tantivy::{
         AggregationCollector, Key,
     },
     collector::{Count, DocSetCollector, FacetCollector, TopDocs},
-    indexer::{LogMergePolicy, UserOperation},
-    query::{AllQuery, BooleanQuery, EmptyQuery, Occur, Query, QueryParser, RangeQuery, TermQuery},
+    indexer::UserOperation,
+    query::{
+        AllQuery, BooleanQuery, EmptyQuery, Occur, Query, RangeQuery, TermQuery, TermSetQuery,
+    },
     schema::{IndexRecordOption, Value},
-    DocAddress, Index, IndexReader, IndexWriter, Order, TantivyDocument, Term,
+    DocAddress, Order, TantivyDocument, Term,
 };
-use tantivy::{schema::Facet, Searcher};
 use tokio::{
     sync::{mpsc, Mutex},
-    task::{self, JoinHandle},
+    task::JoinHandle,
 };
-use tracing::{info, warn};
+use tracing::warn;
 
 pub static ENVELOPE_MANAGER: LazyLock<IndexManager> = LazyLock::new(IndexManager::new);
 
@@ -100,22 +110,14 @@ pub struct EnvelopeSnapshot {
 }
 
 pub struct IndexManager {
-    index: Arc<Index>,
-    index_writer: Arc<Mutex<IndexWriter>>,
+    shards: Arc<ShardedIndex>,
     sender: mpsc::Sender<TantivyDocument>,
-    reader: IndexReader,
     handle: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl IndexManager {
-    pub(crate) fn index_writer(&self) -> &Arc<Mutex<IndexWriter>> {
-        &self.index_writer
-    }
-
-    pub(crate) fn create_reader(&self) -> BichonResult<IndexReader> {
-        self.index
-            .reader()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))
+    pub(crate) fn shards(&self) -> &Arc<ShardedIndex> {
+        &self.shards
     }
 
     pub async fn shutdown(&self) {
@@ -125,118 +127,20 @@ impl IndexManager {
         }
     }
     pub fn new() -> Self {
-        let index = Self::open_or_create_index(&DATA_DIR_MANAGER.envelope_dir);
-        index.tokenizers().register("euro", EuroTokenizer::new());
-        let mut merge_policy = LogMergePolicy::default();
-        merge_policy.set_min_num_segments(25);
-        merge_policy.set_min_layer_size(10_000);
-        merge_policy.set_max_docs_before_merge(100_000);
-
-        let index_writer = index
-            .writer_with_num_threads(4, 67_108_864)
-            .unwrap_or_else(|e| {
-                panic!(
-                    "Failed to create IndexWriter with 4 threads and 64MB buffer for {:?}: {}",
-                    &DATA_DIR_MANAGER.envelope_dir, e
-                )
-            });
-        index_writer.set_merge_policy(Box::new(merge_policy));
-        let index_writer = Arc::new(Mutex::new(index_writer));
-        let reader = index.reader().unwrap_or_else(|e| {
-            panic!(
-                "Failed to create IndexReader for {:?}: {}",
-                &DATA_DIR_MANAGER.envelope_dir, e
-            )
-        });
-
-        let (sender, mut receiver) = mpsc::channel::<TantivyDocument>(100);
-
-        let writer = index_writer.clone();
-        let handler = task::spawn(async move {
-            let mut shutdown = SIGNAL_MANAGER.subscribe();
-            let mut commit_interval = tokio::time::interval(Duration::from_secs(60));
-            let mut pending_count = 0;
-            let commit_threshold = 1000;
-            loop {
-                tokio::select! {
-                    maybe_msg = receiver.recv() => {
-                        match maybe_msg {
-                            Some(doc) => {
-                                let mut writer = writer.lock().await;
-                                let mut batch_count = 0;
-                                match writer.add_document(doc) {
-                                    Ok(_) => {
-                                        batch_count += 1;
-                                    }
-                                    Err(e) => {
-                                        eprintln!("[ERROR] Failed to add document: {e:?}");
-                                        tracing::error!("Tantivy: Failed to add document: {e:?}");
-                                    }
-                                }
-                                while let Ok(next_doc) = receiver.try_recv() {
-                                    match writer.add_document(next_doc) {
-                                        Ok(_) => batch_count += 1,
-                                        Err(e) => {
-                                            eprintln!("[ERROR] Failed to add document: {e:?}");
-                                            tracing::error!("Tantivy: Failed to add document: {e:?}");
-                                        }
-                                    }
-                                }
-                                if batch_count > 0 {
-                                    pending_count += batch_count;
-                                }
-                                if pending_count >= commit_threshold {
-                                    tracing::info!(
-                                        "Tantivy: Reached threshold ({} docs), committing...",
-                                        pending_count
-                                    );
-                                    tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                                    tracing::debug!(
-                                        "Tantivy: committed {} docs, pending reset to 0",
-                                        pending_count
-                                    );
-                                    pending_count = 0;
-                                    commit_interval.reset();
-                                }
-                            }
-                            None => {
-                                tracing::info!("Tantivy: Receiver closed. Finalizing...");
-                                if pending_count > 0 {
-                                    let mut writer = writer.lock().await;
-                                    tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                                }
-                                break;
-                            },
-                        }
-                    }
-                    _ = commit_interval.tick() => {
-                        if pending_count > 0 {
-                            let mut writer = writer.lock().await;
-                            tracing::debug!(
-                                "Tantivy: periodic commit ({} docs pending)",
-                                pending_count
-                            );
-                            tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                            pending_count = 0;
-                        }
-                    }
-                    _ = shutdown.recv() => {
-                        tracing::info!("Tantivy: Shutdown signal received. Performing final commit...");
-                        if pending_count > 0 {
-                            let mut writer = writer.lock().await;
-                            tokio::task::block_in_place(|| fatal_commit(&mut writer));
-                        }
-                        tracing::info!("Tantivy: Shutdown cleanup complete.");
-                        break;
-                    }
-                }
-            }
-        });
+        let shards =
+            ShardedIndex::open(&DATA_DIR_MANAGER.envelope_dir, SchemaTools::email_schema())
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to open email index at {:?}: {:#?}",
+                        &DATA_DIR_MANAGER.envelope_dir, e
+                    )
+                });
+        let shards = Arc::new(shards);
+        let (sender, receiver) = mpsc::channel::<TantivyDocument>(100);
+        let handler = shards.spawn_ingest(receiver);
         Self {
-            index: Arc::new(index),
-            index_writer,
+            shards,
             sender,
-            reader,
             handle: Mutex::new(Some(handler)),
         }
     }
@@ -247,33 +151,6 @@ impl IndexManager {
         }
     }
 
-    fn open_or_create_index(index_dir: &PathBuf) -> Index {
-        let need_create = !index_dir.exists()
-            || index_dir
-                .read_dir()
-                .map(|mut d| d.next().is_none())
-                .unwrap_or(true);
-        if need_create {
-            info!(
-                "Email index not found or empty, creating new index at {}",
-                index_dir.display()
-            );
-            std::fs::create_dir_all(&index_dir).unwrap_or_else(|e| {
-                panic!("Failed to create index directory {:?}: {}", index_dir, e)
-            });
-            Index::create_in_dir(&index_dir, SchemaTools::email_schema())
-                .unwrap_or_else(|e| panic!("Failed to create index in {:?}: {}", index_dir, e))
-        } else {
-            info!("Opening existing email index at {}", index_dir.display());
-            Self::open(&index_dir)
-        }
-    }
-
-    fn open(index_dir: &PathBuf) -> Index {
-        Index::open_in_dir(index_dir)
-            .unwrap_or_else(|e| panic!("Failed to open index in {:?}: {}", index_dir, e))
-    }
-
     fn account_query(&self, account_id: u64) -> Box<TermQuery> {
         let account_term =
             Term::from_field_u64(SchemaTools::email_fields().f_account_id, account_id);
@@ -306,7 +183,7 @@ impl IndexManager {
     ) -> BichonResult<HashSet<String>> {
         let query = self.mailbox_query(account_id, mailbox_id);
         let fields = SchemaTools::email_fields();
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
 
         let docs = searcher
             .search(&query, &DocSetCollector)
@@ -340,7 +217,7 @@ impl IndexManager {
     ) -> BichonResult<Vec<EnvelopeSnapshot>> {
         let query = self.mailbox_query(account_id, mailbox_id);
         let fields = SchemaTools::email_fields();
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
 
         let docs = searcher
             .search(&query, &DocSetCollector)
@@ -425,7 +302,33 @@ impl IndexManager {
                 )),
             ),
         ]);
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
+        let count = searcher
+            .search(&query, &Count)
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        Ok(count > 0)
+    }
+
+    /// Check whether a message with the given EML content hash is in a
+    /// mailbox. Only committed documents are visible, so a `true` means the
+    /// envelope survives a crash.
+    pub fn mailbox_contains_content_hash(
+        &self,
+        account_id: u64,
+        mailbox_id: u64,
+        content_hash: &str,
+    ) -> BichonResult<bool> {
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, self.mailbox_query(account_id, mailbox_id)),
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(SchemaTools::email_fields().f_content_hash, content_hash),
+                    IndexRecordOption::Basic,
+                )),
+            ),
+        ]);
+        let searcher = self.shards.account_searcher(account_id)?;
         let count = searcher
             .search(&query, &Count)
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
@@ -480,8 +383,9 @@ impl IndexManager {
         }
 
         if let Some(ref text) = filter.text {
-            let query_parser =
-                QueryParser::for_index(&self.index, SchemaTools::email_default_fields());
+            let query_parser = self
+                .shards
+                .query_parser(SchemaTools::email_default_fields());
 
             let query = query_parser
                 .parse_query(text)
@@ -490,7 +394,7 @@ impl IndexManager {
         }
 
         if let Some(ref subject_val) = filter.subject {
-            let query_parser = QueryParser::for_index(&self.index, vec![f.f_subject]);
+            let query_parser = self.shards.query_parser(vec![f.f_subject]);
             let q = query_parser
                 .parse_query(subject_val)
                 .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InvalidParameter))?;
@@ -498,7 +402,7 @@ impl IndexManager {
         }
 
         if let Some(ref body_val) = filter.body {
-            let query_parser = QueryParser::for_index(&self.index, vec![f.f_body]);
+            let query_parser = self.shards.query_parser(vec![f.f_body]);
 
             let q = query_parser
                 .parse_query(body_val)
@@ -526,6 +430,75 @@ impl IndexManager {
             }
         }
 
+        for (occur, flags) in [
+            (Occur::Must, &filter.flags),
+            (Occur::MustNot, &filter.without_flags),
+        ] {
+            for flag in flags.iter().flatten() {
+                let facet = search_term_to_facet(flag).ok_or_else(|| {
+                    raise_error!(
+                        format!("Invalid flag filter: '{}'", flag),
+                        ErrorCode::InvalidParameter
+                    )
+                })?;
+                subqueries.push((
+                    occur,
+                    Box::new(TermQuery::new(
+                        Term::from_facet(f.f_tags, &facet),
+                        IndexRecordOption::Basic,
+                    )),
+                ));
+            }
+        }
+
+        if let Some(ref labels) = filter.labels {
+            if !labels.is_empty() {
+                let mut should_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+                for label in labels {
+                    let facet = label_to_facet(label).ok_or_else(|| {
+                        raise_error!(
+                            format!("Invalid label filter: '{}'", label),
+                            ErrorCode::InvalidParameter
+                        )
+                    })?;
+                    should_queries.push((
+                        Occur::Should,
+                        Box::new(TermQuery::new(
+                            Term::from_facet(f.f_tags, &facet),
+                            IndexRecordOption::Basic,
+                        )),
+                    ));
+                }
+                subqueries.push((Occur::Must, Box::new(BooleanQuery::new(should_queries))));
+            }
+        }
+
+        if let Some(deleted) = filter.deleted_on_source {
+            subqueries.push((
+                if deleted { Occur::Must } else { Occur::MustNot },
+                Box::new(TermQuery::new(
+                    Term::from_facet(f.f_tags, &deleted_on_source_facet()),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
+        let auth_facets = [
+            filter.dkim.map(dkim_status_facet),
+            filter.dkim_domain.as_deref().map(dkim_domain_facet),
+            filter.signature.map(signature_status_facet),
+            filter.signer.as_deref().map(signer_facet),
+        ];
+        for facet in auth_facets.into_iter().flatten() {
+            subqueries.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_facet(f.f_tags, &facet),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
         for (field, opt_value) in [
             (f.f_from_text, &filter.from),
             (f.f_to_text, &filter.to),
@@ -533,7 +506,7 @@ impl IndexManager {
             (f.f_bcc_text, &filter.bcc),
         ] {
             if let Some(ref v) = opt_value {
-                let query_parser = QueryParser::for_index(&self.index, vec![field]);
+                let query_parser = self.shards.query_parser(vec![field]);
                 let q = query_parser
                     .parse_query(v)
                     .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InvalidParameter))?;
@@ -545,7 +518,7 @@ impl IndexManager {
         if let Some(ref v) = filter.any_recipient {
             let mut recipient_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
             for field in [f.f_to_text, f.f_cc_text, f.f_bcc_text] {
-                let query_parser = QueryParser::for_index(&self.index, vec![field]);
+                let query_parser = self.shards.query_parser(vec![field]);
                 if let Ok(q) = query_parser.parse_query(v) {
                     recipient_queries.push((Occur::Should, q));
                 }
@@ -559,7 +532,7 @@ impl IndexManager {
         if let Some(ref v) = filter.any_participant {
             let mut participant_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
             for field in [f.f_from_text, f.f_to_text, f.f_cc_text, f.f_bcc_text] {
-                let query_parser = QueryParser::for_index(&self.index, vec![field]);
+                let query_parser = self.shards.query_parser(vec![field]);
                 if let Ok(q) = query_parser.parse_query(v) {
                     participant_queries.push((Occur::Should, q));
                 }
@@ -593,8 +566,7 @@ impl IndexManager {
                 let term = Term::from_field_text(f.f_attachment_name_exact, name);
                 let exact_query = TermQuery::new(term, IndexRecordOption::Basic);
 
-                let query_parser =
-                    QueryParser::for_index(&self.index, vec![f.f_attachment_name_text]);
+                let query_parser = self.shards.query_parser(vec![f.f_attachment_name_text]);
                 let q: Box<dyn Query> = query_parser
                     .parse_query(name)
                     .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InvalidParameter))?;
@@ -604,8 +576,7 @@ impl IndexManager {
                 ]);
                 subqueries.push((Occur::Must, Box::new(query)));
             } else {
-                let query_parser =
-                    QueryParser::for_index(&self.index, vec![f.f_attachment_name_text]);
+                let query_parser = self.shards.query_parser(vec![f.f_attachment_name_text]);
                 let q = query_parser
                     .parse_query(name)
                     .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InvalidParameter))?;
@@ -740,6 +711,10 @@ impl IndexManager {
         if subqueries.is_empty() {
             return Ok(Box::new(AllQuery));
         }
+        // A boolean query made only of exclusions matches nothing.
+        if subqueries.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+            subqueries.push((Occur::Must, Box::new(AllQuery)));
+        }
 
         Ok(Box::new(BooleanQuery::new(subqueries)))
     }
@@ -765,7 +740,7 @@ impl IndexManager {
         account_id: u64,
         envelope_id: &str,
     ) -> BichonResult<Option<EnvelopeWithAttachments>> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
         let f = SchemaTools::email_fields();
 
         let query = BooleanQuery::new(vec![
@@ -804,10 +779,7 @@ impl IndexManager {
         &self,
         accounts: &Option<HashSet<u64>>,
     ) -> BichonResult<Vec<LargestEmail>> {
-        self.reader
-            .reload()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-        let searcher = self.reader.searcher();
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         let query: Box<dyn Query> = match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -844,7 +816,7 @@ impl IndexManager {
     }
 
     pub fn total_emails(&self, accounts: &Option<HashSet<u64>>) -> BichonResult<u64> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -868,7 +840,7 @@ impl IndexManager {
     }
 
     pub fn get_max_uid(&self, account_id: u64, mailbox_id: u64) -> BichonResult<Option<u64>> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
 
         let query = self.mailbox_query(account_id, mailbox_id);
         let agg_req: Aggregations = serde_json::from_value(json!({
@@ -897,7 +869,7 @@ impl IndexManager {
     }
 
     pub fn get_account_stats(&self, account_id: u64) -> BichonResult<AccountStats> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
         let query = self.account_query(account_id);
 
         let agg_req: Aggregations = serde_json::from_value(json!({
@@ -955,28 +927,18 @@ impl IndexManager {
     pub async fn delete_account_envelopes(&self, account_id: u64) -> BichonResult<()> {
         let query = self.account_query(account_id);
         let (eml_content_hashes, attachments_content_hashes) =
-            self.collect_content_hashes(query)?;
-
-        let query = self.account_query(account_id);
+            self.collect_content_hashes(account_id, query)?;
 
-        let mut writer = self.index_writer.lock().await;
-        writer
-            .delete_query(query)
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-        writer
-            .commit()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        // The account's documents all live in its shard.
+        self.shards.drop_shard(account_id).await?;
 
         ATTACHMENT_MANAGER
             .delete_account_attachments(account_id)
             .await?;
 
         if !eml_content_hashes.is_empty() || !attachments_content_hashes.is_empty() {
-            self.cleanup_unused_content(
-                &mut writer,
-                eml_content_hashes,
-                attachments_content_hashes,
-            )?;
+            self.cleanup_unused_content(eml_content_hashes, attachments_content_hashes)
+                .await?;
         }
 
         DEDUP_CACHE.remove_by_account(account_id);
@@ -997,7 +959,7 @@ impl IndexManager {
 
         for mailbox_id in &mailbox_ids {
             let query = self.mailbox_query(account_id, *mailbox_id);
-            let (eml_hashes, attachment_hashes) = self.collect_content_hashes(query)?;
+            let (eml_hashes, attachment_hashes) = self.collect_content_hashes(account_id, query)?;
             eml_content_hashes.extend(eml_hashes);
             attachments_content_hashes.extend(attachment_hashes);
         }
@@ -1006,22 +968,21 @@ impl IndexManager {
         for mailbox_id in &mailbox_ids {
             queries.push(self.mailbox_query(account_id, *mailbox_id));
         }
-        let mut writer = self.index_writer.lock().await;
-        for query in queries {
+        if let Some(shard) = self.shards.shard(account_id) {
+            let mut writer = shard.writer().await?;
+            for query in queries {
+                writer
+                    .delete_query(query)
+                    .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            }
             writer
-                .delete_query(query)
+                .commit()
                 .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
         }
-        writer
-            .commit()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
         if !eml_content_hashes.is_empty() || !attachments_content_hashes.is_empty() {
-            self.cleanup_unused_content(
-                &mut writer,
-                eml_content_hashes,
-                attachments_content_hashes,
-            )?;
+            self.cleanup_unused_content(eml_content_hashes, attachments_content_hashes)
+                .await?;
         }
 
         for mailbox_id in mailbox_ids {
@@ -1033,10 +994,11 @@ impl IndexManager {
 
     fn collect_content_hashes(
         &self,
+        account_id: u64,
         query: Box<dyn Query>,
     ) -> BichonResult<(HashSet<String>, HashSet<String>)> {
         let (eml_with_mailbox, attachments_content_hashes) =
-            self.collect_content_hashes_with_mailbox(query)?;
+            self.collect_content_hashes_with_mailbox(account_id, query)?;
 
         let eml_content_hashes = eml_with_mailbox
             .into_iter()
@@ -1048,13 +1010,14 @@ impl IndexManager {
 
     fn collect_content_hashes_with_mailbox(
         &self,
+        account_id: u64,
         query: Box<dyn Query>,
     ) -> BichonResult<(HashSet<(String, u64)>, HashSet<String>)> {
         let mut eml_content_hashes = HashSet::new();
         let mut attachments_content_hashes = HashSet::new();
 
         let fields = SchemaTools::email_fields();
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
 
         let docs = searcher
             .search(&query, &DocSetCollector)
@@ -1088,20 +1051,21 @@ impl IndexManager {
         Ok((eml_content_hashes, attachments_content_hashes))
     }
 
-    fn cleanup_unused_content(
+    async fn cleanup_unused_content(
         &self,
-        writer: &mut IndexWriter,
         eml_content_hashes: HashSet<String>,
         attachments_content_hashes: HashSet<String>,
     ) -> BichonResult<()> {
-        // Reference-count barrier: commit the writer and reload the reader so the
-        // `Count` below is evaluated against a fully committed, freshly-reloaded
-        // index state. Without this, an envelope that shares a content hash but
-        // is still sitting uncommitted in the writer buffer (e.g. added by the
-        // background ingest task before this delete acquired the writer lock)
-        // would be invisible to the searcher, the count would read 0, and a
-        // still-referenced blob would be deleted.
-        fatal_commit(writer);
+        // Reference-count barrier: commit every shard writer and reload the
+        // readers so the `Count` below is evaluated against a fully committed,
+        // freshly-reloaded index state. Blobs are shared across accounts, so
+        // every shard counts. Without this, an envelope that shares a content
+        // hash but is still sitting uncommitted in a writer buffer (e.g. added
+        // by the background ingest task before this delete started) would be
+        // invisible to the searcher, the count would read 0, and a
+        // still-referenced blob would be deleted. The writers stay locked
+        // until the blobs are gone.
+        let _writers = self.shards.commit_all().await;
         let searcher = self.create_searcher()?;
         let fields = SchemaTools::email_fields();
         let mut eml: HashSet<String> = HashSet::new();
@@ -1157,7 +1121,7 @@ impl IndexManager {
             for eid in unique_ids {
                 let query = self.envelope_query(*account_id, eid);
                 let (eml_hashes_with_mailbox, attachment_hashes) =
-                    self.collect_content_hashes_with_mailbox(query)?;
+                    self.collect_content_hashes_with_mailbox(*account_id, query)?;
 
                 eml_content_hash_triples.extend(
                     eml_hashes_with_mailbox
@@ -1168,23 +1132,25 @@ impl IndexManager {
             }
         }
 
-        let mut writer = self.index_writer.lock().await;
-
         for (account_id, envelope_ids) in deletes {
             let unique_ids: HashSet<&String> = envelope_ids.iter().collect();
             if unique_ids.is_empty() {
                 continue;
             }
+            let Some(shard) = self.shards.shard(account_id) else {
+                continue;
+            };
+            let mut writer = shard.writer().await?;
             for eid in unique_ids {
                 let query = self.envelope_query(account_id, eid);
                 writer
                     .delete_query(query)
                     .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
             }
+            writer
+                .commit()
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
         }
-        writer
-            .commit()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
         if !eml_content_hash_triples.is_empty() || !attachments_content_hashes.is_empty() {
             let eml_content_hashes: HashSet<String> = eml_content_hash_triples
@@ -1192,11 +1158,8 @@ impl IndexManager {
                 .map(|(_, _, hash)| hash.clone())
                 .collect();
 
-            self.cleanup_unused_content(
-                &mut writer,
-                eml_content_hashes,
-                attachments_content_hashes,
-            )?;
+            self.cleanup_unused_content(eml_content_hashes, attachments_content_hashes)
+                .await?;
         }
 
         for (aid, mid, hash) in eml_content_hash_triples {
@@ -1208,7 +1171,7 @@ impl IndexManager {
 
     fn collect_facets_recursive(
         query: &dyn Query,
-        searcher: &Searcher,
+        searcher: &ShardSearcher,
         parent_facet: &str,
         all_facets: &mut Vec<TagCount>,
     ) -> BichonResult<()> {
@@ -1220,6 +1183,9 @@ impl IndexManager {
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
         for (facet, count) in facet_counts.get(parent_facet) {
+            if is_reserved_facet(facet) {
+                continue;
+            }
             all_facets.push(TagCount {
                 tag: facet.to_string(),
                 count,
@@ -1231,7 +1197,7 @@ impl IndexManager {
     }
 
     pub fn get_all_tags(&self, accounts: Option<HashSet<u64>>) -> BichonResult<Vec<TagCount>> {
-        let searcher = self.reader.searcher();
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         let query: Box<dyn Query> = match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -1258,7 +1224,7 @@ impl IndexManager {
         &self,
         accounts: Option<HashSet<u64>>,
     ) -> BichonResult<HashSet<String>> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.searcher(accounts.as_ref())?;
 
         let query: Box<dyn Query> = match accounts {
             Some(ref ids) if !ids.is_empty() => {
@@ -1299,9 +1265,16 @@ impl IndexManager {
             tracing::warn!("update_envelope_tags: request is empty, nothing to update");
             return Ok(());
         }
-        let searcher = self.create_searcher()?;
-        let mut writer = self.index_writer.lock().await;
-
+        if let Some(tag) = request.tags.iter().find(|tag| {
+            Facet::from_text(tag)
+                .map(|facet| is_reserved_facet(&facet))
+                .unwrap_or(false)
+        }) {
+            return Err(raise_error!(
+                format!("Tag '{}' is reserved", tag),
+                ErrorCode::InvalidParameter
+            ));
+        }
         let f = SchemaTools::email_fields();
         let f_tags = f.f_tags;
         let f_id = f.f_id;
@@ -1311,9 +1284,13 @@ impl IndexManager {
             .map(|(account_id, envelope_ids)| (account_id, envelope_ids.into_iter().collect()))
             .collect();
 
-        let mut operations = Vec::new();
-
         for (account_id, envelope_ids) in &deduplicated_updates {
+            let Some(shard) = self.shards.shard(*account_id) else {
+                continue;
+            };
+            let mut writer = shard.writer().await?;
+            let searcher = shard.searcher()?;
+            let mut operations = Vec::new();
             for eid in envelope_ids {
                 let query = self.envelope_query(*account_id, eid);
                 let docs = searcher
@@ -1325,11 +1302,15 @@ impl IndexManager {
                         .doc(*doc_address)
                         .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
-                    let mut current_tags: HashSet<String> = old_doc
+                    // IMAP flags and the source state share the facet field
+                    // but are not tags; keep them as is.
+                    let (reserved_facets, tag_facets): (Vec<Facet>, Vec<Facet>) = old_doc
                         .get_all(f_tags)
                         .filter_map(|val| val.as_facet())
-                        .map(|facet| facet.to_string())
-                        .collect();
+                        .filter_map(|encoded| Facet::from_encoded(encoded.as_bytes().to_vec()).ok())
+                        .partition(is_reserved_facet);
+                    let mut current_tags: HashSet<String> =
+                        tag_facets.iter().map(|facet| facet.to_string()).collect();
 
                     match request.action {
                         TagAction::Add => {
@@ -1347,113 +1328,10 @@ impl IndexManager {
                         }
                     }
 
-                    let mut new_doc = TantivyDocument::new();
-
-                    // Copy stored fields, excluding f_tags (handled separately).
-                    for (field, value) in old_doc.field_values() {
-                        if field != f_tags {
-                            new_doc.add_field_value(field, value);
-                        }
-                    }
-
-                    // Reconstruct non-stored text-search fields from their
-                    // stored counterparts. f_from_text / f_to_text / f_cc_text /
-                    // f_bcc_text carry the same content as f_from / f_to / f_cc / f_bcc.
-                    for val in old_doc.get_all(f.f_from) {
-                        if let Some(s) = val.as_str() {
-                            new_doc.add_text(f.f_from_text, s);
-                        }
-                    }
-                    for val in old_doc.get_all(f.f_to) {
-                        if let Some(s) = val.as_str() {
-                            new_doc.add_text(f.f_to_text, s);
-                        }
-                    }
-                    for val in old_doc.get_all(f.f_cc) {
-                        if let Some(s) = val.as_str() {
-                            new_doc.add_text(f.f_cc_text, s);
-                        }
-                    }
-                    for val in old_doc.get_all(f.f_bcc) {
-                        if let Some(s) = val.as_str() {
-                            new_doc.add_text(f.f_bcc_text, s);
-                        }
-                    }
-
-                    // Reconstruct attachment-name fields from the stored
-                    // f_attachments JSON blob.
-                    if let Some(attrs_val) = old_doc.get_first(f.f_attachments) {
-                        if let Some(json_str) = attrs_val.as_str() {
-                            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(json_str)
-                            {
-                                if let Some(arr) = parsed.as_array() {
-                                    for att in arr {
-                                        let is_inline = att
-                                            .get("inline")
-                                            .and_then(|v| v.as_bool())
-                                            .unwrap_or(false);
-                                        let has_cid = att
-                                            .get("content_id")
-                                            .and_then(|v| v.as_str())
-                                            .map(|s| !s.is_empty())
-                                            .unwrap_or(false);
-                                        if is_inline && has_cid {
-                                            continue;
-                                        }
-                                        if let Some(filename) = att
-                                            .get("filename")
-                                            .and_then(|v| v.as_str())
-                                            .filter(|s| !s.is_empty())
-                                        {
-                                            new_doc.add_text(f.f_attachment_name_text, filename);
-                                            new_doc.add_text(f.f_attachment_name_exact, filename);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Reconstruct body text from the original EML stored in the
-                    // blob store, referenced by f_content_hash.
-                    if let Some(hash_val) = old_doc.get_first(f.f_content_hash) {
-                        if let Some(content_hash) = hash_val.as_str() {
-                            match BLOB_MANAGER.get_email(content_hash) {
-                                Ok(Some(eml_bytes)) => {
-                                    if let Some(message) = MessageParser::new().parse(&eml_bytes) {
-                                        let text = message
-                                            .body_text(0)
-                                            .map(|cow| cow.into_owned())
-                                            .or_else(|| {
-                                                message
-                                                    .body_html(0)
-                                                    .map(|cow| extract_text(cow.into_owned()))
-                                            })
-                                            .unwrap_or_default();
-                                        let body_text =
-                                            text.split_whitespace().collect::<Vec<_>>().join(" ");
-                                        if !body_text.is_empty() {
-                                            new_doc.add_text(f.f_body, &body_text);
-                                        }
-                                    }
-                                }
-                                Ok(None) => {
-                                    tracing::warn!(
-                                        content_hash,
-                                        "EML not found in blob store during tag update"
-                                    );
-                                }
-                                Err(e) => {
-                                    tracing::warn!(
-                                        content_hash,
-                                        error = %e,
-                                        "Failed to fetch EML during tag update"
-                                    );
-                                }
-                            }
-                        }
+                    let mut new_doc = Self::rebuild_document_without_tags(&old_doc);
+                    for facet in &reserved_facets {
+                        new_doc.add_facet(f_tags, facet.clone());
                     }
-
                     for tag in &current_tags {
                         new_doc.add_facet(f_tags, tag);
                     }
@@ -1463,18 +1341,269 @@ impl IndexManager {
                     operations.push(UserOperation::Add(new_doc));
                 }
             }
+
+            writer
+                .run(operations)
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+
+            // commit
+            writer
+                .commit()
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies IMAP flags fetched from the server, keyed by UID, to the
+    /// envelopes of a mailbox. Only documents whose flags actually changed are
+    /// rewritten; user tags are kept. Returns the number of updated envelopes.
+    pub async fn update_envelope_flags(
+        &self,
+        account_id: u64,
+        mailbox_id: u64,
+        flags_by_uid: HashMap<u32, Vec<String>>,
+    ) -> BichonResult<usize> {
+        let uids: Vec<u32> = flags_by_uid.keys().copied().collect();
+        self.rewrite_facets_by_uid(account_id, mailbox_id, &uids, |uid, facets| {
+            facets.retain(|facet| !is_flag_facet(facet));
+            if let Some(flags) = flags_by_uid.get(&uid) {
+                facets.extend(flags.iter().filter_map(|flag| flag_to_facet(flag)));
+            }
+        })
+        .await
+    }
+
+    /// Replaces the Gmail labels and message ID of envelopes, keyed by UID.
+    /// Returns the number of updated envelopes.
+    pub async fn update_gmail_attributes(
+        &self,
+        account_id: u64,
+        mailbox_id: u64,
+        attributes_by_uid: HashMap<u32, GmailAttributes>,
+    ) -> BichonResult<usize> {
+        let uids: Vec<u32> = attributes_by_uid.keys().copied().collect();
+        self.rewrite_facets_by_uid(account_id, mailbox_id, &uids, |uid, facets| {
+            let Some(attributes) = attributes_by_uid.get(&uid) else {
+                return;
+            };
+            facets.retain(|facet| !is_gmail_facet(facet));
+            facets.extend(
+                attributes
+                    .labels
+                    .iter()
+                    .filter_map(|label| label_to_facet(label)),
+            );
+            facets.extend(attributes.msg_id.map(msg_id_facet));
+        })
+        .await
+    }
+
+    /// Marks the envelopes with the given UIDs as expunged on the server.
+    /// They stay in the archive. Returns the number of newly marked envelopes.
+    pub async fn mark_deleted_on_source(
+        &self,
+        account_id: u64,
+        mailbox_id: u64,
+        uids: &[u32],
+    ) -> BichonResult<usize> {
+        let deleted = deleted_on_source_facet();
+        self.rewrite_facets_by_uid(account_id, mailbox_id, uids, |_, facets| {
+            facets.insert(deleted.clone());
+        })
+        .await
+    }
+
+    /// Lets `edit` change the `f_tags` facets of the envelopes with the given
+    /// UIDs in a mailbox and rewrites the documents whose facets changed.
+    async fn rewrite_facets_by_uid<F>(
+        &self,
+        account_id: u64,
+        mailbox_id: u64,
+        uids: &[u32],
+        mut edit: F,
+    ) -> BichonResult<usize>
+    where
+        F: FnMut(u32, &mut HashSet<Facet>),
+    {
+        if uids.is_empty() {
+            return Ok(0);
         }
+        let f = SchemaTools::email_fields();
+        let uid_terms: Vec<Term> = uids
+            .iter()
+            .map(|uid| Term::from_field_u64(f.f_uid, *uid as u64))
+            .collect();
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, self.mailbox_query(account_id, mailbox_id)),
+            (Occur::Must, Box::new(TermSetQuery::new(uid_terms))),
+        ]);
+
+        let searcher = self.shards.account_searcher(account_id)?;
+        let docs = searcher
+            .search(&query, &DocSetCollector)
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
 
+        let mut operations = Vec::new();
+        for doc_address in docs {
+            let old_doc: TantivyDocument = searcher
+                .doc(doc_address)
+                .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            let Some(eid) = old_doc.get_first(f.f_id).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let uid = old_doc
+                .get_first(f.f_uid)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as u32;
+
+            let old_facets: HashSet<Facet> = old_doc
+                .get_all(f.f_tags)
+                .filter_map(|val| val.as_facet())
+                .filter_map(|encoded| Facet::from_encoded(encoded.as_bytes().to_vec()).ok())
+                .collect();
+            let mut new_facets = old_facets.clone();
+            edit(uid, &mut new_facets);
+            if new_facets == old_facets {
+                continue;
+            }
+
+            let mut new_doc = Self::rebuild_document_without_tags(&old_doc);
+            for facet in new_facets {
+                new_doc.add_facet(f.f_tags, facet);
+            }
+            operations.push(UserOperation::Delete(Term::from_field_text(f.f_id, eid)));
+            operations.push(UserOperation::Add(new_doc));
+        }
+
+        let updated = operations.len() / 2;
+        if updated == 0 {
+            return Ok(0);
+        }
+
+        let Some(shard) = self.shards.shard(account_id) else {
+            return Ok(0);
+        };
+        let mut writer = shard.writer().await?;
         writer
             .run(operations)
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-
-        // commit
         writer
             .commit()
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        Ok(updated)
+    }
 
-        Ok(())
+    /// Rebuilds an index document from a stored one, without any `f_tags`
+    /// facets, so a caller can re-add tags or flags and replace the old
+    /// document. Non-stored search fields are reconstructed from their stored
+    /// counterparts and the body text from the EML in the blob store.
+    fn rebuild_document_without_tags(old_doc: &TantivyDocument) -> TantivyDocument {
+        let f = SchemaTools::email_fields();
+        let mut new_doc = TantivyDocument::new();
+
+        // Copy stored fields, excluding f_tags (handled separately).
+        for (field, value) in old_doc.field_values() {
+            if field != f.f_tags {
+                new_doc.add_field_value(field, value);
+            }
+        }
+
+        // Reconstruct non-stored text-search fields from their
+        // stored counterparts. f_from_text / f_to_text / f_cc_text /
+        // f_bcc_text carry the same content as f_from / f_to / f_cc / f_bcc.
+        for val in old_doc.get_all(f.f_from) {
+            if let Some(s) = val.as_str() {
+                new_doc.add_text(f.f_from_text, s);
+            }
+        }
+        for val in old_doc.get_all(f.f_to) {
+            if let Some(s) = val.as_str() {
+                new_doc.add_text(f.f_to_text, s);
+            }
+        }
+        for val in old_doc.get_all(f.f_cc) {
+            if let Some(s) = val.as_str() {
+                new_doc.add_text(f.f_cc_text, s);
+            }
+        }
+        for val in old_doc.get_all(f.f_bcc) {
+            if let Some(s) = val.as_str() {
+                new_doc.add_text(f.f_bcc_text, s);
+            }
+        }
+
+        // Reconstruct attachment-name fields from the stored
+        // f_attachments JSON blob.
+        if let Some(attrs_val) = old_doc.get_first(f.f_attachments) {
+            if let Some(json_str) = attrs_val.as_str() {
+                if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(json_str) {
+                    if let Some(arr) = parsed.as_array() {
+                        for att in arr {
+                            let is_inline =
+                                att.get("inline").and_then(|v| v.as_bool()).unwrap_or(false);
+                            let has_cid = att
+                                .get("content_id")
+                                .and_then(|v| v.as_str())
+                                .map(|s| !s.is_empty())
+                                .unwrap_or(false);
+                            if is_inline && has_cid {
+                                continue;
+                            }
+                            if let Some(filename) = att
+                                .get("filename")
+                                .and_then(|v| v.as_str())
+                                .filter(|s| !s.is_empty())
+                            {
+                                new_doc.add_text(f.f_attachment_name_text, filename);
+                                new_doc.add_text(f.f_attachment_name_exact, filename);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Reconstruct body text from the original EML stored in the
+        // blob store, referenced by f_content_hash.
+        if let Some(hash_val) = old_doc.get_first(f.f_content_hash) {
+            if let Some(content_hash) = hash_val.as_str() {
+                match BLOB_MANAGER.get_email(content_hash) {
+                    Ok(Some(eml_bytes)) => {
+                        if let Some(message) = MessageParser::new().parse(&eml_bytes) {
+                            let text = message
+                                .body_text(0)
+                                .map(|cow| cow.into_owned())
+                                .or_else(|| {
+                                    message
+                                        .body_html(0)
+                                        .map(|cow| extract_text(cow.into_owned()))
+                                })
+                                .unwrap_or_default();
+                            let body_text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !body_text.is_empty() {
+                                new_doc.add_text(f.f_body, &body_text);
+                            }
+                        }
+                    }
+                    Ok(None) => {
+                        tracing::warn!(
+                            content_hash,
+                            "EML not found in blob store while rebuilding document"
+                        );
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            content_hash,
+                            error = %e,
+                            "Failed to fetch EML while rebuilding document"
+                        );
+                    }
+                }
+            }
+        }
+
+        new_doc
     }
 
     pub fn search(
@@ -1488,8 +1617,8 @@ impl IndexManager {
     ) -> BichonResult<DataPage<Envelope>> {
         assert!(page > 0, "Page number must be greater than 0");
         assert!(page_size > 0, "Page size must be greater than 0");
+        let searcher = self.shards.searcher(accounts.as_ref())?;
         let query = self.filter_query(accounts, filter)?;
-        let searcher = self.create_searcher()?;
         let total = searcher
             .search(&query, &Count)
             .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?
@@ -1587,16 +1716,13 @@ impl IndexManager {
         })
     }
 
-    fn create_searcher(&self) -> BichonResult<Searcher> {
-        self.reader
-            .reload()
-            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
-        Ok(self.reader.searcher())
+    pub(crate) fn create_searcher(&self) -> BichonResult<ShardSearcher> {
+        self.shards.searcher(None)
     }
 
     pub fn num_messages_in_thread(
         &self,
-        searcher: &Searcher,
+        searcher: &ShardSearcher,
         account_id: u64,
         thread_id: &str,
     ) -> BichonResult<u64> {
@@ -1652,7 +1778,7 @@ impl IndexManager {
     ) -> BichonResult<DataPage<Envelope>> {
         assert!(page > 0, "Page number must be greater than 0");
         assert!(page_size > 0, "Page size must be greater than 0");
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.account_searcher(account_id)?;
         let total = self.num_messages_in_thread(&searcher, account_id, thread_id)?;
         if total == 0 {
             return Ok(DataPage {
@@ -1708,7 +1834,7 @@ impl IndexManager {
         &self,
         accounts: &Option<HashSet<u64>>,
     ) -> BichonResult<DashboardStats> {
-        let searcher = self.create_searcher()?;
+        let searcher = self.shards.searcher(accounts.as_ref())?;
         let now_ms = utc_now!();
         let week_ago_ms = (Utc::now() - Duration::from_secs(60 * 60 * 24 * 30)).timestamp_millis();
 
@@ -2197,6 +2323,57 @@ mod tests {
         assert_eq!(old_tag_hits, 0, "old tag /unread should have been removed");
     }
 
+    #[test]
+    fn flag_facets_are_searchable_and_kept_apart_from_tags() {
+        let f = SchemaTools::email_fields();
+        let index = Index::create_in_ram(SchemaTools::email_schema());
+        index.tokenizers().register("euro", EuroTokenizer::new());
+
+        {
+            let mut writer = index
+                .writer_with_num_threads(1, 15_000_000)
+                .expect("writer");
+            let mut doc = build_test_doc();
+            for flag in ["\\Flagged", "ProjectX"] {
+                doc.add_facet(f.f_tags, flag_to_facet(flag).unwrap());
+            }
+            writer.add_document(doc).unwrap();
+            writer.commit().unwrap();
+        }
+
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+        for term in ["flagged", "\\Flagged", "projectx"] {
+            let facet = search_term_to_facet(term).unwrap();
+            let hits = searcher
+                .search(
+                    &TermQuery::new(Term::from_facet(f.f_tags, &facet), IndexRecordOption::Basic),
+                    &Count,
+                )
+                .unwrap();
+            assert_eq!(hits, 1, "flag search term {term} should match");
+        }
+
+        let stored: TantivyDocument = searcher
+            .doc(
+                searcher
+                    .search(&AllQuery, &TopDocs::with_limit(1).order_by_score())
+                    .unwrap()[0]
+                    .1,
+            )
+            .unwrap();
+        let (flags, tags): (Vec<Facet>, Vec<Facet>) = stored
+            .get_all(f.f_tags)
+            .filter_map(|val| val.as_facet())
+            .filter_map(|encoded| Facet::from_encoded(encoded.as_bytes().to_vec()).ok())
+            .partition(is_flag_facet);
+        assert_eq!(flags.len(), 2);
+        assert_eq!(
+            tags.iter().map(|t| t.to_string()).collect::<HashSet<_>>(),
+            HashSet::from(["/inbox".to_string(), "/unread".to_string()])
+        );
+    }
+
     #[test]
     fn body_reconstruction_from_eml_cache() {
         // Verify the EML → body_text extraction used inside
@@ -30,7 +30,9 @@ pub mod envelope;
 pub mod fields;
 pub mod filter;
 pub mod model;
+pub mod reindex;
 pub mod schema;
+pub mod shard;
 pub mod tokenizers;
 
 pub fn fatal_commit(writer: &mut IndexWriter) {
@@ -23,14 +23,21 @@ use tantivy::{
     schema::{Facet, Value},
     TantivyDocument,
 };
+use uuid::Uuid;
 
 use crate::{
     raise_error,
     {
         account::migration::AccountModel,
         cache::imap::mailbox::MailBox,
+        envelope::{
+            archive::{archive_facet, facet_to_archive},
+            authentication::{facets_to_summary, summary_to_facets},
+            flags::{deleted_on_source_facet, facet_to_flag, flag_to_facet, is_reserved_facet},
+            gmail::{facet_to_label, facet_to_msg_id, label_to_facet, msg_id_facet},
+        },
         error::{code::ErrorCode, BichonResult},
-        message::content::AttachmentInfo,
+        message::content::{ArchiveMember, AttachmentInfo},
         store::{
             envelope::Envelope,
             tantivy::{
@@ -116,6 +123,32 @@ impl EnvelopeWithAttachments {
             }
         }
 
+        if let Some(flags) = &self.envelope.flags {
+            for facet in flags.iter().filter_map(|flag| flag_to_facet(flag)) {
+                doc.add_facet(fields.f_tags, facet);
+            }
+        }
+
+        if self.envelope.deleted_on_source {
+            doc.add_facet(fields.f_tags, deleted_on_source_facet());
+        }
+
+        if let Some(labels) = &self.envelope.labels {
+            for facet in labels.iter().filter_map(|label| label_to_facet(label)) {
+                doc.add_facet(fields.f_tags, facet);
+            }
+        }
+
+        if let Some(msg_id) = self.envelope.gmail_msg_id {
+            doc.add_facet(fields.f_tags, msg_id_facet(msg_id));
+        }
+
+        if let Some(summary) = &self.envelope.authentication {
+            for facet in summary_to_facets(summary) {
+                doc.add_facet(fields.f_tags, facet);
+            }
+        }
+
         doc.add_u64(
             fields.f_attachment_count,
             self.envelope.attachment_count as u64,
@@ -135,16 +168,21 @@ impl EnvelopeWithAttachments {
         let attachments: Option<Vec<AttachmentInfo>> =
             attachments_raw.and_then(|json| serde_json::from_str(&json).ok());
 
-        let tags: Vec<String> = doc
+        // IMAP flags, the source state, Gmail metadata and authentication
+        // results share the facet field with user tags; split them apart.
+        let (reserved_facets, tag_facets): (Vec<Facet>, Vec<Facet>) = doc
             .get_all(fields.f_tags)
             .filter_map(|value| value.as_facet())
-            .map(|facet_encoded_str| {
-                Facet::from_encoded(facet_encoded_str.as_bytes().to_vec())
-                    .ok()
-                    .map(|facet| facet.to_string())
+            .filter_map(|facet_encoded_str| {
+                Facet::from_encoded(facet_encoded_str.as_bytes().to_vec()).ok()
             })
-            .flatten()
-            .collect();
+            .partition(is_reserved_facet);
+        let tags: Vec<String> = tag_facets.iter().map(|facet| facet.to_string()).collect();
+        let flags: Vec<String> = reserved_facets.iter().filter_map(facet_to_flag).collect();
+        let deleted_on_source = reserved_facets.contains(&deleted_on_source_facet());
+        let labels: Vec<String> = reserved_facets.iter().filter_map(facet_to_label).collect();
+        let gmail_msg_id = reserved_facets.iter().find_map(facet_to_msg_id);
+        let authentication = facets_to_summary(&reserved_facets);
 
         let account_id = extract_u64_field(doc, fields.f_account_id, F_ACCOUNT_ID)?;
         let mailbox_id = extract_u64_field(doc, fields.f_mailbox_id, F_MAILBOX_ID)?;
@@ -178,6 +216,11 @@ impl EnvelopeWithAttachments {
                 F_REGULAR_ATTACHMENT_COUNT,
             )? as usize,
             tags: (!tags.is_empty()).then_some(tags),
+            flags: (!flags.is_empty()).then_some(flags),
+            deleted_on_source,
+            labels: (!labels.is_empty()).then_some(labels),
+            gmail_msg_id,
+            authentication,
             content_hash: extract_string_field(doc, fields.f_content_hash, F_CONTENT_HASH)?,
             ingest_at: extract_i64_field(doc, fields.f_ingest_at, F_INGEST_AT)?,
         };
@@ -347,9 +390,38 @@ pub struct AttachmentModel {
     pub name: Option<String>,
     pub tags: Option<Vec<String>>,
     pub auto_tags: Option<Vec<String>>,
+    /// For a file expanded from an archive attachment, the content hash of
+    /// that attachment; `name` is then the file's path inside the archive.
+    #[serde(default)]
+    pub archive_content_hash: Option<String>,
 }
 
 impl AttachmentModel {
+    /// The document of `member`, a file inside the archive attachment
+    /// `self`. Everything about the message is taken from the archive.
+    pub fn archive_member(&self, member: &ArchiveMember) -> AttachmentModel {
+        let has_text = member.extracted_text.is_some();
+        AttachmentModel {
+            id: Uuid::new_v4().to_string(),
+            content_hash: member.content_hash.clone(),
+            size: member.size as u64,
+            ext: member.get_extension(),
+            category: member.get_category().to_string(),
+            content_type: member.file_type.clone(),
+            text: member.extracted_text.clone(),
+            has_text,
+            is_ocr: member.extracted_is_ocr,
+            page_count: member.extracted_page_count.map(|n| n as u64),
+            is_indexed: has_text,
+            is_message: false,
+            name: Some(member.path.clone()),
+            tags: None,
+            auto_tags: None,
+            archive_content_hash: Some(self.content_hash.clone()),
+            ..self.clone()
+        }
+    }
+
     pub fn into_document(self) -> TantivyDocument {
         let f = SchemaTools::attachment_fields();
         let mut doc = TantivyDocument::new();
@@ -403,22 +475,26 @@ impl AttachmentModel {
             }
         }
 
+        if let Some(archive) = &self.archive_content_hash {
+            doc.add_facet(f.f_tags, archive_facet(archive));
+        }
+
         doc
     }
 
     pub fn from_tantivy_doc(doc: &TantivyDocument) -> BichonResult<Self> {
         let f = SchemaTools::attachment_fields();
 
-        let tags: Vec<String> = doc
+        // Archive membership shares the facet field with user tags.
+        let (reserved_facets, tag_facets): (Vec<Facet>, Vec<Facet>) = doc
             .get_all(f.f_tags)
             .filter_map(|value| value.as_facet())
-            .map(|facet_encoded_str| {
-                Facet::from_encoded(facet_encoded_str.as_bytes().to_vec())
-                    .ok()
-                    .map(|facet| facet.to_string())
+            .filter_map(|facet_encoded_str| {
+                Facet::from_encoded(facet_encoded_str.as_bytes().to_vec()).ok()
             })
-            .flatten()
-            .collect();
+            .partition(is_reserved_facet);
+        let tags: Vec<String> = tag_facets.iter().map(|facet| facet.to_string()).collect();
+        let archive_content_hash = reserved_facets.iter().find_map(facet_to_archive);
 
         let auto_tags: Vec<String> = doc
             .get_all(f.f_auto_tags)
@@ -465,6 +541,7 @@ impl AttachmentModel {
             } else {
                 Some(auto_tags)
             },
+            archive_content_hash,
         })
     }
 }
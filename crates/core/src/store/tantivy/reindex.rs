@@ -0,0 +1,971 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rebuilds the envelope and attachment indexes from the blob store.
+//!
+//! Every live envelope of the current index is re-parsed from its stored
+//! EML, so a corrupted index or a changed [`SchemaTools::email_schema`] can be
+//! recovered without re-downloading anything. What cannot be derived from the
+//! message itself is carried over from the old documents: envelope and
+//! attachment ids, `ingest_at`, uid, flags, tags and Gmail metadata, and
+//! extracted attachment text. Encrypted messages keep the preview and the
+//! attachments decrypted at ingestion, but the job has no access to account
+//! keys, so their body text is not rebuilt.
+//!
+//! The new indexes are written next to the live ones (`<dir>.reindex`), one
+//! account shard at a time, and committed every `batch_size` envelopes.
+//! Envelopes already present there are skipped, so an interrupted run resumes
+//! where it stopped. Once the rebuild is done, [`ReindexDirs::swap`] renames
+//! the new directories into place.
+//!
+//! The job opens the index directories and the blob store directly, so the
+//! server must be stopped while it runs.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use mail_parser::{Message, MessageParser};
+use tantivy::{
+    collector::DocSetCollector,
+    query::TermQuery,
+    schema::{Facet, IndexRecordOption, Value},
+    IndexWriter, TantivyDocument, Term,
+};
+use uuid::Uuid;
+
+use crate::{
+    envelope::{
+        decrypt::is_encrypted,
+        extractor::{attachment_info, MessageFields},
+        flags::is_reserved_facet,
+    },
+    error::{code::ErrorCode, BichonResult},
+    message::content::AttachmentInfo,
+    raise_error,
+    store::{
+        blob::{backend::BlobStore, hex_to_key},
+        envelope::Envelope,
+        tantivy::{
+            model::{AttachmentModel, EnvelopeWithAttachments},
+            schema::SchemaTools,
+            shard::{Shard, ShardSearcher, ShardedIndex},
+        },
+    },
+    utils::compute_content_hash,
+};
+
+const SIDE_SUFFIX: &str = "reindex";
+const BACKUP_SUFFIX: &str = "pre-reindex";
+const PLACEHOLDER_PREFIX: &[u8] = b"<<BICHON_DETACH_HASH:";
+const PLACEHOLDER_SUFFIX: &[u8] = b">>";
+const WRITER_HEAP_BYTES: usize = 128 * 1024 * 1024;
+
+/// Live index directories to rebuild.
+#[derive(Debug, Clone)]
+pub struct ReindexDirs {
+    pub envelope_dir: PathBuf,
+    pub attachment_dir: PathBuf,
+}
+
+impl ReindexDirs {
+    pub fn new(envelope_dir: PathBuf, attachment_dir: PathBuf) -> Self {
+        Self {
+            envelope_dir,
+            attachment_dir,
+        }
+    }
+
+    fn live(&self) -> [&Path; 2] {
+        [self.envelope_dir.as_path(), self.attachment_dir.as_path()]
+    }
+
+    /// A previous rebuild left side indexes behind that can be resumed.
+    pub fn has_partial_rebuild(&self) -> bool {
+        self.live()
+            .iter()
+            .any(|dir| sibling(dir, SIDE_SUFFIX).exists())
+    }
+
+    /// A previous swap stopped half-way; [`ReindexDirs::swap`] completes it.
+    pub fn has_pending_swap(&self) -> bool {
+        self.live()
+            .iter()
+            .any(|dir| sibling(dir, BACKUP_SUFFIX).exists())
+    }
+
+    /// Drops the side indexes of an earlier run so the next one starts over.
+    pub fn discard_partial_rebuild(&self) -> BichonResult<()> {
+        for dir in self.live() {
+            let side = sibling(dir, SIDE_SUFFIX);
+            if side.exists() {
+                std::fs::remove_dir_all(&side).map_err(|e| {
+                    raise_error!(
+                        format!("Failed to remove {}: {:#?}", side.display(), e),
+                        ErrorCode::InternalError
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the rebuilt indexes into place and removes the old ones.
+    ///
+    /// Each index is swapped with two renames, old index out first. The
+    /// step is idempotent: re-running it after a crash finishes the swap.
+    pub fn swap(&self) -> BichonResult<()> {
+        for live in self.live() {
+            let side = sibling(live, SIDE_SUFFIX);
+            let backup = sibling(live, BACKUP_SUFFIX);
+            if !side.exists() {
+                continue;
+            }
+            if live.exists() {
+                if backup.exists() {
+                    std::fs::remove_dir_all(&backup).map_err(internal)?;
+                }
+                std::fs::rename(live, &backup).map_err(internal)?;
+            }
+            std::fs::rename(&side, live).map_err(internal)?;
+        }
+        for live in self.live() {
+            let backup = sibling(live, BACKUP_SUFFIX);
+            if backup.exists() {
+                std::fs::remove_dir_all(&backup).map_err(internal)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sibling(dir: &Path, suffix: &str) -> PathBuf {
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dir.with_file_name(format!("{name}.{suffix}"))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ReindexStats {
+    /// Live envelopes in the current index.
+    pub total: u64,
+    pub rebuilt: u64,
+    /// Already rebuilt by an interrupted earlier run.
+    pub resumed: u64,
+    /// Dropped because their mailbox no longer exists.
+    pub orphaned: u64,
+    /// Envelope ids that could not be rebuilt, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+impl ReindexStats {
+    pub fn processed(&self) -> u64 {
+        self.rebuilt + self.resumed + self.orphaned + self.failed.len() as u64
+    }
+}
+
+pub struct ReindexJob<'a> {
+    dirs: ReindexDirs,
+    engine: &'a dyn BlobStore,
+    mailboxes: HashSet<u64>,
+    batch_size: usize,
+}
+
+impl<'a> ReindexJob<'a> {
+    /// `mailboxes` are the mailbox ids known to memdb; envelopes of any other
+    /// mailbox are left out of the new index.
+    pub fn new(
+        dirs: ReindexDirs,
+        engine: &'a dyn BlobStore,
+        mailboxes: HashSet<u64>,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            dirs,
+            engine,
+            mailboxes,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Rebuilds both indexes into their side directories. `on_progress` is
+    /// called after every commit.
+    pub fn run<F>(&self, mut on_progress: F) -> BichonResult<ReindexStats>
+    where
+        F: FnMut(&ReindexStats),
+    {
+        let source = ShardedIndex::open(&self.dirs.envelope_dir, SchemaTools::email_schema())?;
+        // The old attachment index only contributes ids, tags and extracted
+        // text; losing it must not stop the rebuild.
+        let old_attachments =
+            match ShardedIndex::open(&self.dirs.attachment_dir, SchemaTools::attachment_schema()) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    tracing::warn!("Attachment index unreadable, attachment tags are lost: {e:#?}");
+                    None
+                }
+            };
+
+        let side_envelopes = ShardedIndex::open(
+            &sibling(&self.dirs.envelope_dir, SIDE_SUFFIX),
+            SchemaTools::email_schema(),
+        )?;
+        let side_attachments = ShardedIndex::open(
+            &sibling(&self.dirs.attachment_dir, SIDE_SUFFIX),
+            SchemaTools::attachment_schema(),
+        )?;
+
+        let mut stats = ReindexStats {
+            total: source.searcher(None)?.num_docs(),
+            ..Default::default()
+        };
+        for shard in source.shards() {
+            self.run_shard(
+                &shard,
+                old_attachments.as_ref(),
+                &side_envelopes,
+                &side_attachments,
+                &mut stats,
+                &mut on_progress,
+            )?;
+        }
+        on_progress(&stats);
+        Ok(stats)
+    }
+
+    /// Rebuilds the shard of one account into the side indexes.
+    fn run_shard<F>(
+        &self,
+        shard: &Shard,
+        old_attachments: Option<&ShardedIndex>,
+        side_envelopes: &ShardedIndex,
+        side_attachments: &ShardedIndex,
+        stats: &mut ReindexStats,
+        on_progress: &mut F,
+    ) -> BichonResult<()>
+    where
+        F: FnMut(&ReindexStats),
+    {
+        let account_id = shard.id();
+        let source_searcher = shard.searcher()?;
+        let old_attachments = old_attachments
+            .map(|index| index.account_searcher(account_id))
+            .transpose()?;
+        let side_envelopes = side_envelopes.shard_or_create(account_id)?;
+        let side_attachments = side_attachments.shard_or_create(account_id)?;
+        let done = side_envelopes.searcher()?;
+        let mut envelope_writer: IndexWriter = side_envelopes
+            .index()
+            .writer(WRITER_HEAP_BYTES)
+            .map_err(internal)?;
+        let mut attachment_writer: IndexWriter = side_attachments
+            .index()
+            .writer(WRITER_HEAP_BYTES)
+            .map_err(internal)?;
+
+        let f = SchemaTools::email_fields();
+        let af = SchemaTools::attachment_fields();
+        let mut pending = 0;
+
+        for (ordinal, segment) in source_searcher.segment_readers().iter().enumerate() {
+            let store = segment.get_store_reader(2).map_err(internal)?;
+            for doc_id in 0..segment.max_doc() {
+                if segment.is_deleted(doc_id) {
+                    continue;
+                }
+                let location = || format!("shard {account_id} segment {ordinal} doc {doc_id}");
+                let doc: TantivyDocument = match store.get(doc_id) {
+                    Ok(doc) => doc,
+                    Err(e) => {
+                        stats.failed.push((location(), e.to_string()));
+                        continue;
+                    }
+                };
+                let Some(id) = doc.get_first(f.f_id).and_then(|v| v.as_str()) else {
+                    stats
+                        .failed
+                        .push((location(), "missing envelope id".into()));
+                    continue;
+                };
+                let id = id.to_string();
+
+                if done
+                    .doc_freq(&Term::from_field_text(f.f_id, &id))
+                    .map_err(internal)?
+                    > 0
+                {
+                    stats.resumed += 1;
+                    continue;
+                }
+                let mailbox_id = doc.get_first(f.f_mailbox_id).and_then(|v| v.as_u64());
+                if !mailbox_id.is_some_and(|m| self.mailboxes.contains(&m)) {
+                    stats.orphaned += 1;
+                    continue;
+                }
+
+                match self.rebuild(&doc, old_attachments.as_ref()) {
+                    Ok((envelope_doc, attachment_docs)) => {
+                        // Attachments are committed before envelopes, so a
+                        // crash in between leaves some that get rebuilt again.
+                        attachment_writer.delete_term(Term::from_field_text(af.f_envelope_id, &id));
+                        for attachment_doc in attachment_docs {
+                            attachment_writer
+                                .add_document(attachment_doc)
+                                .map_err(internal)?;
+                        }
+                        envelope_writer
+                            .add_document(envelope_doc)
+                            .map_err(internal)?;
+                        stats.rebuilt += 1;
+                        pending += 1;
+                    }
+                    Err(e) => {
+                        tracing::warn!(envelope_id = %id, "Failed to rebuild envelope: {e:#?}");
+                        stats.failed.push((id, e.to_string()));
+                    }
+                }
+
+                if pending >= self.batch_size {
+                    attachment_writer.commit().map_err(internal)?;
+                    envelope_writer.commit().map_err(internal)?;
+                    pending = 0;
+                    on_progress(stats);
+                }
+            }
+        }
+
+        attachment_writer.commit().map_err(internal)?;
+        envelope_writer.commit().map_err(internal)?;
+        attachment_writer.wait_merging_threads().map_err(internal)?;
+        envelope_writer.wait_merging_threads().map_err(internal)?;
+        Ok(())
+    }
+
+    fn rebuild(
+        &self,
+        old: &TantivyDocument,
+        old_attachments: Option<&ShardSearcher>,
+    ) -> BichonResult<(TantivyDocument, Vec<TantivyDocument>)> {
+        let f = SchemaTools::email_fields();
+        let id = stored_str(old, f.f_id, "id")?;
+        let content_hash = stored_str(old, f.f_content_hash, "content_hash")?;
+        let account_id = stored_u64(old, f.f_account_id, "account_id")?;
+        let mailbox_id = stored_u64(old, f.f_mailbox_id, "mailbox_id")?;
+        let ingest_at = stored_i64(old, f.f_ingest_at, "ingest_at")?;
+        // Documents are sharded by account.
+        let shard_id = account_id;
+
+        let stripped = self.blob(&content_hash)?.ok_or_else(|| {
+            raise_error!(
+                format!("Email blob {content_hash} not found"),
+                ErrorCode::ResourceNotFound
+            )
+        })?;
+        let eml = restore_detached(&stripped, |hash| self.blob(hash))?;
+        let message: Message<'_> = MessageParser::new().parse(&eml).ok_or_else(|| {
+            raise_error!(
+                "Email header parse result is not available".into(),
+                ErrorCode::InternalError
+            )
+        })?;
+
+        let mut fields = MessageFields::from_message(&message);
+        // Without a Message-ID one was generated at ingestion; keep it so
+        // threads stay intact.
+        if message.message_id().is_none() {
+            if let Ok(message_id) = stored_str(old, f.f_message_id, "message_id") {
+                fields.message_id = message_id;
+            }
+            if let Ok(thread_id) = stored_str(old, f.f_thread_id, "thread_id") {
+                fields.thread_id = thread_id;
+            }
+        }
+
+        // The plaintext of an encrypted message cannot be rebuilt without
+        // its account's keys; keep the preview derived from it at ingestion.
+        let encrypted = is_encrypted(&message);
+        if encrypted {
+            if let Ok(preview) = stored_str(old, f.f_preview, "preview") {
+                fields.preview = preview;
+            }
+        }
+
+        // Extracted text is expensive to produce (OCR) and not stored in the
+        // attachment index; reuse what the old envelope recorded.
+        let old_list: Vec<AttachmentInfo> = old
+            .get_first(f.f_attachments)
+            .and_then(|v| v.as_str())
+            .and_then(|json| serde_json::from_str::<Vec<AttachmentInfo>>(json).ok())
+            .unwrap_or_default();
+        let old_infos: HashMap<String, AttachmentInfo> = old_list
+            .iter()
+            .map(|info| (info.content_hash.clone(), info.clone()))
+            .collect();
+        let mut attachments: Vec<AttachmentInfo> = message
+            .attachments()
+            .map(|att| {
+                let mut info = attachment_info(att, compute_content_hash(att.contents()));
+                if let Some(previous) = old_infos.get(&info.content_hash) {
+                    info.extracted_text = previous.extracted_text.clone();
+                    info.extracted_page_count = previous.extracted_page_count;
+                    info.extracted_is_ocr = previous.extracted_is_ocr;
+                    info.archive_members = previous.archive_members.clone();
+                }
+                info
+            })
+            .collect();
+        // Attachments detached from the plaintext of an encrypted message
+        // follow those of the ciphertext and are carried over as they are.
+        let ciphertext_parts = attachments.len();
+        if encrypted {
+            let ciphertext: HashSet<String> =
+                attachments.iter().map(|a| a.content_hash.clone()).collect();
+            attachments.extend(
+                old_list
+                    .into_iter()
+                    .filter(|info| !ciphertext.contains(&info.content_hash)),
+            );
+        }
+        let skipped = if attachments.len() > ciphertext_parts {
+            ciphertext_parts
+        } else {
+            0
+        };
+
+        let mut preserved = old_attachments
+            .map(|searcher| preserved_attachments(searcher, &id))
+            .transpose()?
+            .unwrap_or_default();
+        let regular_attachments: Vec<&AttachmentInfo> = attachments
+            .iter()
+            .skip(skipped)
+            .filter(|a| !a.inline || a.content_id.is_none())
+            .collect();
+        let attachment_docs: Vec<TantivyDocument> = regular_attachments
+            .iter()
+            .flat_map(|a| {
+                let previous = preserved
+                    .get_mut(&a.content_hash)
+                    .and_then(|list| list.pop());
+                let has_text = a.extracted_text.is_some();
+                let model = AttachmentModel {
+                    id: previous
+                        .as_ref()
+                        .map(|p| p.id.clone())
+                        .unwrap_or_else(|| Uuid::new_v4().to_string()),
+                    envelope_id: id.clone(),
+                    account_id,
+                    account_email: None,
+                    mailbox_id,
+                    mailbox_name: None,
+                    subject: fields.subject.clone(),
+                    content_hash: a.content_hash.clone(),
+                    from: fields.from.clone(),
+                    date: fields.date,
+                    ingest_at,
+                    size: a.size as u64,
+                    ext: a.get_extension(),
+                    category: a.get_category().to_string(),
+                    content_type: a.file_type.clone(),
+                    shard_id,
+                    text: a.extracted_text.clone(),
+                    has_text,
+                    is_ocr: a.extracted_is_ocr,
+                    page_count: a.extracted_page_count.map(|n| n as u64),
+                    is_indexed: has_text,
+                    is_message: a.is_message,
+                    name: a.filename.clone(),
+                    tags: previous.as_ref().and_then(|p| p.tags.clone()),
+                    auto_tags: previous.and_then(|p| p.auto_tags),
+                    archive_content_hash: None,
+                };
+                let members: Vec<AttachmentModel> = a
+                    .archive_members
+                    .iter()
+                    .flatten()
+                    .map(|member| {
+                        let mut doc = model.archive_member(member);
+                        if let Some(previous) = preserved
+                            .get_mut(&member.content_hash)
+                            .and_then(|list| list.pop())
+                        {
+                            doc.id = previous.id;
+                            doc.tags = previous.tags;
+                            doc.auto_tags = previous.auto_tags;
+                        }
+                        doc
+                    })
+                    .collect();
+                std::iter::once(model).chain(members)
+            })
+            .map(|a| a.into_document())
+            .collect();
+
+        let envelope = Envelope {
+            id,
+            message_id: fields.message_id,
+            account_id,
+            mailbox_id,
+            uid: stored_u64(old, f.f_uid, "uid")? as u32,
+            subject: fields.subject,
+            preview: fields.preview,
+            from: fields.from,
+            to: fields.to,
+            cc: fields.cc,
+            bcc: fields.bcc,
+            date: fields.date,
+            internal_date: old
+                .get_first(f.f_internal_date)
+                .and_then(|v| v.as_i64())
+                .unwrap_or(fields.date),
+            ingest_at,
+            // The server-reported size, which may differ from the EML length.
+            size: stored_u64(old, f.f_size, "size")? as u32,
+            thread_id: fields.thread_id,
+            attachment_count: attachments.len(),
+            regular_attachment_count: regular_attachments.len(),
+            tags: None,
+            flags: None,
+            deleted_on_source: false,
+            labels: None,
+            gmail_msg_id: None,
+            authentication: None,
+            account_email: None,
+            account_name: None,
+            mailbox_name: None,
+            content_hash,
+        };
+        let mut doc = EnvelopeWithAttachments {
+            envelope,
+            attachments: Some(attachments),
+        }
+        .to_document(&fields.body_text, shard_id)?;
+        // Tags, flags, the source state, Gmail metadata and authentication
+        // results all live in the facet field; copy them over as they are.
+        for facet in facets(old, f.f_tags) {
+            doc.add_facet(f.f_tags, facet);
+        }
+        Ok((doc, attachment_docs))
+    }
+
+    fn blob(&self, content_hash: &str) -> BichonResult<Option<Bytes>> {
+        let key = hex_to_key(content_hash)?;
+        self.engine.get(&key).map(|v| v.map(Bytes::from))
+    }
+}
+
+/// Old attachment state that cannot be recovered from the EML.
+struct PreservedAttachment {
+    id: String,
+    tags: Option<Vec<String>>,
+    auto_tags: Option<Vec<String>>,
+}
+
+/// Old attachment documents of one envelope, grouped by content hash.
+fn preserved_attachments(
+    searcher: &ShardSearcher,
+    envelope_id: &str,
+) -> BichonResult<HashMap<String, Vec<PreservedAttachment>>> {
+    let af = SchemaTools::attachment_fields();
+    let query = TermQuery::new(
+        Term::from_field_text(af.f_envelope_id, envelope_id),
+        IndexRecordOption::Basic,
+    );
+    let mut preserved: HashMap<String, Vec<PreservedAttachment>> = HashMap::new();
+    for address in searcher
+        .search(&query, &DocSetCollector)
+        .map_err(internal)?
+    {
+        let doc: TantivyDocument = searcher.doc(address).map_err(internal)?;
+        let (Ok(id), Ok(content_hash)) = (
+            stored_str(&doc, af.f_id, "id"),
+            stored_str(&doc, af.f_content_hash, "content_hash"),
+        ) else {
+            continue;
+        };
+        // The archive link is derived from the envelope, not carried over.
+        let tags: Vec<String> = facets(&doc, af.f_tags)
+            .filter(|f| !is_reserved_facet(f))
+            .map(|f| f.to_string())
+            .collect();
+        let auto_tags: Vec<String> = facets(&doc, af.f_auto_tags)
+            .map(|f| f.to_string())
+            .collect();
+        preserved
+            .entry(content_hash)
+            .or_default()
+            .push(PreservedAttachment {
+                id,
+                tags: (!tags.is_empty()).then_some(tags),
+                auto_tags: (!auto_tags.is_empty()).then_some(auto_tags),
+            });
+    }
+    Ok(preserved)
+}
+
+/// Puts the detached attachment blobs back in place of their
+/// `<<BICHON_DETACH_HASH:..>>` placeholders.
+fn restore_detached<F>(stripped: &[u8], mut fetch: F) -> BichonResult<Vec<u8>>
+where
+    F: FnMut(&str) -> BichonResult<Option<Bytes>>,
+{
+    let mut restored = Vec::with_capacity(stripped.len());
+    let mut rest = stripped;
+    while let Some(start) = find(rest, PLACEHOLDER_PREFIX) {
+        restored.extend_from_slice(&rest[..start]);
+        let after = &rest[start + PLACEHOLDER_PREFIX.len()..];
+        let hash = find(after, PLACEHOLDER_SUFFIX)
+            .map(|end| &after[..end])
+            .filter(|hash| hash.len() == 64 && hash.iter().all(u8::is_ascii_hexdigit));
+        let Some(hash) = hash else {
+            // Not one of ours, e.g. a message quoting a placeholder.
+            restored.extend_from_slice(PLACEHOLDER_PREFIX);
+            rest = after;
+            continue;
+        };
+        let hash = std::str::from_utf8(hash).unwrap_or_default();
+        let data = fetch(hash)?.ok_or_else(|| {
+            raise_error!(
+                format!("Attachment blob {hash} not found"),
+                ErrorCode::ResourceNotFound
+            )
+        })?;
+        restored.extend_from_slice(&data);
+        rest = &after[hash.len() + PLACEHOLDER_SUFFIX.len()..];
+    }
+    restored.extend_from_slice(rest);
+    Ok(restored)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn facets(
+    doc: &TantivyDocument,
+    field: tantivy::schema::Field,
+) -> impl Iterator<Item = Facet> + '_ {
+    doc.get_all(field)
+        .filter_map(|value| value.as_facet())
+        .filter_map(|encoded| Facet::from_encoded(encoded.as_bytes().to_vec()).ok())
+}
+
+fn stored_str(
+    doc: &TantivyDocument,
+    field: tantivy::schema::Field,
+    name: &str,
+) -> BichonResult<String> {
+    doc.get_first(field)
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .ok_or_else(|| missing(name))
+}
+
+fn stored_u64(
+    doc: &TantivyDocument,
+    field: tantivy::schema::Field,
+    name: &str,
+) -> BichonResult<u64> {
+    doc.get_first(field)
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| missing(name))
+}
+
+fn stored_i64(
+    doc: &TantivyDocument,
+    field: tantivy::schema::Field,
+    name: &str,
+) -> BichonResult<i64> {
+    doc.get_first(field)
+        .and_then(|v| v.as_i64())
+        .ok_or_else(|| missing(name))
+}
+
+fn missing(name: &str) -> crate::error::BichonError {
+    raise_error!(
+        format!("miss '{}' field in tantivy document", name),
+        ErrorCode::InternalError
+    )
+}
+
+pub(crate) fn internal<E: std::fmt::Debug>(e: E) -> crate::error::BichonError {
+    raise_error!(format!("{:#?}", e), ErrorCode::InternalError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::flags::flag_to_facet;
+    use bichon_blob::{Codec, Config, Engine};
+    use tantivy::query::AllQuery;
+
+    const EML: &str = "From: Alice <alice@example.com>\r\n\
+To: bob@example.com\r\n\
+Subject: Quarterly report\r\n\
+Message-ID: <report@example.com>\r\n\
+Date: Mon, 6 Jan 2025 10:00:00 +0000\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Numbers attached.\r\n\
+--b1\r\n\
+Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"report.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0xLjQKJcOkw7zDtsOfCg==\r\n\
+--b1--\r\n";
+
+    struct Fixture {
+        root: PathBuf,
+        dirs: ReindexDirs,
+        engine: Engine,
+        attachment_hash: String,
+    }
+
+    impl Fixture {
+        /// An archive holding one message with a PDF attachment in mailbox 1,
+        /// plus an envelope whose mailbox was deleted.
+        fn new() -> Self {
+            let root = std::env::temp_dir()
+                .join("bichon-reindex-test")
+                .join(Uuid::new_v4().to_string());
+            let dirs =
+                ReindexDirs::new(root.join("mail_metadata"), root.join("attachment_metadata"));
+            let config = Config {
+                flush_interval_secs: 0,
+                gc_interval_secs: 0,
+                ..Config::default()
+            };
+            let engine = Engine::open(&root.join("blobs"), config).unwrap();
+
+            let message = MessageParser::new().parse(EML.as_bytes()).unwrap();
+            let att = message.attachments().next().unwrap();
+            let attachment_hash = compute_content_hash(att.contents());
+            let (start, end) = (
+                att.raw_body_offset() as usize,
+                att.raw_end_offset() as usize,
+            );
+            let mut stripped = EML.as_bytes().to_vec();
+            stripped.splice(
+                start..end,
+                format!("<<BICHON_DETACH_HASH:{attachment_hash}>>").into_bytes(),
+            );
+            let email_hash = compute_content_hash(EML.as_bytes());
+            engine
+                .put(hex_to_key(&email_hash).unwrap(), &stripped, Codec::Lz4)
+                .unwrap();
+            engine
+                .put(
+                    hex_to_key(&attachment_hash).unwrap(),
+                    &EML.as_bytes()[start..end],
+                    Codec::Lz4,
+                )
+                .unwrap();
+
+            let envelopes =
+                ShardedIndex::open(&dirs.envelope_dir, SchemaTools::email_schema()).unwrap();
+            let mut writer: IndexWriter = envelopes
+                .shard_or_create(7)
+                .unwrap()
+                .index()
+                .writer(50_000_000)
+                .unwrap();
+            let mut info = attachment_info(att, attachment_hash.clone());
+            info.extracted_text = Some("revenue up".into());
+            for (id, mailbox_id) in [("env-1", 1), ("env-2", 2)] {
+                let stale = Envelope {
+                    id: id.into(),
+                    message_id: "<report@example.com>".into(),
+                    account_id: 7,
+                    mailbox_id,
+                    uid: 12,
+                    subject: "stale subject".into(),
+                    internal_date: 1_000,
+                    ingest_at: 42,
+                    size: 4_096,
+                    tags: Some(vec!["/work/project".into()]),
+                    flags: Some(vec!["\\Seen".into()]),
+                    content_hash: email_hash.clone(),
+                    ..Default::default()
+                };
+                let doc = EnvelopeWithAttachments {
+                    envelope: stale,
+                    attachments: Some(vec![info.clone()]),
+                }
+                .to_document("", 0)
+                .unwrap();
+                writer.add_document(doc).unwrap();
+            }
+            writer.commit().unwrap();
+
+            let attachments =
+                ShardedIndex::open(&dirs.attachment_dir, SchemaTools::attachment_schema()).unwrap();
+            let mut writer: IndexWriter = attachments
+                .shard_or_create(7)
+                .unwrap()
+                .index()
+                .writer(50_000_000)
+                .unwrap();
+            let doc = AttachmentModel {
+                id: "att-1".into(),
+                envelope_id: "env-1".into(),
+                account_id: 7,
+                content_hash: attachment_hash.clone(),
+                tags: Some(vec!["/finance".into()]),
+                ..Default::default()
+            }
+            .into_document();
+            writer.add_document(doc).unwrap();
+            writer.commit().unwrap();
+
+            Self {
+                root,
+                dirs,
+                engine,
+                attachment_hash,
+            }
+        }
+
+        fn job(&self) -> ReindexJob<'_> {
+            ReindexJob::new(self.dirs.clone(), &self.engine, HashSet::from([1]), 10)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = self.engine.shutdown();
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn all_docs(dir: &Path, schema: tantivy::schema::Schema) -> Vec<TantivyDocument> {
+        let searcher = ShardedIndex::open(dir, schema)
+            .unwrap()
+            .searcher(None)
+            .unwrap();
+        searcher
+            .search(&AllQuery, &DocSetCollector)
+            .unwrap()
+            .into_iter()
+            .map(|address| searcher.doc(address).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn restore_detached_splices_blobs_back() {
+        let hash = compute_content_hash(b"payload");
+        let stripped = format!("a<<BICHON_DETACH_HASH:{hash}>>b<<BICHON_DETACH_HASH:nope>>");
+        let restored = restore_detached(stripped.as_bytes(), |h| {
+            Ok((h == hash).then(|| Bytes::from_static(b"payload")))
+        })
+        .unwrap();
+        assert_eq!(restored, b"apayloadb<<BICHON_DETACH_HASH:nope>>");
+
+        let err = restore_detached(stripped.as_bytes(), |_| Ok(None)).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ResourceNotFound);
+    }
+
+    #[test]
+    fn rebuild_reparses_and_keeps_archive_state() {
+        let fixture = Fixture::new();
+        let stats = fixture.job().run(|_| {}).unwrap();
+        assert_eq!((stats.total, stats.rebuilt, stats.orphaned), (2, 1, 1));
+        assert!(stats.failed.is_empty());
+
+        fixture.dirs.swap().unwrap();
+        assert!(!fixture.dirs.has_partial_rebuild());
+        assert!(!fixture.dirs.has_pending_swap());
+
+        let f = SchemaTools::email_fields();
+        let envelopes = all_docs(&fixture.dirs.envelope_dir, SchemaTools::email_schema());
+        assert_eq!(envelopes.len(), 1);
+        let doc = &envelopes[0];
+        assert_eq!(stored_str(doc, f.f_id, "id").unwrap(), "env-1");
+        assert_eq!(
+            stored_str(doc, f.f_subject, "subject").unwrap(),
+            "Quarterly report"
+        );
+        assert_eq!(stored_i64(doc, f.f_ingest_at, "ingest_at").unwrap(), 42);
+        assert_eq!(stored_u64(doc, f.f_uid, "uid").unwrap(), 12);
+        assert_eq!(stored_u64(doc, f.f_shard_id, "shard_id").unwrap(), 7);
+        assert_eq!(
+            stored_u64(doc, f.f_regular_attachment_count, "count").unwrap(),
+            1
+        );
+        let tags: Vec<Facet> = facets(doc, f.f_tags).collect();
+        assert!(tags.contains(&Facet::from("/work/project")));
+        assert!(tags.contains(&flag_to_facet("\\Seen").unwrap()));
+
+        let af = SchemaTools::attachment_fields();
+        let attachments = all_docs(
+            &fixture.dirs.attachment_dir,
+            SchemaTools::attachment_schema(),
+        );
+        assert_eq!(attachments.len(), 1);
+        let doc = &attachments[0];
+        assert_eq!(stored_str(doc, af.f_id, "id").unwrap(), "att-1");
+        assert_eq!(
+            stored_str(doc, af.f_content_hash, "hash").unwrap(),
+            fixture.attachment_hash
+        );
+        assert_eq!(
+            doc.get_first(af.f_has_text).and_then(|v| v.as_bool()),
+            Some(true)
+        );
+        assert_eq!(stored_i64(doc, af.f_ingest_at, "ingest_at").unwrap(), 42);
+        let tags: Vec<String> = facets(doc, af.f_tags).map(|f| f.to_string()).collect();
+        assert_eq!(tags, ["/finance"]);
+    }
+
+    #[test]
+    fn rerun_resumes_without_duplicates() {
+        let fixture = Fixture::new();
+        fixture.job().run(|_| {}).unwrap();
+        assert!(fixture.dirs.has_partial_rebuild());
+
+        let stats = fixture.job().run(|_| {}).unwrap();
+        assert_eq!((stats.rebuilt, stats.resumed), (0, 1));
+
+        assert_eq!(
+            all_docs(
+                &sibling(&fixture.dirs.envelope_dir, SIDE_SUFFIX),
+                SchemaTools::email_schema()
+            )
+            .len(),
+            1
+        );
+        assert_eq!(
+            all_docs(
+                &sibling(&fixture.dirs.attachment_dir, SIDE_SUFFIX),
+                SchemaTools::attachment_schema()
+            )
+            .len(),
+            1
+        );
+    }
+}
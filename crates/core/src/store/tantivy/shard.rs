@@ -0,0 +1,929 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Account-sharded tantivy indexes.
+//!
+//! Every account gets its own index under `<root>/shard-<account_id>`, so
+//! commits, merges and reader reloads only touch the accounts that changed,
+//! and deleting an account removes a directory instead of rewriting segments
+//! shared with every other account. [`ShardSearcher`] runs one query over
+//! several shards and merges the results as if they came from a single index.
+//!
+//! A root that still holds the single index of earlier versions is split into
+//! shards the first time it is opened.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use tantivy::{
+    collector::Collector,
+    indexer::LogMergePolicy,
+    query::{
+        AllQuery, Bm25StatisticsProvider, BooleanQuery, EnableScoring, Occur, Query, QueryParser,
+        TermQuery,
+    },
+    schema::{document::DocumentDeserialize, Field, IndexRecordOption, Schema, Value},
+    tokenizer::TokenizerManager,
+    DocAddress, Index, IndexReader, IndexWriter, ReloadPolicy, Searcher, SegmentReader,
+    TantivyDocument, TantivyError, Term,
+};
+use tokio::{
+    sync::{mpsc, Mutex, OwnedMutexGuard},
+    task::{self, JoinHandle},
+};
+use tracing::{info, warn};
+
+use crate::{
+    common::signal::SIGNAL_MANAGER,
+    error::{code::ErrorCode, BichonResult},
+    raise_error,
+    store::tantivy::{
+        fatal_commit, fields::F_ACCOUNT_ID, reindex::internal, tokenizers::EuroTokenizer,
+    },
+};
+
+const SHARD_PREFIX: &str = "shard-";
+const SPLIT_SUFFIX: &str = "split";
+const META_FILE: &str = "meta.json";
+/// Shard writers are opened on demand, and several can be open at once while
+/// a sync touches many accounts, so each one gets a modest budget.
+const WRITER_THREADS: usize = 2;
+const WRITER_HEAP_BYTES: usize = 32 * 1024 * 1024;
+const SPLIT_WRITER_HEAP_BYTES: usize = 64 * 1024 * 1024;
+const COMMIT_THRESHOLD: usize = 1000;
+const COMMIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The index of a single account.
+pub struct Shard {
+    id: u64,
+    index: Index,
+    reader: IndexReader,
+    writer: Arc<Mutex<Option<IndexWriter>>>,
+    /// A writer was handed out since the reader was last reloaded.
+    stale: AtomicBool,
+    /// A writer was handed out since the last idle check.
+    active: AtomicBool,
+    /// The shard was removed; no new writer may be opened on it.
+    dropped: AtomicBool,
+}
+
+impl Shard {
+    fn open(
+        id: u64,
+        dir: &Path,
+        schema: &Schema,
+        tokenizers: &TokenizerManager,
+    ) -> BichonResult<Self> {
+        let mut index = if dir.join(META_FILE).exists() {
+            Index::open_in_dir(dir)
+        } else {
+            fs::create_dir_all(dir).map_err(internal)?;
+            Index::create_in_dir(dir, schema.clone())
+        }
+        .map_err(|e| {
+            raise_error!(
+                format!("Failed to open index shard {:?}: {:#?}", dir, e),
+                ErrorCode::InternalError
+            )
+        })?;
+        index.set_tokenizers(tokenizers.clone());
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(internal)?;
+        Ok(Self {
+            id,
+            index,
+            reader,
+            writer: Arc::new(Mutex::new(None)),
+            stale: AtomicBool::new(false),
+            active: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+        })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    /// A searcher over the last commit. The reader is only reloaded after a
+    /// writer of this shard has been used.
+    pub fn searcher(&self) -> BichonResult<Searcher> {
+        if self.stale.swap(false, Ordering::AcqRel) {
+            self.reader.reload().map_err(internal)?;
+        }
+        Ok(self.reader.searcher())
+    }
+
+    /// Locks the shard's writer, opening it first if it was closed.
+    pub async fn writer(self: &Arc<Self>) -> BichonResult<ShardWriter> {
+        let mut guard = self.writer.clone().lock_owned().await;
+        if self.dropped.load(Ordering::Acquire) {
+            return Err(raise_error!(
+                format!("Index shard {} was dropped", self.id),
+                ErrorCode::ResourceNotFound
+            ));
+        }
+        if guard.is_none() {
+            *guard = Some(open_writer(&self.index)?);
+        }
+        self.active.store(true, Ordering::Release);
+        Ok(ShardWriter {
+            guard,
+            shard: self.clone(),
+        })
+    }
+
+    /// Closes the writer if nobody used it since the previous call, which
+    /// releases its indexing and merge threads.
+    fn close_if_idle(&self) {
+        let Ok(mut guard) = self.writer.try_lock() else {
+            return;
+        };
+        if guard.is_some() && !self.active.swap(false, Ordering::AcqRel) {
+            if let Some(writer) = guard.take() {
+                task::block_in_place(|| drop(writer));
+            }
+        }
+    }
+}
+
+/// Exclusive access to a shard's writer. The shard's reader is reloaded on
+/// its next search once this is dropped.
+pub struct ShardWriter {
+    guard: OwnedMutexGuard<Option<IndexWriter>>,
+    shard: Arc<Shard>,
+}
+
+impl Deref for ShardWriter {
+    type Target = IndexWriter;
+
+    fn deref(&self) -> &IndexWriter {
+        self.guard
+            .as_ref()
+            .expect("shard writer is open while locked")
+    }
+}
+
+impl DerefMut for ShardWriter {
+    fn deref_mut(&mut self) -> &mut IndexWriter {
+        self.guard
+            .as_mut()
+            .expect("shard writer is open while locked")
+    }
+}
+
+impl Drop for ShardWriter {
+    fn drop(&mut self) {
+        self.shard.stale.store(true, Ordering::Release);
+    }
+}
+
+/// A locked shard writer that is only opened when actually needed, so that
+/// holding every shard of an index does not start a writer for each of them.
+pub struct ShardLock {
+    guard: OwnedMutexGuard<Option<IndexWriter>>,
+    shard: Arc<Shard>,
+}
+
+impl ShardLock {
+    pub fn id(&self) -> u64 {
+        self.shard.id
+    }
+
+    /// The shard's writer, opened first if it was closed.
+    pub fn writer(&mut self) -> BichonResult<&mut IndexWriter> {
+        if self.shard.dropped.load(Ordering::Acquire) {
+            return Err(raise_error!(
+                format!("Index shard {} was dropped", self.shard.id),
+                ErrorCode::ResourceNotFound
+            ));
+        }
+        if self.guard.is_none() {
+            *self.guard = Some(open_writer(&self.shard.index)?);
+        }
+        self.shard.active.store(true, Ordering::Release);
+        Ok(self.guard.as_mut().expect("shard writer was just opened"))
+    }
+}
+
+impl Drop for ShardLock {
+    fn drop(&mut self) {
+        self.shard.stale.store(true, Ordering::Release);
+    }
+}
+
+fn open_writer(index: &Index) -> BichonResult<IndexWriter> {
+    let writer: IndexWriter = index
+        .writer_with_num_threads(WRITER_THREADS, WRITER_HEAP_BYTES)
+        .map_err(internal)?;
+    let mut merge_policy = LogMergePolicy::default();
+    merge_policy.set_min_num_segments(25);
+    merge_policy.set_min_layer_size(10_000);
+    merge_policy.set_max_docs_before_merge(100_000);
+    writer.set_merge_policy(Box::new(merge_policy));
+    Ok(writer)
+}
+
+/// A set of per-account indexes sharing one schema.
+pub struct ShardedIndex {
+    root: PathBuf,
+    schema: Schema,
+    account_field: Field,
+    tokenizers: TokenizerManager,
+    shards: RwLock<BTreeMap<u64, Arc<Shard>>>,
+}
+
+impl ShardedIndex {
+    /// Opens every shard under `root`, creating the directory if needed and
+    /// splitting an unsharded index found there.
+    pub fn open(root: &Path, schema: Schema) -> BichonResult<Self> {
+        fs::create_dir_all(root).map_err(|e| {
+            raise_error!(
+                format!("Failed to create index directory {:?}: {:#?}", root, e),
+                ErrorCode::InternalError
+            )
+        })?;
+        let tokenizers = TokenizerManager::default();
+        tokenizers.register("euro", EuroTokenizer::new());
+        let account_field = schema.get_field(F_ACCOUNT_ID).map_err(internal)?;
+
+        if root.join(META_FILE).exists() {
+            split_unsharded(root, account_field, &tokenizers)?;
+        }
+
+        let mut shards = BTreeMap::new();
+        for entry in fs::read_dir(root).map_err(internal)? {
+            let path = entry.map_err(internal)?.path();
+            if path.is_file() {
+                // Left behind by a split that stopped while removing the
+                // unsharded index.
+                warn!("Removing leftover index file {}", path.display());
+                fs::remove_file(&path).map_err(internal)?;
+                continue;
+            }
+            let Some(id) = parse_shard_dir(&path) else {
+                continue;
+            };
+            shards.insert(id, Arc::new(Shard::open(id, &path, &schema, &tokenizers)?));
+        }
+        info!("Opened {} index shards at {}", shards.len(), root.display());
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            schema,
+            account_field,
+            tokenizers,
+            shards: RwLock::new(shards),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn shard(&self, account_id: u64) -> Option<Arc<Shard>> {
+        self.shards.read().unwrap().get(&account_id).cloned()
+    }
+
+    pub fn shard_or_create(&self, account_id: u64) -> BichonResult<Arc<Shard>> {
+        if let Some(shard) = self.shard(account_id) {
+            return Ok(shard);
+        }
+        let mut shards = self.shards.write().unwrap();
+        if let Some(shard) = shards.get(&account_id) {
+            return Ok(shard.clone());
+        }
+        let shard = Arc::new(Shard::open(
+            account_id,
+            &self.shard_dir(account_id),
+            &self.schema,
+            &self.tokenizers,
+        )?);
+        shards.insert(account_id, shard.clone());
+        Ok(shard)
+    }
+
+    /// All shards, in account id order.
+    pub fn shards(&self) -> Vec<Arc<Shard>> {
+        self.shards.read().unwrap().values().cloned().collect()
+    }
+
+    /// A searcher over the shards of `accounts`, or over every shard.
+    pub fn searcher(&self, accounts: Option<&HashSet<u64>>) -> BichonResult<ShardSearcher> {
+        let shards: Vec<Arc<Shard>> = {
+            let shards = self.shards.read().unwrap();
+            match accounts {
+                Some(ids) => shards
+                    .iter()
+                    .filter(|(id, _)| ids.contains(id))
+                    .map(|(_, shard)| shard.clone())
+                    .collect(),
+                None => shards.values().cloned().collect(),
+            }
+        };
+        let searchers = shards
+            .iter()
+            .map(|shard| shard.searcher())
+            .collect::<BichonResult<Vec<_>>>()?;
+        Ok(ShardSearcher::new(searchers))
+    }
+
+    pub fn account_searcher(&self, account_id: u64) -> BichonResult<ShardSearcher> {
+        self.searcher(Some(&HashSet::from([account_id])))
+    }
+
+    pub fn query_parser(&self, default_fields: Vec<Field>) -> QueryParser {
+        QueryParser::new(self.schema.clone(), default_fields, self.tokenizers.clone())
+    }
+
+    /// Removes an account's shard with all its documents. Returns whether
+    /// the shard existed.
+    pub async fn drop_shard(&self, account_id: u64) -> BichonResult<bool> {
+        let Some(shard) = self.shards.write().unwrap().remove(&account_id) else {
+            return Ok(false);
+        };
+        let mut guard = shard.writer.lock().await;
+        shard.dropped.store(true, Ordering::Release);
+        if let Some(writer) = guard.take() {
+            task::block_in_place(|| drop(writer));
+        }
+        drop(guard);
+        fs::remove_dir_all(self.shard_dir(account_id)).map_err(internal)?;
+        info!(
+            "Dropped index shard {} at {}",
+            account_id,
+            self.root.display()
+        );
+        Ok(true)
+    }
+
+    /// Commits every open writer and keeps all writers locked until the
+    /// returned locks are dropped, so a search made in between sees every
+    /// document and nothing can be added meanwhile. The caller must not hold
+    /// a [`ShardWriter`] of this index.
+    pub async fn commit_all(&self) -> Vec<ShardLock> {
+        let shards = self.shards();
+        let mut locks = Vec::with_capacity(shards.len());
+        for shard in shards {
+            let mut guard = shard.writer.clone().lock_owned().await;
+            if let Some(writer) = guard.as_mut() {
+                task::block_in_place(|| fatal_commit(writer));
+                shard.stale.store(true, Ordering::Release);
+            }
+            locks.push(ShardLock { guard, shard });
+        }
+        locks
+    }
+
+    /// Spawns the task that adds queued documents to the shard of their
+    /// account. Documents are committed every [`COMMIT_THRESHOLD`] documents,
+    /// every minute and on shutdown; writers left idle for a whole interval
+    /// are closed.
+    pub fn spawn_ingest(
+        self: &Arc<Self>,
+        mut receiver: mpsc::Receiver<TantivyDocument>,
+    ) -> JoinHandle<()> {
+        let shards = self.clone();
+        task::spawn(async move {
+            let mut shutdown = SIGNAL_MANAGER.subscribe();
+            let mut commit_interval = tokio::time::interval(COMMIT_INTERVAL);
+            let mut pending: HashMap<u64, usize> = HashMap::new();
+            loop {
+                tokio::select! {
+                    maybe_msg = receiver.recv() => {
+                        match maybe_msg {
+                            Some(doc) => {
+                                let mut batch = vec![doc];
+                                while let Ok(next_doc) = receiver.try_recv() {
+                                    batch.push(next_doc);
+                                }
+                                shards.add_batch(batch, &mut pending).await;
+                                let pending_count: usize = pending.values().sum();
+                                if pending_count >= COMMIT_THRESHOLD {
+                                    tracing::info!(
+                                        "Tantivy: Reached threshold ({} docs), committing {}...",
+                                        pending_count,
+                                        shards.root.display()
+                                    );
+                                    shards.commit_pending(&mut pending).await;
+                                    commit_interval.reset();
+                                }
+                            }
+                            None => {
+                                tracing::info!("Tantivy: Receiver closed. Finalizing...");
+                                shards.commit_pending(&mut pending).await;
+                                break;
+                            }
+                        }
+                    }
+                    _ = commit_interval.tick() => {
+                        if !pending.is_empty() {
+                            tracing::debug!(
+                                "Tantivy: periodic commit of {} ({} shards pending)",
+                                shards.root.display(),
+                                pending.len()
+                            );
+                            shards.commit_pending(&mut pending).await;
+                        }
+                        for shard in shards.shards() {
+                            shard.close_if_idle();
+                        }
+                    }
+                    _ = shutdown.recv() => {
+                        tracing::info!("Tantivy: Shutdown signal received. Performing final commit...");
+                        shards.commit_pending(&mut pending).await;
+                        tracing::info!("Tantivy: Shutdown cleanup complete.");
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    async fn add_batch(&self, batch: Vec<TantivyDocument>, pending: &mut HashMap<u64, usize>) {
+        let mut by_account: BTreeMap<u64, Vec<TantivyDocument>> = BTreeMap::new();
+        for doc in batch {
+            match doc.get_first(self.account_field).and_then(|v| v.as_u64()) {
+                Some(account_id) => by_account.entry(account_id).or_default().push(doc),
+                None => tracing::error!("Tantivy: Dropping document without an account id"),
+            }
+        }
+        for (account_id, docs) in by_account {
+            let writer = match self.shard_or_create(account_id) {
+                Ok(shard) => shard.writer().await,
+                Err(e) => Err(e),
+            };
+            let writer = match writer {
+                Ok(writer) => writer,
+                Err(e) => {
+                    eprintln!("[ERROR] Failed to open index shard {account_id}: {e:?}");
+                    tracing::error!("Tantivy: Failed to open index shard {account_id}: {e:?}");
+                    continue;
+                }
+            };
+            for doc in docs {
+                match writer.add_document(doc) {
+                    Ok(_) => *pending.entry(account_id).or_default() += 1,
+                    Err(e) => {
+                        eprintln!("[ERROR] Failed to add document: {e:?}");
+                        tracing::error!("Tantivy: Failed to add document: {e:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn commit_pending(&self, pending: &mut HashMap<u64, usize>) {
+        for (account_id, count) in pending.drain() {
+            // A shard dropped since its documents were added has nothing left
+            // to commit.
+            let Some(shard) = self.shard(account_id) else {
+                continue;
+            };
+            match shard.writer().await {
+                Ok(mut writer) => {
+                    task::block_in_place(|| fatal_commit(&mut writer));
+                    tracing::debug!("Tantivy: committed {count} docs to shard {account_id}");
+                }
+                Err(e) => {
+                    tracing::error!("Tantivy: Failed to commit shard {account_id}: {e:?}")
+                }
+            }
+        }
+    }
+
+    fn shard_dir(&self, account_id: u64) -> PathBuf {
+        self.root.join(format!("{SHARD_PREFIX}{account_id}"))
+    }
+}
+
+fn parse_shard_dir(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(SHARD_PREFIX)?
+        .parse()
+        .ok()
+}
+
+/// Splits the single index that `root` held before sharding into one shard
+/// per account.
+///
+/// Each shard starts out as hard links to the old segment files, deletes the
+/// other accounts' documents and is merged down, so fields that are indexed
+/// but not stored survive. The old index is removed only once every shard is
+/// in place; a split that was interrupted before that starts over.
+fn split_unsharded(
+    root: &Path,
+    account_field: Field,
+    tokenizers: &TokenizerManager,
+) -> BichonResult<()> {
+    let account_ids = {
+        let index = Index::open_in_dir(root).map_err(internal)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(internal)?;
+        account_ids(&reader.searcher(), account_field)?
+    };
+    info!(
+        "Splitting the index at {} into {} account shards",
+        root.display(),
+        account_ids.len()
+    );
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(root).map_err(internal)? {
+        let path = entry.map_err(internal)?.path();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if path.is_dir() {
+            if name.starts_with(SHARD_PREFIX) {
+                fs::remove_dir_all(&path).map_err(internal)?;
+            }
+        } else if !name.starts_with(".tantivy-") {
+            files.push(path);
+        }
+    }
+
+    for account_id in &account_ids {
+        let staging = root.join(format!("{SHARD_PREFIX}{account_id}.{SPLIT_SUFFIX}"));
+        fs::create_dir_all(&staging).map_err(internal)?;
+        for file in &files {
+            let target = staging.join(file.file_name().unwrap_or_default());
+            if fs::hard_link(file, &target).is_err() {
+                fs::copy(file, &target).map_err(internal)?;
+            }
+        }
+
+        let mut index = Index::open_in_dir(&staging).map_err(internal)?;
+        index.set_tokenizers(tokenizers.clone());
+        let mut writer: IndexWriter = index
+            .writer_with_num_threads(1, SPLIT_WRITER_HEAP_BYTES)
+            .map_err(internal)?;
+        writer
+            .delete_query(Box::new(BooleanQuery::new(vec![
+                (Occur::Must, Box::new(AllQuery) as Box<dyn Query>),
+                (
+                    Occur::MustNot,
+                    Box::new(TermQuery::new(
+                        Term::from_field_u64(account_field, *account_id),
+                        IndexRecordOption::Basic,
+                    )),
+                ),
+            ])))
+            .map_err(internal)?;
+        writer.commit().map_err(internal)?;
+        let segments = index.searchable_segment_ids().map_err(internal)?;
+        if !segments.is_empty() {
+            writer.merge(&segments).wait().map_err(internal)?;
+        }
+        writer.garbage_collect_files().wait().map_err(internal)?;
+        writer.wait_merging_threads().map_err(internal)?;
+        drop(index);
+
+        fs::rename(&staging, root.join(format!("{SHARD_PREFIX}{account_id}"))).map_err(internal)?;
+    }
+
+    // meta.json goes first: without it the old files are just leftovers.
+    fs::remove_file(root.join(META_FILE)).map_err(internal)?;
+    for entry in fs::read_dir(root).map_err(internal)? {
+        let path = entry.map_err(internal)?.path();
+        if path.is_file() {
+            fs::remove_file(&path).map_err(internal)?;
+        }
+    }
+    info!("Split the index at {} into account shards", root.display());
+    Ok(())
+}
+
+fn account_ids(searcher: &Searcher, account_field: Field) -> BichonResult<Vec<u64>> {
+    let name = searcher.schema().get_field_name(account_field).to_string();
+    let mut ids = HashSet::new();
+    for segment in searcher.segment_readers() {
+        let column = segment.fast_fields().u64(&name).map_err(internal)?;
+        for doc_id in segment.doc_ids_alive() {
+            ids.extend(column.values_for_doc(doc_id));
+        }
+    }
+    let mut ids: Vec<u64> = ids.into_iter().collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// A point-in-time view of several shards that searches like one index.
+///
+/// The segment ordinals in the [`DocAddress`]es it returns are numbered across
+/// all its shards, so they can only be resolved by the searcher that produced
+/// them.
+pub struct ShardSearcher {
+    searchers: Vec<Searcher>,
+    /// Global ordinal of the first segment of each searcher.
+    segment_bases: Vec<u32>,
+}
+
+impl ShardSearcher {
+    pub fn new(searchers: Vec<Searcher>) -> Self {
+        let mut next = 0u32;
+        let segment_bases = searchers
+            .iter()
+            .map(|searcher| {
+                let base = next;
+                next += searcher.segment_readers().len() as u32;
+                base
+            })
+            .collect();
+        Self {
+            searchers,
+            segment_bases,
+        }
+    }
+
+    pub fn searchers(&self) -> &[Searcher] {
+        &self.searchers
+    }
+
+    pub fn num_docs(&self) -> u64 {
+        self.searchers.iter().map(Searcher::num_docs).sum()
+    }
+
+    pub fn segment_readers(&self) -> impl Iterator<Item = &SegmentReader> {
+        self.searchers
+            .iter()
+            .flat_map(|searcher| searcher.segment_readers())
+    }
+
+    /// Runs `query` on every segment of every shard and merges the segment
+    /// results with `collector`, the same way [`Searcher::search`] does for
+    /// the segments of one index. Scores use statistics of all shards.
+    pub fn search<C: Collector>(
+        &self,
+        query: &dyn Query,
+        collector: &C,
+    ) -> tantivy::Result<C::Fruit> {
+        let mut fruits = Vec::new();
+        for (searcher, base) in self.searchers.iter().zip(&self.segment_bases) {
+            let scoring = if collector.requires_scoring() {
+                EnableScoring::enabled_from_statistics_provider(self, searcher)
+            } else {
+                EnableScoring::disabled_from_searcher(searcher)
+            };
+            let weight = query.weight(scoring)?;
+            for (ordinal, segment) in searcher.segment_readers().iter().enumerate() {
+                fruits.push(collector.collect_segment(
+                    weight.as_ref(),
+                    base + ordinal as u32,
+                    segment,
+                )?);
+            }
+        }
+        collector.merge_fruits(fruits)
+    }
+
+    pub fn doc<D: DocumentDeserialize>(&self, address: DocAddress) -> tantivy::Result<D> {
+        let shard = self
+            .segment_bases
+            .partition_point(|base| *base <= address.segment_ord);
+        let searcher = shard
+            .checked_sub(1)
+            .map(|shard| (&self.searchers[shard], self.segment_bases[shard]))
+            .filter(|(searcher, base)| {
+                ((address.segment_ord - base) as usize) < searcher.segment_readers().len()
+            });
+        let Some((searcher, base)) = searcher else {
+            return Err(TantivyError::InvalidArgument(format!(
+                "No segment {} in this searcher",
+                address.segment_ord
+            )));
+        };
+        searcher.doc(DocAddress::new(address.segment_ord - base, address.doc_id))
+    }
+}
+
+impl Bm25StatisticsProvider for ShardSearcher {
+    fn total_num_tokens(&self, field: Field) -> tantivy::Result<u64> {
+        let mut total = 0;
+        for searcher in &self.searchers {
+            total += searcher.total_num_tokens(field)?;
+        }
+        Ok(total)
+    }
+
+    fn total_num_docs(&self) -> tantivy::Result<u64> {
+        Ok(self.num_docs())
+    }
+
+    fn doc_freq(&self, term: &Term) -> tantivy::Result<u64> {
+        let mut total = 0;
+        for searcher in &self.searchers {
+            total += searcher.doc_freq(term)?;
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tantivy::{
+        fields::{F_DATE, F_SIZE},
+        schema::SchemaTools,
+    };
+    use tantivy::{
+        collector::{Count, DocSetCollector, TopDocs},
+        Order,
+    };
+
+    fn temp_dir(prefix: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bichon-shard-{prefix}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn envelope(id: &str, account_id: u64, date: i64) -> TantivyDocument {
+        let f = SchemaTools::email_fields();
+        let mut doc = TantivyDocument::new();
+        doc.add_text(f.f_id, id);
+        doc.add_u64(f.f_account_id, account_id);
+        doc.add_u64(f.f_mailbox_id, account_id * 10);
+        doc.add_text(f.f_subject, format!("report {id}"));
+        doc.add_text(f.f_body, "quarterly numbers for 2024 attached");
+        doc.add_i64(f.f_date, date);
+        doc.add_u64(f.f_size, date as u64);
+        doc.add_u64(f.f_shard_id, account_id);
+        doc
+    }
+
+    fn ids(
+        searcher: &ShardSearcher,
+        addresses: impl IntoIterator<Item = DocAddress>,
+    ) -> Vec<String> {
+        let f = SchemaTools::email_fields();
+        addresses
+            .into_iter()
+            .map(|address| {
+                let doc: TantivyDocument = searcher.doc(address).unwrap();
+                doc.get_first(f.f_id).unwrap().as_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    fn write(index: &Index, docs: Vec<TantivyDocument>) {
+        let mut writer: IndexWriter = index.writer(15_000_000).unwrap();
+        for doc in docs {
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+        writer.wait_merging_threads().unwrap();
+    }
+
+    #[test]
+    fn search_merges_results_across_shards() {
+        let root = temp_dir("search");
+        let shards = ShardedIndex::open(&root, SchemaTools::email_schema()).unwrap();
+        write(
+            shards.shard_or_create(1).unwrap().index(),
+            vec![envelope("a1", 1, 10), envelope("a2", 1, 40)],
+        );
+        let second = shards.shard_or_create(2).unwrap();
+        write(second.index(), vec![envelope("b1", 2, 20)]);
+        write(second.index(), vec![envelope("b2", 2, 30)]);
+        shards.shard_or_create(3).unwrap();
+        for shard in shards.shards() {
+            shard.stale.store(true, Ordering::Release);
+        }
+
+        let searcher = shards.searcher(None).unwrap();
+        assert_eq!(searcher.num_docs(), 4);
+        let page: Vec<(Option<i64>, DocAddress)> = searcher
+            .search(
+                &AllQuery,
+                &TopDocs::with_limit(2)
+                    .and_offset(1)
+                    .order_by_fast_field(F_DATE, Order::Desc),
+            )
+            .unwrap();
+        assert_eq!(
+            ids(&searcher, page.into_iter().map(|(_, address)| address)),
+            vec!["b2", "b1"]
+        );
+
+        let query = shards
+            .query_parser(vec![SchemaTools::email_fields().f_body])
+            .parse_query("2024")
+            .unwrap();
+        assert_eq!(searcher.search(&query, &Count).unwrap(), 4);
+        let scored = searcher
+            .search(&query, &TopDocs::with_limit(10).order_by_score())
+            .unwrap();
+        assert_eq!(scored.len(), 4);
+
+        let only_second = shards.account_searcher(2).unwrap();
+        let docs = only_second.search(&AllQuery, &DocSetCollector).unwrap();
+        let mut found = ids(&only_second, docs);
+        found.sort();
+        assert_eq!(found, vec!["b1", "b2"]);
+        let largest: Vec<(Option<u64>, DocAddress)> = only_second
+            .search(
+                &AllQuery,
+                &TopDocs::with_limit(1).order_by_fast_field(F_SIZE, Order::Desc),
+            )
+            .unwrap();
+        assert_eq!(ids(&only_second, [largest[0].1]), vec!["b2"]);
+
+        assert!(searcher
+            .doc::<TantivyDocument>(DocAddress::new(99, 0))
+            .is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn unsharded_index_is_split_by_account() {
+        let root = temp_dir("split");
+        let legacy = Index::create_in_dir(&root, SchemaTools::email_schema()).unwrap();
+        legacy.tokenizers().register("euro", EuroTokenizer::new());
+        write(&legacy, vec![envelope("a1", 1, 10), envelope("b1", 2, 20)]);
+        write(&legacy, vec![envelope("a2", 1, 30), envelope("c1", 7, 40)]);
+        drop(legacy);
+
+        let shards = ShardedIndex::open(&root, SchemaTools::email_schema()).unwrap();
+        assert!(!root.join(META_FILE).exists());
+        assert_eq!(
+            shards.shards().iter().map(|s| s.id()).collect::<Vec<_>>(),
+            vec![1, 2, 7]
+        );
+        let first = shards.account_searcher(1).unwrap();
+        assert_eq!(first.num_docs(), 2);
+        assert_eq!(first.segment_readers().count(), 1);
+        // f_body is not stored; it must still be searchable after the split.
+        let query = shards
+            .query_parser(vec![SchemaTools::email_fields().f_body])
+            .parse_query("2024")
+            .unwrap();
+        assert_eq!(first.search(&query, &Count).unwrap(), 2);
+        assert_eq!(shards.searcher(None).unwrap().num_docs(), 4);
+        drop(shards);
+
+        let reopened = ShardedIndex::open(&root, SchemaTools::email_schema()).unwrap();
+        assert_eq!(reopened.shards().len(), 3);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dropped_shard_leaves_others_alone() {
+        let root = temp_dir("drop");
+        let shards = ShardedIndex::open(&root, SchemaTools::email_schema()).unwrap();
+        for account_id in [1, 2] {
+            let shard = shards.shard_or_create(account_id).unwrap();
+            let mut writer = shard.writer().await.unwrap();
+            writer
+                .add_document(envelope(&format!("e{account_id}"), account_id, 1))
+                .unwrap();
+            writer.commit().unwrap();
+        }
+        assert_eq!(shards.searcher(None).unwrap().num_docs(), 2);
+
+        let first = shards.shard(1).unwrap();
+        assert!(shards.drop_shard(1).await.unwrap());
+        assert!(!shards.drop_shard(1).await.unwrap());
+        assert!(!root.join("shard-1").exists());
+        assert!(first.writer().await.is_err());
+        assert_eq!(shards.searcher(None).unwrap().num_docs(), 1);
+        assert_eq!(shards.account_searcher(2).unwrap().num_docs(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -16,17 +16,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
 
+use crate::account::migration::AccountModel;
 use crate::database::manager::DB_MANAGER;
 use crate::database::{
     MemDbModel, delete_impl, filter_impl, find_impl, insert_impl, list_all_impl, update_impl, with_transaction
 };
+use crate::common::auth::ClientContext;
 use crate::error::code::ErrorCode;
 use crate::raise_error;
 use crate::settings::cli::SETTINGS;
 use crate::token::view::AccessTokenResp;
 use crate::users::UserModel;
+use crate::utils::encrypt::hash_token;
 use crate::{
     error::BichonResult, generate_token, token::payload::AccessTokenCreateRequest, utc_now,
 };
@@ -48,8 +52,13 @@ pub enum TokenType {
 pub struct AccessTokenModel {
     /// The ID of the user who owns this token
     pub user_id: u64,
-    /// The unique token string used for authentication
+    /// Keyed hash of the token secret. It identifies the token; the secret
+    /// itself is only shown once, when the token is issued.
     pub token: String,
+    /// The first characters of the secret, so users can tell tokens apart.
+    /// Empty for records written before tokens were hashed.
+    #[serde(default)]
+    pub prefix: String,
     /// An optional name of the token.
     pub name: Option<String>,
     /// Token type: WebUI or API
@@ -63,6 +72,42 @@ pub struct AccessTokenModel {
     pub expire_at: Option<i64>,
     /// The timestamp (in milliseconds since epoch) when the token was last used.
     pub last_access_at: i64,
+    /// Optional subset of permissions the token may exercise.
+    /// None means every permission granted to the owning user.
+    #[serde(default)]
+    pub permissions: Option<BTreeSet<String>>,
+    /// Optional subset of account IDs the token may access.
+    /// None means every account the owning user can access.
+    #[serde(default)]
+    pub account_ids: Option<BTreeSet<u64>>,
+}
+
+/// Limits a token places on its owner's rights. Authorization always uses the
+/// intersection of the scope and the owner's roles, so a scope can only take
+/// rights away.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenScope {
+    pub permissions: Option<BTreeSet<String>>,
+    pub account_ids: Option<BTreeSet<u64>>,
+}
+
+impl TokenScope {
+    pub fn is_restricted(&self) -> bool {
+        self.permissions.is_some() || self.account_ids.is_some()
+    }
+
+    /// Whether everything `other` allows is also allowed by `self`.
+    pub fn covers(&self, other: &TokenScope) -> bool {
+        fn subset<T: Ord>(outer: &Option<BTreeSet<T>>, inner: &Option<BTreeSet<T>>) -> bool {
+            match (outer, inner) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(outer), Some(inner)) => inner.is_subset(outer),
+            }
+        }
+        subset(&self.permissions, &other.permissions)
+            && subset(&self.account_ids, &other.account_ids)
+    }
 }
 
 impl MemDbModel for AccessTokenModel {
@@ -74,15 +119,24 @@ impl MemDbModel for AccessTokenModel {
     }
 }
 
+/// Number of secret characters kept in clear as the token prefix.
+const TOKEN_PREFIX_LEN: usize = 8;
+
+fn token_prefix(secret: &str) -> String {
+    secret.chars().take(TOKEN_PREFIX_LEN).collect()
+}
+
 impl AccessTokenModel {
     pub fn new_api_token(
-        token: String,
+        secret: &str,
         user_id: u64,
         name: Option<String>,
         expire_at: Option<i64>,
+        scope: TokenScope,
     ) -> Self {
         Self {
-            token,
+            token: hash_token(secret),
+            prefix: token_prefix(secret),
             created_at: utc_now!(),
             updated_at: utc_now!(),
             last_access_at: Default::default(),
@@ -90,27 +144,39 @@ impl AccessTokenModel {
             user_id,
             token_type: TokenType::Api,
             expire_at,
+            permissions: scope.permissions,
+            account_ids: scope.account_ids,
         }
     }
 
-    pub fn new_webui_token(user_id: u64) -> AccessTokenModel {
+    pub fn new_webui_token(secret: &str, user_id: u64, name: Option<String>) -> AccessTokenModel {
         let now = utc_now!();
         AccessTokenModel {
-            token: generate_token!(128),
+            token: hash_token(secret),
+            prefix: token_prefix(secret),
             created_at: now,
             updated_at: now,
             last_access_at: Default::default(),
-            name: None,
+            name,
             user_id,
             token_type: TokenType::WebUI,
             expire_at: None,
+            permissions: None,
+            account_ids: None,
+        }
+    }
+
+    pub fn scope(&self) -> TokenScope {
+        TokenScope {
+            permissions: self.permissions.clone(),
+            account_ids: self.account_ids.clone(),
         }
     }
 
     pub fn reset_webui_token(user_id: u64) -> BichonResult<String> {
         let old_token = Self::get_user_webui_token(user_id)?;
-        let new_token = Self::new_webui_token(user_id);
-        let new_token_str = new_token.token.clone();
+        let new_token_str = generate_token!(128);
+        let new_token = Self::new_webui_token(&new_token_str, user_id, None);
 
         match old_token {
             Some(old) => {
@@ -146,7 +212,25 @@ impl AccessTokenModel {
     }
 
     pub fn resolve_user_from_token(token: &str) -> BichonResult<UserModel> {
-        let token_str = token.to_string();
+        Self::resolve(token).map(|(_, user)| user)
+    }
+
+    /// Authenticate a request and build its context, carrying the token's
+    /// scope so permission checks can be narrowed to it.
+    pub fn resolve_context_from_token(
+        token: &str,
+        ip_addr: Option<IpAddr>,
+    ) -> BichonResult<ClientContext> {
+        let (token_model, user) = Self::resolve(token)?;
+        Ok(ClientContext {
+            ip_addr,
+            user,
+            scope: token_model.scope(),
+        })
+    }
+
+    fn resolve(token: &str) -> BichonResult<(AccessTokenModel, UserModel)> {
+        let token_str = hash_token(token);
         let token_model = find_impl::<AccessTokenModel>(DB_MANAGER.db(), &token_str)?
             .ok_or_else(|| {
                 raise_error!(
@@ -194,7 +278,7 @@ impl AccessTokenModel {
         let user = UserModel::find(token_model.user_id)
             ?
             .ok_or_else(|| raise_error!("The user associated with this access token does not exist or may have been deleted.".into(), ErrorCode::ResourceNotFound))?;
-        Ok(user)
+        Ok((token_model, user))
     }
 
     pub fn create_api_token(
@@ -206,9 +290,20 @@ impl AccessTokenModel {
         let expire_at = request
             .expire_in
             .map(|hours| utc_now!() + (hours as i64) * 60 * 60 * 1000);
+        let scope = request.scope();
+        if let Some(account_ids) = &scope.account_ids {
+            for account_id in account_ids {
+                if AccountModel::find(*account_id)?.is_none() {
+                    return Err(raise_error!(
+                        format!("Account with ID {} does not exist.", account_id),
+                        ErrorCode::ResourceNotFound
+                    ));
+                }
+            }
+        }
         let token = generate_token!(128);
         let access_token =
-            AccessTokenModel::new_api_token(token.clone(), user_id, request.name, expire_at);
+            AccessTokenModel::new_api_token(&token, user_id, request.name, expire_at, scope);
         insert_impl(DB_MANAGER.db(), access_token)?;
         Ok(token)
     }
@@ -217,15 +312,46 @@ impl AccessTokenModel {
         delete_impl::<AccessTokenModel>(DB_MANAGER.db(), token)
     }
 
+    /// Look up a token by its ID (the stored hash) or by its secret.
     pub fn get_token(token: &str) -> BichonResult<AccessTokenModel> {
-        find_impl::<AccessTokenModel>(DB_MANAGER.db(), token)?.ok_or_else(|| {
-            raise_error!(
-                format!("Access token '{}' not found", token),
-                ErrorCode::ResourceNotFound
-            )
+        if let Some(found) = find_impl::<AccessTokenModel>(DB_MANAGER.db(), token)? {
+            return Ok(found);
+        }
+        find_impl::<AccessTokenModel>(DB_MANAGER.db(), &hash_token(token))?.ok_or_else(|| {
+            raise_error!("Access token not found".into(), ErrorCode::ResourceNotFound)
         })
     }
 
+    /// Replace tokens still stored under their plaintext secret with hashed
+    /// records. Existing secrets keep working; a snapshot is taken afterwards
+    /// so the plaintext keys are also dropped from the snapshot and WAL.
+    pub fn hash_legacy_tokens() -> BichonResult<usize> {
+        let legacy: Vec<AccessTokenModel> =
+            filter_impl(DB_MANAGER.db(), |t: &AccessTokenModel| t.prefix.is_empty())?;
+        if legacy.is_empty() {
+            return Ok(0);
+        }
+        let count = legacy.len();
+        with_transaction(DB_MANAGER.db(), move |txn| {
+            let mut txn = txn;
+            for old in legacy {
+                let mut hashed = old.clone();
+                hashed.token = hash_token(&old.token);
+                hashed.prefix = token_prefix(&old.token);
+                txn = txn
+                    .delete(AccessTokenModel::collection(), old.token)
+                    .insert(AccessTokenModel::collection(), hashed.key(), &hashed)
+                    .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+            }
+            Ok(txn)
+        })?;
+        DB_MANAGER
+            .db()
+            .snapshot()
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+        Ok(count)
+    }
+
     pub fn list_all_api_tokens() -> BichonResult<Vec<AccessTokenResp>> {
         let users = UserModel::list_all()?;
         let all = list_all_impl::<AccessTokenModel>(DB_MANAGER.db())?;
@@ -247,11 +373,14 @@ impl AccessTokenModel {
                     user_id: token.user_id,
                     name: token.name,
                     token: token.token,
+                    prefix: token.prefix,
                     token_type: token.token_type,
                     created_at: token.created_at,
                     updated_at: token.updated_at,
                     expire_at: token.expire_at,
                     last_access_at: token.last_access_at,
+                    permissions: token.permissions,
+                    account_ids: token.account_ids,
                 }
             })
             .collect();
@@ -259,3 +388,58 @@ impl AccessTokenModel {
         Ok(resp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::permissions::Permission;
+
+    fn scope(permissions: Option<&[&str]>, account_ids: Option<&[u64]>) -> TokenScope {
+        TokenScope {
+            permissions: permissions.map(|p| p.iter().map(|s| s.to_string()).collect()),
+            account_ids: account_ids.map(|a| a.iter().copied().collect()),
+        }
+    }
+
+    #[test]
+    fn unrestricted_scope_covers_everything() {
+        let full = TokenScope::default();
+        assert!(!full.is_restricted());
+        assert!(full.covers(&scope(Some(&[Permission::DATA_READ]), Some(&[1]))));
+        assert!(full.covers(&TokenScope::default()));
+    }
+
+    #[test]
+    fn restricted_scope_only_covers_subsets() {
+        let reader = scope(Some(&[Permission::DATA_READ]), Some(&[1, 2]));
+        assert!(reader.is_restricted());
+        assert!(reader.covers(&scope(Some(&[Permission::DATA_READ]), Some(&[2]))));
+        assert!(!reader.covers(&scope(Some(&[Permission::DATA_DELETE]), Some(&[2]))));
+        assert!(!reader.covers(&scope(Some(&[Permission::DATA_READ]), Some(&[3]))));
+        assert!(!reader.covers(&scope(Some(&[Permission::DATA_READ]), None)));
+        assert!(!reader.covers(&TokenScope::default()));
+    }
+
+    #[test]
+    fn create_request_rejects_invalid_scopes() {
+        let mut request = AccessTokenCreateRequest {
+            permissions: Some(BTreeSet::new()),
+            ..Default::default()
+        };
+        assert!(request.validate().is_err());
+
+        request.permissions = Some(["data:everything".to_string()].into());
+        assert!(request.validate().is_err());
+
+        request.permissions = Some([Permission::DATA_SMTP_INGEST.to_string()].into());
+        request.account_ids = Some(BTreeSet::new());
+        assert!(request.validate().is_err());
+
+        request.account_ids = Some([7].into());
+        assert!(request.validate().is_ok());
+        assert_eq!(
+            request.scope(),
+            scope(Some(&[Permission::DATA_SMTP_INGEST]), Some(&[7]))
+        );
+    }
+}
@@ -16,9 +16,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
+
 use crate::{
     error::{code::ErrorCode, BichonResult},
     raise_error,
+    token::TokenScope,
+    users::permissions::VALID_PERMISSION_SET,
 };
 //use poem_openapi::Object;
 use serde::{Deserialize, Serialize};
@@ -36,6 +40,13 @@ pub struct AccessTokenCreateRequest {
     /// If not specified, the token will be created for the current authenticated user.
     /// Accessing this for another user typically requires `USER_MANAGE` permissions.
     pub user_id: Option<u64>,
+    /// Optional subset of the owner's permissions the token may use,
+    /// e.g. only `data:read` for a reporting script.
+    /// None means the token carries all of the owner's permissions.
+    pub permissions: Option<BTreeSet<String>>,
+    /// Optional subset of account IDs the token may access.
+    /// None means every account the owner can access.
+    pub account_ids: Option<BTreeSet<u64>>,
 }
 
 impl AccessTokenCreateRequest {
@@ -48,6 +59,40 @@ impl AccessTokenCreateRequest {
                 ));
             }
         }
+        if let Some(permissions) = &self.permissions {
+            if permissions.is_empty() {
+                return Err(raise_error!(
+                    "permissions must list at least one permission when provided.".into(),
+                    ErrorCode::InvalidParameter
+                ));
+            }
+            for permission in permissions {
+                if !VALID_PERMISSION_SET.contains(permission.as_str()) {
+                    return Err(raise_error!(
+                        format!(
+                            "Invalid permission '{}' specified in the request.",
+                            permission
+                        ),
+                        ErrorCode::InvalidParameter
+                    ));
+                }
+            }
+        }
+        if let Some(account_ids) = &self.account_ids {
+            if account_ids.is_empty() {
+                return Err(raise_error!(
+                    "account_ids must list at least one account when provided.".into(),
+                    ErrorCode::InvalidParameter
+                ));
+            }
+        }
         Ok(())
     }
+
+    pub fn scope(&self) -> TokenScope {
+        TokenScope {
+            permissions: self.permissions.clone(),
+            account_ids: self.account_ids.clone(),
+        }
+    }
 }
@@ -16,6 +16,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
+
 use crate::token::TokenType;
 use serde::{Deserialize, Serialize};
 
@@ -23,7 +25,10 @@ use serde::{Deserialize, Serialize};
 #[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
 pub struct AccessTokenResp {
     pub user_id: u64,
+    /// Token ID (keyed hash of the secret).
     pub token: String,
+    /// The first characters of the secret, for identification.
+    pub prefix: String,
     /// An optional name of the token.
     pub name: Option<String>,
     /// Token type: WebUI or API
@@ -37,6 +42,10 @@ pub struct AccessTokenResp {
     pub expire_at: Option<i64>,
     /// The timestamp (in milliseconds since epoch) when the token was last used.
     pub last_access_at: i64,
+    /// Permissions the token is limited to. None means unrestricted.
+    pub permissions: Option<BTreeSet<String>>,
+    /// Account IDs the token is limited to. None means unrestricted.
+    pub account_ids: Option<BTreeSet<u64>>,
 
     pub user_name: String,
     pub user_email: String,
@@ -19,14 +19,24 @@
 use crate::{
     context::Initialize,
     error::BichonResult,
+    token::AccessTokenModel,
     users::{role::UserRole, UserModel},
 };
+use tracing::info;
 
 pub struct UserManager;
 
 impl Initialize for UserManager {
     async fn initialize() -> BichonResult<()> {
         UserRole::ensure_default_roles_exists()?;
-        UserModel::ensure_default_admin_exists()
+        UserModel::ensure_default_admin_exists()?;
+        let hashed = AccessTokenModel::hash_legacy_tokens()?;
+        if hashed > 0 {
+            info!(
+                "Migrated {} plaintext access tokens to hashed storage",
+                hashed
+            );
+        }
+        Ok(())
     }
 }
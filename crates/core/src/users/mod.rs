@@ -24,12 +24,13 @@ use crate::{
     decrypt, encrypt,
     error::{code::ErrorCode, BichonResult},
     generate_token, id, raise_error,
-    token::{AccessTokenModel, TokenType},
+    token::AccessTokenModel,
     users::{
         acl::AccessControl,
         payload::{UserCreateRequest, UserUpdateRequest},
         permissions::Permission,
         role::{UserRole, DEFAULT_ADMIN_ROLE_ID},
+        totp::{TotpEnrollment, UserTotp},
         view::UserView,
     },
     utc_now,
@@ -44,6 +45,7 @@ pub mod minimal;
 pub mod payload;
 pub mod permissions;
 pub mod role;
+pub mod totp;
 pub mod view;
 
 pub type UserModel = BichonUserV2;
@@ -55,6 +57,21 @@ pub struct LoginResult {
     pub access_token: Option<String>,
     pub theme: Option<String>,
     pub language: Option<String>,
+    /// The password was correct but a two-factor code is needed.
+    pub two_factor_required: bool,
+    /// Set when a role requires 2FA and the user has not enrolled yet: the
+    /// client shows it and logs in again with a code from the new secret.
+    pub two_factor_enrollment: Option<TotpEnrollment>,
+    /// Recovery codes issued by an enrollment completed during this login.
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+enum TwoFactorOutcome {
+    /// Continue the login, handing out recovery codes if an enrollment was
+    /// just completed.
+    Passed(Option<Vec<String>>),
+    /// Stop and return this result (code prompt, enrollment or failure).
+    Challenge(LoginResult),
 }
 
 pub const DEFAULT_ADMIN_USER_ID: u64 = 100000000000000;
@@ -238,16 +255,11 @@ impl BichonUserV2 {
             };
 
             // 3. Generate and insert an initial access token for the first-time setup
-            let access_token = AccessTokenModel {
-                token: generate_token!(128),
-                created_at: now,
-                updated_at: now,
-                last_access_at: Default::default(),
-                name: Some("Initial Setup Token".into()),
-                user_id: DEFAULT_ADMIN_USER_ID,
-                token_type: TokenType::WebUI,
-                expire_at: None, // Admin setup token usually persistent until changed
-            };
+            let access_token = AccessTokenModel::new_webui_token(
+                &generate_token!(128),
+                DEFAULT_ADMIN_USER_ID,
+                Some("Initial Setup Token".into()),
+            );
 
             with_transaction(DB_MANAGER.db(), move |txn| {
                 let txn = txn
@@ -262,30 +274,19 @@ impl BichonUserV2 {
         Ok(())
     }
 
-    pub fn authenticate_user(username: String, password: String) -> BichonResult<LoginResult> {
-        // Find by username
-        let username_for_first = username.clone();
-        let users = filter_impl::<UserModel, _>(DB_MANAGER.db(), move |u| {
-            u.username == username_for_first
-        })?;
-        let user = match users.into_iter().next() {
+    pub fn authenticate_user(
+        username: String,
+        password: String,
+        totp_code: Option<String>,
+    ) -> BichonResult<LoginResult> {
+        let user = match Self::find_by_login(&username)? {
             Some(u) => u,
             None => {
-                // Fallback: find by email
-                let users =
-                    filter_impl::<UserModel, _>(DB_MANAGER.db(), move |u| u.email == username)?;
-                match users.into_iter().next() {
-                    Some(u) => u,
-                    None => {
-                        return Ok(LoginResult {
-                            success: false,
-                            error_message: Some("User or email not found.".to_string()),
-                            access_token: None,
-                            theme: None,
-                            language: None,
-                        });
-                    }
-                }
+                return Ok(LoginResult {
+                    success: false,
+                    error_message: Some("User or email not found.".to_string()),
+                    ..Default::default()
+                });
             }
         };
 
@@ -293,6 +294,10 @@ impl BichonUserV2 {
             Some(encrypted_password) => {
                 let decrypted = decrypt!(encrypted_password)?;
                 if password == decrypted {
+                    let recovery_codes = match Self::check_two_factor(&user, totp_code)? {
+                        TwoFactorOutcome::Passed(recovery_codes) => recovery_codes,
+                        TwoFactorOutcome::Challenge(challenge) => return Ok(challenge),
+                    };
                     let new_token = AccessTokenModel::reset_webui_token(user.id)?;
                     Ok(LoginResult {
                         success: true,
@@ -300,6 +305,8 @@ impl BichonUserV2 {
                         access_token: Some(new_token),
                         theme: user.theme,
                         language: user.language,
+                        recovery_codes,
+                        ..Default::default()
                     })
                 } else {
                     warn!(
@@ -309,9 +316,7 @@ impl BichonUserV2 {
                     Ok(LoginResult {
                         success: false,
                         error_message: Some("Incorrect password.".to_string()),
-                        access_token: None,
-                        theme: None,
-                        language: None,
+                        ..Default::default()
                     })
                 }
             }
@@ -328,18 +333,88 @@ impl BichonUserV2 {
                             user.username
                         )
                     ),
-                    access_token: None,
-                    theme: None,
-                    language: None,
+                    ..Default::default()
                 })
             }
         }
     }
 
+    /// Second step of a password login.
+    fn check_two_factor(
+        user: &UserModel,
+        totp_code: Option<String>,
+    ) -> BichonResult<TwoFactorOutcome> {
+        let code = totp_code
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty());
+        let failed = |message: &str| LoginResult {
+            success: false,
+            error_message: Some(message.to_string()),
+            two_factor_required: true,
+            ..Default::default()
+        };
+
+        let pending = match UserTotp::find(user.id)? {
+            Some(totp) if totp.enabled => {
+                let Some(code) = code else {
+                    return Ok(TwoFactorOutcome::Challenge(failed(
+                        "Two-factor code required.",
+                    )));
+                };
+                if !UserTotp::verify(user.id, code)? {
+                    warn!(
+                        "Login failed: Invalid two-factor code for user '{}'.",
+                        user.username
+                    );
+                    return Ok(TwoFactorOutcome::Challenge(failed(
+                        "Invalid two-factor code.",
+                    )));
+                }
+                return Ok(TwoFactorOutcome::Passed(None));
+            }
+            pending => pending.is_some(),
+        };
+
+        if !totp::is_required_for(user)? {
+            return Ok(TwoFactorOutcome::Passed(None));
+        }
+        // Enforced by a role but not set up yet: enroll as part of the login.
+        if let (true, Some(code)) = (pending, code) {
+            if let Some(recovery_codes) = UserTotp::confirm_enrollment(user.id, code)? {
+                return Ok(TwoFactorOutcome::Passed(Some(recovery_codes)));
+            }
+            return Ok(TwoFactorOutcome::Challenge(failed(
+                "Invalid two-factor code.",
+            )));
+        }
+        Ok(TwoFactorOutcome::Challenge(LoginResult {
+            success: false,
+            error_message: Some(
+                "Two-factor authentication is required for your account. Add the secret to an authenticator app and log in again with a code.".to_string(),
+            ),
+            two_factor_required: true,
+            two_factor_enrollment: Some(UserTotp::begin_enrollment(user)?),
+            ..Default::default()
+        }))
+    }
+
     pub fn find(user_id: u64) -> BichonResult<Option<UserModel>> {
         find_impl::<UserModel>(DB_MANAGER.db(), &user_id.to_string())
     }
 
+    /// The user a login name refers to: a username, or failing that an email.
+    pub fn find_by_login(login: &str) -> BichonResult<Option<UserModel>> {
+        let name = login.to_string();
+        let users = filter_impl::<UserModel, _>(DB_MANAGER.db(), move |u| u.username == name)?;
+        if let Some(user) = users.into_iter().next() {
+            return Ok(Some(user));
+        }
+        let email = login.to_string();
+        let users = filter_impl::<UserModel, _>(DB_MANAGER.db(), move |u| u.email == email)?;
+        Ok(users.into_iter().next())
+    }
+
     pub fn check_username_conflict(username: &str) -> BichonResult<()> {
         let username_clone = username.to_string();
         let users =
@@ -398,16 +473,11 @@ impl BichonUserV2 {
         let user_clone = user.clone();
 
         // 4. Atomic transaction for User and Initial Token
-        let access_token = AccessTokenModel {
-            token: generate_token!(128),
-            created_at: now,
-            updated_at: now,
-            last_access_at: Default::default(),
-            name: Some("Default WebUI Token".into()),
-            user_id: user.id,
-            token_type: TokenType::WebUI,
-            expire_at: None,
-        };
+        let access_token = AccessTokenModel::new_webui_token(
+            &generate_token!(128),
+            user.id,
+            Some("Default WebUI Token".into()),
+        );
 
         with_transaction(DB_MANAGER.db(), move |txn| {
             let txn = txn
@@ -431,6 +501,7 @@ impl BichonUserV2 {
         }
 
         delete_impl::<UserModel>(DB_MANAGER.db(), &id.to_string())?;
+        UserTotp::reset(id)?;
 
         // Find and delete tokens belonging to this user
         let uid = id;
@@ -92,6 +92,8 @@ pub struct RoleCreateRequest {
     pub role_type: RoleType,
     pub description: Option<String>,
     pub permissions: BTreeSet<String>,
+    /// Require two-factor authentication for users holding this role.
+    pub require_two_factor: Option<bool>,
 }
 
 impl RoleCreateRequest {
@@ -145,14 +147,19 @@ pub struct RoleUpdateRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub permissions: Option<BTreeSet<String>>,
+    pub require_two_factor: Option<bool>,
 }
 
 impl RoleUpdateRequest {
     pub fn validate(&self) -> BichonResult<()> {
         // 1. Ensure at least one field is provided for the update
-        if self.name.is_none() && self.description.is_none() && self.permissions.is_none() {
+        if self.name.is_none()
+            && self.description.is_none()
+            && self.permissions.is_none()
+            && self.require_two_factor.is_none()
+        {
             return Err(raise_error!(
-                "Update request must contain at least one field to modify (name, description, or permissions).".into(),
+                "Update request must contain at least one field to modify (name, description, permissions, or require_two_factor).".into(),
                 ErrorCode::InvalidParameter
             ));
         }
@@ -476,3 +483,10 @@ impl UserUpdateRequest {
         Ok(())
     }
 }
+
+/// A TOTP code or a recovery code confirming a two-factor operation.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
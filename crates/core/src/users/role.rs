@@ -190,6 +190,10 @@ pub struct UserRole {
     pub created_at: i64,
     pub role_type: RoleType,
     pub updated_at: i64,
+    /// Users holding this role must use two-factor authentication for
+    /// password logins.
+    #[serde(default)]
+    pub require_two_factor: bool,
 }
 
 impl MemDbModel for UserRole {
@@ -248,6 +252,7 @@ impl UserRole {
                         updated_at: now,
                         is_builtin: true,
                         role_type,
+                        require_two_factor: false,
                     };
                     txn = txn
                         .insert(UserRole::collection(), role_item.key(), &role_item)
@@ -280,6 +285,7 @@ impl UserRole {
             updated_at: now,
             is_builtin: false,
             role_type: request.role_type,
+            require_two_factor: request.require_two_factor.unwrap_or(false),
         };
         insert_impl(DB_MANAGER.db(), new_role.clone())?;
         Ok(new_role)
@@ -318,6 +324,9 @@ impl UserRole {
                 if let Some(permissions) = request.permissions {
                     updated.permissions = permissions;
                 }
+                if let Some(require_two_factor) = request.require_two_factor {
+                    updated.require_two_factor = require_two_factor;
+                }
                 updated.updated_at = utc_now!();
                 Ok(updated)
             },
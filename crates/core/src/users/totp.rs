@@ -0,0 +1,460 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RFC 6238 TOTP second factor for password logins.
+
+use std::collections::BTreeSet;
+
+use qrcode::render::svg;
+use qrcode::QrCode;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{delete_impl, find_impl, manager::DB_MANAGER, upsert_impl, MemDbModel},
+    decrypt, encrypt,
+    error::{code::ErrorCode, BichonError, BichonResult},
+    raise_error,
+    users::{role::UserRole, UserModel},
+    utc_now,
+    utils::encrypt::hash_token,
+};
+
+const TOTP_ISSUER: &str = "Bichon";
+const TOTP_PERIOD_SECS: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+/// Accepted clock drift, in periods, on either side of the current one.
+const TOTP_SKEW: u64 = 1;
+const SECRET_LEN: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UserTotp {
+    pub user_id: u64,
+    /// Shared secret (base32), encrypted with the server key.
+    pub secret: String,
+    /// False while the enrollment waits for its first valid code.
+    pub enabled: bool,
+    /// Keyed hashes of the recovery codes that have not been used yet.
+    pub recovery_codes: BTreeSet<String>,
+    /// Time step of the last accepted code. Codes from this step or an
+    /// earlier one are rejected, so a code cannot be replayed.
+    pub last_step: u64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl MemDbModel for UserTotp {
+    fn collection() -> &'static str {
+        "user_totp"
+    }
+    fn key(&self) -> String {
+        self.user_id.to_string()
+    }
+}
+
+/// Provisioning data for an authenticator app.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct TotpEnrollment {
+    /// Base32 secret, for manual entry.
+    pub secret: String,
+    /// `otpauth://` URI, for authenticator apps on the same device.
+    pub otpauth_uri: String,
+    /// The `otpauth://` URI as an SVG QR code.
+    pub qr_svg: String,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    /// Whether one of the user's roles requires two-factor authentication.
+    pub required: bool,
+    pub recovery_codes_remaining: u32,
+}
+
+impl UserTotp {
+    pub fn find(user_id: u64) -> BichonResult<Option<UserTotp>> {
+        find_impl::<UserTotp>(DB_MANAGER.db(), &user_id.to_string())
+    }
+
+    pub fn status(user: &UserModel) -> BichonResult<TwoFactorStatus> {
+        let totp = Self::find(user.id)?.filter(|t| t.enabled);
+        Ok(TwoFactorStatus {
+            enabled: totp.is_some(),
+            required: is_required_for(user)?,
+            recovery_codes_remaining: totp.map_or(0, |t| t.recovery_codes.len() as u32),
+        })
+    }
+
+    /// Generate a new secret and store it as a pending enrollment, replacing
+    /// any previous pending one.
+    pub fn begin_enrollment(user: &UserModel) -> BichonResult<TotpEnrollment> {
+        if Self::find(user.id)?.is_some_and(|t| t.enabled) {
+            return Err(raise_error!(
+                "Two-factor authentication is already enabled.".into(),
+                ErrorCode::InvalidParameter
+            ));
+        }
+        let secret = base32_encode(&random_bytes(SECRET_LEN)?);
+        let now = utc_now!();
+        upsert_impl(
+            DB_MANAGER.db(),
+            UserTotp {
+                user_id: user.id,
+                secret: encrypt!(&secret)?,
+                enabled: false,
+                recovery_codes: BTreeSet::new(),
+                last_step: 0,
+                created_at: now,
+                updated_at: now,
+            },
+        )?;
+        let otpauth_uri = provisioning_uri(&secret, &user.username);
+        Ok(TotpEnrollment {
+            qr_svg: qr_svg(&otpauth_uri)?,
+            otpauth_uri,
+            secret,
+        })
+    }
+
+    /// Activate a pending enrollment. Returns the recovery codes in clear
+    /// (they are only stored hashed), or `None` if `code` is wrong.
+    pub fn confirm_enrollment(user_id: u64, code: &str) -> BichonResult<Option<Vec<String>>> {
+        let mut totp = Self::find(user_id)?.filter(|t| !t.enabled).ok_or_else(|| {
+            raise_error!(
+                "No pending two-factor enrollment. Start the enrollment first.".into(),
+                ErrorCode::InvalidParameter
+            )
+        })?;
+        let secret = totp.decoded_secret()?;
+        let Some(step) = verify_code(&secret, code, unix_now(), totp.last_step) else {
+            return Ok(None);
+        };
+        let codes = new_recovery_codes()?;
+        totp.enabled = true;
+        totp.last_step = step;
+        totp.recovery_codes = codes.iter().map(|c| hash_recovery_code(c)).collect();
+        totp.updated_at = utc_now!();
+        upsert_impl(DB_MANAGER.db(), totp)?;
+        Ok(Some(codes))
+    }
+
+    /// Check a TOTP code or an unused recovery code for a user with 2FA
+    /// enabled. A matching recovery code is consumed.
+    pub fn verify(user_id: u64, code: &str) -> BichonResult<bool> {
+        let Some(mut totp) = Self::find(user_id)?.filter(|t| t.enabled) else {
+            return Ok(false);
+        };
+        let secret = totp.decoded_secret()?;
+        if let Some(step) = verify_code(&secret, code, unix_now(), totp.last_step) {
+            totp.last_step = step;
+        } else if !totp.recovery_codes.remove(&hash_recovery_code(code)) {
+            return Ok(false);
+        }
+        totp.updated_at = utc_now!();
+        upsert_impl(DB_MANAGER.db(), totp)?;
+        Ok(true)
+    }
+
+    /// Replace all recovery codes after verifying `code`.
+    pub fn regenerate_recovery_codes(user_id: u64, code: &str) -> BichonResult<Vec<String>> {
+        Self::require_valid_code(user_id, code)?;
+        let mut totp = Self::find(user_id)?.ok_or_else(not_enabled)?;
+        let codes = new_recovery_codes()?;
+        totp.recovery_codes = codes.iter().map(|c| hash_recovery_code(c)).collect();
+        totp.updated_at = utc_now!();
+        upsert_impl(DB_MANAGER.db(), totp)?;
+        Ok(codes)
+    }
+
+    /// Turn 2FA off for `user` after verifying `code`. Not allowed while one
+    /// of the user's roles requires it.
+    pub fn disable(user: &UserModel, code: &str) -> BichonResult<()> {
+        if is_required_for(user)? {
+            return Err(raise_error!(
+                "Two-factor authentication is required by your role and cannot be disabled.".into(),
+                ErrorCode::Forbidden
+            ));
+        }
+        Self::require_valid_code(user.id, code)?;
+        Self::reset(user.id)
+    }
+
+    /// Remove any 2FA enrollment without verification (administrative reset).
+    pub fn reset(user_id: u64) -> BichonResult<()> {
+        if Self::find(user_id)?.is_some() {
+            delete_impl::<UserTotp>(DB_MANAGER.db(), &user_id.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn require_valid_code(user_id: u64, code: &str) -> BichonResult<()> {
+        if Self::find(user_id)?.is_none_or(|t| !t.enabled) {
+            return Err(not_enabled());
+        }
+        if !Self::verify(user_id, code)? {
+            return Err(raise_error!(
+                "Invalid two-factor code.".into(),
+                ErrorCode::PermissionDenied
+            ));
+        }
+        Ok(())
+    }
+
+    fn decoded_secret(&self) -> BichonResult<Vec<u8>> {
+        let secret = decrypt!(&self.secret)?;
+        base32_decode(&secret).ok_or_else(|| {
+            raise_error!(
+                "Stored two-factor secret is corrupted.".into(),
+                ErrorCode::InternalError
+            )
+        })
+    }
+}
+
+/// Whether any of the user's global or account roles requires 2FA.
+pub fn is_required_for(user: &UserModel) -> BichonResult<bool> {
+    let role_ids: BTreeSet<u64> = user
+        .global_roles
+        .iter()
+        .chain(user.account_access_map.values())
+        .copied()
+        .collect();
+    for role_id in role_ids {
+        if UserRole::find(role_id)?.is_some_and(|r| r.require_two_factor) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn not_enabled() -> BichonError {
+    raise_error!(
+        "Two-factor authentication is not enabled.".into(),
+        ErrorCode::InvalidParameter
+    )
+}
+
+fn unix_now() -> u64 {
+    (utc_now!() / 1000) as u64
+}
+
+fn random_bytes(len: usize) -> BichonResult<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| {
+        raise_error!(
+            "Failed to generate random bytes.".into(),
+            ErrorCode::InternalError
+        )
+    })?;
+    Ok(bytes)
+}
+
+/// HOTP value (RFC 4226) for `counter`, truncated to `TOTP_DIGITS` digits.
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &counter.to_be_bytes());
+    let digest = digest.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Return the time step `code` is valid for, if it is within the allowed
+/// skew of `now` and newer than `last_step`.
+fn verify_code(secret: &[u8], code: &str, now: u64, last_step: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current = now / TOTP_PERIOD_SECS;
+    (current.saturating_sub(TOTP_SKEW)..=current + TOTP_SKEW)
+        .filter(|step| *step > last_step)
+        .find(|step| hotp(secret, *step) == code)
+}
+
+fn provisioning_uri(secret: &str, username: &str) -> String {
+    let label = encode_uri_component(&format!("{TOTP_ISSUER}:{username}"));
+    format!(
+        "otpauth://totp/{label}?secret={secret}&issuer={TOTP_ISSUER}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD_SECS}"
+    )
+}
+
+fn qr_svg(uri: &str) -> BichonResult<String> {
+    let code = QrCode::new(uri.as_bytes())
+        .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+fn encode_uri_component(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+fn new_recovery_codes() -> BichonResult<Vec<String>> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let bytes = random_bytes(RECOVERY_CODE_LEN)?;
+            let code: String = bytes
+                .iter()
+                .map(|b| BASE32_ALPHABET[(*b & 0x1f) as usize] as char)
+                .collect();
+            let (head, tail) = code.split_at(RECOVERY_CODE_LEN / 2);
+            Ok(format!("{head}-{tail}").to_lowercase())
+        })
+        .collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hash_token(&normalize_recovery_code(code))
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(data: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in data.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_rfc6238_vectors() {
+        // RFC 6238 Appendix B (SHA-1), truncated to six digits.
+        for (time, expected) in [
+            (59, 287082),
+            (1111111109, 81804),
+            (1111111111, 50471),
+            (1234567890, 5924),
+            (2000000000, 279037),
+        ] {
+            assert_eq!(hotp(RFC_SECRET, time / TOTP_PERIOD_SECS), expected);
+        }
+    }
+
+    #[test]
+    fn accepts_skew_and_rejects_replay() {
+        let now = 1234567890;
+        let step = now / TOTP_PERIOD_SECS;
+        let previous = format!("{:06}", hotp(RFC_SECRET, step - 1));
+        assert_eq!(verify_code(RFC_SECRET, &previous, now, 0), Some(step - 1));
+        assert_eq!(verify_code(RFC_SECRET, &previous, now, step - 1), None);
+
+        let stale = format!("{:06}", hotp(RFC_SECRET, step - 2));
+        assert_eq!(verify_code(RFC_SECRET, &stale, now, 0), None);
+        assert_eq!(verify_code(RFC_SECRET, "12345", now, 0), None);
+        assert_eq!(verify_code(RFC_SECRET, "abcdef", now, 0), None);
+    }
+
+    #[test]
+    fn base32_roundtrip() {
+        assert_eq!(
+            base32_encode(RFC_SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+        for len in 0..12 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37) as u8).collect();
+            assert_eq!(base32_decode(&base32_encode(&data)).unwrap(), data);
+        }
+        assert!(base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn provisioning_uri_escapes_label() {
+        assert_eq!(
+            provisioning_uri("ABC", "jane doe@example.com"),
+            "otpauth://totp/Bichon%3Ajane%20doe%40example.com?secret=ABC&issuer=Bichon&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn qr_code_renders_as_svg() {
+        let svg = qr_svg(&provisioning_uri("ABC", "jane")).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        assert_eq!(normalize_recovery_code(" abcde-f2345 "), "ABCDEF2345");
+        let codes = new_recovery_codes().unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|c| c.len() == RECOVERY_CODE_LEN + 1));
+    }
+}
@@ -18,6 +18,7 @@
 
 use base64::{engine::general_purpose, Engine as _};
 use ring::aead::{Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, AES_256_GCM};
+use ring::hmac;
 use ring::pbkdf2::{self, derive};
 use ring::rand::{SecureRandom, SystemRandom};
 use std::fs;
@@ -72,6 +73,20 @@ pub fn decrypt_string(data: &str) -> BichonResult<String> {
     })
 }
 
+/// Keyed hash of an access token secret, used as its storage key so the
+/// secret itself never reaches disk.
+pub fn hash_token(token: &str) -> String {
+    internal_hash_token(&ENCRYPT_PASSWORD, token)
+}
+
+pub fn internal_hash_token(password: &str, token: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, password.as_bytes());
+    let mut ctx = hmac::Context::with_key(&key);
+    ctx.update(b"bichon-access-token\0");
+    ctx.update(token.as_bytes());
+    hex::encode(ctx.sign().as_ref())
+}
+
 pub fn internal_encrypt_string(
     password: &str,
     plaintext: &str,
@@ -183,4 +198,13 @@ mod tests {
         encrypted.push('X');
         assert!(internal_decrypt_string("pw", &encrypted).is_err());
     }
+
+    #[test]
+    fn test_token_hash_is_stable_and_keyed() {
+        let hash = internal_hash_token("pw", "token");
+        assert_eq!(hash, internal_hash_token("pw", "token"));
+        assert_eq!(hash.len(), 64);
+        assert_ne!(hash, internal_hash_token("pw", "token2"));
+        assert_ne!(hash, internal_hash_token("other_pw", "token"));
+    }
 }
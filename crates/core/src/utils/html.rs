@@ -17,7 +17,8 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 
-use regex::Regex;
+use crate::utils::sanitize::escape_attribute;
+use regex::{Captures, Regex};
 use std::panic;
 use std::sync::LazyLock;
 use tracing::error;
@@ -74,6 +75,50 @@ pub fn block_remote_content(html: &str) -> String {
     result
 }
 
+/// Points remote `src`/`poster`/`data` attributes and CSS `url()` references
+/// at the URLs returned by `rewrite`, then strips whatever remote references
+/// remain (`srcset`, stylesheets and other forms that cannot be rewritten).
+///
+/// Expects serialized HTML as produced by the sanitizer: URLs are unescaped
+/// before being passed to `rewrite`, protocol-relative URLs are treated as
+/// https, and the replacement is escaped for use inside an attribute.
+pub fn rewrite_remote_content(html: &str, rewrite: impl Fn(&str) -> String) -> String {
+    static SRC_ATTR_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(?i)(\s(?:src|poster|data)\s*=\s*)(["'])\s*((?:https?:)?//[^"'\s]*)\s*["']"#)
+            .unwrap()
+    });
+    static CSS_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(?i)url\(\s*["']?\s*((?:https?:)?//[^)"'\s]*)\s*["']?\s*\)"#).unwrap()
+    });
+
+    let proxied = |raw: &str| {
+        let url = unescape_attribute(raw);
+        let url = if url.starts_with("//") {
+            format!("https:{url}")
+        } else {
+            url
+        };
+        escape_attribute(&rewrite(&url))
+    };
+    let result = SRC_ATTR_RE.replace_all(html, |caps: &Captures| {
+        format!("{}{}{}{}", &caps[1], &caps[2], proxied(&caps[3]), &caps[2])
+    });
+    let result = CSS_URL_RE.replace_all(&result, |caps: &Captures| {
+        format!("url({})", proxied(&caps[1]))
+    });
+    block_remote_content(&result)
+}
+
+fn unescape_attribute(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
 pub fn extract_text(html: String) -> String {
     let result = panic::catch_unwind(|| {
         html2text::config::plain()
@@ -184,8 +229,7 @@ mod tests {
 
         #[test]
         fn strips_link_stylesheet_href() {
-            let html =
-                r#"<link rel="stylesheet" href="https://fonts.example.com/font.css">"#;
+            let html = r#"<link rel="stylesheet" href="https://fonts.example.com/font.css">"#;
             let result = block_remote_content(html);
             assert!(!result.contains("https://fonts.example.com"));
             assert!(result.contains("<link")); // tag preserved
@@ -221,8 +265,7 @@ mod tests {
 
         #[test]
         fn strips_css_import() {
-            let html =
-                r#"<style>@import url("https://fonts.example.com/font.css");</style>"#;
+            let html = r#"<style>@import url("https://fonts.example.com/font.css");</style>"#;
             let result = block_remote_content(html);
             assert!(!result.contains("https://fonts.example.com"));
         }
@@ -236,8 +279,7 @@ mod tests {
 
         #[test]
         fn strips_srcset() {
-            let html =
-                r#"<img srcset="https://cdn.example.com/img1.jpg 1x, https://cdn.example.com/img2.jpg 2x">"#;
+            let html = r#"<img srcset="https://cdn.example.com/img1.jpg 1x, https://cdn.example.com/img2.jpg 2x">"#;
             let result = block_remote_content(html);
             assert!(!result.contains("https://cdn.example.com"));
         }
@@ -270,4 +312,52 @@ mod tests {
             assert!(result.contains(r#"href="https://example.com/read-more""#));
         }
     }
+    mod rewrite_remote {
+        use super::*;
+
+        fn rewrite(html: &str) -> String {
+            rewrite_remote_content(html, |url| {
+                format!("/proxy?url={}&sig=1", urlencoding::encode(url))
+            })
+        }
+
+        #[test]
+        fn rewrites_img_src_and_unescapes_the_url() {
+            let result = rewrite(r#"<img src="https://t.example/p.gif?a=1&amp;b=2" alt="x">"#);
+            assert_eq!(
+                result,
+                r#"<img src="/proxy?url=https%3A%2F%2Ft.example%2Fp.gif%3Fa%3D1%26b%3D2&amp;sig=1" alt="x">"#
+            );
+        }
+
+        #[test]
+        fn protocol_relative_urls_become_https() {
+            let result = rewrite(r#"<img src="//t.example/p.gif">"#);
+            assert!(result.contains("url=https%3A%2F%2Ft.example%2Fp.gif"));
+        }
+
+        #[test]
+        fn rewrites_css_urls() {
+            let result = rewrite(
+                r#"<td style="background: url(https://t.example/bg.png) no-repeat">x</td>"#,
+            );
+            assert!(result.contains(
+                "background: url(/proxy?url=https%3A%2F%2Ft.example%2Fbg.png&amp;sig=1) no-repeat"
+            ));
+        }
+
+        #[test]
+        fn strips_what_cannot_be_rewritten() {
+            let result = rewrite(r#"<img srcset="https://t.example/a.png 1x" alt="x">"#);
+            assert!(!result.contains("t.example"));
+            assert!(result.contains("alt="));
+        }
+
+        #[test]
+        fn leaves_local_references_alone() {
+            let html =
+                r#"<img src="data:image/png;base64,AAAA"><img src="/api/inline-attachment/1/2">"#;
+            assert_eq!(rewrite(html), html);
+        }
+    }
 }
@@ -29,7 +29,9 @@ pub mod encrypt;
 pub mod html;
 pub mod net;
 pub mod rate_limit;
+pub mod sanitize;
 pub mod shutdown;
+pub mod signed_url;
 pub mod tls;
 
 #[macro_export]
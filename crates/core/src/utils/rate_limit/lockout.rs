@@ -0,0 +1,365 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Failed-login tracking shared by the WebUI login and SMTP AUTH.
+//!
+//! Failures are counted per username and per client IP. After a few free
+//! attempts every further attempt has to wait an exponentially growing
+//! delay, and reaching the threshold locks the subject out for the lockout
+//! duration. Counters are forgotten after a quiet period of the same length,
+//! and the username counter is cleared by a successful login. State is kept
+//! in memory only.
+
+use std::{
+    cmp::Reverse,
+    hash::Hash,
+    net::IpAddr,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{code::ErrorCode, BichonResult},
+    ext::event_bus::{emit, Event},
+    raise_error,
+    settings::cli::SETTINGS,
+};
+
+pub static LOGIN_GUARD: LazyLock<LoginGuard> = LazyLock::new(|| {
+    LoginGuard::new(LockoutPolicy {
+        max_failures: SETTINGS.bichon_login_max_failures.max(1),
+        lockout: Duration::from_secs(SETTINGS.bichon_login_lockout_minutes.max(1) as u64 * 60),
+    })
+});
+
+/// Failures that are not delayed at all.
+const FREE_ATTEMPTS: u32 = 3;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// Many users can share one address (NAT, proxies), so an IP is only locked
+/// after this many times the per-user threshold.
+const IP_THRESHOLD_FACTOR: u32 = 5;
+/// Expired entries are pruned once a map grows past this size.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub lockout: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+}
+
+impl Failures {
+    fn expired(&self, policy: &LockoutPolicy, now: Instant) -> bool {
+        now.duration_since(self.last_failure) >= policy.lockout
+    }
+
+    /// Until when further attempts are refused.
+    fn blocked_until(&self, policy: &LockoutPolicy, threshold: u32) -> Option<Instant> {
+        if self.count >= threshold {
+            return Some(self.last_failure + policy.lockout);
+        }
+        if self.count <= FREE_ATTEMPTS {
+            return None;
+        }
+        let exponent = (self.count - FREE_ATTEMPTS - 1).min(16);
+        let delay = (BASE_DELAY * 2u32.pow(exponent)).min(policy.lockout);
+        Some(self.last_failure + delay)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web-api", derive(poem_openapi::Object))]
+pub struct LoginLockout {
+    /// Username or IP address.
+    pub subject: String,
+    pub is_ip: bool,
+    pub failures: u32,
+    /// Seconds until the next attempt is accepted; 0 if not blocked.
+    pub retry_after_secs: u64,
+}
+
+pub struct LoginGuard {
+    policy: LockoutPolicy,
+    users: DashMap<String, Failures>,
+    ips: DashMap<IpAddr, Failures>,
+}
+
+impl LoginGuard {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            users: DashMap::new(),
+            ips: DashMap::new(),
+        }
+    }
+
+    /// Refuse the attempt with `TooManyRequest` while the username or the
+    /// client IP is backing off or locked out.
+    pub fn check(&self, username: &str, ip: Option<IpAddr>) -> BichonResult<()> {
+        match self.retry_after(username, ip, Instant::now()) {
+            Some(wait) => Err(raise_error!(
+                format!(
+                    "Too many failed login attempts. Try again in {} seconds.",
+                    wait.as_secs().max(1)
+                ),
+                ErrorCode::TooManyRequest
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a failed attempt against `subject` and the client IP, and
+    /// report it on the event bus as a failed login of `user`.
+    pub fn record_failure(
+        &self,
+        subject: &str,
+        user: &str,
+        ip: Option<IpAddr>,
+        channel: &str,
+        reason: &str,
+    ) {
+        self.record_failure_at(subject, ip, Instant::now());
+        emit(Event::LoginFailed {
+            user: user.to_string(),
+            ip,
+            channel: channel.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    pub fn record_success(&self, subject: &str) {
+        self.users.remove(&normalize(subject));
+    }
+
+    /// Counter subject for a username the client can choose freely, such as
+    /// the SMTP AUTH name in front of an access token. It is scoped to the
+    /// client address so that failures from one host cannot lock the name
+    /// out everywhere else.
+    pub fn scoped_subject(username: &str, ip: Option<IpAddr>) -> String {
+        match ip {
+            Some(ip) => format!("{} ({})", normalize(username), ip),
+            None => normalize(username),
+        }
+    }
+
+    /// Clear the counters of a username or IP address. Returns whether
+    /// anything was tracked for it.
+    pub fn clear(&self, subject: &str) -> bool {
+        let subject = subject.trim();
+        let ip_cleared = subject
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| self.ips.remove(&ip).is_some());
+        let user_cleared = self.users.remove(&normalize(subject)).is_some();
+        ip_cleared || user_cleared
+    }
+
+    /// Currently tracked subjects, most failures first.
+    pub fn list(&self) -> Vec<LoginLockout> {
+        let now = Instant::now();
+        let mut entries: Vec<LoginLockout> = self
+            .users
+            .iter()
+            .filter_map(|e| self.describe(e.key().clone(), false, e.value(), now))
+            .chain(
+                self.ips
+                    .iter()
+                    .filter_map(|e| self.describe(e.key().to_string(), true, e.value(), now)),
+            )
+            .collect();
+        entries.sort_by_key(|e| Reverse(e.failures));
+        entries
+    }
+
+    fn describe(
+        &self,
+        subject: String,
+        is_ip: bool,
+        failures: &Failures,
+        now: Instant,
+    ) -> Option<LoginLockout> {
+        if failures.expired(&self.policy, now) {
+            return None;
+        }
+        let retry_after = failures
+            .blocked_until(&self.policy, self.threshold(is_ip))
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        Some(LoginLockout {
+            subject,
+            is_ip,
+            failures: failures.count,
+            retry_after_secs: retry_after.as_secs(),
+        })
+    }
+
+    fn threshold(&self, is_ip: bool) -> u32 {
+        if is_ip {
+            self.policy.max_failures.saturating_mul(IP_THRESHOLD_FACTOR)
+        } else {
+            self.policy.max_failures
+        }
+    }
+
+    fn retry_after(&self, username: &str, ip: Option<IpAddr>, now: Instant) -> Option<Duration> {
+        let user_wait = self.wait(&self.users, &normalize(username), false, now);
+        let ip_wait = ip.and_then(|ip| self.wait(&self.ips, &ip, true, now));
+        user_wait.max(ip_wait)
+    }
+
+    fn wait<K: Eq + Hash>(
+        &self,
+        map: &DashMap<K, Failures>,
+        key: &K,
+        is_ip: bool,
+        now: Instant,
+    ) -> Option<Duration> {
+        let failures = *map.get(key)?;
+        if failures.expired(&self.policy, now) {
+            return None;
+        }
+        let until = failures.blocked_until(&self.policy, self.threshold(is_ip))?;
+        Some(until.saturating_duration_since(now)).filter(|wait| !wait.is_zero())
+    }
+
+    fn record_failure_at(&self, username: &str, ip: Option<IpAddr>, now: Instant) {
+        self.bump(&self.users, normalize(username), now);
+        if let Some(ip) = ip {
+            self.bump(&self.ips, ip, now);
+        }
+    }
+
+    fn bump<K: Eq + Hash>(&self, map: &DashMap<K, Failures>, key: K, now: Instant) {
+        if map.len() > PRUNE_THRESHOLD {
+            map.retain(|_, f| !f.expired(&self.policy, now));
+        }
+        let mut entry = map.entry(key).or_insert(Failures {
+            count: 0,
+            last_failure: now,
+        });
+        if entry.expired(&self.policy, now) {
+            entry.count = 0;
+        }
+        entry.count = entry.count.saturating_add(1);
+        entry.last_failure = now;
+    }
+}
+
+fn normalize(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> LoginGuard {
+        LoginGuard::new(LockoutPolicy {
+            max_failures: 6,
+            lockout: Duration::from_secs(600),
+        })
+    }
+
+    #[test]
+    fn backs_off_exponentially_then_locks() {
+        let guard = guard();
+        let start = Instant::now();
+        for _ in 0..FREE_ATTEMPTS {
+            guard.record_failure_at("Alice", None, start);
+        }
+        assert_eq!(guard.retry_after("alice", None, start), None);
+
+        guard.record_failure_at("alice", None, start);
+        assert_eq!(
+            guard.retry_after("alice", None, start),
+            Some(Duration::from_secs(1))
+        );
+        guard.record_failure_at("alice", None, start);
+        assert_eq!(
+            guard.retry_after("alice", None, start),
+            Some(Duration::from_secs(2))
+        );
+
+        guard.record_failure_at("alice", None, start);
+        assert_eq!(
+            guard.retry_after("alice", None, start),
+            Some(Duration::from_secs(600))
+        );
+        let later = start + Duration::from_secs(600);
+        assert_eq!(guard.retry_after("alice", None, later), None);
+    }
+
+    #[test]
+    fn success_clears_user_but_not_ip() {
+        let guard = guard();
+        let ip: IpAddr = "192.0.2.7".parse().unwrap();
+        let now = Instant::now();
+        for _ in 0..30 {
+            guard.record_failure_at("bob", Some(ip), now);
+        }
+        guard.record_success("bob");
+        assert_eq!(guard.retry_after("bob", None, now), None);
+        assert_eq!(
+            guard.retry_after("carol", Some(ip), now),
+            Some(Duration::from_secs(600))
+        );
+
+        assert!(guard.clear("192.0.2.7"));
+        assert_eq!(guard.retry_after("carol", Some(ip), now), None);
+        assert!(!guard.clear("192.0.2.7"));
+    }
+
+    #[test]
+    fn scoped_subjects_are_per_address() {
+        let guard = guard();
+        let first: IpAddr = "192.0.2.7".parse().unwrap();
+        let second: IpAddr = "198.51.100.1".parse().unwrap();
+        let now = Instant::now();
+        for _ in 0..6 {
+            guard.record_failure_at(&LoginGuard::scoped_subject("Erin", Some(first)), None, now);
+        }
+        let locked = LoginGuard::scoped_subject("erin", Some(first));
+        assert!(guard.retry_after(&locked, None, now).is_some());
+        let elsewhere = LoginGuard::scoped_subject("erin", Some(second));
+        assert_eq!(guard.retry_after(&elsewhere, None, now), None);
+
+        guard.record_success(&LoginGuard::scoped_subject("mallory", Some(first)));
+        assert!(guard.retry_after(&locked, None, now).is_some());
+        guard.record_success(&locked);
+        assert_eq!(guard.retry_after(&locked, None, now), None);
+    }
+
+    #[test]
+    fn counters_decay_after_quiet_period() {
+        let guard = guard();
+        let start = Instant::now();
+        for _ in 0..5 {
+            guard.record_failure_at("dave", None, start);
+        }
+        let later = start + Duration::from_secs(601);
+        guard.record_failure_at("dave", None, later);
+        assert_eq!(guard.retry_after("dave", None, later), None);
+        assert_eq!(guard.list()[0].failures, 1);
+    }
+}
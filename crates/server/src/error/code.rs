@@ -44,6 +44,10 @@ impl IntoStatusCode for ErrorCode {
             | ErrorCode::ImapUnexpectedResult
             | ErrorCode::HttpResponseError
             | ErrorCode::ImapAuthenticationFailed
+            | ErrorCode::Pop3CommandFailed
+            | ErrorCode::Pop3AuthenticationFailed
+            | ErrorCode::GraphRequestFailed
+            | ErrorCode::JmapRequestFailed
             | ErrorCode::MissingRefreshToken
             | ErrorCode::NetworkError
             | ErrorCode::ConnectionTimeout
@@ -138,6 +142,10 @@ mod tests {
             ErrorCode::ImapUnexpectedResult,
             ErrorCode::HttpResponseError,
             ErrorCode::ImapAuthenticationFailed,
+            ErrorCode::Pop3CommandFailed,
+            ErrorCode::Pop3AuthenticationFailed,
+            ErrorCode::GraphRequestFailed,
+            ErrorCode::JmapRequestFailed,
             ErrorCode::MissingRefreshToken,
             ErrorCode::NetworkError,
             ErrorCode::ConnectionTimeout,
@@ -25,7 +25,7 @@ use std::sync::LazyLock;
 
 use bichon_core::{
     bichon_version,
-    cache::imap::task::SYNC_TASKS,
+    cache::imap::{idle::IDLE_WATCHERS, task::SYNC_TASKS},
     common::{rustls::BichonTls, signal::SignalManager},
     context::{executors::BichonContext, Initialize},
     database::manager::DB_MANAGER,
@@ -34,6 +34,7 @@ use bichon_core::{
     migrate::check_data_status,
     raise_error,
     settings::{cli::SETTINGS, dir::DataDirManager},
+    smtp::spool::SmtpSpool,
     store::{
         blob::BLOB_MANAGER,
         tantivy::{attachment::ATTACHMENT_MANAGER, envelope::ENVELOPE_MANAGER},
@@ -115,6 +116,7 @@ pub async fn run() -> BichonResult<()> {
         info!("SMTP server stopped.");
     }
 
+    IDLE_WATCHERS.shutdown().await;
     SYNC_TASKS.shutdown().await;
     ENVELOPE_MANAGER.shutdown().await;
     ATTACHMENT_MANAGER.shutdown().await;
@@ -130,6 +132,9 @@ async fn initialize() -> BichonResult<()> {
     UserManager::initialize().await?;
     BichonTls::initialize().await?;
     BichonContext::initialize().await?;
+    // Replayed regardless of `bichon_enable_smtp`: mail that was already
+    // acknowledged with `250` must still reach the archive.
+    SmtpSpool::initialize().await?;
     LazyLock::force(&BLOB_MANAGER);
     LazyLock::force(&ENVELOPE_MANAGER);
     LazyLock::force(&ATTACHMENT_MANAGER);
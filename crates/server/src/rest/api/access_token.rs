@@ -19,7 +19,9 @@
 use crate::common::auth::WrappedContext;
 use crate::rest::api::ApiTags;
 use crate::rest::ApiResult;
+use bichon_core::error::code::ErrorCode;
 use bichon_core::ext::event_bus::{emit, Event};
+use bichon_core::raise_error;
 use bichon_core::token::view::AccessTokenResp;
 use bichon_core::users::permissions::Permission;
 use bichon_core::{token::payload::AccessTokenCreateRequest, token::AccessTokenModel};
@@ -92,6 +94,14 @@ impl AccessTokenApi {
         if target_user_id != current_user_id {
             context.require_permission(None, Permission::USER_MANAGE)?;
         }
+        // A scoped token must not be able to mint a broader one.
+        if !context.scope.covers(&payload.0.scope()) {
+            return Err(raise_error!(
+                "Access denied: a scoped token can only create tokens within its own scope."
+                    .into(),
+                ErrorCode::Forbidden
+            ))?;
+        }
         let token_name = payload.0.name.clone();
         let token_string = AccessTokenModel::create_api_token(target_user_id, payload.0)?;
         let target_username = bichon_core::users::UserModel::find(target_user_id)?
@@ -20,13 +20,14 @@ use crate::common::auth::WrappedContext;
 use crate::rest::api::ApiTags;
 use crate::rest::ApiResult;
 use bichon_core::account::grant::BatchAccountRoleRequest;
-use bichon_core::account::migration::{AccountModel, AccountType};
+use bichon_core::account::migration::AccountModel;
 use bichon_core::account::payload::{
     filter_accessible_accounts, AccountCreateRequest, AccountUpdateRequest, MinimalAccount,
 };
 use bichon_core::account::state::{DownloadState, GapFillState};
 use bichon_core::account::stats::AccountStats;
 use bichon_core::account::view::AccountResp;
+use bichon_core::cache::imap::idle::IDLE_WATCHERS;
 use bichon_core::cache::imap::task::SYNC_TASKS;
 use bichon_core::common::paginated::{paginate_vec, DataPage};
 use bichon_core::error::code::ErrorCode;
@@ -134,6 +135,8 @@ impl AccountApi {
         let account_id = account_id.0;
         context.require_permission(Some(account_id), Permission::ACCOUNT_MANAGE)?;
         AccountModel::update(account_id, payload.0, true)?;
+        // Folders or the enabled flag may have changed.
+        tokio::spawn(IDLE_WATCHERS.restart(account_id));
         let email = AccountModel::find(account_id)?
             .map(|a| a.email)
             .unwrap_or_else(|| format!("account-{account_id}"));
@@ -157,7 +160,7 @@ impl AccountApi {
         desc: Query<Option<bool>>,
         context: WrappedContext,
     ) -> ApiResult<Json<DataPage<AccountResp>>> {
-        let is_admin = context.user.is_admin();
+        let is_admin = context.user.is_admin() && context.scope.account_ids.is_none();
         let sort_desc = desc.0.unwrap_or(true);
 
         let user_map: HashMap<u64, UserModel> = UserModel::list_all()?
@@ -167,8 +170,7 @@ impl AccountApi {
         let page_data: DataPage<AccountModel> = if is_admin {
             AccountModel::paginate_list(page.0, page_size.0, desc.0)?
         } else {
-            let authorized_ids: HashSet<u64> =
-                context.user.account_access_map.keys().cloned().collect();
+            let authorized_ids: HashSet<u64> = context.assigned_account_ids();
 
             if authorized_ids.is_empty() {
                 return Ok(Json(DataPage {
@@ -270,9 +272,9 @@ impl AccountApi {
     ) -> ApiResult<()> {
         let account_id = account_id.0;
         let account = AccountModel::check_account_exists(account_id)?;
-        if !matches!(account.account_type, AccountType::IMAP) {
+        if !account.is_downloadable() {
             return Err(raise_error!(
-                format!("Manual download is not supported for '{:#?}' accounts. Only IMAP accounts are supported.", account.account_type),
+                format!("Manual download is not supported for '{:#?}' accounts. Only IMAP, POP3, Graph and JMAP accounts are supported.", account.account_type),
                 ErrorCode::InvalidParameter
             ))?;
         }
@@ -301,9 +303,9 @@ impl AccountApi {
         let account_id = account_id.0;
         let account = AccountModel::check_account_exists(account_id)?;
 
-        if !matches!(account.account_type, AccountType::IMAP) {
+        if !account.is_downloadable() {
             return Err(raise_error!(
-                "This operation is only supported for IMAP accounts.".into(),
+                "This operation is only supported for IMAP, POP3, Graph and JMAP accounts.".into(),
                 ErrorCode::InvalidParameter
             ))?;
         }
@@ -356,7 +358,7 @@ impl AccountApi {
         only_nosync: Query<Option<bool>>,
         context: WrappedContext,
     ) -> ApiResult<Json<Vec<MinimalAccount>>> {
-        let is_admin = context.user.is_admin();
+        let is_admin = context.user.is_admin() && context.scope.account_ids.is_none();
         let only_nosync = only_nosync.0.unwrap_or_default();
 
         let minimal_list = AccountModel::minimal_list(only_nosync)?;
@@ -364,7 +366,7 @@ impl AccountApi {
             return Ok(Json(minimal_list));
         }
 
-        let authorized_ids: Vec<u64> = context.user.account_access_map.keys().cloned().collect();
+        let authorized_ids: Vec<u64> = context.assigned_account_ids().into_iter().collect();
         let result = filter_accessible_accounts(&minimal_list, &authorized_ids);
         Ok(Json(result))
     }
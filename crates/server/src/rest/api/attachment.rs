@@ -54,11 +54,7 @@ impl AttachmentApi {
         context: WrappedContext,
     ) -> ApiResult<Json<DataPage<AttachmentModel>>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         let search_text = payload
             .0
             .filter()
@@ -119,11 +115,7 @@ impl AttachmentApi {
         context: WrappedContext,
     ) -> ApiResult<Json<Vec<TagCount>>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         Ok(Json(ATTACHMENT_MANAGER.get_all_tags(authorized_ids)?))
     }
 
@@ -177,11 +169,7 @@ impl AttachmentApi {
         context: WrappedContext,
     ) -> ApiResult<Json<HashSet<String>>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         Ok(Json(ATTACHMENT_MANAGER.get_all_senders(authorized_ids)?))
     }
 
@@ -196,11 +184,7 @@ impl AttachmentApi {
         context: WrappedContext,
     ) -> ApiResult<Json<AttachmentMetadata>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         Ok(Json(
             ATTACHMENT_MANAGER.collect_attachment_metadata(authorized_ids)?,
         ))
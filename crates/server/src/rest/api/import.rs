@@ -105,7 +105,7 @@ impl ImportApi {
         Ok(Json(result))
     }
 
-    /// Upload an EML or MBOX file for import into a NoSync account.
+    /// Upload an EML, MBOX, PST or Outlook MSG file for import into a NoSync account.
     ///
     /// The file is sent as the raw request body. Both `account_id` and `mail_folder`
     /// must be provided as query parameters, along with the original `file_name` for
@@ -121,7 +121,7 @@ impl ImportApi {
         mail_folder: Query<String>,
         /// Original file name, used for extension validation (e.g. "export.eml").
         file_name: Query<String>,
-        /// The raw file bytes (.eml or .mbox).
+        /// The raw file bytes (.eml, .mbox, .pst or .msg).
         data: Binary<Body>,
         context: WrappedContext,
     ) -> ApiResult<Json<ImportProgress>> {
@@ -156,10 +156,11 @@ impl ImportApi {
         let is_mbox_ext = ext_lower == "mbox";
         let is_eml_ext = ext_lower == "eml";
         let is_pst_ext = ext_lower == "pst";
-        if !is_mbox_ext && !is_eml_ext && !is_pst_ext {
+        let is_msg_ext = ext_lower == "msg";
+        if !is_mbox_ext && !is_eml_ext && !is_pst_ext && !is_msg_ext {
             return Err(raise_error!(
                 format!(
-                    "Unsupported file type '.{}'. Only .eml, .mbox and .pst files are allowed.",
+                    "Unsupported file type '.{}'. Only .eml, .mbox, .pst and .msg files are allowed.",
                     ext_lower
                 ),
                 ErrorCode::InvalidParameter
@@ -202,8 +203,10 @@ impl ImportApi {
         let (format_detected, file_len) = stream_body_to_temp(
             data.0,
             &temp_path,
+            &file_name,
             is_mbox_ext,
             is_pst_ext,
+            is_msg_ext,
         ).await?;
 
         let format = format_detected.unwrap_or_else(|| {
@@ -211,6 +214,8 @@ impl ImportApi {
                 FileFormat::Mbox
             } else if is_pst_ext {
                 FileFormat::Pst
+            } else if is_msg_ext {
+                FileFormat::Msg
             } else {
                 FileFormat::Eml
             }
@@ -220,6 +225,7 @@ impl ImportApi {
             FileFormat::Mbox => "mbox".to_string(),
             FileFormat::Eml => "eml".to_string(),
             FileFormat::Pst => "pst".to_string(),
+            FileFormat::Msg => "msg".to_string(),
         };
 
         let max_mbox = SETTINGS.bichon_web_mbox_upload_limit_mb as usize * 1024 * 1024;
@@ -227,7 +233,7 @@ impl ImportApi {
         let max_size = match format {
             FileFormat::Mbox => max_mbox,
             FileFormat::Pst => max_pst,
-            FileFormat::Eml => MAX_WEB_EML_BYTES,
+            FileFormat::Eml | FileFormat::Msg => MAX_WEB_EML_BYTES,
         };
         if file_len > max_size {
             let _ = std::fs::remove_file(&temp_path);
@@ -339,8 +345,10 @@ impl ImportApi {
 async fn stream_body_to_temp(
     body: Body,
     temp_path: &std::path::Path,
+    file_name: &str,
     is_mbox_ext: bool,
     is_pst_ext: bool,
+    is_msg_ext: bool,
 ) -> ApiResult<(Option<FileFormat>, usize)> {
     let max_mbox = SETTINGS.bichon_web_mbox_upload_limit_mb as usize * 1024 * 1024;
     let max_pst = SETTINGS.bichon_web_pst_upload_limit_mb as usize * 1024 * 1024;
@@ -399,15 +407,15 @@ async fn stream_body_to_temp(
         // Once we have enough data, validate format and text
         if first_chunk.len() >= 512 && !text_checked {
             text_checked = true;
-            format_detected = bichon_core::import::detect_format(&first_chunk, "upload");
+            format_detected = bichon_core::import::detect_format(&first_chunk, file_name);
 
             // If extension is .eml but content looks like MBOX (or vice versa), that's OK.
-            // PST files are binary — skip text detection.
-            if !is_pst_ext && !detect_text_file(&first_chunk) {
+            // PST and MSG files are binary — skip text detection.
+            if !is_pst_ext && !is_msg_ext && !detect_text_file(&first_chunk) {
                 drop(file);
                 let _ = tokio::fs::remove_file(temp_path).await;
                 return Err(raise_error!(
-                    "The uploaded file appears to be binary (not a valid email file). Only .eml, .mbox and .pst files are accepted.".into(),
+                    "The uploaded file appears to be binary (not a valid email file). Only .eml, .mbox, .pst and .msg files are accepted.".into(),
                     ErrorCode::InvalidParameter
                 ))?;
             }
@@ -24,6 +24,7 @@ use bichon_core::common::paginated::DataPage;
 use bichon_core::error::code::ErrorCode;
 use bichon_core::message::append::restore_emails;
 use bichon_core::message::append::RestoreMessagesRequest;
+use bichon_core::message::attachment::retrieve_archive_member_content;
 use bichon_core::message::attachment::retrieve_attachment_content;
 use bichon_core::message::attachment::retrieve_nested_attachment_content;
 use bichon_core::message::content::retrieve_nested_eml_content;
@@ -100,11 +101,7 @@ impl MessageApi {
         context: WrappedContext,
     ) -> ApiResult<Json<DataPage<Envelope>>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         let search_text = payload
             .0
             .filter
@@ -444,15 +441,69 @@ impl MessageApi {
         Ok(attachment)
     }
 
+    /// Downloads a file from within an archive attachment (zip, tar, gzip or 7z).
+    #[oai(
+        path = "/download-archive-member/:account_id/:envelope_id",
+        method = "get",
+        operation_id = "download_archive_member"
+    )]
+    async fn download_archive_member(
+        &self,
+        /// The ID of the account.
+        account_id: Path<u64>,
+        /// The ID of the message containing the archive.
+        envelope_id: Path<String>,
+        /// The content hash of the archive attachment.
+        content_hash: Query<String>,
+        /// The content hash of the file within the archive.
+        member_content_hash: Query<String>,
+        context: WrappedContext,
+    ) -> ApiResult<Attachment<Body>> {
+        let account_id = account_id.0;
+        let envelope_id = envelope_id.0.trim().to_string();
+        AccountModel::check_account_exists(account_id)?;
+        context.require_permission(Some(account_id), Permission::DATA_READ)?;
+        let content_hash = content_hash.0.trim().to_string();
+        let member_content_hash = member_content_hash.0.trim().to_string();
+        let meta = attachment_meta_for_audit(account_id, &envelope_id, &content_hash);
+        // Expanding the archive again is CPU-bound, keep it off the runtime.
+        let (path, reader) = {
+            let envelope_id = envelope_id.clone();
+            let member_content_hash = member_content_hash.clone();
+            tokio::task::spawn_blocking(move || {
+                retrieve_archive_member_content(
+                    account_id,
+                    envelope_id,
+                    &content_hash,
+                    &member_content_hash,
+                )
+            })
+            .await
+            .map_err(|e| raise_error!(format!("{:#?}", e), ErrorCode::InternalError))??
+        };
+        emit(Event::AttachmentDownloaded {
+            email_id: envelope_id.clone(),
+            content_hash: member_content_hash,
+            user: context.user.username.clone(),
+            account_id,
+            mailbox_id: meta.mailbox_id,
+            filename: Some(path.clone()),
+            size: None,
+            ext: None,
+            parent_content_hash: meta.parent_content_hash,
+        });
+        let body = Body::from_async_read(reader);
+        let attachment = Attachment::new(body)
+            .attachment_type(AttachmentType::Attachment)
+            .filename(path);
+        Ok(attachment)
+    }
+
     /// Returns all facets in the index along with their document counts.
     #[oai(path = "/all-tags", method = "get", operation_id = "get_all_tags")]
     async fn get_all_tags(&self, context: WrappedContext) -> ApiResult<Json<Vec<TagCount>>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         Ok(Json(ENVELOPE_MANAGER.get_all_tags(authorized_ids)?))
     }
 
@@ -503,11 +554,7 @@ impl MessageApi {
     )]
     async fn get_all_contacts(&self, context: WrappedContext) -> ApiResult<Json<HashSet<String>>> {
         let authorized_ids: Option<HashSet<u64>> =
-            if context.has_permission(None, Permission::DATA_READ_ALL) {
-                None
-            } else {
-                Some(context.user.account_access_map.keys().cloned().collect())
-            };
+            context.authorized_account_ids(Permission::DATA_READ_ALL, Permission::DATA_READ);
         Ok(Json(ENVELOPE_MANAGER.get_all_contacts(authorized_ids)?))
     }
 }
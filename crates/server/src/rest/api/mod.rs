@@ -24,7 +24,9 @@ use mailbox::MailBoxApi;
 use message::MessageApi;
 use oauth2::OAuth2Api;
 use poem_openapi::{OpenApiService, Tags};
-use crate::rest::api::{attachment::AttachmentApi, import::ImportApi, users::UsersApi};
+use crate::rest::api::{
+    attachment::AttachmentApi, import::ImportApi, smtp::SmtpApi, users::UsersApi,
+};
 use system::SystemApi;
 
 pub mod access_token;
@@ -35,6 +37,7 @@ pub mod import;
 pub mod mailbox;
 pub mod message;
 pub mod oauth2;
+pub mod smtp;
 pub mod system;
 pub mod users;
 
@@ -49,6 +52,7 @@ pub enum ApiTags {
     Message,
     System,
     Import,
+    Smtp,
     Users,
 }
 
@@ -62,6 +66,7 @@ type RustMailOpenApi = (
     OAuth2Api,
     MessageApi,
     ImportApi,
+    SmtpApi,
     UsersApi,
 );
 
@@ -77,6 +82,7 @@ pub fn create_openapi_service() -> OpenApiService<RustMailOpenApi, ()> {
             OAuth2Api,
             MessageApi,
             ImportApi,
+            SmtpApi,
             UsersApi,
         ),
         "BichonApi",
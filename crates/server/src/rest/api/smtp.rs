@@ -0,0 +1,105 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::common::auth::WrappedContext;
+use crate::rest::api::ApiTags;
+use crate::rest::ApiResult;
+use bichon_core::smtp::spool::{SpoolEntry, SMTP_SPOOL};
+use bichon_core::users::permissions::Permission;
+use poem::Body;
+use poem_openapi::param::Path;
+use poem_openapi::payload::{Attachment, AttachmentType, Json};
+use poem_openapi::OpenApi;
+
+pub struct SmtpApi;
+
+#[OpenApi(prefix_path = "/api/v1", tag = "ApiTags::Smtp")]
+impl SmtpApi {
+    /// List SMTP messages that could not be ingested and were quarantined.
+    ///
+    /// Messages end up here after exhausting their ingest retries. They were
+    /// already acknowledged to the sender, so they are kept until an admin
+    /// retries or deletes them. Requires root permission.
+    #[oai(
+        path = "/smtp-quarantine",
+        method = "get",
+        operation_id = "list_smtp_quarantine"
+    )]
+    async fn list_smtp_quarantine(
+        &self,
+        context: WrappedContext,
+    ) -> ApiResult<Json<Vec<SpoolEntry>>> {
+        context.require_permission(None, Permission::ROOT)?;
+        Ok(Json(SMTP_SPOOL.list_quarantined()?))
+    }
+
+    /// Download the raw EML of a quarantined SMTP message. Requires root permission.
+    #[oai(
+        path = "/smtp-quarantine/:id/download",
+        method = "get",
+        operation_id = "download_smtp_quarantine"
+    )]
+    async fn download_smtp_quarantine(
+        &self,
+        /// The spool id of the quarantined message.
+        id: Path<String>,
+        context: WrappedContext,
+    ) -> ApiResult<Attachment<Body>> {
+        context.require_permission(None, Permission::ROOT)?;
+        let id = id.0;
+        let content = SMTP_SPOOL.get_quarantined_content(&id)?;
+        let attachment = Attachment::new(Body::from(content))
+            .attachment_type(AttachmentType::Attachment)
+            .filename(format!("{id}.eml"));
+        Ok(attachment)
+    }
+
+    /// Move a quarantined SMTP message back to the ingest queue. Requires root permission.
+    #[oai(
+        path = "/smtp-quarantine/:id/retry",
+        method = "post",
+        operation_id = "retry_smtp_quarantine"
+    )]
+    async fn retry_smtp_quarantine(
+        &self,
+        /// The spool id of the quarantined message.
+        id: Path<String>,
+        context: WrappedContext,
+    ) -> ApiResult<()> {
+        context.require_permission(None, Permission::ROOT)?;
+        SMTP_SPOOL.retry_quarantined(&id.0)?;
+        Ok(())
+    }
+
+    /// Permanently delete a quarantined SMTP message. Requires root permission.
+    #[oai(
+        path = "/smtp-quarantine/:id",
+        method = "delete",
+        operation_id = "remove_smtp_quarantine"
+    )]
+    async fn remove_smtp_quarantine(
+        &self,
+        /// The spool id of the quarantined message.
+        id: Path<String>,
+        context: WrappedContext,
+    ) -> ApiResult<()> {
+        context.require_permission(None, Permission::ROOT)?;
+        SMTP_SPOOL.delete_quarantined(&id.0)?;
+        Ok(())
+    }
+}
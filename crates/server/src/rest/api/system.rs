@@ -26,6 +26,7 @@ use bichon_core::raise_error;
 use bichon_core::settings::cli::SETTINGS;
 use bichon_core::settings::proxy::{Proxy, ProxyTestResult};
 use bichon_core::settings::SystemConfigurations;
+use bichon_core::store::fsck::{run_fsck, FsckRepair, FsckReport};
 use bichon_core::users::permissions::Permission;
 use bichon_core::version::{fetch_notifications, Notifications};
 use poem_openapi::param::Path;
@@ -175,4 +176,30 @@ impl SystemApi {
         let config: SystemConfigurations = SystemConfigurations::from(&*SETTINGS);
         Ok(Json(config))
     }
+
+    /// Check envelopes, attachments, blobs and mailboxes for consistency.
+    ///
+    /// Reports envelopes with missing blobs, blobs no document references,
+    /// attachment documents without a parent envelope, and envelopes whose
+    /// mailbox or account was deleted. Repairs selected in the request body
+    /// are applied afterwards. Orphan blobs can only be deleted with the
+    /// offline admin tool. Requires root permission.
+    #[oai(method = "post", path = "/fsck", operation_id = "run_fsck")]
+    async fn run_fsck(
+        &self,
+        repair: Json<FsckRepair>,
+        context: WrappedContext,
+    ) -> ApiResult<Json<FsckReport>> {
+        context.require_permission(None, Permission::ROOT)?;
+        let report = run_fsck(repair.0).await?;
+        if let Some(repaired) = &report.repaired {
+            emit(Event::StorageRepaired {
+                user: context.user.username.clone(),
+                attachments_deleted: repaired.attachments_deleted,
+                envelopes_deleted: repaired.envelopes_deleted,
+                messages_recovered: repaired.messages_recovered,
+            });
+        }
+        Ok(Json(report))
+    }
 }
@@ -21,16 +21,21 @@ use std::collections::BTreeMap;
 use crate::common::auth::WrappedContext;
 use crate::rest::api::ApiTags;
 use crate::rest::ApiResult;
+use bichon_core::error::code::ErrorCode;
 use bichon_core::ext::event_bus::{emit, Event};
+use bichon_core::raise_error;
 use bichon_core::token::AccessTokenModel;
 use bichon_core::users::minimal::MinimalUser;
 use bichon_core::users::payload::{
-    RoleCreateRequest, RoleUpdateRequest, UserCreateRequest, UserUpdateRequest,
+    RoleCreateRequest, RoleUpdateRequest, TwoFactorCodeRequest, UserCreateRequest,
+    UserUpdateRequest,
 };
 use bichon_core::users::permissions::Permission;
 use bichon_core::users::role::{RoleType, UserRole};
+use bichon_core::users::totp::{TotpEnrollment, TwoFactorStatus, UserTotp};
 use bichon_core::users::view::UserView;
 use bichon_core::users::UserModel;
+use bichon_core::utils::rate_limit::lockout::{LoginLockout, LOGIN_GUARD};
 use poem::web::Path;
 use poem_openapi::payload::Json;
 use poem_openapi::OpenApi;
@@ -224,13 +229,8 @@ impl UsersApi {
         &self,
         context: WrappedContext,
     ) -> ApiResult<Json<Vec<MinimalUser>>> {
-        let is_admin = context.user.is_admin();
-        let minimal_list = MinimalUser::list_all()?;
-        if is_admin {
-            return Ok(Json(minimal_list));
-        }
         context.require_permission(None, Permission::USER_VIEW)?;
-        Ok(Json(minimal_list))
+        Ok(Json(MinimalUser::list_all()?))
     }
 
     #[oai(
@@ -247,4 +247,149 @@ impl UsersApi {
                 .collect(),
         ))
     }
+
+    /// Two-factor status of the current user
+    #[oai(
+        path = "/two-factor",
+        method = "get",
+        operation_id = "get_two_factor_status"
+    )]
+    async fn get_two_factor_status(
+        &self,
+        context: WrappedContext,
+    ) -> ApiResult<Json<TwoFactorStatus>> {
+        Ok(Json(UserTotp::status(&context.user)?))
+    }
+
+    /// Start TOTP enrollment for the current user
+    #[oai(
+        path = "/two-factor/enroll",
+        method = "post",
+        operation_id = "enroll_two_factor"
+    )]
+    async fn enroll_two_factor(&self, context: WrappedContext) -> ApiResult<Json<TotpEnrollment>> {
+        Ok(Json(UserTotp::begin_enrollment(&context.user)?))
+    }
+
+    /// Finish TOTP enrollment with a code from the authenticator app.
+    /// Returns the recovery codes; they are not shown again.
+    #[oai(
+        path = "/two-factor/confirm",
+        method = "post",
+        operation_id = "confirm_two_factor"
+    )]
+    async fn confirm_two_factor(
+        &self,
+        payload: Json<TwoFactorCodeRequest>,
+        context: WrappedContext,
+    ) -> ApiResult<Json<Vec<String>>> {
+        let codes =
+            UserTotp::confirm_enrollment(context.user.id, &payload.0.code)?.ok_or_else(|| {
+                raise_error!(
+                    "Invalid two-factor code.".into(),
+                    ErrorCode::InvalidParameter
+                )
+            })?;
+        emit(Event::TwoFactorEnabled {
+            user: context.user.username.clone(),
+        });
+        Ok(Json(codes))
+    }
+
+    /// Replace the current user's recovery codes
+    #[oai(
+        path = "/two-factor/recovery-codes",
+        method = "post",
+        operation_id = "regenerate_recovery_codes"
+    )]
+    async fn regenerate_recovery_codes(
+        &self,
+        payload: Json<TwoFactorCodeRequest>,
+        context: WrappedContext,
+    ) -> ApiResult<Json<Vec<String>>> {
+        Ok(Json(UserTotp::regenerate_recovery_codes(
+            context.user.id,
+            &payload.0.code,
+        )?))
+    }
+
+    /// Turn off two-factor authentication for the current user
+    #[oai(
+        path = "/two-factor/disable",
+        method = "post",
+        operation_id = "disable_two_factor"
+    )]
+    async fn disable_two_factor(
+        &self,
+        payload: Json<TwoFactorCodeRequest>,
+        context: WrappedContext,
+    ) -> ApiResult<()> {
+        UserTotp::disable(&context.user, &payload.0.code)?;
+        emit(Event::TwoFactorDisabled {
+            user: context.user.username.clone(),
+            target_user: context.user.username.clone(),
+        });
+        Ok(())
+    }
+
+    /// Reset another user's two-factor enrollment (e.g. a lost device)
+    #[oai(
+        path = "/users/:id/two-factor",
+        method = "delete",
+        operation_id = "reset_user_two_factor"
+    )]
+    async fn reset_user_two_factor(
+        &self,
+        /// The User ID whose two-factor enrollment is removed
+        id: Path<u64>,
+        context: WrappedContext,
+    ) -> ApiResult<()> {
+        let id = id.0;
+        context.require_permission(None, Permission::USER_MANAGE)?;
+        let target_username = UserModel::find(id)?
+            .map(|u| u.username)
+            .unwrap_or_else(|| format!("user-{id}"));
+        UserTotp::reset(id)?;
+        emit(Event::TwoFactorDisabled {
+            user: context.user.username.clone(),
+            target_user: target_username,
+        });
+        Ok(())
+    }
+
+    /// List usernames and client IPs with recent failed login attempts
+    #[oai(
+        path = "/login-lockouts",
+        method = "get",
+        operation_id = "list_login_lockouts"
+    )]
+    async fn list_login_lockouts(
+        &self,
+        context: WrappedContext,
+    ) -> ApiResult<Json<Vec<LoginLockout>>> {
+        context.require_permission(None, Permission::USER_MANAGE)?;
+        Ok(Json(LOGIN_GUARD.list()))
+    }
+
+    /// Clear the failed-login counter of a username or client IP
+    #[oai(
+        path = "/login-lockouts/:subject",
+        method = "delete",
+        operation_id = "clear_login_lockout"
+    )]
+    async fn clear_login_lockout(
+        &self,
+        /// The username or IP address to unlock
+        subject: Path<String>,
+        context: WrappedContext,
+    ) -> ApiResult<()> {
+        context.require_permission(None, Permission::USER_MANAGE)?;
+        if !LOGIN_GUARD.clear(&subject.0) {
+            return Err(raise_error!(
+                format!("No failed logins recorded for '{}'.", subject.0),
+                ErrorCode::ResourceNotFound
+            ))?;
+        }
+        Ok(())
+    }
 }
@@ -23,7 +23,9 @@ use crate::common::tls::rustls_config;
 use crate::common::timeout::{Timeout, TIMEOUT_HEADER};
 use crate::error::handler::error_handler;
 use crate::rest::public::features::get_features;
+use crate::rest::public::inline_attachment::get_inline_attachment;
 use crate::rest::public::login::login;
+use crate::rest::public::remote_content::get_remote_content;
 use crate::rest::public::status::get_status;
 use bichon_core::common::signal::SIGNAL_MANAGER;
 use bichon_core::error::code::ErrorCode;
@@ -114,6 +116,11 @@ pub fn build_routes() -> impl Endpoint {
         .nest("/api/v1/features", get(get_features))
         .nest("/api/status", get(get_status))
         .nest("/api/login", post(login))
+        .at(
+            "/api/inline-attachment/:account_id/:envelope_id",
+            get(get_inline_attachment),
+        )
+        .nest("/api/remote-content", get(get_remote_content))
         .nest_no_strip("/api/v1", open_api_route);
 
     let app_logic = add_web_assets(app_logic);
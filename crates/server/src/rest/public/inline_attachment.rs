@@ -0,0 +1,81 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use bichon_core::error::code::ErrorCode;
+use bichon_core::message::attachment::retrieve_inline_attachment;
+use bichon_core::message::content::verify_inline_attachment_url;
+use http::{header, StatusCode};
+use poem::web::{Path, Query};
+use poem::{handler, IntoResponse, Response};
+use serde::Deserialize;
+use tracing::{error, warn};
+
+#[derive(Deserialize)]
+pub struct InlineAttachmentParams {
+    content_hash: String,
+    nested_content_hash: Option<String>,
+    expires: i64,
+    signature: String,
+}
+
+/// Serves an inline part (typically a `cid:` image) referenced from a
+/// sanitized message body.
+///
+/// Access is granted by the signed, expiring URL issued with the message
+/// content rather than by an access token, since the browser requests these
+/// from inside the rendered message.
+#[handler]
+pub async fn get_inline_attachment(
+    Path((account_id, envelope_id)): Path<(u64, String)>,
+    Query(params): Query<InlineAttachmentParams>,
+) -> Response {
+    let nested_content_hash = params.nested_content_hash.as_deref();
+    if let Err(e) = verify_inline_attachment_url(
+        account_id,
+        &envelope_id,
+        &params.content_hash,
+        nested_content_hash,
+        params.expires,
+        &params.signature,
+    ) {
+        warn!("Rejected inline attachment request: {}", e);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match retrieve_inline_attachment(
+        account_id,
+        envelope_id,
+        &params.content_hash,
+        nested_content_hash,
+    ) {
+        Ok((content_type, bytes)) => Response::builder()
+            .content_type(content_type)
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            .header(
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; sandbox",
+            )
+            .header(header::CACHE_CONTROL, "private, max-age=3600")
+            .body(bytes),
+        Err(e) if e.code() == ErrorCode::ResourceNotFound => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            error!("Failed to load inline attachment: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
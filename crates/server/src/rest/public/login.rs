@@ -18,55 +18,83 @@
 
 use bichon_core::ext::event_bus::{emit, Event};
 use bichon_core::token::AccessTokenModel;
-use bichon_core::users::UserModel;
+use bichon_core::users::{LoginResult, UserModel};
+use bichon_core::utils::rate_limit::lockout::LOGIN_GUARD;
 use poem::web::{Json, RealIp};
 use poem::{handler, FromRequest, IntoResponse, Request, Response};
 use serde::Deserialize;
-use tracing::error;
+use tracing::{error, warn};
 
 #[derive(Deserialize)]
 pub struct LoginPayload {
     pub username: String,
     pub password: String,
+    /// TOTP or recovery code, for users with two-factor authentication.
+    #[serde(default)]
+    pub totp_code: Option<String>,
 }
 
 /// Login endpoint
 ///
 /// Accepts a plain text password and returns the `root_token`
-/// on successful authentication.
+/// on successful authentication. Failed attempts are throttled per
+/// username and client IP.
 #[handler]
 pub async fn login(payload: Json<LoginPayload>, req: &Request) -> Response {
     let login_username = payload.0.username.clone();
-    match UserModel::authenticate_user(payload.0.username, payload.0.password) {
+    let ip = RealIp::from_request_without_body(req)
+        .await
+        .ok()
+        .and_then(|r| r.0);
+
+    // Throttle the account itself, whether it was named by username or email.
+    let subject = UserModel::find_by_login(&login_username)
+        .ok()
+        .flatten()
+        .map_or_else(|| login_username.clone(), |u| u.username);
+
+    if let Err(e) = LOGIN_GUARD.check(&subject, ip) {
+        warn!("Login for '{}' refused: {}", login_username, e);
+        return json_response(
+            http::StatusCode::TOO_MANY_REQUESTS,
+            &LoginResult {
+                success: false,
+                error_message: Some(e.to_string()),
+                ..Default::default()
+            },
+        );
+    }
+
+    let code_submitted = payload
+        .0
+        .totp_code
+        .as_deref()
+        .is_some_and(|c| !c.trim().is_empty());
+    match UserModel::authenticate_user(payload.0.username, payload.0.password, payload.0.totp_code)
+    {
         Ok(result) => {
-            // Audit: record the successful login (user + client IP).
-            let username = result
-                .access_token
-                .as_deref()
-                .and_then(|t| AccessTokenModel::resolve_user_from_token(t).ok())
-                .map(|u| u.username)
-                .unwrap_or(login_username);
-            let ip = RealIp::from_request_without_body(req)
-                .await
-                .ok()
-                .and_then(|r| r.0);
-            if let Some(ip) = ip {
-                emit(Event::UserLoggedIn {
-                    user: username,
+            if result.success {
+                LOGIN_GUARD.record_success(&subject);
+                // Audit: record the successful login (user + client IP).
+                let username = result
+                    .access_token
+                    .as_deref()
+                    .and_then(|t| AccessTokenModel::resolve_user_from_token(t).ok())
+                    .map(|u| u.username)
+                    .unwrap_or(login_username);
+                if let Some(ip) = ip {
+                    emit(Event::UserLoggedIn { user: username, ip });
+                }
+            } else if is_rejected_credential(&result, code_submitted) {
+                LOGIN_GUARD.record_failure(
+                    &subject,
+                    &login_username,
                     ip,
-                });
-            }
-            match serde_json::to_string(&result) {
-                Ok(json_string) => Response::builder()
-                    .status(http::StatusCode::OK)
-                    .content_type("application/json")
-                    .body(json_string)
-                    .into_response(),
-                Err(_) => Response::builder()
-                    .status(http::StatusCode::INTERNAL_SERVER_ERROR)
-                    .body("Internal server error during response serialization.")
-                    .into_response(),
+                    "webui",
+                    result.error_message.as_deref().unwrap_or("login failed"),
+                );
             }
+            json_response(http::StatusCode::OK, &result)
         }
         Err(e) => {
             error!("Authentication failed with system error: {:?}", e);
@@ -77,3 +105,26 @@ pub async fn login(payload: Json<LoginPayload>, req: &Request) -> Response {
         }
     }
 }
+
+/// A two-factor prompt (password correct, no code sent yet) or an
+/// enrollment challenge is not a failed attempt; everything else is.
+fn is_rejected_credential(result: &LoginResult, code_submitted: bool) -> bool {
+    if result.two_factor_enrollment.is_some() {
+        return false;
+    }
+    !result.two_factor_required || code_submitted
+}
+
+fn json_response(status: http::StatusCode, result: &LoginResult) -> Response {
+    match serde_json::to_string(result) {
+        Ok(json_string) => Response::builder()
+            .status(status)
+            .content_type("application/json")
+            .body(json_string)
+            .into_response(),
+        Err(_) => Response::builder()
+            .status(http::StatusCode::INTERNAL_SERVER_ERROR)
+            .body("Internal server error during response serialization.")
+            .into_response(),
+    }
+}
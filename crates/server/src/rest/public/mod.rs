@@ -18,6 +18,8 @@
 
 
 pub mod features;
+pub mod inline_attachment;
 pub mod login;
 pub mod oauth2;
+pub mod remote_content;
 pub mod status;
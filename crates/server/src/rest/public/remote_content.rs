@@ -0,0 +1,68 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use bichon_core::error::code::ErrorCode;
+use bichon_core::message::remote::{fetch_remote_content, verify_remote_content_url};
+use http::{header, StatusCode};
+use poem::web::Query;
+use poem::{handler, IntoResponse, Response};
+use serde::Deserialize;
+use tracing::warn;
+
+#[derive(Deserialize)]
+pub struct RemoteContentParams {
+    url: String,
+    expires: i64,
+    signature: String,
+}
+
+/// Serves a remote image referenced from a message body, fetched and cached
+/// by the server so the reviewer's browser never contacts the remote host.
+///
+/// Like inline attachments, access is granted by the signed, expiring URL
+/// issued with the message content.
+#[handler]
+pub async fn get_remote_content(Query(params): Query<RemoteContentParams>) -> Response {
+    if let Err(e) = verify_remote_content_url(&params.url, params.expires, &params.signature) {
+        warn!("Rejected remote content request: {}", e);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match fetch_remote_content(&params.url).await {
+        Ok(resource) => Response::builder()
+            .content_type(resource.content_type)
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            .header(
+                header::CONTENT_SECURITY_POLICY,
+                "default-src 'none'; sandbox",
+            )
+            .header(header::REFERRER_POLICY, "no-referrer")
+            .header(header::CACHE_CONTROL, "private, max-age=86400")
+            .body(resource.data),
+        Err(e) => {
+            warn!("Failed to proxy remote content {}: {}", params.url, e);
+            match e.code() {
+                ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorCode::InvalidParameter => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+                _ => StatusCode::BAD_GATEWAY,
+            }
+            .into_response()
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{future::Future, net::IpAddr, pin::Pin};
+
+use bichon_core::{
+    account::migration::AccountModel,
+    common::auth::ClientContext,
+    error::BichonResult,
+    smtp::spool::SMTP_SPOOL,
+    token::AccessTokenModel,
+    users::permissions::Permission,
+    utils::rate_limit::lockout::{LoginGuard, LOGIN_GUARD},
+};
+
+pub type DeliveryFuture = Pin<Box<dyn Future<Output = BichonResult<String>> + Send>>;
+
+/// Everything the SMTP session needs from the rest of Bichon. The protocol
+/// state machine only talks to this trait, so it can be exercised without
+/// a database or spool.
+pub trait SmtpBackend: Send + Sync {
+    /// Resolve the user and token scope behind an `AUTH` secret (an API
+    /// access token). `username` is the name the client sent; it is only
+    /// used, together with `ip`, to throttle repeated failures.
+    fn authenticate(
+        &self,
+        username: &str,
+        secret: &str,
+        ip: Option<IpAddr>,
+    ) -> BichonResult<ClientContext>;
+
+    /// Look up the Bichon account a `RCPT TO` address belongs to.
+    fn find_account(&self, address: &str) -> BichonResult<Option<AccountModel>>;
+
+    /// Whether the authenticated client may ingest mail into `account`.
+    fn can_ingest(&self, context: &ClientContext, account: &AccountModel) -> bool;
+
+    /// Durably accept a message for `account`, returning its queue id.
+    fn deliver(
+        &self,
+        account: AccountModel,
+        mail_from: Option<String>,
+        data: Vec<u8>,
+    ) -> DeliveryFuture;
+}
+
+/// The production backend: API tokens, memdb accounts and the disk spool.
+pub struct ArchiveBackend;
+
+impl SmtpBackend for ArchiveBackend {
+    fn authenticate(
+        &self,
+        username: &str,
+        secret: &str,
+        ip: Option<IpAddr>,
+    ) -> BichonResult<ClientContext> {
+        // The token alone picks the account, so the name is untrusted: count
+        // failures per name and address, and on success only clear the
+        // counter of the account that owns the token.
+        let subject = LoginGuard::scoped_subject(username, ip);
+        LOGIN_GUARD.check(&subject, ip)?;
+        match AccessTokenModel::resolve_context_from_token(secret, ip) {
+            Ok(context) => {
+                LOGIN_GUARD.record_success(&LoginGuard::scoped_subject(&context.user.username, ip));
+                Ok(context)
+            }
+            Err(e) => {
+                LOGIN_GUARD.record_failure(&subject, username, ip, "smtp", &e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    fn find_account(&self, address: &str) -> BichonResult<Option<AccountModel>> {
+        AccountModel::find_by_email(address)
+    }
+
+    fn can_ingest(&self, context: &ClientContext, account: &AccountModel) -> bool {
+        context.has_permission(Some(account.id), Permission::DATA_SMTP_INGEST)
+    }
+
+    fn deliver(
+        &self,
+        account: AccountModel,
+        mail_from: Option<String>,
+        data: Vec<u8>,
+    ) -> DeliveryFuture {
+        Box::pin(async move { SMTP_SPOOL.enqueue(&account, mail_from, data).await })
+    }
+}
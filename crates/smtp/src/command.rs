@@ -0,0 +1,310 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+/// Declared body type of a message (`BODY=` parameter, RFC 6152).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyType {
+    #[default]
+    SevenBit,
+    EightBitMime,
+}
+
+/// ESMTP parameters accepted on `MAIL FROM`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MailParams {
+    /// Declared message size (`SIZE=`, RFC 1870).
+    pub size: Option<usize>,
+    pub body: BodyType,
+    /// The client requested internationalized handling (RFC 6531).
+    pub smtputf8: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Ehlo(String),
+    Helo(String),
+    StartTls,
+    Auth {
+        mechanism: String,
+        initial_response: Option<String>,
+    },
+    Mail {
+        address: String,
+        params: MailParams,
+    },
+    Rcpt {
+        address: String,
+    },
+    Data,
+    /// `BDAT <size> [LAST]` (RFC 3030). The chunk payload follows the
+    /// command line immediately and is read by the session.
+    Bdat {
+        size: usize,
+        last: bool,
+    },
+    Rset,
+    Noop,
+    Quit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    /// Unknown verb.
+    Unrecognized,
+    /// Known verb with malformed arguments.
+    Syntax(&'static str),
+    /// A `MAIL`/`RCPT` parameter we do not implement.
+    UnsupportedParameter(String),
+}
+
+impl CommandError {
+    pub fn reply(&self) -> String {
+        match self {
+            CommandError::Unrecognized => "500 5.5.2 Command not recognized\r\n".into(),
+            CommandError::Syntax(msg) => format!("501 5.5.4 Syntax error: {msg}\r\n"),
+            CommandError::UnsupportedParameter(param) => {
+                format!("555 5.5.4 Unsupported parameter: {param}\r\n")
+            }
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, CommandError> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (verb, args) = match line.split_once(' ') {
+            Some((verb, args)) => (verb, args.trim()),
+            None => (line, ""),
+        };
+
+        match verb.to_ascii_uppercase().as_str() {
+            "EHLO" => Ok(Command::Ehlo(required_domain(args)?)),
+            "HELO" => Ok(Command::Helo(required_domain(args)?)),
+            "STARTTLS" => no_args(args, Command::StartTls),
+            "AUTH" => {
+                let mut parts = args.split_whitespace();
+                let mechanism = parts
+                    .next()
+                    .ok_or(CommandError::Syntax("AUTH mechanism required"))?
+                    .to_ascii_uppercase();
+                let initial_response = parts.next().map(String::from);
+                Ok(Command::Auth {
+                    mechanism,
+                    initial_response,
+                })
+            }
+            "MAIL" => {
+                let rest = strip_keyword(args, "FROM:")
+                    .ok_or(CommandError::Syntax("expected MAIL FROM:<address>"))?;
+                let (address, params) = split_path(rest)?;
+                Ok(Command::Mail {
+                    address,
+                    params: parse_mail_params(params)?,
+                })
+            }
+            "RCPT" => {
+                let rest = strip_keyword(args, "TO:")
+                    .ok_or(CommandError::Syntax("expected RCPT TO:<address>"))?;
+                let (address, params) = split_path(rest)?;
+                if address.is_empty() {
+                    return Err(CommandError::Syntax("empty recipient"));
+                }
+                if let Some(param) = params.split_whitespace().next() {
+                    return Err(CommandError::UnsupportedParameter(param.to_string()));
+                }
+                Ok(Command::Rcpt { address })
+            }
+            "DATA" => no_args(args, Command::Data),
+            "BDAT" => {
+                let mut parts = args.split_whitespace();
+                let size = parts
+                    .next()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .ok_or(CommandError::Syntax("BDAT requires a chunk size"))?;
+                let last = match parts.next() {
+                    None => false,
+                    Some(flag) if flag.eq_ignore_ascii_case("LAST") => true,
+                    Some(_) => return Err(CommandError::Syntax("expected BDAT <size> [LAST]")),
+                };
+                if parts.next().is_some() {
+                    return Err(CommandError::Syntax("expected BDAT <size> [LAST]"));
+                }
+                Ok(Command::Bdat { size, last })
+            }
+            "RSET" => no_args(args, Command::Rset),
+            // NOOP may carry an ignored string argument.
+            "NOOP" => Ok(Command::Noop),
+            "QUIT" => no_args(args, Command::Quit),
+            _ => Err(CommandError::Unrecognized),
+        }
+    }
+}
+
+fn required_domain(args: &str) -> Result<String, CommandError> {
+    args.split_whitespace()
+        .next()
+        .map(String::from)
+        .ok_or(CommandError::Syntax("domain or address literal required"))
+}
+
+fn no_args(args: &str, command: Command) -> Result<Command, CommandError> {
+    if args.is_empty() {
+        Ok(command)
+    } else {
+        Err(CommandError::Syntax("no arguments allowed"))
+    }
+}
+
+/// Case-insensitively strip `FROM:` / `TO:`, tolerating the space some
+/// clients put after the colon.
+fn strip_keyword<'a>(args: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = args.get(..keyword.len())?;
+    head.eq_ignore_ascii_case(keyword)
+        .then(|| args[keyword.len()..].trim_start())
+}
+
+/// Split `<address> PARAM=value ...` into the address and the parameter
+/// string. A bare address without angle brackets is accepted as well.
+fn split_path(rest: &str) -> Result<(String, &str), CommandError> {
+    if let Some(inner) = rest.strip_prefix('<') {
+        let end = inner
+            .find('>')
+            .ok_or(CommandError::Syntax("unterminated address"))?;
+        let address = inner[..end].trim();
+        // Drop a source route (`<@a,@b:user@host>`), RFC 5321 §4.1.2.
+        let address = if address.starts_with('@') {
+            address.split_once(':').map(|(_, a)| a).unwrap_or("")
+        } else {
+            address
+        };
+        Ok((address.to_string(), inner[end + 1..].trim()))
+    } else {
+        let (address, params) = rest.split_once(' ').unwrap_or((rest, ""));
+        Ok((address.to_string(), params.trim()))
+    }
+}
+
+fn parse_mail_params(params: &str) -> Result<MailParams, CommandError> {
+    let mut parsed = MailParams::default();
+    for param in params.split_whitespace() {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        match key.to_ascii_uppercase().as_str() {
+            "SIZE" => {
+                parsed.size = Some(
+                    value
+                        .parse()
+                        .map_err(|_| CommandError::Syntax("invalid SIZE value"))?,
+                );
+            }
+            "BODY" => {
+                parsed.body = match value.to_ascii_uppercase().as_str() {
+                    "7BIT" => BodyType::SevenBit,
+                    "8BITMIME" => BodyType::EightBitMime,
+                    _ => return Err(CommandError::UnsupportedParameter(param.to_string())),
+                };
+            }
+            "SMTPUTF8" if value.is_empty() => parsed.smtputf8 = true,
+            // RFC 4954 §5: the submitter identity is informational only.
+            "AUTH" => {}
+            _ => return Err(CommandError::UnsupportedParameter(param.to_string())),
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mail_from_with_parameters() {
+        let cmd = Command::parse("mail from:<a@example.com> SIZE=1024 BODY=8BITMIME SMTPUTF8\r\n")
+            .unwrap();
+        assert_eq!(
+            cmd,
+            Command::Mail {
+                address: "a@example.com".into(),
+                params: MailParams {
+                    size: Some(1024),
+                    body: BodyType::EightBitMime,
+                    smtputf8: true,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn accepts_null_sender_and_space_after_colon() {
+        assert!(matches!(
+            Command::parse("MAIL FROM: <>").unwrap(),
+            Command::Mail { address, .. } if address.is_empty()
+        ));
+    }
+
+    #[test]
+    fn strips_source_route() {
+        assert_eq!(
+            Command::parse("RCPT TO:<@relay.example:user@example.com>").unwrap(),
+            Command::Rcpt {
+                address: "user@example.com".into()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_parameters() {
+        assert_eq!(
+            Command::parse("MAIL FROM:<a@example.com> BODY=BINARYMIME"),
+            Err(CommandError::UnsupportedParameter("BODY=BINARYMIME".into()))
+        );
+        assert_eq!(
+            Command::parse("RCPT TO:<a@example.com> NOTIFY=NEVER"),
+            Err(CommandError::UnsupportedParameter("NOTIFY=NEVER".into()))
+        );
+    }
+
+    #[test]
+    fn parses_bdat() {
+        assert_eq!(
+            Command::parse("BDAT 86 LAST").unwrap(),
+            Command::Bdat {
+                size: 86,
+                last: true
+            }
+        );
+        assert_eq!(
+            Command::parse("bdat 0").unwrap(),
+            Command::Bdat {
+                size: 0,
+                last: false
+            }
+        );
+        assert!(Command::parse("BDAT").is_err());
+        assert!(Command::parse("BDAT 10 FIRST").is_err());
+    }
+
+    #[test]
+    fn keeps_unicode_addresses_intact() {
+        assert_eq!(
+            Command::parse("RCPT TO:<用户@例子.广告>").unwrap(),
+            Command::Rcpt {
+                address: "用户@例子.广告".into()
+            }
+        );
+    }
+}
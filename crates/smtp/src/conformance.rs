@@ -0,0 +1,482 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Protocol conformance tests. The session runs over an in-memory duplex
+//! stream against a recording backend, so no database, spool or sockets
+//! are involved.
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use bichon_core::account::migration::{AccountModel, AccountType};
+use bichon_core::common::auth::ClientContext;
+use bichon_core::error::{code::ErrorCode, BichonResult};
+use bichon_core::raise_error;
+use bichon_core::token::TokenScope;
+use bichon_core::users::UserModel;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf};
+use tokio::task::JoinHandle;
+
+use crate::backend::{DeliveryFuture, SmtpBackend};
+use crate::server::{handle_connection, SmtpConfig};
+
+const TOKEN: &str = "test-token";
+/// Username the backend treats as locked out after failed attempts.
+const LOCKED_USER: &str = "locked";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Delivery {
+    rcpt: String,
+    mail_from: Option<String>,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct RecordingBackend {
+    deliveries: Mutex<Vec<Delivery>>,
+}
+
+impl RecordingBackend {
+    fn recorded(&self) -> Vec<Delivery> {
+        self.deliveries.lock().unwrap().clone()
+    }
+}
+
+impl SmtpBackend for RecordingBackend {
+    fn authenticate(
+        &self,
+        username: &str,
+        secret: &str,
+        _ip: Option<IpAddr>,
+    ) -> BichonResult<ClientContext> {
+        if username == LOCKED_USER {
+            Err(raise_error!(
+                "too many failed attempts".into(),
+                ErrorCode::TooManyRequest
+            ))
+        } else if secret == TOKEN {
+            Ok(ClientContext {
+                ip_addr: None,
+                user: UserModel::default(),
+                scope: TokenScope::default(),
+            })
+        } else {
+            Err(raise_error!(
+                "invalid token".into(),
+                ErrorCode::PermissionDenied
+            ))
+        }
+    }
+
+    fn find_account(&self, address: &str) -> BichonResult<Option<AccountModel>> {
+        let account_type = match address {
+            "archive@bichon.local" | "用户@例子.广告" => AccountType::NoSync,
+            "imap@bichon.local" => AccountType::IMAP,
+            _ => return Ok(None),
+        };
+        Ok(Some(AccountModel {
+            id: 1,
+            email: address.to_string(),
+            account_type,
+            ..Default::default()
+        }))
+    }
+
+    fn can_ingest(&self, _context: &ClientContext, _account: &AccountModel) -> bool {
+        true
+    }
+
+    fn deliver(
+        &self,
+        account: AccountModel,
+        mail_from: Option<String>,
+        data: Vec<u8>,
+    ) -> DeliveryFuture {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        deliveries.push(Delivery {
+            rcpt: account.email,
+            mail_from,
+            data,
+        });
+        let id = format!("q{}", deliveries.len());
+        Box::pin(async move { Ok(id) })
+    }
+}
+
+struct Client {
+    reader: BufReader<ReadHalf<DuplexStream>>,
+    writer: WriteHalf<DuplexStream>,
+    backend: Arc<RecordingBackend>,
+    server: JoinHandle<std::io::Result<()>>,
+}
+
+impl Client {
+    async fn connect(auth_required: bool) -> Self {
+        let backend = Arc::new(RecordingBackend::default());
+        let config = SmtpConfig {
+            auth_required,
+            backend: backend.clone(),
+            ..Default::default()
+        };
+        let (client, server) = tokio::io::duplex(256 * 1024);
+        let server = tokio::spawn(handle_connection(server, config, None));
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = Self {
+            reader: BufReader::new(reader),
+            writer,
+            backend,
+            server,
+        };
+        assert!(client.reply().await.starts_with("220 "));
+        client
+    }
+
+    /// Connect and greet, leaving the session ready for a transaction.
+    async fn ready() -> Self {
+        let mut client = Self::connect(false).await;
+        assert!(client.command("EHLO client.test").await.starts_with("250-"));
+        client
+    }
+
+    async fn send(&mut self, data: impl AsRef<[u8]>) {
+        self.writer.write_all(data.as_ref()).await.unwrap();
+        self.writer.flush().await.unwrap();
+    }
+
+    /// Read one complete, possibly multi-line, reply.
+    async fn reply(&mut self) -> String {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            let n = self.reader.read_line(&mut line).await.unwrap();
+            assert!(n > 0, "connection closed while waiting for a reply");
+            reply.push_str(&line);
+            if line.as_bytes().get(3) == Some(&b' ') {
+                return reply;
+            }
+        }
+    }
+
+    async fn command(&mut self, line: &str) -> String {
+        self.send(format!("{line}\r\n")).await;
+        self.reply().await
+    }
+}
+
+fn code(reply: &str) -> &str {
+    &reply[..3]
+}
+
+#[tokio::test]
+async fn ehlo_advertises_extensions() {
+    let mut client = Client::connect(false).await;
+    let reply = client.command("EHLO client.test").await;
+    for keyword in [
+        "PIPELINING",
+        "SIZE 52428800",
+        "8BITMIME",
+        "SMTPUTF8",
+        "CHUNKING",
+        "AUTH PLAIN LOGIN",
+    ] {
+        assert!(
+            reply.lines().any(|l| &l[4..] == keyword),
+            "missing {keyword} in {reply}"
+        );
+    }
+    // No TLS acceptor configured, so STARTTLS must not be offered.
+    assert!(!reply.contains("STARTTLS"));
+    assert!(reply.ends_with("250 OK\r\n"));
+
+    let reply = client.command("HELO client.test").await;
+    assert_eq!(reply, "250 Bichon Hello client.test\r\n");
+}
+
+#[tokio::test]
+async fn enforces_command_sequence() {
+    let mut client = Client::connect(false).await;
+    assert_eq!(code(&client.command("MAIL FROM:<a@test>").await), "503");
+    assert!(client.command("EHLO client.test").await.starts_with("250-"));
+    assert_eq!(
+        code(&client.command("RCPT TO:<archive@bichon.local>").await),
+        "503"
+    );
+    assert_eq!(code(&client.command("DATA").await), "503");
+    assert_eq!(code(&client.command("MAIL FROM:<a@test>").await), "250");
+    assert_eq!(code(&client.command("MAIL FROM:<a@test>").await), "503");
+    assert_eq!(code(&client.command("DATA").await), "503");
+    assert_eq!(
+        code(&client.command("RCPT TO:<archive@bichon.local>").await),
+        "250"
+    );
+    assert_eq!(
+        code(&client.command("RCPT TO:<archive@bichon.local>").await),
+        "452"
+    );
+    assert_eq!(code(&client.command("RSET").await), "250");
+    assert_eq!(code(&client.command("DATA").await), "503");
+    assert!(client.backend.recorded().is_empty());
+}
+
+#[tokio::test]
+async fn rejects_unknown_commands_and_parameters() {
+    let mut client = Client::ready().await;
+    assert_eq!(code(&client.command("VRFY someone").await), "500");
+    assert_eq!(
+        code(&client.command("MAIL FROM:<a@test> RET=HDRS").await),
+        "555"
+    );
+    assert_eq!(code(&client.command("MAIL FROM:<a@test").await), "501");
+    let long = format!("NOOP {}", "x".repeat(10_000));
+    assert_eq!(code(&client.command(&long).await), "500");
+    assert_eq!(code(&client.command("NOOP").await), "250");
+}
+
+#[tokio::test]
+async fn pipelined_commands_are_answered_in_order() {
+    let mut client = Client::connect(false).await;
+    client
+        .send(
+            "EHLO client.test\r\n\
+             MAIL FROM:<sender@test> BODY=8BITMIME\r\n\
+             RCPT TO:<nobody@bichon.local>\r\n\
+             RSET\r\n\
+             MAIL FROM:<sender@test>\r\n\
+             RCPT TO:<archive@bichon.local>\r\n\
+             DATA\r\n",
+        )
+        .await;
+    assert!(client.reply().await.starts_with("250-"));
+    assert_eq!(code(&client.reply().await), "250");
+    assert_eq!(code(&client.reply().await), "550");
+    assert_eq!(code(&client.reply().await), "250");
+    assert_eq!(code(&client.reply().await), "250");
+    assert_eq!(code(&client.reply().await), "250");
+    assert_eq!(code(&client.reply().await), "354");
+
+    client
+        .send("Subject: pipelined\r\n\r\nbody\r\n.\r\nNOOP\r\nQUIT\r\n")
+        .await;
+    assert_eq!(client.reply().await, "250 2.0.0 OK: queued as q1\r\n");
+    assert_eq!(code(&client.reply().await), "250");
+    assert_eq!(code(&client.reply().await), "221");
+    client.server.await.unwrap().unwrap();
+
+    assert_eq!(
+        client.backend.recorded(),
+        vec![Delivery {
+            rcpt: "archive@bichon.local".into(),
+            mail_from: Some("sender@test".into()),
+            data: b"Subject: pipelined\r\n\r\nbody\r\n".to_vec(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn data_is_dot_unstuffed() {
+    let mut client = Client::ready().await;
+    client.command("MAIL FROM:<>").await;
+    client.command("RCPT TO:<archive@bichon.local>").await;
+    assert_eq!(code(&client.command("DATA").await), "354");
+    client.send("..leading dot\r\n .\r\n.\r\n").await;
+    assert_eq!(code(&client.reply().await), "250");
+
+    let delivery = client.backend.recorded().remove(0);
+    assert_eq!(delivery.mail_from, Some(String::new()));
+    assert_eq!(delivery.data, b".leading dot\r\n .\r\n");
+}
+
+#[tokio::test]
+async fn eof_during_data_is_not_delivered() {
+    let mut client = Client::ready().await;
+    client.command("MAIL FROM:<a@test>").await;
+    client.command("RCPT TO:<archive@bichon.local>").await;
+    client.command("DATA").await;
+    client.send("Subject: truncated\r\n").await;
+    client.writer.shutdown().await.unwrap();
+
+    assert!(client.server.await.unwrap().is_err());
+    assert!(client.backend.recorded().is_empty());
+}
+
+#[tokio::test]
+async fn bdat_chunks_are_concatenated_verbatim() {
+    let mut client = Client::ready().await;
+    client.command("MAIL FROM:<a@test>").await;
+    client.command("RCPT TO:<archive@bichon.local>").await;
+
+    // Binary-safe: no dot-stuffing, and a bare "." line is just content.
+    let first = b"Subject: chunked\r\n\r\n.\r\n";
+    let second = b"\x00\xffrest";
+    client.send(format!("BDAT {}\r\n", first.len())).await;
+    client.send(first).await;
+    assert_eq!(
+        client.reply().await,
+        format!("250 2.0.0 {} octets received\r\n", first.len())
+    );
+    assert_eq!(code(&client.command("DATA").await), "503");
+
+    let mut pipelined = format!("BDAT {} LAST\r\n", second.len()).into_bytes();
+    pipelined.extend_from_slice(second);
+    pipelined.extend_from_slice(b"NOOP\r\n");
+    client.send(pipelined).await;
+    assert_eq!(client.reply().await, "250 2.0.0 OK: queued as q1\r\n");
+    assert_eq!(code(&client.reply().await), "250");
+
+    let mut expected = first.to_vec();
+    expected.extend_from_slice(second);
+    assert_eq!(client.backend.recorded()[0].data, expected);
+}
+
+#[tokio::test]
+async fn rejected_bdat_payload_is_still_consumed() {
+    let mut client = Client::ready().await;
+    // Without a transaction the payload must not be read as commands.
+    client.send("BDAT 6 LAST\r\nQUIT\r\n").await;
+    assert_eq!(code(&client.reply().await), "503");
+    assert_eq!(code(&client.command("NOOP").await), "250");
+    assert!(client.backend.recorded().is_empty());
+}
+
+#[tokio::test]
+async fn oversized_messages_are_rejected() {
+    let mut client = Client::ready().await;
+    assert_eq!(
+        code(&client.command("MAIL FROM:<a@test> SIZE=52428801").await),
+        "552"
+    );
+
+    client.command("MAIL FROM:<a@test>").await;
+    client.command("RCPT TO:<archive@bichon.local>").await;
+    let size = 50 * 1024 * 1024 + 1;
+    client.send(format!("BDAT {size} LAST\r\n")).await;
+    let block = vec![b'x'; 64 * 1024];
+    let mut remaining = size;
+    while remaining > 0 {
+        let n = remaining.min(block.len());
+        client.writer.write_all(&block[..n]).await.unwrap();
+        remaining -= n;
+    }
+    client.writer.flush().await.unwrap();
+    assert_eq!(code(&client.reply().await), "552");
+
+    // The transaction was aborted, and the session is still in sync.
+    assert_eq!(
+        code(&client.command("RCPT TO:<archive@bichon.local>").await),
+        "503"
+    );
+    assert!(client.backend.recorded().is_empty());
+}
+
+#[tokio::test]
+async fn non_ascii_addresses_require_smtputf8() {
+    let mut client = Client::ready().await;
+    assert_eq!(
+        code(&client.command("MAIL FROM:<发件人@例子.广告>").await),
+        "553"
+    );
+
+    client.command("MAIL FROM:<a@test>").await;
+    assert_eq!(
+        code(&client.command("RCPT TO:<用户@例子.广告>").await),
+        "553"
+    );
+    client.command("RSET").await;
+
+    assert_eq!(
+        code(
+            &client
+                .command("MAIL FROM:<发件人@例子.广告> SMTPUTF8")
+                .await
+        ),
+        "250"
+    );
+    assert_eq!(
+        code(&client.command("RCPT TO:<用户@例子.广告>").await),
+        "250"
+    );
+    client.command("DATA").await;
+    client.send("Subject: 你好\r\n\r\n正文\r\n.\r\n").await;
+    assert_eq!(code(&client.reply().await), "250");
+
+    let delivery = client.backend.recorded().remove(0);
+    assert_eq!(delivery.rcpt, "用户@例子.广告");
+    assert_eq!(delivery.data, "Subject: 你好\r\n\r\n正文\r\n".as_bytes());
+}
+
+#[tokio::test]
+async fn auth_is_required_before_mail() {
+    let mut client = Client::connect(true).await;
+    client.command("EHLO client.test").await;
+    assert_eq!(code(&client.command("MAIL FROM:<a@test>").await), "530");
+
+    let bad = BASE64_STANDARD.encode(b"\0user\0wrong");
+    assert_eq!(
+        code(&client.command(&format!("AUTH PLAIN {bad}")).await),
+        "535"
+    );
+
+    assert_eq!(code(&client.command("AUTH LOGIN").await), "334");
+    assert_eq!(
+        code(&client.command(&BASE64_STANDARD.encode("user")).await),
+        "334"
+    );
+    assert_eq!(
+        code(&client.command(&BASE64_STANDARD.encode(TOKEN)).await),
+        "235"
+    );
+    assert_eq!(code(&client.command("AUTH LOGIN").await), "503");
+
+    assert_eq!(code(&client.command("MAIL FROM:<a@test>").await), "250");
+    assert_eq!(
+        code(&client.command("RCPT TO:<imap@bichon.local>").await),
+        "550"
+    );
+    assert_eq!(
+        code(&client.command("RCPT TO:<archive@bichon.local>").await),
+        "250"
+    );
+}
+
+#[tokio::test]
+async fn throttled_auth_is_a_temporary_failure() {
+    let mut client = Client::ready().await;
+    let locked = BASE64_STANDARD.encode(format!("\0{LOCKED_USER}\0{TOKEN}"));
+    assert_eq!(
+        code(&client.command(&format!("AUTH PLAIN {locked}")).await),
+        "454"
+    );
+    assert_eq!(code(&client.command("AUTH LOGIN").await), "334");
+    assert_eq!(
+        code(&client.command(&BASE64_STANDARD.encode(LOCKED_USER)).await),
+        "334"
+    );
+    assert_eq!(
+        code(&client.command(&BASE64_STANDARD.encode(TOKEN)).await),
+        "454"
+    );
+}
+
+#[tokio::test]
+async fn auth_exchange_can_be_cancelled() {
+    let mut client = Client::ready().await;
+    assert_eq!(code(&client.command("AUTH PLAIN").await), "334");
+    assert_eq!(code(&client.command("*").await), "501");
+    assert_eq!(code(&client.command("NOOP").await), "250");
+}
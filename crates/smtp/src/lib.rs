@@ -1,5 +1,9 @@
+pub mod backend;
 pub mod server;
 
+mod command;
+#[cfg(test)]
+mod conformance;
 mod stream;
 #[cfg(test)]
 mod tests;
@@ -16,39 +16,42 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::future::Future;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
 
 use base64::{prelude::BASE64_STANDARD, Engine as _};
-use bichon_core::account::migration::AccountType;
-use bichon_core::cache::imap::mailbox::{Attribute, AttributeEnum};
+use bichon_core::account::migration::{AccountModel, AccountType};
 use bichon_core::common::signal::SIGNAL_MANAGER;
-use bichon_core::envelope::extractor::extract_envelope_from_smtp;
-use bichon_core::error::BichonResult;
+use bichon_core::error::code::ErrorCode;
+use bichon_core::raise_error;
 use bichon_core::settings::cli::{EncryptionMode, SETTINGS};
-use bichon_core::utils::create_hash;
-use bichon_core::{
-    account::migration::AccountModel,
-    cache::imap::mailbox::MailBox,
-    common::auth::ClientContext,
-    token::AccessTokenModel,
-    users::{permissions::Permission, UserModel},
-};
+use bichon_core::common::auth::ClientContext;
 use tokio::time::timeout;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
     sync::broadcast,
 };
 use tokio_rustls::TlsAcceptor;
 
+use crate::backend::{ArchiveBackend, SmtpBackend};
+use crate::command::{Command, MailParams};
 use crate::stream::BufStream;
 use crate::tls::create_acceptor;
 
 const MAX_MAIL_SIZE: usize = 50 * 1024 * 1024; //50MB
 const SMTP_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 const GLOBAL_SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+const DATA_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// RFC 5321 §4.5.3.1.4 asks for 512; leave room for ESMTP parameters and
+/// long SMTPUTF8 addresses.
+const MAX_COMMAND_LINE: usize = 4096;
+/// Upper bound for a single read while receiving message content.
+const DATA_READ_SIZE: usize = 64 * 1024;
+const NON_ASCII_ADDRESS_REPLY: &str = "553 5.6.7 Non-ASCII address requires SMTPUTF8\r\n";
 
 pub async fn run_smtp_server(
     listener: TcpListener,
@@ -63,7 +66,7 @@ pub async fn run_smtp_server(
                         tracing::debug!("SMTP connection from {addr}");
                         let config = config.clone();
                         tokio::spawn(async move {
-                            let res = timeout(GLOBAL_SESSION_TIMEOUT, handle_connection(stream, config)).await;
+                            let res = timeout(GLOBAL_SESSION_TIMEOUT, handle_connection(stream, config, Some(addr.ip()))).await;
                             match res {
                                 Ok(Ok(_)) => tracing::debug!("SMTP session from {addr} finished"),
                                 Ok(Err(e)) => tracing::debug!("SMTP session error from {addr}: {e}"),
@@ -100,7 +103,7 @@ pub async fn run_smtps_server(
                         tokio::spawn(async move {
                             match acceptor.accept(stream).await {
                                 Ok(tls_stream) => {
-                                    let res = timeout(GLOBAL_SESSION_TIMEOUT, handle_tls_connection(tls_stream, config)).await;
+                                    let res = timeout(GLOBAL_SESSION_TIMEOUT, handle_tls_connection(tls_stream, config, Some(addr.ip()))).await;
                                     match res {
                                         Ok(Ok(_)) => tracing::debug!("SMTPS session from {addr} finished"),
                                         Ok(Err(e)) => tracing::debug!("SMTPS session error from {addr}: {e}"),
@@ -131,32 +134,70 @@ enum CommandResult {
     StartTls,
 }
 
+/// Where the session is in the RFC 5321 command sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    /// Greeting sent, waiting for EHLO/HELO.
+    Connected,
+    /// Greeted, no mail transaction in progress.
+    Ready,
+    /// MAIL accepted, waiting for RCPT.
+    Mail,
+    /// Recipient accepted, waiting for DATA or BDAT.
+    Rcpt,
+    /// At least one non-final BDAT chunk received.
+    Chunking,
+}
+
 struct Session {
+    phase: Phase,
     mail_from: Option<String>,
+    params: MailParams,
     rcpt_to: Vec<AccountModel>,
+    chunks: Vec<u8>,
     authenticated: bool,
-    user: Option<UserModel>,
+    context: Option<ClientContext>,
     auth_required: bool,
     tls_active: bool,
     auth_state: AuthState,
+    /// Client address, used for failed-login throttling.
+    peer: Option<IpAddr>,
 }
 
 impl Session {
-    const fn new(auth_required: bool, tls_active: bool) -> Self {
+    fn new(auth_required: bool, tls_active: bool, peer: Option<IpAddr>) -> Self {
         Self {
+            phase: Phase::Connected,
             mail_from: None,
+            params: MailParams::default(),
             rcpt_to: Vec::new(),
+            chunks: Vec::new(),
             authenticated: false,
-            user: None,
+            context: None,
             auth_required,
             tls_active,
             auth_state: AuthState::None,
+            peer,
         }
     }
 
+    /// Abort the current mail transaction, keeping the greeting state.
     fn reset(&mut self) {
         self.mail_from = None;
+        self.params = MailParams::default();
         self.rcpt_to.clear();
+        self.chunks = Vec::new();
+        if self.phase != Phase::Connected {
+            self.phase = Phase::Ready;
+        }
+    }
+
+    fn in_transaction(&self) -> bool {
+        matches!(self.phase, Phase::Mail | Phase::Rcpt | Phase::Chunking)
+    }
+
+    fn needs_auth(&self) -> bool {
+        self.auth_required && !self.authenticated
     }
 }
 
@@ -169,9 +210,22 @@ enum AuthState {
     WaitingForLoginPassword(String),
 }
 
-/// Handle a plain TCP connection with optional STARTTLS upgrade.
-async fn handle_connection(stream: TcpStream, config: SmtpConfig) -> io::Result<()> {
-    let mut session = Session::new(config.auth_required, false);
+enum Line {
+    Eof,
+    TooLong,
+    Complete(Vec<u8>),
+}
+
+/// Handle a plain connection with optional STARTTLS upgrade.
+pub(crate) async fn handle_connection<S>(
+    stream: S,
+    config: SmtpConfig,
+    peer: Option<IpAddr>,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut session = Session::new(config.auth_required, false, peer);
 
     // Use buffered I/O over the raw stream
     let mut stream = BufStream::new(stream);
@@ -191,8 +245,13 @@ async fn handle_connection(stream: TcpStream, config: SmtpConfig) -> io::Result<
                     let inner = stream.into_inner();
                     match acceptor.clone().accept(inner).await {
                         Ok(tls_stream) => {
+                            // RFC 3207 §4.2: forget everything negotiated in
+                            // plaintext, the client has to greet again.
                             session.tls_active = true;
                             session.reset();
+                            session.phase = Phase::Connected;
+                            session.authenticated = false;
+                            session.context = None;
                             return handle_tls_session(tls_stream, session, config).await;
                         }
                         Err(e) => {
@@ -208,11 +267,15 @@ async fn handle_connection(stream: TcpStream, config: SmtpConfig) -> io::Result<
     Ok(())
 }
 
-async fn handle_tls_connection<S>(stream: S, config: SmtpConfig) -> io::Result<()>
+async fn handle_tls_connection<S>(
+    stream: S,
+    config: SmtpConfig,
+    peer: Option<IpAddr>,
+) -> io::Result<()>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    let session = Session::new(config.auth_required, true);
+    let session = Session::new(config.auth_required, true, peer);
     handle_tls_session(stream, session, config).await
 }
 
@@ -239,7 +302,6 @@ where
     Ok(())
 }
 
-#[allow(clippy::too_many_lines)]
 async fn process_command<S>(
     stream: &mut BufStream<S>,
     session: &mut Session,
@@ -248,23 +310,49 @@ async fn process_command<S>(
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    let mut line = String::new();
-    let bytes_read = match timeout(SMTP_IDLE_TIMEOUT, stream.inner.read_line(&mut line)).await {
-        Ok(res) => res?,
-        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "Command timeout")),
-    };
-
-    if bytes_read == 0 {
-        return Ok(CommandResult::Quit);
+    let result = execute_command(stream, session, config).await?;
+    // PIPELINING (RFC 2920): while the client still has commands queued,
+    // keep collecting replies and send them together once we would block.
+    if !stream.has_pending_input() {
+        stream.flush().await?;
     }
+    Ok(result)
+}
 
+async fn execute_command<S>(
+    stream: &mut BufStream<S>,
+    session: &mut Session,
+    config: &SmtpConfig,
+) -> io::Result<CommandResult>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let line = match read_command_line(&mut stream.inner).await? {
+        Line::Eof => return Ok(CommandResult::Quit),
+        Line::TooLong => {
+            stream.write_all(b"500 5.5.6 Line too long\r\n").await?;
+            return Ok(CommandResult::Continue);
+        }
+        Line::Complete(line) => line,
+    };
+    let Ok(line) = String::from_utf8(line) else {
+        stream
+            .write_all(b"500 5.5.2 Invalid UTF-8 in command line\r\n")
+            .await?;
+        return Ok(CommandResult::Continue);
+    };
     let trimmed = line.trim();
-    let cmd = trimmed.to_uppercase();
 
-    match &session.auth_state {
+    match std::mem::take(&mut session.auth_state) {
+        AuthState::None => {}
+        _ if trimmed == "*" => {
+            stream
+                .write_all(b"501 5.7.0 Authentication cancelled\r\n")
+                .await?;
+            return Ok(CommandResult::Continue);
+        }
         AuthState::WaitingForPlain => {
-            verify_plain_auth(trimmed, session, stream).await?;
-            session.auth_state = AuthState::None;
+            verify_plain_auth(trimmed, session, stream, config).await?;
             return Ok(CommandResult::Continue);
         }
         AuthState::WaitingForLoginUsername => {
@@ -275,257 +363,213 @@ where
                 session.auth_state = AuthState::WaitingForLoginPassword(username);
             } else {
                 stream.write_all(b"501 Cannot decode\r\n").await?;
-                stream.flush().await?;
-                session.auth_state = AuthState::None;
             }
             return Ok(CommandResult::Continue);
         }
         AuthState::WaitingForLoginPassword(username) => {
-            let username = username.clone();
             if let Ok(decoded) = BASE64_STANDARD.decode(trimmed) {
                 let password = String::from_utf8_lossy(&decoded);
-                match AccessTokenModel::resolve_user_from_token(&password) {
-                    Ok(user) => {
-                        session.authenticated = true;
-                        session.user = Some(user);
-                        stream
-                            .write_all(b"235 Authentication successful\r\n")
-                            .await?;
-                    }
-                    Err(error) => {
-                        tracing::error!(
-                            "SMTP Auth failed for user '{}' (AUTH LOGIN): {:?}",
-                            username,
-                            error
-                        );
-                        stream.write_all(b"535 Authentication failed\r\n").await?;
-                    }
-                }
+                complete_auth(&username, &password, session, stream, config).await?;
             } else {
                 stream.write_all(b"501 Cannot decode\r\n").await?;
             }
-            stream.flush().await?;
-            session.auth_state = AuthState::None;
             return Ok(CommandResult::Continue);
         }
-        AuthState::None => {}
     }
 
-    if cmd.starts_with("EHLO") || cmd.starts_with("HELO") {
-        let mut response = String::from("250-Bichon Hello\r\n");
-        response.push_str("250-SIZE 52428800\r\n"); // 50MB
-        response.push_str("250-8BITMIME\r\n");
-
-        if config.tls_acceptor.is_some() && !session.tls_active {
-            response.push_str("250-STARTTLS\r\n");
+    let command = match Command::parse(trimmed) {
+        Ok(command) => command,
+        Err(e) => {
+            stream.write_all(e.reply().as_bytes()).await?;
+            return Ok(CommandResult::Continue);
         }
+    };
 
-        response.push_str("250-AUTH PLAIN LOGIN\r\n");
-        response.push_str("250 OK\r\n");
+    let reply: String = match command {
+        Command::Ehlo(domain) => {
+            session.reset();
+            session.phase = Phase::Ready;
 
-        stream.write_all(response.as_bytes()).await?;
-        stream.flush().await?;
-    } else if cmd.starts_with("STARTTLS") {
-        if config.tls_acceptor.is_none() {
-            stream.write_all(b"454 TLS not available\r\n").await?;
-        } else if session.tls_active {
-            stream.write_all(b"503 TLS already active\r\n").await?;
-        } else {
-            stream.write_all(b"220 Ready to start TLS\r\n").await?;
-            stream.flush().await?;
-            return Ok(CommandResult::StartTls);
-        }
-    } else if cmd.starts_with("AUTH ") {
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let mechanism = parts[1].to_uppercase();
-            match mechanism.as_str() {
-                "PLAIN" => {
-                    if parts.len() > 2 {
-                        verify_plain_auth(parts[2], session, stream).await?;
-                    } else {
-                        stream.write_all(b"334 \r\n").await?;
-                        session.auth_state = AuthState::WaitingForPlain;
-                    }
-                }
-                "LOGIN" => {
-                    stream.write_all(b"334 VXNlcm5hbWU6\r\n").await?;
-                    session.auth_state = AuthState::WaitingForLoginUsername;
-                }
-                _ => {
-                    stream.write_all(b"504 Unrecognized auth type\r\n").await?;
-                }
-            }
-        } else {
-            stream.write_all(b"501 Syntax error\r\n").await?;
-        }
-    } else if cmd.starts_with("MAIL FROM:") {
-        if session.auth_required && !session.authenticated {
-            stream.write_all(b"530 Authentication required\r\n").await?;
-        } else {
-            let addr = extract_address(&trimmed[10..]);
-            let mut allowed = true;
-            if let Some(ref whitelist) = config.whitelist {
-                if !whitelist.is_empty() && !whitelist.contains(&addr) {
-                    allowed = false;
-                }
+            let mut response = format!("250-Bichon Hello {domain}\r\n");
+            response.push_str("250-PIPELINING\r\n");
+            response.push_str(&format!("250-SIZE {MAX_MAIL_SIZE}\r\n"));
+            response.push_str("250-8BITMIME\r\n");
+            response.push_str("250-SMTPUTF8\r\n");
+            response.push_str("250-CHUNKING\r\n");
+
+            if config.tls_acceptor.is_some() && !session.tls_active {
+                response.push_str("250-STARTTLS\r\n");
             }
 
-            if allowed {
-                session.mail_from = Some(addr);
-                stream.write_all(b"250 OK\r\n").await?;
+            response.push_str("250-AUTH PLAIN LOGIN\r\n");
+            response.push_str("250 OK\r\n");
+            response
+        }
+        Command::Helo(domain) => {
+            session.reset();
+            session.phase = Phase::Ready;
+            format!("250 Bichon Hello {domain}\r\n")
+        }
+        Command::StartTls => {
+            if config.tls_acceptor.is_none() {
+                "454 TLS not available\r\n".into()
+            } else if session.tls_active {
+                "503 TLS already active\r\n".into()
+            } else if session.in_transaction() {
+                "503 5.5.1 STARTTLS not allowed during a mail transaction\r\n".into()
             } else {
-                stream.write_all(b"550 Sender not allowed\r\n").await?;
+                stream.write_all(b"220 Ready to start TLS\r\n").await?;
+                stream.flush().await?;
+                return Ok(CommandResult::StartTls);
             }
         }
-    } else if cmd.starts_with("RCPT TO:") {
-        if !session.rcpt_to.is_empty() {
-            stream
-                .write_all(b"452 4.5.3 Too many recipients, try again in a new transaction\r\n")
-                .await?;
-            return Ok(CommandResult::Continue);
-        }
-
-        if session.auth_required && !session.authenticated {
-            stream.write_all(b"530 Authentication required\r\n").await?;
-        } else if session.mail_from.is_none() {
-            stream
-                .write_all(b"503 MAIL FROM required first\r\n")
-                .await?;
-        } else {
-            let addr = extract_address(&trimmed[8..]);
-            //println!("DEBUG: SMTP RCPT TO extracted address -> '{}'", addr);
-            let account_result = AccountModel::find_by_email(addr.as_str());
-
-            match account_result {
-                Ok(Some(account)) => {
-                    let mut is_allowed = true;
-                    if session.auth_required {
-                        if let Some(user) = &session.user {
-                            let has_perm = ClientContext::check_has_permission(
-                                user,
-                                Some(account.id),
-                                Permission::DATA_SMTP_INGEST,
-                            );
-
-                            if !has_perm {
-                                tracing::warn!(
-                                    "SMTP: Access denied for User {} to Account <{}>",
-                                    user.id,
-                                    addr
-                                );
-                                stream
-                                    .write_all(
-                                        b"554 5.7.1 Access denied: Insufficient permissions\r\n",
-                                    )
-                                    .await?;
-                                is_allowed = false;
-                            }
+        Command::Auth {
+            mechanism,
+            initial_response,
+        } => {
+            if session.phase == Phase::Connected {
+                "503 5.5.1 Send EHLO first\r\n".into()
+            } else if session.authenticated {
+                "503 5.5.1 Already authenticated\r\n".into()
+            } else if session.in_transaction() {
+                "503 5.5.1 AUTH not allowed during a mail transaction\r\n".into()
+            } else {
+                match mechanism.as_str() {
+                    "PLAIN" => {
+                        if let Some(response) = initial_response {
+                            verify_plain_auth(&response, session, stream, config).await?;
                         } else {
-                            stream
-                                .write_all(b"530 5.7.0 Authentication required\r\n")
-                                .await?;
-                            is_allowed = false;
+                            stream.write_all(b"334 \r\n").await?;
+                            stream.flush().await?;
+                            session.auth_state = AuthState::WaitingForPlain;
                         }
                     }
-
-                    if is_allowed {
-                        if !matches!(account.account_type, AccountType::NoSync) {
-                            tracing::warn!(
-                                "SMTP: Rejected journaling attempt to IMAP account <{}>",
-                                addr
-                            );
-                            let err = format!(
-                                "550 5.7.1 <{}>: Not a Bichon local account, journaling is not supported\r\n",
-                                account.email
-                            );
-                            stream.write_all(err.as_bytes()).await?;
-                        } else {
-                            session.rcpt_to.push(account);
-                            stream.write_all(b"250 OK\r\n").await?;
-                        }
+                    "LOGIN" => {
+                        stream.write_all(b"334 VXNlcm5hbWU6\r\n").await?;
+                        stream.flush().await?;
+                        session.auth_state = AuthState::WaitingForLoginUsername;
+                    }
+                    _ => {
+                        stream.write_all(b"504 Unrecognized auth type\r\n").await?;
                     }
                 }
-                Ok(None) => {
-                    let err = format!("550 5.1.1 <{}>: Bichon account not found\r\n", addr);
-                    stream.write_all(err.as_bytes()).await?;
-                }
-                Err(e) => {
-                    tracing::error!("SMTP: Account query error for {}: {:?}", addr, e);
-                    stream
-                        .write_all(
-                            b"451 4.3.0 Requested action aborted: local error in processing\r\n",
-                        )
-                        .await?;
+                return Ok(CommandResult::Continue);
+            }
+        }
+        Command::Mail { address, params } => {
+            if session.needs_auth() {
+                "530 Authentication required\r\n".into()
+            } else if session.phase == Phase::Connected {
+                "503 5.5.1 Send EHLO/HELO first\r\n".into()
+            } else if session.in_transaction() {
+                "503 5.5.1 Nested MAIL command\r\n".into()
+            } else if params.size.is_some_and(|size| size > MAX_MAIL_SIZE) {
+                size_exceeded_reply()
+            } else if !params.smtputf8 && !address.is_ascii() {
+                NON_ASCII_ADDRESS_REPLY.into()
+            } else if !sender_allowed(config, &address) {
+                "550 Sender not allowed\r\n".into()
+            } else {
+                session.mail_from = Some(address);
+                session.params = params;
+                session.phase = Phase::Mail;
+                "250 OK\r\n".into()
+            }
+        }
+        Command::Rcpt { address } => {
+            if session.needs_auth() {
+                "530 Authentication required\r\n".into()
+            } else if !matches!(session.phase, Phase::Mail | Phase::Rcpt) {
+                "503 MAIL FROM required first\r\n".into()
+            } else if !session.rcpt_to.is_empty() {
+                "452 4.5.3 Too many recipients, try again in a new transaction\r\n".into()
+            } else if !session.params.smtputf8 && !address.is_ascii() {
+                NON_ASCII_ADDRESS_REPLY.into()
+            } else {
+                match resolve_recipient(&address, session, config) {
+                    Ok(account) => {
+                        session.rcpt_to.push(account);
+                        session.phase = Phase::Rcpt;
+                        "250 OK\r\n".into()
+                    }
+                    Err(reply) => reply,
                 }
             }
         }
-    } else if cmd == "DATA" {
-        if session.auth_required && !session.authenticated {
-            stream.write_all(b"530 Authentication required\r\n").await?;
-        } else if session.mail_from.is_none() {
-            stream
-                .write_all(b"503 MAIL FROM required first\r\n")
-                .await?;
-        } else if session.rcpt_to.is_empty() {
-            stream.write_all(b"503 RCPT TO required first\r\n").await?;
-        } else {
-            stream
-                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
-                .await?;
-            stream.flush().await?;
-
-            let data = match read_data(&mut stream.inner).await {
-                Ok(d) => d,
-                Err(e) => {
-                    if e.to_string().contains("552") {
-                        let error_msg = format!(
-                            "552 5.3.4 Message size exceeds limit of {} bytes ({}MB)\r\n",
-                            MAX_MAIL_SIZE,
-                            MAX_MAIL_SIZE / 1024 / 1024
-                        );
-                        stream.write_all(error_msg.as_bytes()).await?;
+        Command::Data => {
+            if session.needs_auth() {
+                "530 Authentication required\r\n".into()
+            } else {
+                match session.phase {
+                    Phase::Connected | Phase::Ready => "503 MAIL FROM required first\r\n".into(),
+                    Phase::Mail => "503 RCPT TO required first\r\n".into(),
+                    Phase::Chunking => "503 5.5.1 DATA cannot be mixed with BDAT\r\n".into(),
+                    Phase::Rcpt => {
+                        stream
+                            .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                            .await?;
                         stream.flush().await?;
-                        return Ok(CommandResult::Continue);
+
+                        match read_data(&mut stream.inner).await? {
+                            Some(data) => deliver(data, session, config).await,
+                            None => {
+                                session.reset();
+                                size_exceeded_reply()
+                            }
+                        }
                     }
-                    return Err(e);
                 }
-            };
-            match parse_email(&data, session).await {
-                Ok(_) => {
-                    stream
-                        .write_all(b"250 2.0.0 OK: queued in Bichon\r\n")
-                        .await?;
-                    tracing::debug!(
-                        "SMTP: Message accepted and archived for {} recipients",
-                        session.rcpt_to.len()
-                    );
-                    session.reset();
+            }
+        }
+        Command::Bdat { size, last } => {
+            // The chunk is already on the wire whatever we answer, so it is
+            // always consumed before replying (RFC 3030 §2).
+            if session.needs_auth() || !matches!(session.phase, Phase::Rcpt | Phase::Chunking) {
+                read_chunk(&mut stream.inner, size, None).await?;
+                if session.needs_auth() {
+                    "530 Authentication required\r\n".into()
+                } else if session.phase == Phase::Mail {
+                    "503 RCPT TO required first\r\n".into()
+                } else {
+                    "503 MAIL FROM required first\r\n".into()
                 }
-                Err(e) => {
-                    tracing::error!("SMTP: Critical error during parse_email: {:?}", e);
-                    stream
-                        .write_all(
-                            b"451 4.3.0 Error: local error in processing, try again later\r\n",
-                        )
-                        .await?;
+            } else if session
+                .chunks
+                .len()
+                .checked_add(size)
+                .is_none_or(|total| total > MAX_MAIL_SIZE)
+            {
+                read_chunk(&mut stream.inner, size, None).await?;
+                tracing::warn!(
+                    "SMTP: BDAT message rejected, exceeds limit of {}MB",
+                    MAX_MAIL_SIZE / 1024 / 1024
+                );
+                session.reset();
+                size_exceeded_reply()
+            } else {
+                read_chunk(&mut stream.inner, size, Some(&mut session.chunks)).await?;
+                if last {
+                    let data = std::mem::take(&mut session.chunks);
+                    deliver(data, session, config).await
+                } else {
+                    session.phase = Phase::Chunking;
+                    format!("250 2.0.0 {size} octets received\r\n")
                 }
             }
         }
-    } else if cmd == "RSET" {
-        session.reset();
-        stream.write_all(b"250 OK\r\n").await?;
-    } else if cmd == "NOOP" {
-        stream.write_all(b"250 OK\r\n").await?;
-    } else if cmd == "QUIT" {
-        stream.write_all(b"221 Bye\r\n").await?;
-        stream.flush().await?;
-        return Ok(CommandResult::Quit);
-    } else {
-        stream.write_all(b"500 Command not recognized\r\n").await?;
-    }
+        Command::Rset => {
+            session.reset();
+            "250 OK\r\n".into()
+        }
+        Command::Noop => "250 OK\r\n".into(),
+        Command::Quit => {
+            stream.write_all(b"221 Bye\r\n").await?;
+            stream.flush().await?;
+            return Ok(CommandResult::Quit);
+        }
+    };
 
-    stream.flush().await?;
+    stream.write_all(reply.as_bytes()).await?;
     Ok(CommandResult::Continue)
 }
 
@@ -533,154 +577,311 @@ async fn verify_plain_auth<S: AsyncRead + AsyncWrite + Unpin>(
     encoded: &str,
     session: &mut Session,
     stream: &mut BufStream<S>,
+    config: &SmtpConfig,
 ) -> io::Result<()> {
     if let Ok(decoded) = BASE64_STANDARD.decode(encoded.trim()) {
         let parts: Vec<&[u8]> = decoded.split(|&b| b == 0).collect();
         if parts.len() >= 3 {
             let username = String::from_utf8_lossy(parts[1]);
             let password = String::from_utf8_lossy(parts[2]);
-
-            match AccessTokenModel::resolve_user_from_token(&password) {
-                Ok(user) => {
-                    session.authenticated = true;
-                    session.user = Some(user);
-                    stream
-                        .write_all(b"235 Authentication successful\r\n")
-                        .await?;
-                    stream.flush().await?;
-                    return Ok(());
-                }
-                Err(error) => {
-                    tracing::error!("SMTP Auth failed for user '{}': {:?}", username, error);
-                }
-            }
+            return complete_auth(&username, &password, session, stream, config).await;
         }
     }
 
     stream.write_all(b"535 Authentication failed\r\n").await?;
-    stream.flush().await?;
     Ok(())
 }
 
-fn extract_address(s: &str) -> String {
-    let s = s.trim();
-    if let (Some(start), Some(end)) = (s.find('<'), s.find('>')) {
-        return s[start + 1..end].to_string();
+/// Check the credentials of an AUTH exchange and send the final reply.
+async fn complete_auth<S: AsyncRead + AsyncWrite + Unpin>(
+    username: &str,
+    password: &str,
+    session: &mut Session,
+    stream: &mut BufStream<S>,
+    config: &SmtpConfig,
+) -> io::Result<()> {
+    match config
+        .backend
+        .authenticate(username, password, session.peer)
+    {
+        Ok(context) => {
+            session.authenticated = true;
+            session.context = Some(context);
+            stream
+                .write_all(b"235 Authentication successful\r\n")
+                .await?;
+        }
+        Err(error) if error.code() == ErrorCode::TooManyRequest => {
+            tracing::warn!("SMTP Auth for user '{}' throttled: {}", username, error);
+            stream
+                .write_all(b"454 4.7.0 Too many failed attempts, try again later\r\n")
+                .await?;
+        }
+        Err(error) => {
+            tracing::error!("SMTP Auth failed for user '{}': {:?}", username, error);
+            stream.write_all(b"535 Authentication failed\r\n").await?;
+        }
+    }
+    Ok(())
+}
+
+fn sender_allowed(config: &SmtpConfig, address: &str) -> bool {
+    match config.whitelist {
+        Some(ref whitelist) if !whitelist.is_empty() => whitelist.iter().any(|a| a == address),
+        _ => true,
+    }
+}
+
+/// Check that `address` is a local archive the session may ingest into,
+/// returning the rejection reply otherwise.
+fn resolve_recipient(
+    address: &str,
+    session: &Session,
+    config: &SmtpConfig,
+) -> Result<AccountModel, String> {
+    let account = match config.backend.find_account(address) {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            return Err(format!(
+                "550 5.1.1 <{address}>: Bichon account not found\r\n"
+            ))
+        }
+        Err(e) => {
+            tracing::error!("SMTP: Account query error for {}: {:?}", address, e);
+            return Err("451 4.3.0 Requested action aborted: local error in processing\r\n".into());
+        }
+    };
+
+    if session.auth_required {
+        let Some(context) = &session.context else {
+            return Err("530 5.7.0 Authentication required\r\n".into());
+        };
+        if !config.backend.can_ingest(context, &account) {
+            tracing::warn!(
+                "SMTP: Access denied for User {} to Account <{}>",
+                context.user.id,
+                address
+            );
+            return Err("554 5.7.1 Access denied: Insufficient permissions\r\n".into());
+        }
+    }
+
+    if !matches!(account.account_type, AccountType::NoSync) {
+        tracing::warn!(
+            "SMTP: Rejected journaling attempt to IMAP account <{}>",
+            address
+        );
+        return Err(format!(
+            "550 5.7.1 <{}>: Not a Bichon local account, journaling is not supported\r\n",
+            account.email
+        ));
+    }
+
+    Ok(account)
+}
+
+fn size_exceeded_reply() -> String {
+    format!(
+        "552 5.3.4 Message size exceeds limit of {} bytes ({}MB)\r\n",
+        MAX_MAIL_SIZE,
+        MAX_MAIL_SIZE / 1024 / 1024
+    )
+}
+
+async fn timed<F, T>(duration: Duration, what: &'static str, fut: F) -> io::Result<T>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    match timeout(duration, fut).await {
+        Ok(res) => res,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, what)),
+    }
+}
+
+async fn read_command_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Line> {
+    let mut line = Vec::new();
+    let mut limited = (&mut *reader).take(MAX_COMMAND_LINE as u64);
+    let bytes_read = timed(
+        SMTP_IDLE_TIMEOUT,
+        "Command timeout",
+        limited.read_until(b'\n', &mut line),
+    )
+    .await?;
+
+    if line.ends_with(b"\n") {
+        return Ok(Line::Complete(line));
+    }
+    if bytes_read < MAX_COMMAND_LINE {
+        // EOF, possibly in the middle of a line.
+        return Ok(Line::Eof);
+    }
+
+    // Skip the rest of the oversized line so the next one parses cleanly.
+    loop {
+        line.clear();
+        let mut limited = (&mut *reader).take(MAX_COMMAND_LINE as u64);
+        let bytes_read = timed(
+            SMTP_IDLE_TIMEOUT,
+            "Command timeout",
+            limited.read_until(b'\n', &mut line),
+        )
+        .await?;
+        if bytes_read == 0 {
+            return Ok(Line::Eof);
+        }
+        if line.ends_with(b"\n") {
+            return Ok(Line::TooLong);
+        }
     }
-    s.to_string()
 }
 
-async fn read_data<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+/// Read a dot-terminated DATA body, undoing dot-stuffing. Returns `None`
+/// when the message exceeded [`MAX_MAIL_SIZE`]; the rest of it is still
+/// consumed so the session stays in sync with the client.
+async fn read_data<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
     let mut data = Vec::with_capacity(65536);
-    let mut line = String::new();
+    let mut line = Vec::new();
     let mut total_bytes = 0;
-    let line_timeout = Duration::from_secs(30);
+    let mut oversized = false;
+    let mut at_line_start = true;
 
     loop {
         line.clear();
-        let bytes_read = match timeout(line_timeout, reader.read_line(&mut line)).await {
-            Ok(res) => res?,
-            Err(_) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::TimedOut,
-                    "Data transmission timeout",
-                ))
-            }
-        };
+        let mut limited = (&mut *reader).take(DATA_READ_SIZE as u64);
+        let bytes_read = timed(
+            DATA_IDLE_TIMEOUT,
+            "Data transmission timeout",
+            limited.read_until(b'\n', &mut line),
+        )
+        .await?;
 
         if bytes_read == 0 {
-            break;
-        }
-        total_bytes += bytes_read;
-
-        if total_bytes > MAX_MAIL_SIZE {
-            tracing::warn!(
-                "SMTP: Message rejected. Size {} bytes exceeds limit of {}MB",
-                total_bytes,
-                MAX_MAIL_SIZE / 1024 / 1024
-            );
             return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "552 5.3.4 Message size exceeds fixed maximum message size",
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed during DATA",
             ));
         }
-        if line.trim() == "." {
+        if at_line_start && (line == b".\r\n" || line == b".\n") {
             break;
         }
-        let content = if line.starts_with("..") {
+
+        let content = if at_line_start && line.starts_with(b".") {
             &line[1..]
         } else {
-            &line
+            &line[..]
         };
-        data.extend_from_slice(content.as_bytes());
-    }
+        at_line_start = line.ends_with(b"\n");
+        total_bytes += content.len();
 
-    Ok(data)
-}
-
-async fn parse_email(data: &[u8], session: &Session) -> BichonResult<()> {
-    let rcpt = match session.rcpt_to.first() {
-        Some(r) => r,
-        None => {
+        if oversized {
+            continue;
+        }
+        if total_bytes > MAX_MAIL_SIZE {
             tracing::warn!(
-                "SMTP: parse_email called with empty recipient list. Skipping processing."
+                "SMTP: Message rejected. Size exceeds limit of {}MB",
+                MAX_MAIL_SIZE / 1024 / 1024
             );
-            return Ok(());
+            oversized = true;
+            data = Vec::new();
+            continue;
         }
-    };
-    let mailbox_id = create_hash(rcpt.id, "INBOX");
-
-    // The INBOX row is owned by the IMAP sync, which maintains `uid_validity`,
-    // `highest_uid` and `uid_next` on it. `batch_upsert` replaces the *whole*
-    // row, so blindly upserting here (with those fields = None) clobbers the
-    // IMAP-maintained state back to None. The next reconcile then sees
-    // `uid_validity` change from Some -> None, treats the mailbox as invalid,
-    // and wipes + rebuilds it — silently losing the local copy of a large
-    // mailbox when that rebuild is interrupted (see #297).
-    //
-    // We only need the row to *exist* so the journaled envelope can attach to
-    // it, so create it only when it is missing and otherwise leave the
-    // IMAP-owned row untouched.
-    if MailBox::find_mailbox(rcpt.id, mailbox_id)?.is_none() {
-        let mailbox = MailBox {
-            id: mailbox_id,
-            account_id: rcpt.id,
-            name: "INBOX".into(),
-            delimiter: Some("/".to_string()),
-            attributes: vec![Attribute {
-                attr: AttributeEnum::Extension,
-                extension: Some("CreatedByBichon".into()),
-            }],
-            exists: 0,
-            unseen: None,
-            uid_next: None,
-            uid_validity: None,
-            highest_uid: None,
+        data.extend_from_slice(content);
+    }
+
+    Ok((!oversized).then_some(data))
+}
+
+/// Read exactly `size` octets of BDAT payload, appending them to `dest`, or
+/// discarding them when the chunk is rejected.
+async fn read_chunk<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    size: usize,
+    mut dest: Option<&mut Vec<u8>>,
+) -> io::Result<()> {
+    let mut scratch = Vec::new();
+    let mut remaining = size;
+
+    while remaining > 0 {
+        let mut limited = (&mut *reader).take(remaining.min(DATA_READ_SIZE) as u64);
+        let buf = match dest.as_deref_mut() {
+            Some(buf) => buf,
+            None => {
+                scratch.clear();
+                &mut scratch
+            }
         };
+        let bytes_read = timed(
+            DATA_IDLE_TIMEOUT,
+            "Data transmission timeout",
+            limited.read_to_end(buf),
+        )
+        .await?;
 
-        if let Err(e) = MailBox::batch_upsert(&[mailbox]) {
-            tracing::error!("SMTP: Failed to upsert mailbox for {}: {:?}", rcpt.email, e);
-            return Err(e.into());
+        if bytes_read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed during BDAT",
+            ));
         }
+        remaining -= bytes_read;
     }
 
-    extract_envelope_from_smtp(data, rcpt.id, mailbox_id)
-        .await
-        .map_err(|e| {
-            tracing::error!(
-                "SMTP: Envelope extraction failed for {}: {:?}",
-                rcpt.email,
-                e
+    Ok(())
+}
+
+/// Hand the completed message to the backend and end the transaction. The
+/// production backend persists it to the on-disk spool before returning, so
+/// nothing acknowledged with `250` can be lost.
+async fn deliver(data: Vec<u8>, session: &mut Session, config: &SmtpConfig) -> String {
+    let result = match session.rcpt_to.first() {
+        Some(rcpt) => {
+            config
+                .backend
+                .deliver(rcpt.clone(), session.mail_from.clone(), data)
+                .await
+        }
+        None => Err(raise_error!(
+            "Message received with an empty recipient list".into(),
+            ErrorCode::InternalError
+        )),
+    };
+
+    let reply = match result {
+        Ok(id) => {
+            tracing::debug!(
+                "SMTP: Message spooled as {} for {} recipients (body {:?})",
+                id,
+                session.rcpt_to.len(),
+                session.params.body
             );
-            e
-        })
+            format!("250 2.0.0 OK: queued as {id}\r\n")
+        }
+        Err(e) => {
+            tracing::error!("SMTP: Failed to spool message: {:?}", e);
+            "451 4.3.0 Error: local error in processing, try again later\r\n".into()
+        }
+    };
+    session.reset();
+    reply
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct SmtpConfig {
     pub whitelist: Option<Vec<String>>,
     pub tls_acceptor: Option<TlsAcceptor>,
     pub auth_required: bool,
+    pub backend: Arc<dyn SmtpBackend>,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            whitelist: None,
+            tls_acceptor: None,
+            auth_required: false,
+            backend: Arc::new(ArchiveBackend),
+        }
+    }
 }
 
 pub struct SmtpServer {
@@ -725,6 +926,7 @@ pub async fn start_smtp_server() -> std::io::Result<SmtpServer> {
             EncryptionMode::Starttls => tls_acceptor.clone(),
         },
         auth_required: SETTINGS.bichon_smtp_auth_required,
+        backend: Arc::new(ArchiveBackend),
     };
 
     let smtp_shutdown = SIGNAL_MANAGER.subscribe();
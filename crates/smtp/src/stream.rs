@@ -17,25 +17,35 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{io, pin::Pin};
-use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, BufReader};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, BufReader, BufWriter};
 
+/// Buffered in both directions: replies to pipelined commands are collected
+/// in the write buffer and sent together once the client's input is drained.
 pub struct BufStream<S> {
-    pub inner: BufReader<S>,
+    pub inner: BufReader<BufWriter<S>>,
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin> BufStream<S> {
     pub fn new(stream: S) -> Self {
         Self {
-            inner: BufReader::new(stream),
+            inner: BufReader::new(BufWriter::new(stream)),
         }
     }
 
+    /// Whether the client has already sent more input than we processed.
+    pub fn has_pending_input(&self) -> bool {
+        !self.inner.buffer().is_empty()
+    }
+
+    /// Unwrap the raw stream. Anything still buffered is discarded, which is
+    /// what STARTTLS requires for input pipelined after the command (RFC 3207
+    /// §4.2); replies must be flushed before calling this.
     pub fn into_inner(self) -> S {
-        self.inner.into_inner()
+        self.inner.into_inner().into_inner()
     }
 }
 
-impl<S: AsyncRead + Unpin> AsyncBufRead for BufStream<S> {
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncBufRead for BufStream<S> {
     fn poll_fill_buf(
         self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -48,7 +58,7 @@ impl<S: AsyncRead + Unpin> AsyncBufRead for BufStream<S> {
     }
 }
 
-impl<S: AsyncRead + Unpin> AsyncRead for BufStream<S> {
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for BufStream<S> {
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
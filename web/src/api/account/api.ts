@@ -120,7 +120,7 @@ export interface DownloadState {
 type Encryption = 'Ssl' | 'StartTls' | 'None';
 type AuthType = 'Password' | 'OAuth2';
 type Unit = 'Days' | 'Months' | 'Years';
-type AccountType = 'IMAP' | 'NoSync';
+type AccountType = 'IMAP' | 'NoSync' | 'POP3' | 'Graph' | 'JMAP';
 
 // Interface definitions
 interface AuthConfig {
@@ -136,6 +136,29 @@ export interface ImapConfig {
     use_proxy?: number;
 }
 
+export interface Pop3Config {
+    host: string;
+    port: number;
+    encryption: Encryption;
+    auth_method: 'User' | 'Apop';
+    password?: string;
+    use_proxy?: number;
+    leave_on_server?: boolean;
+}
+
+export interface GraphConfig {
+    mailbox?: string;
+    endpoint?: string;
+    use_proxy?: number;
+}
+
+export interface JmapConfig {
+    url: string;
+    auth_method: 'Bearer' | 'Basic';
+    password?: string;
+    use_proxy?: number;
+}
+
 interface RelativeDate {
     unit: Unit;
     value: number; // integer, minimum 1
@@ -166,6 +189,9 @@ export interface AccountModel {
     id: number;
     account_type: AccountType;
     imap?: ImapConfig;
+    pop3?: Pop3Config;
+    graph?: GraphConfig;
+    jmap?: JmapConfig;
     enabled: boolean;
     login_name?: string,
     account_name?: string,
@@ -183,11 +209,14 @@ export interface AccountModel {
     created_at: number;
     updated_at: number;
     use_dangerous: boolean;
-    pgp_key?: string;
+    has_pgp_key: boolean;
+    has_smime_key: boolean;
     imap_quota_window?: QuotaWindow;
     imap_quota_bytes?: number;
     auto_download_new_mailboxes?: boolean;
     download_schedule?: string;
+    idle_folders?: string[];
+    gmail_sync?: boolean;
     archive_rules?: ArchiveRules;
     deleting?: boolean;
 }
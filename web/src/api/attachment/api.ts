@@ -50,6 +50,8 @@ export interface AttachmentModel {
     name?: string;
     tags?: string[];
     auto_tags?: string[];
+    /** For a file inside an archive attachment, the content hash of that archive. */
+    archive_content_hash?: string;
 }
 
 export const search_attachment = async (payload: Record<string, any>) => {
@@ -48,5 +48,40 @@ export interface EmailEnvelope {
   attachment_count: number;
   regular_attachment_count: number;
   tags: string[];
+  flags?: string[];
+  deleted_on_source?: boolean;
+  labels?: string[];
+  gmail_msg_id?: number;
+  authentication?: AuthenticationSummary;
   content_hash: string;
+}
+
+export type VerificationStatus = 'Pass' | 'Fail' | 'None' | 'Unknown';
+
+export interface AuthenticationSummary {
+  dkim: VerificationStatus;
+  dkim_domain?: string;
+  signature: VerificationStatus;
+  signature_kind?: 'Smime' | 'OpenPgp';
+  signer?: string;
+}
+
+export interface DkimResult {
+  domain: string;
+  selector: string;
+  algorithm: string;
+  status: VerificationStatus;
+  /** DNS TXT record the signature was checked against at ingest. */
+  key_record?: string;
+  error?: string;
+}
+
+export interface MessageAuthentication {
+  content_hash: string;
+  verified_at: number;
+  summary: AuthenticationSummary;
+  dkim_signatures: DkimResult[];
+  signer_certificate?: string;
+  signer_key_id?: string;
+  signature_error?: string;
 }
\ No newline at end of file
@@ -17,7 +17,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 
-import { EmailEnvelope, PaginatedResponse } from "@/api";
+import { EmailEnvelope, MessageAuthentication, PaginatedResponse } from "@/api";
 import axiosInstance from "@/api/axiosInstance";
 import { Group } from "@/api/system/api";
 import { saveAs } from 'file-saver';
@@ -58,6 +58,20 @@ export const download_nested_attachment = async (accountId: number, id: string,
     const blob = new Blob([response.data]);
     saveAs(blob, fileName);
 };
+export const download_archive_member = async (accountId: number, id: string, content_hash: string, member_content_hash: string, fileName: string) => {
+    const response = await axiosInstance.get(`api/v1/download-archive-member/${accountId}/${id}?content_hash=${content_hash}&member_content_hash=${member_content_hash}`, { responseType: 'blob' });
+    const blob = new Blob([response.data]);
+    saveAs(blob, fileName);
+};
+
+export interface ArchiveMember {
+    /** Path of the file inside the archive, e.g. `2026/q1.zip/invoice.pdf`. */
+    path: string;
+    file_type: string;
+    size: number;
+    content_hash: string;
+}
+
 export interface AttachmentInfo {
     /** MIME content type of the attachment (e.g., `image/png`, `application/pdf`). */
     file_type: string;
@@ -71,6 +85,8 @@ export interface AttachmentInfo {
     size: number;
     content_hash: string;
     is_message: boolean
+    /** Files inside the attachment when it is a zip, tar, gzip or 7z archive. */
+    archive_members?: ArchiveMember[];
 }
 
 export interface MessageContentResponse {
@@ -78,6 +94,8 @@ export interface MessageContentResponse {
     html?: string;
     attachments?: AttachmentInfo[];
     has_remote_content?: boolean;
+    decrypted?: boolean;
+    authentication?: MessageAuthentication;
 }
 
 export interface NestedMessageContentResponse {
@@ -29,6 +29,7 @@ export interface UserRole {
     role_type: RoleType;
     created_at: number;
     updated_at: number;
+    require_two_factor: boolean;
 }
 
 export function getPermissions(t: (key: string) => string) {
@@ -79,6 +80,7 @@ export interface AccessToken {
     user_name: string,
     user_email: string,
     token: string;
+    prefix: string;
     created_at: number;
     updated_at: number;
     name?: string;
@@ -116,6 +118,21 @@ export interface LoginResult {
     access_token?: string | null;
     theme?: Theme,
     language?: string,
+    two_factor_required?: boolean;
+    two_factor_enrollment?: TotpEnrollment | null;
+    recovery_codes?: string[] | null;
+}
+
+export interface TotpEnrollment {
+    secret: string;
+    otpauth_uri: string;
+    qr_svg: string;
+}
+
+export interface TwoFactorStatus {
+    enabled: boolean;
+    required: boolean;
+    recovery_codes_remaining: number;
 }
 
 
@@ -232,3 +249,28 @@ export const get_current_user = async () => {
     const response = await axiosInstance.get<User>("api/v1/current-user");
     return response.data;
 };
+
+export const get_two_factor_status = async () => {
+    const response = await axiosInstance.get<TwoFactorStatus>("api/v1/two-factor");
+    return response.data;
+};
+
+export const enroll_two_factor = async () => {
+    const response = await axiosInstance.post<TotpEnrollment>("api/v1/two-factor/enroll");
+    return response.data;
+};
+
+export const confirm_two_factor = async (code: string) => {
+    const response = await axiosInstance.post<string[]>("api/v1/two-factor/confirm", { code });
+    return response.data;
+};
+
+export const regenerate_recovery_codes = async (code: string) => {
+    const response = await axiosInstance.post<string[]>("api/v1/two-factor/recovery-codes", { code });
+    return response.data;
+};
+
+export const disable_two_factor = async (code: string) => {
+    const response = await axiosInstance.post("api/v1/two-factor/disable", { code });
+    return response.data;
+};
@@ -0,0 +1,45 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+import { useTranslation } from 'react-i18next'
+import { type TotpEnrollment } from '@/api/users/api'
+import { cn } from '@/lib/utils'
+
+interface Props {
+  enrollment: TotpEnrollment
+  className?: string
+}
+
+export function TotpEnrollmentDetails({ enrollment, className }: Props) {
+  const { t } = useTranslation()
+
+  return (
+    <div className={cn('flex flex-col items-center gap-3 text-sm', className)}>
+      <img
+        src={`data:image/svg+xml;charset=utf-8,${encodeURIComponent(enrollment.qr_svg)}`}
+        alt={t('auth.twoFactorQrAlt')}
+        className='h-48 w-48 rounded-md bg-white p-2'
+      />
+      <p className='text-center text-muted-foreground'>{t('auth.twoFactorManualEntry')}</p>
+      <code className='block break-all text-center font-mono'>{enrollment.secret}</code>
+      <a className='block break-all text-center text-xs underline' href={enrollment.otpauth_uri}>
+        {t('auth.twoFactorOpenApp')}
+      </a>
+    </div>
+  )
+}
@@ -35,7 +35,7 @@ import { TabServer } from "./components/tab-server";
 import { TabDownload } from "./components/tab-download";
 import { TabFilters } from "./components/tab-filters";
 import { create_account, autoconfig } from "@/api/account/api";
-import { getAccountSchema, type AccountFormValues } from "./components/schema";
+import { getAccountSchema, parseFolderList, type AccountFormValues } from "./components/schema";
 import type { AxiosError } from "axios";
 
 const defaultValues: AccountFormValues = {
@@ -58,6 +58,8 @@ const defaultValues: AccountFormValues = {
   max_email_size_bytes: 100 * 1024 * 1024,
   auto_download_new_mailboxes: true,
   download_schedule: undefined,
+  idle_folders: undefined,
+  gmail_sync: false,
   archive_rules: undefined,
 };
 
@@ -133,6 +135,8 @@ export function AccountNewPage() {
         max_email_size_bytes: data.max_email_size_bytes,
         auto_download_new_mailboxes: data.auto_download_new_mailboxes,
         download_schedule: data.download_schedule || null,
+        idle_folders: parseFolderList(data.idle_folders),
+        gmail_sync: data.gmail_sync,
         account_type: "IMAP",
         archive_rules: data.archive_rules || null,
       });
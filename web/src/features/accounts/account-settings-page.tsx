@@ -35,7 +35,7 @@ import { TabServer } from "./components/tab-server";
 import { TabDownload } from "./components/tab-download";
 import { TabFilters } from "./components/tab-filters";
 import { update_account, list_accounts, type AccountModel } from "@/api/account/api";
-import { getAccountSchema, type AccountFormValues } from "./components/schema";
+import { getAccountSchema, parseFolderList, type AccountFormValues } from "./components/schema";
 import type { AxiosError } from "axios";
 import { useQuery } from "@tanstack/react-query";
 
@@ -64,6 +64,8 @@ function mapAccountToFormValues(account: AccountModel): AccountFormValues {
     max_email_size_bytes: account.max_email_size_bytes ?? 100 * 1024 * 1024,
     auto_download_new_mailboxes: account.auto_download_new_mailboxes ?? true,
     download_schedule: account.download_schedule ?? undefined,
+    idle_folders: account.idle_folders?.join(', ') ?? undefined,
+    gmail_sync: account.gmail_sync ?? false,
     archive_rules: account.archive_rules ?? undefined,
   };
 }
@@ -157,6 +159,8 @@ export function AccountSettingsPage({ accountId }: AccountSettingsPageProps) {
         max_email_size_bytes: data.max_email_size_bytes,
         auto_download_new_mailboxes: data.auto_download_new_mailboxes,
         download_schedule: data.download_schedule || null,
+        idle_folders: parseFolderList(data.idle_folders),
+        gmail_sync: data.gmail_sync,
         archive_rules: data.archive_rules || null,
       };
 
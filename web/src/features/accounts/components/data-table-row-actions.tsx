@@ -64,7 +64,7 @@ export function DataTableRowActions({ row }: DataTableRowActionsProps) {
     (account_type === 'IMAP' && hasReadPermission)
   );
 
-  const showDownload = !isDeleting && account_type === 'IMAP' && hasPermission;
+  const showDownload = !isDeleting && (account_type === 'IMAP' || account_type === 'POP3' || account_type === 'Graph' || account_type === 'JMAP') && hasPermission;
 
   const handleStartDownload = async () => {
     setStartDialogOpen(true)
@@ -103,7 +103,7 @@ export function DataTableRowActions({ row }: DataTableRowActionsProps) {
                 navigate({ to: '/accounts/$id/settings', params: { id: String(row.original.id) } });
               } else {
                 setCurrentRow(row.original)
-                setOpen("edit-nosync");
+                setOpen(account_type === "POP3" ? "edit-pop3" : account_type === "Graph" ? "edit-graph" : account_type === "JMAP" ? "edit-jmap" : "edit-nosync");
               }
             }}
           >
@@ -37,11 +37,11 @@ export function OAuth2Action({ row }: DataTableRowActionsProps) {
   const account_type = mailer.account_type;
   const hasPermission = require_any_permission(['system:root', 'account:manage'], row.original.id)
 
-  if (account_type === "NoSync") {
+  if (account_type !== "IMAP" && account_type !== "Graph") {
     return <Button variant={"ghost"} className="text-xs text-muted-foreground">n/a</Button>
   }
 
-  const isOAuth2 = mailer.imap?.auth.auth_type === "OAuth2"
+  const isOAuth2 = account_type === "Graph" || mailer.imap?.auth.auth_type === "OAuth2"
 
   if (isOAuth2) {
     return (
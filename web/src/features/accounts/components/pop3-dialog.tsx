@@ -0,0 +1,475 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+import { z } from 'zod';
+import { Button } from '@/components/ui/button';
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from '@/components/ui/dialog';
+import { ScrollArea } from '@/components/ui/scroll-area';
+import { useToast } from '@/hooks/use-toast';
+import { useMutation, useQueryClient } from '@tanstack/react-query';
+import { ToastAction } from '@/components/ui/toast';
+import { AxiosError } from 'axios';
+import React from 'react';
+import { useForm } from 'react-hook-form';
+import { zodResolver } from '@hookform/resolvers/zod';
+import { AccountModel, create_account, update_account } from '@/api/account/api';
+import { Form, FormControl, FormDescription, FormField, FormItem, FormLabel, FormMessage } from '@/components/ui/form';
+import { Input } from '@/components/ui/input';
+import { Checkbox } from '@/components/ui/checkbox';
+import { PasswordInput } from '@/components/password-input';
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from '@/components/ui/select';
+import { Loader2 } from 'lucide-react';
+import { useTranslation } from 'react-i18next';
+import useProxyList from '@/hooks/use-proxy';
+
+
+const accountSchema = (t: (key: string) => string, isEdit: boolean) =>
+  z.object({
+    account_name: z.string().optional(),
+    email: z.string({ required_error: t('validation.emailRequired') }).email({ message: t('validation.invalidEmail') }),
+    login_name: z.string().optional(),
+    host: z.string().min(1, { message: t('validation.pop3HostRequired') }),
+    port: z.number({ invalid_type_error: t('validation.pop3PortMustBePositive') })
+      .int()
+      .min(1, { message: t('validation.pop3PortMustBePositive') })
+      .max(65535, { message: t('validation.pop3PortMustBeLessThan65536') }),
+    encryption: z.enum(['Ssl', 'StartTls', 'None']),
+    auth_method: z.enum(['User', 'Apop']),
+    password: isEdit
+      ? z.string().max(256).optional()
+      : z.string().min(1, { message: t('validation.pop3PasswordRequired') }).max(256),
+    use_proxy: z.number().optional(),
+    download_interval_min: z.number().int().min(1),
+    leave_on_server: z.boolean(),
+    enabled: z.boolean()
+  });
+
+
+export type Pop3Account = {
+  account_name?: string;
+  email: string;
+  login_name?: string;
+  host: string;
+  port: number;
+  encryption: 'Ssl' | 'StartTls' | 'None';
+  auth_method: 'User' | 'Apop';
+  password?: string;
+  use_proxy?: number;
+  download_interval_min: number;
+  leave_on_server: boolean;
+  enabled: boolean;
+};
+
+
+
+interface Props {
+  currentRow?: AccountModel;
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+}
+
+
+const defaultValues: Pop3Account = {
+  account_name: '',
+  email: '',
+  login_name: '',
+  host: '',
+  port: 995,
+  encryption: 'Ssl',
+  auth_method: 'User',
+  password: '',
+  use_proxy: undefined,
+  download_interval_min: 60,
+  leave_on_server: true,
+  enabled: true
+};
+
+
+const mapCurrentRowToFormValues = (currentRow: AccountModel): Pop3Account => {
+  let account = {
+    account_name: currentRow.account_name ?? '',
+    email: currentRow.email,
+    login_name: currentRow.login_name ?? '',
+    host: currentRow.pop3?.host ?? '',
+    port: currentRow.pop3?.port ?? 995,
+    encryption: currentRow.pop3?.encryption ?? 'Ssl',
+    auth_method: currentRow.pop3?.auth_method ?? 'User',
+    password: '',
+    use_proxy: currentRow.pop3?.use_proxy,
+    download_interval_min: currentRow.download_interval_min ?? 60,
+    leave_on_server: currentRow.pop3?.leave_on_server ?? true,
+    enabled: currentRow.enabled
+  };
+  return account;
+};
+
+
+export function Pop3AccountDialog({ currentRow, open, onOpenChange }: Props) {
+  const { t } = useTranslation()
+  const isEdit = !!currentRow;
+  const { toast } = useToast();
+  const { proxyOptions } = useProxyList();
+
+  const form = useForm<Pop3Account>({
+    mode: "onChange",
+    defaultValues: isEdit ? mapCurrentRowToFormValues(currentRow) : defaultValues,
+    resolver: zodResolver(accountSchema(t, isEdit)),
+  });
+
+  const queryClient = useQueryClient();
+
+  const createMutation = useMutation({
+    mutationFn: create_account,
+    onSuccess: handleSuccess,
+    onError: handleError,
+  });
+
+  const updateMutation = useMutation({
+    mutationFn: (data: Record<string, any>) => update_account(currentRow?.id!, data),
+    onSuccess: handleSuccess,
+    onError: handleError,
+  });
+
+  function handleSuccess() {
+    toast({
+      title: isEdit ? t('accounts.accountUpdated') : t('accounts.accountCreated'),
+      description: isEdit ? t('accounts.accountUpdatedDesc') : t('accounts.accountCreatedDesc'),
+      action: <ToastAction altText={t('common.close')}>{t('common.close')}</ToastAction>,
+    });
+
+    queryClient.invalidateQueries({ queryKey: ['account-list'] });
+    form.reset();
+    onOpenChange(false);
+  }
+
+  function handleError(error: AxiosError) {
+    const errorMessage =
+      (error.response?.data as { message?: string })?.message ||
+      error.message ||
+      (isEdit ? t('accounts.updateFailed') : t('accounts.creationFailed'));
+
+    toast({
+      variant: "destructive",
+      title: isEdit ? t('accounts.accountUpdateFailed') : t('accounts.accountCreationFailed'),
+      description: errorMessage as string,
+      action: <ToastAction altText={t('common.tryAgain')}>{t('common.tryAgain')}</ToastAction>,
+    });
+    console.error(error);
+  }
+
+  const onSubmit = React.useCallback(
+    (data: Pop3Account) => {
+      const commonData = {
+        email: data.email,
+        account_name: data.account_name,
+        enabled: data.enabled,
+        download_interval_min: data.download_interval_min,
+        pop3: {
+          host: data.host,
+          port: data.port,
+          encryption: data.encryption,
+          auth_method: data.auth_method,
+          password: data.password ? data.password : undefined,
+          use_proxy: data.use_proxy,
+          leave_on_server: data.leave_on_server
+        }
+      };
+      if (isEdit) {
+        updateMutation.mutate(commonData);
+      } else {
+        const payload = {
+          ...commonData,
+          account_type: "POP3",
+          login_name: data.login_name ? data.login_name : undefined,
+          use_dangerous: false
+        };
+        createMutation.mutate(payload);
+      }
+    },
+    [isEdit, updateMutation, createMutation]
+  );
+  return (
+    <Dialog
+      open={open}
+      onOpenChange={(state) => {
+        form.reset();
+        onOpenChange(state);
+      }}
+    >
+      <DialogContent className='max-w-2xl'>
+        <DialogHeader className='text-left mb-4'>
+          <DialogTitle>{isEdit ? t('accounts.updateAccount') : t('accounts.addAccount')}</DialogTitle>
+          <DialogDescription>
+            {isEdit ? t('accounts.updateTheEmailAccountHere') : t('accounts.addNewEmailAccountHere')}
+            {t('accounts.clickSaveWhenDone')}
+          </DialogDescription>
+        </DialogHeader>
+        <ScrollArea className='h-[32rem] w-full pr-4 -mr-4 py-1'>
+          <Form {...form}>
+            <form
+              id='pop3-account-form'
+              onSubmit={form.handleSubmit(onSubmit)}
+              className='space-y-4 p-0.5'
+            >
+              <FormField
+                control={form.control}
+                name="email"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel className="flex items-center justify-between">
+                      {t('accounts.emailAddress')}:
+                    </FormLabel>
+                    <FormControl>
+                      <Input placeholder={t('accounts.emailPlaceholder')} {...field} disabled={isEdit} />
+                    </FormControl>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name="account_name"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel className="flex items-center justify-between">
+                      {t('accounts.name')}:
+                    </FormLabel>
+                    <FormControl>
+                      <Input placeholder={t('accounts.namePlaceholder')} {...field} />
+                    </FormControl>
+                    <FormDescription>{t('accounts.optional')}</FormDescription>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name="host"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>{t('accounts.pop3Host')}:</FormLabel>
+                    <FormControl>
+                      <Input placeholder={t('accounts.pop3HostPlaceholder')} {...field} />
+                    </FormControl>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <div className="grid grid-cols-2 gap-4">
+                <FormField
+                  control={form.control}
+                  name="port"
+                  render={({ field }) => (
+                    <FormItem>
+                      <FormLabel>{t('accounts.pop3Port')}:</FormLabel>
+                      <FormControl>
+                        <Input
+                          type="number"
+                          placeholder={t('accounts.pop3PortPlaceholder')}
+                          {...field}
+                          onChange={(e) => field.onChange(parseInt(e.target.value, 10))}
+                        />
+                      </FormControl>
+                      <FormMessage />
+                    </FormItem>
+                  )}
+                />
+                <FormField
+                  control={form.control}
+                  name="encryption"
+                  render={({ field }) => (
+                    <FormItem>
+                      <FormLabel>{t('accounts.pop3Encryption')}:</FormLabel>
+                      <Select onValueChange={field.onChange} value={field.value}>
+                        <FormControl>
+                          <SelectTrigger>
+                            <SelectValue />
+                          </SelectTrigger>
+                        </FormControl>
+                        <SelectContent>
+                          <SelectItem value="Ssl">SSL/TLS</SelectItem>
+                          <SelectItem value="StartTls">STLS</SelectItem>
+                          <SelectItem value="None">{t('accounts.none')}</SelectItem>
+                        </SelectContent>
+                      </Select>
+                      <FormMessage />
+                    </FormItem>
+                  )}
+                />
+              </div>
+              <FormField
+                control={form.control}
+                name="login_name"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>{t('accounts.login_name')}:</FormLabel>
+                    <FormControl>
+                      <Input {...field} value={field.value ?? ''} placeholder={t('accounts.namePlaceholder')} disabled={isEdit} />
+                    </FormControl>
+                    <FormDescription>{t('accounts.pop3LoginNameDescription')}</FormDescription>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name="auth_method"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>{t('accounts.pop3AuthMethod')}:</FormLabel>
+                    <Select onValueChange={field.onChange} value={field.value}>
+                      <FormControl>
+                        <SelectTrigger>
+                          <SelectValue />
+                        </SelectTrigger>
+                      </FormControl>
+                      <SelectContent>
+                        <SelectItem value="User">{t('accounts.pop3AuthUser')}</SelectItem>
+                        <SelectItem value="Apop">APOP</SelectItem>
+                      </SelectContent>
+                    </Select>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name="password"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>{t('accounts.pop3Password')}:</FormLabel>
+                    <FormControl>
+                      <PasswordInput placeholder={isEdit ? t('accounts.leaveEmptyToKeepPassword') : t('accounts.enterPassword')} {...field} />
+                    </FormControl>
+                    {isEdit && <FormDescription>{t('accounts.leaveEmptyToKeepPassword')}</FormDescription>}
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name="use_proxy"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>{t('accounts.useProxyOptional')}</FormLabel>
+                    <Select
+                      onValueChange={(v) => field.onChange(v === 'none' ? undefined : Number(v))}
+                      defaultValue={field.value?.toString()}
+                    >
+                      <FormControl>
+                        <SelectTrigger>
+                          <SelectValue placeholder={t('accounts.selectProxy')} />
+                        </SelectTrigger>
+                      </FormControl>
+                      <SelectContent>
+                        <SelectItem key="none" value="none">{t('accounts.useNoProxy')}</SelectItem>
+                        {proxyOptions.map((opt) => (
+                          <SelectItem key={opt.value} value={opt.value}>
+                            <span className="max-w-[280px] truncate block" title={opt.label}>
+                              {opt.label}
+                            </span>
+                          </SelectItem>
+                        ))}
+                      </SelectContent>
+                    </Select>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name="download_interval_min"
+                render={({ field }) => (
+                  <FormItem>
+                    <FormLabel>{t('accounts.downloadInterval')}:</FormLabel>
+                    <FormControl>
+                      <Input type="number" {...field} onChange={(e) => field.onChange(parseInt(e.target.value, 10))} />
+                    </FormControl>
+                    <FormDescription>{t('accounts.downloadIntervalPlaceholder')}</FormDescription>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name='leave_on_server'
+                render={({ field }) => (
+                  <FormItem className='flex flex-col items-start gap-y-1'>
+                    <FormLabel>{t('accounts.leaveOnServer')}:</FormLabel>
+                    <FormControl>
+                      <Checkbox
+                        checked={field.value}
+                        onCheckedChange={field.onChange}
+                      />
+                    </FormControl>
+                    <FormDescription>
+                      {t('accounts.leaveOnServerDesc')}
+                    </FormDescription>
+                  </FormItem>
+                )}
+              />
+              <FormField
+                control={form.control}
+                name='enabled'
+                render={({ field }) => (
+                  <FormItem className='flex flex-col items-start gap-y-1'>
+                    <FormLabel>{t('accounts.enabled')}:</FormLabel>
+                    <FormControl>
+                      <Checkbox
+                        checked={field.value}
+                        onCheckedChange={field.onChange}
+                      />
+                    </FormControl>
+                  </FormItem>
+                )}
+              />
+            </form>
+          </Form>
+        </ScrollArea>
+        <DialogFooter>
+          <Button
+            type='submit'
+            form='pop3-account-form'
+            disabled={isEdit ? updateMutation.isPending : createMutation.isPending}
+          >
+            {isEdit ? (
+              updateMutation.isPending ? (
+                <>
+                  <Loader2 className="mr-2 h-4 w-4 animate-spin" />
+                  {t('oauth2.saving')}
+                </>
+              ) : (
+                t('accounts.saveChanges')
+              )
+            ) : (
+              createMutation.isPending ? (
+                <>
+                  <Loader2 className="mr-2 h-4 w-4 animate-spin" />
+                  {t('oauth2.creating')}
+                </>
+              ) : (
+                t('common.create')
+              )
+            )}
+          </Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
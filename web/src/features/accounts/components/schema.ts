@@ -133,9 +133,24 @@ export const getAccountSchema = (isEdit: boolean, t: (key: string) => string) =>
         },
         { message: t('validation.invalidCronExpression') }
       ),
+    idle_folders: z
+      .string()
+      .optional()
+      .refine((val) => parseFolderList(val).length <= 5, {
+        message: t('validation.tooManyIdleFolders'),
+      }),
+    gmail_sync: z.boolean().optional(),
     archive_rules: archiveRulesSchema.optional(),
   })
 
 export type AccountFormValues = z.infer<
   ReturnType<typeof getAccountSchema>
 >
+
+/** Turn the comma-separated IDLE folder input into mailbox names. */
+export function parseFolderList(value?: string): string[] {
+  return (value ?? '')
+    .split(',')
+    .map((name) => name.trim())
+    .filter((name) => name.length > 0)
+}
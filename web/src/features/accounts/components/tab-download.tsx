@@ -559,6 +559,37 @@ export function TabDownload() {
           </FormItem>
         )}
       />
+
+      <FormField
+        control={control}
+        name="gmail_sync"
+        render={({ field }) => (
+          <FormItem className="flex flex-row items-start space-x-3 space-y-0 rounded-md border p-4">
+            <FormControl>
+              <Checkbox checked={field.value ?? false} onCheckedChange={field.onChange} />
+            </FormControl>
+            <div className="space-y-1 leading-none">
+              <FormLabel>{t('accounts.gmailSync')}</FormLabel>
+              <FormDescription>{t('accounts.gmailSyncDescription')}</FormDescription>
+            </div>
+          </FormItem>
+        )}
+      />
+
+      <FormField
+        control={control}
+        name="idle_folders"
+        render={({ field }) => (
+          <FormItem>
+            <FormLabel>{t('accounts.idleFolders')}</FormLabel>
+            <FormControl>
+              <Input placeholder="INBOX, Sent" {...field} value={field.value ?? ''} />
+            </FormControl>
+            <FormDescription>{t('accounts.idleFoldersDescription')}</FormDescription>
+            <FormMessage />
+          </FormItem>
+        )}
+      />
     </div>
   );
 }
@@ -23,6 +23,12 @@ import React from 'react'
 export type AccountDialogType =
   | 'add-nosync'
   | 'edit-nosync'
+  | 'add-pop3'
+  | 'edit-pop3'
+  | 'add-graph'
+  | 'edit-graph'
+  | 'add-jmap'
+  | 'edit-jmap'
   | 'delete'
   | 'detail'
   | 'oauth2'
@@ -28,7 +28,7 @@ import { AccountTable } from './components/table'
 import AccountProvider, {
   type AccountDialogType,
 } from './context'
-import { Mail, Database } from 'lucide-react'
+import { Mail, Database, Inbox, Cloud, Zap } from 'lucide-react'
 import Logo from '@/assets/logo.svg'
 import { AccountDetailDrawer } from './components/account-detail'
 import { AccountModel, list_accounts } from '@/api/account/api'
@@ -39,6 +39,9 @@ import { RunningStateDialog } from './components/running-state-dialog'
 import { FixedHeader } from '@/components/layout/fixed-header'
 import { DownloadFoldersDialog } from './components/download-folders'
 import { NoSyncAccountDialog } from './components/nosync-dialog'
+import { Pop3AccountDialog } from './components/pop3-dialog'
+import { GraphAccountDialog } from './components/graph-dialog'
+import { JmapAccountDialog } from './components/jmap-dialog'
 import { useTranslation } from 'react-i18next'
 import { AccountAccessAssignmentDialog } from './components/access-assignment-dialog'
 import { useCurrentUser } from '@/hooks/use-current-user'
@@ -81,6 +84,18 @@ export default function Accounts() {
                 <Mail className="mr-1.5 h-4 w-4" />
                 {t('accounts.imapAccount')}
               </Button>
+              <Button variant="outline" onClick={() => setOpen("add-pop3")}>
+                <Inbox className="mr-1.5 h-4 w-4" />
+                {t('accounts.pop3Account')}
+              </Button>
+              <Button variant="outline" onClick={() => setOpen("add-graph")}>
+                <Cloud className="mr-1.5 h-4 w-4" />
+                {t('accounts.graphAccount')}
+              </Button>
+              <Button variant="outline" onClick={() => setOpen("add-jmap")}>
+                <Zap className="mr-1.5 h-4 w-4" />
+                {t('accounts.jmapAccount')}
+              </Button>
               <Button variant="outline" onClick={() => setOpen("add-nosync")}>
                 <Database className="mr-1.5 h-4 w-4" />
                 {t('accounts.noSyncAccount')}
@@ -110,6 +125,18 @@ export default function Accounts() {
                       <Mail className="mr-1.5 h-4 w-4" />
                       {t('accounts.imapAccount')}
                     </Button>
+                    <Button variant="outline" className="w-64" onClick={() => setOpen('add-pop3')}>
+                      <Inbox className="mr-1.5 h-4 w-4" />
+                      {t('accounts.pop3Account')}
+                    </Button>
+                    <Button variant="outline" className="w-64" onClick={() => setOpen('add-graph')}>
+                      <Cloud className="mr-1.5 h-4 w-4" />
+                      {t('accounts.graphAccount')}
+                    </Button>
+                    <Button variant="outline" className="w-64" onClick={() => setOpen('add-jmap')}>
+                      <Zap className="mr-1.5 h-4 w-4" />
+                      {t('accounts.jmapAccount')}
+                    </Button>
                     <Button variant="outline" className="w-64" onClick={() => setOpen('add-nosync')}>
                       <Database className="mr-1.5 h-4 w-4" />
                       {t('accounts.noSyncAccount')}
@@ -128,8 +155,62 @@ export default function Accounts() {
         onOpenChange={() => setOpen('add-nosync')}
       />
 
+      <Pop3AccountDialog
+        key='pop3-account-add'
+        open={open === 'add-pop3'}
+        onOpenChange={() => setOpen('add-pop3')}
+      />
+
+      <GraphAccountDialog
+        key='graph-account-add'
+        open={open === 'add-graph'}
+        onOpenChange={() => setOpen('add-graph')}
+      />
+
+      <JmapAccountDialog
+        key='jmap-account-add'
+        open={open === 'add-jmap'}
+        onOpenChange={() => setOpen('add-jmap')}
+      />
+
       {currentRow && (
         <>
+          <JmapAccountDialog
+            key={`jmap-account-edit-${currentRow.id}`}
+            open={open === 'edit-jmap'}
+            onOpenChange={() => {
+              setOpen('edit-jmap')
+              setTimeout(() => {
+                setCurrentRow(null)
+              }, 500)
+            }}
+            currentRow={currentRow}
+          />
+
+          <GraphAccountDialog
+            key={`graph-account-edit-${currentRow.id}`}
+            open={open === 'edit-graph'}
+            onOpenChange={() => {
+              setOpen('edit-graph')
+              setTimeout(() => {
+                setCurrentRow(null)
+              }, 500)
+            }}
+            currentRow={currentRow}
+          />
+
+          <Pop3AccountDialog
+            key={`pop3-account-edit-${currentRow.id}`}
+            open={open === 'edit-pop3'}
+            onOpenChange={() => {
+              setOpen('edit-pop3')
+              setTimeout(() => {
+                setCurrentRow(null)
+              }, 500)
+            }}
+            currentRow={currentRow}
+          />
+
           <NoSyncAccountDialog
             key={`nosync-account-edit-${currentRow.id}`}
             open={open === 'edit-nosync'}
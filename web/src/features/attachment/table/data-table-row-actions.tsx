@@ -34,7 +34,7 @@ import { AttachmentModel } from '@/api/attachment/api'
 import { useSearchAttachments } from '@/hooks/use-search-attachments'
 import { useToast } from '@/hooks/use-toast'
 import { useMutation } from '@tanstack/react-query'
-import { download_attachment } from '@/api/mailbox/envelope/api'
+import { download_archive_member, download_attachment } from '@/api/mailbox/envelope/api'
 import AttachmentPreview from '@/features/attachment/attachment-preview'
 
 interface DataTableRowActionsProps {
@@ -47,14 +47,26 @@ export function DataTableRowActions({ row }: DataTableRowActionsProps) {
   const { toast } = useToast();
   const [previewOpen, setPreviewOpen] = useState(false);
 
+  const archiveContentHash = row.original.archive_content_hash;
+  // Files inside an archive are named by their path in it.
+  const fileName = row.original.name?.split('/').pop() || row.original.id;
+
   const downloadMutation = useMutation({
     mutationFn: (content_hash: string) =>
-      download_attachment(
-        row.original.account_id,
-        row.original.envelope_id,
-        content_hash,
-        row.original.name ?? row.original.id
-      ),
+      archiveContentHash
+        ? download_archive_member(
+          row.original.account_id,
+          row.original.envelope_id,
+          archiveContentHash,
+          content_hash,
+          fileName
+        )
+        : download_attachment(
+          row.original.account_id,
+          row.original.envelope_id,
+          content_hash,
+          fileName
+        ),
     onError: (error: any) => {
       toast({
         title: t('mail.failedToDownloadFile'),
@@ -92,6 +104,7 @@ export function DataTableRowActions({ row }: DataTableRowActionsProps) {
           <DropdownMenuSeparator />
           <DropdownMenuItem
             className='text-xs'
+            disabled={!!archiveContentHash}
             onClick={(e) => {
               e.stopPropagation();
               setPreviewOpen(true);
@@ -127,7 +140,7 @@ export function DataTableRowActions({ row }: DataTableRowActionsProps) {
         envelopeId={row.original.envelope_id}
         contentHash={row.original.content_hash}
         contentType={row.original.content_type}
-        fileName={row.original.name ?? row.original.id}
+        fileName={fileName}
       />
     </>
   )
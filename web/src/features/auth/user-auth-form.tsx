@@ -42,14 +42,18 @@ import { Button } from '@/components/button'
 import { useTranslation } from 'react-i18next'
 import i18n from '@/i18n'
 import { Loader2, LogIn, Shield } from 'lucide-react'
-import { login } from '@/api/users/api'
+import { login, type LoginResult, type TotpEnrollment } from '@/api/users/api'
 import { useTheme } from '@/context/theme-context'
 import { useEdition } from '@/hooks/use-edition'
+import { TotpEnrollmentDetails } from '@/components/totp-enrollment'
 
 type UserAuthFormProps = HTMLAttributes<HTMLDivElement>
 
 export function UserAuthForm({ className, ...props }: UserAuthFormProps) {
   const [isLoading, setIsLoading] = useState(false)
+  const [twoFactorRequired, setTwoFactorRequired] = useState(false)
+  const [enrollment, setEnrollment] = useState<TotpEnrollment | null>(null)
+  const [recoveryLogin, setRecoveryLogin] = useState<LoginResult | null>(null)
   const { setTheme } = useTheme();
   const navigate = useNavigate()
   const { t } = useTranslation()
@@ -65,6 +69,7 @@ export function UserAuthForm({ className, ...props }: UserAuthFormProps) {
     defaultValues: {
       username: '',
       password: '',
+      totp_code: '',
     },
   })
 
@@ -73,24 +78,40 @@ export function UserAuthForm({ className, ...props }: UserAuthFormProps) {
     retry: 0,
   });
 
+  function completeLogin(result: LoginResult) {
+    setToken(result);
+
+    if (result.theme) {
+      setTheme(result.theme);
+    }
+
+    if (result.language) {
+      i18n.changeLanguage(result.language);
+    }
+
+    navigate({ to: redirect });
+  }
+
   async function onSubmit(data: LoginFormValues) {
     setIsLoading(true)
 
     mutation.mutate(data, {
       onSuccess: (result) => {
         if (result.success) {
-          setToken(result);
-
-          if (result.theme) {
-            setTheme(result.theme);
-          }
-
-          if (result.language) {
-            i18n.changeLanguage(result.language);
+          if (result.recovery_codes?.length) {
+            // Enrollment finished during this login: show the codes once.
+            setRecoveryLogin(result);
+          } else {
+            completeLogin(result);
           }
-
-          navigate({ to: redirect });
         } else {
+          if (result.two_factor_required) {
+            setTwoFactorRequired(true);
+            if (result.two_factor_enrollment) {
+              setEnrollment(result.two_factor_enrollment);
+            }
+            form.setValue('totp_code', '');
+          }
           toast({
             variant: "destructive",
             title: t('auth.loginFailed'),
@@ -109,6 +130,12 @@ export function UserAuthForm({ className, ...props }: UserAuthFormProps) {
             description: t('auth.invalidPassword'),
             action: <ToastAction altText={t('common.tryAgain')}>{t('common.tryAgain')}</ToastAction>,
           })
+        } else if (error instanceof AxiosError && error.response && error.response.status === 429) {
+          toast({
+            variant: "destructive",
+            title: t('auth.loginFailed'),
+            description: error.response.data?.error_message ?? t('auth.tooManyAttempts'),
+          })
         } else {
           toast({
             variant: "destructive",
@@ -122,6 +149,18 @@ export function UserAuthForm({ className, ...props }: UserAuthFormProps) {
     });
   }
 
+  if (recoveryLogin?.recovery_codes) {
+    return (
+      <div className={cn('grid gap-4', className)} {...props}>
+        <p className='text-sm text-muted-foreground'>{t('auth.recoveryCodesDesc')}</p>
+        <pre className='rounded-md border bg-muted p-3 font-mono text-sm'>
+          {recoveryLogin.recovery_codes.join('\n')}
+        </pre>
+        <Button onClick={() => completeLogin(recoveryLogin)}>{t('auth.continue')}</Button>
+      </div>
+    )
+  }
+
   return (
     <div className={cn('grid gap-6', className)} {...props}>
       <Form {...form}>
@@ -155,6 +194,27 @@ export function UserAuthForm({ className, ...props }: UserAuthFormProps) {
                 </FormItem>
               )}
             />
+            {enrollment && (
+              <div className='space-y-3 rounded-md border p-3 text-sm'>
+                <p className='text-muted-foreground'>{t('auth.twoFactorEnrollDesc')}</p>
+                <TotpEnrollmentDetails enrollment={enrollment} />
+              </div>
+            )}
+            {twoFactorRequired && (
+              <FormField
+                control={form.control}
+                name='totp_code'
+                render={({ field }) => (
+                  <FormItem className='space-y-1'>
+                    <FormLabel>{t('auth.twoFactorCode')}</FormLabel>
+                    <FormControl>
+                      <Input autoComplete='one-time-code' autoFocus {...field} />
+                    </FormControl>
+                    <FormMessage />
+                  </FormItem>
+                )}
+              />
+            )}
             <Button className='mt-2' disabled={isLoading}>
               {isLoading ? <Loader2 className='animate-spin' /> : <LogIn size={16} className='mr-2' />}
               {t('auth.login')}
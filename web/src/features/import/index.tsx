@@ -60,15 +60,15 @@ const BLOCKED_MIME_PREFIXES = [
 ];
 
 function isValidFileType(file: File, ext: string): boolean {
-  // Check MIME type: reject known binary types
+  // Check MIME type: reject known binary types (Outlook items are binary but supported)
   const mime = file.type.toLowerCase();
-  if (mime) {
+  if (mime && mime !== 'application/vnd.ms-outlook') {
     for (const prefix of BLOCKED_MIME_PREFIXES) {
       if (mime.startsWith(prefix)) return false;
     }
   }
   // Check extension
-  return ext === 'eml' || ext === 'mbox' || ext === 'pst';
+  return ext === 'eml' || ext === 'mbox' || ext === 'pst' || ext === 'msg';
 }
 
 type FolderMode = '' | 'header' | 'existing' | 'custom';
@@ -593,7 +593,7 @@ export default function ImportPage() {
                 onClick={() => {
                   const input = document.createElement('input');
                   input.type = 'file';
-                  input.accept = '.eml,.mbox,.pst,message/rfc822,application/mbox,text/plain';
+                  input.accept = '.eml,.mbox,.pst,.msg,message/rfc822,application/mbox,application/vnd.ms-outlook,text/plain';
                   input.multiple = true;
                   input.onchange = () => input.files && handleFiles(input.files);
                   input.click();
@@ -601,7 +601,7 @@ export default function ImportPage() {
               >
                 <Upload className="mx-auto h-10 w-10 text-muted-foreground/60 mb-3" />
                 <p className="text-sm font-medium">
-                  {t('import.dropHere', 'Drop .eml / .mbox / .pst files here')}
+                  {t('import.dropHere', 'Drop .eml / .mbox / .pst / .msg files here')}
                 </p>
                 <p className="text-xs text-muted-foreground mt-1">
                   {t('import.orClick', 'or click to browse')}
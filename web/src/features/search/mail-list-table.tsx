@@ -206,13 +206,27 @@ export function MailListTable({
       header: t('search.subject'),
       cell: ({ row }) => {
         const tags = row.original.tags ?? [];
+        const labels = row.original.labels ?? [];
         return (
           <div className="flex flex-col gap-1 py-1.5 min-w-0">
             <LongText className='text-xs font-medium truncate'>
               {row.original.subject}
             </LongText>
-            {tags.length > 0 && (
+            {(tags.length > 0 || labels.length > 0 || row.original.deleted_on_source) && (
               <div className="flex items-center gap-1 flex-wrap">
+                {row.original.deleted_on_source && (
+                  <span className="inline-flex items-center rounded-md bg-destructive/10 px-2 py-0.5 text-[10px] font-semibold text-destructive ring-1 ring-inset ring-destructive/20">
+                    {t('search.deleted_on_source')}
+                  </span>
+                )}
+                {labels.map((label) => (
+                  <span
+                    key={`label-${label}`}
+                    className="inline-flex items-center rounded-md bg-muted px-2 py-0.5 text-[10px] font-medium text-muted-foreground ring-1 ring-inset ring-border"
+                  >
+                    {label}
+                  </span>
+                ))}
                 {tags.map((tag) => (
                   <span
                     key={tag}
@@ -49,6 +49,33 @@ const getPresetFromSize = (min?: number, max?: number) => {
     return 'any';
 };
 
+const FLAG_PRESETS: Record<string, { flags?: string[]; without_flags?: string[] }> = {
+    flagged: { flags: ['flagged'] },
+    unread: { without_flags: ['seen'] },
+    answered: { flags: ['answered'] },
+    forwarded: { flags: ['forwarded'] },
+    junk: { flags: ['junk'] },
+};
+
+const getFlagPreset = (flags?: string[], withoutFlags?: string[]) => {
+    const match = Object.entries(FLAG_PRESETS).find(([, preset]) =>
+        JSON.stringify(preset.flags ?? []) === JSON.stringify(flags ?? []) &&
+        JSON.stringify(preset.without_flags ?? []) === JSON.stringify(withoutFlags ?? [])
+    );
+    return match ? match[0] : 'any';
+};
+
+const SOURCE_STATES: Record<string, boolean> = {
+    present: false,
+    deleted: true,
+};
+
+const getSourceState = (deletedOnSource?: boolean) => {
+    if (deletedOnSource === true) return 'deleted';
+    if (deletedOnSource === false) return 'present';
+    return 'any';
+};
+
 export function MoreFiltersPopover() {
     const { t } = useTranslation();
     const { filter, setFilter } = useSearchContext();
@@ -62,7 +89,10 @@ export function MoreFiltersPopover() {
         attachment_category: filter?.attachment_category || '',
         attachment_content_type: filter?.attachment_content_type || '',
         message_id: filter?.message_id || '',
+        label: filter?.labels?.[0] || '',
         size_preset: getPresetFromSize(filter?.min_size, filter?.max_size),
+        flag_preset: getFlagPreset(filter?.flags, filter?.without_flags),
+        source_state: getSourceState(filter?.deleted_on_source),
         has_attachment: filter?.has_attachment || false
     });
 
@@ -74,7 +104,10 @@ export function MoreFiltersPopover() {
                 attachment_category: filter?.attachment_category || '',
                 attachment_content_type: filter?.attachment_content_type || '',
                 message_id: filter?.message_id || '',
+                label: filter?.labels?.[0] || '',
                 size_preset: getPresetFromSize(filter?.min_size, filter?.max_size),
+                flag_preset: getFlagPreset(filter?.flags, filter?.without_flags),
+                source_state: getSourceState(filter?.deleted_on_source),
                 has_attachment: filter?.has_attachment || false
             });
         }
@@ -99,6 +132,9 @@ export function MoreFiltersPopover() {
             if (localState.message_id) next.message_id = localState.message_id;
             else delete next.message_id;
 
+            if (localState.label.trim()) next.labels = [localState.label.trim()];
+            else delete next.labels;
+
             if (localState.has_attachment) next.has_attachment = true;
             else delete next.has_attachment;
 
@@ -106,6 +142,13 @@ export function MoreFiltersPopover() {
             if (range.min) next.min_size = range.min; else delete next.min_size;
             if (range.max) next.max_size = range.max; else delete next.max_size;
 
+            const flagPreset = FLAG_PRESETS[localState.flag_preset] || {};
+            if (flagPreset.flags) next.flags = flagPreset.flags; else delete next.flags;
+            if (flagPreset.without_flags) next.without_flags = flagPreset.without_flags; else delete next.without_flags;
+
+            const sourceState = SOURCE_STATES[localState.source_state];
+            if (sourceState !== undefined) next.deleted_on_source = sourceState; else delete next.deleted_on_source;
+
             return next;
         });
         setOpen(false);
@@ -116,6 +159,9 @@ export function MoreFiltersPopover() {
         filter?.min_size,
         filter?.max_size,
         filter?.message_id,
+        filter?.labels,
+        filter?.flags || filter?.without_flags,
+        filter?.deleted_on_source !== undefined,
         filter?.has_attachment,
         filter?.attachment_extension,
         filter?.attachment_category,
@@ -157,6 +203,10 @@ export function MoreFiltersPopover() {
                                     delete next.min_size;
                                     delete next.max_size;
                                     delete next.message_id;
+                                    delete next.labels;
+                                    delete next.flags;
+                                    delete next.without_flags;
+                                    delete next.deleted_on_source;
                                     delete next.has_attachment;
                                     delete next.attachment_extension;
                                     delete next.attachment_category;
@@ -257,6 +307,54 @@ export function MoreFiltersPopover() {
                     </Select>
                 </div>
 
+                <div className="space-y-2">
+                    <Label className="text-xs text-muted-foreground">{t('search_more.flag_label')}</Label>
+                    <Select
+                        value={localState.flag_preset}
+                        onValueChange={(v) => setLocalState(prev => ({ ...prev, flag_preset: v }))}
+                    >
+                        <SelectTrigger className="h-8 text-xs">
+                            <SelectValue />
+                        </SelectTrigger>
+                        <SelectContent>
+                            {Object.keys(FLAG_PRESETS).concat('any').map((key) => (
+                                <SelectItem key={key} className="text-xs" value={key}>
+                                    {t(`search_more.flag_presets.${key}`)}
+                                </SelectItem>
+                            ))}
+                        </SelectContent>
+                    </Select>
+                </div>
+
+                <div className="space-y-2">
+                    <Label className="text-xs text-muted-foreground">{t('search_more.source_state_label')}</Label>
+                    <Select
+                        value={localState.source_state}
+                        onValueChange={(v) => setLocalState(prev => ({ ...prev, source_state: v }))}
+                    >
+                        <SelectTrigger className="h-8 text-xs">
+                            <SelectValue />
+                        </SelectTrigger>
+                        <SelectContent>
+                            {Object.keys(SOURCE_STATES).concat('any').map((key) => (
+                                <SelectItem key={key} className="text-xs" value={key}>
+                                    {t(`search_more.source_states.${key}`)}
+                                </SelectItem>
+                            ))}
+                        </SelectContent>
+                    </Select>
+                </div>
+
+                <div className="space-y-2">
+                    <Label className="text-xs text-muted-foreground">{t('search_more.label_label')}</Label>
+                    <Input
+                        className="h-8 text-xs"
+                        placeholder={t('search_more.label_placeholder')}
+                        value={localState.label}
+                        onChange={(e) => setLocalState(prev => ({ ...prev, label: e.target.value }))}
+                    />
+                </div>
+
                 <div className="space-y-2">
                     <Label className="text-xs text-muted-foreground">{t('search_more.message_id_label')}</Label>
                     <Input
@@ -20,7 +20,7 @@
 import { Outlet } from '@tanstack/react-router'
 import { Main } from '@/components/layout/main'
 import SidebarNav from './components/sidebar-nav'
-import { KeyRound, Palette, SettingsIcon, ShieldCheck, UserCog, Waypoints } from 'lucide-react'
+import { KeyRound, LockKeyhole, Palette, SettingsIcon, ShieldCheck, UserCog, Waypoints } from 'lucide-react'
 import { FixedHeader } from '@/components/layout/fixed-header'
 import { useCurrentUser } from '@/hooks/use-current-user'
 import { useTranslation } from 'react-i18next'
@@ -42,6 +42,11 @@ export default function Settings() {
       href: '/settings/access',
       icon: <ShieldCheck size={18} />
     },
+    {
+      title: t('settings.sidebar.twoFactor'),
+      href: '/settings/two-factor',
+      icon: <LockKeyhole size={18} />,
+    },
     {
       title: t('settings.appearance.title'),
       href: '/settings/appearance',
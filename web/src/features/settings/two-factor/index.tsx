@@ -0,0 +1,220 @@
+//
+// Copyright (c) 2025-2026 rustmailer.com (https://rustmailer.com)
+//
+// This file is part of the Bichon Email Archiving Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+import { useState } from 'react'
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query'
+import { AxiosError } from 'axios'
+import { Loader2 } from 'lucide-react'
+import { useTranslation } from 'react-i18next'
+
+import {
+  confirm_two_factor,
+  disable_two_factor,
+  enroll_two_factor,
+  get_two_factor_status,
+  regenerate_recovery_codes,
+  type TotpEnrollment,
+} from '@/api/users/api'
+import { TotpEnrollmentDetails } from '@/components/totp-enrollment'
+import { Badge } from '@/components/ui/badge'
+import { Button } from '@/components/ui/button'
+import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
+import { Input } from '@/components/ui/input'
+import { toast } from '@/hooks/use-toast'
+
+export function SettingsTwoFactor() {
+  const { t } = useTranslation()
+  const queryClient = useQueryClient()
+  const [enrollment, setEnrollment] = useState<TotpEnrollment | null>(null)
+  const [recoveryCodes, setRecoveryCodes] = useState<string[] | null>(null)
+  const [code, setCode] = useState('')
+
+  const { data: status, isLoading, error } = useQuery({
+    queryKey: ['two-factor-status'],
+    queryFn: get_two_factor_status,
+  })
+
+  const onError = (err: AxiosError) => {
+    toast({
+      variant: 'destructive',
+      title: t('settings.twoFactor.toast.failed'),
+      description: (err.response?.data as any)?.message || err.message,
+    })
+  }
+
+  const refresh = () => {
+    setCode('')
+    queryClient.invalidateQueries({ queryKey: ['two-factor-status'] })
+  }
+
+  const enroll = useMutation({
+    mutationFn: enroll_two_factor,
+    onSuccess: (data) => {
+      setCode('')
+      setEnrollment(data)
+    },
+    onError,
+  })
+
+  const confirm = useMutation({
+    mutationFn: confirm_two_factor,
+    onSuccess: (codes) => {
+      setEnrollment(null)
+      setRecoveryCodes(codes)
+      refresh()
+      toast({ title: t('settings.twoFactor.toast.enabled') })
+    },
+    onError,
+  })
+
+  const regenerate = useMutation({
+    mutationFn: regenerate_recovery_codes,
+    onSuccess: (codes) => {
+      setRecoveryCodes(codes)
+      refresh()
+    },
+    onError,
+  })
+
+  const disable = useMutation({
+    mutationFn: disable_two_factor,
+    onSuccess: () => {
+      setRecoveryCodes(null)
+      refresh()
+      toast({ title: t('settings.twoFactor.toast.disabled') })
+    },
+    onError,
+  })
+
+  if (isLoading) {
+    return (
+      <div className="flex justify-center items-center h-64">
+        <Loader2 className="h-6 w-6 animate-spin" />
+      </div>
+    )
+  }
+
+  if (error || !status) {
+    return (
+      <div className="p-6 text-red-600">
+        {t('settings.twoFactor.loadError')}
+      </div>
+    )
+  }
+
+  const busy = confirm.isPending || regenerate.isPending || disable.isPending
+
+  const codeInput = (
+    <Input
+      value={code}
+      onChange={(e) => setCode(e.target.value)}
+      placeholder={t('settings.twoFactor.codePlaceholder')}
+      autoComplete="one-time-code"
+      className="max-w-xs"
+    />
+  )
+
+  return (
+    <div className="w-full max-w-3xl px-4 sm:px-6 lg:px-8">
+      <Card>
+        <CardHeader>
+          <div className="flex items-center justify-between gap-4">
+            <CardTitle>{t('settings.twoFactor.title')}</CardTitle>
+            <Badge variant={status.enabled ? 'default' : 'secondary'}>
+              {status.enabled
+                ? t('settings.twoFactor.status.enabled')
+                : t('settings.twoFactor.status.disabled')}
+            </Badge>
+          </div>
+          <CardDescription>{t('settings.twoFactor.description')}</CardDescription>
+        </CardHeader>
+        <CardContent className="space-y-6">
+          {status.required && (
+            <p className="text-sm text-muted-foreground">{t('settings.twoFactor.requiredByRole')}</p>
+          )}
+
+          {recoveryCodes && (
+            <div className="space-y-3">
+              <p className="text-sm text-muted-foreground">{t('auth.recoveryCodesDesc')}</p>
+              <pre className="rounded-md border bg-muted p-3 font-mono text-sm">
+                {recoveryCodes.join('\n')}
+              </pre>
+              <Button variant="outline" onClick={() => setRecoveryCodes(null)}>
+                {t('settings.twoFactor.button.savedCodes')}
+              </Button>
+            </div>
+          )}
+
+          {!status.enabled && !enrollment && (
+            <Button onClick={() => enroll.mutate()} disabled={enroll.isPending}>
+              {enroll.isPending && <Loader2 className="mr-2 h-4 w-4 animate-spin" />}
+              {t('settings.twoFactor.button.setUp')}
+            </Button>
+          )}
+
+          {!status.enabled && enrollment && (
+            <div className="space-y-4">
+              <p className="text-sm text-muted-foreground">{t('settings.twoFactor.enrollDesc')}</p>
+              <TotpEnrollmentDetails enrollment={enrollment} className="items-start" />
+              <div className="flex flex-wrap gap-2">
+                {codeInput}
+                <Button onClick={() => confirm.mutate(code)} disabled={busy || !code.trim()}>
+                  {confirm.isPending && <Loader2 className="mr-2 h-4 w-4 animate-spin" />}
+                  {t('settings.twoFactor.button.confirm')}
+                </Button>
+                <Button variant="ghost" onClick={() => setEnrollment(null)} disabled={busy}>
+                  {t('settings.twoFactor.button.cancel')}
+                </Button>
+              </div>
+            </div>
+          )}
+
+          {status.enabled && (
+            <div className="space-y-4">
+              <p className="text-sm text-muted-foreground">
+                {t('settings.twoFactor.recoveryRemaining', { count: status.recovery_codes_remaining })}
+              </p>
+              <p className="text-sm text-muted-foreground">{t('settings.twoFactor.manageDesc')}</p>
+              <div className="flex flex-wrap gap-2">
+                {codeInput}
+                <Button
+                  variant="outline"
+                  onClick={() => regenerate.mutate(code)}
+                  disabled={busy || !code.trim()}
+                >
+                  {regenerate.isPending && <Loader2 className="mr-2 h-4 w-4 animate-spin" />}
+                  {t('settings.twoFactor.button.regenerate')}
+                </Button>
+                {!status.required && (
+                  <Button
+                    variant="destructive"
+                    onClick={() => disable.mutate(code)}
+                    disabled={busy || !code.trim()}
+                  >
+                    {disable.isPending && <Loader2 className="mr-2 h-4 w-4 animate-spin" />}
+                    {t('settings.twoFactor.button.disable')}
+                  </Button>
+                )}
+              </div>
+            </div>
+          )}
+        </CardContent>
+      </Card>
+    </div>
+  )
+}
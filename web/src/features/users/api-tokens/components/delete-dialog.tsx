@@ -116,7 +116,7 @@ export function ApiTokenDeleteDialog({ open, onOpenChange, currentRow }: Props)
             <p className="mt-1 text-muted-foreground">
               {t('users.api_tokens.delete.prefix')}:{' '}
               <span className="font-mono">
-                {currentRow.token.substring(0, 8)}...
+                {currentRow.prefix}...
               </span>
             </p>
             <p className="mt-2 font-bold text-destructive">
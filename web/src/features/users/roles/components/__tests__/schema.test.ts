@@ -126,4 +126,26 @@ describe('Role Form Schema', () => {
       expect(result.success).toBe(true)
     })
   })
+
+  describe('require_two_factor field', () => {
+    it('accepts a boolean', () => {
+      const result = schema.safeParse({
+        name: 'Auditor',
+        role_type: 'Global',
+        permissions: ['system:access'],
+        require_two_factor: true,
+      })
+      expect(result.success).toBe(true)
+    })
+
+    it('rejects a non-boolean', () => {
+      const result = schema.safeParse({
+        name: 'Auditor',
+        role_type: 'Global',
+        permissions: ['system:access'],
+        require_two_factor: 'yes',
+      })
+      expect(result.success).toBe(false)
+    })
+  })
 })
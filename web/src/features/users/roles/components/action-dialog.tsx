@@ -42,6 +42,7 @@ import { Loader2, LockIcon, ShieldCheck, UserCog } from 'lucide-react'
 import { create_role, getPermissions, update_role, UserRole } from '@/api/users/api'
 import { Textarea } from '@/components/ui/textarea'
 import { Checkbox } from '@/components/ui/checkbox'
+import { Switch } from '@/components/ui/switch'
 import {
   RadioGroup,
   RadioGroupItem,
@@ -106,6 +107,7 @@ export function RoleActionDialog({ currentRow, open, onOpenChange }: Props) {
       role_type: isEdit ? currentRow.role_type : 'Account',
       permissions: isEdit ? Array.from(currentRow.permissions) : [],
       description: isEdit ? currentRow.description ?? undefined : '',
+      require_two_factor: isEdit ? currentRow.require_two_factor : false,
     },
   })
 
@@ -239,6 +241,26 @@ export function RoleActionDialog({ currentRow, open, onOpenChange }: Props) {
                     </FormItem>
                   )}
                 />
+
+                <FormField
+                  control={form.control}
+                  name="require_two_factor"
+                  render={({ field }) => (
+                    <FormItem className="flex items-start justify-between gap-3 rounded-md border bg-card p-3">
+                      <div className="space-y-1">
+                        <FormLabel className="text-sm font-bold">
+                          {t('roles.form.require_two_factor_label')}
+                        </FormLabel>
+                        <p className="text-xs text-muted-foreground">
+                          {t('roles.form.require_two_factor_desc')}
+                        </p>
+                      </div>
+                      <FormControl>
+                        <Switch checked={!!field.value} onCheckedChange={field.onChange} />
+                      </FormControl>
+                    </FormItem>
+                  )}
+                />
               </div>
 
               <div className="lg:col-span-3">
@@ -6,6 +6,7 @@ export const getRoleFormSchema = (t: (key: string) => string) =>
     role_type: z.enum(['Global', 'Account']),
     permissions: z.array(z.string()).min(1, t('roles.validation.perm_required')),
     description: z.string().optional(),
+    require_two_factor: z.boolean().optional(),
   })
 
 export type RoleFormValues = z.infer<ReturnType<typeof getRoleFormSchema>>
@@ -26,7 +26,7 @@ import {
 } from "@/components/ui/accordion";
 import { Badge } from "@/components/ui/badge";
 import { Button } from "@/components/ui/button";
-import { Copy, Trash2 } from "lucide-react";
+import { Trash2 } from "lucide-react";
 import { Separator } from "@/components/ui/separator";
 import {
     Dialog,
@@ -116,18 +116,8 @@ export const TokenCardList: React.FC<Props> = ({ tokens, userId }) => {
                                         </div>
                                         <div className="flex items-center gap-2 bg-background border rounded-md px-3 py-2">
                                             <code className="font-mono text-xs truncate flex-1">
-                                                {token.token}
+                                                {token.prefix}...
                                             </code>
-                                            <Button
-                                                size="icon"
-                                                variant="ghost"
-                                                className="h-7 w-7"
-                                                onClick={() =>
-                                                    navigator.clipboard.writeText(token.token)
-                                                }
-                                            >
-                                                <Copy className="h-4 w-4" />
-                                            </Button>
                                         </div>
                                     </div>
                                     <div className="grid grid-cols-1 sm:grid-cols-3 gap-3 text-xs text-muted-foreground">
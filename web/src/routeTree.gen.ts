@@ -61,6 +61,9 @@ const AuthenticatedUsersRolesLazyImport = createFileRoute(
 const AuthenticatedUsersApiTokensLazyImport = createFileRoute(
   '/_authenticated/users/api-tokens',
 )()
+const AuthenticatedSettingsTwoFactorLazyImport = createFileRoute(
+  '/_authenticated/settings/two-factor',
+)()
 const AuthenticatedSettingsProxyLazyImport = createFileRoute(
   '/_authenticated/settings/proxy',
 )()
@@ -272,6 +275,17 @@ const AuthenticatedUsersApiTokensLazyRoute =
     ),
   )
 
+const AuthenticatedSettingsTwoFactorLazyRoute =
+  AuthenticatedSettingsTwoFactorLazyImport.update({
+    id: '/two-factor',
+    path: '/two-factor',
+    getParentRoute: () => AuthenticatedSettingsRouteLazyRoute,
+  } as any).lazy(() =>
+    import('./routes/_authenticated/settings/two-factor.lazy').then(
+      (d) => d.Route,
+    ),
+  )
+
 const AuthenticatedSettingsProxyLazyRoute =
   AuthenticatedSettingsProxyLazyImport.update({
     id: '/proxy',
@@ -491,6 +505,13 @@ declare module '@tanstack/react-router' {
       preLoaderRoute: typeof AuthenticatedSettingsProxyLazyImport
       parentRoute: typeof AuthenticatedSettingsRouteLazyImport
     }
+    '/_authenticated/settings/two-factor': {
+      id: '/_authenticated/settings/two-factor'
+      path: '/two-factor'
+      fullPath: '/settings/two-factor'
+      preLoaderRoute: typeof AuthenticatedSettingsTwoFactorLazyImport
+      parentRoute: typeof AuthenticatedSettingsRouteLazyImport
+    }
     '/_authenticated/users/api-tokens': {
       id: '/_authenticated/users/api-tokens'
       path: '/api-tokens'
@@ -587,6 +608,7 @@ interface AuthenticatedSettingsRouteLazyRouteChildren {
   AuthenticatedSettingsConfigurationsLazyRoute: typeof AuthenticatedSettingsConfigurationsLazyRoute
   AuthenticatedSettingsProfileLazyRoute: typeof AuthenticatedSettingsProfileLazyRoute
   AuthenticatedSettingsProxyLazyRoute: typeof AuthenticatedSettingsProxyLazyRoute
+  AuthenticatedSettingsTwoFactorLazyRoute: typeof AuthenticatedSettingsTwoFactorLazyRoute
   AuthenticatedSettingsIndexLazyRoute: typeof AuthenticatedSettingsIndexLazyRoute
 }
 
@@ -602,6 +624,8 @@ const AuthenticatedSettingsRouteLazyRouteChildren: AuthenticatedSettingsRouteLaz
     AuthenticatedSettingsProfileLazyRoute:
       AuthenticatedSettingsProfileLazyRoute,
     AuthenticatedSettingsProxyLazyRoute: AuthenticatedSettingsProxyLazyRoute,
+    AuthenticatedSettingsTwoFactorLazyRoute:
+      AuthenticatedSettingsTwoFactorLazyRoute,
     AuthenticatedSettingsIndexLazyRoute: AuthenticatedSettingsIndexLazyRoute,
   }
 
@@ -686,6 +710,7 @@ export interface FileRoutesByFullPath {
   '/settings/configurations': typeof AuthenticatedSettingsConfigurationsLazyRoute
   '/settings/profile': typeof AuthenticatedSettingsProfileLazyRoute
   '/settings/proxy': typeof AuthenticatedSettingsProxyLazyRoute
+  '/settings/two-factor': typeof AuthenticatedSettingsTwoFactorLazyRoute
   '/users/api-tokens': typeof AuthenticatedUsersApiTokensLazyRoute
   '/users/roles': typeof AuthenticatedUsersRolesLazyRoute
   '/attachment': typeof AuthenticatedAttachmentIndexRoute
@@ -716,6 +741,7 @@ export interface FileRoutesByTo {
   '/settings/configurations': typeof AuthenticatedSettingsConfigurationsLazyRoute
   '/settings/profile': typeof AuthenticatedSettingsProfileLazyRoute
   '/settings/proxy': typeof AuthenticatedSettingsProxyLazyRoute
+  '/settings/two-factor': typeof AuthenticatedSettingsTwoFactorLazyRoute
   '/users/api-tokens': typeof AuthenticatedUsersApiTokensLazyRoute
   '/users/roles': typeof AuthenticatedUsersRolesLazyRoute
   '/attachment': typeof AuthenticatedAttachmentIndexRoute
@@ -751,6 +777,7 @@ export interface FileRoutesById {
   '/_authenticated/settings/configurations': typeof AuthenticatedSettingsConfigurationsLazyRoute
   '/_authenticated/settings/profile': typeof AuthenticatedSettingsProfileLazyRoute
   '/_authenticated/settings/proxy': typeof AuthenticatedSettingsProxyLazyRoute
+  '/_authenticated/settings/two-factor': typeof AuthenticatedSettingsTwoFactorLazyRoute
   '/_authenticated/users/api-tokens': typeof AuthenticatedUsersApiTokensLazyRoute
   '/_authenticated/users/roles': typeof AuthenticatedUsersRolesLazyRoute
   '/_authenticated/attachment/': typeof AuthenticatedAttachmentIndexRoute
@@ -786,6 +813,7 @@ export interface FileRouteTypes {
     | '/settings/configurations'
     | '/settings/profile'
     | '/settings/proxy'
+    | '/settings/two-factor'
     | '/users/api-tokens'
     | '/users/roles'
     | '/attachment'
@@ -815,6 +843,7 @@ export interface FileRouteTypes {
     | '/settings/configurations'
     | '/settings/profile'
     | '/settings/proxy'
+    | '/settings/two-factor'
     | '/users/api-tokens'
     | '/users/roles'
     | '/attachment'
@@ -848,6 +877,7 @@ export interface FileRouteTypes {
     | '/_authenticated/settings/configurations'
     | '/_authenticated/settings/profile'
     | '/_authenticated/settings/proxy'
+    | '/_authenticated/settings/two-factor'
     | '/_authenticated/users/api-tokens'
     | '/_authenticated/users/roles'
     | '/_authenticated/attachment/'
@@ -939,6 +969,7 @@ export const routeTree = rootRoute
         "/_authenticated/settings/configurations",
         "/_authenticated/settings/profile",
         "/_authenticated/settings/proxy",
+        "/_authenticated/settings/two-factor",
         "/_authenticated/settings/"
       ]
     },
@@ -1002,6 +1033,10 @@ export const routeTree = rootRoute
       "filePath": "_authenticated/settings/proxy.lazy.tsx",
       "parent": "/_authenticated/settings"
     },
+    "/_authenticated/settings/two-factor": {
+      "filePath": "_authenticated/settings/two-factor.lazy.tsx",
+      "parent": "/_authenticated/settings"
+    },
     "/_authenticated/users/api-tokens": {
       "filePath": "_authenticated/users/api-tokens.lazy.tsx",
       "parent": "/_authenticated/users"